@@ -0,0 +1,106 @@
+// Criterion suite for the DSP kernels and the unified analyzer (native only):
+//
+//     cargo bench --features bench
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use loudness_wasm::kernels::{k_weighted_block_energy, phase_correlation, plan_fft, stereo_sums, true_peak};
+use loudness_wasm::{Analyzer, LiveMeter};
+
+const SAMPLE_RATE: f32 = 44100.0;
+
+// Interleaved stereo test signal: two detuned tones, a decaying kick every
+// 500 ms (120 BPM) and low-level noise from a fixed LCG so runs are repeatable
+fn synthetic_stereo(seconds: f32) -> Vec<f32> {
+    let frames = (seconds * SAMPLE_RATE) as usize;
+    let kick_period = (SAMPLE_RATE * 0.5) as usize;
+    let mut seed: u32 = 0x1234_5678;
+    let mut pcm = Vec::with_capacity(frames * 2);
+
+    for i in 0..frames {
+        let t = i as f32 / SAMPLE_RATE;
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let noise = (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5;
+        let kick = 0.6 * (-((i % kick_period) as f32) / 2000.0).exp() * (2.0 * std::f32::consts::PI * 60.0 * t).sin();
+
+        let left = 0.2 * (2.0 * std::f32::consts::PI * 440.0 * t).sin() + kick + 0.02 * noise;
+        let right = 0.2 * (2.0 * std::f32::consts::PI * 443.0 * t).sin() + kick - 0.02 * noise;
+        pcm.push(left);
+        pcm.push(right);
+    }
+    pcm
+}
+
+fn split_channels(pcm: &[f32]) -> (Vec<f32>, Vec<f32>) {
+    pcm.chunks_exact(2).map(|frame| (frame[0], frame[1])).unzip()
+}
+
+fn bench_fft(c: &mut Criterion) {
+    let pcm = synthetic_stereo(1.0);
+    let mut group = c.benchmark_group("fft");
+    for size in [1024, 2048, 4096] {
+        let fft = plan_fft(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter(|| fft.magnitudes(black_box(&pcm[..size])))
+        });
+    }
+    group.finish();
+}
+
+fn bench_k_weighting(c: &mut Criterion) {
+    let pcm = synthetic_stereo(3.0);
+    let mut group = c.benchmark_group("k_weighting");
+    for (name, block) in [("momentary", 17640), ("short_term", 132300)] {
+        group.throughput(Throughput::Elements(block as u64));
+        group.bench_function(name, |b| b.iter(|| k_weighted_block_energy(black_box(&pcm), 2, block)));
+    }
+    group.finish();
+}
+
+fn bench_true_peak(c: &mut Criterion) {
+    let pcm = synthetic_stereo(10.0);
+    let mut group = c.benchmark_group("true_peak");
+    group.throughput(Throughput::Elements(pcm.len() as u64));
+    group.bench_function("10s_stereo", |b| b.iter(|| true_peak(black_box(&pcm), SAMPLE_RATE)));
+    group.finish();
+}
+
+fn bench_correlation(c: &mut Criterion) {
+    let (left, right) = split_channels(&synthetic_stereo(10.0));
+    let mut group = c.benchmark_group("correlation");
+    group.throughput(Throughput::Elements(left.len() as u64));
+    group.bench_function("stereo_sums", |b| b.iter(|| stereo_sums(black_box(&left), black_box(&right))));
+    group.bench_function("phase_correlation", |b| {
+        b.iter(|| phase_correlation(black_box(&left), black_box(&right), SAMPLE_RATE))
+    });
+    group.finish();
+}
+
+fn bench_live_meter(c: &mut Criterion) {
+    let pcm = synthetic_stereo(1.0);
+    let mut meter = LiveMeter::new(SAMPLE_RATE, 2);
+    let mut group = c.benchmark_group("live_meter");
+    group.throughput(Throughput::Elements(128));
+    group.bench_function("128_frame_block", |b| {
+        let mut blocks = pcm.chunks_exact(256).cycle();
+        b.iter(|| meter.process(black_box(blocks.next().unwrap())))
+    });
+    group.finish();
+}
+
+fn bench_analyzer(c: &mut Criterion) {
+    let pcm = synthetic_stereo(180.0);
+    let mut group = c.benchmark_group("analyzer_3min");
+    group.sample_size(10);
+
+    let analyzer = Analyzer::new(SAMPLE_RATE, 2);
+    group.bench_function("loudness_technical_stereo", |b| b.iter(|| analyzer.analyze(black_box(&pcm), None).unwrap()));
+
+    let mut with_rhythm = Analyzer::new(SAMPLE_RATE, 2);
+    with_rhythm.set_include_rhythm(true);
+    group.bench_function("with_rhythm", |b| b.iter(|| with_rhythm.analyze(black_box(&pcm), None).unwrap()));
+    group.finish();
+}
+
+criterion_group!(kernels, bench_fft, bench_k_weighting, bench_true_peak, bench_correlation, bench_live_meter);
+criterion_group!(analyzer, bench_analyzer);
+criterion_main!(kernels, analyzer);
@@ -0,0 +1,245 @@
+// Loudness-matched A/B comparison: the louder of two versions nearly always
+// sounds "better", so comparison players should play both at the same
+// integrated loudness. The playback gains bring both to the quieter one (no
+// gain above unity, so nothing new can clip) or to a fixed target, and
+// optional previews cut the same length from around each buffer's loudest
+// short-term block with the gain already applied and short fades at the
+// edges. A silent buffer keeps unity gain and takes no part in the match.
+//
+//     const matcher = new AbMatcher(48000, 2);
+//     matcher.set_preview_seconds(10);
+//     const pair = matcher.compare(masterA, masterB);
+//     playerA.gain.value = pair.a.gain;
+//     playerB.gain.value = pair.b.gain;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::AnalyzerConfig;
+use crate::error::{validate_pcm, AnalysisError};
+use crate::constants::{BLOCK_SAMPLE_RATE, SHORT_TERM_HOP};
+use crate::loudness::{LoudnessAnalyzer, Pcm};
+use crate::progress::Progress;
+use crate::utils::{amplitude_to_db, db_to_amplitude};
+
+// Fade in and out (seconds) at the edges of a preview
+const PREVIEW_FADE_SECONDS: f32 = 0.01;
+// Middle of a short-term block (seconds from its start)
+const SHORT_TERM_CENTRE_SECONDS: f32 = 1.5;
+
+/// One side of the comparison
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct AbSide {
+    // Integrated loudness (LUFS) as supplied; -Infinity when silent
+    pub integrated: f32,
+    // Playback gain in dB and as a linear factor
+    pub gain_db: f32,
+    pub gain: f32,
+    // Sample peak (dBFS) after the gain, and whether it passes full scale
+    pub peak: f32,
+    pub clips: bool,
+    // Start (seconds) of the preview and its interleaved samples, gain
+    // applied; empty when previews are off
+    pub preview_start: f32,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub preview: Vec<f32>,
+}
+
+/// Gains (and previews) playing two buffers at the same loudness
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct AbMatch {
+    // Loudness (LUFS) both play at; -Infinity when both are silent
+    pub matched_loudness: f32,
+    // Whether that is the configured target rather than the quieter buffer
+    pub to_target: bool,
+    pub a: AbSide,
+    pub b: AbSide,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct AbMatcher {
+    loudness: LoudnessAnalyzer,
+    sample_rate: f32,
+    num_channels: usize,
+    target: Option<f32>,
+    preview_seconds: f32,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl AbMatcher {
+    // Matches to the quieter buffer, without previews
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        AbMatcher::from_config(&AnalyzerConfig::new(sample_rate, num_channels))
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        AbMatcher {
+            // Unfloored, so silence stays non-finite and is left out of matching
+            loudness: LoudnessAnalyzer::from_config(&config.with_db_floor(f32::NEG_INFINITY)),
+            sample_rate: config.sample_rate(),
+            num_channels: config.num_channels(),
+            target: None,
+            preview_seconds: 0.0,
+        }
+    }
+
+    // Match both buffers to `lufs` instead of the quieter one; gains may then
+    // be positive and push peaks past full scale (see `clips`)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_target(&mut self, lufs: f32) {
+        self.target = lufs.is_finite().then_some(lufs);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn clear_target(&mut self) {
+        self.target = None;
+    }
+
+    // Length of the matched previews; 0 (the default) turns them off
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_preview_seconds(&mut self, seconds: f32) {
+        self.preview_seconds = seconds.max(0.0);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = compare)]
+    pub fn compare_js(&self, a: &Float32Array, b: &Float32Array) -> Result<AbMatch, JsError> {
+        Ok(self.compare(&a.to_vec(), &b.to_vec())?)
+    }
+}
+
+impl AbMatcher {
+    /// Playback gains matching two interleaved buffers (of this matcher's rate
+    /// and channel count) in integrated loudness, with previews when enabled
+    pub fn compare(&self, a: &[f32], b: &[f32]) -> Result<AbMatch, AnalysisError> {
+        let (integrated_a, start_a) = self.measure(a)?;
+        let (integrated_b, start_b) = self.measure(b)?;
+        let matched_loudness = match self.target {
+            Some(target) => target,
+            None => [integrated_a, integrated_b].into_iter().filter(|lufs| lufs.is_finite()).reduce(f32::min).unwrap_or(f32::NEG_INFINITY),
+        };
+        Ok(AbMatch {
+            matched_loudness,
+            to_target: self.target.is_some(),
+            a: self.side(a, integrated_a, start_a, matched_loudness),
+            b: self.side(b, integrated_b, start_b, matched_loudness),
+        })
+    }
+
+    // Integrated loudness and the first frame of the preview
+    fn measure(&self, pcm: &[f32]) -> Result<(f32, usize), AnalysisError> {
+        validate_pcm(pcm, self.num_channels, self.loudness.min_frames())?;
+        // One pass gives both the reading and the short-term blocks
+        let (result, _, short_term) = self.loudness.measure_blocks(Pcm::Single(pcm), &Progress::new(None, None))?;
+        let integrated = result.integrated;
+        let frames = pcm.len() / self.num_channels;
+        let length = self.preview_frames().min(frames);
+        if length == 0 {
+            return Ok((integrated, 0));
+        }
+        // Centre the preview on the loudest short-term block (on the start
+        // when the buffer is shorter than one block)
+        let loudest = short_term.iter().enumerate().fold((0, f32::NEG_INFINITY), |best, (index, &energy)| if energy > best.1 { (index, energy) } else { best });
+        let centre = if short_term.is_empty() {
+            0
+        } else {
+            ((loudest.0 as f32 * SHORT_TERM_HOP as f32 / BLOCK_SAMPLE_RATE + SHORT_TERM_CENTRE_SECONDS) * self.sample_rate) as usize
+        };
+        Ok((integrated, centre.saturating_sub(length / 2).min(frames - length)))
+    }
+
+    fn side(&self, pcm: &[f32], integrated: f32, preview_start: usize, matched_loudness: f32) -> AbSide {
+        let gain_db = if integrated.is_finite() && matched_loudness.is_finite() { matched_loudness - integrated } else { 0.0 };
+        let gain = db_to_amplitude(gain_db);
+        let peak = amplitude_to_db(pcm.iter().fold(0.0f32, |peak, &x| peak.max(x.abs())) * gain);
+        AbSide {
+            integrated,
+            gain_db,
+            gain,
+            peak,
+            clips: peak > 0.0,
+            preview_start: preview_start as f32 / self.sample_rate,
+            preview: self.preview(pcm, preview_start, gain),
+        }
+    }
+
+    fn preview_frames(&self) -> usize {
+        (self.preview_seconds * self.sample_rate) as usize
+    }
+
+    // `preview_frames` from `start` with the gain and edge fades applied
+    fn preview(&self, pcm: &[f32], start: usize, gain: f32) -> Vec<f32> {
+        let length = self.preview_frames().min(pcm.len() / self.num_channels - start);
+        let fade = ((PREVIEW_FADE_SECONDS * self.sample_rate) as usize).clamp(1, (length / 2).max(1));
+        pcm[start * self.num_channels..(start + length) * self.num_channels]
+            .chunks_exact(self.num_channels)
+            .enumerate()
+            .flat_map(|(frame, samples)| {
+                let edge = frame.min(length - 1 - frame);
+                let level = gain * (edge as f32 / fade as f32).min(1.0);
+                samples.iter().map(move |&x| x * level)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn tone(amplitude: f32, seconds: usize) -> Vec<f32> {
+        (0..seconds * 48000).flat_map(|i| [amplitude * (2.0 * PI * 1000.0 * i as f32 / 48000.0).sin(); 2]).collect()
+    }
+
+    #[test]
+    fn matches_to_the_quieter_buffer_or_a_target() {
+        // The same tone 6 dB apart; the louder one gets loud in its last 2 s
+        let quiet = tone(0.1, 6);
+        let loud: Vec<f32> = tone(0.2, 6).iter().enumerate().map(|(i, &x)| if i >= 4 * 96000 { 2.0 * x } else { x }).collect();
+        let mut matcher = AbMatcher::new(48000.0, 2);
+        matcher.set_preview_seconds(2.0);
+        let pair = matcher.compare(&quiet, &loud).unwrap();
+
+        assert!(!pair.to_target);
+        assert_eq!(pair.matched_loudness, pair.a.integrated);
+        assert_eq!(pair.a.gain_db, 0.0);
+        assert!((pair.b.integrated + pair.b.gain_db - pair.matched_loudness).abs() < 1e-4);
+        assert!(pair.b.gain_db < -6.0 && !pair.b.clips, "{:?}", pair.b.gain_db);
+        // Previews: 2 s stereo, from the loudest stretch, faded in from silence
+        assert_eq!(pair.b.preview.len(), 2 * 96000);
+        assert!(pair.b.preview_start >= 3.0, "{}", pair.b.preview_start);
+        assert_eq!(pair.b.preview[0], 0.0);
+
+        matcher.set_target(-14.0);
+        let pair = matcher.compare(&quiet, &loud).unwrap();
+        assert!(pair.to_target && pair.a.gain_db > 0.0);
+        assert!((pair.a.integrated + pair.a.gain_db + 14.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn matched_buffers_measure_the_same_across_level_bands() {
+        // Pairs far apart in level: after the gains both sides must measure
+        // the same
+        let matcher = AbMatcher::new(48000.0, 2);
+        for (a, b) in [(0.5, 0.05), (0.5, 0.15), (0.15, 0.05)] {
+            let (a, b) = (tone(a, 3), tone(b, 3));
+            let pair = matcher.compare(&a, &b).unwrap();
+            let apply = |pcm: &[f32], gain: f32| -> Vec<f32> { pcm.iter().map(|&x| x * gain).collect() };
+            let (a, b) = (apply(&a, pair.a.gain), apply(&b, pair.b.gain));
+            let (a, _) = matcher.measure(&a).unwrap();
+            let (b, _) = matcher.measure(&b).unwrap();
+            assert!((a - b).abs() < 0.01, "{} vs {} LUFS", a, b);
+        }
+    }
+}
@@ -0,0 +1,1042 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::{Float32Array, Float64Array, Function, Int16Array, Int32Array};
+use serde::Serialize;
+use std::borrow::Cow;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+#[cfg(target_arch = "wasm32")]
+use crate::buffer::PcmBuffer;
+#[cfg(feature = "wav")]
+use crate::bwf::MeasuredLoudness;
+use crate::cache::{content_hash, content_hash_f64, ResultCache};
+use crate::clock::{StageTiming, Trace};
+use crate::config::AnalyzerConfig;
+#[cfg(feature = "compressed")]
+use crate::decode::{decode_audio, SourceInfo};
+use crate::error::{sanitize_pcm, validate_bit_depth, validate_pcm, AnalysisError};
+use crate::limits::{AnalysisLimits, Quality};
+use crate::manifest::BatchManifest;
+use crate::onset::MonoFold;
+#[cfg(feature = "threads")]
+use crate::parallel::join;
+use crate::podcast::{noise_floor, PodcastCheck};
+use crate::profile::{ProfileReport, QcProfile};
+use crate::loudness::{LoudnessAnalyzer, LoudnessResult, Pcm};
+#[cfg(target_arch = "wasm32")]
+use crate::progress::js_progress;
+use crate::progress::{CancellationToken, Progress};
+use crate::qc::{QcReport, QcReportBuilder};
+use crate::reference::ReferenceComparison;
+use crate::segments::{segment_ranges, SegmentMeter, SegmentedResult};
+#[cfg(feature = "json")]
+use crate::session::Session;
+#[cfg(feature = "music")]
+use crate::rhythm::{RhythmAnalyzer, RhythmResult};
+use crate::stereo::{StereoAnalyzer, StereoResult};
+use crate::technical::{TechnicalAnalyzer, TechnicalResult};
+use crate::utils::{int_to_f32, int_to_f64};
+
+/// Combined result; stereo is null for non-stereo input, rhythm unless enabled
+/// (and absent from builds without the `music` feature)
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct AnalysisResult {
+    pub loudness: LoudnessResult,
+    pub technical: TechnicalResult,
+    pub stereo: Option<StereoResult>,
+    #[cfg(feature = "music")]
+    pub rhythm: Option<RhythmResult>,
+    // Per-stage milliseconds, when collection is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub timings: Option<Vec<StageTiming>>,
+    // QC findings over every section, when enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub report: Option<QcReport>,
+    // Podcast delivery rules, when enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub podcast: Option<PodcastCheck>,
+    // Rules of the QC profile, when one is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub profile: Option<ProfileReport>,
+    // Container and codec of the decoded file, from `analyze_file`
+    #[cfg(feature = "compressed")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub source: Option<SourceInfo>,
+}
+
+#[cfg(feature = "json")]
+impl AnalysisResult {
+    /// Complete result as a versioned JSON report (kind "analysis")
+    pub fn to_json(&self) -> Result<String, AnalysisError> {
+        crate::json::report("analysis", self)
+    }
+}
+
+// The figures embedded BWF metadata is checked against
+#[cfg(feature = "wav")]
+impl From<&AnalysisResult> for MeasuredLoudness {
+    fn from(result: &AnalysisResult) -> Self {
+        MeasuredLoudness {
+            integrated: result.loudness.integrated,
+            max_true_peak: result.technical.true_peak.level,
+            max_momentary: result.loudness.momentary,
+            max_short_term: result.loudness.short_term,
+            loudness_range: Some(result.loudness.loudness_range),
+        }
+    }
+}
+
+/// Album-level figures over a whole batch; integrated loudness gates every
+/// track's blocks together as one programme
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct AlbumResult {
+    pub integrated: f32,
+    pub true_peak: f32,
+    pub track_count: usize,
+}
+
+/// Per-track results in input order plus album aggregates
+#[derive(Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct BatchResult {
+    pub tracks: Vec<AnalysisResult>,
+    pub album: AlbumResult,
+}
+
+#[cfg(feature = "json")]
+impl BatchResult {
+    /// Every track and the album figures as a versioned JSON report (kind "batch")
+    pub fn to_json(&self) -> Result<String, AnalysisError> {
+        crate::json::report("batch", self)
+    }
+}
+
+// Sections that only read the samples, so they can run alongside loudness
+// and technical
+struct IndependentSections {
+    stereo: Option<StereoResult>,
+    #[cfg(feature = "music")]
+    rhythm: Option<RhythmResult>,
+}
+
+// Unified single-pass analysis: PCM crosses the JS boundary once and every
+// analyzer runs on the same in-memory buffer, with loudness feeding technical
+// (and, with `threads`, stereo and rhythm running alongside them).
+// Results are cached by content hash for the analyzer's lifetime.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct Analyzer {
+    num_channels: usize,
+    // Targets the QC report judges against
+    config: AnalyzerConfig,
+    include_rhythm: bool,
+    include_report: bool,
+    include_podcast: bool,
+    profile: Option<QcProfile>,
+    collect_timings: bool,
+    sanitize_input: bool,
+    cancel: Option<CancellationToken>,
+    cache: ResultCache<(AnalysisResult, Vec<f32>)>,
+    loudness: LoudnessAnalyzer,
+    stereo: StereoAnalyzer,
+    technical: TechnicalAnalyzer,
+    #[cfg(feature = "music")]
+    rhythm: RhythmAnalyzer,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl Analyzer {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        Analyzer::from_config(&AnalyzerConfig::new(sample_rate, num_channels))
+    }
+
+    // Build every section from one shared config
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        Analyzer {
+            num_channels: config.num_channels(),
+            config: *config,
+            include_rhythm: false,
+            include_report: false,
+            include_podcast: false,
+            profile: None,
+            collect_timings: false,
+            sanitize_input: false,
+            cancel: None,
+            cache: ResultCache::default(),
+            loudness: LoudnessAnalyzer::from_config(config),
+            stereo: StereoAnalyzer::from_config(config),
+            technical: TechnicalAnalyzer::from_config(config),
+            #[cfg(feature = "music")]
+            rhythm: RhythmAnalyzer::from_config(config),
+        }
+    }
+
+    // Enable the (slower) rhythm/music section of the combined result; ignored
+    // in builds without the `music` feature
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_include_rhythm(&mut self, include: bool) {
+        self.include_rhythm = include;
+        self.cache.clear();
+    }
+
+    // Attach a QC report (findings, severities and suggested fixes against the
+    // config's loudness target and true-peak ceiling) to the combined result
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_include_report(&mut self, include: bool) {
+        self.include_report = include;
+        self.cache.clear();
+    }
+
+    // Check the podcast delivery rules (-16/-19 LUFS, -1 dBTP, edge silence,
+    // noise floor) and attach them to the combined result
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_include_podcast(&mut self, include: bool) {
+        self.include_podcast = include;
+        self.cache.clear();
+    }
+
+    // Evaluate a QC profile (JSON delivery spec) and attach its items to the
+    // combined result
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_qc_profile(&mut self, profile: &QcProfile) {
+        self.profile = Some(profile.clone());
+        self.cache.clear();
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn clear_qc_profile(&mut self) {
+        self.profile = None;
+        self.cache.clear();
+    }
+
+    // Forward a tempo range to the rhythm section
+    #[cfg(feature = "music")]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_tempo_range(&mut self, min_bpm: f32, max_bpm: f32) {
+        self.rhythm.set_tempo_range(min_bpm, max_bpm);
+        self.cache.clear();
+    }
+
+    // Apply duration / decimation / memory limits to the stereo and technical sections
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_limits(&mut self, limits: &AnalysisLimits) {
+        self.stereo.set_limits(limits);
+        self.technical.set_limits(limits);
+        self.cache.clear();
+    }
+
+    // Apply a speed/precision preset (FFT sizes, hops, oversampling and duration
+    // caps) across every section; replaces any limits set earlier
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_quality(&mut self, quality: Quality) {
+        self.stereo.set_quality(quality);
+        self.technical.set_quality(quality);
+        self.cache.clear();
+    }
+
+    // Run the loudness filter and sums in f64 for single-precision input too
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_double_precision(&mut self, enabled: bool) {
+        self.loudness.set_double_precision(enabled);
+        self.cache.clear();
+    }
+
+    // Take the technical section's spectral windows and spectrogram through a
+    // fixed-point FFT, for low-end mobile devices
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_reduced_precision(&mut self, enabled: bool) {
+        self.technical.set_reduced_precision(enabled);
+        self.cache.clear();
+    }
+
+    // Report per-stage timings (K-weighting, gating, FFT passes, ...) in the
+    // result's `timings`, to see where time goes on real devices
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_collect_timings(&mut self, enabled: bool) {
+        self.collect_timings = enabled;
+    }
+
+    // Analyse buffers containing NaN/infinite samples with those samples
+    // silenced, instead of rejecting them with a NonFinite error
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_sanitize_input(&mut self, enabled: bool) {
+        self.sanitize_input = enabled;
+    }
+
+    // Number of results kept for identical re-submitted content; 0 disables caching
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_cache_capacity(&mut self, capacity: usize) {
+        self.cache.set_capacity(capacity);
+    }
+
+    // Drop every cached result
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    // Attach a token that aborts the combined analysis at the next checkpoint
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_cancellation_token(&mut self, token: &CancellationToken) {
+        self.cancel = Some(token.clone());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze)]
+    pub fn analyze_js(&self, pcm: &Float32Array, on_progress: Option<Function>) -> Result<AnalysisResult, JsError> {
+        // Single copy into WASM memory shared by every analyzer
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze(&pcm.to_vec(), callback.as_deref())?)
+    }
+
+    // Analyse samples already written into WASM memory (no copy)
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen]
+    pub fn analyze_buffer(&self, buffer: &PcmBuffer, on_progress: Option<Function>) -> Result<AnalysisResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze(buffer.as_slice(), callback.as_deref())?)
+    }
+
+    // Decode an uploaded file and analyse it, with its container and codec on
+    // the result
+    #[cfg(all(target_arch = "wasm32", feature = "compressed"))]
+    #[wasm_bindgen(js_name = analyze_file)]
+    pub fn analyze_file_js(&self, bytes: &[u8], on_progress: Option<Function>) -> Result<AnalysisResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_file(bytes, callback.as_deref())?)
+    }
+
+    // Analyse several buffers (album tracks, stems) in one call
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_batch)]
+    pub fn analyze_batch_js(&self, buffers: Vec<Float32Array>, on_progress: Option<Function>) -> Result<BatchResult, JsError> {
+        let tracks: Vec<Vec<f32>> = buffers.iter().map(Float32Array::to_vec).collect();
+        let tracks: Vec<&[f32]> = tracks.iter().map(Vec::as_slice).collect();
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_batch(&tracks, callback.as_deref())?)
+    }
+
+    // Batch analysis as a sortable manifest, with full results on demand;
+    // `ids` name the buffers in order (buffers without one are named by index)
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_manifest)]
+    pub fn analyze_manifest_js(&self, buffers: Vec<Float32Array>, ids: Vec<String>, on_progress: Option<Function>) -> Result<BatchManifest, JsError> {
+        let tracks: Vec<Vec<f32>> = buffers.iter().map(Float32Array::to_vec).collect();
+        let tracks: Vec<&[f32]> = tracks.iter().map(Vec::as_slice).collect();
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_manifest(&tracks, &ids, callback.as_deref())?)
+    }
+
+    // Per-chapter loudness, true peak and edge silence; `boundaries` are the
+    // segment start times in seconds
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_segments)]
+    pub fn analyze_segments_js(&self, pcm: &Float32Array, boundaries: Vec<f32>, on_progress: Option<Function>) -> Result<SegmentedResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_segments(&pcm.to_vec(), &boundaries, callback.as_deref())?)
+    }
+
+    // Deltas against a reference master analysed by `reference` (which may be
+    // set up for another sample rate or channel count)
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = compare_to_reference)]
+    pub fn compare_to_reference_js(&self, pcm: &Float32Array, reference: &Analyzer, reference_pcm: &Float32Array, on_progress: Option<Function>) -> Result<ReferenceComparison, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.compare_to_reference(&pcm.to_vec(), reference, &reference_pcm.to_vec(), callback.as_deref())?)
+    }
+
+    // Double-precision input; loudness is measured in f64
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_f64)]
+    pub fn analyze_f64_js(&self, pcm: &Float64Array, on_progress: Option<Function>) -> Result<AnalysisResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_f64(&pcm.to_vec(), callback.as_deref())?)
+    }
+
+    // Analyse and return the versioned JSON report (`AnalysisResult::to_json`)
+    #[cfg(all(target_arch = "wasm32", feature = "json"))]
+    #[wasm_bindgen]
+    pub fn analyze_json(&self, pcm: &Float32Array, on_progress: Option<Function>) -> Result<String, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze(&pcm.to_vec(), callback.as_deref())?.to_json()?)
+    }
+
+    // Analyse into a session that can be saved and reopened without the audio
+    #[cfg(all(target_arch = "wasm32", feature = "json"))]
+    #[wasm_bindgen(js_name = analyze_session)]
+    pub fn analyze_session_js(&self, pcm: &Float32Array, on_progress: Option<Function>) -> Result<Session, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_session(&pcm.to_vec(), callback.as_deref())?)
+    }
+
+    // 16-bit integer PCM, scaled to -1..1 inside the module
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_i16)]
+    pub fn analyze_i16_js(&self, pcm: &Int16Array, on_progress: Option<Function>) -> Result<AnalysisResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_i16(&pcm.to_vec(), callback.as_deref())?)
+    }
+
+    // 8- to 32-bit integer PCM in 32-bit words (24-bit samples sign-extended)
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_i32)]
+    pub fn analyze_i32_js(&self, pcm: &Int32Array, bits_per_sample: u32, on_progress: Option<Function>) -> Result<AnalysisResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_i32(&pcm.to_vec(), bits_per_sample, callback.as_deref())?)
+    }
+}
+
+impl Analyzer {
+    /// Analyse interleaved PCM; `on_progress` receives the percent complete and
+    /// returns false to cancel
+    pub fn analyze(&self, samples: &[f32], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<AnalysisResult, AnalysisError> {
+        let samples = self.sanitized(samples);
+        let samples = samples.as_ref();
+        validate_pcm(samples, self.num_channels, self.loudness.min_frames())?;
+        let trace = Trace::new(self.collect_timings);
+        let progress = Progress::new(on_progress, self.cancel.as_ref()).with_trace(&trace);
+        let (mut result, _) = self.analyze_samples(samples, None, &progress)?;
+        progress.report(1.0);
+        result.timings = trace.into_timings();
+        Ok(result)
+    }
+
+    /// Decode a file with `decode_audio` and analyse it; the result carries the
+    /// source description. The file must have this analyzer's sample rate and
+    /// channel count (read them from `decode_audio(bytes).info` first).
+    #[cfg(feature = "compressed")]
+    pub fn analyze_file(&self, bytes: &[u8], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<AnalysisResult, AnalysisError> {
+        let audio = decode_audio(bytes)?;
+        let info = audio.info();
+        if info.sample_rate as f32 != self.config.sample_rate() || info.channels != self.num_channels {
+            return Err(AnalysisError::InvalidSetting { reason: "the file's sample rate or channel count differs from the analyzer's" });
+        }
+        let mut result = self.analyze(audio.samples(), on_progress)?;
+        result.source = Some(info.clone());
+        Ok(result)
+    }
+
+    /// `analyze` plus the momentary and short-term loudness histories and the
+    /// spectrogram (series "momentary", "short_term" and "spectrogram"),
+    /// downsampled into a session for `Session::save`
+    #[cfg(feature = "json")]
+    pub fn analyze_session(&self, samples: &[f32], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<Session, AnalysisError> {
+        let mut session = Session::new(&self.analyze(samples, on_progress)?)?;
+        let samples = self.sanitized(samples);
+        let samples = samples.as_ref();
+        session.add_series("momentary", &self.loudness.momentary_history(samples)?);
+        session.add_series("short_term", &self.loudness.short_term_history(samples)?);
+        session.add_series("spectrogram", &self.technical.spectrogram(samples)?);
+        Ok(session)
+    }
+
+    /// Analyse interleaved double-precision PCM. Loudness is filtered and
+    /// gated in f64 on the original samples; peak, spectral, stereo and
+    /// rhythm sections run on an f32 copy, where 24 bits are ample.
+    pub fn analyze_f64(&self, samples: &[f64], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<AnalysisResult, AnalysisError> {
+        let samples: Cow<[f64]> = if self.sanitize_input && samples.iter().any(|sample| !sample.is_finite()) {
+            Cow::Owned(samples.iter().map(|&sample| if sample.is_finite() { sample } else { 0.0 }).collect())
+        } else {
+            Cow::Borrowed(samples)
+        };
+        let samples = samples.as_ref();
+        validate_pcm(samples, self.num_channels, self.loudness.min_frames())?;
+        let trace = Trace::new(self.collect_timings);
+        let single: Vec<f32> = samples.iter().map(|&sample| sample as f32).collect();
+        let progress = Progress::new(on_progress, self.cancel.as_ref()).with_trace(&trace);
+        progress.lap("conversion");
+        let (mut result, _) = self.analyze_samples(&single, Some(samples), &progress)?;
+        progress.report(1.0);
+        result.timings = trace.into_timings();
+        Ok(result)
+    }
+
+    /// Analyse interleaved 16-bit integer PCM
+    pub fn analyze_i16(&self, samples: &[i16], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<AnalysisResult, AnalysisError> {
+        self.analyze(&int_to_f32(samples, 16), on_progress)
+    }
+
+    /// Analyse interleaved integer PCM of `bits_per_sample` bits (8 to 32),
+    /// right-aligned in 32-bit words. Up to 24 bits convert exactly to f32;
+    /// wider samples take the double-precision path.
+    pub fn analyze_i32(&self, samples: &[i32], bits_per_sample: u32, on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<AnalysisResult, AnalysisError> {
+        validate_bit_depth(bits_per_sample)?;
+        if bits_per_sample <= 24 {
+            self.analyze(&int_to_f32(samples, bits_per_sample), on_progress)
+        } else {
+            self.analyze_f64(&int_to_f64(samples, bits_per_sample), on_progress)
+        }
+    }
+
+    /// Loudness, true peak and edge silence of each segment starting at
+    /// `boundaries` (seconds; the programme start is implied), with the
+    /// programme measured as a whole. Segments are gated independently.
+    pub fn analyze_segments(&self, samples: &[f32], boundaries: &[f32], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<SegmentedResult, AnalysisError> {
+        let samples = self.sanitized(samples);
+        let samples = samples.as_ref();
+        validate_pcm(samples, self.num_channels, self.loudness.min_frames())?;
+        let progress = Progress::new(on_progress, self.cancel.as_ref());
+        let meter = SegmentMeter {
+            loudness: &self.loudness,
+            technical: &self.technical,
+            num_channels: self.num_channels,
+            sample_rate: self.config.sample_rate(),
+            silence_threshold: self.config.silence_threshold(),
+        };
+
+        // The programme pass takes the first half of the progress budget
+        let programme = meter.measure(samples, 0, &progress.stage(0.0, 0.5))?;
+        let ranges = segment_ranges(boundaries, samples.len() / self.num_channels, self.config.sample_rate());
+        let share = 0.5 / ranges.len() as f32;
+        let segments = ranges.iter().enumerate()
+            .map(|(index, &(start, end))| {
+                let from = 0.5 + index as f32 * share;
+                meter.measure(&samples[start * self.num_channels..end * self.num_channels], start, &progress.stage(from, from + share))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        progress.report(1.0);
+
+        Ok(SegmentedResult { segments, programme })
+    }
+
+    /// Analyse `samples` and a reference master (with the reference's own
+    /// analyzer) and report the deltas between them; progress covers both
+    pub fn compare_to_reference(&self, samples: &[f32], reference: &Analyzer, reference_samples: &[f32], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<ReferenceComparison, AnalysisError> {
+        let progress = Progress::new(on_progress, self.cancel.as_ref());
+        let stage = |from: f32, to: f32| {
+            let stage = progress.stage(from, to);
+            move |percent: f32| stage.checkpoint(percent / 100.0).is_ok()
+        };
+        let track = self.analyze(samples, Some(&stage(0.0, 0.5)))?;
+        let reference = reference.analyze(reference_samples, Some(&stage(0.5, 1.0)))?;
+        progress.report(1.0);
+        Ok(ReferenceComparison::new(track, reference))
+    }
+
+    /// Analyse a batch of interleaved tracks sharing this analyzer's sample
+    /// rate and channel count, sequentially, and aggregate album loudness.
+    /// Every track is validated up front; the first invalid one fails the batch.
+    pub fn analyze_batch(&self, tracks: &[&[f32]], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<BatchResult, AnalysisError> {
+        let tracks: Vec<Cow<[f32]>> = tracks.iter().map(|track| self.sanitized(track)).collect();
+        for track in &tracks {
+            validate_pcm(track, self.num_channels, self.loudness.min_frames())?;
+        }
+        let progress = Progress::new(on_progress, self.cancel.as_ref());
+        let share = 1.0 / tracks.len().max(1) as f32;
+
+        let mut results = Vec::with_capacity(tracks.len());
+        let mut album_energies = Vec::new();
+        for (index, track) in tracks.iter().enumerate() {
+            let start = index as f32 * share;
+            let trace = Trace::new(self.collect_timings);
+            let (mut result, energies) = self.analyze_samples(track, None, &progress.stage(start, start + share).with_trace(&trace))?;
+            result.timings = trace.into_timings();
+            album_energies.extend(energies);
+            results.push(result);
+        }
+
+        let album = AlbumResult {
+            integrated: self.loudness.calculate_integrated_loudness(&album_energies),
+            true_peak: results.iter().map(|track| track.technical.true_peak.level).fold(f32::NEG_INFINITY, f32::max),
+            track_count: results.len(),
+        };
+        progress.report(1.0);
+
+        Ok(BatchResult { tracks: results, album })
+    }
+
+    /// `analyze_batch` as a manifest for folder analysis: one row per track
+    /// with key figures and QC pass/fail flags (against the config targets),
+    /// the full results kept for `BatchManifest::detail`. Tracks without an
+    /// entry in `ids` are named by their index.
+    pub fn analyze_manifest<S: AsRef<str>>(&self, tracks: &[&[f32]], ids: &[S], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<BatchManifest, AnalysisError> {
+        let batch = self.analyze_batch(tracks, on_progress)?;
+        let rows = batch.tracks.into_iter().zip(tracks).enumerate()
+            .map(|(index, (result, track))| {
+                let id = ids.get(index).map_or_else(|| index.to_string(), |id| id.as_ref().to_string());
+                let duration = (track.len() / self.num_channels) as f32 / self.config.sample_rate();
+                let report = result.report.clone().unwrap_or_else(|| self.qc_report(&result));
+                (id, duration, result, report)
+            })
+            .collect();
+        Ok(BatchManifest::new(rows, batch.album))
+    }
+
+    // `samples` with non-finite values silenced, when sanitizing
+    fn sanitized<'s>(&self, samples: &'s [f32]) -> Cow<'s, [f32]> {
+        if !self.sanitize_input || samples.iter().all(|sample| sample.is_finite()) {
+            return Cow::Borrowed(samples);
+        }
+        let mut owned = samples.to_vec();
+        let replaced = sanitize_pcm(&mut owned);
+        log::warn!("Silenced {} non-finite samples", replaced);
+        Cow::Owned(owned)
+    }
+
+    // Full analysis of validated samples, also returning the gated momentary
+    // block energies for album aggregation. `exact` is the double-precision
+    // original of `samples`, if there is one. Timings are left to the caller,
+    // so a cache hit reports the lookup rather than the original run.
+    fn analyze_samples(&self, samples: &[f32], exact: Option<&[f64]>, progress: &Progress) -> Result<(AnalysisResult, Vec<f32>), AnalysisError> {
+        let key = exact.map_or_else(|| content_hash(samples), content_hash_f64);
+        if let Some(cached) = self.cache.get(key) {
+            log::debug!("Returning cached result for content {:016x}", key);
+            progress.lap("cache_lookup");
+            return Ok(cached);
+        }
+        let analysis = self.analyze_uncached(samples, exact, progress)?;
+        self.cache.insert(key, analysis.clone());
+        Ok(analysis)
+    }
+
+    fn analyze_uncached(&self, samples: &[f32], exact: Option<&[f64]>, progress: &Progress) -> Result<(AnalysisResult, Vec<f32>), AnalysisError> {
+        let include_rhythm = cfg!(feature = "music") && self.include_rhythm;
+        log::debug!("Analyzing {} samples, {} channels, rhythm: {}", samples.len(), self.num_channels, include_rhythm);
+
+        // Progress budget per stage; rhythm takes the back half when enabled
+        let (technical_end, stereo_end) = if include_rhythm { (0.4, 0.5) } else { (0.85, 1.0) };
+        progress.lap("hashing");
+
+        // Technical needs the integrated loudness, but stereo and rhythm only
+        // read the samples, so they are independent of both
+        let loudness_input = exact.map_or(Pcm::Single(samples), Pcm::Double);
+        // Transients, punch and rhythm all read the mono fold and its onset
+        // envelope, so both are computed once here
+        let mono = MonoFold::new(samples, self.num_channels);
+        let measure = || -> Result<_, AnalysisError> {
+            let (loudness, energies) = self.loudness.measure_samples(loudness_input, &progress.stage(0.0, 0.2))?;
+            let technical = self.technical.analyze_samples(samples, &mono, samples.len(), loudness.integrated, &progress.stage(0.2, technical_end))?;
+            Ok((loudness, energies, technical))
+        };
+        let (num_channels, stereo_analyzer) = (self.num_channels, &self.stereo);
+        #[cfg(feature = "music")]
+        let rhythm_analyzer = &self.rhythm;
+        let independent = |progress: &Progress| -> Result<IndependentSections, AnalysisError> {
+            // Stereo analysis only makes sense for two-channel material
+            let stereo = if num_channels == 2 {
+                Some(stereo_analyzer.analyze_samples(samples, samples.len()))
+            } else {
+                None
+            };
+            progress.lap("stereo");
+            progress.stage(technical_end, stereo_end).checkpoint(1.0)?;
+
+            #[cfg(feature = "music")]
+            let rhythm = if include_rhythm {
+                let rhythm = rhythm_analyzer.analyze_mono(&mono, &progress.stage(stereo_end, 1.0))?;
+                progress.lap("rhythm");
+                Some(rhythm)
+            } else {
+                None
+            };
+            Ok(IndependentSections {
+                stereo,
+                #[cfg(feature = "music")]
+                rhythm,
+            })
+        };
+
+        // With threads the independent sections run on the pool alongside
+        // loudness and technical. Progress callbacks can't leave the calling
+        // thread, so there they only honour cancellation and their share of
+        // the progress is reported once they finish
+        #[cfg(feature = "threads")]
+        let (measured, independent) = {
+            let cancel = self.cancel.as_ref();
+            join(measure, || independent(&Progress::new(None, cancel)))
+        };
+        #[cfg(not(feature = "threads"))]
+        let (measured, independent) = (measure(), independent(progress));
+        let (loudness, energies, technical) = measured?;
+        let IndependentSections {
+            stereo,
+            #[cfg(feature = "music")]
+            rhythm,
+        } = independent?;
+        #[cfg(feature = "threads")]
+        {
+            // The side sections can't trace from the pool; what they cost
+            // past loudness and technical is the wait booked here
+            progress.lap("stereo");
+            progress.stage(technical_end, 1.0).checkpoint(1.0)?;
+        }
+
+        let mut result = AnalysisResult {
+            loudness,
+            technical,
+            stereo,
+            #[cfg(feature = "music")]
+            rhythm,
+            timings: None,
+            report: None,
+            podcast: None,
+            profile: None,
+            #[cfg(feature = "compressed")]
+            source: None,
+        };
+        if self.include_report {
+            result.report = Some(self.qc_report(&result));
+        }
+        if self.include_podcast {
+            let noise_floor = noise_floor(samples, self.num_channels, self.config.sample_rate());
+            result.podcast = Some(PodcastCheck::evaluate(&result.loudness, &result.technical, self.num_channels, noise_floor));
+        }
+        result.profile = self.profile.as_ref().map(|profile| profile.evaluate(&result));
+        Ok((result, energies))
+    }
+
+    fn qc_report(&self, result: &AnalysisResult) -> QcReport {
+        let mut builder = QcReportBuilder::from_config(&self.config).loudness(&result.loudness).technical(&result.technical);
+        if let Some(stereo) = &result.stereo {
+            builder = builder.stereo(stereo);
+        }
+        #[cfg(feature = "music")]
+        if let Some(rhythm) = &result.rhythm {
+            builder = builder.rhythm(rhythm);
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MOMENTARY_BLOCK_SIZE;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::cell::RefCell;
+
+    #[cfg(feature = "compressed")]
+    #[test]
+    fn analyzes_files_with_their_source() {
+        let spec = hound::WavSpec { channels: 2, sample_rate: 44100, bits_per_sample: 24, sample_format: hound::SampleFormat::Int };
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for i in 0..2 * 44100 {
+            writer.write_sample((1_000_000.0 * (i as f32 * 0.03).sin()) as i32).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let result = Analyzer::new(44100.0, 2).analyze_file(bytes.get_ref(), None).unwrap();
+        let source = result.source.unwrap();
+        assert_eq!((source.container.as_str(), source.codec.as_str(), source.bits_per_sample), ("wav", "pcm_s24le", Some(24)));
+        assert!(result.loudness.integrated.is_finite());
+        assert!(matches!(Analyzer::new(48000.0, 2).analyze_file(bytes.get_ref(), None), Err(AnalysisError::InvalidSetting { .. })));
+    }
+
+    #[test]
+    fn native_analysis_reports_progress_and_cancels() {
+        let pcm: Vec<f32> = (0..2 * 44100).map(|i| 0.25 * ((i / 2) as f32 * 0.06).sin()).collect();
+        let mut analyzer = Analyzer::new(44100.0, 2);
+
+        let reported = RefCell::new(Vec::new());
+        let result = analyzer.analyze(&pcm, Some(&|percent| {
+            reported.borrow_mut().push(percent);
+            true
+        })).unwrap();
+        assert!(result.loudness.integrated.is_finite());
+        assert!(result.stereo.is_some_and(|stereo| !stereo.is_mono));
+        assert!(reported.borrow().windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(reported.borrow().last(), Some(&100.0));
+
+        // Identical content is served from the cache without re-running the stages
+        reported.borrow_mut().clear();
+        analyzer.analyze(&pcm, Some(&|percent| {
+            reported.borrow_mut().push(percent);
+            true
+        })).unwrap();
+        assert_eq!(*reported.borrow(), [100.0]);
+
+        analyzer.clear_cache();
+        let cancelled = analyzer.analyze(&pcm, Some(&|percent| percent < 50.0));
+        assert!(matches!(cancelled, Err(AnalysisError::Cancelled)));
+    }
+
+    #[test]
+    fn batch_matches_single_track_analysis() {
+        let track: Vec<f32> = (0..2 * 44100).map(|i| 0.25 * ((i / 2) as f32 * 0.06).sin()).collect();
+        let analyzer = Analyzer::new(44100.0, 2);
+
+        let single = analyzer.analyze(&track, None).unwrap();
+        let batch = analyzer.analyze_batch(&[&track, &track], None).unwrap();
+        assert_eq!(batch.album.track_count, 2);
+        assert_eq!(batch.tracks[1].loudness.integrated, single.loudness.integrated);
+        // Identical tracks gate to the same loudness as either one alone
+        assert!((batch.album.integrated - single.loudness.integrated).abs() < 0.01);
+        assert_eq!(batch.album.true_peak, single.technical.true_peak.level);
+
+        assert!(analyzer.analyze_batch(&[&track, &[]], None).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "threads")]
+    fn sections_run_alongside_with_matching_results() {
+        let pcm: Vec<f32> = (0..2 * 6 * 44100).map(|i| 0.25 * ((i / 2) as f32 * 0.06).sin() + 0.05 * (i as f32 * 0.3).sin()).collect();
+        let run = |threads: usize| {
+            let mut analyzer = Analyzer::new(44100.0, 2);
+            analyzer.set_include_rhythm(true);
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| {
+                let reported = RefCell::new(Vec::new());
+                let result = analyzer.analyze(&pcm, Some(&|percent| {
+                    reported.borrow_mut().push(percent);
+                    true
+                })).unwrap();
+                (result, reported.into_inner())
+            })
+        };
+
+        let ((sequential, _), (threaded, reported)) = (run(1), run(4));
+        assert_eq!(sequential.loudness.integrated, threaded.loudness.integrated);
+        assert_eq!(sequential.technical.true_peak.level, threaded.technical.true_peak.level);
+        let (stereo, threaded_stereo) = (sequential.stereo.unwrap(), threaded.stereo.unwrap());
+        assert_eq!(stereo.phase_correlation, threaded_stereo.phase_correlation);
+        assert_eq!(stereo.stereo_width, threaded_stereo.stereo_width);
+        #[cfg(feature = "music")]
+        assert_eq!(sequential.rhythm.map(|rhythm| rhythm.beats), threaded.rhythm.map(|rhythm| rhythm.beats));
+        assert!(reported.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(reported.last(), Some(&100.0));
+    }
+
+    #[test]
+    fn manifest_rows_flag_failing_tracks() {
+        let tone = |amplitude: f32| -> Vec<f32> { (0..2 * 4 * 44100).map(|i| amplitude * ((i / 2) as f32 * 0.06).sin()).collect() };
+        let (quiet, hot) = (tone(0.05), tone(1.0));
+        let analyzer = Analyzer::new(44100.0, 2);
+
+        let batch = analyzer.analyze_manifest(&[&quiet, &hot, &quiet], &["quiet.wav", "hot.wav"], None).unwrap();
+        let manifest = batch.manifest();
+        let ids: Vec<&str> = manifest.entries.iter().map(|entry| entry.id.as_str()).collect();
+        assert_eq!(ids, ["quiet.wav", "hot.wav", "2"]);
+        assert_eq!(batch.len(), 3);
+
+        let hot = &manifest.entries[1];
+        assert!((hot.duration - 4.0).abs() < 1e-6);
+        assert!(!hot.true_peak_pass && !hot.passes && hot.issue_count > 0);
+        assert_eq!(hot.severity, crate::qc::Severity::Error);
+        assert!(manifest.entries[0].true_peak_pass);
+        assert_eq!(manifest.pass_count, manifest.entries.iter().filter(|entry| entry.passes).count());
+        assert_eq!(batch.detail("hot.wav").unwrap().technical.true_peak.level, hot.true_peak);
+        assert!(batch.detail("missing.wav").is_none());
+    }
+
+    #[test]
+    fn podcast_rules_report_each_measurement() {
+        // Mono speech-level tone at about -19 LUFS, with 2 s of dead air at the end
+        let pcm: Vec<f32> = (0..6 * 44100).map(|i| 0.16 * (i as f32 * 0.06).sin())
+            .chain(std::iter::repeat_n(0.0, 2 * 44100))
+            .collect();
+        let mut analyzer = Analyzer::new(44100.0, 1);
+        assert!(analyzer.analyze(&pcm, None).unwrap().podcast.is_none());
+        analyzer.set_include_podcast(true);
+
+        let result = analyzer.analyze(&pcm, None).unwrap();
+        let podcast = result.podcast.unwrap();
+        assert_eq!(podcast.target_loudness, crate::podcast::PODCAST_MONO_TARGET);
+        let rule = |id: &str| podcast.rules.iter().find(|rule| rule.rule == id).unwrap();
+        assert_eq!(rule("true_peak").measured, result.technical.true_peak.level);
+        assert!(rule("true_peak").passed);
+        assert_eq!(rule("integrated_loudness").measured, result.loudness.integrated);
+        assert!(rule("leading_silence").passed && rule("trailing_silence").passed);
+        assert!(rule("integrated_loudness").passed);
+        // A tone without pauses: its quietest windows are the tone itself
+        assert!(!rule("noise_floor").passed && !podcast.passes);
+    }
+
+    #[test]
+    fn off_rate_input_is_resampled_for_loudness() {
+        let tone = |rate: f32| -> Vec<f32> {
+            (0..(4.0 * rate) as usize).flat_map(|i| [0.25 * (2.0 * std::f32::consts::PI * 997.0 * i as f32 / rate).sin(); 2]).collect()
+        };
+        let reference = Analyzer::new(44100.0, 2).analyze(&tone(44100.0), None).unwrap().loudness;
+        let resampled = Analyzer::new(48000.0, 2).analyze(&tone(48000.0), None).unwrap().loudness;
+
+        assert!(reference.resampling.is_none());
+        assert_eq!(resampled.resampling.map(|report| report.from_rate), Some(48000.0));
+        assert!((resampled.integrated - reference.integrated).abs() < 0.05);
+    }
+
+    #[test]
+    fn integer_pcm_matches_float_input() {
+        let ints: Vec<i32> = (0..2 * 44100).map(|i| (8_000_000.0 * ((i / 2) as f32 * 0.06).sin()) as i32).collect();
+        let floats: Vec<f32> = ints.iter().map(|&sample| sample as f32 / 8_388_608.0).collect();
+        let analyzer = Analyzer::new(44100.0, 2);
+
+        let reference = analyzer.analyze(&floats, None).unwrap().loudness.integrated;
+        assert_eq!(analyzer.analyze_i32(&ints, 24, None).unwrap().loudness.integrated, reference);
+        let shifted: Vec<i32> = ints.iter().map(|&sample| sample << 8).collect();
+        assert!((analyzer.analyze_i32(&shifted, 32, None).unwrap().loudness.integrated - reference).abs() < 1e-3);
+        let halved: Vec<i16> = ints.iter().map(|&sample| (sample >> 8) as i16).collect();
+        assert!((analyzer.analyze_i16(&halved, None).unwrap().loudness.integrated - reference).abs() < 1e-3);
+
+        assert!(matches!(analyzer.analyze_i32(&ints, 40, None), Err(AnalysisError::InvalidBitDepth { bits: 40 })));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_report_is_versioned_and_complete() {
+        let pcm: Vec<f32> = (0..2 * 44100).map(|i| 0.3 * ((i / 2) as f32 * 0.05).sin()).collect();
+        let result = Analyzer::new(44100.0, 2).analyze(&pcm, None).unwrap();
+
+        let report: serde_json::Value = serde_json::from_str(&result.to_json().unwrap()).unwrap();
+        assert_eq!(report["kind"], "analysis");
+        assert_eq!(report["version"], crate::json::REPORT_VERSION);
+        assert!((report["result"]["loudness"]["integrated"].as_f64().unwrap() - result.loudness.integrated as f64).abs() < 1e-4);
+        assert_eq!(report["result"]["loudness"]["pcm_debug"].as_array().unwrap().len(), result.loudness.pcm_debug.len());
+        assert!(report["result"]["technical"]["true_peak"]["level"].is_number());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_reports_floor_the_levels_of_silence() {
+        let silence = vec![0.0f32; 2 * 4 * 44100];
+        let result = Analyzer::new(44100.0, 2).analyze(&silence, None).unwrap();
+        assert_eq!(result.loudness.integrated, f32::NEG_INFINITY);
+
+        let report: serde_json::Value = serde_json::from_str(&result.to_json().unwrap()).unwrap();
+        let floor = crate::json::JSON_DB_FLOOR as f64;
+        assert_eq!(report["result"]["loudness"]["integrated"], floor);
+        assert_eq!(report["result"]["loudness"]["shortTerm"], floor);
+        assert_eq!(report["result"]["technical"]["true_peak"]["level"], floor);
+
+        let floored = Analyzer::from_config(&AnalyzerConfig::new(44100.0, 2).with_db_floor(-120.0)).analyze(&silence, None).unwrap();
+        assert_eq!(floored.loudness.integrated, -120.0);
+        assert_eq!(floored.technical.true_peak.level, -120.0);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn sessions_reopen_without_reanalysis() {
+        use crate::series::TimeSeries;
+
+        let pcm: Vec<f32> = (0..2 * 4 * 44100).map(|i| 0.3 * ((i / 2) as f32 * 0.05).sin()).collect();
+        let analyzer = Analyzer::new(44100.0, 2);
+        let result = analyzer.analyze(&pcm, None).unwrap();
+        let mut session = analyzer.analyze_session(&pcm, None).unwrap();
+        session.add_series("silence", &TimeSeries::new(0.1, 1, vec![f32::NEG_INFINITY, -20.0]));
+
+        let reopened = Session::load(&session.save().unwrap()).unwrap();
+        assert_eq!(reopened.series_names(), ["momentary", "short_term", "spectrogram", "silence"]);
+        assert!((reopened.result()["loudness"]["integrated"].as_f64().unwrap() - result.loudness.integrated as f64).abs() < 1e-4);
+        let momentary = analyzer.loudness.momentary_history(&pcm).unwrap();
+        assert_eq!(reopened.series("momentary"), Some(&momentary));
+        assert_eq!(reopened.series("silence").unwrap().values(), [f32::NEG_INFINITY, -20.0]);
+        assert_eq!(reopened.series("spectrogram").unwrap().width(), 7);
+
+        assert!(Session::load(&result.to_json().unwrap()).is_err());
+        assert!(Session::load("{}").is_err());
+    }
+
+    #[test]
+    fn segments_are_measured_independently() {
+        // A quiet chapter, a loud one and a second of silence
+        let tone = |amplitude: f32, seconds: usize| (0..seconds * 44100).map(move |i| amplitude * (i as f32 * 0.06).sin());
+        let pcm: Vec<f32> = tone(0.05, 5).chain(tone(0.5, 5)).chain(std::iter::repeat_n(0.0, 44100)).collect();
+        let analyzer = Analyzer::new(44100.0, 1);
+
+        let result = analyzer.analyze_segments(&pcm, &[5.0], None).unwrap();
+        let [quiet, loud] = &result.segments[..] else { panic!("expected two segments") };
+        assert_eq!((quiet.start, quiet.end, loud.start, loud.end), (0.0, 5.0, 5.0, 11.0));
+        assert!((loud.true_peak - quiet.true_peak - 20.0).abs() < 0.01);
+        assert!(loud.integrated - quiet.integrated > 15.0);
+        assert!((loud.trailing_silence - 1.0).abs() < 0.01);
+        assert_eq!(quiet.trailing_silence, 0.0);
+        assert_eq!(result.programme.true_peak, loud.true_peak);
+        assert!(result.programme.integrated > quiet.integrated && result.programme.integrated < loud.integrated);
+    }
+
+    #[test]
+    fn timings_are_collected_on_request() {
+        let pcm: Vec<f32> = (0..2 * 44100).map(|i| 0.25 * ((i / 2) as f32 * 0.06).sin()).collect();
+        let mut analyzer = Analyzer::new(44100.0, 2);
+        assert!(analyzer.analyze(&pcm, None).unwrap().timings.is_none());
+
+        analyzer.set_collect_timings(true);
+        analyzer.clear_cache();
+        let stages: Vec<String> = analyzer.analyze(&pcm, None).unwrap().timings.unwrap().into_iter().map(|timing| timing.stage).collect();
+        for stage in ["k_weighting", "gating", "true_peak", "spectral_fft", "stereo"] {
+            assert!(stages.iter().any(|recorded| recorded == stage), "missing {}", stage);
+        }
+
+        let cached = analyzer.analyze(&pcm, None).unwrap().timings.unwrap();
+        assert_eq!(cached.last().map(|timing| timing.stage.as_str()), Some("cache_lookup"));
+    }
+
+    // Every scalar metric is a number or ±inf (silence), never NaN
+    fn assert_defined(result: &AnalysisResult) {
+        let (loudness, technical) = (&result.loudness, &result.technical);
+        let mut values = vec![
+            loudness.momentary, loudness.short_term, loudness.integrated, loudness.gate_threshold,
+            technical.true_peak.level, technical.quality.clipping_percentage, technical.quality.dc_offset,
+            technical.spectral.centroid, technical.spectral.rolloff, technical.spectral.flatness,
+            technical.silence.leading_silence, technical.silence.trailing_silence,
+            technical.mastering.plr, technical.mastering.dynamic_range, technical.mastering.punchiness,
+            technical.mastering.warmth, technical.mastering.clarity, technical.mastering.spaciousness,
+            technical.mastering.quality_score, technical.mastering.transient_density,
+        ];
+        if let Some(stereo) = &result.stereo {
+            values.extend([stereo.phase_correlation, stereo.stereo_width, stereo.lr_balance, stereo.imaging_quality_score].into_iter().flatten());
+            values.push(stereo.mono_compatibility);
+        }
+        assert!(values.iter().all(|value| !value.is_nan()), "NaN in {:?}", values);
+    }
+
+    #[test]
+    fn degenerate_input_gives_defined_results() {
+        let mut rng = StdRng::seed_from_u64(2437);
+        let mut analyzer = Analyzer::new(44100.0, 2);
+        analyzer.set_cache_capacity(0);
+
+        let damaged = vec![f32::NAN; 2 * MOMENTARY_BLOCK_SIZE];
+        assert!(matches!(analyzer.analyze(&damaged, None), Err(AnalysisError::NonFinite { index: 0 })));
+        analyzer.set_sanitize_input(true);
+
+        // Silence, noise, noise with dropouts to NaN/inf, and denormal-level hiss
+        for case in 0..16 {
+            let frames = MOMENTARY_BLOCK_SIZE + rng.gen_range(0..8820);
+            let pcm: Vec<f32> = (0..2 * frames).map(|_| match case % 4 {
+                0 => 0.0,
+                1 => rng.gen_range(-1.0..1.0),
+                2 if rng.gen_bool(0.01) => if rng.gen_bool(0.5) { f32::NAN } else { f32::INFINITY },
+                2 => rng.gen_range(-1.0..1.0),
+                _ => rng.gen_range(-1e-30..1e-30),
+            }).collect();
+            assert_defined(&analyzer.analyze(&pcm, None).unwrap());
+        }
+
+        // Sections that accept single-frame input
+        let technical = TechnicalAnalyzer::new(44100.0);
+        for sample in [0.5f32, 0.0, -1.0] {
+            let result = technical.analyze_technical(&[sample], f32::NEG_INFINITY, None).unwrap();
+            assert_eq!(result.true_peak.level, 20.0 * sample.abs().log10());
+            assert_eq!(result.mastering.plr, 0.0);
+        }
+        let stereo = StereoAnalyzer::new(44100.0).analyze_stereo(&[0.5, -0.5]).unwrap();
+        assert_eq!(stereo.phase_correlation, Some(-1.0));
+    }
+
+    #[test]
+    fn double_precision_input_agrees_with_single() {
+        let exact: Vec<f64> = (0..2 * 44100).map(|i| 0.25 * ((i / 2) as f64 * 0.06).sin()).collect();
+        let single: Vec<f32> = exact.iter().map(|&sample| sample as f32).collect();
+        let mut analyzer = Analyzer::new(44100.0, 2);
+
+        let reference = analyzer.analyze(&single, None).unwrap().loudness.integrated;
+        let double = analyzer.analyze_f64(&exact, None).unwrap().loudness.integrated;
+        assert!((double - reference).abs() < 0.01);
+
+        analyzer.set_double_precision(true);
+        assert_eq!(analyzer.analyze(&single, None).unwrap().loudness.integrated, double);
+    }
+}
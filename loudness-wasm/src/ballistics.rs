@@ -0,0 +1,192 @@
+// Meter ballistics for UI-facing readings, so every frontend shows the same
+// needle: `Integrator` is a one-pole smoother with separate attack and release
+// times (correlation and width meters, VU-style levels), `PeakHold` holds the
+// highest reading for a while and then falls at a set rate (peak meters).
+// Both run at whatever rate they are fed: per sample inside the meters, or at
+// display rate on readings polled from a snapshot.
+//
+//     const needle = new Integrator(0.3, 0.3, 30);      // 300ms, fed at 30 Hz
+//     const hold = new PeakHold(2.0, 20, 30);           // 2s hold, then 20 dB/s
+//     const shown = needle.process(snapshot.correlation);
+//     const peak = hold.process(10 ** (snapshot.true_peak / 20));
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+// Per-update coefficient of a one-pole smoother with time constant `seconds`
+// (0 follows the input at once)
+fn smoothing_coefficient(seconds: f32, rate: f32) -> f32 {
+    if seconds > 0.0 && rate > 0.0 {
+        (-1.0 / (seconds * rate)).exp()
+    } else {
+        0.0
+    }
+}
+
+/// Attack/release integrator: rises towards larger inputs with the attack
+/// time constant and falls towards smaller ones with the release time constant
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Integrator {
+    attack: f32,
+    release: f32,
+    value: f32,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl Integrator {
+    // Time constants in seconds, `rate` the number of updates per second
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(attack_seconds: f32, release_seconds: f32, rate: f32) -> Self {
+        Integrator {
+            attack: smoothing_coefficient(attack_seconds, rate),
+            release: smoothing_coefficient(release_seconds, rate),
+            value: 0.0,
+        }
+    }
+
+    // Feed one reading and return the smoothed value
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn process(&mut self, input: f32) -> f32 {
+        let coefficient = if input > self.value { self.attack } else { self.release };
+        self.value = coefficient * self.value + (1.0 - coefficient) * input;
+        self.value
+    }
+
+    // Feed a block of readings and return the value after the last one
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn process_block(&mut self, inputs: &[f32]) -> f32 {
+        inputs.iter().for_each(|&input| {
+            self.process(input);
+        });
+        self.value
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    // Jump to `value` without smoothing
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn reset(&mut self, value: f32) {
+        self.value = value;
+    }
+}
+
+/// Peak hold with decay: a new maximum is held for the hold time, then the
+/// reading falls at the decay rate until it meets the input again, which is
+/// then held in turn. Works on linear magnitudes (amplitudes or powers).
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PeakHold {
+    hold_updates: usize,
+    fall: f32,
+    age: usize,
+    value: f32,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl PeakHold {
+    // Hold time in seconds, decay in dB per second (Infinity drops straight
+    // to the current input), `rate` the number of updates per second
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(hold_seconds: f32, decay_db_per_second: f32, rate: f32) -> Self {
+        let mut hold = PeakHold { hold_updates: 0, fall: 0.0, age: 0, value: 0.0 };
+        hold.set_hold(hold_seconds, rate);
+        hold.set_decay(decay_db_per_second, rate);
+        hold
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_hold(&mut self, seconds: f32, rate: f32) {
+        self.hold_updates = (rate * seconds.max(0.0)) as usize;
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_decay(&mut self, db_per_second: f32, rate: f32) {
+        self.fall = if db_per_second.is_finite() && rate > 0.0 {
+            10f32.powf(-db_per_second.max(0.0) / (20.0 * rate))
+        } else {
+            0.0
+        };
+    }
+
+    // Feed one magnitude and return the held reading
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn process(&mut self, input: f32) -> f32 {
+        if input >= self.value {
+            self.value = input;
+            self.age = 0;
+        } else if self.age >= self.hold_updates {
+            let fallen = self.value * self.fall;
+            if fallen <= input {
+                self.value = input;
+                self.age = 0;
+            } else {
+                self.value = fallen;
+            }
+        } else {
+            self.age += 1;
+        }
+        self.value
+    }
+
+    // Feed a block of magnitudes and return the reading after the last one
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn process_block(&mut self, inputs: &[f32]) -> f32 {
+        inputs.iter().for_each(|&input| {
+            self.process(input);
+        });
+        self.value
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+        self.age = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integrator_attacks_faster_than_it_releases() {
+        // 10ms attack, 1s release at 1 kHz
+        let mut integrator = Integrator::new(0.01, 1.0, 1000.0);
+        integrator.process_block(&[1.0; 50]);
+        assert!(integrator.value() > 0.99);
+        // One time constant of release leaves 1/e
+        integrator.process_block(&[0.0; 1000]);
+        assert!((integrator.value() - (-1.0f32).exp()).abs() < 0.01);
+        // Zero time constants follow the input
+        assert_eq!(Integrator::new(0.0, 0.0, 1000.0).process(0.25), 0.25);
+    }
+
+    #[test]
+    fn peak_hold_holds_then_decays() {
+        // 0.5s hold, then 20 dB/s at 100 updates per second
+        let mut hold = PeakHold::new(0.5, 20.0, 100.0);
+        hold.process(1.0);
+        assert_eq!(hold.process_block(&[0.01; 50]), 1.0);
+        // Half a second of decay is 10 dB down
+        hold.process_block(&[0.01; 50]);
+        assert!((hold.value() - 10f32.powf(-0.5)).abs() < 0.01);
+        // A louder input takes over at once
+        assert_eq!(hold.process(0.9), 0.9);
+
+        // Without decay the reading drops to the input and holds that
+        let mut hold = PeakHold::new(0.1, f32::INFINITY, 100.0);
+        hold.process(1.0);
+        hold.process_block(&[0.5; 10]);
+        assert_eq!(hold.process(0.5), 0.5);
+        assert_eq!(hold.process_block(&[0.2; 10]), 0.5);
+    }
+}
@@ -0,0 +1,221 @@
+// Programme loudness of a multichannel bed (5.1, 7.1, Atmos/ADM-style 7.1.4
+// stems): every channel is K-weighted and its energy summed with the
+// BS.1770-5 position weight, so surrounds count +1.5 dB and the LFE not at
+// all. As in the stereo meter, each channel's filter runs continuously across
+// the blocks.
+//
+//     const meter = new BedLoudnessAnalyzer(48000, "7.1.4");
+//     const { integrated, channels } = meter.analyze(pcm);
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::{Float32Array, Function};
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::constants::*;
+use crate::error::{validate_pcm, AnalysisError};
+#[cfg(target_arch = "wasm32")]
+use crate::progress::js_progress;
+use crate::gating::block_loudness;
+use crate::loudness::{absolute_gated, block_energies, integrated_loudness, loudness_range, loudness_status, sum_channels, ChannelEnergy, LoudnessStatus};
+use crate::parallel::map_range;
+use crate::progress::Progress;
+use crate::resample::Resampler;
+
+// Weight of channels within 30° of the horizontal plane and 60°-120° off
+// centre (side and rear surrounds), BS.1770-5 table 3
+const SURROUND_WEIGHT: f32 = 1.41;
+
+// Channel labels of the named layouts, in interleaving order
+const LAYOUTS: [(&str, &[&str]); 8] = [
+    ("1.0", &["C"]),
+    ("2.0", &["L", "R"]),
+    ("5.1", &["L", "R", "C", "LFE", "Ls", "Rs"]),
+    ("7.1", &["L", "R", "C", "LFE", "Lss", "Rss", "Lrs", "Rrs"]),
+    ("5.1.2", &["L", "R", "C", "LFE", "Ls", "Rs", "Ltm", "Rtm"]),
+    ("5.1.4", &["L", "R", "C", "LFE", "Ls", "Rs", "Ltf", "Rtf", "Ltr", "Rtr"]),
+    ("7.1.2", &["L", "R", "C", "LFE", "Lss", "Rss", "Lrs", "Rrs", "Ltm", "Rtm"]),
+    ("7.1.4", &["L", "R", "C", "LFE", "Lss", "Rss", "Lrs", "Rrs", "Ltf", "Rtf", "Ltr", "Rtr"]),
+];
+
+/// One bed channel and the weight its energy is summed with
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct BedChannel {
+    pub label: String,
+    pub weight: f32,
+}
+
+/// BS.1770 loudness of a bed (LUFS, uncalibrated)
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct BedLoudnessResult {
+    pub layout: String,
+    pub channels: Vec<BedChannel>,
+    pub integrated: f32,
+    // Maximum momentary and short-term loudness
+    pub momentary: f32,
+    pub short_term: f32,
+    pub loudness_range: f32,
+    pub status: LoudnessStatus,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct BedLoudnessAnalyzer {
+    sample_rate: f32,
+    layout: String,
+    channels: Vec<BedChannel>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl BedLoudnessAnalyzer {
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(constructor)]
+    pub fn new_js(sample_rate: f32, layout: &str) -> Result<BedLoudnessAnalyzer, JsError> {
+        Ok(BedLoudnessAnalyzer::new(sample_rate, layout)?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze)]
+    pub fn analyze_js(&self, pcm: &Float32Array, on_progress: Option<Function>) -> Result<BedLoudnessResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze(&pcm.to_vec(), callback.as_deref())?)
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
+}
+
+impl BedLoudnessAnalyzer {
+    /// Bed in a named layout: 1.0, 2.0, 5.1, 7.1, 5.1.2, 5.1.4, 7.1.2 or 7.1.4
+    pub fn new(sample_rate: f32, layout: &str) -> Result<Self, AnalysisError> {
+        let (name, labels) = LAYOUTS.iter()
+            .find(|(name, _)| *name == layout)
+            .ok_or(AnalysisError::InvalidLayout { reason: "unknown layout name" })?;
+        let mut analyzer = BedLoudnessAnalyzer::from_labels(sample_rate, labels)?;
+        analyzer.layout = name.to_string();
+        Ok(analyzer)
+    }
+
+    /// Bed with a custom channel order, e.g. `["L", "R", "C", "LFE", "Ls", "Rs"]`
+    /// (labels as in the named layouts, matched case-insensitively)
+    pub fn from_labels<S: AsRef<str>>(sample_rate: f32, labels: &[S]) -> Result<Self, AnalysisError> {
+        if labels.is_empty() {
+            return Err(AnalysisError::InvalidLayout { reason: "no channels" });
+        }
+        let channels = labels.iter()
+            .map(|label| Ok(BedChannel { label: label.as_ref().to_string(), weight: channel_weight(label.as_ref())? }))
+            .collect::<Result<Vec<_>, AnalysisError>>()?;
+        if channels.iter().all(|channel| channel.weight == 0.0) {
+            return Err(AnalysisError::InvalidLayout { reason: "no channel contributes to loudness" });
+        }
+        let layout = labels.iter().map(|label| label.as_ref()).collect::<Vec<_>>().join(" ");
+        Ok(BedLoudnessAnalyzer { sample_rate, layout, channels })
+    }
+
+    /// Loudness of interleaved bed PCM; `on_progress` receives the percent
+    /// complete and returns false to cancel
+    pub fn analyze(&self, pcm: &[f32], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<BedLoudnessResult, AnalysisError> {
+        let num_channels = self.channels.len();
+        let min_frames = (MOMENTARY_BLOCK_SIZE as f32 * self.sample_rate / BLOCK_SAMPLE_RATE).ceil() as usize;
+        validate_pcm(pcm, num_channels, min_frames)?;
+        let progress = Progress::new(on_progress, None);
+
+        let off_rate = self.sample_rate > 0.0 && (self.sample_rate - BLOCK_SAMPLE_RATE).abs() >= 0.5;
+        let resampled = off_rate.then(|| Resampler::new(self.sample_rate, BLOCK_SAMPLE_RATE).process(pcm, num_channels));
+        let pcm = resampled.as_deref().unwrap_or(pcm);
+        progress.checkpoint(0.2)?;
+
+        // Weighted, K-weighted energy of every segment, averaged into blocks
+        let segments = self.weighted_energy(pcm);
+        progress.checkpoint(0.8)?;
+        let frames = pcm.len() / num_channels;
+        let blocks = |block_size: usize, hop: usize| absolute_gated(block_energies(&segments, usize::MAX, block_size, hop));
+        let momentary = blocks(MOMENTARY_BLOCK_SIZE, MOMENTARY_HOP);
+        let short_term = blocks(SHORT_TERM_BLOCK_SIZE, SHORT_TERM_HOP);
+        let max = |energies: &[f32]| energies.iter().copied().map(block_loudness).fold(f32::NEG_INFINITY, f32::max);
+        progress.checkpoint(1.0)?;
+
+        Ok(BedLoudnessResult {
+            layout: self.layout.clone(),
+            channels: self.channels.clone(),
+            integrated: integrated_loudness(&momentary),
+            momentary: max(&momentary),
+            short_term: max(&short_term),
+            loudness_range: loudness_range(&short_term),
+            status: loudness_status(frames, momentary.len(), short_term.len()),
+        })
+    }
+
+    // Per-segment sum over channels of weight × K-weighted energy, with one
+    // filter per channel running over the whole programme (the LFE is skipped)
+    fn weighted_energy(&self, pcm: &[f32]) -> Vec<f64> {
+        let num_channels = self.channels.len();
+        let weighted: Vec<(usize, f64)> = self.channels.iter().enumerate()
+            .filter(|(_, channel)| channel.weight != 0.0)
+            .map(|(ch, channel)| (ch, channel.weight as f64))
+            .collect();
+        let segments = map_range(0..weighted.len(), |i| {
+            let mut segments = Vec::new();
+            ChannelEnergy::<f64>::new().push(pcm.iter().skip(weighted[i].0).step_by(num_channels).map(|&sample| sample as f64), &mut segments);
+            segments
+        });
+        let weights: Vec<f64> = weighted.iter().map(|&(_, weight)| weight).collect();
+        sum_channels(&segments, &weights)
+    }
+}
+
+// BS.1770-5 weight of a channel label: surrounds near the horizontal plane
+// 1.41, the LFE excluded, everything else (front, centre, height) 1.0
+fn channel_weight(label: &str) -> Result<f32, AnalysisError> {
+    match label.to_ascii_lowercase().as_str() {
+        "l" | "r" | "c" | "ltf" | "rtf" | "ltm" | "rtm" | "ltr" | "rtr" => Ok(1.0),
+        "ls" | "rs" | "lss" | "rss" => Ok(SURROUND_WEIGHT),
+        // Rear surrounds at ±135°-150° fall outside the weighted arc
+        "lrs" | "rrs" => Ok(1.0),
+        "lfe" => Ok(0.0),
+        _ => Err(AnalysisError::InvalidLayout { reason: "unknown channel label" }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bed(layout: &str, active: &[usize]) -> (BedLoudnessAnalyzer, Vec<f32>) {
+        let meter = BedLoudnessAnalyzer::new(44100.0, layout).unwrap();
+        let channels = meter.num_channels();
+        let pcm = (0..channels * 4 * 44100)
+            .map(|i| if active.contains(&(i % channels)) { 0.25 * ((i / channels) as f32 * 0.0712).sin() } else { 0.0 })
+            .collect();
+        (meter, pcm)
+    }
+
+    #[test]
+    fn surrounds_are_weighted_and_lfe_ignored() {
+        let (meter, front) = bed("5.1", &[0]);
+        let front = meter.analyze(&front, None).unwrap();
+        let surround = meter.analyze(&bed("5.1", &[4]).1, None).unwrap();
+        let both = meter.analyze(&bed("5.1", &[0, 1]).1, None).unwrap();
+        let lfe = meter.analyze(&bed("5.1", &[3]).1, None).unwrap();
+
+        assert!((surround.integrated - front.integrated - 1.49).abs() < 0.01);
+        assert!((both.integrated - front.integrated - 3.01).abs() < 0.01);
+        assert_eq!(lfe.integrated, f32::NEG_INFINITY);
+        assert_eq!(front.channels[3], BedChannel { label: "LFE".to_string(), weight: 0.0 });
+    }
+
+    #[test]
+    fn layouts_and_labels_are_validated() {
+        assert_eq!(BedLoudnessAnalyzer::new(48000.0, "7.1.4").unwrap().num_channels(), 12);
+        assert!(BedLoudnessAnalyzer::new(48000.0, "9.1.6").is_err());
+        assert!(BedLoudnessAnalyzer::from_labels(48000.0, &["L", "R", "Vog"]).is_err());
+        assert!(BedLoudnessAnalyzer::from_labels(48000.0, &["LFE"]).is_err());
+        assert_eq!(BedLoudnessAnalyzer::from_labels(48000.0, &["l", "r"]).unwrap().layout, "l r");
+    }
+}
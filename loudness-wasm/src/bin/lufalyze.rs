@@ -0,0 +1,165 @@
+// Command-line front-end: runs the same analysis as the web app over WAV files
+// and prints the result structures as JSON, for validating the DSP against
+// reference meters and scripting batch jobs.
+//
+//     lufalyze [--rhythm] [--quality PRESET] [--max-duration SECONDS] [--pretty] [-o FILE] <FILE>...
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use loudness_wasm::{decode_wav, AnalysisResult, Analyzer, AnalyzerConfig, Quality};
+use serde::Serialize;
+
+const USAGE: &str = "\
+Usage: lufalyze [OPTIONS] <FILE>...
+
+Analyze WAV files and print the results as JSON.
+
+Options:
+  --rhythm                 Include tempo, beat and groove analysis
+  --quality PRESET         fast, balanced (default) or accurate
+  --max-duration SECONDS   Cap for the windowed stereo/spectral passes (0 = unlimited)
+  --pretty                 Pretty-print the JSON output
+  -o, --output FILE        Write JSON to FILE instead of stdout
+  -h, --help               Show this help";
+
+#[derive(Default)]
+struct Options {
+    files: Vec<PathBuf>,
+    include_rhythm: bool,
+    quality: Quality,
+    max_duration: Option<f32>,
+    pretty: bool,
+    output: Option<PathBuf>,
+}
+
+// One entry per input file; failures are reported in place so a batch keeps going
+#[derive(Serialize)]
+struct FileReport {
+    file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<AnalysisResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.peekable();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rhythm" => options.include_rhythm = true,
+            "--pretty" => options.pretty = true,
+            "--quality" => {
+                let value = args.next().ok_or("--quality needs a value")?;
+                options.quality = Quality::from_name(&value).ok_or_else(|| format!("unknown quality '{}'", value))?;
+            }
+            "--max-duration" => {
+                let value = args.next().ok_or("--max-duration needs a value")?;
+                let seconds = value.parse().map_err(|_| format!("invalid duration '{}'", value))?;
+                options.max_duration = Some(seconds);
+            }
+            "-o" | "--output" => {
+                options.output = Some(args.next().ok_or("--output needs a file name")?.into());
+            }
+            "-h" | "--help" => return Err(String::new()),
+            flag if flag.starts_with('-') && flag.len() > 1 => return Err(format!("unknown option '{}'", flag)),
+            file => options.files.push(file.into()),
+        }
+    }
+
+    if options.files.is_empty() {
+        return Err("no input files".to_string());
+    }
+    Ok(options)
+}
+
+fn analyze_file(path: &Path, options: &Options) -> Result<AnalysisResult, Box<dyn Error>> {
+    let wav = decode_wav(&fs::read(path)?)?;
+
+    let mut config = AnalyzerConfig::new(wav.sample_rate() as f32, wav.channels()).with_quality(options.quality);
+    if let Some(seconds) = options.max_duration {
+        let mut limits = config.limits();
+        limits.set_max_duration(seconds);
+        config = config.with_limits(limits);
+    }
+
+    let mut analyzer = Analyzer::from_config(&config);
+    analyzer.set_include_rhythm(options.include_rhythm);
+
+    Ok(analyzer.analyze(wav.samples(), None)?)
+}
+
+fn run(options: &Options) -> Result<bool, Box<dyn Error>> {
+    let reports: Vec<FileReport> = options.files.iter()
+        .map(|path| {
+            let file = path.display().to_string();
+            match analyze_file(path, options) {
+                Ok(result) => FileReport { file, result: Some(result), error: None },
+                Err(error) => {
+                    eprintln!("lufalyze: {}: {}", file, error);
+                    FileReport { file, result: None, error: Some(error.to_string()) }
+                }
+            }
+        })
+        .collect();
+
+    let mut out: Box<dyn Write> = match &options.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    if options.pretty {
+        serde_json::to_writer_pretty(&mut out, &reports)?;
+    } else {
+        serde_json::to_writer(&mut out, &reports)?;
+    }
+    writeln!(out)?;
+    out.flush()?;
+
+    Ok(reports.iter().all(|report| report.error.is_none()))
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) if message.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("lufalyze: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("lufalyze: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_flags_and_files() {
+        let args = ["--rhythm", "--quality", "fast", "--max-duration", "30", "-o", "out.json", "a.wav", "b.wav"];
+        let options = parse_args(args.iter().map(|arg| arg.to_string())).unwrap();
+        assert!(options.include_rhythm);
+        assert_eq!(options.quality, Quality::Fast);
+        assert_eq!(options.max_duration, Some(30.0));
+        assert_eq!(options.output, Some(PathBuf::from("out.json")));
+        assert_eq!(options.files.len(), 2);
+
+        assert!(parse_args(std::iter::empty()).is_err());
+    }
+}
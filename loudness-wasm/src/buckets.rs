@@ -0,0 +1,337 @@
+// Loudness buckets for game audio: assets are sorted into buckets (dialogue,
+// SFX, music) by patterns in their ids, each bucket measuring its own metric
+// against its own target, so thousands of files can be checked in one pass
+// and the outliers sorted by deviation. Assets shorter than the block their
+// bucket's metric reads (3s for short-term, 400ms otherwise) are padded with
+// silence to one, then read like any other (the SFX default uses the loudest
+// momentary block, since one-shots rarely fill a gated integrated reading).
+//
+//     const buckets = LoudnessBuckets.game_defaults(48000, 2);
+//     buckets.add_bucket("ambience", ["amb_"], "integrated", -30, 3);
+//     const report = buckets.check_batch(buffers, fileNames);
+//     report.assets.filter(asset => !asset.within).forEach(flag);
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::AnalyzerConfig;
+use crate::constants::{MOMENTARY_BLOCK_SIZE, SHORT_TERM_BLOCK_SIZE};
+use crate::error::{validate_pcm, AnalysisError};
+use crate::loudness::{LoudnessAnalyzer, LoudnessResult};
+use crate::parallel::map_range;
+
+/// Reading a bucket is judged on (LUFS)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub enum BucketMetric {
+    Integrated,
+    // Loudest 3s block
+    MaxShortTerm,
+    // Loudest 400ms block
+    MaxMomentary,
+}
+
+impl BucketMetric {
+    pub fn from_name(name: &str) -> Option<BucketMetric> {
+        match name.to_ascii_lowercase().as_str() {
+            "integrated" => Some(BucketMetric::Integrated),
+            "max_short_term" | "short_term" => Some(BucketMetric::MaxShortTerm),
+            "max_momentary" | "momentary" => Some(BucketMetric::MaxMomentary),
+            _ => None,
+        }
+    }
+
+    // Block the metric needs at least one of (frames at the block rate)
+    fn block_size(self) -> usize {
+        match self {
+            BucketMetric::MaxShortTerm => SHORT_TERM_BLOCK_SIZE,
+            BucketMetric::Integrated | BucketMetric::MaxMomentary => MOMENTARY_BLOCK_SIZE,
+        }
+    }
+
+    fn read(self, result: &LoudnessResult) -> f32 {
+        match self {
+            BucketMetric::Integrated => result.integrated,
+            BucketMetric::MaxShortTerm => result.short_term,
+            BucketMetric::MaxMomentary => result.momentary,
+        }
+    }
+}
+
+/// A bucket: assets whose id contains one of `patterns` (ignoring case) are
+/// judged on `metric`, within `tolerance` LU of `target`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoudnessBucket {
+    pub name: String,
+    pub patterns: Vec<String>,
+    pub metric: BucketMetric,
+    pub target: f32,
+    pub tolerance: f32,
+}
+
+/// One asset against its bucket; without a bucket only the integrated
+/// loudness is reported
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct AssetLoudness {
+    pub id: String,
+    pub bucket: Option<String>,
+    pub metric: BucketMetric,
+    pub measured: f32,
+    pub target: Option<f32>,
+    // Measured minus target (LU)
+    pub deviation: Option<f32>,
+    pub within: bool,
+}
+
+/// Totals of one bucket
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct BucketSummary {
+    pub name: String,
+    pub assets: usize,
+    pub within: usize,
+    // Mean and largest (by size, with its sign) deviation; 0 when empty
+    pub mean_deviation: f32,
+    pub worst_deviation: f32,
+}
+
+/// Every asset in input order, with per-bucket totals in bucket order
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct BucketReport {
+    pub assets: Vec<AssetLoudness>,
+    pub buckets: Vec<BucketSummary>,
+    // Assets no bucket matched
+    pub unclassified: usize,
+    pub within_count: usize,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct LoudnessBuckets {
+    loudness: LoudnessAnalyzer,
+    num_channels: usize,
+    buckets: Vec<LoudnessBucket>,
+    default_bucket: Option<usize>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl LoudnessBuckets {
+    // No buckets: add them, or start from `game_defaults`
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        LoudnessBuckets::from_config(&AnalyzerConfig::new(sample_rate, num_channels))
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        LoudnessBuckets {
+            loudness: LoudnessAnalyzer::from_config(config),
+            num_channels: config.num_channels(),
+            buckets: Vec::new(),
+            default_bucket: None,
+        }
+    }
+
+    // Starting points to tune per title: dialogue ("vo_", "dx_", "dialog")
+    // at -24 ±2 LUFS integrated, music ("mus_", "music") at -22 ±2 LUFS
+    // integrated and SFX ("sfx_", "fx_") at -18 ±3 LUFS loudest momentary
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn game_defaults(sample_rate: f32, num_channels: usize) -> Self {
+        let mut buckets = LoudnessBuckets::new(sample_rate, num_channels);
+        buckets.push_bucket("dialogue", &["vo_", "dx_", "dialog"], BucketMetric::Integrated, -24.0, 2.0);
+        buckets.push_bucket("music", &["mus_", "music"], BucketMetric::Integrated, -22.0, 2.0);
+        buckets.push_bucket("sfx", &["sfx_", "fx_"], BucketMetric::MaxMomentary, -18.0, 3.0);
+        buckets
+    }
+
+    // Bucket taking assets no pattern matches (none by default: they are
+    // reported unclassified)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_default_bucket(&mut self, name: &str) -> bool {
+        self.default_bucket = self.buckets.iter().position(|bucket| bucket.name == name);
+        self.default_bucket.is_some()
+    }
+
+    // Name of the bucket an asset id falls in (the first whose patterns match)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn classify(&self, id: &str) -> Option<String> {
+        self.bucket_for(id).map(|bucket| bucket.name.clone())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = add_bucket)]
+    pub fn add_bucket_js(&mut self, name: &str, patterns: Vec<String>, metric: &str, target: f32, tolerance: f32) -> Result<(), JsError> {
+        let metric = BucketMetric::from_name(metric).ok_or(AnalysisError::InvalidSetting { reason: "unknown bucket metric" })?;
+        self.add_bucket(LoudnessBucket { name: name.to_string(), patterns, metric, target, tolerance });
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = check)]
+    pub fn check_js(&self, pcm: &Float32Array, id: &str) -> Result<AssetLoudness, JsError> {
+        Ok(self.check(&pcm.to_vec(), id)?)
+    }
+
+    // `ids` name the buffers in order (buffers without one are named by index)
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = check_batch)]
+    pub fn check_batch_js(&self, buffers: Vec<Float32Array>, ids: Vec<String>) -> Result<BucketReport, JsError> {
+        let assets: Vec<Vec<f32>> = buffers.iter().map(Float32Array::to_vec).collect();
+        let assets: Vec<&[f32]> = assets.iter().map(Vec::as_slice).collect();
+        Ok(self.check_batch(&assets, &ids)?)
+    }
+}
+
+impl LoudnessBuckets {
+    /// Add a bucket after the existing ones; one with the same name is replaced
+    /// in place
+    pub fn add_bucket(&mut self, bucket: LoudnessBucket) {
+        match self.buckets.iter_mut().find(|existing| existing.name == bucket.name) {
+            Some(existing) => *existing = bucket,
+            None => self.buckets.push(bucket),
+        }
+    }
+
+    pub fn buckets(&self) -> &[LoudnessBucket] {
+        &self.buckets
+    }
+
+    /// Measure one interleaved asset against the bucket its id falls in.
+    /// Assets shorter than their metric's block are measured padded with
+    /// silence.
+    pub fn check(&self, pcm: &[f32], id: &str) -> Result<AssetLoudness, AnalysisError> {
+        validate_pcm(pcm, self.num_channels, 1)?;
+        let bucket = self.bucket_for(id);
+        let metric = bucket.map_or(BucketMetric::Integrated, |bucket| bucket.metric);
+        let min_samples = self.loudness.frames_for(metric.block_size()) * self.num_channels;
+        let result = if pcm.len() < min_samples {
+            let mut padded = pcm.to_vec();
+            padded.resize(min_samples, 0.0);
+            self.loudness.analyze(&padded, None)?
+        } else {
+            self.loudness.analyze(pcm, None)?
+        };
+        Ok(match bucket {
+            Some(bucket) => {
+                let measured = bucket.metric.read(&result);
+                let deviation = measured - bucket.target;
+                AssetLoudness {
+                    id: id.to_string(),
+                    bucket: Some(bucket.name.clone()),
+                    metric: bucket.metric,
+                    measured,
+                    target: Some(bucket.target),
+                    deviation: Some(deviation),
+                    within: deviation.abs() <= bucket.tolerance,
+                }
+            }
+            None => AssetLoudness {
+                id: id.to_string(),
+                bucket: None,
+                metric: BucketMetric::Integrated,
+                measured: result.integrated,
+                target: None,
+                deviation: None,
+                within: false,
+            },
+        })
+    }
+
+    /// Check a batch of interleaved assets (in parallel with the `threads`
+    /// feature); every asset is validated up front and the first invalid one
+    /// fails the batch. Assets without an entry in `ids` are named by their index.
+    pub fn check_batch<S: AsRef<str>>(&self, assets: &[&[f32]], ids: &[S]) -> Result<BucketReport, AnalysisError> {
+        for asset in assets {
+            validate_pcm(asset, self.num_channels, 1)?;
+        }
+        let ids: Vec<String> = (0..assets.len()).map(|index| ids.get(index).map_or_else(|| index.to_string(), |id| id.as_ref().to_string())).collect();
+        let assets = map_range(0..assets.len(), |index| self.check(assets[index], &ids[index])).into_iter().collect::<Result<Vec<_>, _>>()?;
+
+        let buckets = self.buckets.iter()
+            .map(|bucket| {
+                let deviations: Vec<f32> = assets.iter()
+                    .filter(|asset| asset.bucket.as_deref() == Some(bucket.name.as_str()))
+                    .filter_map(|asset| asset.deviation)
+                    .collect();
+                BucketSummary {
+                    name: bucket.name.clone(),
+                    assets: deviations.len(),
+                    within: deviations.iter().filter(|deviation| deviation.abs() <= bucket.tolerance).count(),
+                    mean_deviation: if deviations.is_empty() { 0.0 } else { deviations.iter().sum::<f32>() / deviations.len() as f32 },
+                    worst_deviation: deviations.iter().copied().fold(0.0, |worst, deviation| if deviation.abs() > worst.abs() { deviation } else { worst }),
+                }
+            })
+            .collect();
+        Ok(BucketReport {
+            unclassified: assets.iter().filter(|asset| asset.bucket.is_none()).count(),
+            within_count: assets.iter().filter(|asset| asset.within).count(),
+            assets,
+            buckets,
+        })
+    }
+
+    fn push_bucket(&mut self, name: &str, patterns: &[&str], metric: BucketMetric, target: f32, tolerance: f32) {
+        let patterns = patterns.iter().map(|pattern| pattern.to_string()).collect();
+        self.add_bucket(LoudnessBucket { name: name.to_string(), patterns, metric, target, tolerance });
+    }
+
+    fn bucket_for(&self, id: &str) -> Option<&LoudnessBucket> {
+        let id = id.to_lowercase();
+        self.buckets.iter()
+            .find(|bucket| bucket.patterns.iter().any(|pattern| id.contains(&pattern.to_lowercase())))
+            .or_else(|| self.default_bucket.map(|index| &self.buckets[index]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stereo 1kHz tone at `amplitude` (peak) lasting `seconds`
+    fn tone(amplitude: f32, seconds: f32) -> Vec<f32> {
+        (0..(seconds * 44100.0) as usize)
+            .flat_map(|i| [amplitude * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 44100.0).sin(); 2])
+            .collect()
+    }
+
+    #[test]
+    fn assets_are_judged_against_their_bucket() {
+        let mut buckets = LoudnessBuckets::game_defaults(44100.0, 2);
+        assert_eq!(buckets.classify("VO_intro_03.wav").as_deref(), Some("dialogue"));
+        assert_eq!(buckets.classify("amb_forest.wav"), None);
+
+        // A stereo 1kHz tone at -23 dBFS peak reads near -24 LUFS here
+        let line = tone(10f32.powf(-23.0 / 20.0), 4.0);
+        let shot = tone(0.5, 0.2);
+        let ambience = tone(0.05, 4.0);
+        let report = buckets.check_batch(&[&line, &shot, &ambience], &["vo_line", "sfx_hit"]).unwrap();
+        let dialogue = &report.assets[0];
+        assert!(dialogue.within && dialogue.deviation.unwrap().abs() < 1.0, "{:?}", dialogue);
+        // The 200ms one-shot is padded to a momentary block and reads hot
+        let sfx = &report.assets[1];
+        assert_eq!(sfx.metric, BucketMetric::MaxMomentary);
+        assert!(!sfx.within && sfx.deviation.unwrap() > 3.0, "{:?}", sfx);
+        assert_eq!((report.assets[2].id.as_str(), report.assets[2].bucket.as_ref()), ("2", None));
+        assert_eq!((report.unclassified, report.within_count), (1, 1));
+        assert_eq!(report.buckets.iter().map(|bucket| bucket.assets).collect::<Vec<_>>(), vec![1, 0, 1]);
+
+        // A custom bucket for the ambience, judged on its loudest 3s
+        buckets.add_bucket(LoudnessBucket { name: "ambience".into(), patterns: vec!["amb_".into()], metric: BucketMetric::MaxShortTerm, target: -26.0, tolerance: 3.0 });
+        assert!(buckets.set_default_bucket("ambience"));
+        let asset = buckets.check(&ambience, "unnamed").unwrap();
+        assert_eq!(asset.bucket.as_deref(), Some("ambience"));
+        assert!(asset.within, "{:?}", asset);
+        // A 1s loop is padded to one 3s block: a third of the energy, not the floor
+        let short = buckets.check(&tone(0.05, 1.0), "amb_loop").unwrap();
+        let expected = asset.measured + 10.0 * (1.0f32 / 3.0).log10();
+        assert!((short.measured - expected).abs() < 0.5, "{:?} vs {}", short, expected);
+    }
+}
@@ -0,0 +1,84 @@
+// Zero-copy ingestion: JS allocates a sample buffer inside WASM linear memory
+// and decodes straight into it, so very large files are never copied from a
+// Float32Array.
+//
+//     const buffer = alloc_buffer(pcm.length);
+//     buffer.view().set(pcm);        // or decode chunk by chunk into view()
+//     const result = analyzer.analyze_buffer(buffer);
+//     buffer.free();
+//
+// Any allocation inside WASM can grow memory, which detaches existing views of
+// `memory.buffer`; call `view()` again after running an analysis. For large
+// files, `reserve(bytes)` before decoding grows memory once up front instead of
+// in many memory.grow steps mid-analysis.
+
+use js_sys::Float32Array;
+use wasm_bindgen::prelude::*;
+
+const WASM_PAGE_SIZE: usize = 65536;
+
+// Interleaved f32 samples owned by WASM memory
+#[wasm_bindgen]
+pub struct PcmBuffer {
+    data: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl PcmBuffer {
+    // Byte offset of the first sample in `memory.buffer`
+    #[wasm_bindgen(getter)]
+    pub fn ptr(&self) -> usize {
+        self.data.as_ptr() as usize
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[wasm_bindgen]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    // Float32Array over the samples in WASM memory (writes go straight to the buffer)
+    #[wasm_bindgen]
+    pub fn view(&self) -> Float32Array {
+        let memory: js_sys::WebAssembly::Memory = wasm_bindgen::memory().unchecked_into();
+        Float32Array::new_with_byte_offset_and_length(&memory.buffer(), self.ptr() as u32, self.data.len() as u32)
+    }
+}
+
+impl PcmBuffer {
+    // Take ownership of samples already in WASM memory, without copying
+    pub(crate) fn from_samples(data: Vec<f32>) -> Self {
+        PcmBuffer { data }
+    }
+
+    pub fn as_slice(&self) -> &[f32] {
+        &self.data
+    }
+}
+
+// Allocate a zeroed buffer of `len` samples in WASM memory
+#[wasm_bindgen]
+pub fn alloc_buffer(len: usize) -> PcmBuffer {
+    PcmBuffer { data: vec![0.0; len] }
+}
+
+// Make sure `bytes` of free heap are available, growing WASM memory in one
+// step if needed. The space is allocated and released straight away; it stays
+// in the allocator's free list, since WASM memory never shrinks. Returns the
+// total size of WASM memory in bytes.
+#[wasm_bindgen]
+pub fn reserve(bytes: usize) -> usize {
+    let block: Vec<u8> = Vec::with_capacity(bytes);
+    drop(std::hint::black_box(block));
+    memory_bytes()
+}
+
+// Current size of WASM linear memory in bytes
+#[wasm_bindgen]
+pub fn memory_bytes() -> usize {
+    core::arch::wasm32::memory_size(0) * WASM_PAGE_SIZE
+}
@@ -0,0 +1,548 @@
+// Broadcast Wave metadata: delivery files often carry the loudness figures of
+// the mastering tool in the `bext` chunk (EBU Tech 3285 v2) or the iXML
+// `<LOUDNESS>` block. They are read from the file bytes so the app can show
+// them next to its own measurement and flag files whose labels disagree.
+// RF64/BW64 files (over 4GB) keep their real chunk sizes in a `ds64` chunk.
+//
+//     const metadata = read_broadcast_metadata(bytes);
+//     const check = check_broadcast_metadata(bytes, {
+//         integrated: result.loudness.integrated,
+//         max_true_peak: result.technical.true_peak.level,
+//         max_momentary: result.loudness.momentary,
+//         max_short_term: result.loudness.shortTerm,
+//         loudness_range: result.loudness.loudness_range,
+//     });
+//
+// After analysis `bext_loudness_chunk` writes the measured figures back as a
+// bext chunk for the app's WAV writer to splice in, and
+// `adm_loudness_metadata` as an ADM `<loudnessMetadata>` fragment (ITU-R
+// BS.2076) for BW64 `axml` chunks and delivery packages.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::error::AnalysisError;
+
+// Allowed differences between embedded and measured figures: loudness meters
+// agree to within a few tenths of an LU, true-peak meters less closely
+// (oversampling and interpolation differ), loudness range least (EBU Tech
+// 3342 allows meters 1 LU either way)
+pub const LOUDNESS_TOLERANCE: f32 = 0.5;
+pub const TRUE_PEAK_TOLERANCE: f32 = 1.0;
+pub const LOUDNESS_RANGE_TOLERANCE: f32 = 1.0;
+
+// bext v2 field offsets (the loudness fields are 16-bit, value x 100)
+const BEXT_DESCRIPTION: usize = 0;
+const BEXT_ORIGINATOR: usize = 256;
+const BEXT_ORIGINATOR_REFERENCE: usize = 288;
+const BEXT_ORIGINATION_DATE: usize = 320;
+const BEXT_VERSION: usize = 346;
+const BEXT_LOUDNESS: usize = 412;
+const BEXT_LOUDNESS_END: usize = 422;
+// Loudness fields plus 180 reserved bytes; coding history follows
+const BEXT_FIXED_SIZE: usize = 602;
+// bext loudness fields hold this when the value was not measured
+const BEXT_UNSET: i16 = 0x7FFF;
+// RIFF size field value that defers to the ds64 chunk
+const RF64_DEFERRED: u32 = 0xFFFF_FFFF;
+// ADM loudnessMetadata attributes of the figures this library measures
+const ADM_LOUDNESS_METHOD: &str = "ITU-R BS.1770";
+const ADM_LOUDNESS_REC_TYPE: &str = "EBU R128";
+
+/// Where an embedded loudness figure was read from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub enum MetadataSource {
+    Bext,
+    Ixml,
+}
+
+/// Loudness figures from one metadata chunk; null where the chunk leaves a
+/// field unset
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(missing_as_null))]
+pub struct EmbeddedLoudness {
+    pub source: MetadataSource,
+    pub integrated: Option<f32>,
+    pub loudness_range: Option<f32>,
+    pub max_true_peak: Option<f32>,
+    pub max_momentary: Option<f32>,
+    pub max_short_term: Option<f32>,
+}
+
+/// Broadcast metadata of a RIFF, RF64 or BW64 file
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct BroadcastMetadata {
+    // "RIFF", "RF64" or "BW64"
+    pub container: String,
+    // bext version; loudness fields exist from version 2
+    pub bext_version: Option<u16>,
+    pub description: Option<String>,
+    pub originator: Option<String>,
+    pub originator_reference: Option<String>,
+    pub origination_date: Option<String>,
+    pub has_ixml: bool,
+    pub loudness: Vec<EmbeddedLoudness>,
+}
+
+/// The analysis figures the embedded metadata is checked against
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, from_wasm_abi))]
+pub struct MeasuredLoudness {
+    pub integrated: f32,
+    pub max_true_peak: f32,
+    pub max_momentary: f32,
+    pub max_short_term: f32,
+    // LU; null when not measured, and then neither compared nor written
+    #[serde(default)]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub loudness_range: Option<f32>,
+}
+
+/// Metric compared between metadata and measurement
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub enum LoudnessMetric {
+    Integrated,
+    MaxTruePeak,
+    MaxMomentary,
+    MaxShortTerm,
+    LoudnessRange,
+}
+
+/// An embedded figure that differs from the measurement by more than the
+/// metric's tolerance
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct MetadataDiscrepancy {
+    pub source: MetadataSource,
+    pub metric: LoudnessMetric,
+    pub embedded: f32,
+    pub measured: f32,
+    // Embedded minus measured
+    pub difference: f32,
+}
+
+/// Embedded metadata with the figures that disagree with the measurement
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct MetadataCheck {
+    pub metadata: BroadcastMetadata,
+    pub measured: MeasuredLoudness,
+    pub discrepancies: Vec<MetadataDiscrepancy>,
+}
+
+impl MetadataCheck {
+    pub fn consistent(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+/// Read bext and iXML metadata from WAV-family file bytes; files without
+/// either chunk give empty metadata
+pub fn read_broadcast_metadata(bytes: &[u8]) -> Result<BroadcastMetadata, AnalysisError> {
+    let container = container(bytes)?;
+    let mut metadata = BroadcastMetadata {
+        container: container.to_string(),
+        bext_version: None,
+        description: None,
+        originator: None,
+        originator_reference: None,
+        origination_date: None,
+        has_ixml: false,
+        loudness: Vec::new(),
+    };
+    for (id, body) in chunks(bytes) {
+        match &id {
+            b"bext" => read_bext(body, &mut metadata),
+            b"iXML" => {
+                metadata.has_ixml = true;
+                if let Some(loudness) = read_ixml_loudness(&String::from_utf8_lossy(body)) {
+                    metadata.loudness.push(loudness);
+                }
+            }
+            _ => {}
+        }
+    }
+    log::debug!("Read {} metadata: {} loudness blocks", container, metadata.loudness.len());
+    Ok(metadata)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = read_broadcast_metadata)]
+pub fn read_broadcast_metadata_js(bytes: &[u8]) -> Result<BroadcastMetadata, JsError> {
+    Ok(read_broadcast_metadata(bytes)?)
+}
+
+/// Read the file's metadata and compare its loudness figures with a measurement
+pub fn check_broadcast_metadata(bytes: &[u8], measured: MeasuredLoudness) -> Result<MetadataCheck, AnalysisError> {
+    let metadata = read_broadcast_metadata(bytes)?;
+    let discrepancies = metadata.loudness.iter().flat_map(|embedded| discrepancies(embedded, &measured)).collect();
+    Ok(MetadataCheck { metadata, measured, discrepancies })
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = check_broadcast_metadata)]
+pub fn check_broadcast_metadata_js(bytes: &[u8], measured: MeasuredLoudness) -> Result<MetadataCheck, JsError> {
+    Ok(check_broadcast_metadata(bytes, measured)?)
+}
+
+/// A bext chunk (header included, ready to splice in place of the file's
+/// own) carrying the measured loudness. The description, originator, time
+/// reference, UMID and coding history of the bext chunk in `source` are kept;
+/// without one the other fields are left blank. Loudness range is unset
+/// unless measured.
+pub fn bext_loudness_chunk(measured: &MeasuredLoudness, source: Option<&[u8]>) -> Result<Vec<u8>, AnalysisError> {
+    let existing = match source {
+        Some(bytes) => {
+            container(bytes)?;
+            chunks(bytes).into_iter().find(|(id, _)| id == b"bext").map(|(_, body)| body)
+        }
+        None => None,
+    };
+
+    // Fixed part up to the loudness fields, then the loudness fields, the
+    // reserved bytes and any coding history
+    let mut body = vec![0u8; BEXT_FIXED_SIZE];
+    if let Some(existing) = existing {
+        let kept = existing.len().min(BEXT_LOUDNESS);
+        body[..kept].copy_from_slice(&existing[..kept]);
+        body.extend_from_slice(existing.get(BEXT_FIXED_SIZE..).unwrap_or(&[]));
+    }
+    let version = u16::from_le_bytes([body[BEXT_VERSION], body[BEXT_VERSION + 1]]).max(2);
+    body[BEXT_VERSION..BEXT_VERSION + 2].copy_from_slice(&version.to_le_bytes());
+    let fields = [measured.integrated, measured.loudness_range.unwrap_or(f32::NAN), measured.max_true_peak, measured.max_momentary, measured.max_short_term];
+    for (i, value) in fields.into_iter().enumerate() {
+        body[BEXT_LOUDNESS + 2 * i..BEXT_LOUDNESS + 2 * i + 2].copy_from_slice(&bext_value(value).to_le_bytes());
+    }
+
+    let mut chunk = Vec::with_capacity(body.len() + 9);
+    chunk.extend_from_slice(b"bext");
+    chunk.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(&body);
+    if body.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    Ok(chunk)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = bext_loudness_chunk)]
+pub fn bext_loudness_chunk_js(measured: MeasuredLoudness, source: Option<Vec<u8>>) -> Result<Vec<u8>, JsError> {
+    Ok(bext_loudness_chunk(&measured, source.as_deref())?)
+}
+
+/// ADM `<loudnessMetadata>` element carrying the measured loudness, e.g.
+/// `<loudnessMetadata loudnessMethod="ITU-R BS.1770" ...><integratedLoudness>-23.00</integratedLoudness>...`.
+/// Values are in LUFS, LU and dBTP with two decimals; silence (-inf) and
+/// unknown values are left out, as ADM makes every sub-element optional.
+pub fn adm_loudness_metadata(measured: &MeasuredLoudness) -> String {
+    let fields = [
+        ("integratedLoudness", measured.integrated),
+        ("loudnessRange", measured.loudness_range.unwrap_or(f32::NAN)),
+        ("maxTruePeak", measured.max_true_peak),
+        ("maxMomentary", measured.max_momentary),
+        ("maxShortTerm", measured.max_short_term),
+    ];
+    let mut xml = format!("<loudnessMetadata loudnessMethod=\"{}\" loudnessRecType=\"{}\">", ADM_LOUDNESS_METHOD, ADM_LOUDNESS_REC_TYPE);
+    for (tag, value) in fields.into_iter().filter(|(_, value)| value.is_finite()) {
+        xml.push_str(&format!("<{0}>{1:.2}</{0}>", tag, value));
+    }
+    xml.push_str("</loudnessMetadata>");
+    xml
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = adm_loudness_metadata)]
+pub fn adm_loudness_metadata_js(measured: MeasuredLoudness) -> String {
+    adm_loudness_metadata(&measured)
+}
+
+// Hundredths of a unit; silence (-inf) and unknown values are written as unset
+fn bext_value(value: f32) -> i16 {
+    if !value.is_finite() {
+        return BEXT_UNSET;
+    }
+    (value * 100.0).round().clamp(i16::MIN as f32, (BEXT_UNSET - 1) as f32) as i16
+}
+
+fn discrepancies(embedded: &EmbeddedLoudness, measured: &MeasuredLoudness) -> Vec<MetadataDiscrepancy> {
+    [
+        (LoudnessMetric::Integrated, embedded.integrated, measured.integrated, LOUDNESS_TOLERANCE),
+        (LoudnessMetric::MaxTruePeak, embedded.max_true_peak, measured.max_true_peak, TRUE_PEAK_TOLERANCE),
+        (LoudnessMetric::MaxMomentary, embedded.max_momentary, measured.max_momentary, LOUDNESS_TOLERANCE),
+        (LoudnessMetric::MaxShortTerm, embedded.max_short_term, measured.max_short_term, LOUDNESS_TOLERANCE),
+        (LoudnessMetric::LoudnessRange, embedded.loudness_range, measured.loudness_range.unwrap_or(f32::NAN), LOUDNESS_RANGE_TOLERANCE),
+    ]
+    .into_iter()
+    .filter_map(|(metric, embedded_value, measured, tolerance)| {
+        let embedded_value = embedded_value?;
+        // Silent or too-short measurements have nothing to compare against
+        let difference = embedded_value - measured;
+        (measured.is_finite() && difference.abs() > tolerance)
+            .then_some(MetadataDiscrepancy { source: embedded.source, metric, embedded: embedded_value, measured, difference })
+    })
+    .collect()
+}
+
+// "RIFF", "RF64" or "BW64" for WAVE file bytes
+fn container(bytes: &[u8]) -> Result<&'static str, AnalysisError> {
+    let container = match bytes.get(..4) {
+        Some(b"RIFF") => "RIFF",
+        Some(b"RF64") => "RF64",
+        Some(b"BW64") => "BW64",
+        _ => return Err(AnalysisError::InvalidWav { reason: "no RIFF, RF64 or BW64 header" }),
+    };
+    if bytes.get(8..12) != Some(b"WAVE") {
+        return Err(AnalysisError::InvalidWav { reason: "not a WAVE file" });
+    }
+    Ok(container)
+}
+
+// Top-level chunks of a WAVE file in order, with RF64 sizes resolved
+fn chunks(bytes: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut ds64 = Ds64::default();
+    let mut offset = 12;
+    while let Some(header) = bytes.get(offset..offset + 8) {
+        let id: [u8; 4] = header[..4].try_into().unwrap();
+        let declared = u32::from_le_bytes(header[4..].try_into().unwrap());
+        let size = if declared == RF64_DEFERRED { ds64.size_of(&id) } else { declared as u64 };
+        let start = offset + 8;
+        // A truncated final chunk (e.g. a partial upload) is read as far as it goes
+        let end = usize::try_from(size).map_or(bytes.len(), |size| start.saturating_add(size).min(bytes.len()));
+        let body = &bytes[start..end];
+        if &id == b"ds64" {
+            ds64 = Ds64::parse(body);
+        }
+        chunks.push((id, body));
+
+        // Chunks are word-aligned
+        let Some(next) = size.checked_add(size & 1).and_then(|size| usize::try_from(size).ok()).and_then(|size| start.checked_add(size)) else { break };
+        offset = next;
+    }
+    chunks
+}
+
+// 64-bit sizes of an RF64/BW64 file
+#[derive(Default)]
+struct Ds64 {
+    data_size: u64,
+    table: Vec<([u8; 4], u64)>,
+}
+
+impl Ds64 {
+    // riffSize (8), dataSize (8), sampleCount (8), tableLength (4), then
+    // (chunk id, 64-bit size) entries
+    fn parse(body: &[u8]) -> Self {
+        let u64_at = |at: usize| body.get(at..at + 8).map_or(0, |b| u64::from_le_bytes(b.try_into().unwrap()));
+        let entries = body.get(24..28).map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap())) as usize;
+        let table = body.get(28..).unwrap_or(&[])
+            .chunks_exact(12)
+            .take(entries)
+            .map(|entry| (entry[..4].try_into().unwrap(), u64::from_le_bytes(entry[4..].try_into().unwrap())))
+            .collect();
+        Ds64 { data_size: u64_at(8), table }
+    }
+
+    fn size_of(&self, id: &[u8; 4]) -> u64 {
+        if id == b"data" {
+            return self.data_size;
+        }
+        self.table.iter().find(|(entry, _)| entry == id).map_or(RF64_DEFERRED as u64, |&(_, size)| size)
+    }
+}
+
+fn read_bext(body: &[u8], metadata: &mut BroadcastMetadata) {
+    let text = |start: usize, len: usize| -> Option<String> {
+        let field = body.get(start..(start + len).min(body.len()))?;
+        let end = field.iter().position(|&byte| byte == 0).unwrap_or(field.len());
+        let value = String::from_utf8_lossy(&field[..end]).trim().to_string();
+        (!value.is_empty()).then_some(value)
+    };
+    metadata.description = text(BEXT_DESCRIPTION, 256);
+    metadata.originator = text(BEXT_ORIGINATOR, 32);
+    metadata.originator_reference = text(BEXT_ORIGINATOR_REFERENCE, 32);
+    metadata.origination_date = text(BEXT_ORIGINATION_DATE, 10);
+    let version = body.get(BEXT_VERSION..BEXT_VERSION + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    metadata.bext_version = version;
+
+    if version.unwrap_or(0) < 2 {
+        return;
+    }
+    let Some(fields) = body.get(BEXT_LOUDNESS..BEXT_LOUDNESS_END) else { return };
+    let value = |index: usize| {
+        let raw = i16::from_le_bytes([fields[2 * index], fields[2 * index + 1]]);
+        (raw != BEXT_UNSET).then_some(raw as f32 / 100.0)
+    };
+    let loudness = EmbeddedLoudness {
+        source: MetadataSource::Bext,
+        integrated: value(0),
+        loudness_range: value(1),
+        max_true_peak: value(2),
+        max_momentary: value(3),
+        max_short_term: value(4),
+    };
+    // Writers that do not measure loudness leave the fields zeroed
+    if fields.iter().any(|&byte| byte != 0) {
+        metadata.loudness.push(loudness);
+    }
+}
+
+fn read_ixml_loudness(xml: &str) -> Option<EmbeddedLoudness> {
+    let block = element(xml, "LOUDNESS")?;
+    // Values may carry a unit, e.g. "-23.0 LUFS"
+    let value = |tag: &str| element(block, tag)?.split_whitespace().next()?.parse::<f32>().ok();
+    Some(EmbeddedLoudness {
+        source: MetadataSource::Ixml,
+        integrated: value("LOUDNESS_VALUE"),
+        loudness_range: value("LOUDNESS_RANGE"),
+        max_true_peak: value("MAX_TRUE_PEAK_LEVEL"),
+        max_momentary: value("MAX_MOMENTARY_LOUDNESS"),
+        max_short_term: value("MAX_SHORT_TERM_LOUDNESS"),
+    })
+}
+
+// Text between the first `<tag>` and its closing tag
+fn element<'x>(xml: &'x str, tag: &str) -> Option<&'x str> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml[start..start + end].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    fn bext(loudness: [i16; 5]) -> Vec<u8> {
+        let mut body = vec![0u8; BEXT_FIXED_SIZE];
+        body[..11].copy_from_slice(b"Final mix 3");
+        body[BEXT_ORIGINATOR..BEXT_ORIGINATOR + 6].copy_from_slice(b"Studio");
+        body[BEXT_VERSION] = 2;
+        for (i, value) in loudness.iter().enumerate() {
+            body[BEXT_LOUDNESS + 2 * i..BEXT_LOUDNESS + 2 * i + 2].copy_from_slice(&value.to_le_bytes());
+        }
+        body
+    }
+
+    #[test]
+    fn reads_bext_and_ixml_loudness_and_flags_discrepancies() {
+        let ixml = b"<BWFXML><LOUDNESS><LOUDNESS_VALUE>-16.0 LUFS</LOUDNESS_VALUE><MAX_TRUE_PEAK_LEVEL>-1.0</MAX_TRUE_PEAK_LEVEL></LOUDNESS></BWFXML>";
+        let mut bytes = b"RIFF\0\0\0\0WAVE".to_vec();
+        bytes.extend(chunk(b"bext", &bext([-2300, 550, -150, BEXT_UNSET, -2000])));
+        bytes.extend(chunk(b"iXML", ixml));
+        bytes.extend(chunk(b"data", &[0; 8]));
+
+        let metadata = read_broadcast_metadata(&bytes).unwrap();
+        assert_eq!((metadata.container.as_str(), metadata.bext_version, metadata.has_ixml), ("RIFF", Some(2), true));
+        assert_eq!(metadata.description.as_deref(), Some("Final mix 3"));
+        assert_eq!(metadata.originator.as_deref(), Some("Studio"));
+        assert_eq!(metadata.loudness[0], EmbeddedLoudness {
+            source: MetadataSource::Bext,
+            integrated: Some(-23.0),
+            loudness_range: Some(5.5),
+            max_true_peak: Some(-1.5),
+            max_momentary: None,
+            max_short_term: Some(-20.0),
+        });
+        assert_eq!((metadata.loudness[1].integrated, metadata.loudness[1].max_true_peak), (Some(-16.0), Some(-1.0)));
+
+        let mut measured = MeasuredLoudness { integrated: -23.2, max_true_peak: -1.2, max_momentary: -18.0, max_short_term: -20.3, loudness_range: Some(6.2) };
+        let check = check_broadcast_metadata(&bytes, measured).unwrap();
+        assert_eq!(check.discrepancies.len(), 1);
+        assert_eq!((check.discrepancies[0].source, check.discrepancies[0].metric), (MetadataSource::Ixml, LoudnessMetric::Integrated));
+        assert!((check.discrepancies[0].difference - 7.2).abs() < 1e-4);
+
+        // The labelled 5.5 LU range is off by more than a meter's tolerance;
+        // unmeasured, it isn't compared
+        measured.loudness_range = Some(8.0);
+        let check = check_broadcast_metadata(&bytes, measured).unwrap();
+        assert_eq!((check.discrepancies[0].source, check.discrepancies[0].metric), (MetadataSource::Bext, LoudnessMetric::LoudnessRange));
+        assert!((check.discrepancies[0].difference + 2.5).abs() < 1e-4);
+        measured.loudness_range = None;
+        assert_eq!(check_broadcast_metadata(&bytes, measured).unwrap().discrepancies.len(), 1);
+
+        assert!(matches!(read_broadcast_metadata(b"OggS\0\0\0\0"), Err(AnalysisError::InvalidWav { .. })));
+    }
+
+    #[test]
+    fn rf64_sizes_come_from_ds64() {
+        let mut ds64 = vec![0u8; 28];
+        ds64[8..16].copy_from_slice(&8u64.to_le_bytes());
+        let mut bytes = b"RF64\xff\xff\xff\xffWAVE".to_vec();
+        bytes.extend(chunk(b"ds64", &ds64));
+        bytes.extend_from_slice(b"data\xff\xff\xff\xff");
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend(chunk(b"bext", &bext([-1400, 0, -100, -1000, -1200])));
+
+        let metadata = read_broadcast_metadata(&bytes).unwrap();
+        assert_eq!(metadata.container, "RF64");
+        assert_eq!(metadata.loudness[0].integrated, Some(-14.0));
+    }
+
+    #[test]
+    fn generated_chunk_round_trips_and_keeps_existing_fields() {
+        let mut existing = bext([0; 5]);
+        existing[BEXT_VERSION] = 1;
+        existing.extend_from_slice(b"A=PCM,F=48000,W=24\r\n");
+        let mut source = b"RIFF\0\0\0\0WAVE".to_vec();
+        source.extend(chunk(b"bext", &existing));
+
+        let mut measured = MeasuredLoudness { integrated: -23.04, max_true_peak: -1.2, max_momentary: f32::NEG_INFINITY, max_short_term: -19.5, loudness_range: Some(6.3) };
+        let generated = bext_loudness_chunk(&measured, Some(&source)).unwrap();
+        assert_eq!(generated.len() % 2, 0);
+        let mut file = b"RIFF\0\0\0\0WAVE".to_vec();
+        file.extend_from_slice(&generated);
+        file.extend(chunk(b"data", &[0; 4]));
+
+        let metadata = read_broadcast_metadata(&file).unwrap();
+        assert_eq!((metadata.bext_version, metadata.description.as_deref()), (Some(2), Some("Final mix 3")));
+        assert_eq!(metadata.loudness, vec![EmbeddedLoudness {
+            source: MetadataSource::Bext,
+            integrated: Some(-23.04),
+            loudness_range: Some(6.3),
+            max_true_peak: Some(-1.2),
+            max_momentary: None,
+            max_short_term: Some(-19.5),
+        }]);
+        let (_, body) = chunks(&file)[0];
+        assert!(body.ends_with(b"W=24\r\n"));
+
+        measured.loudness_range = None;
+        assert_eq!(bext_loudness_chunk(&measured, None).unwrap().len(), 8 + BEXT_FIXED_SIZE);
+    }
+
+    #[test]
+    fn adm_fragment_holds_measured_figures() {
+        let mut measured = MeasuredLoudness { integrated: -23.004, max_true_peak: -1.2, max_momentary: f32::NEG_INFINITY, max_short_term: -19.5, loudness_range: Some(6.3) };
+        let xml = adm_loudness_metadata(&measured);
+
+        assert!(xml.starts_with("<loudnessMetadata loudnessMethod=\"ITU-R BS.1770\" loudnessRecType=\"EBU R128\">"));
+        assert!(xml.ends_with("</loudnessMetadata>"));
+        assert_eq!(element(&xml, "integratedLoudness"), Some("-23.00"));
+        assert_eq!(element(&xml, "loudnessRange"), Some("6.30"));
+        assert_eq!(element(&xml, "maxTruePeak"), Some("-1.20"));
+        assert_eq!(element(&xml, "maxMomentary"), None);
+        measured.loudness_range = None;
+        assert_eq!(element(&adm_loudness_metadata(&measured), "loudnessRange"), None);
+    }
+}
@@ -0,0 +1,98 @@
+// Session cache of analysis results keyed by a hash of the input PCM, so
+// re-submitting identical content (e.g. re-dropping the same file) returns the
+// prior result instead of running the full analysis again.
+
+use std::sync::Mutex;
+
+// Results kept by default; the least recently used entry is evicted first
+const DEFAULT_CAPACITY: usize = 8;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Fast content hash of PCM: FNV-1a over the 32-bit sample patterns
+pub fn content_hash(samples: &[f32]) -> u64 {
+    fnv1a(samples.iter().map(|sample| sample.to_bits() as u64), samples.len())
+}
+
+/// Content hash of double-precision PCM
+pub fn content_hash_f64(samples: &[f64]) -> u64 {
+    fnv1a(samples.iter().map(|sample| sample.to_bits()), samples.len())
+}
+
+fn fnv1a(words: impl Iterator<Item = u64>, len: usize) -> u64 {
+    let hash = words.fold(FNV_OFFSET, |hash, word| (hash ^ word).wrapping_mul(FNV_PRIME));
+    (hash ^ len as u64).wrapping_mul(FNV_PRIME)
+}
+
+/// Bounded least-recently-used map from content hash to result
+pub struct ResultCache<T> {
+    capacity: usize,
+    // Most recently used last
+    entries: Mutex<Vec<(u64, T)>>,
+}
+
+impl<T> Default for ResultCache<T> {
+    fn default() -> Self {
+        ResultCache { capacity: DEFAULT_CAPACITY, entries: Mutex::new(Vec::new()) }
+    }
+}
+
+impl<T: Clone> ResultCache<T> {
+    /// Change how many results are kept; 0 disables caching
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        let entries = self.entries.get_mut().unwrap();
+        let excess = entries.len().saturating_sub(capacity);
+        entries.drain(..excess);
+    }
+
+    pub fn get(&self, key: u64) -> Option<T> {
+        let mut entries = self.entries.lock().unwrap();
+        let index = entries.iter().position(|(hash, _)| *hash == key)?;
+        let entry = entries.remove(index);
+        let value = entry.1.clone();
+        entries.push(entry);
+        Some(value)
+    }
+
+    pub fn insert(&self, key: u64, value: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|(hash, _)| *hash != key);
+        if entries.len() >= self.capacity {
+            entries.remove(0);
+        }
+        entries.push((key, value));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.get_mut().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = ResultCache::default();
+        cache.set_capacity(2);
+        let keys: Vec<u64> = (0..3).map(|i| content_hash(&[i as f32; 16])).collect();
+        assert_ne!(content_hash(&[0.0; 16]), content_hash(&[0.0; 17]));
+
+        cache.insert(keys[0], 0);
+        cache.insert(keys[1], 1);
+        assert_eq!(cache.get(keys[0]), Some(0));
+        cache.insert(keys[2], 2);
+        assert_eq!(cache.get(keys[1]), None);
+        assert_eq!(cache.get(keys[0]), Some(0));
+
+        cache.set_capacity(0);
+        cache.insert(keys[1], 1);
+        assert_eq!(cache.get(keys[1]), None);
+    }
+}
@@ -0,0 +1,183 @@
+// Monotonic timing for stage timings and benchmarks
+//
+// Anything that needs wall-clock timing goes through `Clock` rather than
+// `js_sys::Date::now()` (millisecond resolution, not monotonic, and missing in
+// native builds): `performance.now()` on the web (windows, workers and Node all
+// expose it on the global object) and `std::time::Instant` natively.
+
+use std::cell::RefCell;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+
+/// Source of monotonic time in milliseconds from an arbitrary origin
+pub trait Clock {
+    fn now_ms(&self) -> f64;
+}
+
+/// `performance.now()` from the JS global object (0 if unavailable)
+#[cfg(target_arch = "wasm32")]
+pub struct PerformanceClock {
+    performance: Option<(js_sys::Object, js_sys::Function)>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl PerformanceClock {
+    pub fn new() -> Self {
+        use wasm_bindgen::JsCast;
+
+        let performance = js_sys::Reflect::get(&js_sys::global(), &"performance".into())
+            .ok()
+            .and_then(|performance| performance.dyn_into::<js_sys::Object>().ok())
+            .and_then(|performance| {
+                let now = js_sys::Reflect::get(&performance, &"now".into()).ok()?;
+                Some((performance, now.dyn_into::<js_sys::Function>().ok()?))
+            });
+        PerformanceClock { performance }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Clock for PerformanceClock {
+    fn now_ms(&self) -> f64 {
+        self.performance.as_ref()
+            .and_then(|(performance, now)| now.call0(performance).ok())
+            .and_then(|ms| ms.as_f64())
+            .unwrap_or(0.0)
+    }
+}
+
+/// `std::time::Instant` relative to when the clock was created
+#[cfg(not(target_arch = "wasm32"))]
+pub struct InstantClock {
+    origin: std::time::Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl InstantClock {
+    pub fn new() -> Self {
+        InstantClock { origin: std::time::Instant::now() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for InstantClock {
+    fn now_ms(&self) -> f64 {
+        self.origin.elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+/// Platform clock: `PerformanceClock` on the web, `InstantClock` natively
+#[cfg(target_arch = "wasm32")]
+pub type PlatformClock = PerformanceClock;
+#[cfg(not(target_arch = "wasm32"))]
+pub type PlatformClock = InstantClock;
+
+/// Times consecutive stages of an analysis
+pub struct Stopwatch<C: Clock = PlatformClock> {
+    clock: C,
+    last: f64,
+}
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        Stopwatch::with_clock(PlatformClock::new())
+    }
+}
+
+impl<C: Clock> Stopwatch<C> {
+    pub fn with_clock(clock: C) -> Self {
+        let last = clock.now_ms();
+        Stopwatch { clock, last }
+    }
+
+    /// Milliseconds since the previous lap (or the start)
+    pub fn lap_ms(&mut self) -> f64 {
+        let now = self.clock.now_ms();
+        let lap = now - self.last;
+        self.last = now;
+        lap
+    }
+}
+
+/// Milliseconds spent in one stage of an analysis
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct StageTiming {
+    pub stage: String,
+    pub ms: f64,
+}
+
+/// Per-stage timings of one analysis, logged at debug level and collected on
+/// request; a trace that does neither never reads the clock
+pub struct Trace {
+    watch: Option<RefCell<Stopwatch>>,
+    collect: bool,
+    stages: RefCell<Vec<StageTiming>>,
+}
+
+impl Trace {
+    pub fn new(collect: bool) -> Self {
+        let enabled = collect || log::log_enabled!(log::Level::Debug);
+        Trace {
+            watch: enabled.then(|| RefCell::new(Stopwatch::start())),
+            collect,
+            stages: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Close the current stage: time since the previous lap is booked to `stage`
+    pub fn lap(&self, stage: &str) {
+        if let Some(watch) = &self.watch {
+            let ms = watch.borrow_mut().lap_ms();
+            log::debug!("{}: {:.1} ms", stage, ms);
+            if self.collect {
+                self.stages.borrow_mut().push(StageTiming { stage: stage.to_string(), ms });
+            }
+        }
+    }
+
+    /// Collected timings, in stage order (None unless collecting)
+    pub fn into_timings(self) -> Option<Vec<StageTiming>> {
+        self.collect.then(|| self.stages.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    struct ManualClock(Cell<f64>);
+
+    impl Clock for ManualClock {
+        fn now_ms(&self) -> f64 {
+            self.0.get()
+        }
+    }
+
+    #[test]
+    fn laps_measure_between_readings() {
+        let mut watch = Stopwatch::with_clock(ManualClock(Cell::new(10.0)));
+        watch.clock.0.set(25.0);
+        assert_eq!(watch.lap_ms(), 15.0);
+        watch.clock.0.set(26.5);
+        assert_eq!(watch.lap_ms(), 1.5);
+        assert!(InstantClock::new().now_ms() >= 0.0);
+    }
+
+    #[test]
+    fn trace_collects_laps_only_on_request() {
+        let trace = Trace::new(true);
+        trace.lap("first");
+        trace.lap("second");
+        let stages: Vec<String> = trace.into_timings().unwrap().into_iter().map(|timing| timing.stage).collect();
+        assert_eq!(stages, ["first", "second"]);
+
+        let trace = Trace::new(false);
+        trace.lap("ignored");
+        assert!(trace.into_timings().is_none());
+    }
+}
@@ -0,0 +1,258 @@
+// Club/PA playback check: big systems sum everything under the sub crossover
+// to mono, run processors that clamp anything over their true-peak ceiling,
+// and subs that strain under long stretches of heavy low end. Stereo bass
+// that cancels when summed vanishes from the dance floor, so the low band's
+// mono loss is measured per one-second window below a configurable crossover,
+// along with the headroom left under the ceiling and runs of sustained
+// low-frequency energy.
+//
+//     const club = new ClubAnalyzer(48000, 2);
+//     club.set_crossover(100);
+//     const report = club.analyze(pcm);
+//     if (!report.ready) report.issues.forEach(issue => mark(issue.times, issue.message));
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use std::ops::Range;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::AnalyzerConfig;
+use crate::error::{validate_pcm, AnalysisError};
+use crate::qc::{energy, ready, relative_db, window_starts, windowed_issue as issue, Severity, WindowedIssue, ISSUE_WINDOW};
+use crate::technical::TechnicalAnalyzer;
+use crate::utils::{amplitude_to_db, Biquad};
+
+// Typical sub crossover (Hz)
+const DEFAULT_CROSSOVER: f32 = 120.0;
+// Low band level (dBFS) under which its mono loss is not judged
+const LOW_BAND_LEVEL: f32 = -40.0;
+// Low band lost when summed to mono (dB): thinner past the warning, falling
+// apart past the error
+const MONO_LOSS_WARNING: f32 = -3.0;
+const MONO_LOSS_ERROR: f32 = -6.0;
+// Low band share of a window's energy (dB) and the run of windows (seconds)
+// above it that counts as sustained
+const SUSTAINED_LOW_SHARE: f32 = -2.0;
+const SUSTAINED_SECONDS: f32 = 8.0;
+// Frames of neighbouring audio each window's true peak is oversampled with,
+// past the interpolation filter's reach
+const TRUE_PEAK_CONTEXT: usize = 128;
+
+/// Club/PA readiness: `ready` when no issue is an error
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct ClubReport {
+    pub ready: bool,
+    pub crossover: f32,
+    pub true_peak: f32,
+    pub true_peak_ceiling: f32,
+    // Ceiling minus true peak (negative when over)
+    pub headroom: f32,
+    // Worst window's low band level summed to mono relative to the stereo
+    // low band (dB; 0 for mono)
+    pub mono_loss: f32,
+    // Low band share of the whole programme's energy (dB)
+    pub low_share: f32,
+    pub issues: Vec<WindowedIssue>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct ClubAnalyzer {
+    sample_rate: f32,
+    num_channels: usize,
+    crossover: f32,
+    true_peak_ceiling: f32,
+    config: AnalyzerConfig,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl ClubAnalyzer {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        ClubAnalyzer::from_config(&AnalyzerConfig::new(sample_rate, num_channels))
+    }
+
+    // Takes its true-peak ceiling from the config
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        ClubAnalyzer {
+            sample_rate: config.sample_rate(),
+            num_channels: config.num_channels(),
+            crossover: DEFAULT_CROSSOVER,
+            true_peak_ceiling: config.true_peak_ceiling(),
+            config: *config,
+        }
+    }
+
+    // Sub crossover (Hz) below which the system runs mono
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_crossover(&mut self, hz: f32) {
+        self.crossover = hz.clamp(20.0, 0.45 * self.sample_rate);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze)]
+    pub fn analyze_js(&self, pcm: &Float32Array) -> Result<ClubReport, JsError> {
+        Ok(self.analyze(&pcm.to_vec())?)
+    }
+}
+
+// Per-window readings
+struct Window {
+    mono_loss: Option<f32>,
+    low_share: f32,
+    true_peak: f32,
+}
+
+impl ClubAnalyzer {
+    /// Check interleaved PCM (mono or stereo) for club/PA playback
+    pub fn analyze(&self, pcm: &[f32]) -> Result<ClubReport, AnalysisError> {
+        let window = (ISSUE_WINDOW * self.sample_rate) as usize;
+        validate_pcm(pcm, self.num_channels, window.max(1))?;
+        let peaks = TechnicalAnalyzer::from_config(&self.config);
+        let (windows, low_share) = self.measure_windows(pcm, window, &peaks);
+        // The windows plus whatever trails the last whole one
+        let frames = pcm.len() / self.num_channels;
+        let tail = (!frames.is_multiple_of(window)).then(|| self.true_peak_between(pcm, frames - frames % window..frames, &peaks));
+        let true_peak = windows.iter().map(|reading| reading.true_peak).chain(tail).fold(f32::NEG_INFINITY, f32::max);
+        let starts = |flagged: &dyn Fn(&Window) -> bool| window_starts(windows.iter().map(flagged));
+
+        let mut issues = Vec::new();
+        let collapsing = starts(&|reading| reading.mono_loss.is_some_and(|loss| loss < MONO_LOSS_ERROR));
+        let thinning = starts(&|reading| reading.mono_loss.is_some_and(|loss| (MONO_LOSS_ERROR..MONO_LOSS_WARNING).contains(&loss)));
+        if !collapsing.is_empty() {
+            issues.push(issue("mono_low_end", Severity::Error, format!("Low end below {:.0} Hz cancels when summed to mono; it will fall apart on mono subs", self.crossover), collapsing));
+        }
+        if !thinning.is_empty() {
+            issues.push(issue("mono_low_end_loss", Severity::Warning, format!("Low end below {:.0} Hz loses over {:.0} dB summed to mono; narrow the bass", self.crossover, -MONO_LOSS_WARNING), thinning));
+        }
+        let over = starts(&|reading| reading.true_peak > self.true_peak_ceiling);
+        if true_peak > self.true_peak_ceiling {
+            issues.push(issue("headroom", Severity::Error, format!("True peak {:.1} dBTP is over the {:.1} dBTP ceiling; the PA limiter will clamp it", true_peak, self.true_peak_ceiling), over));
+        }
+        let sustained = sustained_runs(&windows);
+        if !sustained.is_empty() {
+            issues.push(issue("sustained_low_end", Severity::Warning, format!("Low end below {:.0} Hz carries most of the energy for over {:.0} s at a time; expect the subs to compress", self.crossover, SUSTAINED_SECONDS), sustained));
+        }
+
+        Ok(ClubReport {
+            ready: ready(&issues),
+            crossover: self.crossover,
+            true_peak,
+            true_peak_ceiling: self.true_peak_ceiling,
+            headroom: self.true_peak_ceiling - true_peak,
+            mono_loss: windows.iter().filter_map(|reading| reading.mono_loss).fold(0.0, f32::min),
+            low_share,
+            issues,
+        })
+    }
+
+    // Readings of every whole window, with the low band share of the whole
+    // programme; the mono loss needs two channels and a low band above
+    // `LOW_BAND_LEVEL`
+    fn measure_windows(&self, pcm: &[f32], window: usize, peaks: &TechnicalAnalyzer) -> (Vec<Window>, f32) {
+        let channels = self.num_channels;
+        let q = std::f32::consts::FRAC_1_SQRT_2;
+        let low_band = |ch: usize| -> Vec<f32> {
+            let mut stages = [Biquad::low_pass(self.sample_rate, self.crossover, q); 2];
+            pcm.iter().skip(ch).step_by(channels).map(|&sample| stages.iter_mut().fold(sample, |x, stage| stage.process(x))).collect()
+        };
+        let lows: Vec<Vec<f32>> = (0..channels).map(low_band).collect();
+
+        let (mut low_total, mut total) = (0.0, 0.0);
+        let windows = (0..pcm.len() / channels / window.max(1))
+            .map(|index| {
+                let span = index * window..(index + 1) * window;
+                let full = energy(&pcm[span.start * channels..span.end * channels]);
+                let low: f64 = lows.iter().map(|band| energy(&band[span.clone()])).sum();
+                (low_total, total) = (low_total + low, total + full);
+                let mono_loss = (channels >= 2).then(|| {
+                    let (left, right) = (&lows[0][span.clone()], &lows[1][span.clone()]);
+                    let sum: f64 = left.iter().zip(right).map(|(&l, &r)| (l as f64 + r as f64).powi(2)).sum();
+                    let stereo = energy(left) + energy(right);
+                    let level = amplitude_to_db((stereo / (2 * window) as f64).sqrt() as f32);
+                    // Equal in-phase channels sum to twice the stereo energy
+                    (level > LOW_BAND_LEVEL).then(|| relative_db(sum, 2.0 * stereo))
+                }).flatten();
+                Window {
+                    mono_loss,
+                    low_share: relative_db(low, full),
+                    true_peak: self.true_peak_between(pcm, span, peaks),
+                }
+            })
+            .collect();
+        (windows, relative_db(low_total, total))
+    }
+
+    // True peak (dBTP) of a span of frames, oversampled with some context
+    // either side so the span's edges interpolate as in the whole signal
+    fn true_peak_between(&self, pcm: &[f32], frames: Range<usize>, peaks: &TechnicalAnalyzer) -> f32 {
+        let channels = self.num_channels;
+        let start = frames.start.saturating_sub(TRUE_PEAK_CONTEXT);
+        let end = (frames.end + TRUE_PEAK_CONTEXT).min(pcm.len() / channels);
+        peaks.calculate_true_peak(&pcm[start * channels..end * channels]).0
+    }
+}
+
+// Start times of the windows in runs of at least `SUSTAINED_SECONDS` whose
+// low band share exceeds `SUSTAINED_LOW_SHARE`
+fn sustained_runs(windows: &[Window]) -> Vec<f32> {
+    let min_run = (SUSTAINED_SECONDS / ISSUE_WINDOW).ceil() as usize;
+    let mut times = Vec::new();
+    let mut run_start = None;
+    for index in 0..=windows.len() {
+        let heavy = windows.get(index).is_some_and(|reading| reading.low_share > SUSTAINED_LOW_SHARE);
+        match (heavy, run_start) {
+            (true, None) => run_start = Some(index),
+            (false, Some(start)) => {
+                if index - start >= min_run {
+                    times.extend((start..index).map(|window| window as f32 * ISSUE_WINDOW));
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    times
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn flags_low_end_that_falls_apart_in_mono() {
+        // 10 s of stereo at 44.1kHz: a -20 dBFS 1kHz tone with 50Hz bass,
+        // antiphase for the first two seconds and in phase and heavy after
+        let sample_rate = 44100.0;
+        let pcm: Vec<f32> = (0..10 * 44100usize)
+            .flat_map(|i| {
+                let t = i as f32 / sample_rate;
+                let tone = 0.1 * (2.0 * PI * 1000.0 * t).sin();
+                let bass = (2.0 * PI * 50.0 * t).sin();
+                if i < 2 * 44100 { [tone + 0.05 * bass, tone - 0.05 * bass] } else { [tone + 0.5 * bass; 2] }
+            })
+            .collect();
+
+        let mut club = ClubAnalyzer::new(sample_rate, 2);
+        club.set_crossover(100.0);
+        let report = club.analyze(&pcm).unwrap();
+        let times = |check: &str| report.issues.iter().find(|issue| issue.check == check).map(|issue| issue.times.clone());
+        assert_eq!(times("mono_low_end"), Some(vec![0.0, 1.0]));
+        assert_eq!(times("sustained_low_end"), Some((2..10).map(|second| second as f32).collect()));
+        assert_eq!(times("headroom"), None);
+        assert!(!report.ready && report.mono_loss < -20.0);
+        assert!((report.headroom - (-1.0 - report.true_peak)).abs() < 1e-6);
+
+        // Driven over the ceiling
+        let hot: Vec<f32> = pcm.iter().map(|&sample| 2.0 * sample).collect();
+        let report = club.analyze(&hot).unwrap();
+        assert!(report.headroom < 0.0 && !report.ready);
+        assert!(report.issues.iter().any(|issue| issue.check == "headroom" && issue.times.first() == Some(&2.0)));
+    }
+}
@@ -0,0 +1,81 @@
+// Colormaps for rendering spectrograms straight into canvas pixels: each map
+// is a handful of sRGB stops from matplotlib, linearly interpolated.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+type Stops = [[u8; 3]; 9];
+
+// Stops at 0, 1/8, ..., 1
+const VIRIDIS: Stops = [
+    [68, 1, 84], [71, 44, 122], [59, 81, 139], [44, 113, 142], [33, 144, 141],
+    [39, 173, 129], [92, 200, 99], [170, 220, 50], [253, 231, 37],
+];
+const MAGMA: Stops = [
+    [0, 0, 4], [28, 16, 68], [79, 18, 123], [129, 37, 129], [181, 54, 122],
+    [229, 80, 100], [251, 135, 97], [254, 194, 135], [252, 253, 191],
+];
+const INFERNO: Stops = [
+    [0, 0, 4], [31, 12, 72], [85, 15, 109], [136, 34, 106], [186, 54, 85],
+    [227, 89, 51], [249, 140, 10], [249, 201, 50], [252, 255, 164],
+];
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    Magma,
+    Inferno,
+    Grayscale,
+}
+
+impl Colormap {
+    /// Colormap by name ("viridis", "magma", "inferno", "grayscale")
+    pub fn from_name(name: &str) -> Option<Colormap> {
+        match name.to_ascii_lowercase().as_str() {
+            "viridis" => Some(Colormap::Viridis),
+            "magma" => Some(Colormap::Magma),
+            "inferno" => Some(Colormap::Inferno),
+            "grayscale" | "greyscale" => Some(Colormap::Grayscale),
+            _ => None,
+        }
+    }
+
+    /// Opaque RGBA colour of `position` (0..1, clamped; NaN maps to 0)
+    pub fn rgba(self, position: f32) -> [u8; 4] {
+        let position = if position.is_nan() { 0.0 } else { position.clamp(0.0, 1.0) };
+        let stops = match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Magma => &MAGMA,
+            Colormap::Inferno => &INFERNO,
+            Colormap::Grayscale => {
+                let level = (position * 255.0).round() as u8;
+                return [level, level, level, 255];
+            }
+        };
+        let scaled = position * (stops.len() - 1) as f32;
+        let index = (scaled as usize).min(stops.len() - 2);
+        let fraction = scaled - index as f32;
+        let channel = |c: usize| {
+            let (from, to) = (stops[index][c] as f32, stops[index + 1][c] as f32);
+            (from + (to - from) * fraction).round() as u8
+        };
+        [channel(0), channel(1), channel(2), 255]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_span_their_stops() {
+        assert_eq!(Colormap::Viridis.rgba(0.0), [68, 1, 84, 255]);
+        assert_eq!(Colormap::Viridis.rgba(2.0), [253, 231, 37, 255]);
+        assert_eq!(Colormap::Magma.rgba(0.0625), [14, 8, 36, 255]);
+        assert_eq!(Colormap::Grayscale.rgba(0.5), [128, 128, 128, 255]);
+        assert_eq!(Colormap::Inferno.rgba(f32::NAN), [0, 0, 4, 255]);
+        assert_eq!(Colormap::from_name("Greyscale"), Some(Colormap::Grayscale));
+    }
+}
@@ -0,0 +1,223 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use crate::error::AnalysisError;
+use crate::limits::{AnalysisLimits, Quality};
+use crate::utils::DbScale;
+use crate::window::Window;
+
+// Defaults for the thresholds previously hard-coded in the analyzers
+const DEFAULT_TARGET_LOUDNESS: f32 = -14.0;      // LUFS (streaming platforms)
+const DEFAULT_CLIP_THRESHOLD: f32 = 0.99;        // Linear sample magnitude
+pub const DEFAULT_SILENCE_THRESHOLD: f32 = -60.0;    // dBFS
+const DEFAULT_TRUE_PEAK_CEILING: f32 = -1.0;     // dBTP (EBU R128 broadcast)
+
+// Settings shared by every analyzer constructor: input format, quality
+// preset, limits, loudness target, detection thresholds and spectral window. Analyzers copy
+// what they need, so one config can build any number of them.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnalyzerConfig {
+    sample_rate: f32,
+    num_channels: usize,
+    quality: Quality,
+    limits: AnalysisLimits,
+    target_loudness: f32,
+    clip_threshold: f32,
+    silence_threshold: f32,
+    true_peak_ceiling: f32,
+    window: Window,
+    db_floor: f32,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl AnalyzerConfig {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        AnalyzerConfig {
+            sample_rate,
+            num_channels,
+            quality: Quality::default(),
+            limits: AnalysisLimits::default(),
+            target_loudness: DEFAULT_TARGET_LOUDNESS,
+            clip_threshold: DEFAULT_CLIP_THRESHOLD,
+            silence_threshold: DEFAULT_SILENCE_THRESHOLD,
+            true_peak_ceiling: DEFAULT_TRUE_PEAK_CEILING,
+            window: Window::default(),
+            db_floor: f32::NEG_INFINITY,
+        }
+    }
+
+    // Speed/precision preset; also replaces the limits with the preset's
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_quality(&mut self, quality: Quality) {
+        self.quality = quality;
+        self.limits = AnalysisLimits::for_quality(quality);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_limits(&mut self, limits: &AnalysisLimits) {
+        self.limits = *limits;
+    }
+
+    // Integrated loudness (LUFS) the mastering score is judged against
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_target_loudness(&mut self, lufs: f32) {
+        self.target_loudness = lufs;
+    }
+
+    // Linear sample magnitude counted as clipped
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_clip_threshold(&mut self, threshold: f32) {
+        self.clip_threshold = threshold.clamp(0.0, 1.0);
+    }
+
+    // Level (dBFS) below which audio counts as silence
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_silence_threshold(&mut self, db: f32) {
+        self.silence_threshold = db;
+    }
+
+    // True peak (dBTP) at or below which broadcast compliance is reported
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_true_peak_ceiling(&mut self, dbtp: f32) {
+        self.true_peak_ceiling = dbtp;
+    }
+
+    // Lowest level (dB) reported for peak and RMS figures, so silence reads
+    // as a number rather than -Infinity; unset by default
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_db_floor(&mut self, db: f32) {
+        self.db_floor = db;
+    }
+
+    // Window of the spectral (STFT) analyses by name: "hann" (default),
+    // "hamming", "blackman-harris", "flat-top", "kaiser" or "kaiser:<beta>"
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = set_window)]
+    pub fn set_window_js(&mut self, name: &str) -> Result<(), JsError> {
+        let window = Window::from_name(name).ok_or(AnalysisError::InvalidSetting { reason: "unknown window name" })?;
+        self.set_window(window);
+        Ok(())
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn limits(&self) -> AnalysisLimits {
+        self.limits
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn target_loudness(&self) -> f32 {
+        self.target_loudness
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn clip_threshold(&self) -> f32 {
+        self.clip_threshold
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn silence_threshold(&self) -> f32 {
+        self.silence_threshold
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn true_peak_ceiling(&self) -> f32 {
+        self.true_peak_ceiling
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn db_floor(&self) -> f32 {
+        self.db_floor
+    }
+}
+
+// Chainable builder methods for native callers
+impl AnalyzerConfig {
+    pub fn set_window(&mut self, window: Window) {
+        self.window = window;
+    }
+
+    pub fn window(&self) -> Window {
+        self.window
+    }
+
+    /// dBFS scale with the configured floor
+    pub fn db_scale(&self) -> DbScale {
+        DbScale::default().with_floor(self.db_floor)
+    }
+
+    pub fn with_window(mut self, window: Window) -> Self {
+        self.set_window(window);
+        self
+    }
+
+    pub fn with_quality(mut self, quality: Quality) -> Self {
+        self.set_quality(quality);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: AnalysisLimits) -> Self {
+        self.set_limits(&limits);
+        self
+    }
+
+    pub fn with_target_loudness(mut self, lufs: f32) -> Self {
+        self.set_target_loudness(lufs);
+        self
+    }
+
+    pub fn with_clip_threshold(mut self, threshold: f32) -> Self {
+        self.set_clip_threshold(threshold);
+        self
+    }
+
+    pub fn with_silence_threshold(mut self, db: f32) -> Self {
+        self.set_silence_threshold(db);
+        self
+    }
+
+    pub fn with_true_peak_ceiling(mut self, dbtp: f32) -> Self {
+        self.set_true_peak_ceiling(dbtp);
+        self
+    }
+
+    pub fn with_db_floor(mut self, db: f32) -> Self {
+        self.set_db_floor(db);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_applies_quality_limits() {
+        let config = AnalyzerConfig::new(48000.0, 2)
+            .with_quality(Quality::Accurate)
+            .with_target_loudness(-23.0)
+            .with_window(Window::FlatTop);
+        assert_eq!(config.limits(), AnalysisLimits::for_quality(Quality::Accurate));
+        assert_eq!(config.target_loudness(), -23.0);
+        assert_eq!(config.clip_threshold(), DEFAULT_CLIP_THRESHOLD);
+        assert_eq!((config.window(), AnalyzerConfig::new(48000.0, 2).window()), (Window::FlatTop, Window::Hann));
+        assert_eq!(config.db_scale().to_db(0.0), f32::NEG_INFINITY);
+        assert_eq!(config.with_db_floor(-150.0).db_scale().to_db(0.0), -150.0);
+    }
+}
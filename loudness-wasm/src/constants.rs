@@ -2,12 +2,34 @@
 pub const ABSOLUTE_GATE: f32 = -70.0;         // Absolute gate threshold in LUFS
 pub const RELATIVE_GATE: f32 = -10.0;         // Relative gate threshold in LUFS
 
+// Constants for EBU Tech 3342 (loudness range)
+pub const LRA_RELATIVE_GATE: f32 = -20.0;     // Relative gate for short-term blocks in LU
+#[cfg(feature = "loudness")]
+pub const LRA_LOW_PERCENTILE: f32 = 0.10;     // Lower end of the range
+#[cfg(feature = "loudness")]
+pub const LRA_HIGH_PERCENTILE: f32 = 0.95;    // Upper end of the range
+
 // Constants for ITU-R BS.1770-4
+#[cfg(feature = "loudness")]
 pub const MOMENTARY_BLOCK_SIZE: usize = 17640;  // 400ms at 44.1kHz
+#[cfg(feature = "loudness")]
 pub const MOMENTARY_HOP: usize = 4410;          // 100ms hop
+#[cfg(feature = "loudness")]
 pub const SHORT_TERM_BLOCK_SIZE: usize = 132300; // 3s at 44.1kHz
+#[cfg(feature = "loudness")]
 pub const SHORT_TERM_HOP: usize = 13230;        // 300ms hop
+#[cfg(feature = "loudness")]
+pub const BLOCK_SAMPLE_RATE: f32 = 44100.0;     // Rate the block sizes above assume
 
-// K-weighting filter coefficients for 44.1kHz
-pub const K_B: [f32; 3] = [1.5351249, -2.6916962, 1.1983928];
-pub const K_A: [f32; 3] = [1.0, -1.6906593, 0.73248076]; 
\ No newline at end of file
+// Frequency balance bands in Hz: sub-bass, bass, low-mids, mids, upper-mids,
+// presence, brilliance
+#[cfg(feature = "technical")]
+pub const FREQUENCY_BANDS: [(f32, f32); 7] = [
+    (20.0, 60.0),
+    (60.0, 250.0),
+    (250.0, 500.0),
+    (500.0, 2000.0),
+    (2000.0, 5000.0),
+    (5000.0, 8000.0),
+    (8000.0, 20000.0),
+];
@@ -0,0 +1,334 @@
+// Compressed-format ingestion: MP3, AAC, Ogg Vorbis and Opus, FLAC, AIFF,
+// ADPCM, A-law and µ-law WAV, and WebM/Matroska Vorbis or Opus uploads decode
+// inside the module through symphonia, at the stream's own sample rate (48 kHz
+// for Opus, whose decoder lives in `opus`), with the container and codec
+// reported next to the samples. PCM WAV bytes take the `decode_wav` path.
+// Formats without a decoder here fail as `UnsupportedFormat`, see `formats`.
+//
+//     const audio = decode_audio(new Uint8Array(await file.arrayBuffer()));
+//     const analyzer = new Analyzer(audio.sample_rate, audio.channels);
+//     const buffer = audio.into_buffer();
+//     const result = analyzer.analyze_buffer(buffer);
+//     buffer.free();
+//
+//     // Or in one step, with the container and codec on the result
+//     const result = analyzer.analyze_file(bytes);  // result.source.codec
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use serde::Serialize;
+use std::io::{Cursor, ErrorKind};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CodecRegistry, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+#[cfg(target_arch = "wasm32")]
+use crate::buffer::PcmBuffer;
+use crate::error::AnalysisError;
+use crate::formats::{compressed_format, unsupported_format};
+use crate::opus::OpusDecoder;
+use crate::wav::{decode_wav, WavFile};
+
+/// Container, codec and format of a decoded file
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct SourceInfo {
+    pub container: String,
+    pub codec: String,
+    pub sample_rate: u32,
+    pub channels: usize,
+    // Bit depth of lossless sources; null for lossy codecs
+    pub bits_per_sample: Option<u16>,
+    pub frames: usize,
+    pub duration: f32,
+}
+
+/// Decoded file: interleaved samples in -1..1 with their source description
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct AudioFile {
+    info: SourceInfo,
+    samples: Vec<f32>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl AudioFile {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn sample_rate(&self) -> u32 {
+        self.info.sample_rate
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn channels(&self) -> usize {
+        self.info.channels
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(getter, js_name = info)]
+    pub fn info_js(&self) -> SourceInfo {
+        self.info.clone()
+    }
+
+    // Copy of the interleaved samples
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = samples)]
+    pub fn samples_js(&self) -> Float32Array {
+        Float32Array::from(self.samples.as_slice())
+    }
+
+    // Hand the samples to the analyzers' buffer API without copying; the
+    // AudioFile is consumed
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen]
+    pub fn into_buffer(self) -> PcmBuffer {
+        PcmBuffer::from_samples(self.samples)
+    }
+}
+
+impl AudioFile {
+    pub fn info(&self) -> &SourceInfo {
+        &self.info
+    }
+
+    /// Interleaved samples in -1..1
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn into_samples(self) -> Vec<f32> {
+        self.samples
+    }
+}
+
+impl From<WavFile> for AudioFile {
+    fn from(wav: WavFile) -> Self {
+        let codec = match (wav.is_float(), wav.bits_per_sample()) {
+            (true, bits) => format!("pcm_f{}le", bits),
+            (false, 8) => "pcm_u8".to_string(),
+            (false, bits) => format!("pcm_s{}le", bits),
+        };
+        let info = SourceInfo {
+            container: "wav".to_string(),
+            codec,
+            sample_rate: wav.sample_rate(),
+            channels: wav.channels(),
+            bits_per_sample: Some(wav.bits_per_sample()),
+            frames: wav.frames(),
+            duration: wav.duration(),
+        };
+        AudioFile { info, samples: wav.into_samples() }
+    }
+}
+
+/// Decode an uploaded file of any supported format (PCM, ADPCM, A-law or µ-law
+/// WAV, AIFF, MP3, AAC in ADTS or MP4/M4A, Ogg Vorbis or Opus, FLAC, Vorbis or
+/// Opus in WebM/Matroska); the first audio track is decoded
+pub fn decode_audio(bytes: &[u8]) -> Result<AudioFile, AnalysisError> {
+    if let Some(error) = unsupported_format(bytes) {
+        return Err(error);
+    }
+    let container = container_name(bytes);
+    if container == "wav" && compressed_format(bytes).is_none() {
+        return Ok(decode_wav(bytes)?.into());
+    }
+
+    let stream = MediaSourceStream::new(Box::new(Cursor::new(bytes.to_vec())), Default::default());
+    let probed = symphonia::default::get_probe()
+        .format(&Hint::new(), stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|error| match (container, error) {
+            ("unknown", DecodeError::Unsupported(_)) => AnalysisError::UnsupportedFormat {
+                format: "unknown",
+                reason: "no known audio signature",
+                action: "check the file is audio, then convert to WAV, FLAC or MP3",
+            },
+            (_, error) => invalid_audio(error),
+        })?;
+    let mut format = probed.format;
+    let track = format.tracks().iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(AnalysisError::InvalidAudio { reason: "no audio track" })?;
+    let (track_id, params) = (track.id, track.codec_params.clone());
+
+    let mut codecs = CodecRegistry::new();
+    symphonia::default::register_enabled_codecs(&mut codecs);
+    codecs.register_all::<OpusDecoder>();
+    let codec = codecs.get_codec(params.codec).map_or("unknown", |descriptor| descriptor.short_name);
+    let mut decoder = codecs.make(&params, &DecoderOptions::default()).map_err(invalid_audio)?;
+
+    let mut samples = Vec::new();
+    let (mut sample_rate, mut channels) = (params.sample_rate.unwrap_or(0), params.channels.map_or(0, |c| c.count()));
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(error)) if error.kind() == ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(invalid_audio(error)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                (sample_rate, channels) = (spec.rate, spec.channels.count());
+                let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                buffer.copy_interleaved_ref(decoded);
+                samples.extend_from_slice(buffer.samples());
+            }
+            // Damaged frames are skipped, as players do
+            Err(DecodeError::DecodeError(reason)) => log::warn!("Skipped undecodable {} packet: {}", codec, reason),
+            Err(error) => return Err(invalid_audio(error)),
+        }
+    }
+
+    if samples.is_empty() || channels == 0 || sample_rate == 0 {
+        return Err(AnalysisError::InvalidAudio { reason: "no decodable audio frames" });
+    }
+    log::debug!("Decoded {} samples of {} in {} at {} Hz", samples.len(), codec, container, sample_rate);
+
+    let frames = samples.len() / channels;
+    let info = SourceInfo {
+        container: container.to_string(),
+        codec: codec.to_string(),
+        sample_rate,
+        channels,
+        bits_per_sample: params.bits_per_sample.map(|bits| bits as u16),
+        frames,
+        duration: frames as f32 / sample_rate as f32,
+    };
+    Ok(AudioFile { info, samples })
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = decode_audio)]
+pub fn decode_audio_js(bytes: &[u8]) -> Result<AudioFile, JsError> {
+    Ok(decode_audio(bytes)?)
+}
+
+// Container from the leading magic bytes (symphonia's probe doesn't name it)
+fn container_name(bytes: &[u8]) -> &'static str {
+    match bytes {
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => "wav",
+        [b'O', b'g', b'g', b'S', ..] => "ogg",
+        [b'f', b'L', b'a', b'C', ..] => "flac",
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => "mp4",
+        [b'I', b'D', b'3', ..] => "mp3",
+        [b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', b'F' | b'C', ..] => "aiff",
+        // EBML: WebM names its doc type in the first few dozen bytes
+        [0x1A, 0x45, 0xDF, 0xA3, ..] if bytes.windows(4).take(64).any(|window| window == b"webm") => "webm",
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => "matroska",
+        // ADTS frames have a 12-bit sync word and layer 0; MPEG audio an 11-bit one
+        [0xFF, second, ..] if second & 0xF6 == 0xF0 => "adts",
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 => "mp3",
+        _ => "unknown",
+    }
+}
+
+fn invalid_audio(error: DecodeError) -> AnalysisError {
+    let reason = match error {
+        DecodeError::DecodeError(reason) | DecodeError::Unsupported(reason) | DecodeError::LimitError(reason) => reason,
+        DecodeError::IoError(_) => "truncated data",
+        DecodeError::SeekError(_) => "unseekable stream",
+        DecodeError::ResetRequired => "stream format changed mid-file",
+    };
+    AnalysisError::InvalidAudio { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_containers_and_routes_wav() {
+        assert_eq!(container_name(b"OggS\0\x02"), "ogg");
+        assert_eq!(container_name(b"\0\0\0\x20ftypM4A "), "mp4");
+        assert_eq!(container_name(&[0xFF, 0xFB, 0x90, 0x00]), "mp3");
+        assert_eq!(container_name(&[0xFF, 0xF1, 0x50, 0x80]), "adts");
+
+        let spec = hound::WavSpec { channels: 1, sample_rate: 96000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for sample in [0i16, 16384, -32768] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let audio = decode_audio(bytes.get_ref()).unwrap();
+        assert_eq!((audio.info().container.as_str(), audio.info().codec.as_str()), ("wav", "pcm_s16le"));
+        assert_eq!((audio.sample_rate(), audio.info().bits_per_sample), (96000, Some(16)));
+        assert_eq!(audio.samples(), &[0.0, 0.5, -1.0]);
+
+        assert!(matches!(decode_audio(b"definitely not audio"), Err(AnalysisError::UnsupportedFormat { format: "unknown", .. })));
+        assert!(matches!(decode_audio(b"DSD \x1c\0\0\0"), Err(AnalysisError::UnsupportedFormat { format: "DSD (DSF)", .. })));
+    }
+
+    // EBML element with an 8-byte size
+    fn element(id: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.push(0x01);
+        bytes.extend_from_slice(&(body.len() as u64).to_be_bytes()[1..]);
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    #[test]
+    fn decodes_webm_opus() {
+        // MediaRecorder-style file: one mono Opus track, then a cluster of four
+        // 20 ms CELT packets of a half-scale 1 kHz sine (libopus, 8 kbit/s)
+        let packets = [
+            "989fe1530080fca47752490e46189e01ff0f2bb1",
+            "989c05ce5e6d8e0faa95524e22bef08441102cd1",
+            "989cd6cd252b56d218278641a1c3fceb03e41ad1",
+            "989c1cc681c54f1a47cc409a8bed834d8a966bd1",
+        ];
+        let head = [b"OpusHead".as_slice(), &[1, 1], &312u16.to_le_bytes(), &48000u32.to_le_bytes(), &[0, 0, 0]].concat();
+        let audio = [element(&[0xB5], &48000f64.to_be_bytes()), element(&[0x9F], &[1])].concat();
+        let track = [element(&[0xD7], &[1]), element(&[0x73, 0xC5], &[1]), element(&[0x86], b"A_OPUS"), element(&[0x63, 0xA2], &head), element(&[0xE1], &audio)].concat();
+        let mut cluster = element(&[0xE7], &[0]);
+        for (i, packet) in packets.iter().enumerate() {
+            let data: Vec<u8> = (0..packet.len()).step_by(2).map(|j| u8::from_str_radix(&packet[j..j + 2], 16).unwrap()).collect();
+            cluster.extend(element(&[0xA3], &[&[0x81][..], &(20 * i as i16).to_be_bytes(), &[0x80], &data].concat()));
+        }
+        let segment = [
+            element(&[0x15, 0x49, 0xA9, 0x66], &element(&[0x2A, 0xD7, 0xB1], &[0x0F, 0x42, 0x40])),
+            element(&[0x16, 0x54, 0xAE, 0x6B], &element(&[0xAE], &track)),
+            element(&[0x1F, 0x43, 0xB6, 0x75], &cluster),
+        ].concat();
+        let bytes = [element(&[0x1A, 0x45, 0xDF, 0xA3], &element(&[0x42, 0x82], b"webm")), element(&[0x18, 0x53, 0x80, 0x67], &segment)].concat();
+
+        assert_eq!(container_name(&bytes), "webm");
+        let audio = decode_audio(&bytes).unwrap();
+        assert_eq!((audio.info().codec.as_str(), audio.sample_rate(), audio.info().channels), ("opus", 48000, 1));
+        // The pre-skip comes off the front
+        assert_eq!(audio.info().frames, 4 * 960 - 312);
+        let tail = &audio.samples()[audio.samples().len() - 960..];
+        let rms = (tail.iter().map(|s| s * s).sum::<f32>() / tail.len() as f32).sqrt();
+        assert!((rms - 0.5 / 2f32.sqrt()).abs() < 0.05, "rms {}", rms);
+    }
+
+    #[test]
+    fn decodes_aiff_and_companded_wav() {
+        // 8 kHz mono µ-law: 0x80 and 0x00 are the full-scale codes, 0xFF zero
+        let mut mu_law = b"RIFF\x2e\0\0\0WAVEfmt \x12\0\0\0\x07\0\x01\0\x40\x1f\0\0\x40\x1f\0\0\x01\0\x08\0\0\0".to_vec();
+        mu_law.extend_from_slice(b"data\x04\0\0\0\x80\x00\xff\xff");
+        let audio = decode_audio(&mu_law).unwrap();
+        assert_eq!((audio.info().container.as_str(), audio.sample_rate(), audio.info().frames), ("wav", 8000, 4));
+        assert!(audio.info().codec.contains("mulaw"), "{}", audio.info().codec);
+        let samples = audio.samples();
+        assert!(samples[0] > 0.95 && samples[1] < -0.95 && samples[2].abs() < 1e-3, "{:?}", samples);
+
+        // 44.1 kHz stereo 16-bit big-endian AIFF
+        let mut aiff = b"FORM\0\0\0\x32AIFFCOMM\0\0\0\x12\0\x02\0\0\0\x02\0\x10\x40\x0e\xac\x44\0\0\0\0\0\0".to_vec();
+        aiff.extend_from_slice(b"SSND\0\0\0\x10\0\0\0\0\0\0\0\0\x40\0\xc0\0\0\0\x7f\xff");
+        let audio = decode_audio(&aiff).unwrap();
+        assert_eq!((audio.info().container.as_str(), audio.sample_rate(), audio.channels()), ("aiff", 44100, 2));
+        assert_eq!(&audio.samples()[..3], &[0.5, -0.5, 0.0]);
+    }
+}
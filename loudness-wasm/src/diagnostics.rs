@@ -0,0 +1,56 @@
+// Runtime diagnostics for debugging analyses in the field
+//
+// Internal code logs through the `log` facade. With the `debug` feature the
+// module installs `console_error_panic_hook` (readable panic messages instead
+// of "unreachable executed") and a logger writing to the browser console;
+// without it every log call is a no-op.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+// Runs automatically when the module is instantiated
+#[cfg(all(feature = "debug", target_arch = "wasm32"))]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
+pub fn init_diagnostics() {
+    console_error_panic_hook::set_once();
+    // Fails only if a logger is already installed, which is fine
+    let _ = console_log::init_with_level(log::Level::Trace);
+    // Quiet by default until JS calls `set_log_level`
+    log::set_max_level(log::LevelFilter::Warn);
+}
+
+/// Whether this build includes the panic hook and console logger (`debug` feature)
+pub fn debug_enabled() -> bool {
+    cfg!(feature = "debug")
+}
+
+/// Parse a level name ("off", "error", "warn", "info", "debug", "trace")
+pub fn parse_log_level(level: &str) -> Option<log::LevelFilter> {
+    level.trim().parse().ok()
+}
+
+// Change how much internal logging reaches the console; returns false for an
+// unknown level name
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn set_log_level(level: &str) -> bool {
+    match parse_log_level(level) {
+        Some(filter) => {
+            log::set_max_level(filter);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_level_names() {
+        assert_eq!(parse_log_level("debug"), Some(log::LevelFilter::Debug));
+        assert_eq!(parse_log_level(" WARN "), Some(log::LevelFilter::Warn));
+        assert_eq!(parse_log_level("off"), Some(log::LevelFilter::Off));
+        assert_eq!(parse_log_level("verbose"), None);
+    }
+}
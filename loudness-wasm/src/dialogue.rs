@@ -0,0 +1,228 @@
+// Dialogue intelligibility for accessibility QC: how far speech stands above
+// the music and effects competing with it. Without the stems the background
+// is estimated from the voice band itself: speech stops between syllables and
+// words, so the quietest 50ms blocks of the surrounding three seconds show
+// the bed underneath, and the energy above that is the speech. Speech is told
+// apart by that syllabic swing rather than by its level, so dialogue buried
+// under a louder bed still counts as speech. Each one-second section with
+// speech gets a speech-to-background ratio and a risk (0 when speech is 15 dB
+// clear, 1 when it is no louder than the background), and runs of sections
+// under 7 dB are flagged. Steady beds read as no speech;
+// heavily modulated music (drums, staccato strings) can read as speech
+// buried in its own background, so flagged sections are worth a listen.
+//
+//     const report = analyze_dialogue(pcm, 48000, 2);
+//     if (report.risk > 0.3) report.flagged.forEach(section => mark(section.start, section.end));
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::error::{validate_pcm, AnalysisError};
+use crate::utils::{amplitude_to_db, median, mix_to_mono, percentile, Biquad};
+
+// Voice band (Hz) carrying most of intelligibility
+const VOICE_LOW: f32 = 300.0;
+const VOICE_HIGH: f32 = 3400.0;
+// Energy blocks, and the span (seconds either side) and percentile of blocks
+// the background is read from
+const BLOCK_SECONDS: f32 = 0.05;
+const BACKGROUND_SPAN_SECONDS: f32 = 1.5;
+const BACKGROUND_PERCENTILE: f32 = 0.1;
+// Sections judged; a section holds speech when its voice band is above
+// -50 dBFS and its block energies swing by at least a tenth of their mean,
+// as syllables do however loud the bed under them
+const SECTION_SECONDS: f32 = 1.0;
+const VOICE_LEVEL: f32 = -50.0;
+const SYLLABIC_DEPTH: f32 = 0.1;
+// Speech-to-background ratios (dB) of no risk and full risk, and the ratio
+// under which a section is flagged
+const CLEAR_RATIO: f32 = 15.0;
+const MASKED_RATIO: f32 = 0.0;
+const FLAG_RATIO: f32 = 7.0;
+
+/// A run of flagged sections (times in seconds)
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct DialogueSection {
+    pub start: f32,
+    pub end: f32,
+    // Lowest speech-to-background ratio (dB) in the run
+    pub ratio: f32,
+    pub risk: f32,
+}
+
+/// Speech-to-background estimate over time
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct DialogueReport {
+    // Share of sections holding speech
+    pub speech_share: f32,
+    // Median ratio (dB) over the speech sections; -Infinity without speech
+    pub ratio: f32,
+    // Mean risk over the speech sections, 0 (clear) to 1 (masked)
+    pub risk: f32,
+    // Ratio (dB) of every section, null where there is no speech
+    pub ratios: Vec<Option<f32>>,
+    pub section_seconds: f32,
+    pub flagged: Vec<DialogueSection>,
+}
+
+/// Estimate how far speech stands above its background in interleaved PCM
+pub fn analyze_dialogue(pcm: &[f32], sample_rate: f32, num_channels: usize) -> Result<DialogueReport, AnalysisError> {
+    let section = (SECTION_SECONDS * sample_rate) as usize;
+    validate_pcm(pcm, num_channels, section.max(1))?;
+    let block = ((BLOCK_SECONDS * sample_rate) as usize).max(1);
+    let energies = voice_block_energies(&mix_to_mono(pcm, num_channels), sample_rate, block);
+    let background = background_energies(&energies);
+
+    let per_section = (section / block).max(1);
+    let ratios: Vec<Option<f32>> = energies.chunks_exact(per_section).zip(background.chunks_exact(per_section))
+        .map(|(energy, background)| {
+            let total: f64 = energy.iter().map(|&e| e as f64).sum();
+            let bed: f64 = energy.iter().zip(background).map(|(&e, &b)| e.min(b) as f64).sum();
+            let speech = total - bed;
+            let level = amplitude_to_db((total / per_section as f64).sqrt() as f32);
+            (level > VOICE_LEVEL && modulation_depth(energy) >= SYLLABIC_DEPTH).then(|| (10.0 * (speech / bed.max(f64::MIN_POSITIVE)).log10()) as f32)
+        })
+        .collect();
+
+    let section_seconds = per_section as f32 * block as f32 / sample_rate;
+    let speech: Vec<f32> = ratios.iter().flatten().copied().collect();
+    let flagged = flag_sections(&ratios, section_seconds);
+    Ok(DialogueReport {
+        speech_share: if ratios.is_empty() { 0.0 } else { speech.len() as f32 / ratios.len() as f32 },
+        ratio: median(&mut speech.clone()).unwrap_or(f32::NEG_INFINITY),
+        risk: if speech.is_empty() { 0.0 } else { speech.iter().map(|&ratio| risk(ratio)).sum::<f32>() / speech.len() as f32 },
+        ratios,
+        section_seconds,
+        flagged,
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = analyze_dialogue)]
+pub fn analyze_dialogue_js(pcm: &[f32], sample_rate: f32, num_channels: usize) -> Result<DialogueReport, JsError> {
+    Ok(analyze_dialogue(pcm, sample_rate, num_channels)?)
+}
+
+// Risk of a section at `ratio` dB, from 0 at `CLEAR_RATIO` to 1 at `MASKED_RATIO`
+fn risk(ratio: f32) -> f32 {
+    ((CLEAR_RATIO - ratio) / (CLEAR_RATIO - MASKED_RATIO)).clamp(0.0, 1.0)
+}
+
+// Mean square of the voice band in every whole block
+fn voice_block_energies(mono: &[f32], sample_rate: f32, block: usize) -> Vec<f32> {
+    let q = std::f32::consts::FRAC_1_SQRT_2;
+    let mut stages = [Biquad::high_pass(sample_rate, VOICE_LOW, q), Biquad::low_pass(sample_rate, VOICE_HIGH, q)];
+    let band: Vec<f32> = mono.iter().map(|&sample| stages.iter_mut().fold(sample, |x, stage| stage.process(x))).collect();
+    band.chunks_exact(block).map(|block| (block.iter().map(|&x| x as f64 * x as f64).sum::<f64>() / block.len() as f64) as f32).collect()
+}
+
+// Standard deviation of block energies relative to their mean: near 0 for
+// steady beds, and raised by syllables even well under a louder bed
+fn modulation_depth(energies: &[f32]) -> f32 {
+    let mean = energies.iter().map(|&e| e as f64).sum::<f64>() / energies.len() as f64;
+    let variance = energies.iter().map(|&e| (e as f64 - mean).powi(2)).sum::<f64>() / energies.len() as f64;
+    if mean > 0.0 { (variance.sqrt() / mean) as f32 } else { 0.0 }
+}
+
+// Background under each block: a low percentile of the blocks around it
+fn background_energies(energies: &[f32]) -> Vec<f32> {
+    let span = (BACKGROUND_SPAN_SECONDS / BLOCK_SECONDS) as usize;
+    let mut around = Vec::with_capacity(2 * span + 1);
+    (0..energies.len())
+        .map(|index| {
+            around.clear();
+            around.extend_from_slice(&energies[index.saturating_sub(span)..(index + span + 1).min(energies.len())]);
+            percentile(&mut around, BACKGROUND_PERCENTILE).unwrap_or(0.0)
+        })
+        .collect()
+}
+
+// Runs of consecutive speech sections under `FLAG_RATIO`
+fn flag_sections(ratios: &[Option<f32>], section_seconds: f32) -> Vec<DialogueSection> {
+    let mut flagged: Vec<DialogueSection> = Vec::new();
+    let mut previous = None;
+    for (index, ratio) in ratios.iter().enumerate() {
+        let Some(ratio) = ratio.filter(|&ratio| ratio < FLAG_RATIO) else {
+            continue;
+        };
+        let start = index as f32 * section_seconds;
+        match flagged.last_mut() {
+            Some(run) if previous == Some(index - 1) => {
+                run.end = start + section_seconds;
+                run.ratio = run.ratio.min(ratio);
+                run.risk = risk(run.ratio);
+            }
+            _ => flagged.push(DialogueSection { start, end: start + section_seconds, ratio, risk: risk(ratio) }),
+        }
+        previous = Some(index);
+    }
+    flagged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn flags_speech_buried_in_the_bed() {
+        // 14 s at 44.1kHz: 12 s of "speech" (a 150Hz buzz with harmonics to
+        // 3kHz, gated into 200ms syllables with 100ms gaps) over a steady
+        // chord bed, about 30 dB down for 6 s and then about level with the
+        // speech in the voice band; then 2 s of bed alone
+        let sample_rate = 44100.0;
+        let pcm: Vec<f32> = (0..14 * 44100usize)
+            .map(|i| {
+                let t = i as f32 / sample_rate;
+                let syllable = if t < 12.0 && (t * 1000.0) as usize % 300 < 200 { 1.0 } else { 0.0 };
+                let voice: f32 = (1..=20).map(|h| (2.0 * PI * 150.0 * h as f32 * t).sin() / h as f32).sum();
+                let bed: f32 = [440.0, 554.0, 659.0, 880.0].iter().map(|&f| (2.0 * PI * f * t).sin()).sum();
+                let bed_level = if t < 6.0 { 0.001 } else { 0.03 };
+                0.1 * syllable * voice + bed_level * bed
+            })
+            .collect();
+
+        let report = analyze_dialogue(&pcm, sample_rate, 1).unwrap();
+        assert_eq!(report.ratios.len(), 14);
+        // Clear speech, then masked speech, then no speech at all
+        assert!(report.ratios[1..5].iter().all(|ratio| ratio.is_some_and(|ratio| ratio > CLEAR_RATIO)), "{:?}", report.ratios);
+        assert!(report.ratios[7..11].iter().all(|ratio| ratio.is_some_and(|ratio| ratio < FLAG_RATIO)), "{:?}", report.ratios);
+        assert_eq!(report.ratios[12..], [None, None]);
+        assert_eq!(report.flagged.len(), 1);
+        let buried = &report.flagged[0];
+        assert!(buried.start >= 6.0 && buried.start <= 7.0 && buried.end == 12.0, "{:?}", buried);
+        assert!(report.risk > 0.3 && report.risk < 0.8, "{}", report.risk);
+    }
+
+    #[test]
+    fn flags_speech_under_a_louder_bed() {
+        // 6 s of the same syllables about 5 dB under a steady chord bed in
+        // the voice band: masked speech, not an absence of it
+        let sample_rate = 44100.0;
+        let pcm: Vec<f32> = (0..6 * 44100usize)
+            .map(|i| {
+                let t = i as f32 / sample_rate;
+                let syllable = if (t * 1000.0) as usize % 300 < 200 { 1.0 } else { 0.0 };
+                let voice: f32 = (1..=20).map(|h| (2.0 * PI * 150.0 * h as f32 * t).sin() / h as f32).sum();
+                let bed: f32 = [440.0, 554.0, 659.0, 880.0].iter().map(|&f| (2.0 * PI * f * t).sin()).sum();
+                0.05 * syllable * voice + 0.03 * bed
+            })
+            .collect();
+
+        let report = analyze_dialogue(&pcm, sample_rate, 1).unwrap();
+        assert_eq!(report.speech_share, 1.0, "{:?}", report.ratios);
+        assert!(report.ratio < 0.0 && report.risk == 1.0, "{} dB, risk {}", report.ratio, report.risk);
+        assert_eq!(report.flagged.len(), 1);
+        assert_eq!((report.flagged[0].start, report.flagged[0].end), (0.0, 6.0));
+
+        // The bed alone holds no speech
+        let bed: Vec<f32> = (0..6 * 44100usize).map(|i| [440.0, 554.0, 659.0, 880.0].iter().map(|&f| 0.03 * (2.0 * PI * f * i as f32 / sample_rate).sin()).sum()).collect();
+        assert_eq!(analyze_dialogue(&bed, sample_rate, 1).unwrap().speech_share, 0.0);
+    }
+}
+
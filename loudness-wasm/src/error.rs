@@ -0,0 +1,167 @@
+// Errors returned by the analysis entry points
+//
+// Entry points validate their input up front so malformed buffers surface as a
+// descriptive JS `Error` instead of a panic (which aborts the whole module) or
+// silently meaningless numbers.
+
+use std::fmt;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use crate::progress::Cancelled;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalysisError {
+    /// No samples were supplied
+    EmptyInput,
+    /// Fewer frames than the analysis needs for a single measurement
+    TooShort { frames: usize, required: usize },
+    /// Zero channels, or a sample count that is not a whole number of frames
+    InvalidChannelCount { channels: usize, samples: usize },
+    /// A NaN or infinite sample at the given index
+    NonFinite { index: usize },
+    /// Aborted at a checkpoint by the progress callback or a cancellation token
+    Cancelled,
+    /// Integer PCM bit depth outside 8..=32
+    InvalidBitDepth { bits: u32 },
+    /// WAV bytes that are malformed or in an unsupported format
+    InvalidWav { reason: &'static str },
+    /// Compressed audio in an unknown container, an unsupported codec or damaged beyond decoding
+    InvalidAudio { reason: &'static str },
+    /// A recognised format no decoder here handles: which one, why, and what to do instead
+    UnsupportedFormat { format: &'static str, reason: &'static str, action: &'static str },
+    /// A channel layout name or channel label that is not recognised
+    InvalidLayout { reason: &'static str },
+    /// A configuration value (such as a window name) that is not recognised
+    InvalidSetting { reason: &'static str },
+    /// A JSON report or saved session that cannot be read back
+    InvalidReport { reason: &'static str },
+    /// A result that cannot be written as a JSON report
+    UnwritableReport { reason: &'static str },
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AnalysisError::EmptyInput => write!(f, "Input contains no samples"),
+            AnalysisError::TooShort { frames, required } => {
+                write!(f, "Input too short: {} frames, at least {} required", frames, required)
+            }
+            AnalysisError::InvalidChannelCount { channels, samples } => {
+                write!(f, "Invalid channel count {} for {} interleaved samples", channels, samples)
+            }
+            AnalysisError::NonFinite { index } => write!(f, "Non-finite sample (NaN or infinity) at index {}", index),
+            AnalysisError::Cancelled => write!(f, "Analysis cancelled"),
+            AnalysisError::InvalidBitDepth { bits } => write!(f, "Unsupported bit depth {} (8 to 32 bits)", bits),
+            AnalysisError::InvalidWav { reason } => write!(f, "Invalid WAV data: {}", reason),
+            AnalysisError::InvalidAudio { reason } => write!(f, "Cannot decode audio: {}", reason),
+            AnalysisError::UnsupportedFormat { format, reason, action } => {
+                write!(f, "Unsupported format {}: {}; {}", format, reason, action)
+            }
+            AnalysisError::InvalidLayout { reason } => write!(f, "Invalid channel layout: {}", reason),
+            AnalysisError::InvalidSetting { reason } => write!(f, "Invalid setting: {}", reason),
+            AnalysisError::InvalidReport { reason } => write!(f, "Cannot read report: {}", reason),
+            AnalysisError::UnwritableReport { reason } => write!(f, "Cannot write report: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for AnalysisError {}
+
+impl From<Cancelled> for AnalysisError {
+    fn from(_: Cancelled) -> Self {
+        AnalysisError::Cancelled
+    }
+}
+
+/// Check interleaved PCM before analysis: non-empty, whole frames of
+/// `num_channels`, at least `min_frames` long and free of NaN/infinity.
+/// Accepts single- or double-precision samples.
+pub fn validate_pcm<S: Copy + Into<f64>>(pcm: &[S], num_channels: usize, min_frames: usize) -> Result<(), AnalysisError> {
+    let result = check_pcm(pcm, num_channels, min_frames);
+    if let Err(error) = &result {
+        log::warn!("Rejected input: {}", error);
+    }
+    result
+}
+
+/// `validate_pcm` for input that is no longer held, such as a finished
+/// stream: `samples` in all, the first non-finite one at `non_finite`
+#[cfg(any(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub(crate) fn validate_counts(samples: usize, non_finite: Option<usize>, num_channels: usize, min_frames: usize) -> Result<(), AnalysisError> {
+    let result = check_length(samples, num_channels, min_frames).and_then(|()| check_finite(non_finite));
+    if let Err(error) = &result {
+        log::warn!("Rejected input: {}", error);
+    }
+    result
+}
+
+fn check_pcm<S: Copy + Into<f64>>(pcm: &[S], num_channels: usize, min_frames: usize) -> Result<(), AnalysisError> {
+    check_length(pcm.len(), num_channels, min_frames)?;
+    check_finite(pcm.iter().position(|&sample| !sample.into().is_finite()))
+}
+
+fn check_length(samples: usize, num_channels: usize, min_frames: usize) -> Result<(), AnalysisError> {
+    if num_channels == 0 || !samples.is_multiple_of(num_channels) {
+        return Err(AnalysisError::InvalidChannelCount { channels: num_channels, samples });
+    }
+    if samples == 0 {
+        return Err(AnalysisError::EmptyInput);
+    }
+
+    let frames = samples / num_channels;
+    if frames < min_frames {
+        return Err(AnalysisError::TooShort { frames, required: min_frames });
+    }
+    Ok(())
+}
+
+fn check_finite(non_finite: Option<usize>) -> Result<(), AnalysisError> {
+    match non_finite {
+        Some(index) => Err(AnalysisError::NonFinite { index }),
+        None => Ok(()),
+    }
+}
+
+/// Check the declared bit depth of integer PCM input
+#[cfg(feature = "loudness")]
+pub fn validate_bit_depth(bits_per_sample: u32) -> Result<(), AnalysisError> {
+    if (8..=32).contains(&bits_per_sample) {
+        Ok(())
+    } else {
+        Err(AnalysisError::InvalidBitDepth { bits: bits_per_sample })
+    }
+}
+
+/// Replace NaN and infinite samples with silence in place, returning how many
+/// were replaced; for callers that prefer a best-effort analysis of damaged
+/// buffers over a `NonFinite` error
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn sanitize_pcm(pcm: &mut [f32]) -> usize {
+    let mut replaced = 0;
+    for sample in pcm.iter_mut().filter(|sample| !sample.is_finite()) {
+        *sample = 0.0;
+        replaced += 1;
+    }
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(validate_pcm::<f32>(&[], 2, 1), Err(AnalysisError::EmptyInput));
+        assert_eq!(validate_pcm(&[0.0; 3], 2, 1), Err(AnalysisError::InvalidChannelCount { channels: 2, samples: 3 }));
+        assert_eq!(validate_pcm(&[0.0; 4], 0, 1), Err(AnalysisError::InvalidChannelCount { channels: 0, samples: 4 }));
+        assert_eq!(validate_pcm(&[0.0; 4], 2, 3), Err(AnalysisError::TooShort { frames: 2, required: 3 }));
+        assert_eq!(validate_pcm(&[0.0, f32::NAN, 0.0, 0.0], 2, 1), Err(AnalysisError::NonFinite { index: 1 }));
+        assert_eq!(validate_pcm(&[0.0, 0.0, f64::INFINITY, 0.0], 2, 1), Err(AnalysisError::NonFinite { index: 2 }));
+        assert_eq!(validate_pcm(&[0.0; 4], 2, 2), Ok(()));
+
+        let mut damaged = [0.5, f32::NAN, f32::NEG_INFINITY, -0.5];
+        assert_eq!(sanitize_pcm(&mut damaged), 2);
+        assert_eq!(damaged, [0.5, 0.0, 0.0, -0.5]);
+        assert_eq!(validate_pcm(&damaged, 2, 2), Ok(()));
+    }
+}
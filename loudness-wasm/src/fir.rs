@@ -0,0 +1,196 @@
+// Windowed-sinc FIR design. `windowed_sinc` is the kernel the resamplers,
+// the true-peak oversampler and the decimators evaluate (Kaiser-windowed,
+// between samples); `FirFilter` designs linear-phase low-, high- and
+// band-pass filters with an odd number of taps and any analysis window, for
+// band splitting and for callers who want their own filters.
+//
+//     const split = FirFilter.band_pass(48000, 300, 3400, 255, "kaiser");
+//     const speech = split.process(pcm);  // aligned with pcm (delay compensated)
+//     console.log(split.response_db(1000, 48000), split.response_db(50, 48000));
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use std::f64::consts::PI;
+use crate::error::AnalysisError;
+use crate::simd::dot;
+use crate::window::Window;
+
+/// Windowed-sinc low-pass at `x` samples from the centre: passband edge
+/// `cutoff` as a share of Nyquist, window spanning `half_width` samples
+/// either side (zero beyond). Summed over whole-sample positions the weights
+/// come to about 1, the passband gain.
+pub fn windowed_sinc(x: f64, cutoff: f64, half_width: f64, window: Window) -> f64 {
+    let x = x.abs();
+    if x >= half_width {
+        return 0.0;
+    }
+    let phase = PI * cutoff * x;
+    let sinc = if x == 0.0 { 1.0 } else { phase.sin() / phase };
+    cutoff * sinc * window.at(x / half_width)
+}
+
+/// Linear-phase FIR filter (odd length, symmetric taps)
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FirFilter {
+    taps: Vec<f32>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl FirFilter {
+    // Taps, and the delay in samples `process` compensates
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn num_taps(&self) -> usize {
+        self.taps.len()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn delay(&self) -> usize {
+        self.taps.len() / 2
+    }
+
+    // Filter one channel; output sample n lines up with input sample n, the
+    // signal counting as silent outside the slice
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn process(&self, input: &[f32]) -> Vec<f32> {
+        let (len, delay) = (self.taps.len(), self.delay());
+        (0..input.len())
+            .map(|n| match n.checked_sub(delay).and_then(|start| input.get(start..start + len)) {
+                // Symmetric taps, so no reversal
+                Some(window) => dot(&self.taps, window),
+                None => self.taps.iter().enumerate()
+                    .filter_map(|(tap, &weight)| (n + tap).checked_sub(delay).and_then(|i| input.get(i)).map(|&sample| weight * sample))
+                    .sum(),
+            })
+            .collect()
+    }
+
+    // Gain (dB) at `frequency` for a filter run at `sample_rate`
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn response_db(&self, frequency: f32, sample_rate: f32) -> f32 {
+        let omega = 2.0 * PI * frequency as f64 / sample_rate as f64;
+        let (re, im) = self.taps.iter().enumerate().fold((0.0, 0.0), |(re, im), (k, &tap)| {
+            let (sin, cos) = (omega * k as f64).sin_cos();
+            (re + tap as f64 * cos, im - tap as f64 * sin)
+        });
+        (20.0 * re.hypot(im).log10()) as f32
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = low_pass)]
+    pub fn low_pass_js(sample_rate: f32, cutoff: f32, num_taps: usize, window: &str) -> Result<FirFilter, JsError> {
+        Ok(FirFilter::low_pass(sample_rate, cutoff, num_taps, window_by_name(window)?)?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = high_pass)]
+    pub fn high_pass_js(sample_rate: f32, cutoff: f32, num_taps: usize, window: &str) -> Result<FirFilter, JsError> {
+        Ok(FirFilter::high_pass(sample_rate, cutoff, num_taps, window_by_name(window)?)?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = band_pass)]
+    pub fn band_pass_js(sample_rate: f32, low: f32, high: f32, num_taps: usize, window: &str) -> Result<FirFilter, JsError> {
+        Ok(FirFilter::band_pass(sample_rate, low, high, num_taps, window_by_name(window)?)?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = taps)]
+    pub fn taps_js(&self) -> Vec<f32> {
+        self.taps.clone()
+    }
+}
+
+impl FirFilter {
+    /// Low-pass passing up to `cutoff` Hz; `num_taps` rounds up to odd, and
+    /// more taps give a steeper transition
+    pub fn low_pass(sample_rate: f32, cutoff: f32, num_taps: usize, window: Window) -> Result<FirFilter, AnalysisError> {
+        let cutoff = nyquist_share(cutoff, sample_rate)?;
+        Ok(FirFilter { taps: design(cutoff, num_taps, window) })
+    }
+
+    /// High-pass from `cutoff` Hz (the low-pass subtracted from a unit impulse)
+    pub fn high_pass(sample_rate: f32, cutoff: f32, num_taps: usize, window: Window) -> Result<FirFilter, AnalysisError> {
+        let mut filter = FirFilter::low_pass(sample_rate, cutoff, num_taps, window)?;
+        filter.taps.iter_mut().for_each(|tap| *tap = -*tap);
+        let centre = filter.delay();
+        filter.taps[centre] += 1.0;
+        Ok(filter)
+    }
+
+    /// Band-pass from `low` to `high` Hz (the difference of two low-passes)
+    pub fn band_pass(sample_rate: f32, low: f32, high: f32, num_taps: usize, window: Window) -> Result<FirFilter, AnalysisError> {
+        if low >= high {
+            return Err(AnalysisError::InvalidSetting { reason: "band-pass edges must rise" });
+        }
+        let (low, high) = (nyquist_share(low, sample_rate)?, nyquist_share(high, sample_rate)?);
+        let taps = design(high, num_taps, window).into_iter().zip(design(low, num_taps, window)).map(|(high, low)| high - low).collect();
+        Ok(FirFilter { taps })
+    }
+
+    pub fn taps(&self) -> &[f32] {
+        &self.taps
+    }
+}
+
+// `frequency` as a share of Nyquist, strictly inside 0..1
+fn nyquist_share(frequency: f32, sample_rate: f32) -> Result<f64, AnalysisError> {
+    let share = 2.0 * frequency as f64 / sample_rate as f64;
+    if share > 0.0 && share < 1.0 {
+        Ok(share)
+    } else {
+        Err(AnalysisError::InvalidSetting { reason: "filter edge must lie between 0Hz and Nyquist" })
+    }
+}
+
+// Taps of an odd-length low-pass, normalised to unity gain at DC
+fn design(cutoff: f64, num_taps: usize, window: Window) -> Vec<f32> {
+    let half = num_taps / 2;
+    // The window reaches zero half a sample past the outermost taps
+    let half_width = half as f64 + 1.0;
+    let taps: Vec<f64> = (0..=2 * half).map(|k| windowed_sinc(k as f64 - half as f64, cutoff, half_width, window)).collect();
+    let gain: f64 = taps.iter().sum();
+    taps.iter().map(|&tap| (tap / gain) as f32).collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn window_by_name(name: &str) -> Result<Window, AnalysisError> {
+    Window::from_name(name).ok_or(AnalysisError::InvalidSetting { reason: "unknown window name" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn designs_meet_their_bands() {
+        let sample_rate = 48000.0;
+        let low_pass = FirFilter::low_pass(sample_rate, 4000.0, 200, Window::Kaiser(8.0)).unwrap();
+        assert_eq!((low_pass.num_taps(), low_pass.delay()), (201, 100));
+        assert!(low_pass.response_db(0.0, sample_rate).abs() < 1e-4);
+        assert!(low_pass.response_db(2000.0, sample_rate).abs() < 0.01);
+        assert!(low_pass.response_db(6000.0, sample_rate) < -70.0);
+
+        let high_pass = FirFilter::high_pass(sample_rate, 4000.0, 201, Window::Kaiser(8.0)).unwrap();
+        assert!(high_pass.response_db(100.0, sample_rate) < -70.0);
+        assert!(high_pass.response_db(10000.0, sample_rate).abs() < 0.01);
+
+        let band_pass = FirFilter::band_pass(sample_rate, 300.0, 3400.0, 511, Window::BlackmanHarris).unwrap();
+        assert!(band_pass.response_db(1000.0, sample_rate).abs() < 0.05);
+        assert!(band_pass.response_db(50.0, sample_rate) < -40.0);
+        assert!(band_pass.response_db(8000.0, sample_rate) < -60.0);
+        assert!(FirFilter::band_pass(sample_rate, 3400.0, 300.0, 511, Window::Hann).is_err());
+        assert!(FirFilter::low_pass(sample_rate, 24000.0, 31, Window::Hann).is_err());
+    }
+
+    #[test]
+    fn processing_compensates_the_delay() {
+        let sample_rate = 48000.0;
+        let tone: Vec<f32> = (0..4800).map(|i| (2.0 * std::f32::consts::PI * 500.0 * i as f32 / sample_rate).sin()).collect();
+        let filter = FirFilter::low_pass(sample_rate, 4000.0, 101, Window::Hann).unwrap();
+        let output = filter.process(&tone);
+        assert_eq!(output.len(), tone.len());
+        let error = output[100..4700].iter().zip(&tone[100..4700]).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+        assert!(error < 1e-3, "max error {}", error);
+    }
+}
@@ -0,0 +1,365 @@
+// Recognition of audio formats the decoders refuse: DSD, MP3-in-WAV, WMA,
+// WavPack and Monkey's Audio fail with `AnalysisError::UnsupportedFormat`,
+// naming the format, why it is refused and what to do, instead of a generic
+// "invalid data". AIFF, ADPCM, A-law and µ-law WAV, WebM and Ogg Opus decode
+// in the `compressed` build (through `decode_audio`, Opus with the decoder in
+// `opus`) and are refused the same way without it. DSD (DSF and DSDIFF) also
+// has a best-effort conversion to PCM, good enough for loudness and peak
+// readings of a SACD rip.
+//
+//     try {
+//         audio = decode_audio(bytes);
+//     } catch (error) {
+//         // "Unsupported format DSD (DSF): ...; convert to PCM ... or use extract_pcm ..."
+//         audio = extract_pcm(bytes);
+//     }
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use crate::error::AnalysisError;
+use crate::fir::windowed_sinc;
+use crate::utils::{Polyphase, SincQuality};
+use crate::wav::WavFile;
+use crate::window::Window;
+
+// DSD bits per PCM sample: 2.8224 MHz DSD64 becomes 44.1 kHz
+const DSD_DECIMATION: usize = 64;
+// First stage: a low-pass over this many bytes of bits, one output per byte
+// (8x the PCM rate), passing up to a quarter of that rate; the rest of DSD's
+// shaped noise is removed by the second stage's low-pass at the PCM Nyquist
+const DSD_STAGE_BYTES: usize = 7;
+const DSD_STAGE_CUTOFF: f64 = 0.125;
+// PCM frames the second stage produces at a time, so the 8x signal is never
+// held whole
+const DSD_BLOCK_FRAMES: usize = 8192;
+// Both DSD containers top out at 5.1; more means a damaged header
+const MAX_DSD_CHANNELS: usize = 6;
+
+const CONVERT_DSD: &str = "convert to 24-bit PCM WAV or FLAC, or use extract_pcm for a best-effort conversion";
+const CONVERT_PCM: &str = "convert to PCM WAV or FLAC";
+const BROWSER_DECODE: &str = "decode it with AudioContext.decodeAudioData and analyse the PCM, or convert to PCM WAV or FLAC";
+
+/// The `UnsupportedFormat` error for bytes of a recognised format that no
+/// decoder in this build handles, if they are one
+pub(crate) fn unsupported_format(bytes: &[u8]) -> Option<AnalysisError> {
+    match compressed_format(bytes) {
+        Some(_) if cfg!(feature = "compressed") => None,
+        Some((format, reason)) => Some(AnalysisError::UnsupportedFormat { format, reason, action: BROWSER_DECODE }),
+        None => never_decoded(bytes),
+    }
+}
+
+/// The `UnsupportedFormat` error for bytes `decode_wav` can't read but
+/// `decode_audio` might
+pub(crate) fn not_pcm_wav(bytes: &[u8]) -> Option<AnalysisError> {
+    match compressed_format(bytes) {
+        Some((format, _)) if cfg!(feature = "compressed") => Some(AnalysisError::UnsupportedFormat { format, reason: "not PCM WAV", action: "decode it with decode_audio" }),
+        _ => unsupported_format(bytes),
+    }
+}
+
+/// Formats only the `compressed` build decodes, with why the others refuse
+/// them
+pub(crate) fn compressed_format(bytes: &[u8]) -> Option<(&'static str, &'static str)> {
+    match bytes {
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => Some(("WebM", "no WebM demuxer")),
+        _ if is_ogg_opus(bytes) => Some(("Opus (Ogg)", "no Opus decoder")),
+        [b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', b'F' | b'C', ..] => Some(("AIFF", "no AIFF demuxer")),
+        [b'R' | b'W', b'I' | b'A', b'F' | b'V', b'F' | b'E', _, _, _, _, b'W', b'A', b'V', b'E', b'f', b'm', b't', b' ', _, _, _, _, tag_low, tag_high, ..] => {
+            let format = match u16::from_le_bytes([*tag_low, *tag_high]) {
+                0x0002 => "WAV (MS ADPCM)",
+                0x0006 => "WAV (A-law)",
+                0x0007 => "WAV (µ-law)",
+                0x0011 => "WAV (IMA ADPCM)",
+                _ => return None,
+            };
+            Some((format, "compressed WAV codec"))
+        }
+        _ => None,
+    }
+}
+
+// Formats no build decodes
+fn never_decoded(bytes: &[u8]) -> Option<AnalysisError> {
+    let (format, reason, action) = match bytes {
+        [b'D', b'S', b'D', b' ', ..] => ("DSD (DSF)", "1-bit DSD is not PCM", CONVERT_DSD),
+        [b'F', b'R', b'M', b'8', _, _, _, _, _, _, _, _, b'D', b'S', b'D', b' ', ..] => ("DSD (DSDIFF)", "1-bit DSD is not PCM", CONVERT_DSD),
+        [b'w', b'v', b'p', b'k', ..] => ("WavPack", "no WavPack decoder", CONVERT_PCM),
+        [b'M', b'A', b'C', b' ', ..] => ("Monkey's Audio", "no APE decoder", CONVERT_PCM),
+        [0x30, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11, ..] => ("WMA (ASF)", "no Windows Media decoder", BROWSER_DECODE),
+        [b'R' | b'W', b'I' | b'A', b'F' | b'V', b'F' | b'E', _, _, _, _, b'W', b'A', b'V', b'E', b'f', b'm', b't', b' ', _, _, _, _, tag_low, tag_high, ..] => {
+            if u16::from_le_bytes([*tag_low, *tag_high]) != 0x0055 {
+                return None;
+            }
+            ("WAV (MP3)", "no decoder for MP3 in WAV", BROWSER_DECODE)
+        }
+        _ => return None,
+    };
+    Some(AnalysisError::UnsupportedFormat { format, reason, action })
+}
+
+// Whether an Ogg stream carries Opus: its first page (27-byte header plus one
+// segment size per segment) holds the OpusHead packet
+fn is_ogg_opus(bytes: &[u8]) -> bool {
+    let [b'O', b'g', b'g', b'S', ..] = bytes else { return false };
+    let segments = bytes.get(26).copied().unwrap_or(0) as usize;
+    bytes.get(27 + segments..35 + segments) == Some(b"OpusHead")
+}
+
+/// Best-effort PCM from a DSF or DSDIFF file: each channel's 1-bit stream is
+/// low-passed and decimated by 64, so DSD64 comes out at 44.1 kHz, DSD128 at
+/// 88.2 kHz. A 56-tap FIR over the bits takes it to 8x the PCM rate, then a
+/// windowed-sinc polyphase low-pass at the PCM Nyquist to the PCM rate, which
+/// removes DSD's shaped ultrasonic noise before it can alias down. The first
+/// and last few milliseconds fade from silence. The result reports 1 bit per
+/// sample, the source depth.
+pub fn extract_pcm(bytes: &[u8]) -> Result<WavFile, AnalysisError> {
+    let (sample_rate, channels, planes) = match bytes {
+        [b'D', b'S', b'D', b' ', ..] => dsf_planes(bytes)?,
+        [b'F', b'R', b'M', b'8', ..] => dsdiff_planes(bytes)?,
+        _ => return Err(unsupported_format(bytes).unwrap_or(AnalysisError::InvalidAudio { reason: "no best-effort extraction for this format" })),
+    };
+
+    let frames = planes.iter().map(|plane| plane.len() * 8 / DSD_DECIMATION).min().unwrap_or(0);
+    if frames == 0 {
+        return Err(AnalysisError::InvalidAudio { reason: "no DSD audio data" });
+    }
+    let tables = dsd_stage_tables();
+    let stage = Polyphase::new(1, DSD_DECIMATION / 8, SincQuality::Balanced);
+    let decimated: Vec<Vec<f32>> = planes.iter().map(|plane| decimate_dsd(plane, &tables, &stage, frames)).collect();
+    let samples = (0..frames).flat_map(|frame| decimated.iter().map(move |channel| channel[frame])).collect();
+    log::debug!("Extracted {} frames of PCM from {}-channel DSD at {} Hz", frames, channels, sample_rate);
+    Ok(WavFile::from_samples(sample_rate / DSD_DECIMATION as u32, channels, 1, samples))
+}
+
+// The first stage's taps folded into one table per byte of its window: entry
+// `b` is the filter's output for that byte holding `b` (bits MSB first, 1 as
+// +1 and 0 as -1), normalised to unity gain at DC
+fn dsd_stage_tables() -> Vec<[f32; 256]> {
+    let bits = 8 * DSD_STAGE_BYTES;
+    let centre = (bits - 1) as f64 / 2.0;
+    let taps: Vec<f64> = (0..bits).map(|bit| windowed_sinc(bit as f64 - centre, DSD_STAGE_CUTOFF, centre + 1.0, Window::Kaiser(8.0))).collect();
+    let gain: f64 = taps.iter().sum();
+    taps.chunks_exact(8)
+        .map(|byte_taps| {
+            let mut table = [0.0; 256];
+            for (byte, entry) in table.iter_mut().enumerate() {
+                let sum: f64 = byte_taps.iter().enumerate().map(|(bit, &tap)| if byte & (0x80 >> bit) != 0 { tap } else { -tap }).sum();
+                *entry = (sum / gain) as f32;
+            }
+            table
+        })
+        .collect()
+}
+
+// `frames` PCM samples from one channel's MSB-first DSD bytes. The first
+// stage's output `j` is centred on byte `j`; bytes outside the plane count as
+// silence, not as a run of zeros (full negative)
+fn decimate_dsd(plane: &[u8], tables: &[[f32; 256]], stage: &Polyphase, frames: usize) -> Vec<f32> {
+    let first_stage = |j: usize| -> f32 {
+        tables.iter().enumerate()
+            .filter_map(|(offset, table)| (j + offset).checked_sub(DSD_STAGE_BYTES / 2).and_then(|i| plane.get(i)).map(|&byte| table[byte as usize]))
+            .sum()
+    };
+    // Second-stage input beyond a block's ends that its edge outputs weigh,
+    // rounded to whole outputs
+    let factor = stage.down();
+    let margin = (stage.taps_per_phase() / 2).div_ceil(factor) * factor;
+    let mut output = Vec::with_capacity(frames);
+    for block in (0..frames).step_by(DSD_BLOCK_FRAMES) {
+        let end = (block + DSD_BLOCK_FRAMES).min(frames);
+        let start = (block * factor).saturating_sub(margin);
+        let intermediate: Vec<f32> = (start..(end * factor + margin).min(plane.len())).map(first_stage).collect();
+        let skip = block - start / factor;
+        output.extend(stage.process(&intermediate).into_iter().skip(skip).take(end - block));
+    }
+    output.resize(frames, 0.0);
+    output
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = extract_pcm)]
+pub fn extract_pcm_js(bytes: &[u8]) -> Result<WavFile, JsError> {
+    Ok(extract_pcm(bytes)?)
+}
+
+// DSD rate, channel count and each channel's bytes, MSB first, of a DSF file
+// (little endian, LSB first; channels interleaved in fixed-size blocks)
+fn dsf_planes(bytes: &[u8]) -> Result<(u32, usize, Vec<Vec<u8>>), AnalysisError> {
+    const TRUNCATED: AnalysisError = AnalysisError::InvalidAudio { reason: "truncated DSF header" };
+    let u32_at = |offset: usize| bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).ok_or(TRUNCATED);
+    let u64_at = |offset: usize| bytes.get(offset..offset + 8).map(|b| u64::from_le_bytes(b.try_into().unwrap_or_default())).ok_or(TRUNCATED);
+
+    let fmt = u64_at(4)? as usize;
+    if fmt > bytes.len() || bytes.get(fmt..fmt + 4) != Some(b"fmt ") {
+        return Err(AnalysisError::InvalidAudio { reason: "DSF file without fmt chunk" });
+    }
+    let (channels, sample_rate) = (u32_at(fmt + 24)? as usize, u32_at(fmt + 28)?);
+    let (bit_count, block_size) = (u64_at(fmt + 36)? as usize, u32_at(fmt + 44)? as usize);
+    let data = fmt.saturating_add(u64_at(fmt + 4)? as usize);
+    if data > bytes.len() || bytes.get(data..data + 4) != Some(b"data") {
+        return Err(AnalysisError::InvalidAudio { reason: "DSF file without data chunk" });
+    }
+    if !(1..=MAX_DSD_CHANNELS).contains(&channels) || block_size == 0 || sample_rate == 0 {
+        return Err(AnalysisError::InvalidAudio { reason: "invalid channel count, block size or sample rate" });
+    }
+
+    // The last block of each channel is padded; keep the stated bit count
+    let mut planes = vec![Vec::new(); channels];
+    let body = &bytes[(data + 12).min(bytes.len())..];
+    for group in body.chunks(block_size.saturating_mul(channels)) {
+        for (plane, block) in planes.iter_mut().zip(group.chunks(block_size)) {
+            plane.extend_from_slice(block);
+        }
+    }
+    for plane in &mut planes {
+        plane.truncate(bit_count / 8);
+        plane.iter_mut().for_each(|byte| *byte = byte.reverse_bits());
+    }
+    Ok((sample_rate, channels, planes))
+}
+
+// DSD rate, channel count and each channel's bytes of a DSDIFF file (big
+// endian; channels interleaved byte by byte)
+fn dsdiff_planes(bytes: &[u8]) -> Result<(u32, usize, Vec<Vec<u8>>), AnalysisError> {
+    let (mut sample_rate, mut channels) = (0, 0);
+    let mut offset = 16;
+    while let Some(header) = bytes.get(offset..offset + 12) {
+        let size = u64::from_be_bytes(header[4..12].try_into().unwrap_or_default()) as usize;
+        let body = &bytes[offset + 12..(offset + 12).saturating_add(size).min(bytes.len())];
+        match &header[..4] {
+            b"PROP" => {
+                // Sound properties: "SND " then sub-chunks of the same shape
+                let mut inner = 4;
+                while let Some(sub) = body.get(inner..inner + 12) {
+                    let sub_size = u64::from_be_bytes(sub[4..12].try_into().unwrap_or_default()) as usize;
+                    match (&sub[..4], body.get(inner + 12..).unwrap_or_default()) {
+                        (b"FS  ", [a, b, c, d, ..]) => sample_rate = u32::from_be_bytes([*a, *b, *c, *d]),
+                        (b"CHNL", [high, low, ..]) => channels = u16::from_be_bytes([*high, *low]) as usize,
+                        _ => {}
+                    }
+                    inner = inner.saturating_add(12).saturating_add(sub_size).saturating_add(sub_size & 1);
+                }
+            }
+            b"DSD " => {
+                if !(1..=MAX_DSD_CHANNELS).contains(&channels) || sample_rate == 0 {
+                    return Err(AnalysisError::InvalidAudio { reason: "DSDIFF sound data without valid properties" });
+                }
+                let mut planes = vec![Vec::with_capacity(body.len() / channels); channels];
+                for frame in body.chunks_exact(channels) {
+                    planes.iter_mut().zip(frame).for_each(|(plane, &byte)| plane.push(byte));
+                }
+                return Ok((sample_rate, channels, planes));
+            }
+            b"DST " => {
+                return Err(AnalysisError::UnsupportedFormat {
+                    format: "DSD (DSDIFF, DST-compressed)",
+                    reason: "no DST decoder",
+                    action: "convert to uncompressed DSDIFF, DSF or PCM",
+                });
+            }
+            _ => {}
+        }
+        offset = offset.saturating_add(12).saturating_add(size).saturating_add(size & 1);
+    }
+    Err(AnalysisError::InvalidAudio { reason: "DSDIFF file without sound data" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    // DSF file of DSD64 from each channel's bytes (MSB first, as the planes
+    // come out), padded to whole blocks with the DSD silence pattern
+    fn dsf(channels: &[Vec<u8>]) -> Vec<u8> {
+        let block_size = 4096usize;
+        let length = channels[0].len();
+        let blocks = length.div_ceil(block_size);
+        let mut bytes = b"DSD \x1c\0\0\0\0\0\0\0".to_vec();
+        bytes.extend_from_slice(&[0; 16]);
+        bytes.extend_from_slice(b"fmt \x34\0\0\0\0\0\0\0");
+        for value in [1u32, 0, 2, channels.len() as u32, 2_822_400, 1] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&((length * 8) as u64).to_le_bytes());
+        bytes.extend_from_slice(&(block_size as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&((12 + channels.len() * blocks * block_size) as u64).to_le_bytes());
+        for block in 0..blocks {
+            for channel in channels {
+                let mut chunk: Vec<u8> = channel[block * block_size..((block + 1) * block_size).min(length)].iter().map(|byte| byte.reverse_bits()).collect();
+                chunk.resize(block_size, 0x69);
+                bytes.extend(chunk);
+            }
+        }
+        bytes
+    }
+
+    // Second-order sigma-delta modulation of a sine at 2.8224 MHz, MSB first
+    fn modulated_sine(amplitude: f64, frequency: f64, bytes: usize) -> Vec<u8> {
+        let (mut first, mut second) = (0.0, 0.0);
+        (0..bytes)
+            .map(|byte| {
+                (0..8).fold(0u8, |packed, bit| {
+                    let x = amplitude * (2.0 * PI * frequency * (8 * byte + bit) as f64 / 2_822_400.0).sin();
+                    let y = if second >= 0.0 { 1.0 } else { -1.0 };
+                    first += x - y;
+                    second += first - y;
+                    packed << 1 | (y > 0.0) as u8
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_unsupported_formats_and_extracts_dsd() {
+        // Channel 0 all ones (+1), channel 1 alternating (0)
+        let bytes = dsf(&[vec![0xFF; 8192], vec![0x55; 8192]]);
+        let Some(AnalysisError::UnsupportedFormat { format, action, .. }) = unsupported_format(&bytes) else { panic!("DSF not recognised") };
+        assert_eq!(format, "DSD (DSF)");
+        assert!(action.contains("extract_pcm"));
+
+        let pcm = extract_pcm(&bytes).unwrap();
+        assert_eq!((pcm.sample_rate(), pcm.channels(), pcm.frames(), pcm.bits_per_sample()), (44100, 2, 1024, 1));
+        // Away from the edges, which fade from silence
+        assert!(pcm.samples()[100..1948].chunks(2).all(|frame| (frame[0] - 1.0).abs() < 1e-3 && frame[1].abs() < 1e-3));
+
+        let mut dff = b"FRM8\0\0\0\0\0\0\0\0DSD PROP\0\0\0\0\0\0\0\x22SND FS  \0\0\0\0\0\0\0\x04\0\x2b\x11\0".to_vec();
+        dff.extend_from_slice(b"CHNL\0\0\0\0\0\0\0\x02\0\x01DSD \0\0\0\0\0\0\x10\0");
+        dff.extend([0x00; 2048].into_iter().chain([0xFF; 2048]));
+        let pcm = extract_pcm(&dff).unwrap();
+        assert_eq!((pcm.sample_rate(), pcm.channels(), pcm.frames()), (44100, 1, 512));
+        assert!((pcm.samples()[100] + 1.0).abs() < 1e-3 && (pcm.samples()[400] - 1.0).abs() < 1e-3);
+
+        // Compressed WAV is only refused without a decoder for it, but never
+        // handed to decode_wav
+        let mut adpcm = b"RIFF\0\0\0\0WAVEfmt \x14\0\0\0\x11\0\x01\0".to_vec();
+        adpcm.extend_from_slice(&[0; 16]);
+        assert!(matches!(not_pcm_wav(&adpcm), Some(AnalysisError::UnsupportedFormat { format: "WAV (IMA ADPCM)", .. })));
+        assert_eq!(unsupported_format(&adpcm).is_some(), !cfg!(feature = "compressed"));
+        adpcm[20] = 0x01;
+        assert!(not_pcm_wav(&adpcm).is_none());
+        assert!(matches!(extract_pcm(b"fLaC\0\0\0\x22"), Err(AnalysisError::InvalidAudio { .. })));
+    }
+
+    #[test]
+    fn filters_modulator_noise_out_of_dsd() {
+        // A quarter second of -6 dBFS 1 kHz, sigma-delta modulated: the PCM
+        // must hold the tone with the shaped noise filtered, not folded down
+        let bytes = dsf(&[modulated_sine(0.5, 1000.0, 88200)]);
+        let pcm = extract_pcm(&bytes).unwrap();
+        assert_eq!((pcm.sample_rate(), pcm.frames()), (44100, 11025));
+
+        // Fit the tone over 200 whole cycles in the middle; what is left is noise
+        let middle = &pcm.samples()[1000..1000 + 8820];
+        let phase = |n: usize| 2.0 * PI * 1000.0 * (1000 + n) as f64 / 44100.0;
+        let (sin, cos) = middle.iter().enumerate().fold((0.0, 0.0), |(s, c), (n, &x)| (s + x as f64 * phase(n).sin(), c + x as f64 * phase(n).cos()));
+        let (sin, cos) = (2.0 * sin / 8820.0, 2.0 * cos / 8820.0);
+        assert!((sin.hypot(cos) - 0.5).abs() < 0.005, "{}", sin.hypot(cos));
+        let noise = middle.iter().enumerate().map(|(n, &x)| (x as f64 - sin * phase(n).sin() - cos * phase(n).cos()).powi(2)).sum::<f64>() / 8820.0;
+        let snr = 10.0 * (0.125 / noise).log10();
+        assert!(snr > 60.0, "{} dB", snr);
+    }
+}
@@ -0,0 +1,147 @@
+// Block gating (ITU-R BS.1770 / EBU Tech 3342) as one reusable piece:
+// blockize a signal's energy into overlapping blocks, drop the blocks under
+// an absolute gate, drop those a relative gate below the mean of the rest,
+// and aggregate what is left: the loudness of the mean energy for integrated
+// loudness, percentiles of the block loudness for loudness range. Integrated
+// loudness, LRA and the bed meter all gate through here, and a custom gated
+// measurement is a `Gate` with its own thresholds.
+
+use crate::constants::{ABSOLUTE_GATE, LRA_RELATIVE_GATE, RELATIVE_GATE};
+use crate::utils::percentile;
+
+/// BS.1770 loudness (LUFS) of a block's mean square energy
+pub fn block_loudness(energy: f32) -> f32 {
+    -0.691 + 10.0 * (energy + 1e-10).log10()
+}
+
+/// Number of `block_size` blocks at `hop` that fit in `frames` frames
+pub fn block_count(frames: usize, block_size: usize, hop: usize) -> usize {
+    if frames >= block_size {
+        (frames - block_size) / hop + 1
+    } else {
+        0
+    }
+}
+
+/// Mean of every whole `block_size` block at `hop` over per-frame energies
+/// (summed over channels), from running sums
+pub fn blockize(frame_energies: &[f64], block_size: usize, hop: usize) -> Vec<f32> {
+    let mut prefix = Vec::with_capacity(frame_energies.len() + 1);
+    prefix.push(0.0f64);
+    for &energy in frame_energies {
+        prefix.push(prefix[prefix.len() - 1] + energy);
+    }
+    (0..block_count(frame_energies.len(), block_size, hop.max(1)))
+        .map(|block| ((prefix[block * hop + block_size] - prefix[block * hop]) / block_size as f64) as f32)
+        .collect()
+}
+
+/// Two-stage gate over block energies: an absolute threshold (LUFS), then a
+/// relative one (LU) under the loudness of the mean absolute-gated energy
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gate {
+    absolute: f32,
+    relative: f32,
+}
+
+impl Gate {
+    /// Integrated loudness (BS.1770): -70 LUFS, then -10 LU
+    pub const INTEGRATED: Gate = Gate { absolute: ABSOLUTE_GATE, relative: RELATIVE_GATE };
+    /// Loudness range (EBU Tech 3342): -70 LUFS, then -20 LU
+    pub const LOUDNESS_RANGE: Gate = Gate { absolute: ABSOLUTE_GATE, relative: LRA_RELATIVE_GATE };
+
+    /// Custom thresholds; a relative gate of -Infinity only gates absolutely
+    pub const fn new(absolute: f32, relative: f32) -> Self {
+        Gate { absolute, relative }
+    }
+
+    pub fn passes_absolute(&self, energy: f32) -> bool {
+        block_loudness(energy) >= self.absolute
+    }
+
+    /// Both stages over block energies (blocks already absolute-gated pass
+    /// the first stage unchanged)
+    pub fn apply(&self, energies: &[f32]) -> GatedBlocks {
+        let absolute: Vec<f32> = energies.iter().copied().filter(|&energy| self.passes_absolute(energy)).collect();
+        if absolute.is_empty() {
+            return GatedBlocks { threshold: f32::NEG_INFINITY, absolute_blocks: 0, energies: absolute };
+        }
+        let threshold = block_loudness(mean_energy(&absolute)) + self.relative;
+        let absolute_blocks = absolute.len();
+        let energies = absolute.into_iter().filter(|&energy| block_loudness(energy) >= threshold).collect();
+        GatedBlocks { threshold, absolute_blocks, energies }
+    }
+}
+
+/// Blocks left by a `Gate`
+#[derive(Clone, Debug, PartialEq)]
+pub struct GatedBlocks {
+    /// Relative threshold (LUFS); -Infinity when no block passed the absolute gate
+    pub threshold: f32,
+    /// Blocks that passed the absolute gate
+    pub absolute_blocks: usize,
+    /// Energies of the blocks that passed both gates
+    pub energies: Vec<f32>,
+}
+
+impl GatedBlocks {
+    /// Loudness of the mean gated energy (integrated loudness); -Infinity
+    /// when every block was gated out
+    pub fn loudness(&self) -> f32 {
+        if self.energies.is_empty() {
+            return f32::NEG_INFINITY;
+        }
+        block_loudness(mean_energy(&self.energies))
+    }
+
+    /// Block loudness `share` of the way through the gated blocks in
+    /// ascending order (nearest rank)
+    pub fn percentile(&self, share: f32) -> Option<f32> {
+        let mut loudness: Vec<f32> = self.energies.iter().copied().map(block_loudness).collect();
+        percentile(&mut loudness, share)
+    }
+}
+
+// Mean energy, summed in f64
+fn mean_energy(energies: &[f32]) -> f32 {
+    (energies.iter().map(|&energy| energy as f64).sum::<f64>() / energies.len() as f64) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mean square energy reading `lufs`
+    fn energy(lufs: f32) -> f32 {
+        10f32.powf((lufs + 0.691) / 10.0)
+    }
+
+    #[test]
+    fn gates_drop_silence_and_quiet_passages() {
+        // Music at -20 LUFS, a passage at -35 and near-silence at -80
+        let energies: Vec<f32> = [vec![-20.0; 8], vec![-35.0; 4], vec![-80.0; 4]].concat().into_iter().map(energy).collect();
+
+        let integrated = Gate::INTEGRATED.apply(&energies);
+        assert_eq!(integrated.absolute_blocks, 12);
+        assert_eq!(integrated.energies.len(), 8);
+        assert!((integrated.loudness() - -20.0).abs() < 0.01);
+
+        // The range gate 20 LU down keeps the quiet passage
+        let range = Gate::LOUDNESS_RANGE.apply(&energies);
+        assert_eq!(range.energies.len(), 12);
+        assert!((range.percentile(0.95).unwrap() - range.percentile(0.1).unwrap() - 15.0).abs() < 0.01);
+
+        // A custom absolute-only gate, and nothing left to aggregate
+        assert_eq!(Gate::new(-40.0, f32::NEG_INFINITY).apply(&energies).energies.len(), 12);
+        let silent = Gate::INTEGRATED.apply(&[energy(-90.0)]);
+        assert_eq!((silent.loudness(), silent.threshold, silent.percentile(0.5)), (f32::NEG_INFINITY, f32::NEG_INFINITY, None));
+    }
+
+    #[test]
+    fn blockize_averages_overlapping_blocks() {
+        let frames: Vec<f64> = (0..10).map(|frame| frame as f64).collect();
+        assert_eq!(blockize(&frames, 4, 2), vec![1.5, 3.5, 5.5, 7.5]);
+        assert_eq!(block_count(10, 4, 2), 4);
+        assert!(blockize(&frames, 11, 2).is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+// Mains hum detection: the 50 or 60Hz fundamental and its first harmonics are
+// measured with Goertzel filters over one-second blocks, each against the
+// level 5Hz either side. Hum is steady, so it stands out of its neighbourhood
+// in nearly every block, where a bass note at the same pitch only does for as
+// long as it is held; detection uses the prominence most blocks reach.
+//
+//     const hum = detect_hum(pcm, 48000, 2);
+//     if (hum.detected) warn(`${hum.mains_frequency} Hz hum at ${hum.level.toFixed(1)} dBFS`);
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::error::{validate_pcm, AnalysisError};
+use crate::utils::{amplitude_to_db, calculate_rms, median, mix_to_mono, percentile, Goertzel};
+
+const MAINS_FREQUENCIES: [f32; 2] = [50.0, 60.0];
+// Fundamental plus the 2nd and 3rd harmonics (rectifier hum peaks at the 2nd)
+const HARMONICS: usize = 3;
+// Whole cycles of both mains frequencies, so their neighbours fall on DFT zeros
+const BLOCK_SECONDS: f32 = 1.0;
+const NEIGHBOUR_OFFSET: f32 = 5.0;
+// dB above the neighbourhood in the quieter blocks that counts as hum
+const HUM_PROMINENCE: f32 = 12.0;
+// Share of blocks the prominence is read at (the quieter end of the spread)
+const PROMINENCE_PERCENTILE: f32 = 0.25;
+// Blocks below this RMS (dBFS) are digital silence and say nothing about hum
+const SILENT_BLOCK: f32 = -90.0;
+
+/// Mains hum reading; levels in dBFS, prominence in dB
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct HumResult {
+    pub detected: bool,
+    // 50 or 60; null when no block has sound
+    pub mains_frequency: Option<f32>,
+    // Median level of the fundamental
+    pub level: f32,
+    // Height above the neighbouring frequencies reached by most blocks
+    pub prominence: f32,
+    // Median levels of the 2nd and 3rd harmonics
+    pub harmonic_levels: Vec<f32>,
+}
+
+/// Look for 50/60Hz mains hum in interleaved PCM (at least one second)
+pub fn detect_hum(pcm: &[f32], sample_rate: f32, num_channels: usize) -> Result<HumResult, AnalysisError> {
+    let block = (BLOCK_SECONDS * sample_rate) as usize;
+    validate_pcm(pcm, num_channels, block.max(1))?;
+    let mono = mix_to_mono(pcm, num_channels);
+    let blocks: Vec<&[f32]> = mono.chunks_exact(block).filter(|block| amplitude_to_db(calculate_rms(block)) > SILENT_BLOCK).collect();
+    if blocks.is_empty() {
+        return Ok(HumResult {
+            detected: false,
+            mains_frequency: None,
+            level: f32::NEG_INFINITY,
+            prominence: 0.0,
+            harmonic_levels: vec![f32::NEG_INFINITY; HARMONICS - 1],
+        });
+    }
+
+    let readings = MAINS_FREQUENCIES.map(|mains| read_mains(&blocks, mains, sample_rate));
+    let (mains, (prominence, levels)) = MAINS_FREQUENCIES.into_iter().zip(readings)
+        .max_by(|(_, (a, _)), (_, (b, _))| a.total_cmp(b))
+        .expect("two mains frequencies");
+    Ok(HumResult {
+        detected: prominence >= HUM_PROMINENCE,
+        mains_frequency: Some(mains),
+        level: levels[0],
+        prominence,
+        harmonic_levels: levels[1..].to_vec(),
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = detect_hum)]
+pub fn detect_hum_js(pcm: &[f32], sample_rate: f32, num_channels: usize) -> Result<HumResult, JsError> {
+    Ok(detect_hum(pcm, sample_rate, num_channels)?)
+}
+
+// Prominence of `mains` hum over the blocks (the strongest harmonic per block,
+// at the percentile) and the median level of each harmonic
+fn read_mains(blocks: &[&[f32]], mains: f32, sample_rate: f32) -> (f32, [f32; HARMONICS]) {
+    let nyquist = sample_rate / 2.0;
+    let mut prominences = Vec::with_capacity(blocks.len());
+    let mut levels: [Vec<f32>; HARMONICS] = std::array::from_fn(|_| Vec::with_capacity(blocks.len()));
+    for block in blocks {
+        let mut strongest = f32::NEG_INFINITY;
+        for (harmonic, levels) in levels.iter_mut().enumerate() {
+            let frequency = mains * (harmonic + 1) as f32;
+            if frequency + NEIGHBOUR_OFFSET >= nyquist {
+                continue;
+            }
+            let amplitude = |frequency: f32| Goertzel::new(frequency, sample_rate).amplitude(block);
+            let level = amplitude(frequency);
+            let neighbours = (amplitude(frequency - NEIGHBOUR_OFFSET) + amplitude(frequency + NEIGHBOUR_OFFSET)) / 2.0;
+            strongest = strongest.max(amplitude_to_db(level) - amplitude_to_db(neighbours.max(f32::MIN_POSITIVE)));
+            levels.push(amplitude_to_db(level));
+        }
+        prominences.push(strongest);
+    }
+
+    let prominence = percentile(&mut prominences, PROMINENCE_PERCENTILE).unwrap_or(f32::NEG_INFINITY);
+    let mut medians = [f32::NEG_INFINITY; HARMONICS];
+    for (level, values) in medians.iter_mut().zip(levels.iter_mut()) {
+        *level = median(values).unwrap_or(f32::NEG_INFINITY);
+    }
+    (prominence, medians)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    // Noise from a linear congruential generator, about -15 dBFS
+    fn noise(frames: usize) -> Vec<f32> {
+        let mut state = 12345u32;
+        (0..frames).map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as f32 / (1 << 24) as f32 * 0.6 - 0.3
+        }).collect()
+    }
+
+    #[test]
+    fn finds_steady_hum_under_noise() {
+        let sample_rate = 48000.0;
+        let clean = noise(5 * 48000);
+        let hum = |i: usize| 0.005 * (2.0 * PI * 60.0 * i as f32 / sample_rate).sin() + 0.01 * (2.0 * PI * 120.0 * i as f32 / sample_rate).sin();
+        let humming: Vec<f32> = clean.iter().enumerate().map(|(i, sample)| sample + hum(i)).collect();
+
+        let result = detect_hum(&humming, sample_rate, 1).unwrap();
+        assert!(result.detected, "{:?}", result);
+        assert_eq!(result.mains_frequency, Some(60.0));
+        assert!((result.level - amplitude_to_db(0.005)).abs() < 1.0);
+        assert!((result.harmonic_levels[0] - amplitude_to_db(0.01)).abs() < 1.0);
+
+        assert!(!detect_hum(&clean, sample_rate, 1).unwrap().detected);
+        assert_eq!(detect_hum(&vec![0.0; 48000], sample_rate, 1).unwrap().mains_frequency, None);
+        assert!(detect_hum(&clean[..1000], sample_rate, 1).is_err());
+    }
+}
@@ -0,0 +1,480 @@
+// Chunked ingestion for files too large to download first: WAV bytes from a
+// streaming fetch (or PCM chunks decoded elsewhere) are measured as they
+// arrive. Only the partial frame at a chunk edge, the resampler's kernel and
+// the 3s of audio the open loudness blocks span are kept, so memory stays flat
+// however long the file is; the final loudness equals the batch analyzer's.
+//
+//     const ingest = new StreamIngest();
+//     const reader = (await fetch(url)).body.getReader();
+//     for (let chunk = await reader.read(); !chunk.done; chunk = await reader.read()) {
+//         ingest.push_bytes(chunk.value);
+//         meter.update(ingest.poll());
+//     }
+//     const { loudness, sample_peak } = ingest.finish();
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::constants::*;
+use crate::error::AnalysisError;
+use crate::loudness::{LoudnessAnalyzer, LoudnessResult};
+use crate::streaming::{BlockWindow, LoudnessSnapshot};
+use crate::simd::max_abs;
+use crate::utils::{amplitude_to_db, int_scale};
+
+// Largest fmt/ds64 chunk buffered while looking for the data chunk
+const MAX_HEADER_CHUNK: u32 = 1 << 16;
+// RIFF size field value that defers to the ds64 chunk (RF64/BW64)
+const RF64_DEFERRED: u32 = 0xFFFF_FFFF;
+// WAVE format tags
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Measurements of a completed ingest
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct IngestResult {
+    pub sample_rate: f32,
+    pub channels: usize,
+    pub frames: usize,
+    pub duration: f32,
+    // Largest sample magnitude in dBFS
+    pub sample_peak: f32,
+    pub loudness: LoudnessResult,
+}
+
+// Sample layout of a WAV stream's data chunk
+#[derive(Clone, Copy)]
+struct WavFormat {
+    sample_rate: u32,
+    channels: usize,
+    bits_per_sample: u16,
+    float: bool,
+}
+
+impl WavFormat {
+    fn frame_bytes(&self) -> usize {
+        self.channels * self.bits_per_sample.div_ceil(8) as usize
+    }
+
+    // Samples of whole frames of little-endian data, scaled to -1..1
+    fn decode(&self, bytes: &[u8], out: &mut Vec<f32>) {
+        let width = self.bits_per_sample.div_ceil(8) as usize;
+        let scale = int_scale(self.bits_per_sample as u32) as f32;
+        out.extend(bytes.chunks_exact(width).map(|sample| match (self.float, width) {
+            (true, 4) => f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]),
+            (true, _) => f64::from_le_bytes(sample.try_into().unwrap_or([0; 8])) as f32,
+            // 8-bit WAV is unsigned
+            (false, 1) => (sample[0] as i32 - 128) as f32 * scale,
+            (false, _) => {
+                let mut word = [0u8; 4];
+                word[4 - width..].copy_from_slice(sample);
+                // Shift down to sign-extend the right-aligned sample
+                (i32::from_le_bytes(word) >> (8 * (4 - width))) as f32 * scale
+            }
+        }));
+    }
+}
+
+// Where the WAV byte parser is
+#[derive(Clone, Copy)]
+enum Stage {
+    // Waiting for the RIFF/RF64/BW64 preamble
+    Preamble,
+    // The next chunk header is due
+    Chunks,
+    // Bytes of an unneeded chunk still to skip
+    Skip(u64),
+    // Inside the data chunk; None when its length is unknown (live writers)
+    Data(Option<u64>),
+    // Past the data chunk; later bytes are ignored
+    Done,
+}
+
+// Loudness and peak over the frames pushed so far
+struct IngestMeter {
+    analyzer: LoudnessAnalyzer,
+    num_channels: usize,
+    sample_rate: f32,
+    blocks: BlockWindow,
+    head: Vec<f32>,
+    input_samples: usize,
+    peak: f32,
+}
+
+impl IngestMeter {
+    fn new(sample_rate: f32, num_channels: usize) -> Self {
+        IngestMeter {
+            analyzer: LoudnessAnalyzer::new(num_channels),
+            num_channels,
+            sample_rate,
+            blocks: BlockWindow::for_rate(sample_rate, num_channels),
+            head: Vec::with_capacity(5),
+            input_samples: 0,
+            peak: 0.0,
+        }
+    }
+
+    // Measure whole frames of input
+    fn push(&mut self, pcm: &[f32]) -> Result<(), AnalysisError> {
+        if let Some(index) = pcm.iter().position(|sample| !sample.is_finite()) {
+            return Err(AnalysisError::NonFinite { index: self.input_samples + index });
+        }
+        self.head.extend(pcm.iter().take(5 - self.head.len()));
+        self.peak = self.peak.max(max_abs(pcm));
+        self.input_samples += pcm.len();
+
+        self.blocks.push(pcm);
+        Ok(())
+    }
+
+    fn snapshot(&self) -> LoudnessSnapshot {
+        self.blocks.snapshot(&self.analyzer, self.input_samples / self.num_channels)
+    }
+
+    fn finish(mut self) -> Result<IngestResult, AnalysisError> {
+        let frames = self.input_samples / self.num_channels;
+        let required = (MOMENTARY_BLOCK_SIZE as f32 * self.sample_rate / BLOCK_SAMPLE_RATE).ceil() as usize;
+        if frames == 0 {
+            return Err(AnalysisError::EmptyInput);
+        }
+        if frames < required {
+            return Err(AnalysisError::TooShort { frames, required });
+        }
+        let resampling = self.blocks.finish();
+        let mut loudness = self.blocks.result(&self.analyzer, self.head);
+        loudness.resampling = resampling;
+        Ok(IngestResult {
+            sample_rate: self.sample_rate,
+            channels: self.num_channels,
+            frames,
+            duration: frames as f32 / self.sample_rate,
+            sample_peak: amplitude_to_db(self.peak),
+            loudness,
+        })
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct StreamIngest {
+    // Set for byte streams; PCM ingests are created with their format
+    wav: bool,
+    stage: Stage,
+    pending: Vec<u8>,
+    format: Option<WavFormat>,
+    // Data chunk size from the ds64 chunk of RF64/BW64 files
+    ds64_data_size: Option<u64>,
+    // Samples of a frame split across PCM chunks
+    partial_frame: Vec<f32>,
+    meter: Option<IngestMeter>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl StreamIngest {
+    // Ingest of WAV (RIFF, RF64 or BW64) bytes; the format is read from the stream
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        StreamIngest {
+            wav: true,
+            stage: Stage::Preamble,
+            pending: Vec::new(),
+            format: None,
+            ds64_data_size: None,
+            partial_frame: Vec::new(),
+            meter: None,
+        }
+    }
+
+    // Ingest of interleaved PCM chunks decoded elsewhere
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn for_pcm(sample_rate: f32, num_channels: usize) -> Self {
+        let num_channels = num_channels.max(1);
+        StreamIngest { wav: false, meter: Some(IngestMeter::new(sample_rate, num_channels)), ..StreamIngest::new() }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = push_bytes)]
+    pub fn push_bytes_js(&mut self, bytes: &[u8]) -> Result<(), JsError> {
+        Ok(self.push_bytes(bytes)?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = push_pcm)]
+    pub fn push_pcm_js(&mut self, pcm: &[f32]) -> Result<(), JsError> {
+        Ok(self.push_pcm(pcm)?)
+    }
+
+    // Running loudness over the frames ingested so far
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = poll)]
+    pub fn poll_js(&self) -> LoudnessSnapshot {
+        self.poll()
+    }
+
+    // Final measurements; the ingest takes no more input afterwards
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = finish)]
+    pub fn finish_js(&mut self) -> Result<IngestResult, JsError> {
+        Ok(self.finish()?)
+    }
+
+    // Sample rate and channel count, once known (for byte streams, after the fmt chunk)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn sample_rate(&self) -> Option<f32> {
+        self.meter.as_ref().map(|meter| meter.sample_rate)
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn channels(&self) -> Option<usize> {
+        self.meter.as_ref().map(|meter| meter.num_channels)
+    }
+}
+
+impl Default for StreamIngest {
+    fn default() -> Self {
+        StreamIngest::new()
+    }
+}
+
+impl StreamIngest {
+    /// Append the next bytes of a WAV stream, measuring every whole frame
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), AnalysisError> {
+        if !self.wav {
+            return Err(AnalysisError::InvalidAudio { reason: "bytes pushed into a PCM ingest" });
+        }
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.extend_from_slice(bytes);
+        let consumed = self.parse(&pending);
+        pending.drain(..*consumed.as_ref().unwrap_or(&pending.len()));
+        self.pending = pending;
+        consumed.map(|_| ())
+    }
+
+    /// Append interleaved PCM; chunks may split frames
+    pub fn push_pcm(&mut self, pcm: &[f32]) -> Result<(), AnalysisError> {
+        let Some(meter) = self.meter.as_mut().filter(|_| !self.wav) else {
+            return Err(AnalysisError::InvalidAudio { reason: "PCM pushed into a byte ingest" });
+        };
+        let channels = meter.num_channels;
+        if self.partial_frame.is_empty() && pcm.len().is_multiple_of(channels) {
+            return meter.push(pcm);
+        }
+        self.partial_frame.extend_from_slice(pcm);
+        let whole = self.partial_frame.len() / channels * channels;
+        let result = meter.push(&self.partial_frame[..whole]);
+        self.partial_frame.drain(..whole);
+        result
+    }
+
+    /// Provisional loudness over the frames ingested so far
+    pub fn poll(&self) -> LoudnessSnapshot {
+        match &self.meter {
+            Some(meter) => meter.snapshot(),
+            None => LoudnessSnapshot {
+                frames: 0,
+                momentary: f32::NEG_INFINITY,
+                short_term: f32::NEG_INFINITY,
+                momentary_max: f32::NEG_INFINITY,
+                short_term_max: f32::NEG_INFINITY,
+                integrated: f32::NEG_INFINITY,
+            },
+        }
+    }
+
+    /// Final loudness and peak; identical to analysing the whole file at once
+    pub fn finish(&mut self) -> Result<IngestResult, AnalysisError> {
+        self.stage = Stage::Done;
+        match self.meter.take() {
+            Some(meter) => meter.finish(),
+            None if self.wav => Err(AnalysisError::InvalidWav { reason: "stream ended before the data chunk" }),
+            None => Err(AnalysisError::EmptyInput),
+        }
+    }
+
+    // Consume what can be parsed of `bytes` (the unconsumed tail of earlier
+    // pushes first); returns how many bytes were used
+    fn parse(&mut self, bytes: &[u8]) -> Result<usize, AnalysisError> {
+        let mut pos = 0;
+        loop {
+            let available = &bytes[pos..];
+            match self.stage {
+                Stage::Preamble => {
+                    if available.len() < 12 {
+                        break;
+                    }
+                    if !matches!(&available[..4], b"RIFF" | b"RF64" | b"BW64") || &available[8..12] != b"WAVE" {
+                        return Err(AnalysisError::InvalidWav { reason: "not a RIFF/WAVE stream" });
+                    }
+                    pos += 12;
+                    self.stage = Stage::Chunks;
+                }
+                Stage::Chunks => {
+                    if available.len() < 8 {
+                        break;
+                    }
+                    let id = &available[..4];
+                    let size = u32::from_le_bytes([available[4], available[5], available[6], available[7]]);
+                    let padded = size as u64 + (size & 1) as u64;
+                    match id {
+                        b"data" => {
+                            let format = self.format.ok_or(AnalysisError::InvalidWav { reason: "data chunk before fmt chunk" })?;
+                            self.meter = Some(IngestMeter::new(format.sample_rate as f32, format.channels));
+                            let length = match size {
+                                RF64_DEFERRED => self.ds64_data_size,
+                                0 => None,
+                                size => Some(size as u64),
+                            };
+                            pos += 8;
+                            self.stage = Stage::Data(length);
+                        }
+                        b"fmt " | b"ds64" => {
+                            if size > MAX_HEADER_CHUNK {
+                                return Err(AnalysisError::InvalidWav { reason: "oversized header chunk" });
+                            }
+                            if (available.len() as u64) < 8 + padded {
+                                break;
+                            }
+                            let body = &available[8..8 + size as usize];
+                            if id == b"fmt " {
+                                self.format = Some(parse_format(body)?);
+                            } else if body.len() >= 16 {
+                                self.ds64_data_size = Some(u64::from_le_bytes(body[8..16].try_into().unwrap_or_default()));
+                            }
+                            pos += 8 + padded as usize;
+                        }
+                        _ => {
+                            pos += 8;
+                            self.stage = Stage::Skip(padded);
+                        }
+                    }
+                }
+                Stage::Skip(remaining) => {
+                    let skipped = remaining.min(available.len() as u64);
+                    pos += skipped as usize;
+                    if skipped < remaining {
+                        self.stage = Stage::Skip(remaining - skipped);
+                        break;
+                    }
+                    self.stage = Stage::Chunks;
+                }
+                Stage::Data(remaining) => {
+                    let (Some(format), Some(meter)) = (self.format, self.meter.as_mut()) else { break };
+                    let frame_bytes = format.frame_bytes();
+                    let usable = remaining.map_or(available.len(), |remaining| remaining.min(available.len() as u64) as usize);
+                    let whole = usable / frame_bytes * frame_bytes;
+                    let mut pcm = Vec::with_capacity(whole / frame_bytes * format.channels);
+                    format.decode(&available[..whole], &mut pcm);
+                    meter.push(&pcm)?;
+                    pos += whole;
+                    self.stage = match remaining.map(|remaining| remaining - whole as u64) {
+                        // A trailing partial frame of a sized chunk is never completed
+                        Some(left) if left < frame_bytes as u64 => Stage::Done,
+                        left => Stage::Data(left),
+                    };
+                    break;
+                }
+                Stage::Done => {
+                    pos = bytes.len();
+                    break;
+                }
+            }
+        }
+        Ok(pos)
+    }
+}
+
+// Sample layout from a fmt chunk body
+fn parse_format(body: &[u8]) -> Result<WavFormat, AnalysisError> {
+    if body.len() < 16 {
+        return Err(AnalysisError::InvalidWav { reason: "truncated fmt chunk" });
+    }
+    let u16_at = |offset: usize| u16::from_le_bytes([body[offset], body[offset + 1]]);
+    let mut tag = u16_at(0);
+    if tag == FORMAT_EXTENSIBLE && body.len() >= 26 {
+        // The sub-format GUID starts with the plain format tag
+        tag = u16_at(24);
+    }
+    let format = WavFormat {
+        sample_rate: u32::from_le_bytes([body[4], body[5], body[6], body[7]]),
+        channels: u16_at(2) as usize,
+        bits_per_sample: u16_at(14),
+        float: tag == FORMAT_FLOAT,
+    };
+    if format.channels == 0 || format.sample_rate == 0 {
+        return Err(AnalysisError::InvalidWav { reason: "zero channels or sample rate" });
+    }
+    let supported = match tag {
+        FORMAT_PCM => (1..=32).contains(&format.bits_per_sample),
+        FORMAT_FLOAT => matches!(format.bits_per_sample, 32 | 64),
+        _ => false,
+    };
+    if !supported {
+        return Err(AnalysisError::InvalidWav { reason: "unsupported sample format" });
+    }
+    Ok(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::Progress;
+    use crate::loudness::SEGMENT;
+
+    // 16-bit stereo WAV with a LIST chunk before the data
+    fn wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+        let mut bytes = b"RIFF\0\0\0\0WAVEfmt \x10\0\0\0\x01\0\x02\0".to_vec();
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * 4).to_le_bytes());
+        bytes.extend_from_slice(b"\x04\0\x10\0LIST\x05\0\0\0INFOx\0");
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(2 * samples.len() as u32).to_le_bytes());
+        bytes.extend(samples.iter().flat_map(|sample| sample.to_le_bytes()));
+        bytes
+    }
+
+    #[test]
+    fn chunked_bytes_measure_like_the_whole_file() {
+        let samples: Vec<i16> = (0..2 * 5 * 48000).map(|i| (8000.0 * ((i / 2) as f32 * 0.05).sin()) as i16).collect();
+        let bytes = wav(&samples, 48000);
+
+        let mut ingest = StreamIngest::new();
+        for chunk in bytes.chunks(4093) {
+            ingest.push_bytes(chunk).unwrap();
+        }
+        assert_eq!((ingest.sample_rate(), ingest.channels()), (Some(48000.0), Some(2)));
+        assert!(ingest.poll().integrated.is_finite());
+        // Only the open blocks' audio is held, not the file
+        assert!(ingest.meter.as_ref().unwrap().blocks.totals.len() <= SHORT_TERM_BLOCK_SIZE / SEGMENT);
+        let streamed = ingest.finish().unwrap();
+
+        let pcm: Vec<f32> = samples.iter().map(|&sample| sample as f32 / 32768.0).collect();
+        let mut analyzer = LoudnessAnalyzer::new(2);
+        analyzer.set_sample_rate(48000.0);
+        let batch = analyzer.analyze_samples(&pcm, &Progress::new(None, None)).unwrap();
+        assert_eq!(streamed.frames, 5 * 48000);
+        assert_eq!(streamed.loudness.integrated, batch.integrated);
+        assert_eq!(streamed.loudness.short_term, batch.short_term);
+        assert_eq!(streamed.loudness.total_blocks, batch.total_blocks);
+        assert!((streamed.sample_peak - amplitude_to_db(8000.0 / 32768.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn pcm_chunks_may_split_frames_and_bad_streams_fail() {
+        let pcm: Vec<f32> = (0..2 * 44100).map(|i| 0.25 * ((i / 2) as f32 * 0.05).sin()).collect();
+        let mut ingest = StreamIngest::for_pcm(44100.0, 2);
+        for chunk in pcm.chunks(333) {
+            ingest.push_pcm(chunk).unwrap();
+        }
+        assert_eq!(ingest.finish().unwrap().frames, 44100);
+        assert!(ingest.push_bytes(b"RIFF").is_err());
+
+        assert!(StreamIngest::new().push_bytes(b"OggS\0\0\0\0\0\0\0\0").is_err());
+        let mut truncated = StreamIngest::new();
+        truncated.push_bytes(&wav(&[], 48000)[..30]).unwrap();
+        assert!(truncated.finish().is_err());
+    }
+}
@@ -0,0 +1,103 @@
+// Versioned JSON reports: results serialized with serde_json inside an
+// envelope naming the report kind and schema version, so stored or uploaded
+// reports can be told apart and migrated when fields change. JS gets the same
+// string from `Analyzer.analyze_json`, since results cross into JS as plain
+// objects without methods.
+//
+// JSON has no infinities: levels of silence on an unfloored scale (the
+// integrated loudness of silence, say) are written as `JSON_DB_FLOOR`, and any
+// other non-finite figure as null.
+
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+use serde::de::DeserializeOwned;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+use serde::{Deserialize, Serialize};
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+use crate::error::AnalysisError;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+use crate::typed_array::WritingJson;
+
+/// Schema version of the JSON reports; bumped when a field is renamed,
+/// removed or changes meaning
+pub const REPORT_VERSION: u32 = 1;
+
+/// Name and version of the library, recorded in every report
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub(crate) const GENERATOR: &str = concat!("lufalyze ", env!("CARGO_PKG_VERSION"));
+
+/// Level (dB) written for the -Infinity of silence, below anything a 32-bit
+/// signal reaches short of denormals
+pub const JSON_DB_FLOOR: f32 = -200.0;
+
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+#[derive(Serialize)]
+struct Report<'r, T> {
+    kind: &'static str,
+    version: u32,
+    generator: &'static str,
+    result: &'r T,
+}
+
+// A report as read back: the envelope is checked before the result is parsed
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+#[derive(Deserialize)]
+struct StoredReport {
+    kind: String,
+    version: u32,
+    result: serde_json::Value,
+}
+
+/// `result` as a versioned JSON report of the given kind
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub(crate) fn report<T: Serialize>(kind: &'static str, result: &T) -> Result<String, AnalysisError> {
+    let report = Report {
+        kind,
+        version: REPORT_VERSION,
+        generator: GENERATOR,
+        result,
+    };
+    let _writing = WritingJson::start();
+    serde_json::to_string(&report).map_err(unwritable)
+}
+
+/// `result` as a JSON value, written as in a report
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub(crate) fn to_value<T: Serialize>(result: &T) -> Result<serde_json::Value, AnalysisError> {
+    let _writing = WritingJson::start();
+    serde_json::to_value(result).map_err(unwritable)
+}
+
+// Results hold no maps with non-string keys, the one way serde_json fails
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+fn unwritable(_: serde_json::Error) -> AnalysisError {
+    AnalysisError::UnwritableReport { reason: "a result field does not serialize to JSON" }
+}
+
+/// The result of a report of the given kind written by this or an earlier
+/// schema version
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub(crate) fn read_report<T: DeserializeOwned>(kind: &'static str, json: &str) -> Result<T, AnalysisError> {
+    let report: StoredReport = serde_json::from_str(json).map_err(|_| AnalysisError::InvalidReport { reason: "not a JSON report" })?;
+    if report.kind != kind {
+        return Err(AnalysisError::InvalidReport { reason: "report of another kind" });
+    }
+    if report.version > REPORT_VERSION {
+        return Err(AnalysisError::InvalidReport { reason: "report from a newer version" });
+    }
+    serde_json::from_value(report.result).map_err(|_| AnalysisError::InvalidReport { reason: "malformed report contents" })
+}
+
+#[cfg(all(test, feature = "loudness", feature = "stereo", feature = "technical"))]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn unwritable_results_are_errors() {
+        // Maps with non-string keys have no JSON form
+        let result = BTreeMap::from([((1, 2), 3)]);
+        assert!(matches!(report("analysis", &result), Err(AnalysisError::UnwritableReport { .. })));
+        assert!(matches!(to_value(&result), Err(AnalysisError::UnwritableReport { .. })));
+        assert!(!crate::typed_array::writing_json());
+    }
+}
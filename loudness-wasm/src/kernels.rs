@@ -0,0 +1,26 @@
+// DSP kernels exposed to the benches/ suite (`bench` feature only); not part
+// of the supported API
+
+pub use crate::simd::stereo_sums;
+pub use crate::utils::{plan_fft, window, Fft, Polyphase, SincQuality};
+
+use crate::loudness::{LoudnessAnalyzer, Pcm};
+use crate::stereo::StereoAnalyzer;
+use crate::technical::TechnicalAnalyzer;
+
+/// K-weighted mean square of one block of interleaved samples
+pub fn k_weighted_block_energy(pcm: &[f32], num_channels: usize, block_size: usize) -> f32 {
+    let frames = block_size.min(pcm.len() / num_channels);
+    let segments = LoudnessAnalyzer::new(num_channels).segment_energies(Pcm::Single(&pcm[..frames * num_channels]));
+    (segments.iter().sum::<f64>() / segments.len().max(1) as f64) as f32
+}
+
+/// 4x oversampled true peak in dBTP
+pub fn true_peak(pcm: &[f32], sample_rate: f32) -> f32 {
+    TechnicalAnalyzer::new(sample_rate).calculate_true_peak(pcm).0
+}
+
+/// Phase correlation of split left/right channels
+pub fn phase_correlation(left: &[f32], right: &[f32], sample_rate: f32) -> f32 {
+    StereoAnalyzer::new(sample_rate).calculate_phase_correlation(left, right)
+}
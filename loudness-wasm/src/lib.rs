@@ -1,17 +1,234 @@
-// Module declarations
+// Module declarations; analysis sections are gated on their cargo features
+// so trimmed builds (e.g. a LUFS-only meter) leave the other DSP out entirely.
+#[cfg(feature = "loudness")]
+mod ab_match;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod analyzer;
+mod ballistics;
+#[cfg(feature = "loudness")]
+mod bed;
+#[cfg(feature = "loudness")]
+mod buckets;
+#[cfg(target_arch = "wasm32")]
+mod buffer;
+#[cfg(feature = "wav")]
+mod bwf;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod cache;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod clock;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod club;
+mod colormap;
+mod config;
 mod constants;
+#[cfg(feature = "compressed")]
+mod decode;
+mod diagnostics;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod dialogue;
+mod error;
+mod fir;
+mod gating;
+#[cfg(feature = "wav")]
+mod formats;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "technical")]
+mod hum;
+#[cfg(feature = "loudness")]
+mod ingest;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod kernels;
 mod utils;
+mod limits;
+#[cfg(feature = "loudness")]
+mod live;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod manifest;
+#[cfg(feature = "loudness")]
 mod loudness;
-mod music;
+#[cfg(feature = "technical")]
+mod null_test;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod podcast;
+#[cfg(any(feature = "technical", feature = "music"))]
+mod onset;
+#[cfg(feature = "compressed")]
+mod opus;
+mod parallel;
+mod preview;
+mod progress;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod profile;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod qc;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod reference;
+#[cfg(feature = "music")]
+mod rhythm;
+mod simd;
+#[cfg(feature = "loudness")]
+mod resample;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod segments;
+#[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+mod session;
+mod series;
+#[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+mod sidecar;
+#[cfg(all(feature = "loudness", feature = "technical"))]
+mod shard;
+#[cfg(feature = "stereo")]
 mod stereo;
+#[cfg(all(feature = "loudness", feature = "technical"))]
+mod stems;
+#[cfg(feature = "technical")]
+mod stft;
+mod streaming;
+#[cfg(feature = "technical")]
 mod technical;
+#[cfg(any(test, feature = "test-signals"))]
+mod test_signals;
+mod typed_array;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+mod vinyl;
+#[cfg(feature = "wav")]
+mod wav;
+#[cfg(feature = "technical")]
+mod weighting;
+mod window;
 
-// Re-export public interfaces
-pub use loudness::LoudnessAnalyzer;
-pub use stereo::StereoAnalyzer;
-pub use technical::TechnicalAnalyzer;
+// Re-export public interfaces, with the result types returned by the native
+// (`&[f32]`) APIs
+pub use ballistics::{Integrator, PeakHold};
+#[cfg(target_arch = "wasm32")]
+pub use buffer::{alloc_buffer, memory_bytes, reserve, PcmBuffer};
+pub use colormap::Colormap;
+pub use config::AnalyzerConfig;
+pub use diagnostics::set_log_level;
+pub use error::{sanitize_pcm, AnalysisError};
+pub use fir::{windowed_sinc, FirFilter};
+pub use gating::{block_loudness, blockize, Gate, GatedBlocks};
+pub use limits::{AnalysisLimits, LimitsReport, MetricStatus, Quality};
+pub use preview::{decimate_preview, preview_factor, PreviewAudio, PREVIEW_MAX_RATE};
+pub use progress::CancellationToken;
+pub use series::{SeriesPage, TimeSeries};
+pub use streaming::{MeterSnapshot, StreamingAnalyzer};
+pub use utils::{ema_smooth, median, median_absolute_deviation, median_smooth, percentile, trimmed_mean, DbScale};
+#[cfg(any(feature = "loudness", feature = "music"))]
+pub use utils::{Biquad, BiquadSample};
+#[cfg(any(feature = "technical", feature = "music"))]
+pub use utils::real_cepstrum;
+pub use window::{Window, DEFAULT_KAISER_BETA};
 
-// Module-based architecture for professional audio analysis WASM library
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use analyzer::{AlbumResult, AnalysisResult, Analyzer, BatchResult};
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use club::{ClubAnalyzer, ClubReport};
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use dialogue::{analyze_dialogue, DialogueReport, DialogueSection};
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use manifest::{BatchManifest, Manifest, ManifestEntry};
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use podcast::{analyze_podcast, FixPriority, PodcastCheck, PodcastFix, PodcastReport, PodcastRule, PODCAST_MONO_TARGET, PODCAST_STEREO_TARGET};
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use profile::{ProfileItem, ProfileMetric, ProfileReport, ProfileRule, QcProfile};
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use qc::{Finding, QcReport, QcReportBuilder, ReportSection, Severity, WindowedIssue};
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use vinyl::{check_vinyl, VinylReport};
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use reference::{BandDelta, MetricDelta, ReferenceComparison, ReferenceDeltas};
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use segments::{SegmentResult, SegmentedResult};
+#[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use session::{Session, SESSION_SERIES_FRAMES};
+#[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+pub use sidecar::{Sidecar, SIDECAR_EXTENSION, SIDECAR_VERSION};
+#[cfg(feature = "loudness")]
+pub use ab_match::{AbMatch, AbMatcher, AbSide};
+#[cfg(feature = "loudness")]
+pub use bed::{BedChannel, BedLoudnessAnalyzer, BedLoudnessResult};
+#[cfg(feature = "loudness")]
+pub use buckets::{AssetLoudness, BucketMetric, BucketReport, BucketSummary, LoudnessBucket, LoudnessBuckets};
+#[cfg(feature = "loudness")]
+pub use ingest::{IngestResult, StreamIngest};
+#[cfg(feature = "loudness")]
+pub use live::LiveMeter;
+#[cfg(feature = "loudness")]
+pub use loudness::{LoudnessAnalyzer, LoudnessResult};
+#[cfg(feature = "loudness")]
+pub use streaming::{LoudnessSnapshot, LoudnessStream};
+#[cfg(feature = "technical")]
+pub use hum::{detect_hum, HumResult};
+#[cfg(feature = "technical")]
+pub use weighting::{measure_leq, LeqResult, Weighting, WeightingFilter};
+#[cfg(feature = "technical")]
+pub use null_test::{BandResidual, NullTestAnalyzer, NullTestResult};
+#[cfg(any(feature = "technical", feature = "music"))]
+pub use onset::{OnsetDetector, OnsetResult};
+#[cfg(feature = "music")]
+pub use rhythm::{ClickConformanceResult, RhythmAnalyzer, RhythmResult};
+#[cfg(all(feature = "loudness", feature = "technical"))]
+pub use shard::{plan_shards, ShardAnalyzer, ShardPlan, ShardRange, ShardSet, ShardStats, ShardedResult};
+#[cfg(all(feature = "loudness", feature = "technical"))]
+pub use stems::{MaskingHotspot, StemAnalyzer, StemContribution, StemReport};
+#[cfg(feature = "stereo")]
+pub use stereo::{StereoAnalyzer, StereoResult};
+#[cfg(feature = "stereo")]
+pub use streaming::{StereoSnapshot, StereoStream};
+#[cfg(feature = "technical")]
+pub use technical::{TechnicalAnalyzer, TechnicalResult};
+#[cfg(feature = "technical")]
+pub use streaming::{TechnicalSnapshot, TechnicalStream};
+#[cfg(feature = "test-signals")]
+pub use test_signals::{Expectation, Metric, ReferenceSignal, REFERENCE_SIGNALS, TEST_SIGNAL_CHANNELS, TEST_SIGNAL_RATE};
+#[cfg(all(feature = "test-signals", feature = "technical"))]
+pub use test_signals::{run_self_test, SelfTestCase, SelfTestReport};
+#[cfg(feature = "wav")]
+pub use formats::extract_pcm;
+#[cfg(feature = "wav")]
+pub use wav::{decode_wav, WavFile};
+#[cfg(feature = "wav")]
+pub use bwf::{
+    adm_loudness_metadata, bext_loudness_chunk, check_broadcast_metadata, read_broadcast_metadata, BroadcastMetadata, EmbeddedLoudness, LoudnessMetric,
+    MeasuredLoudness, MetadataCheck, MetadataDiscrepancy, MetadataSource,
+};
+#[cfg(feature = "compressed")]
+pub use decode::{decode_audio, AudioFile, SourceInfo};
+#[cfg(feature = "json")]
+pub use json::{JSON_DB_FLOOR, REPORT_VERSION};
+
+// Module-based architecture for professional audio analysis WASM library.
+// Everything JS-specific is gated on wasm32; native builds expose the same
+// analyzers through plain Rust APIs taking `&[f32]`.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// Whether this build was compiled with the SIMD128 kernels (`simd` feature)
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn simd_enabled() -> bool {
+    simd::simd_enabled()
+}
+
+/// Whether this build includes the panic hook and console logger (`debug` feature)
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn debug_enabled() -> bool {
+    diagnostics::debug_enabled()
+}
+
+/// Whether this build runs analysis on a worker thread pool (`threads` feature)
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn threads_enabled() -> bool {
+    parallel::threads_enabled()
+}
+
+// Thread pool initialisation exported to JS as `initThreadPool(numThreads)`
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
 
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
@@ -0,0 +1,273 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+#[cfg(feature = "technical")]
+use crate::utils::SincQuality;
+
+// Default analysis cap in seconds (previously hard-coded per analyzer)
+const DEFAULT_MAX_DURATION: f32 = 60.0;
+// Cap for the fast preset, aimed at mobile devices
+const FAST_MAX_DURATION: f32 = 30.0;
+
+// Bytes of working memory per input sample: the copy into WASM memory plus,
+// for stereo, the split left/right buffers
+#[cfg(any(feature = "technical", all(target_arch = "wasm32", feature = "stereo")))]
+pub const BYTES_PER_SAMPLE: usize = 4;
+#[cfg(feature = "stereo")]
+pub const STEREO_BYTES_PER_SAMPLE: usize = 8;
+
+/// Limits applied to an analysis; unlimited settings are null
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct LimitsReport {
+    pub max_duration: Option<f32>,
+    pub decimation: usize,
+    pub memory_ceiling: Option<usize>,
+    pub analyzed_duration: f32,
+    pub truncated: bool,
+}
+
+// Share of silent material above which a metric is flagged silence-dominated
+#[cfg(any(feature = "loudness", feature = "stereo", feature = "music"))]
+const SILENCE_DOMINATED_SHARE: f32 = 0.5;
+
+/// Whether a metric can be trusted; when several conditions hold the most
+/// severe (listed first) is reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub enum MetricStatus {
+    Ok,
+    InsufficientDuration,
+    SilenceDominated,
+    TruncatedToLimit,
+}
+
+impl MetricStatus {
+    // Status of a metric measured over too little input, over material that
+    // is `silent_share` silent, or over a limited prefix of the input
+    #[cfg(any(feature = "loudness", feature = "stereo", feature = "music"))]
+    pub(crate) fn assess(too_short: bool, silent_share: f32, truncated: bool) -> Self {
+        if too_short {
+            MetricStatus::InsufficientDuration
+        } else if silent_share > SILENCE_DOMINATED_SHARE {
+            MetricStatus::SilenceDominated
+        } else if truncated {
+            MetricStatus::TruncatedToLimit
+        } else {
+            MetricStatus::Ok
+        }
+    }
+}
+
+// Explicit duration / decimation / memory limits shared by the analyzers.
+// A zero duration or memory ceiling means unlimited.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnalysisLimits {
+    max_duration: f32,
+    decimation: usize,
+    memory_ceiling: usize,
+}
+
+impl Default for AnalysisLimits {
+    fn default() -> Self {
+        AnalysisLimits {
+            max_duration: DEFAULT_MAX_DURATION,
+            decimation: 1,
+            memory_ceiling: 0,
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl AnalysisLimits {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        AnalysisLimits::default()
+    }
+
+    // Longest stretch (seconds) analysed by the windowed stereo/spectral passes
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_max_duration(&mut self, seconds: f32) {
+        self.max_duration = seconds.max(0.0);
+    }
+
+    // Analyse every Nth frame (stereo) or window (spectral)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_decimation(&mut self, factor: usize) {
+        self.decimation = factor.max(1);
+    }
+
+    // Upper bound in bytes on the PCM working set copied into WASM memory
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_memory_ceiling(&mut self, bytes: usize) {
+        self.memory_ceiling = bytes;
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn max_duration(&self) -> f32 {
+        self.max_duration
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn decimation(&self) -> usize {
+        self.decimation
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn memory_ceiling(&self) -> usize {
+        self.memory_ceiling
+    }
+}
+
+impl AnalysisLimits {
+    /// Limits matching a quality preset
+    pub fn for_quality(quality: Quality) -> Self {
+        match quality {
+            Quality::Fast => AnalysisLimits { max_duration: FAST_MAX_DURATION, decimation: 2, memory_ceiling: 0 },
+            Quality::Balanced => AnalysisLimits::default(),
+            Quality::Accurate => AnalysisLimits { max_duration: 0.0, decimation: 1, memory_ceiling: 0 },
+        }
+    }
+
+    /// Number of input samples that fit the memory ceiling
+    pub fn max_samples(&self, total: usize, bytes_per_sample: usize) -> usize {
+        if self.memory_ceiling == 0 {
+            total
+        } else {
+            total.min(self.memory_ceiling / bytes_per_sample)
+        }
+    }
+
+    /// Number of frames covered by the duration cap
+    pub fn max_frames(&self, total: usize, sample_rate: f32) -> usize {
+        if self.max_duration <= 0.0 {
+            total
+        } else {
+            total.min((self.max_duration * sample_rate) as usize)
+        }
+    }
+
+    /// Limits as applied to one analysis, for the result object
+    pub fn report(&self, analyzed_seconds: f32, total_seconds: f32) -> LimitsReport {
+        LimitsReport {
+            max_duration: (self.max_duration > 0.0).then_some(self.max_duration),
+            decimation: self.decimation,
+            memory_ceiling: (self.memory_ceiling > 0).then_some(self.memory_ceiling),
+            analyzed_duration: analyzed_seconds,
+            truncated: analyzed_seconds < total_seconds,
+        }
+    }
+}
+
+// Speed/precision presets: FFT size, window hop, true-peak oversampling and
+// the duration/decimation limits move together. Balanced is the historical
+// behaviour.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Quality {
+    Fast,
+    #[default]
+    Balanced,
+    Accurate,
+}
+
+impl Quality {
+    /// Preset by name ("fast", "balanced", "accurate")
+    pub fn from_name(name: &str) -> Option<Quality> {
+        match name.to_ascii_lowercase().as_str() {
+            "fast" => Some(Quality::Fast),
+            "balanced" => Some(Quality::Balanced),
+            "accurate" => Some(Quality::Accurate),
+            _ => None,
+        }
+    }
+
+    /// Spectral analysis window (and FFT) size
+    pub fn fft_size(self) -> usize {
+        match self {
+            Quality::Fast => 1024,
+            Quality::Balanced => 2048,
+            Quality::Accurate => 4096,
+        }
+    }
+
+    /// Hop between analysis windows (spectral, stereo coherence); balanced
+    /// drops the overlap on files over 10 seconds at `sample_rate`
+    pub fn spectral_hop(self, window_size: usize, analysis_length: usize, sample_rate: f32) -> usize {
+        match self {
+            Quality::Fast => window_size,
+            Quality::Balanced if analysis_length as f32 > 10.0 * sample_rate => window_size,
+            Quality::Balanced | Quality::Accurate => window_size / 2,
+        }
+    }
+
+    /// Seconds from the start the mastering (punchiness) estimate reads;
+    /// accurate reads the whole input
+    pub fn mastering_window(self) -> f32 {
+        match self {
+            Quality::Fast => 15.0,
+            Quality::Balanced => 30.0,
+            Quality::Accurate => f32::INFINITY,
+        }
+    }
+
+    /// Oversampling factor for true-peak detection
+    pub fn true_peak_oversampling(self) -> usize {
+        match self {
+            Quality::Fast => 2,
+            Quality::Balanced => 4,
+            Quality::Accurate => 8,
+        }
+    }
+
+    // Windowed-sinc filter for resampling and true-peak oversampling
+    #[cfg(feature = "technical")]
+    pub(crate) fn resampling(self) -> SincQuality {
+        match self {
+            Quality::Fast => SincQuality::Fast,
+            Quality::Balanced => SincQuality::Balanced,
+            Quality::Accurate => SincQuality::Accurate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_means_unlimited() {
+        let mut limits = AnalysisLimits::new();
+        assert_eq!(limits.max_frames(10_000_000, 44100.0), 44100 * 60);
+
+        limits.set_max_duration(0.0);
+        limits.set_memory_ceiling(4000);
+        assert_eq!(limits.max_frames(10_000_000, 44100.0), 10_000_000);
+        assert_eq!(limits.max_samples(10_000_000, 8), 500);
+    }
+
+    #[test]
+    fn balanced_preset_keeps_defaults() {
+        assert_eq!(AnalysisLimits::for_quality(Quality::Balanced), AnalysisLimits::default());
+        assert_eq!(Quality::from_name("Accurate"), Some(Quality::Accurate));
+        assert_eq!(Quality::Balanced.mastering_window(), 30.0);
+        // "Long" is in seconds: 8 s at 96 kHz keeps the overlap, 11 s drops it
+        assert_eq!(Quality::Balanced.spectral_hop(512, 8 * 96000, 96000.0), 256);
+        assert_eq!(Quality::Balanced.spectral_hop(512, 11 * 96000, 96000.0), 512);
+        assert_eq!(AnalysisLimits::for_quality(Quality::Accurate).max_frames(10_000_000, 44100.0), 10_000_000);
+        assert!(Quality::Fast.fft_size() < Quality::Accurate.fft_size());
+    }
+
+    #[test]
+    #[cfg(any(feature = "loudness", feature = "stereo", feature = "music"))]
+    fn most_severe_status_wins() {
+        assert_eq!(MetricStatus::assess(true, 1.0, true), MetricStatus::InsufficientDuration);
+        assert_eq!(MetricStatus::assess(false, 0.8, true), MetricStatus::SilenceDominated);
+        assert_eq!(MetricStatus::assess(false, 0.2, true), MetricStatus::TruncatedToLimit);
+        assert_eq!(MetricStatus::assess(false, 0.0, false), MetricStatus::Ok);
+    }
+}
@@ -0,0 +1,273 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use std::collections::VecDeque;
+use crate::ballistics::{Integrator, PeakHold};
+use crate::streaming::MeterSnapshot;
+use crate::utils::{amplitude_to_db, KWeighting, Polyphase, SincQuality};
+
+// Momentary and short-term loudness windows (ITU-R BS.1770) in seconds
+const MOMENTARY_SECONDS: f32 = 0.4;
+const SHORT_TERM_SECONDS: f32 = 3.0;
+
+// True peak oversampling factor
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+// Default peak hold time and correlation integration time in seconds
+const DEFAULT_PEAK_HOLD_SECONDS: f32 = 2.0;
+const CORRELATION_SECONDS: f32 = 0.3;
+
+// Sliding window of per-frame weighted power (summed over channels); the
+// running sum is kept in f64 so add/subtract rounding stays negligible over
+// long sessions
+struct PowerWindow {
+    powers: VecDeque<f32>,
+    len: usize,
+    sum: f64,
+}
+
+impl PowerWindow {
+    fn new(len: usize) -> Self {
+        PowerWindow { powers: VecDeque::with_capacity(len), len, sum: 0.0 }
+    }
+
+    // Slide the window forward by one frame
+    fn push(&mut self, power: f64) {
+        if self.powers.len() == self.len {
+            if let Some(oldest) = self.powers.pop_front() {
+                self.sum -= oldest as f64;
+            }
+        }
+        self.powers.push_back(power as f32);
+        self.sum += power;
+    }
+
+    // Loudness (LUFS) of the window, or of the frames so far while it fills
+    fn loudness(&self) -> f32 {
+        if self.powers.is_empty() {
+            return f32::NEG_INFINITY;
+        }
+        let mean = (self.sum.max(0.0) / self.powers.len() as f64) as f32;
+        -0.691 + 10.0 * (mean + 1e-10).log10()
+    }
+}
+
+// Low-latency meter for live input, driven from an AudioWorkletProcessor with
+// small blocks (128-2048 frames). Filter, window and oversampler state carry
+// over between blocks and nothing is allocated per block once the window fills.
+// The true peak lags the input by the oversampler's reach (under 1 ms).
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct LiveMeter {
+    sample_rate: f32,
+    num_channels: usize,
+    // K-weighting filter per channel
+    filters: Vec<KWeighting>,
+    momentary: PowerWindow,
+    short_term: PowerWindow,
+    // Band-limited true peak oversampler (as in the batch measure) and its
+    // input history per channel, each sample written twice so the newest
+    // `taps_per_phase` are contiguous
+    oversampler: Polyphase,
+    history: Vec<Vec<f32>>,
+    history_pos: usize,
+    // True peak amplitude, held and then dropped (no decay by default)
+    peak: PeakHold,
+    // Exponentially averaged stereo sums (l·r, l², r²)
+    correlation_sums: [Integrator; 3],
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl LiveMeter {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        let num_channels = num_channels.max(1);
+        let window_len = |seconds: f32| ((sample_rate * seconds) as usize).max(1);
+        let oversampler = Polyphase::oversampler(TRUE_PEAK_OVERSAMPLING, SincQuality::Balanced);
+        LiveMeter {
+            sample_rate,
+            num_channels,
+            filters: vec![KWeighting::new(sample_rate); num_channels],
+            momentary: PowerWindow::new(window_len(MOMENTARY_SECONDS)),
+            short_term: PowerWindow::new(window_len(SHORT_TERM_SECONDS)),
+            history: vec![vec![0.0; 2 * oversampler.taps_per_phase()]; num_channels],
+            history_pos: 0,
+            oversampler,
+            peak: PeakHold::new(DEFAULT_PEAK_HOLD_SECONDS, f32::INFINITY, sample_rate),
+            correlation_sums: [Integrator::new(CORRELATION_SECONDS, CORRELATION_SECONDS, sample_rate); 3],
+        }
+    }
+
+    // How long the true peak reading is held before it falls back to the current level
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_peak_hold(&mut self, seconds: f32) {
+        self.peak.set_hold(seconds, self.sample_rate);
+    }
+
+    // How fast (dB/s) the true peak reading falls once the hold time is up;
+    // Infinity (the default) drops straight back to the current level
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_peak_decay(&mut self, db_per_second: f32) {
+        self.peak.set_decay(db_per_second, self.sample_rate);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn reset_peak(&mut self) {
+        self.peak.reset();
+    }
+
+    // Feed one block of interleaved samples (copied straight into WASM memory)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn process(&mut self, block: &[f32]) {
+        for frame in block.chunks_exact(self.num_channels) {
+            let mut power = 0.0;
+            let mut frame_peak: f32 = 0.0;
+
+            for (ch, &sample) in frame.iter().enumerate() {
+                let weighted = self.filters[ch].process(sample);
+                power += weighted * weighted;
+                frame_peak = frame_peak.max(self.intersample_peak(ch, sample));
+            }
+
+            self.history_pos = (self.history_pos + 1) % self.oversampler.taps_per_phase();
+            self.momentary.push(power as f64);
+            self.short_term.push(power as f64);
+            self.peak.process(frame_peak);
+            if self.num_channels >= 2 {
+                self.update_correlation(frame[0], frame[1]);
+            }
+        }
+    }
+
+    // Momentary loudness (LUFS) over the last 400 ms
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn momentary(&self) -> f32 {
+        self.momentary.loudness()
+    }
+
+    // Short-term loudness (LUFS) over the last 3 s
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn short_term(&self) -> f32 {
+        self.short_term.loudness()
+    }
+
+    // Held true peak in dBTP
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn true_peak(&self) -> f32 {
+        amplitude_to_db(self.peak.value())
+    }
+
+    // Running phase correlation (-1 to +1); 0 for mono input or silence
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn correlation(&self) -> f32 {
+        let [lr, ll, rr] = self.correlation_sums.map(|sum| sum.value());
+        let denominator = (ll * rr).sqrt();
+        if denominator > 1e-10 {
+            (lr / denominator).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    // Running stereo width (0 mono to 1 wide); 0 for mono input or silence
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn width(&self) -> f32 {
+        // Side over mid + side energy, doubled: (l² + r² - 2·l·r) / (l² + r²)
+        let [lr, ll, rr] = self.correlation_sums.map(|sum| sum.value());
+        let total = ll + rr;
+        if total > 1e-10 {
+            ((total - 2.0 * lr) / total).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    // All readings at once, for meter UIs polling at 10-30 Hz
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = get_meter_snapshot))]
+    pub fn meter_snapshot(&self) -> MeterSnapshot {
+        MeterSnapshot {
+            momentary: self.momentary(),
+            short_term: self.short_term(),
+            true_peak: self.true_peak(),
+            correlation: self.correlation(),
+            width: self.width(),
+        }
+    }
+
+    // Largest magnitude of the oversampled points following the sample at
+    // the middle of the channel's history, once `sample` joins it
+    fn intersample_peak(&mut self, ch: usize, sample: f32) -> f32 {
+        let width = self.oversampler.taps_per_phase();
+        let history = &mut self.history[ch];
+        history[self.history_pos] = sample;
+        history[self.history_pos + width] = sample;
+        let window = &history[self.history_pos + 1..=self.history_pos + width];
+        (0..self.oversampler.up())
+            .map(|phase| self.oversampler.phase(phase, window).abs())
+            .fold(0.0, f32::max)
+    }
+
+    fn update_correlation(&mut self, left: f32, right: f32) {
+        let [lr, ll, rr] = &mut self.correlation_sums;
+        lr.process(left * right);
+        ll.process(left * left);
+        rr.process(right * right);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meters_track_a_stereo_sine() {
+        let sample_rate = 44100.0;
+        let mut meter = LiveMeter::new(sample_rate, 2);
+        let mut phase = 0usize;
+
+        for _ in 0..400 {
+            let block: Vec<f32> = (0..128)
+                .flat_map(|i| {
+                    let x = 0.5 * (2.0 * std::f32::consts::PI * 997.0 * (phase + i) as f32 / sample_rate).sin();
+                    [x, x]
+                })
+                .collect();
+            phase += 128;
+            meter.process(&block);
+        }
+
+        assert!((meter.correlation() - 1.0).abs() < 1e-3);
+        assert!((meter.true_peak() - amplitude_to_db(0.5)).abs() < 0.1);
+        assert!(meter.momentary().is_finite() && meter.momentary() < 0.0);
+
+        let snapshot = meter.meter_snapshot();
+        assert_eq!((snapshot.momentary, snapshot.true_peak), (meter.momentary(), meter.true_peak()));
+        // 1.16 s of a steady sine: the partly filled short-term window reads the same level
+        assert!((snapshot.short_term - snapshot.momentary).abs() < 0.1);
+        assert!(snapshot.width < 1e-3);
+    }
+
+    #[test]
+    fn true_peak_finds_intersample_peaks() {
+        // A quarter-rate sine sampled 45° off its crests: every sample sits 3 dB under the peak
+        let mut meter = LiveMeter::new(48000.0, 1);
+        let block: Vec<f32> = (0..4800).map(|i| 0.5 * (std::f32::consts::FRAC_PI_2 * i as f32 + std::f32::consts::FRAC_PI_4).sin()).collect();
+        for chunk in block.chunks(128) {
+            meter.process(chunk);
+        }
+        // Past the ringing of the abrupt start
+        meter.reset_peak();
+        meter.process(&block[..128]);
+        assert!((meter.true_peak() - amplitude_to_db(0.5)).abs() < 0.05);
+    }
+
+    #[test]
+    fn width_tracks_side_energy() {
+        let mut meter = LiveMeter::new(48000.0, 2);
+        let block: Vec<f32> = (0..48000).flat_map(|i| {
+            let x = 0.5 * (i as f32 * 0.05).sin();
+            [x, -x]
+        }).collect();
+        meter.process(&block);
+        assert!((meter.width() - 1.0).abs() < 1e-3);
+        assert!((meter.correlation() + 1.0).abs() < 1e-3);
+    }
+}
@@ -1,188 +1,555 @@
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
-use js_sys::Float32Array;
+#[cfg(target_arch = "wasm32")]
+use js_sys::{Float32Array, Float64Array, Function, Int16Array, Int32Array};
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+#[cfg(target_arch = "wasm32")]
+use crate::buffer::PcmBuffer;
+use crate::config::AnalyzerConfig;
 use crate::constants::*;
+use crate::error::{validate_bit_depth, validate_pcm, AnalysisError};
+use crate::gating::{block_count, block_loudness, blockize, Gate};
+use crate::limits::MetricStatus;
+use crate::parallel::map_range;
+#[cfg(target_arch = "wasm32")]
+use crate::progress::js_progress;
+use crate::progress::{CancellationToken, Progress};
+use crate::resample::{Resampler, ResamplingReport};
+use crate::series::TimeSeries;
+use crate::utils::{int_to_f32, int_to_f64, BiquadSample, DbScale, KWeighting};
 
-#[wasm_bindgen]
+/// EBU R128 loudness measurements (LUFS) with gating diagnostics
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct LoudnessResult {
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub pcm_debug: Vec<f32>,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub block_energy_debug: Vec<f32>,
+    #[serde(serialize_with = "crate::utils::serialize_level")]
+    pub momentary: f32,
+    #[serde(rename = "shortTerm", serialize_with = "crate::utils::serialize_level")]
+    pub short_term: f32,
+    #[serde(serialize_with = "crate::utils::serialize_level")]
+    pub integrated: f32,
+    // EBU Tech 3342 loudness range (LU); shares the short-term trust flag
+    pub loudness_range: f32,
+    #[serde(serialize_with = "crate::utils::serialize_level")]
+    pub preliminary_loudness: f32,
+    #[serde(serialize_with = "crate::utils::serialize_level")]
+    pub gate_threshold: f32,
+    pub abs_gated_blocks: usize,
+    pub rel_gated_blocks: usize,
+    #[serde(rename = "totalBlocks")]
+    pub total_blocks: usize,
+    pub status: LoudnessStatus,
+    // Set when the input was resampled to the 44.1kHz the block sizes and
+    // K-weighting coefficients are defined at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub resampling: Option<ResamplingReport>,
+}
+
+/// Trust flags of the loudness measurements: short-term and integrated
+/// loudness need at least one 3s block, and a measurement is silence-dominated
+/// when most of its blocks fall below the absolute gate
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct LoudnessStatus {
+    pub momentary: MetricStatus,
+    #[serde(rename = "shortTerm")]
+    pub short_term: MetricStatus,
+    pub integrated: MetricStatus,
+}
+
+// Interleaved input in either precision
+#[derive(Clone, Copy)]
+pub(crate) enum Pcm<'a> {
+    Single(&'a [f32]),
+    Double(&'a [f64]),
+}
+
+impl Pcm<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Pcm::Single(samples) => samples.len(),
+            Pcm::Double(samples) => samples.len(),
+        }
+    }
+
+    fn head(&self, count: usize) -> Vec<f32> {
+        match self {
+            Pcm::Single(samples) => samples.iter().take(count).copied().collect(),
+            Pcm::Double(samples) => samples.iter().take(count).map(|&sample| sample as f32).collect(),
+        }
+    }
+
+    fn resample(&self, resampler: &Resampler, num_channels: usize) -> OwnedPcm {
+        match self {
+            Pcm::Single(samples) => OwnedPcm::Single(resampler.process(samples, num_channels)),
+            Pcm::Double(samples) => OwnedPcm::Double(resampler.process_f64(samples, num_channels)),
+        }
+    }
+}
+
+// Resampled copy of `Pcm` input
+enum OwnedPcm {
+    Single(Vec<f32>),
+    Double(Vec<f64>),
+}
+
+impl OwnedPcm {
+    fn as_pcm(&self) -> Pcm<'_> {
+        match self {
+            OwnedPcm::Single(samples) => Pcm::Single(samples),
+            OwnedPcm::Double(samples) => Pcm::Double(samples),
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 pub struct LoudnessAnalyzer {
     num_channels: usize,
+    sample_rate: f32,
+    double_precision: bool,
+    // Floor of the reported levels; silence reads -Infinity by default
+    db: DbScale,
+    cancel: Option<CancellationToken>,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 impl LoudnessAnalyzer {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
     pub fn new(num_channels: usize) -> Self {
-        LoudnessAnalyzer { num_channels }
+        LoudnessAnalyzer { num_channels, sample_rate: BLOCK_SAMPLE_RATE, double_precision: false, db: DbScale::default(), cancel: None }
     }
 
-    fn calculate_block_energy(&self, pcm: &Float32Array, start: usize, block_size: usize) -> f32 {
-        let mut energy = 0.0;
-        
-        // Process each channel separately, then sum
-        for ch in 0..self.num_channels {
-            let mut x1 = 0.0;
-            let mut x2 = 0.0;
-            let mut y1 = 0.0;
-            let mut y2 = 0.0;
-            
-            for i in 0..block_size {
-                let idx = (start + i) * self.num_channels + ch;
-                let sample = if idx < pcm.length() as usize {
-                    pcm.get_index(idx as u32)
-                } else {
-                    0.0
-                };
-                
-                // Apply K-weighting filter
-                let filtered = K_B[0] * sample + K_B[1] * x1 + K_B[2] * x2 - K_A[1] * y1 - K_A[2] * y2;
-                
-                // Update filter state
-                x2 = x1;
-                x1 = sample;
-                y2 = y1;
-                y1 = filtered;
-                
-                // Accumulate energy for this channel
-                energy += filtered * filtered;
-            }
-        }
-        
-        // Return mean square energy
-        energy / (block_size as f32 * self.num_channels as f32)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        let mut analyzer = LoudnessAnalyzer::new(config.num_channels());
+        analyzer.set_sample_rate(config.sample_rate());
+        analyzer.db = config.db_scale();
+        analyzer
     }
 
-    fn process_blocks(&self, pcm: &Float32Array, block_size: usize, hop: usize) -> Vec<f32> {
-        let samples_per_channel = pcm.length() as usize / self.num_channels;
-        let mut block_energies = Vec::new();
-        
-        let mut i = 0;
-        while i + block_size <= samples_per_channel {
-            let energy = self.calculate_block_energy(pcm, i, block_size);
-            let loudness = -0.691 + 10.0 * (energy + 1e-10).log10();
-            
-            // Only include blocks above absolute gate
-            if loudness >= ABSOLUTE_GATE {
-                block_energies.push(energy);
-            }
-            
-            i += hop;
-        }
-        
-        block_energies
+    // Input at any other rate than the 44.1kHz the block sizes and filter
+    // assume is resampled before measuring (`new` assumes 44.1kHz input)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
     }
 
-    fn calculate_integrated_loudness(&self, energies: &[f32]) -> f32 {
-        if energies.is_empty() {
-            return f32::NEG_INFINITY;
-        }
-        
-        // First stage: absolute gating (already done in process_blocks)
-        let abs_gated = energies;
-        
-        // Calculate preliminary loudness
-        let preliminary_mean = abs_gated.iter().sum::<f32>() / abs_gated.len() as f32;
-        let preliminary_loudness = -0.691 + 10.0 * (preliminary_mean + 1e-10).log10();
-        
-        // Second stage: relative gating
-        let relative_threshold = preliminary_loudness + RELATIVE_GATE;
-        let rel_gated: Vec<f32> = abs_gated.iter()
-            .filter(|&&energy| {
-                let loudness = -0.691 + 10.0 * (energy + 1e-10).log10();
-                loudness >= relative_threshold
-            })
-            .copied()
+    // Run the K-weighting filter in f64 for single-precision input too
+    // (double-precision input always is; energy sums are f64 either way)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_double_precision(&mut self, enabled: bool) {
+        self.double_precision = enabled;
+    }
+
+    // Attach a token that aborts analyses at the next checkpoint when tripped
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_cancellation_token(&mut self, token: &CancellationToken) {
+        self.cancel = Some(token.clone());
+    }
+
+    // Mean K-weighted energy of every whole `SEGMENT` of `pcm`, summed over
+    // channels, with one filter per channel running across the whole signal.
+    // Channels are filtered independently, so in parallel; the filter runs in
+    // the precision the input and settings call for
+    pub(crate) fn segment_energies(&self, pcm: Pcm) -> Vec<f64> {
+        let n = self.num_channels;
+        let channels = match pcm {
+            Pcm::Single(samples) if !self.double_precision => map_range(0..n, |ch| {
+                ChannelEnergy::<f32>::new().segments(samples.iter().skip(ch).step_by(n).copied())
+            }),
+            Pcm::Single(samples) => map_range(0..n, |ch| {
+                ChannelEnergy::<f64>::new().segments(samples.iter().skip(ch).step_by(n).map(|&sample| sample as f64))
+            }),
+            Pcm::Double(samples) => map_range(0..n, |ch| {
+                ChannelEnergy::<f64>::new().segments(samples.iter().skip(ch).step_by(n).copied())
+            }),
+        };
+        sum_channels(&channels, &vec![1.0; n])
+    }
+
+    // Loudness (LUFS) of every block, ungated, so the maximum matches the
+    // reported momentary / short-term value
+    fn loudness_history(&self, pcm: &[f32], block_size: usize, hop: usize) -> TimeSeries {
+        let resampled = self.resampler().map(|resampler| resampler.process(pcm, self.num_channels));
+        let pcm = resampled.as_deref().unwrap_or(pcm);
+        let segments = self.segment_energies(Pcm::Single(pcm));
+        let loudness: Vec<f32> = block_energies(&segments, usize::MAX, block_size, hop)
+            .into_iter()
+            .map(block_loudness)
             .collect();
-        
-        if rel_gated.is_empty() {
-            return f32::NEG_INFINITY;
-        }
-        
-        // Calculate final integrated loudness
-        let final_mean = rel_gated.iter().sum::<f32>() / rel_gated.len() as f32;
-        -0.691 + 10.0 * (final_mean + 1e-10).log10()
+        TimeSeries::new(hop as f32 / BLOCK_SAMPLE_RATE, 1, loudness)
+    }
+
+    pub(crate) fn calculate_integrated_loudness(&self, energies: &[f32]) -> f32 {
+        integrated_loudness(energies)
     }
 
-    fn calculate_max_loudness(&self, energies: &[f32]) -> f32 {
+    pub(crate) fn calculate_max_loudness(&self, energies: &[f32]) -> f32 {
         if energies.is_empty() {
             return f32::NEG_INFINITY;
         }
         
         energies.iter()
-            .map(|&energy| -0.691 + 10.0 * (energy + 1e-10).log10())
+            .map(|&energy| block_loudness(energy))
             .fold(f32::NEG_INFINITY, f32::max)
     }
 
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze)]
+    pub fn analyze_js(&self, pcm: &Float32Array, on_progress: Option<Function>) -> Result<LoudnessResult, JsError> {
+        // Copy PCM into WASM memory once instead of crossing the JS boundary per sample
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze(&pcm.to_vec(), callback.as_deref())?)
+    }
+
+    // Analyse samples already written into WASM memory (no copy)
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen]
-    pub fn analyze(&self, pcm: &Float32Array) -> JsValue {
-        // Collect debug PCM values
-        let mut pcm_debug = Vec::new();
-        for i in 0..5.min(pcm.length()) {
-            pcm_debug.push(pcm.get_index(i));
-        }
+    pub fn analyze_buffer(&self, buffer: &PcmBuffer, on_progress: Option<Function>) -> Result<LoudnessResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze(buffer.as_slice(), callback.as_deref())?)
+    }
 
-        // Process momentary blocks (400ms)
-        let momentary_energies = self.process_blocks(pcm, MOMENTARY_BLOCK_SIZE, MOMENTARY_HOP);
-        let momentary_max = self.calculate_max_loudness(&momentary_energies);
-        
-        // Process short-term blocks (3s)
-        let short_term_energies = self.process_blocks(pcm, SHORT_TERM_BLOCK_SIZE, SHORT_TERM_HOP);
-        let short_term_max = self.calculate_max_loudness(&short_term_energies);
-        
-        // Calculate integrated loudness
-        let integrated_loudness = self.calculate_integrated_loudness(&momentary_energies);
-        
-        // Apply volume-dependent calibration for better precision across all levels
-        // Lower volume files need different corrections due to gating and noise floor effects
-        
-        // Volume-dependent integrated loudness calibration
-        let integrated_offset = if integrated_loudness > -15.0 {
-            0.77  // High volume: sample2.wav range
-        } else if integrated_loudness > -22.0 {
-            0.29  // Medium volume: sample.wav range  
-        } else {
-            1.58  // Low volume: sample3.wav range (needs more correction)
-        };
-        
-        // Volume-dependent short-term calibration
-        let short_term_offset = if short_term_max > -12.0 {
-            0.41  // High volume
-        } else if short_term_max > -18.0 {
-            0.34  // Medium volume
-        } else {
-            2.40  // Low volume (needs significant correction)
-        };
-        
-        // Volume-dependent momentary calibration
-        let momentary_offset = if momentary_max > -8.0 {
-            0.53  // High volume
-        } else if momentary_max > -15.0 {
-            0.97  // Medium volume
+    // Double-precision input, filtered and summed in f64
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_f64)]
+    pub fn analyze_f64_js(&self, pcm: &Float64Array, on_progress: Option<Function>) -> Result<LoudnessResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_f64(&pcm.to_vec(), callback.as_deref())?)
+    }
+
+    // 16-bit integer PCM, scaled to -1..1 inside the module
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_i16)]
+    pub fn analyze_i16_js(&self, pcm: &Int16Array, on_progress: Option<Function>) -> Result<LoudnessResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_i16(&pcm.to_vec(), callback.as_deref())?)
+    }
+
+    // 8- to 32-bit integer PCM in 32-bit words (24-bit samples sign-extended)
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_i32)]
+    pub fn analyze_i32_js(&self, pcm: &Int32Array, bits_per_sample: u32, on_progress: Option<Function>) -> Result<LoudnessResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_i32(&pcm.to_vec(), bits_per_sample, callback.as_deref())?)
+    }
+
+    // Momentary loudness every 100ms, read back page by page
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = momentary_history)]
+    pub fn momentary_history_js(&self, pcm: &Float32Array) -> Result<TimeSeries, JsError> {
+        Ok(self.momentary_history(&pcm.to_vec())?)
+    }
+
+    // Short-term loudness every 300ms, read back page by page
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = short_term_history)]
+    pub fn short_term_history_js(&self, pcm: &Float32Array) -> Result<TimeSeries, JsError> {
+        Ok(self.short_term_history(&pcm.to_vec())?)
+    }
+}
+
+impl LoudnessAnalyzer {
+    /// EBU R128 loudness of interleaved PCM; `on_progress` receives the percent
+    /// complete and returns false to cancel
+    pub fn analyze(&self, pcm: &[f32], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<LoudnessResult, AnalysisError> {
+        // At least one momentary block is needed for any loudness reading
+        validate_pcm(pcm, self.num_channels, self.min_frames())?;
+        let progress = Progress::new(on_progress, self.cancel.as_ref());
+        let result = self.analyze_samples(pcm, &progress)?;
+        progress.report(1.0);
+        Ok(result)
+    }
+
+    /// EBU R128 loudness of interleaved double-precision PCM, for input with
+    /// more than 24 bits of resolution
+    pub fn analyze_f64(&self, pcm: &[f64], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<LoudnessResult, AnalysisError> {
+        validate_pcm(pcm, self.num_channels, self.min_frames())?;
+        let progress = Progress::new(on_progress, self.cancel.as_ref());
+        let (result, _) = self.measure_samples(Pcm::Double(pcm), &progress)?;
+        progress.report(1.0);
+        Ok(result)
+    }
+
+    /// Analyse interleaved 16-bit integer PCM
+    pub fn analyze_i16(&self, samples: &[i16], on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<LoudnessResult, AnalysisError> {
+        self.analyze(&int_to_f32(samples, 16), on_progress)
+    }
+
+    /// Analyse interleaved integer PCM of `bits_per_sample` bits (8 to 32),
+    /// right-aligned in 32-bit words. Up to 24 bits convert exactly to f32;
+    /// wider samples take the double-precision path.
+    pub fn analyze_i32(&self, samples: &[i32], bits_per_sample: u32, on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<LoudnessResult, AnalysisError> {
+        validate_bit_depth(bits_per_sample)?;
+        if bits_per_sample <= 24 {
+            self.analyze(&int_to_f32(samples, bits_per_sample), on_progress)
         } else {
-            1.25  // Low volume
-        };
-        
-        let integrated_final = integrated_loudness + integrated_offset;
-        let short_term_final = short_term_max + short_term_offset;
-        let momentary_final = momentary_max + momentary_offset;
-        
-        // Collect debug block energies
-        let mut block_energy_debug = Vec::new();
-        for i in 0..5.min(momentary_energies.len()) {
-            block_energy_debug.push(momentary_energies[i]);
+            self.analyze_f64(&int_to_f64(samples, bits_per_sample), on_progress)
         }
+    }
+
+    /// Momentary loudness (LUFS) of every 400ms block, one per 100ms hop
+    pub fn momentary_history(&self, pcm: &[f32]) -> Result<TimeSeries, AnalysisError> {
+        validate_pcm(pcm, self.num_channels, self.min_frames())?;
+        Ok(self.loudness_history(pcm, MOMENTARY_BLOCK_SIZE, MOMENTARY_HOP))
+    }
+
+    /// Short-term loudness (LUFS) of every 3s block, one per 300ms hop;
+    /// empty for input shorter than one block
+    pub fn short_term_history(&self, pcm: &[f32]) -> Result<TimeSeries, AnalysisError> {
+        validate_pcm(pcm, self.num_channels, self.min_frames())?;
+        Ok(self.loudness_history(pcm, SHORT_TERM_BLOCK_SIZE, SHORT_TERM_HOP))
+    }
+
+    // Shortest input (frames at the input rate) holding one momentary block
+    pub(crate) fn min_frames(&self) -> usize {
+        self.frames_for(MOMENTARY_BLOCK_SIZE)
+    }
+
+    // Frames at the input rate spanning `block` frames at the block rate
+    pub(crate) fn frames_for(&self, block: usize) -> usize {
+        (block as f32 * self.sample_rate / BLOCK_SAMPLE_RATE).ceil() as usize
+    }
+
+    // Resampler to the block rate, when the input is at another rate
+    fn resampler(&self) -> Option<Resampler> {
+        let off_rate = self.sample_rate > 0.0 && (self.sample_rate - BLOCK_SAMPLE_RATE).abs() >= 0.5;
+        off_rate.then(|| Resampler::new(self.sample_rate, BLOCK_SAMPLE_RATE))
+    }
+
+    // Loudness analysis of interleaved samples already in WASM memory
+    pub(crate) fn analyze_samples(&self, pcm: &[f32], progress: &Progress) -> Result<LoudnessResult, AnalysisError> {
+        Ok(self.measure_samples(Pcm::Single(pcm), progress)?.0)
+    }
+
+    // Loudness analysis that also hands back the absolute-gated momentary block
+    // energies, so callers can gate several tracks together (album loudness)
+    pub(crate) fn measure_samples(&self, pcm: Pcm, progress: &Progress) -> Result<(LoudnessResult, Vec<f32>), AnalysisError> {
+        let (result, momentary_energies, _) = self.measure_blocks(pcm, progress)?;
+        Ok((result, momentary_energies))
+    }
+
+    // `measure_samples`, also handing back the ungated energy of every
+    // short-term block (one per `SHORT_TERM_HOP` at the block rate), so the
+    // short-term history comes from the same resample and K-weighting pass
+    pub(crate) fn measure_blocks(&self, pcm: Pcm, progress: &Progress) -> Result<(LoudnessResult, Vec<f32>, Vec<f32>), AnalysisError> {
+        let head = pcm.head(5);
+        let resampler = self.resampler();
+        let resampled = resampler.as_ref().map(|resampler| pcm.resample(resampler, self.num_channels));
+        let pcm = resampled.as_ref().map_or(pcm, OwnedPcm::as_pcm);
+        if resampled.is_some() {
+            progress.lap("resampling");
+        }
+
+        // K-weight once, then sum the segments into momentary (400ms) and
+        // short-term (3s) blocks
+        let segments = self.segment_energies(pcm);
+        progress.lap("k_weighting");
+        progress.checkpoint(0.9)?;
+
+        let momentary_energies = absolute_gated(block_energies(&segments, usize::MAX, MOMENTARY_BLOCK_SIZE, MOMENTARY_HOP));
+        let short_term_blocks = block_energies(&segments, usize::MAX, SHORT_TERM_BLOCK_SIZE, SHORT_TERM_HOP);
+        let short_term_energies = absolute_gated(short_term_blocks.clone());
+
+        let mut result = self.result_from_energies(head, pcm.len() / self.num_channels, &momentary_energies, &short_term_energies);
+        result.resampling = resampler.map(|resampler| resampler.report());
+        progress.lap("gating");
+        Ok((result, momentary_energies, short_term_blocks))
+    }
+
+    // Gate absolute-gated momentary and short-term block energies
+    // of a `frames`-frame signal
+    pub(crate) fn result_from_energies(&self, pcm_debug: Vec<f32>, frames: usize, momentary_energies: &[f32], short_term_energies: &[f32]) -> LoudnessResult {
+        let momentary_max = self.calculate_max_loudness(momentary_energies);
+        let short_term_max = self.calculate_max_loudness(short_term_energies);
+
+        // Gate the momentary blocks for integrated loudness; the preliminary
+        // loudness (absolute-gated blocks only) sets the relative threshold
+        let gated = Gate::INTEGRATED.apply(momentary_energies);
+        let integrated_loudness = gated.loudness();
+        log::debug!("Integrated {:.2} LUFS over {} gated blocks", integrated_loudness, gated.energies.len());
         
-        // Return results
-        let result = js_sys::Object::new();
-        js_sys::Reflect::set(&result, &"pcm_debug".into(), &js_sys::Array::from_iter(pcm_debug.iter().map(|v| JsValue::from_f64(*v as f64)))).unwrap();
-        js_sys::Reflect::set(&result, &"block_energy_debug".into(), &js_sys::Array::from_iter(block_energy_debug.iter().map(|v| JsValue::from_f64(*v as f64)))).unwrap();
-        js_sys::Reflect::set(&result, &"momentary".into(), &momentary_final.into()).unwrap();
-        js_sys::Reflect::set(&result, &"shortTerm".into(), &short_term_final.into()).unwrap();
-        js_sys::Reflect::set(&result, &"integrated".into(), &integrated_final.into()).unwrap();
-        js_sys::Reflect::set(&result, &"preliminary_loudness".into(), &integrated_loudness.into()).unwrap();
-        js_sys::Reflect::set(&result, &"gate_threshold".into(), &(integrated_loudness + RELATIVE_GATE).into()).unwrap();
-        js_sys::Reflect::set(&result, &"abs_gated_blocks".into(), &(momentary_energies.len() as f32).into()).unwrap();
-        js_sys::Reflect::set(&result, &"rel_gated_blocks".into(), &(momentary_energies.len() as f32).into()).unwrap();
-        js_sys::Reflect::set(&result, &"totalBlocks".into(), &(momentary_energies.len() as f32).into()).unwrap();
+        // Collect debug block energies
+        let block_energy_debug = momentary_energies.iter().take(5).copied().collect();
         
-        result.into()
+        LoudnessResult {
+            pcm_debug,
+            block_energy_debug,
+            momentary: self.db.floored(momentary_max),
+            short_term: self.db.floored(short_term_max),
+            integrated: self.db.floored(integrated_loudness),
+            loudness_range: loudness_range(short_term_energies),
+            preliminary_loudness: self.db.floored(gated.threshold - RELATIVE_GATE),
+            gate_threshold: self.db.floored(gated.threshold),
+            abs_gated_blocks: gated.absolute_blocks,
+            rel_gated_blocks: gated.energies.len(),
+            total_blocks: block_count(frames, MOMENTARY_BLOCK_SIZE, MOMENTARY_HOP),
+            status: loudness_status(frames, momentary_energies.len(), short_term_energies.len()),
+            resampling: None,
+        }
+    }
+}
+
+// Frames per energy segment: every block size and hop is a whole number of
+// segments (100ms at 44.1kHz), so a block's energy is the mean of its segments'
+pub(crate) const SEGMENT: usize = MOMENTARY_HOP;
+
+// One channel's K-weighting filter, run continuously across the signal, and
+// the sum of its squared output over the current segment
+pub(crate) struct ChannelEnergy<T: BiquadSample> {
+    filter: KWeighting<T>,
+    sum: f64,
+    filled: usize,
+}
+
+impl<T: BiquadSample + Into<f64>> ChannelEnergy<T> {
+    pub(crate) fn new() -> Self {
+        ChannelEnergy { filter: KWeighting::new(BLOCK_SAMPLE_RATE), sum: 0.0, filled: 0 }
     }
-} 
\ No newline at end of file
+
+    // Filter `samples`, pushing the squared-output sum of every segment they complete
+    pub(crate) fn push(&mut self, samples: impl Iterator<Item = T>, segments: &mut Vec<f64>) {
+        for sample in samples {
+            let filtered: f64 = self.filter.process(sample).into();
+            self.sum += filtered * filtered;
+            self.filled += 1;
+            if self.filled == SEGMENT {
+                segments.push(self.sum);
+                self.sum = 0.0;
+                self.filled = 0;
+            }
+        }
+    }
+
+    fn segments(mut self, samples: impl Iterator<Item = T>) -> Vec<f64> {
+        let mut segments = Vec::new();
+        self.push(samples, &mut segments);
+        segments
+    }
+}
+
+// Mean energy of each segment every channel has completed, summed over
+// channels with their BS.1770 weights (1.0 for front channels)
+pub(crate) fn sum_channels(channels: &[Vec<f64>], weights: &[f64]) -> Vec<f64> {
+    let count = channels.iter().map(Vec::len).min().unwrap_or(0);
+    (0..count)
+        .map(|segment| channels.iter().zip(weights).map(|(channel, weight)| weight * channel[segment]).sum::<f64>() / SEGMENT as f64)
+        .collect()
+}
+
+// Ungated energies of every block starting within the first `owned_frames`
+// frames (the rest is lookahead for the last blocks), from segment energies
+pub(crate) fn block_energies(segments: &[f64], owned_frames: usize, block_size: usize, hop: usize) -> Vec<f32> {
+    let num_blocks = block_count(segments.len() * SEGMENT, block_size, hop).min(owned_frames.div_ceil(hop));
+    let used = if num_blocks == 0 { 0 } else { ((num_blocks - 1) * hop + block_size) / SEGMENT };
+    blockize(&segments[..used], block_size / SEGMENT, hop / SEGMENT)
+}
+
+// Only the blocks above the absolute gate
+pub(crate) fn absolute_gated(energies: Vec<f32>) -> Vec<f32> {
+    energies.into_iter().filter(|&energy| Gate::INTEGRATED.passes_absolute(energy)).collect()
+}
+
+// Relative-gated loudness (LUFS) of absolute-gated block energies
+pub(crate) fn integrated_loudness(energies: &[f32]) -> f32 {
+    Gate::INTEGRATED.apply(energies).loudness()
+}
+
+// Spread between the 10th and 95th percentile of the relative-gated
+// short-term loudness; 0 without short-term blocks
+pub(crate) fn loudness_range(short_term_energies: &[f32]) -> f32 {
+    let gated = Gate::LOUDNESS_RANGE.apply(short_term_energies);
+    match (gated.percentile(LRA_HIGH_PERCENTILE), gated.percentile(LRA_LOW_PERCENTILE)) {
+        (Some(high), Some(low)) => high - low,
+        _ => 0.0,
+    }
+}
+
+// Trust flags from the number of blocks that passed the absolute gate
+pub(crate) fn loudness_status(frames: usize, momentary_gated: usize, short_term_gated: usize) -> LoudnessStatus {
+    let gated_out = |gated: usize, total: usize| if total == 0 { 1.0 } else { 1.0 - gated as f32 / total as f32 };
+    let momentary_silence = gated_out(momentary_gated, block_count(frames, MOMENTARY_BLOCK_SIZE, MOMENTARY_HOP));
+    let short_term_silence = gated_out(short_term_gated, block_count(frames, SHORT_TERM_BLOCK_SIZE, SHORT_TERM_HOP));
+    let too_short = frames < SHORT_TERM_BLOCK_SIZE;
+
+    LoudnessStatus {
+        momentary: MetricStatus::assess(false, momentary_silence, false),
+        short_term: MetricStatus::assess(too_short, short_term_silence, false),
+        integrated: MetricStatus::assess(too_short, momentary_silence, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::Progress;
+
+    #[test]
+    fn loudness_range_spans_quiet_and_loud_passages() {
+        // 12s at -30dB then 12s at -10dB: the range is the 20dB step, give or
+        // take the short-term blocks straddling it
+        let tone = |amplitude: f32| (0..12 * 44100).map(move |i| amplitude * (i as f32 * 0.06).sin());
+        let pcm: Vec<f32> = tone(0.0316).chain(tone(0.316)).collect();
+        let analyzer = LoudnessAnalyzer::new(1);
+
+        let result = analyzer.analyze_samples(&pcm, &Progress::new(None, None)).unwrap();
+        assert!((result.loudness_range - 20.0).abs() < 1.0, "LRA {}", result.loudness_range);
+        let steady = analyzer.analyze_samples(&pcm[..12 * 44100], &Progress::new(None, None)).unwrap();
+        assert!(steady.loudness_range < 0.1);
+    }
+
+    #[test]
+    fn gating_counts_follow_the_gates() {
+        // 1s of silence, then a quiet passage 20dB under a loud one: the
+        // silence fails the absolute gate, the quiet passage the relative one
+        let tone = |amplitude: f32, seconds: usize| (0..seconds * 44100).map(move |i| amplitude * (i as f32 * 0.06).sin());
+        let pcm: Vec<f32> = tone(0.0, 1).chain(tone(0.0316, 6)).chain(tone(0.316, 6)).collect();
+        let result = LoudnessAnalyzer::new(1).analyze_samples(&pcm, &Progress::new(None, None)).unwrap();
+
+        assert_eq!(result.total_blocks, block_count(pcm.len(), MOMENTARY_BLOCK_SIZE, MOMENTARY_HOP));
+        assert!(result.abs_gated_blocks < result.total_blocks);
+        assert!(result.rel_gated_blocks < result.abs_gated_blocks);
+        assert!(result.preliminary_loudness < result.integrated);
+        assert_eq!(result.gate_threshold, result.preliminary_loudness + RELATIVE_GATE);
+    }
+
+    #[test]
+    fn silence_reads_at_the_configured_floor() {
+        let silence = vec![0.0f32; 4 * 44100];
+        let unfloored = LoudnessAnalyzer::new(1).analyze_samples(&silence, &Progress::new(None, None)).unwrap();
+        assert_eq!(unfloored.integrated, f32::NEG_INFINITY);
+
+        let config = AnalyzerConfig::new(44100.0, 1).with_db_floor(-150.0);
+        let result = LoudnessAnalyzer::from_config(&config).analyze_samples(&silence, &Progress::new(None, None)).unwrap();
+        for level in [result.momentary, result.short_term, result.integrated, result.preliminary_loudness, result.gate_threshold] {
+            assert_eq!(level, -150.0);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "threads")]
+    fn threaded_analysis_matches_sequential() {
+        let pcm: Vec<f32> = (0..6 * 44100).flat_map(|i| [0.3 * (i as f32 * 0.05).sin(), 0.1 * (i as f32 * 0.13).sin()]).collect();
+        let analyzer = LoudnessAnalyzer::new(2);
+        let run = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| analyzer.analyze_samples(&pcm, &Progress::new(None, None)).unwrap())
+        };
+
+        let (sequential, threaded) = (run(1), run(4));
+        assert_eq!(sequential.block_energy_debug, threaded.block_energy_debug);
+        assert_eq!(sequential.integrated, threaded.integrated);
+        assert_eq!(sequential.short_term, threaded.short_term);
+        assert_eq!(sequential.loudness_range, threaded.loudness_range);
+    }
+}
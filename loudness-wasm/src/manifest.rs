@@ -0,0 +1,121 @@
+// Batch manifest for folder analysis: one flat row per file with the figures
+// a results table sorts by and pass/fail flags from the QC report, while the
+// full results stay in WASM memory until the UI opens a file's detail view.
+//
+//     const batch = analyzer.analyze_manifest(buffers, fileNames);
+//     table.render(batch.manifest().entries);
+//     onRowClick(id => showDetail(batch.detail(id)));
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::analyzer::{AlbumResult, AnalysisResult};
+use crate::qc::{QcReport, Severity};
+
+/// One file of the batch; loudness in LUFS/LU, peaks in dBTP, duration in seconds
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct ManifestEntry {
+    pub id: String,
+    // Position in the input
+    pub index: usize,
+    pub duration: f32,
+    pub integrated: f32,
+    pub loudness_range: f32,
+    pub true_peak: f32,
+    pub plr: f32,
+    // Worst QC finding; the file passes unless it is an error
+    pub severity: Severity,
+    pub passes: bool,
+    // Integrated loudness within tolerance of the config target
+    pub loudness_pass: bool,
+    // True peak at or below the config ceiling
+    pub true_peak_pass: bool,
+    pub clipping: bool,
+    // QC findings at warning or error
+    pub issue_count: usize,
+}
+
+/// Rows in input order plus the album figures
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+    pub album: AlbumResult,
+    pub pass_count: usize,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct BatchManifest {
+    manifest: Manifest,
+    details: Vec<AnalysisResult>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl BatchManifest {
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = manifest)]
+    pub fn manifest_js(&self) -> Manifest {
+        self.manifest.clone()
+    }
+
+    // Full result of the file with `id`, for its detail view
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = detail)]
+    pub fn detail_js(&self, id: &str) -> Option<AnalysisResult> {
+        self.detail(id).cloned()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn len(&self) -> usize {
+        self.details.len()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn is_empty(&self) -> bool {
+        self.details.is_empty()
+    }
+}
+
+impl BatchManifest {
+    /// Manifest of `tracks` (results in input order, each with its id,
+    /// duration and QC report)
+    pub(crate) fn new(tracks: Vec<(String, f32, AnalysisResult, QcReport)>, album: AlbumResult) -> Self {
+        let mut entries = Vec::with_capacity(tracks.len());
+        let mut details = Vec::with_capacity(tracks.len());
+        for (index, (id, duration, result, report)) in tracks.into_iter().enumerate() {
+            let failed = |check: &str, severity: Severity| report.findings_at_least(severity).any(|finding| finding.check == check);
+            entries.push(ManifestEntry {
+                id,
+                index,
+                duration,
+                integrated: result.loudness.integrated,
+                loudness_range: result.loudness.loudness_range,
+                true_peak: result.technical.true_peak.level,
+                plr: result.technical.mastering.plr,
+                severity: report.severity,
+                passes: report.severity < Severity::Error,
+                loudness_pass: !failed("integrated", Severity::Warning),
+                true_peak_pass: !failed("true_peak", Severity::Error),
+                clipping: result.technical.quality.has_clipping,
+                issue_count: report.findings_at_least(Severity::Warning).count(),
+            });
+            details.push(result);
+        }
+        let pass_count = entries.iter().filter(|entry| entry.passes).count();
+        BatchManifest { manifest: Manifest { entries, album, pass_count }, details }
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Full result of the first file with `id`
+    pub fn detail(&self, id: &str) -> Option<&AnalysisResult> {
+        let index = self.manifest.entries.iter().position(|entry| entry.id == id)?;
+        self.details.get(index)
+    }
+}
@@ -1,6 +0,0 @@
-use wasm_bindgen::prelude::*;
-
-pub mod chroma;
-
-// Music analysis has been completely removed from this application
-// This module is kept for compatibility but no longer provides music analysis functionality 
\ No newline at end of file
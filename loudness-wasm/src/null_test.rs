@@ -0,0 +1,243 @@
+// Null test of two renders of the same material (before/after a plugin update,
+// two export settings): the second render is time-aligned to the first by
+// cross-correlation, subtracted, and the residual measured overall and per
+// frequency band. A residual below the null threshold means nothing audible
+// changed; otherwise the band figures show where the renders differ.
+//
+//     const tester = new NullTestAnalyzer(48000, 2);
+//     const result = tester.compare(before, after);
+//     if (!result.nulls) console.log(result.residual_rms_db, result.bands);
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::AnalyzerConfig;
+use crate::constants::FREQUENCY_BANDS;
+use crate::error::{validate_pcm, AnalysisError};
+use crate::stft::Stft;
+use crate::simd::{axpy, max_abs};
+use crate::utils::{calculate_rms, cross_correlation, mix_to_mono, DbScale};
+use crate::window::Window;
+
+// Longest offset searched between the renders, in seconds
+const DEFAULT_MAX_OFFSET: f32 = 1.0;
+// Span (seconds, from the start) cross-correlated to find the offset
+const ALIGNMENT_SECONDS: f32 = 10.0;
+// Residual peak (dBFS) at or below which the renders null: under the 24-bit
+// noise floor, so dither and float rounding still count as identical
+const DEFAULT_NULL_THRESHOLD: f32 = -120.0;
+// STFT size for the per-band residual
+const BAND_FFT_SIZE: usize = 4096;
+
+/// Residual in one frequency band (levels in dBFS)
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct BandResidual {
+    pub low_hz: f32,
+    pub high_hz: f32,
+    pub residual_db: f32,
+    pub reference_db: f32,
+    // Residual relative to the first render's level in the band
+    pub relative_db: f32,
+}
+
+/// Outcome of a null test; levels in dBFS
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct NullTestResult {
+    // Frames the second render lags the first by (negative when it leads)
+    pub offset_frames: i64,
+    pub offset_seconds: f32,
+    // Normalised cross-correlation at the chosen offset (1 = same waveform)
+    pub alignment_correlation: f32,
+    // Overlap of the aligned renders that was compared
+    pub compared_duration: f32,
+    pub residual_rms_db: f32,
+    pub residual_peak_db: f32,
+    pub reference_rms_db: f32,
+    // Residual RMS relative to the first render's RMS
+    pub null_depth_db: f32,
+    pub nulls: bool,
+    pub bands: Vec<BandResidual>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct NullTestAnalyzer {
+    sample_rate: f32,
+    num_channels: usize,
+    max_offset: f32,
+    null_threshold: f32,
+    window: Window,
+    db: DbScale,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl NullTestAnalyzer {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        NullTestAnalyzer::from_config(&AnalyzerConfig::new(sample_rate, num_channels))
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        NullTestAnalyzer {
+            sample_rate: config.sample_rate(),
+            num_channels: config.num_channels(),
+            max_offset: DEFAULT_MAX_OFFSET,
+            null_threshold: DEFAULT_NULL_THRESHOLD,
+            window: config.window(),
+            db: config.db_scale(),
+        }
+    }
+
+    // Longest offset (seconds) searched when aligning; 0 compares the renders as given
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_max_offset(&mut self, seconds: f32) {
+        self.max_offset = seconds.max(0.0);
+    }
+
+    // Residual peak (dBFS) at or below which the renders count as identical
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_null_threshold(&mut self, db: f32) {
+        self.null_threshold = db;
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = compare)]
+    pub fn compare_js(&self, first: &Float32Array, second: &Float32Array) -> Result<NullTestResult, JsError> {
+        Ok(self.compare(&first.to_vec(), &second.to_vec())?)
+    }
+}
+
+impl NullTestAnalyzer {
+    /// Align `second` to `first` (interleaved, same format), subtract and
+    /// measure the residual over their overlap
+    pub fn compare(&self, first: &[f32], second: &[f32]) -> Result<NullTestResult, AnalysisError> {
+        validate_pcm(first, self.num_channels, 1)?;
+        validate_pcm(second, self.num_channels, 1)?;
+        let channels = self.num_channels;
+
+        let (offset, alignment_correlation) = self.find_offset(&mix_to_mono(first, channels), &mix_to_mono(second, channels));
+        // Overlapping frames: first[n] lines up with second[n + offset]
+        let (first_start, second_start) = if offset >= 0 { (0, offset as usize) } else { ((-offset) as usize, 0) };
+        let frames = (first.len() / channels).saturating_sub(first_start).min((second.len() / channels).saturating_sub(second_start));
+        let reference = &first[first_start * channels..(first_start + frames) * channels];
+        let mut residual = second[second_start * channels..(second_start + frames) * channels].to_vec();
+        axpy(-1.0, reference, &mut residual);
+        log::debug!("Null test: offset {} frames, {} frames compared", offset, frames);
+
+        let residual_rms_db = self.db.to_db(calculate_rms(&residual));
+        let reference_rms_db = self.db.to_db(calculate_rms(reference));
+        let residual_peak_db = self.db.to_db(max_abs(&residual));
+        Ok(NullTestResult {
+            offset_frames: offset,
+            offset_seconds: offset as f32 / self.sample_rate,
+            alignment_correlation,
+            compared_duration: frames as f32 / self.sample_rate,
+            residual_rms_db,
+            residual_peak_db,
+            reference_rms_db,
+            null_depth_db: residual_rms_db - reference_rms_db,
+            nulls: residual_peak_db <= self.null_threshold,
+            bands: self.band_residuals(&residual, reference),
+        })
+    }
+
+    // Lag of `second` behind `first` maximising their cross-correlation over
+    // the leading span, with the normalised correlation there
+    fn find_offset(&self, first: &[f32], second: &[f32]) -> (i64, f32) {
+        let span = ((self.sample_rate * ALIGNMENT_SECONDS) as usize).max(1);
+        let (a, b) = (&first[..first.len().min(span)], &second[..second.len().min(span)]);
+        let max_lag = ((self.sample_rate * self.max_offset) as usize).min(a.len().max(b.len()) - 1);
+        let energy = (a.iter().map(|&x| x as f64 * x as f64).sum::<f64>() * b.iter().map(|&x| x as f64 * x as f64).sum::<f64>()).sqrt();
+        if energy <= 0.0 {
+            return (0, 0.0);
+        }
+
+        let correlation = cross_correlation(a, b, max_lag);
+        let (best, peak) = (-(max_lag as i64)..=max_lag as i64)
+            .zip(correlation)
+            .max_by(|(x, cx), (y, cy)| cx.total_cmp(cy).then(y.abs().cmp(&x.abs())))
+            .unwrap_or((0, 0.0));
+        (best, (peak as f64 / energy) as f32)
+    }
+
+    // Residual and reference energy per band, averaged over windowed frames
+    // of every channel
+    fn band_residuals(&self, residual: &[f32], reference: &[f32]) -> Vec<BandResidual> {
+        let stft = Stft::new(BAND_FFT_SIZE, BAND_FFT_SIZE, self.window);
+        let window_power: f32 = stft.window().iter().map(|w| w * w).sum();
+        let channels = self.num_channels;
+        let frames = residual.len() / channels;
+
+        let band_energies = |pcm: &[f32]| {
+            let mut energies = [0.0f64; FREQUENCY_BANDS.len()];
+            let mut count = 0;
+            for start in stft.frame_starts(frames) {
+                for channel in 0..channels {
+                    let magnitudes = stft.forward((0..BAND_FFT_SIZE).map(|i| pcm[(start + i) * channels + channel])).magnitudes();
+                    for (energy, &(low, high)) in energies.iter_mut().zip(&FREQUENCY_BANDS) {
+                        let bins = band_bins(low, high, self.sample_rate, magnitudes.len());
+                        // One-sided spectrum: each bin stands for its mirror too
+                        *energy += magnitudes[bins].iter().map(|&m| 2.0 * (m as f64).powi(2)).sum::<f64>() / (BAND_FFT_SIZE as f64 * window_power as f64);
+                    }
+                }
+                count += channels;
+            }
+            energies.map(|energy| (energy / count.max(1) as f64) as f32)
+        };
+        let residual_energies = band_energies(residual);
+        let reference_energies = band_energies(reference);
+
+        FREQUENCY_BANDS.iter().zip(residual_energies.iter().zip(&reference_energies))
+            .map(|(&(low_hz, high_hz), (&residual, &reference))| {
+                let residual_db = self.db.to_db(residual.sqrt());
+                let reference_db = self.db.to_db(reference.sqrt());
+                BandResidual { low_hz, high_hz, residual_db, reference_db, relative_db: residual_db - reference_db }
+            })
+            .collect()
+    }
+}
+
+// FFT bins (of `bins` one-sided bins) covering low..high Hz
+pub(crate) fn band_bins(low: f32, high: f32, sample_rate: f32, bins: usize) -> std::ops::Range<usize> {
+    let bin_hz = sample_rate / (2 * bins) as f32;
+    let start = ((low / bin_hz) as usize).min(bins);
+    start..((high / bin_hz) as usize).clamp(start, bins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::amplitude_to_db;
+
+    #[test]
+    fn aligns_offset_renders_and_measures_residual() {
+        let signal: Vec<f32> = (0..44100).map(|i| 0.4 * (i as f32 * 0.031).sin() + 0.2 * (i as f32 * 0.0073).sin() * (i as f32 * 0.5).sin()).collect();
+        let tester = NullTestAnalyzer::new(44100.0, 1);
+
+        // The same render delayed by 300 frames nulls completely
+        let delayed: Vec<f32> = std::iter::repeat_n(0.0, 300).chain(signal.iter().copied()).collect();
+        let result = tester.compare(&signal, &delayed).unwrap();
+        assert_eq!(result.offset_frames, 300);
+        assert!(result.alignment_correlation > 0.99);
+        assert!(result.nulls && result.residual_rms_db == f32::NEG_INFINITY);
+
+        // A 1kHz tone added 40dB down shows up in the 500Hz-2kHz band
+        let changed: Vec<f32> = signal.iter().enumerate()
+            .map(|(i, &x)| x + 0.004 * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 44100.0).sin())
+            .collect();
+        let result = tester.compare(&signal, &changed).unwrap();
+        assert_eq!(result.offset_frames, 0);
+        assert!(!result.nulls);
+        assert!((result.residual_peak_db - amplitude_to_db(0.004)).abs() < 0.1);
+        let loudest = result.bands.iter().max_by(|a, b| a.residual_db.total_cmp(&b.residual_db)).unwrap();
+        assert_eq!((loudest.low_hz, loudest.high_hz), (500.0, 2000.0));
+        assert!((loudest.residual_db - amplitude_to_db(0.004 / 2f32.sqrt())).abs() < 1.0);
+    }
+}
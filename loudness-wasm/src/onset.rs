@@ -0,0 +1,179 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::error::{validate_pcm, AnalysisError};
+use crate::utils::{mix_to_mono, PeakPicker};
+
+// Onset envelope framing (~23ms frames, ~11.6ms hop at 44.1kHz)
+pub const ONSET_FRAME_SIZE: usize = 1024;
+pub const ONSET_HOP: usize = 512;
+
+// Peak picking: local-maximum radius, adaptive-mean radius and threshold
+// above the local mean (the envelope is normalised to unit std deviation)
+const PEAK_RADIUS: usize = 3;
+const MEAN_RADIUS: usize = 8;
+const PEAK_DELTA: f32 = 0.5;
+
+/// Onset strength envelope: half-wave rectified log-energy flux, combining the
+/// full band with a first-difference (high-passed) band so both kicks and hats
+/// contribute. One value per `ONSET_HOP` samples, normalised to unit std deviation.
+pub fn onset_envelope(mono: &[f32]) -> Vec<f32> {
+    if mono.len() < ONSET_FRAME_SIZE {
+        return Vec::new();
+    }
+
+    let num_frames = (mono.len() - ONSET_FRAME_SIZE) / ONSET_HOP + 1;
+    let (full_band, high_band): (Vec<f32>, Vec<f32>) = (0..num_frames)
+        .map(|frame| onset_bands(&mono[frame * ONSET_HOP..frame * ONSET_HOP + ONSET_FRAME_SIZE]))
+        .unzip();
+    envelope_from_bands(&full_band, &high_band)
+}
+
+/// The mono fold of interleaved PCM and its onset envelope, computed once for
+/// the sections that both read them (transients and punch, rhythm)
+pub(crate) struct MonoFold {
+    pub(crate) samples: Vec<f32>,
+    pub(crate) envelope: Vec<f32>,
+}
+
+impl MonoFold {
+    pub(crate) fn new(pcm: &[f32], num_channels: usize) -> Self {
+        let samples = mix_to_mono(pcm, num_channels);
+        let envelope = onset_envelope(&samples);
+        MonoFold { samples, envelope }
+    }
+}
+
+// Log energies (full band, first-difference band) of one `ONSET_FRAME_SIZE` frame
+pub(crate) fn onset_bands(samples: &[f32]) -> (f32, f32) {
+    let mut energy = 0.0;
+    let mut hf_energy = 0.0;
+    let mut previous = samples[0];
+    for &sample in samples {
+        energy += sample * sample;
+        let diff = sample - previous;
+        hf_energy += diff * diff;
+        previous = sample;
+    }
+
+    ((energy / ONSET_FRAME_SIZE as f32 + 1e-10).log10(), (hf_energy / ONSET_FRAME_SIZE as f32 + 1e-10).log10())
+}
+
+// Onset envelope from the per-frame band energies of `onset_bands`
+pub(crate) fn envelope_from_bands(full_band: &[f32], high_band: &[f32]) -> Vec<f32> {
+    let num_frames = full_band.len();
+    if num_frames == 0 {
+        return Vec::new();
+    }
+
+    let mut envelope = vec![0.0; num_frames];
+    for t in 1..num_frames {
+        let full_flux = (full_band[t] - full_band[t - 1]).max(0.0);
+        let high_flux = (high_band[t] - high_band[t - 1]).max(0.0);
+        envelope[t] = full_flux + high_flux;
+    }
+
+    // Normalise to unit standard deviation so thresholds are level-independent
+    let mean = envelope.iter().sum::<f32>() / num_frames as f32;
+    let variance = envelope.iter().map(|&v| (v - mean) * (v - mean)).sum::<f32>() / num_frames as f32;
+    let std_dev = variance.sqrt();
+    if std_dev > 1e-8 {
+        for value in envelope.iter_mut() {
+            *value /= std_dev;
+        }
+    }
+
+    envelope
+}
+
+/// Pick onset frames from an onset envelope: local maxima that exceed the
+/// surrounding mean by a fixed margin, more than `PEAK_RADIUS` frames apart
+pub fn pick_onsets(envelope: &[f32]) -> Vec<usize> {
+    // Plateaus produce equal neighbours; the spacing keeps only the first frame
+    PeakPicker { radius: PEAK_RADIUS, mean_radius: MEAN_RADIUS, delta: PEAK_DELTA, floor: 0.0, min_distance: PEAK_RADIUS + 1 }.pick(envelope)
+}
+
+/// Convert an onset-envelope frame index to seconds
+pub fn frame_to_time(frame: f32, sample_rate: f32) -> f32 {
+    frame * ONSET_HOP as f32 / sample_rate
+}
+
+/// Onset envelope (frames at `frame_rate` Hz) and picked onsets in seconds
+#[derive(Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct OnsetResult {
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub envelope: Vec<f32>,
+    pub frame_rate: f32,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub onsets: Vec<f32>,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub onset_strengths: Vec<f32>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct OnsetDetector {
+    sample_rate: f32,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl OnsetDetector {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32) -> Self {
+        OnsetDetector { sample_rate }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = detect_onsets)]
+    pub fn detect_onsets_js(&self, pcm: &Float32Array, num_channels: usize) -> Result<OnsetResult, JsError> {
+        Ok(self.detect_onsets(&pcm.to_vec(), num_channels)?)
+    }
+}
+
+impl OnsetDetector {
+    /// Onset envelope and picked onset times (seconds) of interleaved PCM
+    pub fn detect_onsets(&self, pcm: &[f32], num_channels: usize) -> Result<OnsetResult, AnalysisError> {
+        validate_pcm(pcm, num_channels, 1)?;
+        let mono = mix_to_mono(pcm, num_channels);
+        let envelope = onset_envelope(&mono);
+        let onsets = pick_onsets(&envelope);
+
+        let onset_times: Vec<f32> = onsets.iter().map(|&t| frame_to_time(t as f32, self.sample_rate)).collect();
+        let onset_strengths: Vec<f32> = onsets.iter().map(|&t| envelope[t]).collect();
+
+        Ok(OnsetResult {
+            envelope,
+            frame_rate: self.sample_rate / ONSET_HOP as f32,
+            onsets: onset_times,
+            onset_strengths,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_one_onset_per_click() {
+        let sample_rate = 44100;
+        let mut samples = vec![0.0; sample_rate * 4];
+        for click in 0..8 {
+            let start = click * sample_rate / 2 + 5000;
+            for i in 0..300 {
+                samples[start + i] = 0.8 * (-(i as f32) / 60.0).exp();
+            }
+        }
+
+        let onsets = pick_onsets(&onset_envelope(&samples));
+        assert_eq!(onsets.len(), 8);
+    }
+}
@@ -0,0 +1,843 @@
+// CELT band shapes: PVQ codewords, band splitting and folding (RFC 6716
+// §4.3.4)
+//
+// Each band's normalized shape is either decoded from a pyramid vector
+// codeword, split recursively in two with the energy ratio coded as an
+// angle, or — when no pulses fit the budget — folded from lower bands or
+// filled with noise. Stereo bands code mid/side (or intensity) the same way.
+
+use super::range::{ilog, RangeDecoder, BITRES};
+use super::tables::{CACHE_BITS, CACHE_INDEX, EBANDS, LOG_N, NB_EBANDS, ORDERY};
+
+pub(super) const SPREAD_NONE: usize = 0;
+pub(super) const SPREAD_AGGRESSIVE: usize = 3;
+const QTHETA_OFFSET: i32 = 4;
+const QTHETA_OFFSET_TWOPHASE: i32 = 16;
+const LOG_MAX_PSEUDO: usize = 6;
+
+pub(super) fn lcg_rand(seed: u32) -> u32 {
+    seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223)
+}
+
+fn frac_mul16(a: i32, b: i32) -> i32 {
+    (16384 + (a as i16 as i32) * (b as i16 as i32)) >> 15
+}
+
+/// Bit-exact cosine approximation; it feeds the allocation so it must
+/// match the encoder on every platform.
+fn bitexact_cos(x: i32) -> i32 {
+    let tmp = (4096 + x * x) >> 13;
+    let x2 = tmp;
+    let x2 = (32767 - x2) + frac_mul16(x2, -7651 + frac_mul16(x2, 8277 + frac_mul16(-626, x2)));
+    1 + x2
+}
+
+fn bitexact_log2tan(isin: i32, icos: i32) -> i32 {
+    let lc = ilog(icos as u32);
+    let ls = ilog(isin as u32);
+    let icos = icos << (15 - lc);
+    let isin = isin << (15 - ls);
+    (ls - lc) * (1 << 11) + frac_mul16(isin, frac_mul16(isin, -2597) + 7932)
+        - frac_mul16(icos, frac_mul16(icos, -2597) + 7932)
+}
+
+fn isqrt32(mut val: u32) -> u32 {
+    let mut g = 0u32;
+    let mut bshift = (ilog(val) - 1) >> 1;
+    let mut b = 1u32 << bshift;
+    loop {
+        let t = ((g << 1) + b) << bshift;
+        if t <= val {
+            g += b;
+            val -= t;
+        }
+        b >>= 1;
+        bshift -= 1;
+        if bshift < 0 {
+            return g;
+        }
+    }
+}
+
+pub(super) fn get_pulses(i: i32) -> i32 {
+    if i < 8 {
+        i
+    } else {
+        (8 + (i & 7)) << ((i >> 3) - 1)
+    }
+}
+
+fn cache(band: usize, lm: i32) -> &'static [u8] {
+    &CACHE_BITS[CACHE_INDEX[(lm + 1) as usize * NB_EBANDS + band] as usize..]
+}
+
+pub(super) fn bits2pulses(band: usize, lm: i32, bits: i32) -> i32 {
+    let cache = cache(band, lm);
+    let mut lo = 0;
+    let mut hi = i32::from(cache[0]);
+    let bits = bits - 1;
+    for _ in 0..LOG_MAX_PSEUDO {
+        let mid = (lo + hi + 1) >> 1;
+        if i32::from(cache[mid as usize]) >= bits {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    let below = if lo == 0 { -1 } else { i32::from(cache[lo as usize]) };
+    if bits - below <= i32::from(cache[hi as usize]) - bits {
+        lo
+    } else {
+        hi
+    }
+}
+
+pub(super) fn pulses2bits(band: usize, lm: i32, pulses: i32) -> i32 {
+    if pulses == 0 {
+        0
+    } else {
+        i32::from(cache(band, lm)[pulses as usize]) + 1
+    }
+}
+
+// --- PVQ codewords (small-footprint CWRS) ---
+
+fn unext(u: &mut [u32], len: usize, mut ui0: u32) {
+    let mut j = 1;
+    loop {
+        let ui1 = u[j].wrapping_add(u[j - 1]).wrapping_add(ui0);
+        u[j - 1] = ui0;
+        ui0 = ui1;
+        j += 1;
+        if j >= len {
+            break;
+        }
+    }
+    u[j - 1] = ui0;
+}
+
+fn uprev(u: &mut [u32], n: usize, mut ui0: u32) {
+    let mut j = 1;
+    loop {
+        let ui1 = u[j].wrapping_sub(u[j - 1]).wrapping_sub(ui0);
+        u[j - 1] = ui0;
+        ui0 = ui1;
+        j += 1;
+        if j >= n {
+            break;
+        }
+    }
+    u[j - 1] = ui0;
+}
+
+/// V(n, k) and row n of U(·, 0..=k+1).
+fn ncwrs_urow(n: usize, k: usize, u: &mut [u32]) -> u32 {
+    let len = k + 2;
+    u[0] = 0;
+    u[1] = 1;
+    for (i, v) in u.iter_mut().enumerate().take(len).skip(2) {
+        *v = ((i as u32) << 1) - 1;
+    }
+    for _ in 2..n {
+        unext(&mut u[1..], k + 1, 1);
+    }
+    u[k].wrapping_add(u[k + 1])
+}
+
+fn cwrsi(n: usize, mut k: usize, mut i: u32, y: &mut [i32], u: &mut [u32]) -> f32 {
+    let mut yy = 0.0f32;
+    for yj in y.iter_mut().take(n) {
+        let p = u[k + 1];
+        let s: i32 = if i >= p { -1 } else { 0 };
+        if s != 0 {
+            i -= p;
+        }
+        let k0 = k;
+        let mut p = u[k];
+        while p > i {
+            k -= 1;
+            p = u[k];
+        }
+        i -= p;
+        let val = ((k0 - k) as i32 + s) ^ s;
+        *yj = val;
+        yy += (val * val) as f32;
+        uprev(u, k + 2, 0);
+    }
+    yy
+}
+
+fn decode_pulses(y: &mut [i32], n: usize, k: usize, dec: &mut RangeDecoder) -> f32 {
+    let mut u = vec![0u32; k + 2];
+    let total = ncwrs_urow(n, k, &mut u);
+    let index = dec.uint(total);
+    cwrsi(n, k, index, y, &mut u)
+}
+
+// --- Shape reconstruction ---
+
+fn exp_rotation1(x: &mut [f32], len: usize, stride: usize, c: f32, s: f32) {
+    let ms = -s;
+    for i in 0..len - stride {
+        let x1 = x[i];
+        let x2 = x[i + stride];
+        x[i + stride] = c * x2 + s * x1;
+        x[i] = c * x1 + ms * x2;
+    }
+    if len > 2 * stride {
+        for i in (0..len - 2 * stride).rev() {
+            let x1 = x[i];
+            let x2 = x[i + stride];
+            x[i + stride] = c * x2 + s * x1;
+            x[i] = c * x1 + ms * x2;
+        }
+    }
+}
+
+/// Undoes the encoder's spreading rotation.
+fn exp_rotation(x: &mut [f32], len: usize, stride: usize, k: usize, spread: usize) {
+    const SPREAD_FACTOR: [usize; 3] = [15, 10, 5];
+    if 2 * k >= len || spread == SPREAD_NONE {
+        return;
+    }
+    let factor = SPREAD_FACTOR[spread - 1];
+    let gain = len as f32 / (len + factor * k) as f32;
+    let theta = 0.5 * (gain * gain);
+    let c = (0.5 * std::f32::consts::PI * theta).cos();
+    let s = (0.5 * std::f32::consts::PI * (1.0 - theta)).cos();
+    let mut stride2 = 0;
+    if len >= 8 * stride {
+        stride2 = 1;
+        while (stride2 * stride2 + stride2) * stride + (stride >> 2) < len {
+            stride2 += 1;
+        }
+    }
+    let len = len / stride;
+    for i in 0..stride {
+        let block = &mut x[i * len..(i + 1) * len];
+        if stride2 != 0 {
+            exp_rotation1(block, len, stride2, s, c);
+        }
+        exp_rotation1(block, len, 1, c, s);
+    }
+}
+
+pub(super) fn renormalise_vector(x: &mut [f32], gain: f32) {
+    let e = 1e-15 + x.iter().map(|v| v * v).sum::<f32>();
+    let g = gain / e.sqrt();
+    for v in x.iter_mut() {
+        *v *= g;
+    }
+}
+
+fn extract_collapse_mask(iy: &[i32], n: usize, b: usize) -> u32 {
+    if b <= 1 {
+        return 1;
+    }
+    let n0 = n / b;
+    let mut mask = 0;
+    for i in 0..b {
+        if iy[i * n0..(i + 1) * n0].iter().any(|&v| v != 0) {
+            mask |= 1 << i;
+        }
+    }
+    mask
+}
+
+fn alg_unquant(x: &mut [f32], n: usize, k: usize, spread: usize, b: usize, dec: &mut RangeDecoder, gain: f32) -> u32 {
+    let mut iy = [0i32; 176];
+    let ryy = decode_pulses(&mut iy, n, k, dec);
+    let g = gain / ryy.sqrt();
+    for (v, &p) in x.iter_mut().zip(&iy[..n]) {
+        *v = g * p as f32;
+    }
+    exp_rotation(x, n, b, k, spread);
+    extract_collapse_mask(&iy, n, b)
+}
+
+fn deinterleave_hadamard(x: &mut [f32], n0: usize, stride: usize, hadamard: bool) {
+    let n = n0 * stride;
+    let mut tmp = [0.0f32; 176];
+    for i in 0..stride {
+        let row = if hadamard { ORDERY[stride - 2 + i] } else { i };
+        for j in 0..n0 {
+            tmp[row * n0 + j] = x[j * stride + i];
+        }
+    }
+    x[..n].copy_from_slice(&tmp[..n]);
+}
+
+fn interleave_hadamard(x: &mut [f32], n0: usize, stride: usize, hadamard: bool) {
+    let n = n0 * stride;
+    let mut tmp = [0.0f32; 176];
+    for i in 0..stride {
+        let row = if hadamard { ORDERY[stride - 2 + i] } else { i };
+        for j in 0..n0 {
+            tmp[j * stride + i] = x[row * n0 + j];
+        }
+    }
+    x[..n].copy_from_slice(&tmp[..n]);
+}
+
+pub(super) fn haar1(x: &mut [f32], n0: usize, stride: usize) {
+    let n0 = n0 >> 1;
+    for i in 0..stride {
+        for j in 0..n0 {
+            let tmp1 = std::f32::consts::FRAC_1_SQRT_2 * x[stride * 2 * j + i];
+            let tmp2 = std::f32::consts::FRAC_1_SQRT_2 * x[stride * (2 * j + 1) + i];
+            x[stride * 2 * j + i] = tmp1 + tmp2;
+            x[stride * (2 * j + 1) + i] = tmp1 - tmp2;
+        }
+    }
+}
+
+fn compute_qn(n: i32, b: i32, offset: i32, pulse_cap: i32, stereo: bool) -> i32 {
+    const EXP2_TABLE8: [i32; 8] = [16384, 17866, 19483, 21247, 23170, 25267, 27554, 30048];
+    let mut n2 = 2 * n - 1;
+    if stereo && n == 2 {
+        n2 -= 1;
+    }
+    let qb = (b + n2 * offset) / n2;
+    let qb = qb.min(b - pulse_cap - (4 << BITRES)).min(8 << BITRES);
+    if qb < (1 << BITRES >> 1) {
+        1
+    } else {
+        let qn = EXP2_TABLE8[(qb & 0x7) as usize] >> (14 - (qb >> BITRES));
+        (qn + 1) >> 1 << 1
+    }
+}
+
+fn stereo_merge(x: &mut [f32], y: &mut [f32], mid: f32, n: usize) {
+    let mut xp = 0.0f32;
+    let mut side = 0.0f32;
+    for j in 0..n {
+        xp += y[j] * x[j];
+        side += y[j] * y[j];
+    }
+    let xp = mid * xp;
+    let mid2 = mid;
+    let el = mid2 * mid2 + side - 2.0 * xp;
+    let er = mid2 * mid2 + side + 2.0 * xp;
+    if er < 6e-4 || el < 6e-4 {
+        y[..n].copy_from_slice(&x[..n]);
+        return;
+    }
+    let lgain = 1.0 / el.sqrt();
+    let rgain = 1.0 / er.sqrt();
+    for j in 0..n {
+        let l = mid * x[j];
+        let r = y[j];
+        x[j] = lgain * (l - r);
+        y[j] = rgain * (l + r);
+    }
+}
+
+struct BandCtx<'a, 'b> {
+    dec: &'a mut RangeDecoder<'b>,
+    band: usize,
+    intensity: usize,
+    spread: usize,
+    tf_change: i32,
+    remaining_bits: i32,
+    seed: u32,
+    disable_inv: bool,
+}
+
+struct Split {
+    inv: bool,
+    imid: i32,
+    iside: i32,
+    delta: i32,
+    itheta: i32,
+    qalloc: i32,
+}
+
+impl BandCtx<'_, '_> {
+    #[allow(clippy::too_many_arguments)]
+    fn compute_theta(&mut self, n: usize, b: &mut i32, big_b: usize, b0: usize, lm: i32, stereo: bool, fill: &mut u32) -> Split {
+        let n_i = n as i32;
+        let pulse_cap = i32::from(LOG_N[self.band]) + lm * (1 << BITRES);
+        let offset = (pulse_cap >> 1) - if stereo && n == 2 { QTHETA_OFFSET_TWOPHASE } else { QTHETA_OFFSET };
+        let mut qn = compute_qn(n_i, *b, offset, pulse_cap, stereo);
+        if stereo && self.band >= self.intensity {
+            qn = 1;
+        }
+        let tell = self.dec.tell_frac();
+        let mut itheta = 0;
+        let mut inv = false;
+        if qn != 1 {
+            if stereo && n > 2 {
+                let p0 = 3;
+                let x0 = qn / 2;
+                let ft = p0 * (x0 + 1) + x0;
+                let fs = self.dec.decode(ft as u32) as i32;
+                let x = if fs < (x0 + 1) * p0 { fs / p0 } else { x0 + 1 + (fs - (x0 + 1) * p0) };
+                let (fl, fh) = if x <= x0 {
+                    (p0 * x, p0 * (x + 1))
+                } else {
+                    ((x - 1 - x0) + (x0 + 1) * p0, (x - x0) + (x0 + 1) * p0)
+                };
+                self.dec.update(fl as u32, fh as u32, ft as u32);
+                itheta = x;
+            } else if b0 > 1 || stereo {
+                itheta = self.dec.uint((qn + 1) as u32) as i32;
+            } else {
+                let ft = ((qn >> 1) + 1) * ((qn >> 1) + 1);
+                let fm = self.dec.decode(ft as u32) as i32;
+                let (fs, fl);
+                if fm < (((qn >> 1) * ((qn >> 1) + 1)) >> 1) {
+                    itheta = (isqrt32(8 * fm as u32 + 1) as i32 - 1) >> 1;
+                    fs = itheta + 1;
+                    fl = (itheta * (itheta + 1)) >> 1;
+                } else {
+                    itheta = (2 * (qn + 1) - isqrt32(8 * (ft - fm - 1) as u32 + 1) as i32) >> 1;
+                    fs = qn + 1 - itheta;
+                    fl = ft - (((qn + 1 - itheta) * (qn + 2 - itheta)) >> 1);
+                }
+                self.dec.update(fl as u32, (fl + fs) as u32, ft as u32);
+            }
+            itheta = itheta * 16384 / qn;
+        } else if stereo {
+            if *b > 2 << BITRES && self.remaining_bits > 2 << BITRES {
+                inv = self.dec.bit_logp(2);
+            }
+            if self.disable_inv {
+                inv = false;
+            }
+            itheta = 0;
+        }
+        let qalloc = self.dec.tell_frac() - tell;
+        *b -= qalloc;
+
+        let (imid, iside, delta);
+        if itheta == 0 {
+            imid = 32767;
+            iside = 0;
+            *fill &= (1 << big_b) - 1;
+            delta = -16384;
+        } else if itheta == 16384 {
+            imid = 0;
+            iside = 32767;
+            *fill &= ((1 << big_b) - 1) << big_b;
+            delta = 16384;
+        } else {
+            imid = bitexact_cos(itheta);
+            iside = bitexact_cos(16384 - itheta);
+            delta = frac_mul16((n_i - 1) << 7, bitexact_log2tan(iside, imid));
+        }
+        Split { inv, imid, iside, delta, itheta, qalloc }
+    }
+
+    fn quant_band_n1(&mut self, x: &mut [f32], y: Option<&mut [f32]>, lowband_out: Option<&mut [f32]>) -> u32 {
+        let decode_sign = |ctx: &mut Self, v: &mut f32| {
+            let mut sign = 0;
+            if ctx.remaining_bits >= 1 << BITRES {
+                sign = ctx.dec.bits(1);
+                ctx.remaining_bits -= 1 << BITRES;
+            }
+            *v = if sign != 0 { -1.0 } else { 1.0 };
+        };
+        decode_sign(self, &mut x[0]);
+        if let Some(y) = y {
+            decode_sign(self, &mut y[0]);
+        }
+        if let Some(out) = lowband_out {
+            out[0] = x[0];
+        }
+        1
+    }
+
+    /// A mono partition, split in two (recursively) while it needs more
+    /// bits than a single codebook can use.
+    #[allow(clippy::too_many_arguments)]
+    fn quant_partition(&mut self, x: &mut [f32], n: usize, mut b: i32, mut big_b: usize, lowband: Option<&[f32]>, mut lm: i32, gain: f32, mut fill: u32) -> u32 {
+        let b0 = big_b;
+        // Single-bin bands have no cache at LM = -1, so only look once the
+        // split is possible.
+        let max_bits = |lm| {
+            let cache = cache(self.band, lm);
+            i32::from(cache[cache[0] as usize])
+        };
+        if lm != -1 && b > max_bits(lm) + 12 && n > 2 {
+            let n = n >> 1;
+            let (xl, yl) = x.split_at_mut(n);
+            lm -= 1;
+            if big_b == 1 {
+                fill = (fill & 1) | (fill << 1);
+            }
+            big_b = (big_b + 1) >> 1;
+            let s = self.compute_theta(n, &mut b, big_b, b0, lm, false, &mut fill);
+            let mid = s.imid as f32 / 32768.0;
+            let side = s.iside as f32 / 32768.0;
+            let mut delta = s.delta;
+            if b0 > 1 && (s.itheta & 0x3fff) != 0 {
+                if s.itheta > 8192 {
+                    delta -= delta >> (4 - lm);
+                } else {
+                    delta = 0.min(delta + ((n as i32) << BITRES >> (5 - lm)));
+                }
+            }
+            let mut mbits = 0.max(b.min((b - delta) / 2));
+            let mut sbits = b - mbits;
+            self.remaining_bits -= s.qalloc;
+            let next_lowband2 = lowband.map(|l| &l[n..]);
+            let rebalance = self.remaining_bits;
+            let mut cm;
+            if mbits >= sbits {
+                cm = self.quant_partition(xl, n, mbits, big_b, lowband, lm, gain * mid, fill);
+                let rebalance = mbits - (rebalance - self.remaining_bits);
+                if rebalance > 3 << BITRES && s.itheta != 0 {
+                    sbits += rebalance - (3 << BITRES);
+                }
+                cm |= self.quant_partition(yl, n, sbits, big_b, next_lowband2, lm, gain * side, fill >> big_b) << (b0 >> 1);
+            } else {
+                cm = self.quant_partition(yl, n, sbits, big_b, next_lowband2, lm, gain * side, fill >> big_b) << (b0 >> 1);
+                let rebalance = sbits - (rebalance - self.remaining_bits);
+                if rebalance > 3 << BITRES && s.itheta != 16384 {
+                    mbits += rebalance - (3 << BITRES);
+                }
+                cm |= self.quant_partition(xl, n, mbits, big_b, lowband, lm, gain * mid, fill);
+            }
+            return cm;
+        }
+
+        let mut q = bits2pulses(self.band, lm, b);
+        let mut curr_bits = pulses2bits(self.band, lm, q);
+        self.remaining_bits -= curr_bits;
+        while self.remaining_bits < 0 && q > 0 {
+            self.remaining_bits += curr_bits;
+            q -= 1;
+            curr_bits = pulses2bits(self.band, lm, q);
+            self.remaining_bits -= curr_bits;
+        }
+        if q != 0 {
+            let k = get_pulses(q) as usize;
+            return alg_unquant(&mut x[..n], n, k, self.spread, big_b, self.dec, gain);
+        }
+        let cm_mask = ((1u64 << big_b) - 1) as u32;
+        fill &= cm_mask;
+        if fill == 0 {
+            x[..n].fill(0.0);
+            return 0;
+        }
+        let cm = match lowband {
+            None => {
+                for v in x[..n].iter_mut() {
+                    self.seed = lcg_rand(self.seed);
+                    *v = ((self.seed as i32) >> 20) as f32;
+                }
+                cm_mask
+            }
+            Some(lowband) => {
+                for (v, &l) in x[..n].iter_mut().zip(lowband) {
+                    self.seed = lcg_rand(self.seed);
+                    let tmp = if self.seed & 0x8000 != 0 { 1.0 / 256.0 } else { -1.0 / 256.0 };
+                    *v = l + tmp;
+                }
+                fill
+            }
+        };
+        renormalise_vector(&mut x[..n], gain);
+        cm
+    }
+
+    /// A mono band: applies the TF resolution change, then codes it as one
+    /// partition.
+    #[allow(clippy::too_many_arguments)]
+    fn quant_band(&mut self, x: &mut [f32], n: usize, b: i32, mut big_b: usize, mut lowband: Option<&mut [f32]>, lm: i32, lowband_out: Option<&mut [f32]>, gain: f32, mut fill: u32) -> u32 {
+        const BIT_INTERLEAVE: [u32; 16] = [0, 1, 1, 1, 2, 3, 3, 3, 2, 3, 3, 3, 2, 3, 3, 3];
+        const BIT_DEINTERLEAVE: [u32; 16] = [
+            0x00, 0x03, 0x0C, 0x0F, 0x30, 0x33, 0x3C, 0x3F, 0xC0, 0xC3, 0xCC, 0xCF, 0xF0, 0xF3, 0xFC, 0xFF,
+        ];
+        let n0 = n;
+        let mut n_b = n / big_b;
+        let long_blocks = big_b == 1;
+        let mut tf_change = self.tf_change;
+        if n == 1 {
+            return self.quant_band_n1(x, None, lowband_out);
+        }
+        let recombine = tf_change.max(0) as usize;
+
+        for k in 0..recombine {
+            if let Some(l) = lowband.as_deref_mut() {
+                haar1(l, n >> k, 1 << k);
+            }
+            fill = BIT_INTERLEAVE[(fill & 0xF) as usize] | BIT_INTERLEAVE[(fill >> 4) as usize] << 2;
+        }
+        big_b >>= recombine;
+        n_b <<= recombine;
+
+        let mut time_divide = 0;
+        while n_b & 1 == 0 && tf_change < 0 {
+            if let Some(l) = lowband.as_deref_mut() {
+                haar1(l, n_b, big_b);
+            }
+            fill |= fill << big_b;
+            big_b <<= 1;
+            n_b >>= 1;
+            time_divide += 1;
+            tf_change += 1;
+        }
+        let b0 = big_b;
+        let n_b0 = n_b;
+
+        if b0 > 1 {
+            if let Some(l) = lowband.as_deref_mut() {
+                deinterleave_hadamard(l, n_b >> recombine, b0 << recombine, long_blocks);
+            }
+        }
+
+        let mut cm = self.quant_partition(x, n, b, big_b, lowband.as_deref(), lm, gain, fill);
+
+        if b0 > 1 {
+            interleave_hadamard(x, n_b >> recombine, b0 << recombine, long_blocks);
+        }
+        n_b = n_b0;
+        big_b = b0;
+        for _ in 0..time_divide {
+            big_b >>= 1;
+            n_b <<= 1;
+            cm |= cm >> big_b;
+            haar1(x, n_b, big_b);
+        }
+        for k in 0..recombine {
+            cm = BIT_DEINTERLEAVE[cm as usize];
+            haar1(x, n0 >> k, 1 << k);
+        }
+        big_b <<= recombine;
+
+        if let Some(out) = lowband_out {
+            let scale = (n0 as f32).sqrt();
+            for (o, &v) in out[..n0].iter_mut().zip(&x[..n0]) {
+                *o = scale * v;
+            }
+        }
+        cm & ((1 << big_b) - 1)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn quant_band_stereo(&mut self, x: &mut [f32], y: &mut [f32], n: usize, mut b: i32, big_b: usize, lowband: Option<&mut [f32]>, lm: i32, lowband_out: Option<&mut [f32]>, mut fill: u32) -> u32 {
+        if n == 1 {
+            return self.quant_band_n1(x, Some(y), lowband_out);
+        }
+        let orig_fill = fill;
+        let s = self.compute_theta(n, &mut b, big_b, big_b, lm, true, &mut fill);
+        let mid = s.imid as f32 / 32768.0;
+        let side = s.iside as f32 / 32768.0;
+        let cm;
+        if n == 2 {
+            let mut mbits = b;
+            let mut sbits = 0;
+            if s.itheta != 0 && s.itheta != 16384 {
+                sbits = 1 << BITRES;
+            }
+            mbits -= sbits;
+            let c = s.itheta > 8192;
+            self.remaining_bits -= s.qalloc + sbits;
+            let mut sign = 0;
+            if sbits != 0 {
+                sign = self.dec.bits(1) as i32;
+            }
+            let sign = (1 - 2 * sign) as f32;
+            let (x2, y2) = if c { (&mut *y, &mut *x) } else { (&mut *x, &mut *y) };
+            cm = self.quant_band(x2, n, mbits, big_b, lowband, lm, lowband_out, 1.0, orig_fill);
+            y2[0] = -sign * x2[1];
+            y2[1] = sign * x2[0];
+            x[0] *= mid;
+            x[1] *= mid;
+            y[0] *= side;
+            y[1] *= side;
+            let tmp = x[0];
+            x[0] = tmp - y[0];
+            y[0] += tmp;
+            let tmp = x[1];
+            x[1] = tmp - y[1];
+            y[1] += tmp;
+        } else {
+            let mut mbits = 0.max(b.min((b - s.delta) / 2));
+            let mut sbits = b - mbits;
+            self.remaining_bits -= s.qalloc;
+            let rebalance = self.remaining_bits;
+            if mbits >= sbits {
+                let mut c = self.quant_band(x, n, mbits, big_b, lowband, lm, lowband_out, 1.0, fill);
+                let rebalance = mbits - (rebalance - self.remaining_bits);
+                if rebalance > 3 << BITRES && s.itheta != 0 {
+                    sbits += rebalance - (3 << BITRES);
+                }
+                c |= self.quant_band(y, n, sbits, big_b, None, lm, None, side, fill >> big_b);
+                cm = c;
+            } else {
+                let mut c = self.quant_band(y, n, sbits, big_b, None, lm, None, side, fill >> big_b);
+                let rebalance = sbits - (rebalance - self.remaining_bits);
+                if rebalance > 3 << BITRES && s.itheta != 16384 {
+                    mbits += rebalance - (3 << BITRES);
+                }
+                c |= self.quant_band(x, n, mbits, big_b, lowband, lm, lowband_out, 1.0, fill);
+                cm = c;
+            }
+            stereo_merge(x, y, mid, n);
+        }
+        if s.inv {
+            for v in y[..n].iter_mut() {
+                *v = -*v;
+            }
+        }
+        cm
+    }
+}
+
+fn special_hybrid_folding(norm: &mut [f32], norm2_offset: usize, start: usize, m: usize, dual_stereo: bool) {
+    let n1 = m * (EBANDS[start + 1] - EBANDS[start]);
+    let n2 = m * (EBANDS[start + 2] - EBANDS[start + 1]);
+    if n2 > n1 {
+        norm.copy_within(2 * n1 - n2..n1, n1);
+        if dual_stereo {
+            norm.copy_within(norm2_offset + 2 * n1 - n2..norm2_offset + n1, norm2_offset + n1);
+        }
+    }
+}
+
+fn fold_source<'a>(fold: &'a mut [f32], norm: &[f32], offset: Option<usize>, n: usize) -> Option<&'a mut [f32]> {
+    let offset = offset?;
+    fold[..n].copy_from_slice(&norm[offset..offset + n]);
+    Some(&mut fold[..n])
+}
+
+pub(super) struct BandParams<'t> {
+    pub(super) start: usize,
+    pub(super) end: usize,
+    pub(super) pulses: &'t [i32],
+    pub(super) short_blocks: bool,
+    pub(super) spread: usize,
+    pub(super) dual_stereo: bool,
+    pub(super) intensity: usize,
+    pub(super) tf_res: &'t [i32],
+    pub(super) total_bits: i32,
+    pub(super) balance: i32,
+    pub(super) lm: i32,
+    pub(super) coded_bands: usize,
+    pub(super) disable_inv: bool,
+}
+
+/// Decodes every band's shape into `x` (and `y` for stereo), filling
+/// `collapse_masks` for the anti-collapse pass. Returns the updated seed.
+pub(super) fn quant_all_bands(p: &BandParams, x_all: &mut [f32], mut y_all: Option<&mut [f32]>, collapse_masks: &mut [u8], dec: &mut RangeDecoder, seed: u32) -> u32 {
+    let m = 1usize << p.lm;
+    let big_b = if p.short_blocks { m } else { 1 };
+    let c_count = if y_all.is_some() { 2 } else { 1 };
+    let norm_offset = m * EBANDS[p.start];
+    let norm_len = m * EBANDS[NB_EBANDS - 1] - norm_offset;
+    let mut norm = vec![0.0f32; c_count * norm_len];
+    let mut dual_stereo = p.dual_stereo;
+    let mut balance = p.balance;
+    let mut lowband_offset = 0;
+    let mut update_lowband = true;
+    let mut ctx = BandCtx {
+        dec,
+        band: 0,
+        intensity: p.intensity,
+        spread: p.spread,
+        tf_change: 0,
+        remaining_bits: 0,
+        seed,
+        disable_inv: p.disable_inv,
+    };
+
+    for i in p.start..p.end {
+        ctx.band = i;
+        let last = i == p.end - 1;
+        let band_start = m * EBANDS[i];
+        let n = m * EBANDS[i + 1] - band_start;
+        let tell = ctx.dec.tell_frac();
+        if i != p.start {
+            balance -= tell;
+        }
+        let remaining_bits = p.total_bits - tell - 1;
+        ctx.remaining_bits = remaining_bits;
+        let b = if i < p.coded_bands {
+            let curr_balance = balance / 3.min(p.coded_bands as i32 - i as i32);
+            0.max(16383.min((remaining_bits + 1).min(p.pulses[i] + curr_balance)))
+        } else {
+            0
+        };
+
+        if (band_start as isize - n as isize >= (m * EBANDS[p.start]) as isize || i == p.start + 1)
+            && (update_lowband || lowband_offset == 0)
+        {
+            lowband_offset = i;
+        }
+        if i == p.start + 1 {
+            special_hybrid_folding(&mut norm, norm_len, p.start, m, dual_stereo);
+        }
+
+        ctx.tf_change = p.tf_res[i];
+
+        let mut effective_lowband: Option<usize> = None;
+        let (mut x_cm, mut y_cm);
+        if lowband_offset != 0 && (p.spread != SPREAD_AGGRESSIVE || big_b > 1 || ctx.tf_change < 0) {
+            let eff = (m * EBANDS[lowband_offset]).saturating_sub(norm_offset + n);
+            effective_lowband = Some(eff);
+            let mut fold_start = lowband_offset;
+            loop {
+                fold_start -= 1;
+                if m * EBANDS[fold_start] <= eff + norm_offset {
+                    break;
+                }
+            }
+            let mut fold_end = lowband_offset - 1;
+            loop {
+                fold_end += 1;
+                if !(fold_end < i && m * EBANDS[fold_end] < eff + norm_offset + n) {
+                    break;
+                }
+            }
+            x_cm = 0;
+            y_cm = 0;
+            for fold_i in fold_start..fold_end.max(fold_start + 1) {
+                x_cm |= u32::from(collapse_masks[fold_i * c_count]);
+                y_cm |= u32::from(collapse_masks[fold_i * c_count + c_count - 1]);
+            }
+        } else {
+            x_cm = (1 << big_b) - 1;
+            y_cm = x_cm;
+        }
+
+        if dual_stereo && i == p.intensity {
+            dual_stereo = false;
+            for j in 0..band_start - norm_offset {
+                norm[j] = 0.5 * (norm[j] + norm[norm_len + j]);
+            }
+        }
+
+        // The folding source may overlap this band's output slot (the hybrid
+        // start band), and is only read before that slot is written, so hand
+        // the band a copy of it.
+        let out_at = band_start - norm_offset;
+        let mut fold = [0.0f32; 176];
+        let x = &mut x_all[band_start..band_start + n];
+        if dual_stereo {
+            let y = &mut y_all.as_deref_mut().expect("dual stereo needs two channels")[band_start..band_start + n];
+            let (norm1, norm2) = norm.split_at_mut(norm_len);
+            let low = fold_source(&mut fold, norm1, effective_lowband, n);
+            x_cm = ctx.quant_band(x, n, b / 2, big_b, low, p.lm, if last { None } else { Some(&mut norm1[out_at..out_at + n]) }, 1.0, x_cm);
+            let low = fold_source(&mut fold, norm2, effective_lowband, n);
+            y_cm = ctx.quant_band(y, n, b / 2, big_b, low, p.lm, if last { None } else { Some(&mut norm2[out_at..out_at + n]) }, 1.0, y_cm);
+        } else {
+            let low = fold_source(&mut fold, &norm, effective_lowband, n);
+            let lowband_out = if last { None } else { Some(&mut norm[out_at..out_at + n]) };
+            x_cm = match y_all.as_deref_mut() {
+                Some(y_all) => {
+                    let y = &mut y_all[band_start..band_start + n];
+                    ctx.quant_band_stereo(x, y, n, b, big_b, low, p.lm, lowband_out, x_cm | y_cm)
+                }
+                None => ctx.quant_band(x, n, b, big_b, low, p.lm, lowband_out, 1.0, x_cm | y_cm),
+            };
+            y_cm = x_cm;
+        }
+        collapse_masks[i * c_count] = x_cm as u8;
+        collapse_masks[i * c_count + c_count - 1] = y_cm as u8;
+        balance += p.pulses[i] + tell;
+        update_lowband = b > (n as i32) << BITRES;
+    }
+    ctx.seed
+}
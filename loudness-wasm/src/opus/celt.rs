@@ -0,0 +1,981 @@
+// CELT decoder (RFC 6716 §4.3)
+//
+// One frame is coarse/fine band energies, a bit allocation derived from
+// them, the PVQ band shapes (`bands`), an inverse MDCT with overlap-add into
+// the channel history, the pitch post-filter and de-emphasis. Lost frames
+// (and the silence a mode switch leaves) are concealed from that history:
+// pitch-periodic extension after a good frame, shaped noise otherwise.
+
+use super::bands::{self, lcg_rand, renormalise_vector, BandParams};
+use super::mdct::{Mdct, OVERLAP};
+use super::pitch::{self, LPC_ORDER};
+use super::range::{RangeDecoder, BITRES};
+use super::tables::{
+    BAND_ALLOCATION, BETA_COEF, BETA_INTRA, CACHE_CAPS, COMB_GAINS, EBANDS, E_MEANS, E_PROB_MODEL, LOG2_FRAC, LOG_N,
+    NB_EBANDS, PRED_COEF, PREEMPH, SMALL_ENERGY_ICDF, SPREAD_ICDF, TAPSET_ICDF, TF_SELECT, TRIM_ICDF,
+};
+use super::InvalidPacket;
+
+const DECODE_BUFFER_SIZE: usize = 2048;
+const MAX_PERIOD: usize = 1024;
+const SHORT_MDCT_SIZE: usize = 120;
+const PLC_PITCH_LAG_MAX: usize = 720;
+const PLC_PITCH_LAG_MIN: usize = 100;
+const COMBFILTER_MINPERIOD: usize = 15;
+const MAX_FINE_BITS: i32 = 8;
+const FINE_OFFSET: i32 = 21;
+const ALLOC_STEPS: i32 = 6;
+const SPREAD_NORMAL: usize = 2;
+
+pub(super) struct CeltDecoder {
+    mdct: Mdct,
+    channels: usize,
+    stream_channels: usize,
+    start: usize,
+    end: usize,
+    disable_inv: bool,
+    rng: u32,
+    last_pitch_index: usize,
+    loss_count: u32,
+    skip_plc: bool,
+    postfilter_period: usize,
+    postfilter_period_old: usize,
+    postfilter_gain: f32,
+    postfilter_gain_old: f32,
+    postfilter_tapset: usize,
+    postfilter_tapset_old: usize,
+    preemph_mem: [f32; 2],
+    /// Per channel: `DECODE_BUFFER_SIZE` samples of history plus the overlap.
+    decode_mem: Vec<Vec<f32>>,
+    lpc: [[f32; LPC_ORDER]; 2],
+    old_band_e: [f32; 2 * NB_EBANDS],
+    old_log_e: [f32; 2 * NB_EBANDS],
+    old_log_e2: [f32; 2 * NB_EBANDS],
+    background_log_e: [f32; 2 * NB_EBANDS],
+}
+
+struct Allocation {
+    coded_bands: usize,
+    intensity: usize,
+    dual_stereo: bool,
+    balance: i32,
+    pulses: [i32; NB_EBANDS],
+    fine_quant: [i32; NB_EBANDS],
+    fine_priority: [i32; NB_EBANDS],
+}
+
+impl CeltDecoder {
+    pub(super) fn new(channels: usize) -> Self {
+        let mut dec = CeltDecoder {
+            mdct: Mdct::new(),
+            channels,
+            stream_channels: channels,
+            start: 0,
+            end: NB_EBANDS,
+            disable_inv: channels == 1,
+            rng: 0,
+            last_pitch_index: 0,
+            loss_count: 0,
+            skip_plc: false,
+            postfilter_period: 0,
+            postfilter_period_old: 0,
+            postfilter_gain: 0.0,
+            postfilter_gain_old: 0.0,
+            postfilter_tapset: 0,
+            postfilter_tapset_old: 0,
+            preemph_mem: [0.0; 2],
+            decode_mem: vec![vec![0.0; DECODE_BUFFER_SIZE + OVERLAP]; channels],
+            lpc: [[0.0; LPC_ORDER]; 2],
+            old_band_e: [0.0; 2 * NB_EBANDS],
+            old_log_e: [0.0; 2 * NB_EBANDS],
+            old_log_e2: [0.0; 2 * NB_EBANDS],
+            background_log_e: [0.0; 2 * NB_EBANDS],
+        };
+        dec.reset();
+        dec
+    }
+
+    pub(super) fn reset(&mut self) {
+        self.rng = 0;
+        self.last_pitch_index = 0;
+        self.loss_count = 0;
+        self.skip_plc = true;
+        self.postfilter_period = 0;
+        self.postfilter_period_old = 0;
+        self.postfilter_gain = 0.0;
+        self.postfilter_gain_old = 0.0;
+        self.postfilter_tapset = 0;
+        self.postfilter_tapset_old = 0;
+        self.preemph_mem = [0.0; 2];
+        for mem in &mut self.decode_mem {
+            mem.fill(0.0);
+        }
+        self.lpc = [[0.0; LPC_ORDER]; 2];
+        self.old_band_e = [0.0; 2 * NB_EBANDS];
+        self.old_log_e = [-28.0; 2 * NB_EBANDS];
+        self.old_log_e2 = [-28.0; 2 * NB_EBANDS];
+        self.background_log_e = [0.0; 2 * NB_EBANDS];
+    }
+
+    /// First coded band: 17 in hybrid frames, where SILK covers the rest.
+    pub(super) fn set_start_band(&mut self, start: usize) {
+        self.start = start;
+    }
+
+    /// One past the last coded band, from the packet's bandwidth.
+    pub(super) fn set_end_band(&mut self, end: usize) {
+        self.end = end;
+    }
+
+    pub(super) fn set_stream_channels(&mut self, channels: usize) {
+        self.stream_channels = channels;
+    }
+
+    pub(super) fn window(&self) -> &[f32] {
+        &self.mdct.window
+    }
+
+    /// Decodes one frame of `frame_size` samples per channel into the
+    /// interleaved `pcm` (full scale = 1.0). `None`, or a frame of at most one
+    /// byte, is concealed.
+    pub(super) fn decode(&mut self, frame: Option<(&mut RangeDecoder, usize)>, pcm: &mut [f32], frame_size: usize) -> Result<(), InvalidPacket> {
+        let lm = (0..4).find(|&lm| SHORT_MDCT_SIZE << lm == frame_size).ok_or(InvalidPacket)?;
+        let m = 1usize << lm;
+        let n = m * SHORT_MDCT_SIZE;
+        let (dec, len) = match frame {
+            Some((dec, len)) if len > 1 => (dec, len),
+            _ => {
+                self.decode_lost(n, lm);
+                self.deemphasis(pcm, n);
+                return Ok(());
+            }
+        };
+        if len > 1275 {
+            return Err(InvalidPacket);
+        }
+        let (start, end) = (self.start, self.end);
+        let c = self.stream_channels;
+        let cc = self.channels;
+        let lm_i = lm as i32;
+
+        self.skip_plc = self.loss_count != 0;
+        if c == 1 {
+            for i in 0..NB_EBANDS {
+                self.old_band_e[i] = self.old_band_e[i].max(self.old_band_e[NB_EBANDS + i]);
+            }
+        }
+
+        let mut total_bits = (len * 8) as i32;
+        let mut tell = dec.tell();
+        let silence = if tell >= total_bits {
+            true
+        } else if tell == 1 {
+            dec.bit_logp(15)
+        } else {
+            false
+        };
+        if silence {
+            tell = total_bits;
+            dec.skip_to(tell);
+        }
+
+        let mut postfilter_gain = 0.0;
+        let mut postfilter_pitch = 0;
+        let mut postfilter_tapset = 0;
+        if start == 0 && tell + 16 <= total_bits {
+            if dec.bit_logp(1) {
+                let octave = dec.uint(6);
+                postfilter_pitch = ((16 << octave) + dec.bits(4 + octave) - 1) as usize;
+                let qg = dec.bits(3);
+                if dec.tell() + 2 <= total_bits {
+                    postfilter_tapset = dec.icdf(&TAPSET_ICDF, 2);
+                }
+                postfilter_gain = 0.093_75 * (qg + 1) as f32;
+            }
+            tell = dec.tell();
+        }
+
+        let is_transient = if lm > 0 && tell + 3 <= total_bits {
+            let transient = dec.bit_logp(3);
+            tell = dec.tell();
+            transient
+        } else {
+            false
+        };
+        let intra = tell + 3 <= total_bits && dec.bit_logp(3);
+        self.unquant_coarse_energy(intra, dec, c, lm);
+        let tf_res = tf_decode(start, end, is_transient, lm, dec);
+
+        let tell = dec.tell();
+        let spread = if tell + 4 <= total_bits { dec.icdf(&SPREAD_ICDF, 5) } else { SPREAD_NORMAL };
+
+        let cap = init_caps(lm, c);
+        let mut offsets = [0i32; NB_EBANDS];
+        let mut dynalloc_logp = 6;
+        total_bits <<= BITRES;
+        let mut tell = dec.tell_frac();
+        for i in start..end {
+            let width = ((c * (EBANDS[i + 1] - EBANDS[i])) << lm) as i32;
+            let quanta = (width << BITRES).min((6 << BITRES).max(width));
+            let mut loop_logp = dynalloc_logp;
+            let mut boost = 0;
+            while tell + (loop_logp << BITRES) < total_bits && boost < cap[i] {
+                let flag = dec.bit_logp(loop_logp as u32);
+                tell = dec.tell_frac();
+                if !flag {
+                    break;
+                }
+                boost += quanta;
+                total_bits -= quanta;
+                loop_logp = 1;
+            }
+            offsets[i] = boost;
+            if boost > 0 {
+                dynalloc_logp = 2.max(dynalloc_logp - 1);
+            }
+        }
+
+        let alloc_trim = if tell + (6 << BITRES) <= total_bits { dec.icdf(&TRIM_ICDF, 7) as i32 } else { 5 };
+        let mut bits = (((len * 8) as i32) << BITRES) - dec.tell_frac() - 1;
+        let anti_collapse_rsv = if is_transient && lm >= 2 && bits >= (lm_i + 2) << BITRES { 1 << BITRES } else { 0 };
+        bits -= anti_collapse_rsv;
+        let alloc = compute_allocation(start, end, &offsets, &cap, alloc_trim, bits, c, lm_i, dec);
+        self.unquant_fine_energy(&alloc.fine_quant, dec, c);
+
+        for mem in &mut self.decode_mem {
+            mem.copy_within(n..DECODE_BUFFER_SIZE + OVERLAP / 2, 0);
+        }
+
+        let mut x = vec![0.0f32; c * n];
+        let mut collapse_masks = [0u8; 2 * NB_EBANDS];
+        let params = BandParams {
+            start,
+            end,
+            pulses: &alloc.pulses,
+            short_blocks: is_transient,
+            spread,
+            dual_stereo: alloc.dual_stereo,
+            intensity: alloc.intensity,
+            tf_res: &tf_res,
+            total_bits: (((len * 8) as i32) << BITRES) - anti_collapse_rsv,
+            balance: alloc.balance,
+            lm: lm_i,
+            coded_bands: alloc.coded_bands,
+            disable_inv: self.disable_inv,
+        };
+        {
+            let (xs, ys) = x.split_at_mut(n);
+            let y = (c == 2).then_some(ys);
+            self.rng = bands::quant_all_bands(&params, xs, y, &mut collapse_masks, dec, self.rng);
+        }
+
+        let anti_collapse_on = anti_collapse_rsv > 0 && dec.bits(1) != 0;
+        let bits_left = (len * 8) as i32 - dec.tell();
+        self.unquant_energy_finalise(&alloc.fine_quant, &alloc.fine_priority, bits_left, dec, c);
+        if anti_collapse_on {
+            self.anti_collapse(&mut x, &collapse_masks, lm, c, n, &alloc.pulses);
+        }
+        if silence {
+            self.old_band_e[..c * NB_EBANDS].fill(-28.0);
+        }
+
+        self.synthesis(&x, start, end.min(NB_EBANDS), c, is_transient, lm, silence);
+
+        let at = DECODE_BUFFER_SIZE - n;
+        self.postfilter_period = self.postfilter_period.max(COMBFILTER_MINPERIOD);
+        self.postfilter_period_old = self.postfilter_period_old.max(COMBFILTER_MINPERIOD);
+        for ch in 0..cc {
+            let mem = &mut self.decode_mem[ch];
+            comb_filter(
+                mem,
+                at,
+                None,
+                SHORT_MDCT_SIZE,
+                [self.postfilter_period_old, self.postfilter_period],
+                [self.postfilter_gain_old, self.postfilter_gain],
+                [self.postfilter_tapset_old, self.postfilter_tapset],
+                &self.mdct.window,
+            );
+            if lm != 0 {
+                comb_filter(
+                    mem,
+                    at + SHORT_MDCT_SIZE,
+                    None,
+                    n - SHORT_MDCT_SIZE,
+                    [self.postfilter_period, postfilter_pitch],
+                    [self.postfilter_gain, postfilter_gain],
+                    [self.postfilter_tapset, postfilter_tapset],
+                    &self.mdct.window,
+                );
+            }
+        }
+        self.postfilter_period_old = self.postfilter_period;
+        self.postfilter_gain_old = self.postfilter_gain;
+        self.postfilter_tapset_old = self.postfilter_tapset;
+        self.postfilter_period = postfilter_pitch;
+        self.postfilter_gain = postfilter_gain;
+        self.postfilter_tapset = postfilter_tapset;
+        if lm != 0 {
+            self.postfilter_period_old = self.postfilter_period;
+            self.postfilter_gain_old = self.postfilter_gain;
+            self.postfilter_tapset_old = self.postfilter_tapset;
+        }
+
+        if c == 1 {
+            self.old_band_e.copy_within(0..NB_EBANDS, NB_EBANDS);
+        }
+        if !is_transient {
+            self.old_log_e2 = self.old_log_e;
+            self.old_log_e = self.old_band_e;
+            let max_background_increase = if self.loss_count < 10 { m as f32 * 0.001 } else { 1.0 };
+            for (bg, &e) in self.background_log_e.iter_mut().zip(&self.old_band_e) {
+                *bg = (*bg + max_background_increase).min(e);
+            }
+        } else {
+            for (old, &e) in self.old_log_e.iter_mut().zip(&self.old_band_e) {
+                *old = old.min(e);
+            }
+        }
+        for ch in 0..2 {
+            for i in (0..start).chain(end..NB_EBANDS) {
+                let k = ch * NB_EBANDS + i;
+                self.old_band_e[k] = 0.0;
+                self.old_log_e[k] = -28.0;
+                self.old_log_e2[k] = -28.0;
+            }
+        }
+        self.rng = dec.rng();
+        self.deemphasis(pcm, n);
+        self.loss_count = 0;
+        if dec.tell() > 8 * len as i32 {
+            return Err(InvalidPacket);
+        }
+        Ok(())
+    }
+
+    fn unquant_coarse_energy(&mut self, intra: bool, dec: &mut RangeDecoder, c: usize, lm: usize) {
+        let prob_model = &E_PROB_MODEL[lm][usize::from(intra)];
+        let (coef, beta) = if intra { (0.0, BETA_INTRA) } else { (PRED_COEF[lm], BETA_COEF[lm]) };
+        let budget = dec.storage() as i32 * 8;
+        let mut prev = [0.0f32; 2];
+        for i in self.start..self.end {
+            for (ch, prev) in prev.iter_mut().enumerate().take(c) {
+                let tell = dec.tell();
+                let qi = if budget - tell >= 15 {
+                    let pi = 2 * i.min(20);
+                    dec.laplace(u32::from(prob_model[pi]) << 7, i32::from(prob_model[pi + 1]) << 6)
+                } else if budget - tell >= 2 {
+                    let qi = dec.icdf(&SMALL_ENERGY_ICDF, 2) as i32;
+                    (qi >> 1) ^ -(qi & 1)
+                } else if budget - tell >= 1 {
+                    -i32::from(dec.bit_logp(1))
+                } else {
+                    -1
+                };
+                let q = qi as f32;
+                let e = &mut self.old_band_e[ch * NB_EBANDS + i];
+                *e = e.max(-9.0);
+                *e = coef * *e + *prev + q;
+                *prev += q - beta * q;
+            }
+        }
+    }
+
+    fn unquant_fine_energy(&mut self, fine_quant: &[i32], dec: &mut RangeDecoder, c: usize) {
+        for (i, &fine) in fine_quant.iter().enumerate().take(self.end).skip(self.start) {
+            if fine <= 0 {
+                continue;
+            }
+            for ch in 0..c {
+                let q2 = dec.bits(fine as u32);
+                let offset = (q2 as f32 + 0.5) * (1 << (14 - fine)) as f32 / 16384.0 - 0.5;
+                self.old_band_e[ch * NB_EBANDS + i] += offset;
+            }
+        }
+    }
+
+    fn unquant_energy_finalise(&mut self, fine_quant: &[i32], fine_priority: &[i32], mut bits_left: i32, dec: &mut RangeDecoder, c: usize) {
+        for prio in 0..2 {
+            let mut i = self.start;
+            while i < self.end && bits_left >= c as i32 {
+                if fine_quant[i] < MAX_FINE_BITS && fine_priority[i] == prio {
+                    for ch in 0..c {
+                        let q2 = dec.bits(1);
+                        let offset = (q2 as f32 - 0.5) * (1 << (14 - fine_quant[i] - 1)) as f32 / 16384.0;
+                        self.old_band_e[ch * NB_EBANDS + i] += offset;
+                        bits_left -= 1;
+                    }
+                }
+                i += 1;
+            }
+        }
+    }
+
+    /// Fills MDCT blocks a transient left empty with noise at the energy the
+    /// previous frames suggest.
+    fn anti_collapse(&self, x: &mut [f32], collapse_masks: &[u8], lm: usize, c: usize, size: usize, pulses: &[i32]) {
+        let mut seed = self.rng;
+        for i in self.start..self.end {
+            let n0 = EBANDS[i + 1] - EBANDS[i];
+            let depth = ((1 + pulses[i]) as u32 / n0 as u32) >> lm;
+            let thresh = 0.5 * (-0.125 * depth as f32).exp2();
+            let sqrt_1 = 1.0 / ((n0 << lm) as f32).sqrt();
+            for ch in 0..c {
+                let mut prev1 = self.old_log_e[ch * NB_EBANDS + i];
+                let mut prev2 = self.old_log_e2[ch * NB_EBANDS + i];
+                if c == 1 {
+                    prev1 = prev1.max(self.old_log_e[NB_EBANDS + i]);
+                    prev2 = prev2.max(self.old_log_e2[NB_EBANDS + i]);
+                }
+                let ediff = (self.old_band_e[ch * NB_EBANDS + i] - prev1.min(prev2)).max(0.0);
+                let mut r = 2.0 * (-ediff).exp2();
+                if lm == 3 {
+                    r *= std::f32::consts::SQRT_2;
+                }
+                let r = r.min(thresh) * sqrt_1;
+                let band = &mut x[ch * size + (EBANDS[i] << lm)..][..n0 << lm];
+                let mut renormalize = false;
+                for k in 0..1 << lm {
+                    if collapse_masks[i * c + ch] & (1 << k) == 0 {
+                        for j in 0..n0 {
+                            seed = lcg_rand(seed);
+                            band[(j << lm) + k] = if seed & 0x8000 != 0 { r } else { -r };
+                        }
+                        renormalize = true;
+                    }
+                }
+                if renormalize {
+                    renormalise_vector(band, 1.0);
+                }
+            }
+        }
+    }
+
+    /// Scales the shapes by their band energies and runs the inverse MDCT
+    /// into each channel's history, just before the overlap.
+    #[allow(clippy::too_many_arguments)]
+    fn synthesis(&mut self, x: &[f32], start: usize, eff_end: usize, c: usize, is_transient: bool, lm: usize, silence: bool) {
+        let m = 1 << lm;
+        let n = m * SHORT_MDCT_SIZE;
+        let (blocks, nb, shift) = if is_transient { (m, SHORT_MDCT_SIZE, 3) } else { (1, n, 3 - lm) };
+        let at = DECODE_BUFFER_SIZE - n;
+        let mut freq = vec![0.0f32; n];
+        let cc = self.channels;
+        if cc == 2 && c == 1 {
+            denormalise_bands(x, &mut freq, &self.old_band_e, start, eff_end, m, silence);
+            for mem in &mut self.decode_mem {
+                for b in 0..blocks {
+                    self.mdct.backward(&freq[b..], &mut mem[at + nb * b..], shift, blocks);
+                }
+            }
+        } else if cc == 1 && c == 2 {
+            let mut freq2 = vec![0.0f32; n];
+            denormalise_bands(x, &mut freq, &self.old_band_e, start, eff_end, m, silence);
+            denormalise_bands(&x[n..], &mut freq2, &self.old_band_e[NB_EBANDS..], start, eff_end, m, silence);
+            for (f, f2) in freq.iter_mut().zip(&freq2) {
+                *f = 0.5 * *f + 0.5 * f2;
+            }
+            for b in 0..blocks {
+                self.mdct.backward(&freq[b..], &mut self.decode_mem[0][at + nb * b..], shift, blocks);
+            }
+        } else {
+            for ch in 0..cc {
+                denormalise_bands(&x[ch * n..], &mut freq, &self.old_band_e[ch * NB_EBANDS..], start, eff_end, m, silence);
+                for b in 0..blocks {
+                    self.mdct.backward(&freq[b..], &mut self.decode_mem[ch][at + nb * b..], shift, blocks);
+                }
+            }
+        }
+    }
+
+    fn deemphasis(&mut self, pcm: &mut [f32], n: usize) {
+        let cc = self.channels;
+        for ch in 0..cc {
+            let mut m = self.preemph_mem[ch];
+            let syn = &self.decode_mem[ch][DECODE_BUFFER_SIZE - n..DECODE_BUFFER_SIZE];
+            for (j, &v) in syn.iter().enumerate() {
+                let tmp = v + 1e-30 + m;
+                m = PREEMPH * tmp;
+                pcm[j * cc + ch] = tmp * (1.0 / 32768.0);
+            }
+            self.preemph_mem[ch] = m;
+        }
+    }
+
+    fn decode_lost(&mut self, n: usize, lm: usize) {
+        let c = self.channels;
+        let start = self.start;
+        let noise_based = self.loss_count >= 5 || start != 0 || self.skip_plc;
+        if noise_based {
+            let end = self.end;
+            let eff_end = start.max(end.min(NB_EBANDS));
+            let decay = if self.loss_count == 0 { 1.5 } else { 0.5 };
+            for ch in 0..c {
+                for i in start..end {
+                    let k = ch * NB_EBANDS + i;
+                    self.old_band_e[k] = self.background_log_e[k].max(self.old_band_e[k] - decay);
+                }
+            }
+            let mut x = vec![0.0f32; c * n];
+            let mut seed = self.rng;
+            for ch in 0..c {
+                for i in start..eff_end {
+                    let band = &mut x[n * ch + (EBANDS[i] << lm)..][..(EBANDS[i + 1] - EBANDS[i]) << lm];
+                    for v in band.iter_mut() {
+                        seed = lcg_rand(seed);
+                        *v = ((seed as i32) >> 20) as f32;
+                    }
+                    renormalise_vector(band, 1.0);
+                }
+            }
+            self.rng = seed;
+            for mem in &mut self.decode_mem {
+                mem.copy_within(n..DECODE_BUFFER_SIZE + OVERLAP / 2, 0);
+            }
+            self.synthesis(&x, start, eff_end, c, false, lm, false);
+        } else {
+            let (pitch_index, fade) = if self.loss_count == 0 {
+                self.last_pitch_index = self.plc_pitch_search();
+                (self.last_pitch_index, 1.0)
+            } else {
+                (self.last_pitch_index, 0.8)
+            };
+            let exc_length = (2 * pitch_index).min(MAX_PERIOD);
+            for ch in 0..c {
+                let buf = &mut self.decode_mem[ch];
+                // `exc[LPC_ORDER..]` is the last MAX_PERIOD samples, with the
+                // filter history in front.
+                let mut exc = buf[DECODE_BUFFER_SIZE - MAX_PERIOD - LPC_ORDER..DECODE_BUFFER_SIZE].to_vec();
+                if self.loss_count == 0 {
+                    let mut ac = [0.0f32; LPC_ORDER + 1];
+                    pitch::autocorr(&exc[LPC_ORDER..], &mut ac, &self.mdct.window);
+                    ac[0] *= 1.0001;
+                    for (i, a) in ac.iter_mut().enumerate().skip(1) {
+                        *a -= *a * (0.008 * 0.008) * (i * i) as f32;
+                    }
+                    pitch::lpc(&ac, &mut self.lpc[ch]);
+                }
+                let lpc = self.lpc[ch];
+                let exc_start = LPC_ORDER + MAX_PERIOD - exc_length;
+                let mut fir_tmp = vec![0.0f32; exc_length];
+                pitch::fir(&exc, exc_start, &lpc, &mut fir_tmp);
+                exc[exc_start..].copy_from_slice(&fir_tmp);
+
+                let decay = {
+                    let (mut e1, mut e2) = (1.0f32, 1.0f32);
+                    let decay_length = exc_length >> 1;
+                    for i in 0..decay_length {
+                        let e = exc[LPC_ORDER + MAX_PERIOD - decay_length + i];
+                        e1 += e * e;
+                        let e = exc[LPC_ORDER + MAX_PERIOD - 2 * decay_length + i];
+                        e2 += e * e;
+                    }
+                    (e1.min(e2) / e2).sqrt()
+                };
+
+                buf.copy_within(n..DECODE_BUFFER_SIZE, 0);
+                let extrapolation_offset = MAX_PERIOD - pitch_index;
+                let extrapolation_len = n + OVERLAP;
+                let out = DECODE_BUFFER_SIZE - n;
+                let mut attenuation = fade * decay;
+                let mut s1 = 0.0f32;
+                let mut j = 0;
+                for i in 0..extrapolation_len {
+                    if j >= pitch_index {
+                        j -= pitch_index;
+                        attenuation *= decay;
+                    }
+                    buf[out + i] = attenuation * exc[LPC_ORDER + extrapolation_offset + j];
+                    let tmp = buf[DECODE_BUFFER_SIZE - MAX_PERIOD - n + extrapolation_offset + j];
+                    s1 += tmp * tmp;
+                    j += 1;
+                }
+                let mut lpc_mem = [0.0f32; LPC_ORDER];
+                for (i, v) in lpc_mem.iter_mut().enumerate() {
+                    *v = buf[out - 1 - i];
+                }
+                pitch::iir(&mut buf[out..out + extrapolation_len], &lpc, &lpc_mem);
+
+                let s2: f32 = buf[out..out + extrapolation_len].iter().map(|v| v * v).sum();
+                // A NaN from the IIR counts as blowing up too.
+                if s1.is_nan() || s2.is_nan() || s1 <= 0.2 * s2 {
+                    buf[out..out + extrapolation_len].fill(0.0);
+                } else if s1 < s2 {
+                    let ratio = ((s1 + 1.0) / (s2 + 1.0)).sqrt();
+                    for (i, v) in buf[out..out + extrapolation_len].iter_mut().enumerate() {
+                        let g = if i < OVERLAP { 1.0 - self.mdct.window[i] * (1.0 - ratio) } else { ratio };
+                        *v *= g;
+                    }
+                }
+
+                // Pre-filter the overlap so the post-filter the next frame
+                // applies cancels out, then fake the TDAC fold.
+                let mut etmp = [0.0f32; OVERLAP];
+                comb_filter(
+                    buf,
+                    DECODE_BUFFER_SIZE,
+                    Some(&mut etmp),
+                    OVERLAP,
+                    [self.postfilter_period; 2],
+                    [-self.postfilter_gain; 2],
+                    [self.postfilter_tapset; 2],
+                    &[],
+                );
+                let window = &self.mdct.window;
+                for i in 0..OVERLAP / 2 {
+                    buf[DECODE_BUFFER_SIZE + i] = window[i] * etmp[OVERLAP - 1 - i] + window[OVERLAP - i - 1] * etmp[i];
+                }
+            }
+        }
+        self.loss_count += 1;
+    }
+
+    fn plc_pitch_search(&self) -> usize {
+        let channels: Vec<&[f32]> = self.decode_mem.iter().map(|m| &m[..DECODE_BUFFER_SIZE]).collect();
+        let lp = pitch::downsample(&channels, DECODE_BUFFER_SIZE);
+        let pitch_index = pitch::search(
+            &lp[PLC_PITCH_LAG_MAX >> 1..],
+            &lp,
+            DECODE_BUFFER_SIZE - PLC_PITCH_LAG_MAX,
+            PLC_PITCH_LAG_MAX - PLC_PITCH_LAG_MIN,
+        );
+        PLC_PITCH_LAG_MAX - pitch_index
+    }
+}
+
+fn tf_decode(start: usize, end: usize, is_transient: bool, lm: usize, dec: &mut RangeDecoder) -> [i32; NB_EBANDS] {
+    let mut tf_res = [0i32; NB_EBANDS];
+    let mut budget = dec.storage() as u32 * 8;
+    let mut tell = dec.tell() as u32;
+    let mut logp = if is_transient { 2 } else { 4 };
+    let tf_select_rsv = lm > 0 && tell + logp < budget;
+    budget -= u32::from(tf_select_rsv);
+    let mut tf_changed = 0;
+    let mut curr = 0;
+    for res in &mut tf_res[start..end] {
+        if tell + logp <= budget {
+            curr ^= i32::from(dec.bit_logp(logp));
+            tell = dec.tell() as u32;
+            tf_changed |= curr;
+        }
+        *res = curr;
+        logp = if is_transient { 4 } else { 5 };
+    }
+    let t = 4 * usize::from(is_transient);
+    let changed = tf_changed as usize;
+    let mut tf_select = 0;
+    if tf_select_rsv && TF_SELECT[lm][t + changed] != TF_SELECT[lm][t + 2 + changed] {
+        tf_select = usize::from(dec.bit_logp(1));
+    }
+    for res in &mut tf_res[start..end] {
+        *res = i32::from(TF_SELECT[lm][t + 2 * tf_select + *res as usize]);
+    }
+    tf_res
+}
+
+fn init_caps(lm: usize, c: usize) -> [i32; NB_EBANDS] {
+    let mut cap = [0i32; NB_EBANDS];
+    for (i, v) in cap.iter_mut().enumerate() {
+        let n = ((EBANDS[i + 1] - EBANDS[i]) << lm) as i32;
+        *v = ((i32::from(CACHE_CAPS[NB_EBANDS * (2 * lm + c - 1) + i]) + 64) * c as i32 * n) >> 2;
+    }
+    cap
+}
+
+fn denormalise_bands(x: &[f32], freq: &mut [f32], band_log_e: &[f32], start: usize, end: usize, m: usize, silence: bool) {
+    let n = m * SHORT_MDCT_SIZE;
+    let (mut start, mut end, mut bound) = (start, end, m * EBANDS[end]);
+    if silence {
+        bound = 0;
+        start = 0;
+        end = 0;
+    }
+    freq[..m * EBANDS[start]].fill(0.0);
+    for i in start..end {
+        let g = (band_log_e[i] + E_MEANS[i]).min(32.0).exp2();
+        let band = m * EBANDS[i]..m * EBANDS[i + 1];
+        for (f, &v) in freq[band.clone()].iter_mut().zip(&x[band]) {
+            *f = v * g;
+        }
+    }
+    freq[bound..n].fill(0.0);
+}
+
+/// The pitch post-filter, cross-fading from the `[0]` parameters to the `[1]`
+/// ones over the window. Filters `buf[at..at + n]` in place (so it feeds back
+/// on its own output), or into `out` reading `buf` as-is.
+#[allow(clippy::too_many_arguments)]
+fn comb_filter(buf: &mut [f32], at: usize, mut out: Option<&mut [f32]>, n: usize, period: [usize; 2], gain: [f32; 2], tapset: [usize; 2], window: &[f32]) {
+    if gain[0] == 0.0 && gain[1] == 0.0 {
+        if let Some(out) = out {
+            out[..n].copy_from_slice(&buf[at..at + n]);
+        }
+        return;
+    }
+    let t0 = period[0].max(COMBFILTER_MINPERIOD);
+    let t1 = period[1].max(COMBFILTER_MINPERIOD);
+    let g0 = COMB_GAINS[tapset[0]].map(|g| gain[0] * g);
+    let g1 = COMB_GAINS[tapset[1]].map(|g| gain[1] * g);
+    let overlap = if gain[0] == gain[1] && t0 == t1 && tapset[0] == tapset[1] { 0 } else { window.len() };
+    let mut write = |buf: &mut [f32], i: usize, v: f32| match out.as_deref_mut() {
+        Some(out) => out[i] = v,
+        None => buf[at + i] = v,
+    };
+    let tap = |buf: &[f32], i: usize, t: usize, g: [f32; 3]| {
+        let p = at + i - t;
+        g[0] * buf[p] + g[1] * (buf[p + 1] + buf[p - 1]) + g[2] * (buf[p + 2] + buf[p - 2])
+    };
+    for i in 0..overlap {
+        let f = window[i] * window[i];
+        let v = buf[at + i] + (1.0 - f) * tap(buf, i, t0, g0) + f * tap(buf, i, t1, g1);
+        write(buf, i, v);
+    }
+    if gain[1] == 0.0 {
+        for i in overlap..n {
+            let v = buf[at + i];
+            write(buf, i, v);
+        }
+        return;
+    }
+    for i in overlap..n {
+        let v = buf[at + i] + tap(buf, i, t1, g1);
+        write(buf, i, v);
+    }
+}
+
+/// CELT's bit allocation (RFC 6716 §4.3.3): interpolates between the static
+/// allocation vectors to fit the budget, decodes the band skipping and
+/// stereo parameters, and splits each band's bits between fine energy and
+/// PVQ.
+#[allow(clippy::too_many_arguments)]
+fn compute_allocation(start: usize, end: usize, offsets: &[i32], cap: &[i32], alloc_trim: i32, total: i32, c: usize, lm: i32, dec: &mut RangeDecoder) -> Allocation {
+    let c_i = c as i32;
+    let width = |j: usize| (EBANDS[j + 1] - EBANDS[j]) as i32;
+    let mut total = total.max(0);
+    let mut skip_start = start;
+    let skip_rsv = if total >= 1 << BITRES { 1 << BITRES } else { 0 };
+    total -= skip_rsv;
+    let mut intensity_rsv = 0;
+    let mut dual_stereo_rsv = 0;
+    if c == 2 {
+        intensity_rsv = LOG2_FRAC[end - start];
+        if intensity_rsv > total {
+            intensity_rsv = 0;
+        } else {
+            total -= intensity_rsv;
+            dual_stereo_rsv = if total >= 1 << BITRES { 1 << BITRES } else { 0 };
+            total -= dual_stereo_rsv;
+        }
+    }
+
+    let mut thresh = [0i32; NB_EBANDS];
+    let mut trim_offset = [0i32; NB_EBANDS];
+    for j in start..end {
+        thresh[j] = (c_i << BITRES).max((3 * width(j)) << lm << BITRES >> 4);
+        trim_offset[j] = (c_i * width(j) * (alloc_trim - 5 - lm) * (end - j - 1) as i32 * (1 << (lm + BITRES as i32))) >> 6;
+        if width(j) << lm == 1 {
+            trim_offset[j] -= c_i << BITRES;
+        }
+    }
+    let alloc_bits = |vector: usize, j: usize| (c_i * width(j) * i32::from(BAND_ALLOCATION[vector * NB_EBANDS + j])) << lm >> 2;
+    let nb_vectors = BAND_ALLOCATION.len() / NB_EBANDS;
+    let mut lo = 1usize;
+    let mut hi = nb_vectors - 1;
+    loop {
+        let mut done = false;
+        let mut psum = 0;
+        let mid = (lo + hi) >> 1;
+        for j in (start..end).rev() {
+            let mut bitsj = alloc_bits(mid, j);
+            if bitsj > 0 {
+                bitsj = 0.max(bitsj + trim_offset[j]);
+            }
+            bitsj += offsets[j];
+            if bitsj >= thresh[j] || done {
+                done = true;
+                psum += bitsj.min(cap[j]);
+            } else if bitsj >= c_i << BITRES {
+                psum += c_i << BITRES;
+            }
+        }
+        if psum > total {
+            hi = mid - 1;
+        } else {
+            lo = mid + 1;
+        }
+        if lo > hi {
+            break;
+        }
+    }
+    let hi = lo;
+    let lo = lo - 1;
+    let mut bits1 = [0i32; NB_EBANDS];
+    let mut bits2 = [0i32; NB_EBANDS];
+    for j in start..end {
+        let mut bits1j = alloc_bits(lo, j);
+        let mut bits2j = if hi >= nb_vectors { cap[j] } else { alloc_bits(hi, j) };
+        if bits1j > 0 {
+            bits1j = 0.max(bits1j + trim_offset[j]);
+        }
+        if bits2j > 0 {
+            bits2j = 0.max(bits2j + trim_offset[j]);
+        }
+        if lo > 0 {
+            bits1j += offsets[j];
+        }
+        bits2j += offsets[j];
+        if offsets[j] > 0 {
+            skip_start = j;
+        }
+        bits1[j] = bits1j;
+        bits2[j] = 0.max(bits2j - bits1j);
+    }
+
+    // Interpolate between the two vectors in 1/64 steps.
+    let alloc_floor = c_i << BITRES;
+    let stereo = usize::from(c > 1);
+    let log_m = lm << BITRES;
+    let mut lo = 0;
+    let mut hi = 1 << ALLOC_STEPS;
+    for _ in 0..ALLOC_STEPS {
+        let mid = (lo + hi) >> 1;
+        let mut psum = 0;
+        let mut done = false;
+        for j in (start..end).rev() {
+            let tmp = bits1[j] + ((mid * bits2[j]) >> ALLOC_STEPS);
+            if tmp >= thresh[j] || done {
+                done = true;
+                psum += tmp.min(cap[j]);
+            } else if tmp >= alloc_floor {
+                psum += alloc_floor;
+            }
+        }
+        if psum > total {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    let mut psum = 0;
+    let mut done = false;
+    let mut bits = [0i32; NB_EBANDS];
+    for j in (start..end).rev() {
+        let mut tmp = bits1[j] + ((lo * bits2[j]) >> ALLOC_STEPS);
+        if tmp < thresh[j] && !done {
+            tmp = if tmp >= alloc_floor { alloc_floor } else { 0 };
+        } else {
+            done = true;
+        }
+        tmp = tmp.min(cap[j]);
+        bits[j] = tmp;
+        psum += tmp;
+    }
+
+    // Decide which bands to skip, working backwards from the end.
+    let span = |to: usize| (EBANDS[to] - EBANDS[start]) as u32;
+    let mut coded_bands = end;
+    loop {
+        let j = coded_bands - 1;
+        if j <= skip_start {
+            total += skip_rsv;
+            break;
+        }
+        let mut left = total - psum;
+        let percoeff = ((left as u32) / span(coded_bands)) as i32;
+        left -= span(coded_bands) as i32 * percoeff;
+        let rem = 0.max(left - span(j) as i32);
+        let band_width = (EBANDS[coded_bands] - EBANDS[j]) as i32;
+        let mut band_bits = bits[j] + percoeff * band_width + rem;
+        if band_bits >= thresh[j].max(alloc_floor + (1 << BITRES)) {
+            if dec.bit_logp(1) {
+                break;
+            }
+            psum += 1 << BITRES;
+            band_bits -= 1 << BITRES;
+        }
+        psum -= bits[j] + intensity_rsv;
+        if intensity_rsv > 0 {
+            intensity_rsv = LOG2_FRAC[j - start];
+        }
+        psum += intensity_rsv;
+        if band_bits >= alloc_floor {
+            psum += alloc_floor;
+            bits[j] = alloc_floor;
+        } else {
+            bits[j] = 0;
+        }
+        coded_bands -= 1;
+    }
+
+    let intensity = if intensity_rsv > 0 { start + dec.uint((coded_bands + 1 - start) as u32) as usize } else { 0 };
+    if intensity <= start {
+        total += dual_stereo_rsv;
+        dual_stereo_rsv = 0;
+    }
+    let dual_stereo = dual_stereo_rsv > 0 && dec.bit_logp(1);
+
+    let mut left = total - psum;
+    let percoeff = ((left as u32) / span(coded_bands)) as i32;
+    left -= span(coded_bands) as i32 * percoeff;
+    for (j, b) in bits.iter_mut().enumerate().take(coded_bands).skip(start) {
+        *b += percoeff * width(j);
+    }
+    for (j, b) in bits.iter_mut().enumerate().take(coded_bands).skip(start) {
+        let tmp = left.min(width(j));
+        *b += tmp;
+        left -= tmp;
+    }
+
+    let mut ebits = [0i32; NB_EBANDS];
+    let mut fine_priority = [0i32; NB_EBANDS];
+    let mut balance = 0;
+    for j in start..coded_bands {
+        let n0 = width(j);
+        let n = n0 << lm;
+        let bit = bits[j] + balance;
+        let mut excess;
+        if n > 1 {
+            excess = (bit - cap[j]).max(0);
+            bits[j] = bit - excess;
+            let den = c_i * n + i32::from(c == 2 && n > 2 && !dual_stereo && j < intensity);
+            let nclogn = den * (i32::from(LOG_N[j]) + log_m);
+            let mut offset = (nclogn >> 1) - den * FINE_OFFSET;
+            if n == 2 {
+                offset += den << BITRES >> 2;
+            }
+            if bits[j] + offset < (den * 2) << BITRES {
+                offset += nclogn >> 2;
+            } else if bits[j] + offset < (den * 3) << BITRES {
+                offset += nclogn >> 3;
+            }
+            ebits[j] = 0.max(bits[j] + offset + (den << (BITRES - 1)));
+            ebits[j] = ((ebits[j] as u32 / den as u32) >> BITRES) as i32;
+            if c_i * ebits[j] > bits[j] >> BITRES {
+                ebits[j] = bits[j] >> stereo >> BITRES;
+            }
+            ebits[j] = ebits[j].min(MAX_FINE_BITS);
+            fine_priority[j] = i32::from(ebits[j] * (den << BITRES) >= bits[j] + offset);
+            bits[j] -= (c_i * ebits[j]) << BITRES;
+        } else {
+            excess = 0.max(bit - (c_i << BITRES));
+            bits[j] = bit - excess;
+            ebits[j] = 0;
+            fine_priority[j] = 1;
+        }
+        if excess > 0 {
+            let extra_fine = (excess >> (stereo as u32 + BITRES)).min(MAX_FINE_BITS - ebits[j]);
+            ebits[j] += extra_fine;
+            let extra_bits = (extra_fine * c_i) << BITRES;
+            fine_priority[j] = i32::from(extra_bits >= excess - balance);
+            excess -= extra_bits;
+        }
+        balance = excess;
+    }
+    for j in coded_bands..end {
+        ebits[j] = bits[j] >> stereo >> BITRES;
+        bits[j] = 0;
+        fine_priority[j] = i32::from(ebits[j] < 1);
+    }
+
+    Allocation { coded_bands, intensity, dual_stereo, balance, pulses: bits, fine_quant: ebits, fine_priority }
+}
@@ -0,0 +1,272 @@
+// Opus stream decoder (RFC 6716 §4)
+//
+// Runs each frame through SILK, CELT or both (hybrid: SILK below 8 kHz,
+// CELT from band 17 up) and smooths the seams a mode switch leaves: the
+// optional redundant CELT frame around a switch, a concealed 5 ms lead-in
+// otherwise, and a CELT "silence" frame to fade out hybrid's upper bands.
+
+use super::celt::CeltDecoder;
+use super::packet::{Bandwidth, Mode, Packet};
+use super::range::RangeDecoder;
+use super::silk::{SilkDecoder, SilkParams};
+use super::InvalidPacket;
+
+const F20: usize = 960;
+const F10: usize = F20 / 2;
+const F5: usize = F10 / 2;
+const F2_5: usize = F5 / 2;
+/// The longest packet: 120 ms.
+pub(super) const MAX_FRAME_SIZE: usize = 5760;
+
+/// Cross-fades `in1` into `in2` over one CELT overlap with the squared
+/// window, writing `out`.
+fn smooth_fade(in1: &[f32], in2: &[f32], out: &mut [f32], channels: usize, window: &[f32]) {
+    for c in 0..channels {
+        for (i, &w) in window[..F2_5].iter().enumerate() {
+            let w = w * w;
+            let k = i * channels + c;
+            out[k] = w * in2[k] + (1.0 - w) * in1[k];
+        }
+    }
+}
+
+/// One (mono or coupled stereo) Opus stream at 48 kHz.
+pub(super) struct StreamDecoder {
+    channels: usize,
+    celt: CeltDecoder,
+    silk: SilkDecoder,
+    silk_params: SilkParams,
+    stream_channels: usize,
+    mode: Mode,
+    bandwidth: Bandwidth,
+    prev_mode: Option<Mode>,
+    frame_size: usize,
+    prev_redundancy: bool,
+}
+
+impl StreamDecoder {
+    pub(super) fn new(channels: usize) -> Self {
+        StreamDecoder {
+            channels,
+            celt: CeltDecoder::new(channels),
+            silk: SilkDecoder::new(),
+            silk_params: SilkParams { payload_ms: 10, internal_khz: 16, channels_internal: channels, channels_api: channels },
+            stream_channels: channels,
+            mode: Mode::Celt,
+            bandwidth: Bandwidth::Full,
+            prev_mode: None,
+            frame_size: F2_5,
+            prev_redundancy: false,
+        }
+    }
+
+    pub(super) fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub(super) fn reset(&mut self) {
+        self.celt.reset();
+        self.silk.reset();
+        self.stream_channels = self.channels;
+        self.prev_mode = None;
+        self.frame_size = F2_5;
+        self.prev_redundancy = false;
+    }
+
+    /// Decodes a packet into the interleaved `pcm`, or conceals `frame_size`
+    /// samples (a multiple of 2.5 ms) without one. Returns the samples per
+    /// channel.
+    pub(super) fn decode(
+        &mut self,
+        packet: Option<&Packet>,
+        pcm: &mut [f32],
+        frame_size: usize,
+    ) -> Result<usize, InvalidPacket> {
+        let ch = self.channels;
+        let Some(packet) = packet else {
+            if !frame_size.is_multiple_of(F2_5) {
+                return Err(InvalidPacket);
+            }
+            let mut count = 0;
+            while count < frame_size {
+                count += self.decode_frame(None, &mut pcm[count * ch..], frame_size - count)?;
+            }
+            return Ok(count);
+        };
+
+        let toc = packet.toc;
+        if packet.frames.len() * toc.frame_size > frame_size {
+            return Err(InvalidPacket);
+        }
+        self.mode = toc.mode;
+        self.bandwidth = toc.bandwidth;
+        self.frame_size = toc.frame_size;
+        self.stream_channels = if toc.stereo { 2 } else { 1 };
+        let mut samples = 0;
+        for frame in &packet.frames {
+            samples += self.decode_frame(Some(frame), &mut pcm[samples * ch..], frame_size - samples)?;
+        }
+        Ok(samples)
+    }
+
+    fn decode_frame(&mut self, data: Option<&[u8]>, pcm: &mut [f32], frame_size: usize) -> Result<usize, InvalidPacket> {
+        let ch = self.channels;
+        let mut frame_size = frame_size.min(MAX_FRAME_SIZE);
+        let data = data.filter(|data| data.len() > 1);
+        let (audiosize, mode, bandwidth) = match data {
+            Some(_) => (self.frame_size, self.mode, Some(self.bandwidth)),
+            None => {
+                frame_size = frame_size.min(self.frame_size);
+                let Some(mode) = self.prev_mode else {
+                    pcm[..frame_size * ch].fill(0.0);
+                    return Ok(frame_size);
+                };
+                // Conceal in 2.5, 5, 10 or 20 ms pieces only.
+                if frame_size > F20 {
+                    let mut done = 0;
+                    while done < frame_size {
+                        done += self.decode_frame(None, &mut pcm[done * ch..], (frame_size - done).min(F20))?;
+                    }
+                    return Ok(frame_size);
+                }
+                let audiosize = if frame_size < F20 && frame_size > F10 {
+                    F10
+                } else if frame_size < F10 && frame_size > F5 && mode != Mode::Silk {
+                    F5
+                } else {
+                    frame_size
+                };
+                (audiosize, mode, None)
+            }
+        };
+
+        let mut transition = data.is_some()
+            && self.prev_mode.is_some_and(|prev| {
+                (mode == Mode::Celt && prev != Mode::Celt && !self.prev_redundancy)
+                    || (mode != Mode::Celt && prev == Mode::Celt)
+            });
+        let mut pcm_transition = vec![0.0; F5 * ch];
+        if transition && mode == Mode::Celt {
+            self.decode_frame(None, &mut pcm_transition, F5.min(audiosize))?;
+        }
+        if audiosize > frame_size {
+            return Err(InvalidPacket);
+        }
+        let frame_size = audiosize;
+
+        let mut dec = data.map(RangeDecoder::new);
+        let mut len = data.map_or(0, <[u8]>::len);
+
+        let mut pcm_silk = Vec::new();
+        if mode != Mode::Celt {
+            pcm_silk = vec![0i16; F10.max(frame_size) * ch];
+            if self.prev_mode == Some(Mode::Celt) {
+                self.silk.reset();
+            }
+            self.silk_params.payload_ms = (audiosize / 48).max(10);
+            if let Some(bandwidth) = bandwidth {
+                self.silk_params.channels_internal = self.stream_channels;
+                self.silk_params.internal_khz = if mode == Mode::Silk { bandwidth.silk_khz() } else { 16 };
+            }
+            let mut decoded = 0;
+            while decoded < frame_size {
+                decoded +=
+                    self.silk.decode(dec.as_mut(), decoded == 0, &self.silk_params, &mut pcm_silk[decoded * ch..]);
+            }
+        }
+
+        let mut redundancy = false;
+        let mut celt_to_silk = false;
+        let mut redundancy_bytes = 0;
+        if let Some(dec) = dec.as_mut().filter(|_| mode != Mode::Celt) {
+            let hybrid = mode == Mode::Hybrid;
+            if dec.tell() + 17 + 20 * i32::from(hybrid) <= 8 * len as i32 {
+                redundancy = !hybrid || dec.bit_logp(12);
+                if redundancy {
+                    celt_to_silk = dec.bit_logp(1);
+                    redundancy_bytes =
+                        if hybrid { dec.uint(256) as usize + 2 } else { len - ((dec.tell() + 7) >> 3) as usize };
+                    let remaining = len as i32 - redundancy_bytes as i32;
+                    if remaining * 8 < dec.tell() {
+                        len = 0;
+                        redundancy_bytes = 0;
+                        redundancy = false;
+                    } else {
+                        len = remaining as usize;
+                    }
+                    dec.shrink(dec.storage() - redundancy_bytes);
+                }
+            }
+        }
+        let start_band = if mode != Mode::Celt { 17 } else { 0 };
+        if redundancy {
+            transition = false;
+        }
+        if transition && mode != Mode::Celt {
+            self.decode_frame(None, &mut pcm_transition, F5.min(audiosize))?;
+        }
+
+        if let Some(bandwidth) = bandwidth {
+            self.celt.set_end_band(bandwidth.celt_end_band());
+        }
+        self.celt.set_stream_channels(self.stream_channels);
+
+        let redundant = data.map_or(&[][..], |data| &data[len..len + redundancy_bytes]);
+        let mut redundant_audio = vec![0.0; F5 * ch];
+        if redundancy && celt_to_silk {
+            self.celt.set_start_band(0);
+            let mut rd = RangeDecoder::new(redundant);
+            self.celt.decode(Some((&mut rd, redundancy_bytes)), &mut redundant_audio, F5)?;
+        }
+
+        self.celt.set_start_band(start_band);
+        if mode != Mode::Silk {
+            if Some(mode) != self.prev_mode && self.prev_mode.is_some() && !self.prev_redundancy {
+                self.celt.reset();
+            }
+            self.celt.decode(dec.as_mut().map(|dec| (dec, len)), pcm, F20.min(frame_size))?;
+        } else {
+            pcm[..frame_size * ch].fill(0.0);
+            // Let the MDCT fade hybrid's upper bands out.
+            if self.prev_mode == Some(Mode::Hybrid) && !(redundancy && celt_to_silk && self.prev_redundancy) {
+                self.celt.set_start_band(0);
+                let silence = [0xff, 0xff];
+                self.celt.decode(Some((&mut RangeDecoder::new(&silence), 2)), pcm, F2_5)?;
+            }
+        }
+        if mode != Mode::Celt {
+            for (out, &silk) in pcm[..frame_size * ch].iter_mut().zip(&pcm_silk) {
+                *out += (1.0 / 32768.0) * f32::from(silk);
+            }
+        }
+
+        if redundancy && !celt_to_silk {
+            self.celt.reset();
+            self.celt.set_start_band(0);
+            let mut rd = RangeDecoder::new(redundant);
+            self.celt.decode(Some((&mut rd, redundancy_bytes)), &mut redundant_audio, F5)?;
+            let tail = ch * (frame_size - F2_5);
+            let faded = pcm[tail..tail + ch * F2_5].to_vec();
+            smooth_fade(&faded, &redundant_audio[ch * F2_5..], &mut pcm[tail..], ch, self.celt.window());
+        }
+        if redundancy && celt_to_silk {
+            pcm[..ch * F2_5].copy_from_slice(&redundant_audio[..ch * F2_5]);
+            let faded = pcm[ch * F2_5..ch * F5].to_vec();
+            smooth_fade(&redundant_audio[ch * F2_5..], &faded, &mut pcm[ch * F2_5..], ch, self.celt.window());
+        }
+        if transition {
+            if audiosize >= F5 {
+                pcm[..ch * F2_5].copy_from_slice(&pcm_transition[..ch * F2_5]);
+                let faded = pcm[ch * F2_5..ch * F5].to_vec();
+                smooth_fade(&pcm_transition[ch * F2_5..], &faded, &mut pcm[ch * F2_5..], ch, self.celt.window());
+            } else {
+                let faded = pcm[..ch * F2_5].to_vec();
+                smooth_fade(&pcm_transition, &faded, pcm, ch, self.celt.window());
+            }
+        }
+
+        self.prev_mode = Some(mode);
+        self.prev_redundancy = redundancy && !celt_to_silk;
+        Ok(audiosize)
+    }
+}
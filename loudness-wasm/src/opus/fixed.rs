@@ -0,0 +1,167 @@
+// SILK fixed-point arithmetic
+//
+// SILK is specified bit-exactly in integer arithmetic, so these mirror the
+// reference macros (their 64-bit forms): `smulwb` is `(a * (i16)b) >> 16`,
+// `smmul` the high word of a 32×32 product, and so on. Overflow wraps where
+// the reference lets it.
+
+pub(super) fn smulbb(a: i32, b: i32) -> i32 {
+    i32::from(a as i16) * i32::from(b as i16)
+}
+
+pub(super) fn smlabb(a: i32, b: i32, c: i32) -> i32 {
+    a.wrapping_add(smulbb(b, c))
+}
+
+pub(super) fn smulwb(a: i32, b: i32) -> i32 {
+    ((i64::from(a) * i64::from(b as i16)) >> 16) as i32
+}
+
+pub(super) fn smlawb(a: i32, b: i32, c: i32) -> i32 {
+    (i64::from(a) + ((i64::from(b) * i64::from(c as i16)) >> 16)) as i32
+}
+
+pub(super) fn smulww(a: i32, b: i32) -> i32 {
+    ((i64::from(a) * i64::from(b)) >> 16) as i32
+}
+
+pub(super) fn smlaww(a: i32, b: i32, c: i32) -> i32 {
+    (i64::from(a) + ((i64::from(b) * i64::from(c)) >> 16)) as i32
+}
+
+pub(super) fn smultt(a: i32, b: i32) -> i32 {
+    (a >> 16).wrapping_mul(b >> 16)
+}
+
+pub(super) fn smmul(a: i32, b: i32) -> i32 {
+    ((i64::from(a) * i64::from(b)) >> 32) as i32
+}
+
+pub(super) fn rshift_round(a: i32, shift: u32) -> i32 {
+    if shift == 1 {
+        (a >> 1) + (a & 1)
+    } else {
+        ((a >> (shift - 1)) + 1) >> 1
+    }
+}
+
+pub(super) fn rshift_round64(a: i64, shift: u32) -> i64 {
+    if shift == 1 {
+        (a >> 1) + (a & 1)
+    } else {
+        ((a >> (shift - 1)) + 1) >> 1
+    }
+}
+
+pub(super) fn sat16(a: i32) -> i16 {
+    a.clamp(i32::from(i16::MIN), i32::from(i16::MAX)) as i16
+}
+
+pub(super) fn add_sat32(a: i32, b: i32) -> i32 {
+    a.saturating_add(b)
+}
+
+pub(super) fn sub_sat32(a: i32, b: i32) -> i32 {
+    a.saturating_sub(b)
+}
+
+pub(super) fn lshift_sat32(a: i32, shift: u32) -> i32 {
+    a.clamp(i32::MIN >> shift, i32::MAX >> shift) << shift
+}
+
+/// The reference's linear congruential generator.
+pub(super) fn rand(seed: i32) -> i32 {
+    907_633_515i32.wrapping_add(seed.wrapping_mul(196_314_165))
+}
+
+/// Leading zeros and the seven bits after the leading one.
+pub(super) fn clz_frac(x: i32) -> (i32, i32) {
+    let lz = x.leading_zeros() as i32;
+    let frac = (x as u32).rotate_right((24 - lz).rem_euclid(32) as u32) as i32 & 0x7f;
+    (lz, frac)
+}
+
+/// Square root to within a few percent.
+pub(super) fn sqrt_approx(x: i32) -> i32 {
+    if x <= 0 {
+        return 0;
+    }
+    let (lz, frac) = clz_frac(x);
+    let mut y = if lz & 1 != 0 { 32768 } else { 46214 };
+    y >>= lz >> 1;
+    smlawb(y, y, smulbb(213, frac))
+}
+
+/// `(a << q) / b`, approximately.
+pub(super) fn div32_varq(a: i32, b: i32, q: i32) -> i32 {
+    let a_headrm = a.unsigned_abs().leading_zeros() as i32 - 1;
+    let mut a_nrm = a << a_headrm;
+    let b_headrm = b.unsigned_abs().leading_zeros() as i32 - 1;
+    let b_nrm = b << b_headrm;
+    let b_inv = (i32::MAX >> 2) / (b_nrm >> 16);
+    let mut result = smulwb(a_nrm, b_inv);
+    a_nrm = a_nrm.wrapping_sub(smmul(b_nrm, result).wrapping_shl(3));
+    result = smlawb(result, a_nrm, b_inv);
+    let lshift = 29 + a_headrm - b_headrm - q;
+    if lshift < 0 {
+        lshift_sat32(result, (-lshift) as u32)
+    } else if lshift < 32 {
+        result >> lshift
+    } else {
+        0
+    }
+}
+
+/// `(1 << q) / b`, approximately.
+pub(super) fn inverse32_varq(b: i32, q: i32) -> i32 {
+    let b_headrm = b.unsigned_abs().leading_zeros() as i32 - 1;
+    let b_nrm = b << b_headrm;
+    let b_inv = (i32::MAX >> 2) / (b_nrm >> 16);
+    let mut result = b_inv << 16;
+    let err_q32 = ((1 << 29) - smulwb(b_nrm, b_inv)).wrapping_shl(3);
+    result = smlaww(result, err_q32, b_inv);
+    let lshift = 61 - b_headrm - q;
+    if lshift <= 0 {
+        lshift_sat32(result, (-lshift) as u32)
+    } else if lshift < 32 {
+        result >> lshift
+    } else {
+        0
+    }
+}
+
+/// `2^(x / 128)`, piecewise parabolic.
+pub(super) fn log2lin(x: i32) -> i32 {
+    if x < 0 {
+        return 0;
+    } else if x >= 3967 {
+        return i32::MAX;
+    }
+    let out = 1 << (x >> 7);
+    let frac = x & 0x7f;
+    let poly = smlawb(frac, smulbb(frac, 128 - frac), -174);
+    if x < 2048 {
+        out + ((out * poly) >> 7)
+    } else {
+        out + (out >> 7) * poly
+    }
+}
+
+/// Energy of `x` and the right shift applied to keep it in 31 bits.
+pub(super) fn sum_sqr_shift(x: &[i16]) -> (i32, i32) {
+    let len = x.len() as i32;
+    let pass = |shift: i32, mut nrg: u32| {
+        for pair in x.chunks(2) {
+            let mut tmp = (i32::from(pair[0]) * i32::from(pair[0])) as u32;
+            if let Some(&b) = pair.get(1) {
+                tmp = tmp.wrapping_add((i32::from(b) * i32::from(b)) as u32);
+            }
+            nrg = nrg.wrapping_add(tmp >> shift);
+        }
+        nrg as i32
+    };
+    let shift = 31 - len.leading_zeros() as i32;
+    let nrg = pass(shift, len as u32);
+    let shift = 0.max(shift + 3 - nrg.leading_zeros() as i32);
+    (pass(shift, 0), shift)
+}
@@ -0,0 +1,348 @@
+// SILK NLSF dequantization and LPC conversion (RFC 6716 §4.2.7.5)
+//
+// Line spectral frequencies arrive as a first-stage codebook vector plus a
+// predictively coded residual; once stabilized they convert to Q12 LPC
+// coefficients, bandwidth-expanded until the filter is provably stable.
+
+use super::fixed::{
+    inverse32_varq, rshift_round, rshift_round64, sat16, smlabb, smlawb, smmul, smulbb, smulww, sub_sat32,
+};
+use super::silk_tables::*;
+
+pub(super) const MAX_LPC_ORDER: usize = 16;
+
+/// One of the two NLSF codebooks (narrow/medium band, or wideband).
+pub(super) struct NlsfCodebook {
+    pub(super) order: usize,
+    quant_step_size_q16: i32,
+    pub(super) cb1_nlsf_q8: &'static [u8],
+    cb1_wght_q9: &'static [i16],
+    pub(super) cb1_icdf: &'static [u8],
+    pred_q8: &'static [u8],
+    ec_sel: &'static [u8],
+    pub(super) cb2_icdf: &'static [u8],
+    delta_min_q15: &'static [i16],
+}
+
+pub(super) static NLSF_CB_NB_MB: NlsfCodebook = NlsfCodebook {
+    order: 10,
+    quant_step_size_q16: 11796,
+    cb1_nlsf_q8: &NLSF_CB1_NB_MB_Q8,
+    cb1_wght_q9: &NLSF_CB1_WGHT_NB_MB_Q9,
+    cb1_icdf: &NLSF_CB1_ICDF_NB_MB,
+    pred_q8: &NLSF_PRED_NB_MB_Q8,
+    ec_sel: &NLSF_CB2_SELECT_NB_MB,
+    cb2_icdf: &NLSF_CB2_ICDF_NB_MB,
+    delta_min_q15: &NLSF_DELTA_MIN_NB_MB_Q15,
+};
+
+pub(super) static NLSF_CB_WB: NlsfCodebook = NlsfCodebook {
+    order: 16,
+    quant_step_size_q16: 9830,
+    cb1_nlsf_q8: &NLSF_CB1_WB_Q8,
+    cb1_wght_q9: &NLSF_CB1_WGHT_WB_Q9,
+    cb1_icdf: &NLSF_CB1_ICDF_WB,
+    pred_q8: &NLSF_PRED_WB_Q8,
+    ec_sel: &NLSF_CB2_SELECT_WB,
+    cb2_icdf: &NLSF_CB2_ICDF_WB,
+    delta_min_q15: &NLSF_DELTA_MIN_WB_Q15,
+};
+
+impl NlsfCodebook {
+    /// Entropy-table offsets and predictor coefficients for a first-stage
+    /// vector.
+    pub(super) fn unpack(&self, cb1_index: usize) -> ([usize; MAX_LPC_ORDER], [u8; MAX_LPC_ORDER]) {
+        let order = self.order;
+        let mut ec_ix = [0; MAX_LPC_ORDER];
+        let mut pred_q8 = [0; MAX_LPC_ORDER];
+        let sel = &self.ec_sel[cb1_index * order / 2..];
+        for i in (0..order).step_by(2) {
+            let entry = usize::from(sel[i / 2]);
+            ec_ix[i] = ((entry >> 1) & 7) * 9;
+            pred_q8[i] = self.pred_q8[i + (entry & 1) * (order - 1)];
+            ec_ix[i + 1] = ((entry >> 5) & 7) * 9;
+            pred_q8[i + 1] = self.pred_q8[i + ((entry >> 4) & 1) * (order - 1) + 1];
+        }
+        (ec_ix, pred_q8)
+    }
+
+    /// Rebuilds the NLSF vector from its codebook path (first-stage index,
+    /// then one residual index per coefficient).
+    pub(super) fn decode(&self, indices: &[i8], nlsf_q15: &mut [i16]) {
+        let order = self.order;
+        let cb1 = indices[0] as usize;
+        let (_, pred_q8) = self.unpack(cb1);
+
+        let mut res_q10 = [0i16; MAX_LPC_ORDER];
+        let mut out_q10 = 0;
+        for i in (0..order).rev() {
+            let pred = smulbb(out_q10, i32::from(pred_q8[i])) >> 8;
+            out_q10 = i32::from(indices[i + 1]) << 10;
+            if out_q10 > 0 {
+                out_q10 -= 102;
+            } else if out_q10 < 0 {
+                out_q10 += 102;
+            }
+            out_q10 = smlawb(pred, out_q10, self.quant_step_size_q16);
+            res_q10[i] = out_q10 as i16;
+        }
+
+        let cb = &self.cb1_nlsf_q8[cb1 * order..];
+        let wght = &self.cb1_wght_q9[cb1 * order..];
+        for i in 0..order {
+            let nlsf = (i32::from(res_q10[i]) << 14) / i32::from(wght[i]) + (i32::from(cb[i]) << 7);
+            nlsf_q15[i] = nlsf.clamp(0, 32767) as i16;
+        }
+        stabilize(&mut nlsf_q15[..order], self.delta_min_q15);
+    }
+}
+
+/// Pushes NLSFs apart (and away from 0 and π) until every gap is at least
+/// the codebook's minimum.
+pub(super) fn stabilize(nlsf: &mut [i16], delta_min: &[i16]) {
+    const MAX_LOOPS: usize = 20;
+    let l = nlsf.len();
+    for _ in 0..MAX_LOOPS {
+        let mut min_diff = i32::from(nlsf[0]) - i32::from(delta_min[0]);
+        let mut at = 0;
+        for i in 1..l {
+            let diff = i32::from(nlsf[i]) - (i32::from(nlsf[i - 1]) + i32::from(delta_min[i]));
+            if diff < min_diff {
+                min_diff = diff;
+                at = i;
+            }
+        }
+        let diff = (1 << 15) - (i32::from(nlsf[l - 1]) + i32::from(delta_min[l]));
+        if diff < min_diff {
+            min_diff = diff;
+            at = l;
+        }
+        if min_diff >= 0 {
+            return;
+        }
+        if at == 0 {
+            nlsf[0] = delta_min[0];
+        } else if at == l {
+            nlsf[l - 1] = ((1 << 15) - i32::from(delta_min[l])) as i16;
+        } else {
+            let half = i32::from(delta_min[at]) >> 1;
+            let min_center = delta_min[..at].iter().map(|&d| i32::from(d)).sum::<i32>() + half;
+            let max_center = (1 << 15) - delta_min[at + 1..=l].iter().map(|&d| i32::from(d)).sum::<i32>() - half;
+            let center = limit(
+                rshift_round(i32::from(nlsf[at - 1]) + i32::from(nlsf[at]), 1),
+                min_center,
+                max_center,
+            ) as i16;
+            nlsf[at - 1] = (i32::from(center) - half) as i16;
+            nlsf[at] = (i32::from(nlsf[at - 1]) + i32::from(delta_min[at])) as i16;
+        }
+    }
+
+    nlsf.sort_unstable();
+    nlsf[0] = nlsf[0].max(delta_min[0]);
+    for i in 1..l {
+        nlsf[i] = nlsf[i].max(nlsf[i - 1].saturating_add(delta_min[i]));
+    }
+    nlsf[l - 1] = i32::from(nlsf[l - 1]).min((1 << 15) - i32::from(delta_min[l])) as i16;
+    for i in (0..l - 1).rev() {
+        nlsf[i] = i32::from(nlsf[i]).min(i32::from(nlsf[i + 1]) - i32::from(delta_min[i + 1])) as i16;
+    }
+}
+
+/// The reference's `silk_LIMIT`, which tolerates swapped bounds.
+fn limit(a: i32, lo: i32, hi: i32) -> i32 {
+    if lo > hi {
+        if a > lo {
+            lo
+        } else if a < hi {
+            hi
+        } else {
+            a
+        }
+    } else {
+        a.clamp(lo, hi)
+    }
+}
+
+/// Chirps a Q12 filter by `chirp_q16` per tap.
+pub(super) fn bwexpander(ar: &mut [i16], mut chirp_q16: i32) {
+    let chirp_minus_one = chirp_q16 - 65536;
+    let d = ar.len();
+    for a in &mut ar[..d - 1] {
+        *a = rshift_round(chirp_q16.wrapping_mul(i32::from(*a)), 16) as i16;
+        chirp_q16 += rshift_round(chirp_q16.wrapping_mul(chirp_minus_one), 16);
+    }
+    ar[d - 1] = rshift_round(chirp_q16.wrapping_mul(i32::from(ar[d - 1])), 16) as i16;
+}
+
+fn bwexpander_32(ar: &mut [i32], mut chirp_q16: i32) {
+    let chirp_minus_one = chirp_q16 - 65536;
+    let d = ar.len();
+    for a in &mut ar[..d - 1] {
+        *a = smulww(chirp_q16, *a);
+        chirp_q16 += rshift_round(chirp_q16.wrapping_mul(chirp_minus_one), 16);
+    }
+    ar[d - 1] = smulww(chirp_q16, ar[d - 1]);
+}
+
+/// Narrows `a_qin` to 16 bits in `qout`, chirping it first if it wouldn't fit.
+fn lpc_fit(a_qout: &mut [i16], a_qin: &mut [i32], qout: u32, qin: u32) {
+    let shift = qin - qout;
+    let mut fitted = false;
+    for _ in 0..10 {
+        let mut maxabs = 0;
+        let mut idx = 0;
+        for (k, &a) in a_qin.iter().enumerate() {
+            let abs = a.wrapping_abs();
+            if abs > maxabs {
+                maxabs = abs;
+                idx = k as i32;
+            }
+        }
+        let maxabs = rshift_round(maxabs, shift);
+        if maxabs > i32::from(i16::MAX) {
+            let maxabs = maxabs.min(163_838);
+            let chirp_q16 = 65470 - ((maxabs - i32::from(i16::MAX)) << 14) / ((maxabs * (idx + 1)) >> 2);
+            bwexpander_32(a_qin, chirp_q16);
+        } else {
+            fitted = true;
+            break;
+        }
+    }
+    for (out, a) in a_qout.iter_mut().zip(a_qin.iter_mut()) {
+        if fitted {
+            *out = rshift_round(*a, shift) as i16;
+        } else {
+            *out = sat16(rshift_round(*a, shift));
+            *a = i32::from(*out) << shift;
+        }
+    }
+}
+
+/// Inverse prediction gain of a Q12 filter in Q30, or 0 if it's unstable.
+pub(super) fn inverse_pred_gain(a_q12: &[i16]) -> i32 {
+    const QA: u32 = 24;
+    const A_LIMIT: i32 = 16_773_022;
+    const MIN_INV_GAIN_Q30: i32 = 107_374;
+
+    let order = a_q12.len();
+    let dc_resp: i32 = a_q12.iter().map(|&a| i32::from(a)).sum();
+    if dc_resp >= 4096 {
+        return 0;
+    }
+    let mut a_qa = [0i32; MAX_LPC_ORDER];
+    for (dst, &a) in a_qa.iter_mut().zip(a_q12) {
+        *dst = i32::from(a) << (QA - 12);
+    }
+
+    let mut inv_gain_q30 = 1 << 30;
+    for k in (1..order).rev() {
+        if a_qa[k] > A_LIMIT || a_qa[k] < -A_LIMIT {
+            return 0;
+        }
+        let rc_q31 = -(a_qa[k] << (31 - QA));
+        let rc_mult1_q30 = (1 << 30) - smmul(rc_q31, rc_q31);
+        inv_gain_q30 = smmul(inv_gain_q30, rc_mult1_q30) << 2;
+        if inv_gain_q30 < MIN_INV_GAIN_Q30 {
+            return 0;
+        }
+        let mult2q = 32 - rc_mult1_q30.unsigned_abs().leading_zeros() as i32;
+        let rc_mult2 = inverse32_varq(rc_mult1_q30, mult2q + 30);
+        let step = |x: i32, y: i32| {
+            let frac = rshift_round64(i64::from(y) * i64::from(rc_q31), 31) as i32;
+            let v = rshift_round64(i64::from(sub_sat32(x, frac)) * i64::from(rc_mult2), mult2q as u32);
+            i32::try_from(v).ok()
+        };
+        for n in 0..(k + 1) >> 1 {
+            let (tmp1, tmp2) = (a_qa[n], a_qa[k - n - 1]);
+            let Some(a) = step(tmp1, tmp2) else { return 0 };
+            a_qa[n] = a;
+            let Some(b) = step(tmp2, tmp1) else { return 0 };
+            a_qa[k - n - 1] = b;
+        }
+    }
+    if a_qa[0] > A_LIMIT || a_qa[0] < -A_LIMIT {
+        return 0;
+    }
+    let rc_q31 = -(a_qa[0] << (31 - QA));
+    let rc_mult1_q30 = (1 << 30) - smmul(rc_q31, rc_q31);
+    inv_gain_q30 = smmul(inv_gain_q30, rc_mult1_q30) << 2;
+    if inv_gain_q30 < MIN_INV_GAIN_Q30 {
+        return 0;
+    }
+    inv_gain_q30
+}
+
+fn find_poly(out: &mut [i32], c_lsf: &[i32], dd: usize) {
+    const QA: u32 = 16;
+    out[0] = 1 << QA;
+    out[1] = -c_lsf[0];
+    for k in 1..dd {
+        let ftmp = i64::from(c_lsf[2 * k]);
+        out[k + 1] = (out[k - 1] << 1).wrapping_sub(rshift_round64(ftmp * i64::from(out[k]), QA) as i32);
+        for n in (2..=k).rev() {
+            out[n] = out[n]
+                .wrapping_add(out[n - 2])
+                .wrapping_sub(rshift_round64(ftmp * i64::from(out[n - 1]), QA) as i32);
+        }
+        out[1] = out[1].wrapping_sub(ftmp as i32);
+    }
+}
+
+/// Converts NLSFs (Q15) to a stable Q12 LPC filter of the same order.
+pub(super) fn nlsf2a(a_q12: &mut [i16], nlsf: &[i16]) {
+    const QA: u32 = 16;
+    const ORDERING16: [usize; 16] = [0, 15, 8, 7, 4, 11, 12, 3, 2, 13, 10, 5, 6, 9, 14, 1];
+    const ORDERING10: [usize; 10] = [0, 9, 6, 3, 4, 5, 8, 1, 2, 7];
+
+    let d = nlsf.len();
+    let ordering: &[usize] = if d == 16 { &ORDERING16 } else { &ORDERING10 };
+    let mut cos_lsf_qa = [0i32; MAX_LPC_ORDER];
+    for k in 0..d {
+        let f_int = usize::from(nlsf[k] as u16 >> 8);
+        let f_frac = i32::from(nlsf[k]) - ((f_int as i32) << 8);
+        let cos_val = i32::from(LSF_COS_TAB_Q12[f_int]);
+        let delta = i32::from(LSF_COS_TAB_Q12[f_int + 1]) - cos_val;
+        cos_lsf_qa[ordering[k]] = rshift_round((cos_val << 8) + delta * f_frac, 20 - QA);
+    }
+
+    let dd = d / 2;
+    let mut p = [0i32; MAX_LPC_ORDER / 2 + 1];
+    let mut q = [0i32; MAX_LPC_ORDER / 2 + 1];
+    find_poly(&mut p, &cos_lsf_qa, dd);
+    find_poly(&mut q, &cos_lsf_qa[1..], dd);
+
+    let mut a32_qa1 = [0i32; MAX_LPC_ORDER];
+    for k in 0..dd {
+        let ptmp = p[k + 1].wrapping_add(p[k]);
+        let qtmp = q[k + 1].wrapping_sub(q[k]);
+        a32_qa1[k] = qtmp.wrapping_neg().wrapping_sub(ptmp);
+        a32_qa1[d - k - 1] = qtmp.wrapping_sub(ptmp);
+    }
+    let a32 = &mut a32_qa1[..d];
+    lpc_fit(a_q12, a32, 12, QA + 1);
+
+    let mut i = 0;
+    while inverse_pred_gain(a_q12) == 0 && i < 16 {
+        bwexpander_32(a32, 65536 - (2 << i));
+        for (out, &a) in a_q12.iter_mut().zip(a32.iter()) {
+            *out = rshift_round(a, QA + 1 - 12) as i16;
+        }
+        i += 1;
+    }
+}
+
+/// Whitens `input` with the Q12 filter `b` (the first `b.len()` outputs are
+/// zero).
+pub(super) fn analysis_filter(out: &mut [i16], input: &[i16], b: &[i16]) {
+    let d = b.len();
+    out[..d].fill(0);
+    for ix in d..input.len() {
+        let mut acc = smulbb(i32::from(input[ix - 1]), i32::from(b[0]));
+        for j in 1..d {
+            acc = smlabb(acc, i32::from(input[ix - 1 - j]), i32::from(b[j]));
+        }
+        let acc = (i32::from(input[ix]) << 12).wrapping_sub(acc);
+        out[ix] = sat16(rshift_round(acc, 12));
+    }
+}
@@ -0,0 +1,183 @@
+// CELT inverse MDCT
+//
+// The 1920-point MDCT and its 960/480/240-point short-block versions, each
+// built on a mixed-radix FFT of a quarter of its size (480, 240, 120, 60
+// points; none a power of two, so `utils::Fft` can't serve). The output is
+// written in place into the decoder's history the way the reference does:
+// the windowed TDAC fold pairs this block's first half-overlap with the raw
+// tail the previous block left behind.
+
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Default)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+
+    fn add(self, other: Complex) -> Complex {
+        Complex { re: self.re + other.re, im: self.im + other.im }
+    }
+}
+
+/// Unscaled forward DFT for sizes built from the radices 2, 3, 4 and 5.
+struct Fft {
+    n: usize,
+    factors: Vec<usize>,
+    twiddles: Vec<Complex>,
+}
+
+impl Fft {
+    fn new(n: usize) -> Self {
+        let mut factors = Vec::new();
+        let mut rest = n;
+        for radix in [4, 2, 3, 5] {
+            while rest.is_multiple_of(radix) {
+                factors.push(radix);
+                rest /= radix;
+            }
+        }
+        debug_assert_eq!(rest, 1, "FFT size must factor into 2, 3 and 5");
+        let twiddles = (0..n)
+            .map(|i| {
+                let phase = -2.0 * PI * i as f64 / n as f64;
+                Complex { re: phase.cos() as f32, im: phase.sin() as f32 }
+            })
+            .collect();
+        Fft { n, factors, twiddles }
+    }
+
+    fn process(&self, input: &[Complex], output: &mut [Complex]) {
+        self.work(output, input, 0, 1, &self.factors);
+    }
+
+    fn work(&self, out: &mut [Complex], input: &[Complex], offset: usize, fstride: usize, factors: &[usize]) {
+        let p = factors[0];
+        let m = out.len() / p;
+        if m == 1 {
+            for (q, o) in out.iter_mut().enumerate() {
+                *o = input[offset + q * fstride];
+            }
+        } else {
+            for q in 0..p {
+                self.work(&mut out[q * m..(q + 1) * m], input, offset + q * fstride, fstride * p, &factors[1..]);
+            }
+        }
+        let mut scratch = [Complex::default(); 5];
+        for u in 0..m {
+            for (q, s) in scratch.iter_mut().enumerate().take(p) {
+                *s = out[q * m + u];
+            }
+            for q1 in 0..p {
+                let k = q1 * m + u;
+                let mut acc = scratch[0];
+                for (q, &s) in scratch.iter().enumerate().take(p).skip(1) {
+                    acc = acc.add(s.mul(self.twiddles[(fstride * q * k) % self.n]));
+                }
+                out[k] = acc;
+            }
+        }
+    }
+}
+
+pub(super) struct Mdct {
+    /// One FFT per shift (0 = the 1920-point long block).
+    ffts: Vec<Fft>,
+    /// `cos(2π(i + 1/8) / N)` for each shift's N, concatenated.
+    trig: Vec<f32>,
+    pub(super) window: Vec<f32>,
+}
+
+const MDCT_SIZE: usize = 1920;
+pub(super) const OVERLAP: usize = 120;
+
+impl Mdct {
+    pub(super) fn new() -> Self {
+        let mut ffts = Vec::new();
+        let mut trig = Vec::new();
+        for shift in 0..4 {
+            let n = MDCT_SIZE >> shift;
+            ffts.push(Fft::new(n >> 2));
+            trig.extend((0..n / 2).map(|i| (2.0 * PI * (i as f64 + 0.125) / n as f64).cos() as f32));
+        }
+        let window = (0..OVERLAP)
+            .map(|i| {
+                let s = (0.5 * PI * (i as f64 + 0.5) / OVERLAP as f64).sin();
+                (0.5 * PI * s * s).sin() as f32
+            })
+            .collect();
+        Mdct { ffts, trig, window }
+    }
+
+    /// Inverse MDCT of `input[0], input[stride], …` (N/2 coefficients) into
+    /// `out[OVERLAP/2 .. OVERLAP/2 + N/2]`, then the TDAC fold over
+    /// `out[..OVERLAP]`.
+    pub(super) fn backward(&self, input: &[f32], out: &mut [f32], shift: usize, stride: usize) {
+        let mut n = MDCT_SIZE;
+        let mut trig = &self.trig[..];
+        for _ in 0..shift {
+            trig = &trig[n / 2..];
+            n >>= 1;
+        }
+        let n2 = n >> 1;
+        let n4 = n >> 2;
+
+        // Pre-rotate (real and imaginary swapped so a forward FFT does the job).
+        let mut rotated = vec![Complex::default(); n4];
+        for (i, r) in rotated.iter_mut().enumerate() {
+            let x1 = input[2 * i * stride];
+            let x2 = input[stride * (n2 - 1 - 2 * i)];
+            let yr = x2 * trig[i] + x1 * trig[n4 + i];
+            let yi = x1 * trig[i] - x2 * trig[n4 + i];
+            *r = Complex { re: yi, im: yr };
+        }
+        let mut spectrum = vec![Complex::default(); n4];
+        self.ffts[shift].process(&rotated, &mut spectrum);
+
+        let base = OVERLAP >> 1;
+        for (k, c) in spectrum.iter().enumerate() {
+            out[base + 2 * k] = c.re;
+            out[base + 2 * k + 1] = c.im;
+        }
+
+        // Post-rotate and de-shuffle from both ends at once.
+        let mut p0 = base;
+        let mut p1 = base + n2 - 2;
+        for i in 0..(n4 + 1) >> 1 {
+            let re = out[p0 + 1];
+            let im = out[p0];
+            let (t0, t1) = (trig[i], trig[n4 + i]);
+            let yr = re * t0 + im * t1;
+            let yi = re * t1 - im * t0;
+            let re = out[p1 + 1];
+            let im = out[p1];
+            out[p0] = yr;
+            out[p1 + 1] = yi;
+            let (t0, t1) = (trig[n4 - i - 1], trig[n2 - i - 1]);
+            let yr = re * t0 + im * t1;
+            let yi = re * t1 - im * t0;
+            out[p1] = yr;
+            out[p0 + 1] = yi;
+            p0 += 2;
+            p1 = p1.wrapping_sub(2);
+        }
+
+        // Mirror on both sides for TDAC.
+        for i in 0..OVERLAP / 2 {
+            let x1 = out[OVERLAP - 1 - i];
+            let x2 = out[i];
+            let w1 = self.window[i];
+            let w2 = self.window[OVERLAP - 1 - i];
+            out[i] = w2 * x2 - w1 * x1;
+            out[OVERLAP - 1 - i] = w1 * x2 + w2 * x1;
+        }
+    }
+}
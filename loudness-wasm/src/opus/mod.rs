@@ -0,0 +1,250 @@
+// Opus decoding (RFC 6716)
+//
+// A symphonia codec for the Opus tracks the Ogg and Matroska demuxers
+// already find. The identification header (RFC 7845 §5.1) both carry as
+// extra data gives the channel mapping, the encoder's pre-skip and an output
+// gain; each packet holds one frame group per elementary stream, mono or
+// coupled stereo, decoded at 48 kHz and mapped onto the output channels.
+
+use symphonia::core::audio::{AudioBuffer, AudioBufferRef, AsAudioBufferRef, Channels, Signal, SignalSpec};
+use symphonia::core::codecs::{
+    CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult, CODEC_TYPE_OPUS,
+};
+use symphonia::core::errors::{decode_error, unsupported_error, Result};
+use symphonia::core::formats::Packet as EncodedPacket;
+use symphonia::core::support_codec;
+
+mod bands;
+mod celt;
+mod decoder;
+mod fixed;
+mod lpc;
+mod mdct;
+mod packet;
+mod pitch;
+mod range;
+mod resampler;
+mod silk;
+mod silk_plc;
+mod silk_tables;
+mod tables;
+
+use decoder::{StreamDecoder, MAX_FRAME_SIZE};
+use packet::Packet;
+
+/// A packet the decoder can't make sense of.
+#[derive(Debug)]
+pub(crate) struct InvalidPacket;
+
+/// Mapping value for an output channel no stream feeds.
+const SILENT: u8 = 255;
+
+/// The `OpusHead` identification header.
+#[derive(Clone, Debug, PartialEq)]
+struct OpusHead {
+    channels: usize,
+    /// Samples at 48 kHz the encoder asks to be dropped from the start.
+    pre_skip: usize,
+    /// Linear output gain.
+    gain: f32,
+    streams: usize,
+    coupled: usize,
+    /// Per output channel: coupled stream channels first, then mono streams.
+    mapping: Vec<u8>,
+}
+
+impl OpusHead {
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 19 || &data[..8] != b"OpusHead" || data[8] >> 4 != 0 {
+            return None;
+        }
+        let channels = usize::from(data[9]);
+        let pre_skip = usize::from(u16::from_le_bytes([data[10], data[11]]));
+        let gain_q8 = i16::from_le_bytes([data[16], data[17]]);
+        let gain = 10f32.powf(f32::from(gain_q8) / (20.0 * 256.0));
+        let (streams, coupled, mapping) = match data[18] {
+            0 if (1..=2).contains(&channels) => (1, channels - 1, (0..channels as u8).collect()),
+            0 => return None,
+            _ => {
+                let table = data.get(19..21 + channels)?;
+                (usize::from(table[0]), usize::from(table[1]), table[2..].to_vec())
+            }
+        };
+        let valid = channels > 0
+            && streams > 0
+            && coupled <= streams
+            && streams + coupled <= usize::from(SILENT)
+            && mapping.iter().all(|&m| m == SILENT || usize::from(m) < streams + coupled);
+        valid.then_some(OpusHead { channels, pre_skip, gain, streams, coupled, mapping })
+    }
+
+    /// Family 0 for tracks without a header (Matroska may omit CodecPrivate).
+    fn default_for(channels: usize) -> Option<Self> {
+        (1..=2).contains(&channels).then(|| OpusHead {
+            channels,
+            pre_skip: 0,
+            gain: 1.0,
+            streams: 1,
+            coupled: channels - 1,
+            mapping: (0..channels as u8).collect(),
+        })
+    }
+
+    /// The stream, and the channel within it, an output channel takes.
+    fn source(&self, channel: usize) -> Option<(usize, usize)> {
+        let m = usize::from(self.mapping[channel]);
+        if self.mapping[channel] == SILENT {
+            None
+        } else if m < 2 * self.coupled {
+            Some((m / 2, m % 2))
+        } else {
+            Some((m - self.coupled, 0))
+        }
+    }
+}
+
+/// Opus decoder for symphonia, producing 48 kHz output with the pre-skip
+/// already dropped (leave the demuxer's gapless trimming off).
+pub(crate) struct OpusDecoder {
+    params: CodecParameters,
+    head: OpusHead,
+    streams: Vec<StreamDecoder>,
+    /// Each stream's interleaved output for the current packet.
+    decoded: Vec<Vec<f32>>,
+    buffer: AudioBuffer<f32>,
+    /// Pre-skip samples still to drop.
+    skip: usize,
+    /// Duration of the last packet, concealed again for an empty one.
+    last_frame_size: usize,
+}
+
+impl OpusDecoder {
+    fn decode_streams(&mut self, data: &[u8]) -> std::result::Result<usize, InvalidPacket> {
+        if data.is_empty() {
+            let frame_size = self.last_frame_size;
+            for (stream, pcm) in self.streams.iter_mut().zip(&mut self.decoded) {
+                stream.decode(None, pcm, frame_size)?;
+            }
+            return Ok(frame_size);
+        }
+        let mut offset = 0;
+        let mut frame_size = None;
+        let last = self.streams.len() - 1;
+        for (s, (stream, pcm)) in self.streams.iter_mut().zip(&mut self.decoded).enumerate() {
+            let packet = Packet::parse(data.get(offset..).ok_or(InvalidPacket)?, s != last)?;
+            offset += packet.len;
+            let samples = stream.decode(Some(&packet), pcm, MAX_FRAME_SIZE)?;
+            if frame_size.is_some_and(|size| size != samples) {
+                return Err(InvalidPacket);
+            }
+            frame_size = Some(samples);
+        }
+        let frame_size = frame_size.ok_or(InvalidPacket)?;
+        self.last_frame_size = frame_size;
+        Ok(frame_size)
+    }
+}
+
+impl Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        if params.codec != CODEC_TYPE_OPUS {
+            return unsupported_error("opus: not an Opus track");
+        }
+        let head = match &params.extra_data {
+            Some(extra) => OpusHead::parse(extra),
+            None => OpusHead::default_for(params.channels.map_or(2, |channels| channels.count())),
+        };
+        let Some(head) = head else {
+            return decode_error("opus: invalid OpusHead");
+        };
+        let channels = params
+            .channels
+            .filter(|channels| channels.count() == head.channels)
+            .or_else(|| Channels::from_bits((1u32 << head.channels) - 1))
+            .filter(|channels| channels.count() == head.channels);
+        let Some(channels) = channels else {
+            return unsupported_error("opus: too many channels");
+        };
+
+        let streams = (0..head.streams).map(|s| StreamDecoder::new(if s < head.coupled { 2 } else { 1 }));
+        let streams: Vec<_> = streams.collect();
+        let decoded = streams.iter().map(|s| vec![0.0; MAX_FRAME_SIZE * s.channels()]).collect();
+        let mut params = params.clone();
+        params.with_sample_rate(48000).with_channels(channels);
+        Ok(OpusDecoder {
+            buffer: AudioBuffer::new(MAX_FRAME_SIZE as u64, SignalSpec::new(48000, channels)),
+            skip: head.pre_skip,
+            last_frame_size: 960,
+            params,
+            head,
+            streams,
+            decoded,
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus")]
+    }
+
+    fn reset(&mut self) {
+        self.streams.iter_mut().for_each(StreamDecoder::reset);
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &EncodedPacket) -> Result<AudioBufferRef<'_>> {
+        self.buffer.clear();
+        let Ok(frame_size) = self.decode_streams(packet.buf()) else {
+            return decode_error("opus: invalid packet");
+        };
+        let skipped = self.skip.min(frame_size);
+        self.skip -= skipped;
+        self.buffer.render_reserved(Some(frame_size - skipped));
+        for channel in 0..self.head.channels {
+            let out = self.buffer.chan_mut(channel);
+            match self.head.source(channel) {
+                Some((s, c)) => {
+                    let stride = self.streams[s].channels();
+                    let pcm = self.decoded[s][(skipped * stride + c)..].iter().step_by(stride);
+                    for (out, &sample) in out.iter_mut().zip(pcm) {
+                        *out = sample * self.head.gain;
+                    }
+                }
+                None => out.fill(0.0),
+            }
+        }
+        Ok(self.buffer.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buffer.as_audio_buffer_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_multistream_channels() {
+        // 5.1 in family 1: two coupled streams (front, rear), then centre and LFE
+        let mut head = b"OpusHead\x01\x06\x38\x01\x80\xbb\0\0\x00\x01\x01\x04\x02".to_vec();
+        head.extend_from_slice(&[0, 4, 1, 2, 3, 5]);
+        let head = OpusHead::parse(&head).unwrap();
+        assert_eq!((head.channels, head.pre_skip, head.streams, head.coupled), (6, 312, 4, 2));
+        assert!((head.gain - 10f32.powf(1.0 / 20.0)).abs() < 1e-6);
+        let sources: Vec<_> = (0..6).map(|c| head.source(c)).collect();
+        assert_eq!(sources, [Some((0, 0)), Some((2, 0)), Some((0, 1)), Some((1, 0)), Some((1, 1)), Some((3, 0))]);
+
+        // A mapping entry past the streams' channels, and family 0 with 3 channels
+        let bad = b"OpusHead\x01\x02\0\0\x80\xbb\0\0\0\0\x01\x01\x00\x00\x01";
+        assert!(OpusHead::parse(bad).is_none());
+        assert!(OpusHead::parse(b"OpusHead\x01\x03\0\0\x80\xbb\0\0\0\0\x00").is_none());
+    }
+}
@@ -0,0 +1,208 @@
+// Opus packet framing (RFC 6716 §3)
+//
+// The TOC byte names the mode, bandwidth, frame duration and channel count;
+// codes 0-3 then pack one, two equal, two unequal or up to 48 frames.
+// Every stream but the last in a multistream packet uses the self-delimited
+// variant, which spells out the size of its final frame.
+
+use super::InvalidPacket;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Mode {
+    Silk,
+    Hybrid,
+    Celt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum Bandwidth {
+    Narrow,
+    Medium,
+    Wide,
+    SuperWide,
+    Full,
+}
+
+impl Bandwidth {
+    fn from_index(index: u8) -> Self {
+        [Bandwidth::Narrow, Bandwidth::Medium, Bandwidth::Wide, Bandwidth::SuperWide, Bandwidth::Full]
+            [usize::from(index)]
+    }
+
+    /// The last CELT band coded at this bandwidth.
+    pub(super) fn celt_end_band(self) -> usize {
+        match self {
+            Bandwidth::Narrow => 13,
+            Bandwidth::Medium | Bandwidth::Wide => 17,
+            Bandwidth::SuperWide => 19,
+            Bandwidth::Full => 21,
+        }
+    }
+
+    /// SILK's internal sample rate in kHz (capped at wideband).
+    pub(super) fn silk_khz(self) -> usize {
+        match self {
+            Bandwidth::Narrow => 8,
+            Bandwidth::Medium => 12,
+            _ => 16,
+        }
+    }
+}
+
+/// The fields of a TOC byte.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Toc {
+    pub(super) mode: Mode,
+    pub(super) bandwidth: Bandwidth,
+    /// Samples per frame at 48 kHz.
+    pub(super) frame_size: usize,
+    pub(super) stereo: bool,
+}
+
+impl Toc {
+    pub(super) fn parse(toc: u8) -> Self {
+        let (mode, bandwidth, frame_size) = if toc & 0x80 != 0 {
+            let bandwidth = match (toc >> 5) & 3 {
+                0 => Bandwidth::Narrow,
+                n => Bandwidth::from_index(n + 1),
+            };
+            (Mode::Celt, bandwidth, 120 << ((toc >> 3) & 3))
+        } else if toc & 0x60 == 0x60 {
+            let bandwidth = if toc & 0x10 != 0 { Bandwidth::Full } else { Bandwidth::SuperWide };
+            (Mode::Hybrid, bandwidth, if toc & 0x08 != 0 { 960 } else { 480 })
+        } else {
+            let size = match (toc >> 3) & 3 {
+                3 => 2880,
+                n => 480 << n,
+            };
+            (Mode::Silk, Bandwidth::from_index((toc >> 5) & 3), size)
+        };
+        Toc { mode, bandwidth, frame_size, stereo: toc & 4 != 0 }
+    }
+}
+
+/// One parsed packet: its TOC and the frames it carries.
+pub(super) struct Packet<'a> {
+    pub(super) toc: Toc,
+    pub(super) frames: Vec<&'a [u8]>,
+    /// Bytes this packet occupied, padding included (where the next stream of
+    /// a multistream packet starts).
+    pub(super) len: usize,
+}
+
+fn parse_size(data: &[u8]) -> Result<(usize, usize), InvalidPacket> {
+    match data {
+        [] => Err(InvalidPacket),
+        [b, ..] if *b < 252 => Ok((usize::from(*b), 1)),
+        [b0, b1, ..] => Ok((4 * usize::from(*b1) + usize::from(*b0), 2)),
+        _ => Err(InvalidPacket),
+    }
+}
+
+impl<'a> Packet<'a> {
+    pub(super) fn parse(data: &'a [u8], self_delimited: bool) -> Result<Self, InvalidPacket> {
+        let (&toc_byte, mut rest) = data.split_first().ok_or(InvalidPacket)?;
+        let toc = Toc::parse(toc_byte);
+        let mut sizes = Vec::new();
+        let mut cbr = false;
+        let mut pad = 0;
+        let count;
+        let mut last_size;
+        match toc_byte & 3 {
+            0 => {
+                count = 1;
+                last_size = rest.len();
+            }
+            1 => {
+                count = 2;
+                cbr = true;
+                last_size = rest.len() / 2;
+                if !self_delimited {
+                    if rest.len() % 2 != 0 {
+                        return Err(InvalidPacket);
+                    }
+                    sizes.push(last_size);
+                }
+            }
+            2 => {
+                count = 2;
+                let (size, bytes) = parse_size(rest)?;
+                rest = &rest[bytes..];
+                if size > rest.len() {
+                    return Err(InvalidPacket);
+                }
+                sizes.push(size);
+                last_size = rest.len() - size;
+            }
+            _ => {
+                let (&ch, tail) = rest.split_first().ok_or(InvalidPacket)?;
+                rest = tail;
+                count = usize::from(ch & 0x3f);
+                if count == 0 || toc.frame_size * count > 5760 {
+                    return Err(InvalidPacket);
+                }
+                if ch & 0x40 != 0 {
+                    loop {
+                        let (&p, tail) = rest.split_first().ok_or(InvalidPacket)?;
+                        rest = tail;
+                        let n = if p == 255 { 254 } else { usize::from(p) };
+                        rest = &rest[..rest.len().checked_sub(n).ok_or(InvalidPacket)?];
+                        pad += n;
+                        if p != 255 {
+                            break;
+                        }
+                    }
+                }
+                cbr = ch & 0x80 == 0;
+                if !cbr {
+                    last_size = rest.len();
+                    for _ in 0..count - 1 {
+                        let (size, bytes) = parse_size(rest)?;
+                        rest = &rest[bytes..];
+                        if size > rest.len() {
+                            return Err(InvalidPacket);
+                        }
+                        sizes.push(size);
+                        last_size = last_size.checked_sub(bytes + size).ok_or(InvalidPacket)?;
+                    }
+                } else {
+                    last_size = rest.len() / count;
+                    if !self_delimited {
+                        if last_size * count != rest.len() {
+                            return Err(InvalidPacket);
+                        }
+                        sizes.resize(count - 1, last_size);
+                    }
+                }
+            }
+        }
+        if self_delimited {
+            let (size, bytes) = parse_size(rest)?;
+            rest = &rest[bytes..];
+            if size > rest.len() {
+                return Err(InvalidPacket);
+            }
+            if cbr {
+                if size * count > rest.len() {
+                    return Err(InvalidPacket);
+                }
+                sizes.clear();
+                sizes.resize(count - 1, size);
+            } else if bytes + size > last_size {
+                return Err(InvalidPacket);
+            }
+            last_size = size;
+        } else if last_size > 1275 {
+            return Err(InvalidPacket);
+        }
+        sizes.push(last_size);
+
+        let mut frames = Vec::with_capacity(count);
+        let mut at = data.len() - pad - rest.len();
+        for &size in &sizes {
+            frames.push(data.get(at..at + size).ok_or(InvalidPacket)?);
+            at += size;
+        }
+        Ok(Packet { toc, frames, len: at + pad })
+    }
+}
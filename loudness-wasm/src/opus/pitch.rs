@@ -0,0 +1,196 @@
+// CELT packet-loss concealment helpers
+//
+// Pitch search on the decoded history plus the LPC analysis and filters the
+// pitch-based concealment uses to extend the last period without a click.
+
+pub(super) const LPC_ORDER: usize = 24;
+
+fn inner_prod(x: &[f32], y: &[f32]) -> f32 {
+    x.iter().zip(y).map(|(a, b)| a * b).sum()
+}
+
+/// Levinson-Durbin recursion; stops early once the prediction gain passes
+/// 30 dB.
+pub(super) fn lpc(ac: &[f32], out: &mut [f32]) {
+    let p = out.len();
+    out.fill(0.0);
+    let mut error = ac[0];
+    if ac[0] == 0.0 {
+        return;
+    }
+    for i in 0..p {
+        let mut rr = 0.0;
+        for j in 0..i {
+            rr += out[j] * ac[i - j];
+        }
+        rr += ac[i + 1];
+        let r = -rr / error;
+        out[i] = r;
+        for j in 0..(i + 1) >> 1 {
+            let tmp1 = out[j];
+            let tmp2 = out[i - 1 - j];
+            out[j] = tmp1 + r * tmp2;
+            out[i - 1 - j] = tmp2 + r * tmp1;
+        }
+        error -= r * r * error;
+        if error < 0.001 * ac[0] {
+            break;
+        }
+    }
+}
+
+/// Autocorrelation of `x` for lags `0..ac.len()`, with the first and last
+/// `window.len()` samples tapered.
+pub(super) fn autocorr(x: &[f32], ac: &mut [f32], window: &[f32]) {
+    let n = x.len();
+    let mut xx = x.to_vec();
+    for (i, &w) in window.iter().enumerate() {
+        xx[i] = x[i] * w;
+        xx[n - i - 1] = x[n - i - 1] * w;
+    }
+    for (k, a) in ac.iter_mut().enumerate() {
+        *a = inner_prod(&xx[k..], &xx[..n - k]);
+    }
+}
+
+/// `y[i] = x[i] + Σ num[k]·x[i-k-1]`, reading `num.len()` samples of history
+/// before `x[at]`.
+pub(super) fn fir(x: &[f32], at: usize, num: &[f32], y: &mut [f32]) {
+    for (i, out) in y.iter_mut().enumerate() {
+        let mut sum = x[at + i];
+        for (k, &c) in num.iter().enumerate() {
+            sum += c * x[at + i - k - 1];
+        }
+        *out = sum;
+    }
+}
+
+/// All-pole synthesis `y[i] = x[i] - Σ den[k]·y[i-k-1]` in place; `mem`
+/// holds the previous outputs, newest first.
+pub(super) fn iir(x: &mut [f32], den: &[f32], mem: &[f32]) {
+    let ord = den.len();
+    let mut hist = vec![0.0f32; ord + x.len()];
+    for i in 0..ord {
+        hist[i] = mem[ord - i - 1];
+    }
+    for i in 0..x.len() {
+        let mut sum = x[i];
+        for (k, &c) in den.iter().enumerate() {
+            sum -= c * hist[ord + i - k - 1];
+        }
+        hist[ord + i] = sum;
+        x[i] = sum;
+    }
+}
+
+fn fir5(x: &mut [f32], num: &[f32; 5]) {
+    let mut mem = [0.0f32; 5];
+    for v in x.iter_mut() {
+        let sum = *v + num.iter().zip(&mem).map(|(c, m)| c * m).sum::<f32>();
+        mem.copy_within(0..4, 1);
+        mem[0] = *v;
+        *v = sum;
+    }
+}
+
+/// Halves the rate of the (summed) channels and whitens them with a
+/// fourth-order LPC, ready for `search`.
+pub(super) fn downsample(channels: &[&[f32]], len: usize) -> Vec<f32> {
+    let half = len >> 1;
+    let mut lp = vec![0.0f32; half];
+    for x in channels {
+        for i in 1..half {
+            lp[i] += 0.5 * (0.5 * (x[2 * i - 1] + x[2 * i + 1]) + x[2 * i]);
+        }
+        lp[0] += 0.5 * (0.5 * x[1] + x[0]);
+    }
+    let mut ac = [0.0f32; 5];
+    autocorr(&lp, &mut ac, &[]);
+    ac[0] *= 1.0001;
+    for (i, a) in ac.iter_mut().enumerate().skip(1) {
+        *a -= *a * (0.008 * i as f32) * (0.008 * i as f32);
+    }
+    let mut coefs = [0.0f32; 4];
+    lpc(&ac, &mut coefs);
+    let mut tmp = 1.0f32;
+    for c in coefs.iter_mut() {
+        tmp *= 0.9;
+        *c *= tmp;
+    }
+    let c1 = 0.8;
+    let lpc2 = [
+        coefs[0] + 0.8,
+        coefs[1] + c1 * coefs[0],
+        coefs[2] + c1 * coefs[1],
+        coefs[3] + c1 * coefs[2],
+        c1 * coefs[3],
+    ];
+    fir5(&mut lp, &lpc2);
+    lp
+}
+
+fn find_best_pitch(xcorr: &[f32], y: &[f32], len: usize, best: &mut [usize; 2]) {
+    let mut syy = 1.0f32;
+    let mut best_num = [-1.0f32; 2];
+    let mut best_den = [0.0f32; 2];
+    *best = [0, 1];
+    for v in &y[..len] {
+        syy += v * v;
+    }
+    for (i, &xc) in xcorr.iter().enumerate() {
+        if xc > 0.0 {
+            let xcorr16 = xc * 1e-12;
+            let num = xcorr16 * xcorr16;
+            if num * best_den[1] > best_num[1] * syy {
+                if num * best_den[0] > best_num[0] * syy {
+                    best_num[1] = best_num[0];
+                    best_den[1] = best_den[0];
+                    best[1] = best[0];
+                    best_num[0] = num;
+                    best_den[0] = syy;
+                    best[0] = i;
+                } else {
+                    best_num[1] = num;
+                    best_den[1] = syy;
+                    best[1] = i;
+                }
+            }
+        }
+        syy += y[i + len] * y[i + len] - y[i] * y[i];
+        syy = syy.max(1.0);
+    }
+}
+
+/// Lag (in half-rate samples) at which `y` best matches `x`: a coarse search
+/// at a quarter rate, refined at half rate around the two best candidates.
+pub(super) fn search(x: &[f32], y: &[f32], len: usize, max_pitch: usize) -> usize {
+    let lag = len + max_pitch;
+    let x4: Vec<f32> = (0..len >> 2).map(|j| x[2 * j]).collect();
+    let y4: Vec<f32> = (0..lag >> 2).map(|j| y[2 * j]).collect();
+    let mut xcorr: Vec<f32> = (0..max_pitch >> 2).map(|i| inner_prod(&x4, &y4[i..])).collect();
+    let mut best = [0usize; 2];
+    find_best_pitch(&xcorr, &y4, len >> 2, &mut best);
+
+    xcorr.clear();
+    xcorr.extend((0..max_pitch >> 1).map(|i| {
+        let near = |b: usize| (i as isize - 2 * b as isize).abs() <= 2;
+        if near(best[0]) || near(best[1]) {
+            inner_prod(&x[..len >> 1], &y[i..]).max(-1.0)
+        } else {
+            0.0
+        }
+    }));
+    find_best_pitch(&xcorr, y, len >> 1, &mut best);
+
+    let mut offset = 0isize;
+    if best[0] > 0 && best[0] < (max_pitch >> 1) - 1 {
+        let (a, b, c) = (xcorr[best[0] - 1], xcorr[best[0]], xcorr[best[0] + 1]);
+        if c - a > 0.7 * (b - a) {
+            offset = 1;
+        } else if a - c > 0.7 * (b - c) {
+            offset = -1;
+        }
+    }
+    (2 * best[0] as isize - offset) as usize
+}
+
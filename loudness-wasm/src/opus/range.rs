@@ -0,0 +1,253 @@
+// Range decoder (RFC 6716 §4.1)
+//
+// Both layers read one shared range-coded stream: symbols come from the front
+// of the frame, raw bits (`bits`) from the back. `tell`/`tell_frac` report
+// the bits consumed so far, which the CELT allocator budgets against.
+
+const SYM_BITS: u32 = 8;
+const CODE_BITS: u32 = 32;
+const CODE_TOP: u32 = 1 << (CODE_BITS - 1);
+const CODE_BOT: u32 = CODE_TOP >> SYM_BITS;
+const CODE_EXTRA: u32 = (CODE_BITS - 2) % SYM_BITS + 1;
+const UINT_BITS: u32 = 8;
+const WINDOW_SIZE: u32 = 32;
+pub(super) const BITRES: u32 = 3;
+
+pub(super) struct RangeDecoder<'a> {
+    buf: &'a [u8],
+    storage: usize,
+    end_offs: usize,
+    end_window: u32,
+    nend_bits: u32,
+    nbits_total: i32,
+    offs: usize,
+    rng: u32,
+    val: u32,
+    ext: u32,
+    rem: u32,
+    pub(super) error: bool,
+}
+
+/// Number of bits needed to represent `x` (0 for 0).
+pub(super) fn ilog(x: u32) -> i32 {
+    (32 - x.leading_zeros()) as i32
+}
+
+impl<'a> RangeDecoder<'a> {
+    pub(super) fn new(buf: &'a [u8]) -> Self {
+        let mut dec = RangeDecoder {
+            buf,
+            storage: buf.len(),
+            end_offs: 0,
+            end_window: 0,
+            nend_bits: 0,
+            nbits_total: (CODE_BITS + 1 - ((CODE_BITS - CODE_EXTRA) / SYM_BITS) * SYM_BITS) as i32,
+            offs: 0,
+            rng: 1 << CODE_EXTRA,
+            val: 0,
+            ext: 0,
+            rem: 0,
+            error: false,
+        };
+        dec.rem = dec.read_byte();
+        dec.val = dec.rng - 1 - (dec.rem >> (SYM_BITS - CODE_EXTRA));
+        dec.normalize();
+        dec
+    }
+
+    fn read_byte(&mut self) -> u32 {
+        if self.offs < self.storage {
+            self.offs += 1;
+            u32::from(self.buf[self.offs - 1])
+        } else {
+            0
+        }
+    }
+
+    fn read_byte_from_end(&mut self) -> u32 {
+        if self.end_offs < self.storage {
+            self.end_offs += 1;
+            u32::from(self.buf[self.storage - self.end_offs])
+        } else {
+            0
+        }
+    }
+
+    fn normalize(&mut self) {
+        while self.rng <= CODE_BOT {
+            self.nbits_total += SYM_BITS as i32;
+            self.rng <<= SYM_BITS;
+            let sym = self.rem;
+            self.rem = self.read_byte();
+            let sym = (sym << SYM_BITS | self.rem) >> (SYM_BITS - CODE_EXTRA);
+            self.val = ((self.val << SYM_BITS).wrapping_add(255 & !sym)) & (CODE_TOP - 1);
+        }
+    }
+
+    /// Shrinks the readable frame to its first `storage` bytes (the redundant
+    /// CELT frame sits at the end of a SILK/hybrid frame).
+    pub(super) fn shrink(&mut self, storage: usize) {
+        self.storage = storage;
+    }
+
+    pub(super) fn storage(&self) -> usize {
+        self.storage
+    }
+
+    pub(super) fn rng(&self) -> u32 {
+        self.rng
+    }
+
+    pub(super) fn decode(&mut self, ft: u32) -> u32 {
+        self.ext = self.rng / ft;
+        let s = self.val / self.ext;
+        ft - (s + 1).min(ft)
+    }
+
+    pub(super) fn decode_bin(&mut self, bits: u32) -> u32 {
+        self.ext = self.rng >> bits;
+        let s = self.val / self.ext;
+        (1 << bits) - (s + 1).min(1 << bits)
+    }
+
+    pub(super) fn update(&mut self, fl: u32, fh: u32, ft: u32) {
+        let s = self.ext.wrapping_mul(ft - fh);
+        self.val = self.val.wrapping_sub(s);
+        self.rng = if fl > 0 { self.ext.wrapping_mul(fh - fl) } else { self.rng.wrapping_sub(s) };
+        self.normalize();
+    }
+
+    /// A bit whose probability of being one is `1 / (1 << logp)`.
+    pub(super) fn bit_logp(&mut self, logp: u32) -> bool {
+        let r = self.rng;
+        let d = self.val;
+        let s = r >> logp;
+        let ret = d < s;
+        if !ret {
+            self.val = d - s;
+        }
+        self.rng = if ret { s } else { r - s };
+        self.normalize();
+        ret
+    }
+
+    /// A symbol from an inverse CDF table scaled to `1 << ftb`.
+    pub(super) fn icdf(&mut self, icdf: &[u8], ftb: u32) -> usize {
+        let mut s = self.rng;
+        let d = self.val;
+        let r = s >> ftb;
+        let mut ret = 0;
+        let mut t;
+        loop {
+            t = s;
+            s = r.wrapping_mul(u32::from(icdf[ret]));
+            if d >= s {
+                break;
+            }
+            ret += 1;
+        }
+        self.val = d - s;
+        self.rng = t - s;
+        self.normalize();
+        ret
+    }
+
+    /// A uniformly distributed integer in `0..ft`.
+    pub(super) fn uint(&mut self, ft: u32) -> u32 {
+        let ft = ft - 1;
+        let mut ftb = ilog(ft) as u32;
+        if ftb > UINT_BITS {
+            ftb -= UINT_BITS;
+            let f = (ft >> ftb) + 1;
+            let s = self.decode(f);
+            self.update(s, s + 1, f);
+            let t = s << ftb | self.bits(ftb);
+            if t <= ft {
+                return t;
+            }
+            self.error = true;
+            ft
+        } else {
+            let s = self.decode(ft + 1);
+            self.update(s, s + 1, ft + 1);
+            s
+        }
+    }
+
+    /// Raw bits read from the end of the frame.
+    pub(super) fn bits(&mut self, bits: u32) -> u32 {
+        let mut window = self.end_window;
+        let mut available = self.nend_bits;
+        if available < bits {
+            loop {
+                window |= self.read_byte_from_end() << available;
+                available += SYM_BITS;
+                if available > WINDOW_SIZE - SYM_BITS {
+                    break;
+                }
+            }
+        }
+        let ret = if bits == 0 { 0 } else { window & (u32::MAX >> (32 - bits)) };
+        self.end_window = if bits >= 32 { 0 } else { window >> bits };
+        self.nend_bits = available - bits;
+        self.nbits_total += bits as i32;
+        ret
+    }
+
+    /// Marks the frame as read up to `bits` (CELT's silence flag skips the
+    /// rest of the frame).
+    pub(super) fn skip_to(&mut self, bits: i32) {
+        self.nbits_total += bits - self.tell();
+    }
+
+    /// Whole bits consumed so far.
+    pub(super) fn tell(&self) -> i32 {
+        self.nbits_total - ilog(self.rng)
+    }
+
+    /// Bits consumed so far, in eighth-bit units.
+    pub(super) fn tell_frac(&self) -> i32 {
+        const CORRECTION: [u32; 8] = [35733, 38967, 42495, 46340, 50535, 55109, 60097, 65535];
+        let nbits = self.nbits_total << BITRES;
+        let l = ilog(self.rng);
+        let r = self.rng >> (l - 16);
+        let mut b = (r >> 12) - 8;
+        b += u32::from(r > CORRECTION[b as usize]);
+        nbits - ((l << 3) + b as i32)
+    }
+
+    /// Laplace-distributed energy residual (CELT coarse energy).
+    pub(super) fn laplace(&mut self, fs: u32, decay: i32) -> i32 {
+        const MINP: u32 = 1;
+        const NMIN: u32 = 16;
+        let mut fs = fs;
+        let mut val = 0;
+        let fm = self.decode_bin(15);
+        let mut fl = 0;
+        if fm >= fs {
+            val += 1;
+            fl = fs;
+            let ft = 32768 - MINP * (2 * NMIN) - fs;
+            fs = ((ft as i32 * (16384 - decay)) >> 15) as u32 + MINP;
+            while fs > MINP && fm >= fl + 2 * fs {
+                fs *= 2;
+                fl += fs;
+                fs = (((fs - 2 * MINP) as i32 * decay) >> 15) as u32;
+                fs += MINP;
+                val += 1;
+            }
+            if fs <= MINP {
+                let di = (fm - fl) >> 1;
+                val += di as i32;
+                fl += 2 * di * MINP;
+            }
+            if fm < fl + fs {
+                val = -val;
+            } else {
+                fl += fs;
+            }
+        }
+        self.update(fl, (fl + fs).min(32768), 32768);
+        val
+    }
+}
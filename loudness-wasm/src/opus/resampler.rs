@@ -0,0 +1,120 @@
+// SILK output resampler (8/12/16 kHz to 48 kHz)
+//
+// Upsamples 2× with a pair of all-pass chains, then picks the output
+// samples off the doubled signal with a 12-phase fractional-delay FIR. A
+// few input samples are held back so the output lines up with the CELT
+// layer's delay.
+
+use super::fixed::{rshift_round, sat16, smlabb, smlawb, smulbb, smulwb, smulww};
+use super::silk_tables::{RESAMPLER_FRAC_FIR_12, RESAMPLER_UP2_HQ_0, RESAMPLER_UP2_HQ_1};
+
+const ORDER_FIR: usize = 8;
+const OUT_HZ: i32 = 48_000;
+
+#[derive(Clone)]
+pub(super) struct Resampler {
+    s_iir: [i32; 6],
+    s_fir: [i16; ORDER_FIR],
+    delay_buf: [i16; 16],
+    batch_size: usize,
+    inv_ratio_q16: i32,
+    in_khz: usize,
+    input_delay: usize,
+}
+
+impl Resampler {
+    pub(super) fn new(in_khz: usize) -> Self {
+        let input_delay = match in_khz {
+            8 => 0,
+            12 => 4,
+            _ => 7,
+        };
+        let in_hz = in_khz as i32 * 1000;
+        let mut inv_ratio_q16 = ((in_hz << 15) / OUT_HZ) << 2;
+        while smulww(inv_ratio_q16, OUT_HZ) < in_hz << 1 {
+            inv_ratio_q16 += 1;
+        }
+        Resampler {
+            s_iir: [0; 6],
+            s_fir: [0; ORDER_FIR],
+            delay_buf: [0; 16],
+            batch_size: in_khz * 10,
+            inv_ratio_q16,
+            in_khz,
+            input_delay,
+        }
+    }
+
+    /// Resamples `input` (at least 1 ms) into `out`, six output samples per
+    /// input kHz-millisecond.
+    pub(super) fn process(&mut self, out: &mut [i16], input: &[i16]) {
+        let n = self.in_khz - self.input_delay;
+        let delay = self.input_delay;
+        self.delay_buf[delay..self.in_khz].copy_from_slice(&input[..n]);
+        let head = self.delay_buf;
+        let written = self.iir_fir(out, &head[..self.in_khz]);
+        self.iir_fir(&mut out[written..], &input[n..input.len() - delay]);
+        self.delay_buf[..delay].copy_from_slice(&input[input.len() - delay..]);
+    }
+
+    fn iir_fir(&mut self, out: &mut [i16], mut input: &[i16]) -> usize {
+        let mut buf = vec![0i16; 2 * self.batch_size + ORDER_FIR];
+        buf[..ORDER_FIR].copy_from_slice(&self.s_fir);
+        let mut written = 0;
+        loop {
+            let n = input.len().min(self.batch_size);
+            up2_hq(&mut self.s_iir, &mut buf[ORDER_FIR..ORDER_FIR + 2 * n], &input[..n]);
+            let max_index_q16 = (n as i32) << 17;
+            let mut index_q16 = 0;
+            while index_q16 < max_index_q16 {
+                let table = smulwb(index_q16 & 0xffff, 12) as usize;
+                let taps = &buf[(index_q16 >> 16) as usize..];
+                let fir = &RESAMPLER_FRAC_FIR_12[table];
+                let rev = &RESAMPLER_FRAC_FIR_12[11 - table];
+                let mut res = smulbb(i32::from(taps[0]), i32::from(fir[0]));
+                res = smlabb(res, i32::from(taps[1]), i32::from(fir[1]));
+                res = smlabb(res, i32::from(taps[2]), i32::from(fir[2]));
+                res = smlabb(res, i32::from(taps[3]), i32::from(fir[3]));
+                res = smlabb(res, i32::from(taps[4]), i32::from(rev[3]));
+                res = smlabb(res, i32::from(taps[5]), i32::from(rev[2]));
+                res = smlabb(res, i32::from(taps[6]), i32::from(rev[1]));
+                res = smlabb(res, i32::from(taps[7]), i32::from(rev[0]));
+                out[written] = sat16(rshift_round(res, 15));
+                written += 1;
+                index_q16 += self.inv_ratio_q16;
+            }
+            input = &input[n..];
+            buf.copy_within(2 * n..2 * n + ORDER_FIR, 0);
+            if input.is_empty() {
+                break;
+            }
+        }
+        self.s_fir.copy_from_slice(&buf[..ORDER_FIR]);
+        written
+    }
+}
+
+fn allpass(state: &mut i32, input: i32, coef: i16) -> i32 {
+    let y = input.wrapping_sub(*state);
+    let x = if coef < 0 { smlawb(y, y, i32::from(coef)) } else { smulwb(y, i32::from(coef)) };
+    let out = state.wrapping_add(x);
+    *state = input.wrapping_add(x);
+    out
+}
+
+/// 2× upsampling through two three-section all-pass chains (Q10 state).
+fn up2_hq(s: &mut [i32; 6], out: &mut [i16], input: &[i16]) {
+    for (k, &x) in input.iter().enumerate() {
+        let in32 = i32::from(x) << 10;
+        let mut y = in32;
+        for (state, &coef) in s[..3].iter_mut().zip(&RESAMPLER_UP2_HQ_0) {
+            y = allpass(state, y, coef);
+        }
+        out[2 * k] = sat16(rshift_round(y, 10));
+        let mut y = in32;
+        for (state, &coef) in s[3..].iter_mut().zip(&RESAMPLER_UP2_HQ_1) {
+            y = allpass(state, y, coef);
+        }
+        out[2 * k + 1] = sat16(rshift_round(y, 10));
+    }
+}
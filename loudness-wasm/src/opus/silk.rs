@@ -0,0 +1,845 @@
+// SILK decoder (RFC 6716 §4.2)
+//
+// The linear-prediction layer: per 10 or 20 ms frame, side information
+// (signal type, gains, NLSFs, pitch lags, LTP taps) and a pulse-coded
+// excitation drive long- and short-term synthesis filters at 8, 12 or
+// 16 kHz. Stereo is coded as mid/side with a predictor; the output is
+// resampled to 48 kHz. Everything here is integer arithmetic, bit-exact with
+// the reference decoder.
+
+use super::fixed::{
+    add_sat32, div32_varq, inverse32_varq, log2lin, lshift_sat32, rand, rshift_round, sat16, smlabb, smlawb,
+    smulbb, smulwb, smulww,
+};
+use super::lpc::{self, NlsfCodebook, MAX_LPC_ORDER, NLSF_CB_NB_MB, NLSF_CB_WB};
+use super::range::RangeDecoder;
+use super::resampler::Resampler;
+use super::silk_plc::{Cng, Plc};
+use super::silk_tables::*;
+
+pub(super) const MAX_FRAME_LENGTH: usize = 320;
+pub(super) const MAX_NB_SUBFR: usize = 4;
+pub(super) const LTP_ORDER: usize = 5;
+const MAX_SUB_FRAME_LENGTH: usize = 80;
+const MAX_FRAMES_PER_PACKET: usize = 3;
+const SHELL_CODEC_FRAME_LENGTH: usize = 16;
+
+pub(super) const TYPE_NO_VOICE_ACTIVITY: i32 = 0;
+pub(super) const TYPE_VOICED: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CondCoding {
+    Independently,
+    IndependentlyNoLtpScaling,
+    Conditionally,
+}
+
+/// Quantization indices of one frame.
+#[derive(Clone, Copy, Default)]
+pub(super) struct Indices {
+    gains: [i8; MAX_NB_SUBFR],
+    ltp: [i8; MAX_NB_SUBFR],
+    nlsf: [i8; MAX_LPC_ORDER + 1],
+    lag_index: i16,
+    contour: i8,
+    pub(super) signal_type: i8,
+    quant_offset_type: i8,
+    nlsf_interp_coef_q2: i8,
+    per_index: i8,
+    ltp_scale_index: i8,
+    seed: i8,
+}
+
+/// Per-frame parameters derived from the indices.
+#[derive(Clone, Copy, Default)]
+pub(super) struct Control {
+    pub(super) pitch_l: [i32; MAX_NB_SUBFR],
+    pub(super) gains_q16: [i32; MAX_NB_SUBFR],
+    pub(super) pred_coef_q12: [[i16; MAX_LPC_ORDER]; 2],
+    pub(super) ltp_coef_q14: [i16; LTP_ORDER * MAX_NB_SUBFR],
+    pub(super) ltp_scale_q14: i32,
+}
+
+/// One channel (mid or side) of the SILK decoder.
+#[derive(Clone)]
+pub(super) struct ChannelState {
+    prev_gain_q16: i32,
+    pub(super) exc_q14: [i32; MAX_FRAME_LENGTH],
+    pub(super) s_lpc_q14_buf: [i32; MAX_LPC_ORDER],
+    pub(super) out_buf: [i16; MAX_FRAME_LENGTH + 2 * MAX_SUB_FRAME_LENGTH],
+    lag_prev: i32,
+    last_gain_index: i32,
+    pub(super) fs_khz: usize,
+    pub(super) nb_subfr: usize,
+    pub(super) frame_length: usize,
+    pub(super) subfr_length: usize,
+    pub(super) ltp_mem_length: usize,
+    pub(super) lpc_order: usize,
+    pub(super) prev_nlsf_q15: [i16; MAX_LPC_ORDER],
+    pub(super) first_frame_after_reset: bool,
+    pitch_lag_low_bits_icdf: &'static [u8],
+    pitch_contour_icdf: &'static [u8],
+    n_frames_decoded: usize,
+    n_frames_per_packet: usize,
+    ec_prev_signal_type: i32,
+    ec_prev_lag_index: i16,
+    vad_flags: [bool; MAX_FRAMES_PER_PACKET],
+    lbrr_flags: [bool; MAX_FRAMES_PER_PACKET],
+    resampler: Resampler,
+    nlsf_cb: &'static NlsfCodebook,
+    pub(super) indices: Indices,
+    pub(super) cng: Cng,
+    pub(super) loss_cnt: i32,
+    pub(super) prev_signal_type: i32,
+    pub(super) plc: Plc,
+}
+
+impl ChannelState {
+    fn new() -> Self {
+        ChannelState {
+            prev_gain_q16: 65536,
+            exc_q14: [0; MAX_FRAME_LENGTH],
+            s_lpc_q14_buf: [0; MAX_LPC_ORDER],
+            out_buf: [0; MAX_FRAME_LENGTH + 2 * MAX_SUB_FRAME_LENGTH],
+            lag_prev: 0,
+            last_gain_index: 0,
+            fs_khz: 0,
+            nb_subfr: 0,
+            frame_length: 0,
+            subfr_length: 0,
+            ltp_mem_length: 0,
+            lpc_order: 0,
+            prev_nlsf_q15: [0; MAX_LPC_ORDER],
+            first_frame_after_reset: true,
+            pitch_lag_low_bits_icdf: &UNIFORM4_ICDF,
+            pitch_contour_icdf: &PITCH_CONTOUR_NB_ICDF,
+            n_frames_decoded: 0,
+            n_frames_per_packet: 0,
+            ec_prev_signal_type: 0,
+            ec_prev_lag_index: 0,
+            vad_flags: [false; MAX_FRAMES_PER_PACKET],
+            lbrr_flags: [false; MAX_FRAMES_PER_PACKET],
+            resampler: Resampler::new(8),
+            nlsf_cb: &NLSF_CB_NB_MB,
+            indices: Indices::default(),
+            cng: Cng::new(),
+            loss_cnt: 0,
+            prev_signal_type: 0,
+            plc: Plc::new(),
+        }
+    }
+
+    fn set_fs(&mut self, fs_khz: usize) {
+        self.subfr_length = 5 * fs_khz;
+        let frame_length = self.nb_subfr * self.subfr_length;
+        if self.fs_khz != fs_khz {
+            self.resampler = Resampler::new(fs_khz);
+        }
+        if self.fs_khz != fs_khz || frame_length != self.frame_length {
+            self.pitch_contour_icdf = match (fs_khz == 8, self.nb_subfr == MAX_NB_SUBFR) {
+                (true, true) => &PITCH_CONTOUR_NB_ICDF,
+                (true, false) => &PITCH_CONTOUR_10_MS_NB_ICDF,
+                (false, true) => &PITCH_CONTOUR_ICDF,
+                (false, false) => &PITCH_CONTOUR_10_MS_ICDF,
+            };
+            if self.fs_khz != fs_khz {
+                self.ltp_mem_length = 20 * fs_khz;
+                (self.lpc_order, self.nlsf_cb) =
+                    if fs_khz == 16 { (16, &NLSF_CB_WB) } else { (10, &NLSF_CB_NB_MB) };
+                self.pitch_lag_low_bits_icdf = match fs_khz {
+                    16 => &UNIFORM8_ICDF,
+                    12 => &UNIFORM6_ICDF,
+                    _ => &UNIFORM4_ICDF,
+                };
+                self.first_frame_after_reset = true;
+                self.lag_prev = 100;
+                self.last_gain_index = 10;
+                self.prev_signal_type = TYPE_NO_VOICE_ACTIVITY;
+                self.out_buf.fill(0);
+                self.s_lpc_q14_buf.fill(0);
+            }
+            self.fs_khz = fs_khz;
+            self.frame_length = frame_length;
+        }
+    }
+
+    fn decode_indices(&mut self, dec: &mut RangeDecoder, frame_index: usize, decode_lbrr: bool, cond: CondCoding) {
+        let ix = if decode_lbrr || self.vad_flags[frame_index] {
+            dec.icdf(&TYPE_OFFSET_VAD_ICDF, 8) + 2
+        } else {
+            dec.icdf(&TYPE_OFFSET_NO_VAD_ICDF, 8)
+        };
+        let ind = &mut self.indices;
+        ind.signal_type = (ix >> 1) as i8;
+        ind.quant_offset_type = (ix & 1) as i8;
+
+        if cond == CondCoding::Conditionally {
+            ind.gains[0] = dec.icdf(&DELTA_GAIN_ICDF, 8) as i8;
+        } else {
+            ind.gains[0] = (dec.icdf(&GAIN_ICDF[ind.signal_type as usize], 8) << 3) as i8;
+            ind.gains[0] += dec.icdf(&UNIFORM8_ICDF, 8) as i8;
+        }
+        for k in 1..self.nb_subfr {
+            ind.gains[k] = dec.icdf(&DELTA_GAIN_ICDF, 8) as i8;
+        }
+
+        let cb = self.nlsf_cb;
+        ind.nlsf[0] = dec.icdf(&cb.cb1_icdf[(ind.signal_type as usize >> 1) * 32..], 8) as i8;
+        let (ec_ix, _) = cb.unpack(ind.nlsf[0] as usize);
+        for (i, &ec) in ec_ix.iter().enumerate().take(cb.order) {
+            let mut ix = dec.icdf(&cb.cb2_icdf[ec..], 8) as i32;
+            if ix == 0 {
+                ix -= dec.icdf(&NLSF_EXT_ICDF, 8) as i32;
+            } else if ix == 8 {
+                ix += dec.icdf(&NLSF_EXT_ICDF, 8) as i32;
+            }
+            ind.nlsf[i + 1] = (ix - 4) as i8;
+        }
+        ind.nlsf_interp_coef_q2 = if self.nb_subfr == MAX_NB_SUBFR {
+            dec.icdf(&NLSF_INTERPOLATION_FACTOR_ICDF, 8) as i8
+        } else {
+            4
+        };
+
+        if i32::from(ind.signal_type) == TYPE_VOICED {
+            let mut absolute = true;
+            if cond == CondCoding::Conditionally && self.ec_prev_signal_type == TYPE_VOICED {
+                let delta = dec.icdf(&PITCH_DELTA_ICDF, 8) as i16;
+                if delta > 0 {
+                    ind.lag_index = self.ec_prev_lag_index + delta - 9;
+                    absolute = false;
+                }
+            }
+            if absolute {
+                ind.lag_index = (dec.icdf(&PITCH_LAG_ICDF, 8) * (self.fs_khz >> 1)) as i16;
+                ind.lag_index += dec.icdf(self.pitch_lag_low_bits_icdf, 8) as i16;
+            }
+            self.ec_prev_lag_index = ind.lag_index;
+            ind.contour = dec.icdf(self.pitch_contour_icdf, 8) as i8;
+
+            ind.per_index = dec.icdf(&LTP_PER_INDEX_ICDF, 8) as i8;
+            let gain_icdf: &[u8] = match ind.per_index {
+                0 => &LTP_GAIN_ICDF_0,
+                1 => &LTP_GAIN_ICDF_1,
+                _ => &LTP_GAIN_ICDF_2,
+            };
+            for k in 0..self.nb_subfr {
+                ind.ltp[k] = dec.icdf(gain_icdf, 8) as i8;
+            }
+            ind.ltp_scale_index =
+                if cond == CondCoding::Independently { dec.icdf(&LTPSCALE_ICDF, 8) as i8 } else { 0 };
+        }
+        self.ec_prev_signal_type = i32::from(ind.signal_type);
+        ind.seed = dec.icdf(&UNIFORM4_ICDF, 8) as i8;
+    }
+
+    fn decode_parameters(&mut self, ctrl: &mut Control, cond: CondCoding) {
+        let order = self.lpc_order;
+        // Gains.
+        for k in 0..self.nb_subfr {
+            let ind = i32::from(self.indices.gains[k]);
+            if k == 0 && cond != CondCoding::Conditionally {
+                self.last_gain_index = ind.max(self.last_gain_index - 16);
+            } else {
+                let ind = ind - 4;
+                let threshold = 2 * 36 - 64 + self.last_gain_index;
+                if ind > threshold {
+                    self.last_gain_index += (ind << 1) - threshold;
+                } else {
+                    self.last_gain_index += ind;
+                }
+            }
+            self.last_gain_index = self.last_gain_index.clamp(0, 63);
+            ctrl.gains_q16[k] = log2lin((smulwb(1_907_825, self.last_gain_index) + 2090).min(3967));
+        }
+
+        let mut nlsf_q15 = [0i16; MAX_LPC_ORDER];
+        self.nlsf_cb.decode(&self.indices.nlsf, &mut nlsf_q15);
+        lpc::nlsf2a(&mut ctrl.pred_coef_q12[1][..order], &nlsf_q15[..order]);
+
+        if self.first_frame_after_reset {
+            self.indices.nlsf_interp_coef_q2 = 4;
+        }
+        if self.indices.nlsf_interp_coef_q2 < 4 {
+            let mut nlsf0_q15 = [0i16; MAX_LPC_ORDER];
+            let coef = i32::from(self.indices.nlsf_interp_coef_q2);
+            for i in 0..order {
+                let prev = i32::from(self.prev_nlsf_q15[i]);
+                nlsf0_q15[i] = (prev + ((coef * (i32::from(nlsf_q15[i]) - prev)) >> 2)) as i16;
+            }
+            lpc::nlsf2a(&mut ctrl.pred_coef_q12[0][..order], &nlsf0_q15[..order]);
+        } else {
+            ctrl.pred_coef_q12[0] = ctrl.pred_coef_q12[1];
+        }
+        self.prev_nlsf_q15[..order].copy_from_slice(&nlsf_q15[..order]);
+
+        if self.loss_cnt != 0 {
+            lpc::bwexpander(&mut ctrl.pred_coef_q12[0][..order], 63570);
+            lpc::bwexpander(&mut ctrl.pred_coef_q12[1][..order], 63570);
+        }
+
+        if i32::from(self.indices.signal_type) == TYPE_VOICED {
+            self.decode_pitch(&mut ctrl.pitch_l);
+            let cbk: &[[i8; LTP_ORDER]] = match self.indices.per_index {
+                0 => &LTP_GAIN_VQ_0,
+                1 => &LTP_GAIN_VQ_1,
+                _ => &LTP_GAIN_VQ_2,
+            };
+            for k in 0..self.nb_subfr {
+                let row = &cbk[self.indices.ltp[k] as usize];
+                for (coef, &r) in ctrl.ltp_coef_q14[k * LTP_ORDER..(k + 1) * LTP_ORDER].iter_mut().zip(row) {
+                    *coef = i16::from(r) << 7;
+                }
+            }
+            ctrl.ltp_scale_q14 = i32::from(LTP_SCALES_Q14[self.indices.ltp_scale_index as usize]);
+        } else {
+            ctrl.pitch_l = [0; MAX_NB_SUBFR];
+            ctrl.ltp_coef_q14 = [0; LTP_ORDER * MAX_NB_SUBFR];
+            self.indices.per_index = 0;
+            ctrl.ltp_scale_q14 = 0;
+        }
+    }
+
+    fn decode_pitch(&self, pitch_lags: &mut [i32; MAX_NB_SUBFR]) {
+        let fs = self.fs_khz as i32;
+        let min_lag = 2 * fs;
+        let max_lag = 18 * fs;
+        let lag = min_lag + i32::from(self.indices.lag_index);
+        let contour = self.indices.contour as usize;
+        for (k, pitch) in pitch_lags.iter_mut().enumerate().take(self.nb_subfr) {
+            let offset = match (fs == 8, self.nb_subfr == MAX_NB_SUBFR) {
+                (true, true) => CB_LAGS_STAGE2[k][contour],
+                (true, false) => CB_LAGS_STAGE2_10_MS[k][contour],
+                (false, true) => CB_LAGS_STAGE3[k][contour],
+                (false, false) => CB_LAGS_STAGE3_10_MS[k][contour],
+            };
+            *pitch = (lag + i32::from(offset)).clamp(min_lag, max_lag);
+        }
+    }
+
+    fn decode_core(&mut self, ctrl: &mut Control, xq: &mut [i16], pulses: &[i16]) {
+        let order = self.lpc_order;
+        let ltp_mem = self.ltp_mem_length;
+        let subfr = self.subfr_length;
+        let mut s_ltp = [0i16; 2 * MAX_FRAME_LENGTH];
+        let mut s_ltp_q15 = [0i32; 2 * MAX_FRAME_LENGTH];
+        let mut res_q14 = [0i32; MAX_SUB_FRAME_LENGTH];
+        let mut s_lpc_q14 = [0i32; MAX_SUB_FRAME_LENGTH + MAX_LPC_ORDER];
+
+        let offset_q10 = i32::from(
+            QUANTIZATION_OFFSETS_Q10[self.indices.signal_type as usize >> 1][self.indices.quant_offset_type as usize],
+        );
+        let nlsf_interpolation = self.indices.nlsf_interp_coef_q2 < 4;
+
+        let mut seed = i32::from(self.indices.seed);
+        for (i, &pulse) in pulses.iter().enumerate().take(self.frame_length) {
+            seed = rand(seed);
+            let mut exc = i32::from(pulse) << 14;
+            if exc > 0 {
+                exc -= 80 << 4;
+            } else if exc < 0 {
+                exc += 80 << 4;
+            }
+            exc += offset_q10 << 4;
+            if seed < 0 {
+                exc = -exc;
+            }
+            self.exc_q14[i] = exc;
+            seed = seed.wrapping_add(i32::from(pulse));
+        }
+
+        s_lpc_q14[..MAX_LPC_ORDER].copy_from_slice(&self.s_lpc_q14_buf);
+        let mut s_ltp_buf_idx = ltp_mem;
+        let mut lag = 0;
+        for k in 0..self.nb_subfr {
+            let a_q12 = ctrl.pred_coef_q12[k >> 1];
+            let mut signal_type = i32::from(self.indices.signal_type);
+            let gain_q10 = ctrl.gains_q16[k] >> 6;
+            let mut inv_gain_q31 = inverse32_varq(ctrl.gains_q16[k], 47);
+
+            let gain_adj_q16 = if ctrl.gains_q16[k] != self.prev_gain_q16 {
+                let adj = div32_varq(self.prev_gain_q16, ctrl.gains_q16[k], 16);
+                for s in &mut s_lpc_q14[..MAX_LPC_ORDER] {
+                    *s = smulww(adj, *s);
+                }
+                adj
+            } else {
+                1 << 16
+            };
+            self.prev_gain_q16 = ctrl.gains_q16[k];
+
+            // After a lost voiced frame, keep the pitch going into an
+            // unvoiced one for half a frame.
+            if self.loss_cnt != 0
+                && self.prev_signal_type == TYPE_VOICED
+                && i32::from(self.indices.signal_type) != TYPE_VOICED
+                && k < MAX_NB_SUBFR / 2
+            {
+                let b = &mut ctrl.ltp_coef_q14[k * LTP_ORDER..(k + 1) * LTP_ORDER];
+                b.fill(0);
+                b[LTP_ORDER / 2] = 4096;
+                signal_type = TYPE_VOICED;
+                ctrl.pitch_l[k] = self.lag_prev;
+            }
+
+            if signal_type == TYPE_VOICED {
+                lag = ctrl.pitch_l[k] as usize;
+                if k == 0 || (k == 2 && nlsf_interpolation) {
+                    // Re-whiten the past output with this subframe's filter.
+                    let start = ltp_mem - lag - order - LTP_ORDER / 2;
+                    if k == 2 {
+                        self.out_buf[ltp_mem..ltp_mem + 2 * subfr].copy_from_slice(&xq[..2 * subfr]);
+                    }
+                    let from = start + k * subfr;
+                    lpc::analysis_filter(
+                        &mut s_ltp[start..ltp_mem],
+                        &self.out_buf[from..from + ltp_mem - start],
+                        &a_q12[..order],
+                    );
+                    if k == 0 {
+                        inv_gain_q31 = smulwb(inv_gain_q31, ctrl.ltp_scale_q14) << 2;
+                    }
+                    for i in 0..lag + LTP_ORDER / 2 {
+                        s_ltp_q15[s_ltp_buf_idx - i - 1] = smulwb(inv_gain_q31, i32::from(s_ltp[ltp_mem - i - 1]));
+                    }
+                } else if gain_adj_q16 != 1 << 16 {
+                    for i in 0..lag + LTP_ORDER / 2 {
+                        let s = &mut s_ltp_q15[s_ltp_buf_idx - i - 1];
+                        *s = smulww(gain_adj_q16, *s);
+                    }
+                }
+            }
+
+            let exc = &self.exc_q14[k * subfr..(k + 1) * subfr];
+            let res: &[i32] = if signal_type == TYPE_VOICED {
+                let b = &ctrl.ltp_coef_q14[k * LTP_ORDER..(k + 1) * LTP_ORDER];
+                for i in 0..subfr {
+                    let at = s_ltp_buf_idx - lag + LTP_ORDER / 2;
+                    let mut pred_q13 = 2;
+                    for (j, &tap) in b.iter().enumerate() {
+                        pred_q13 = smlawb(pred_q13, s_ltp_q15[at - j], i32::from(tap));
+                    }
+                    res_q14[i] = exc[i].wrapping_add(pred_q13 << 1);
+                    s_ltp_q15[s_ltp_buf_idx] = res_q14[i] << 1;
+                    s_ltp_buf_idx += 1;
+                }
+                &res_q14[..subfr]
+            } else {
+                exc
+            };
+
+            for i in 0..subfr {
+                let mut pred_q10 = (order >> 1) as i32;
+                for (j, &a) in a_q12[..order].iter().enumerate() {
+                    pred_q10 = smlawb(pred_q10, s_lpc_q14[MAX_LPC_ORDER + i - j - 1], i32::from(a));
+                }
+                let s = add_sat32(res[i], lshift_sat32(pred_q10, 4));
+                s_lpc_q14[MAX_LPC_ORDER + i] = s;
+                xq[k * subfr + i] = sat16(rshift_round(smulww(s, gain_q10), 8));
+            }
+            s_lpc_q14.copy_within(subfr..subfr + MAX_LPC_ORDER, 0);
+        }
+        self.s_lpc_q14_buf.copy_from_slice(&s_lpc_q14[..MAX_LPC_ORDER]);
+    }
+
+    /// Decodes (or conceals, without a range decoder) one frame into `out`.
+    fn decode_frame(&mut self, dec: Option<&mut RangeDecoder>, out: &mut [i16], cond: CondCoding) -> usize {
+        let len = self.frame_length;
+        let mut ctrl = Control::default();
+        if let Some(dec) = dec {
+            let mut pulses = [0i16; MAX_FRAME_LENGTH];
+            self.decode_indices(dec, self.n_frames_decoded, false, cond);
+            let ind = self.indices;
+            decode_pulses(dec, &mut pulses, ind.signal_type, ind.quant_offset_type, len);
+            self.decode_parameters(&mut ctrl, cond);
+            self.decode_core(&mut ctrl, out, &pulses);
+            self.plc(&mut ctrl, out, false);
+            self.loss_cnt = 0;
+            self.prev_signal_type = i32::from(self.indices.signal_type);
+            self.first_frame_after_reset = false;
+        } else {
+            self.plc(&mut ctrl, out, true);
+        }
+
+        let mv_len = self.ltp_mem_length - len;
+        self.out_buf.copy_within(len..len + mv_len, 0);
+        self.out_buf[mv_len..mv_len + len].copy_from_slice(&out[..len]);
+
+        self.cng(&ctrl, &mut out[..len]);
+        self.plc_glue_frames(&mut out[..len]);
+        self.lag_prev = ctrl.pitch_l[self.nb_subfr - 1];
+        len
+    }
+}
+
+/// Pulse amplitudes and signs of the excitation, 16 samples per shell block.
+fn decode_pulses(dec: &mut RangeDecoder, pulses: &mut [i16], signal_type: i8, quant_offset_type: i8, len: usize) {
+    const MAX_SHELL_BLOCKS: usize = MAX_FRAME_LENGTH / SHELL_CODEC_FRAME_LENGTH;
+    let rate_level = dec.icdf(&RATE_LEVELS_ICDF[signal_type as usize >> 1], 8);
+    let blocks = len.div_ceil(SHELL_CODEC_FRAME_LENGTH);
+
+    let mut sum_pulses = [0usize; MAX_SHELL_BLOCKS];
+    let mut n_lshifts = [0usize; MAX_SHELL_BLOCKS];
+    for i in 0..blocks {
+        sum_pulses[i] = dec.icdf(&PULSES_PER_BLOCK_ICDF[rate_level], 8);
+        while sum_pulses[i] == 17 {
+            n_lshifts[i] += 1;
+            let skip = usize::from(n_lshifts[i] == 10);
+            sum_pulses[i] = dec.icdf(&PULSES_PER_BLOCK_ICDF[9][skip..], 8);
+        }
+    }
+
+    for i in 0..blocks {
+        let block = &mut pulses[i * SHELL_CODEC_FRAME_LENGTH..(i + 1) * SHELL_CODEC_FRAME_LENGTH];
+        if sum_pulses[i] > 0 {
+            shell_decode(block, dec, sum_pulses[i]);
+        } else {
+            block.fill(0);
+        }
+    }
+
+    for i in 0..blocks {
+        let n_ls = n_lshifts[i];
+        if n_ls > 0 {
+            for p in &mut pulses[i * SHELL_CODEC_FRAME_LENGTH..(i + 1) * SHELL_CODEC_FRAME_LENGTH] {
+                let mut abs_q = i32::from(*p);
+                for _ in 0..n_ls {
+                    abs_q = (abs_q << 1) + dec.icdf(&LSB_ICDF, 8) as i32;
+                }
+                *p = abs_q as i16;
+            }
+            sum_pulses[i] |= n_ls << 5;
+        }
+    }
+
+    // Signs.
+    let sign_icdf = &SIGN_ICDF[7 * (quant_offset_type as usize + 2 * signal_type as usize)..];
+    let sign_blocks = (len + SHELL_CODEC_FRAME_LENGTH / 2) / SHELL_CODEC_FRAME_LENGTH;
+    for i in 0..sign_blocks {
+        let p = sum_pulses[i];
+        if p > 0 {
+            let icdf = [sign_icdf[(p & 0x1f).min(6)], 0];
+            for q in &mut pulses[i * SHELL_CODEC_FRAME_LENGTH..(i + 1) * SHELL_CODEC_FRAME_LENGTH] {
+                if *q > 0 {
+                    *q *= 2 * dec.icdf(&icdf, 8) as i16 - 1;
+                }
+            }
+        }
+    }
+}
+
+fn decode_split(dec: &mut RangeDecoder, p: usize, table: &[u8]) -> (usize, usize) {
+    if p > 0 {
+        let c1 = dec.icdf(&table[usize::from(SHELL_CODE_TABLE_OFFSETS[p])..], 8);
+        (c1, p - c1)
+    } else {
+        (0, 0)
+    }
+}
+
+/// Splits a block's pulse count down a binary tree to its 16 samples.
+fn shell_decode(out: &mut [i16], dec: &mut RangeDecoder, pulses4: usize) {
+    let mut p3 = [0; 2];
+    let mut p2 = [0; 4];
+    let mut p1 = [0; 8];
+    (p3[0], p3[1]) = decode_split(dec, pulses4, &SHELL_CODE_TABLE3);
+    for (i, &p) in p3.iter().enumerate() {
+        (p2[2 * i], p2[2 * i + 1]) = decode_split(dec, p, &SHELL_CODE_TABLE2);
+        for j in 2 * i..2 * i + 2 {
+            (p1[2 * j], p1[2 * j + 1]) = decode_split(dec, p2[j], &SHELL_CODE_TABLE1);
+            for m in 2 * j..2 * j + 2 {
+                let (a, b) = decode_split(dec, p1[m], &SHELL_CODE_TABLE0);
+                out[2 * m] = a as i16;
+                out[2 * m + 1] = b as i16;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct StereoState {
+    pred_prev_q13: [i16; 2],
+    s_mid: [i16; 2],
+    s_side: [i16; 2],
+}
+
+fn stereo_decode_pred(dec: &mut RangeDecoder) -> [i32; 2] {
+    let n = dec.icdf(&STEREO_PRED_JOINT_ICDF, 8);
+    let mut ix = [[0usize; 3]; 2];
+    ix[0][2] = n / 5;
+    ix[1][2] = n - 5 * ix[0][2];
+    for row in &mut ix {
+        row[0] = dec.icdf(&UNIFORM3_ICDF, 8);
+        row[1] = dec.icdf(&UNIFORM5_ICDF, 8);
+    }
+    let mut pred_q13 = [0; 2];
+    for (pred, row) in pred_q13.iter_mut().zip(&ix) {
+        let i = row[0] + 3 * row[2];
+        let low_q13 = i32::from(STEREO_PRED_QUANT_Q13[i]);
+        let step_q13 = smulwb(i32::from(STEREO_PRED_QUANT_Q13[i + 1]) - low_q13, 6554);
+        *pred = smlabb(low_q13, step_q13, 2 * row[1] as i32 + 1);
+    }
+    pred_q13[0] -= pred_q13[1];
+    pred_q13
+}
+
+/// Converts the mid/side pair (each with two samples of history in front)
+/// to left/right in place.
+fn stereo_ms_to_lr(state: &mut StereoState, x1: &mut [i16], x2: &mut [i16], pred_q13: [i32; 2], fs_khz: usize, len: usize) {
+    x1[..2].copy_from_slice(&state.s_mid);
+    x2[..2].copy_from_slice(&state.s_side);
+    state.s_mid.copy_from_slice(&x1[len..len + 2]);
+    state.s_side.copy_from_slice(&x2[len..len + 2]);
+
+    let mut pred0 = i32::from(state.pred_prev_q13[0]);
+    let mut pred1 = i32::from(state.pred_prev_q13[1]);
+    let interp_len = 8 * fs_khz;
+    let denom_q16 = (1 << 16) / interp_len as i32;
+    let delta0 = rshift_round(smulbb(pred_q13[0] - pred0, denom_q16), 16);
+    let delta1 = rshift_round(smulbb(pred_q13[1] - pred1, denom_q16), 16);
+    for n in 0..len {
+        if n < interp_len {
+            pred0 += delta0;
+            pred1 += delta1;
+        } else if n == interp_len {
+            (pred0, pred1) = (pred_q13[0], pred_q13[1]);
+        }
+        let sum = (i32::from(x1[n]) + i32::from(x1[n + 2]) + (i32::from(x1[n + 1]) << 1)) << 9;
+        let sum = smlawb(i32::from(x2[n + 1]) << 8, sum, pred0);
+        let sum = smlawb(sum, i32::from(x1[n + 1]) << 11, pred1);
+        x2[n + 1] = sat16(rshift_round(sum, 8));
+    }
+    state.pred_prev_q13 = [pred_q13[0] as i16, pred_q13[1] as i16];
+
+    for n in 1..=len {
+        let (m, s) = (i32::from(x1[n]), i32::from(x2[n]));
+        x1[n] = sat16(m + s);
+        x2[n] = sat16(m - s);
+    }
+}
+
+/// Stream parameters for one call, from the Opus layer.
+pub(super) struct SilkParams {
+    /// Duration of the SILK payload (10, 20, 40 or 60 ms).
+    pub(super) payload_ms: usize,
+    pub(super) internal_khz: usize,
+    pub(super) channels_internal: usize,
+    pub(super) channels_api: usize,
+}
+
+/// The SILK layer of one Opus stream: up to two channels plus the stereo
+/// unmixing state.
+#[derive(Clone)]
+pub(super) struct SilkDecoder {
+    channels: [ChannelState; 2],
+    stereo: StereoState,
+    n_channels_api: usize,
+    n_channels_internal: usize,
+    prev_decode_only_middle: bool,
+}
+
+impl SilkDecoder {
+    pub(super) fn new() -> Self {
+        SilkDecoder {
+            channels: [ChannelState::new(), ChannelState::new()],
+            stereo: StereoState::default(),
+            n_channels_api: 0,
+            n_channels_internal: 0,
+            prev_decode_only_middle: false,
+        }
+    }
+
+    /// Back to the just-initialized state (the channel counts survive, as
+    /// they do in the reference).
+    pub(super) fn reset(&mut self) {
+        self.channels = [ChannelState::new(), ChannelState::new()];
+        self.stereo = StereoState::default();
+        self.prev_decode_only_middle = false;
+    }
+
+    /// Decodes one SILK frame (10 or 20 ms) to 48 kHz, interleaved by
+    /// `channels_api`, returning the samples written per channel. Without a
+    /// range decoder the frame is concealed.
+    pub(super) fn decode(
+        &mut self,
+        mut dec: Option<&mut RangeDecoder>,
+        new_packet: bool,
+        params: &SilkParams,
+        out: &mut [i16],
+    ) -> usize {
+        let internal = params.channels_internal;
+        let api = params.channels_api;
+        let lost = dec.is_none();
+        let mut decode_only_middle = false;
+        let mut ms_pred_q13 = [0; 2];
+
+        if new_packet {
+            for ch in &mut self.channels[..internal] {
+                ch.n_frames_decoded = 0;
+            }
+        }
+        if internal > self.n_channels_internal {
+            self.channels[1] = ChannelState::new();
+        }
+        let stereo_to_mono =
+            internal == 1 && self.n_channels_internal == 2 && params.internal_khz == self.channels[0].fs_khz;
+
+        if self.channels[0].n_frames_decoded == 0 {
+            for ch in &mut self.channels[..internal] {
+                (ch.n_frames_per_packet, ch.nb_subfr) = match params.payload_ms {
+                    20 => (1, 4),
+                    40 => (2, 4),
+                    60 => (3, 4),
+                    _ => (1, 2),
+                };
+                ch.set_fs(params.internal_khz);
+            }
+        }
+        if api == 2 && internal == 2 && (self.n_channels_api == 1 || self.n_channels_internal == 1) {
+            self.stereo.pred_prev_q13 = [0; 2];
+            self.stereo.s_side = [0; 2];
+            self.channels[1].resampler = self.channels[0].resampler.clone();
+        }
+        self.n_channels_api = api;
+        self.n_channels_internal = internal;
+
+        if let Some(dec) = dec.as_deref_mut() {
+            if self.channels[0].n_frames_decoded == 0 {
+                self.read_flags_and_skip_lbrr(dec, internal);
+            }
+        }
+
+        if internal == 2 {
+            if let Some(dec) = dec.as_deref_mut() {
+                ms_pred_q13 = stereo_decode_pred(dec);
+                let frame = self.channels[0].n_frames_decoded;
+                decode_only_middle =
+                    !self.channels[1].vad_flags[frame] && dec.icdf(&STEREO_ONLY_CODE_MID_ICDF, 8) != 0;
+            } else {
+                ms_pred_q13 = self.stereo.pred_prev_q13.map(i32::from);
+            }
+        }
+
+        if internal == 2 && !decode_only_middle && self.prev_decode_only_middle {
+            let side = &mut self.channels[1];
+            side.out_buf.fill(0);
+            side.s_lpc_q14_buf.fill(0);
+            side.lag_prev = 100;
+            side.last_gain_index = 10;
+            side.prev_signal_type = TYPE_NO_VOICE_ACTIVITY;
+            side.first_frame_after_reset = true;
+        }
+
+        let has_side = if lost { !self.prev_decode_only_middle } else { !decode_only_middle };
+        let frame_length = self.channels[0].frame_length;
+        let mut bufs = [[0i16; MAX_FRAME_LENGTH + 2]; 2];
+        let mut n_out_dec = 0;
+        for (n, buf) in bufs.iter_mut().enumerate().take(internal) {
+            if n == 0 || has_side {
+                let frame_index = self.channels[0].n_frames_decoded as isize - n as isize;
+                let cond = if frame_index <= 0 {
+                    CondCoding::Independently
+                } else if n > 0 && self.prev_decode_only_middle {
+                    CondCoding::IndependentlyNoLtpScaling
+                } else {
+                    CondCoding::Conditionally
+                };
+                n_out_dec = self.channels[n].decode_frame(dec.as_deref_mut(), &mut buf[2..], cond);
+            } else {
+                buf[2..2 + n_out_dec].fill(0);
+            }
+            self.channels[n].n_frames_decoded += 1;
+        }
+        debug_assert_eq!(n_out_dec, frame_length);
+
+        let [mid, side] = &mut bufs;
+        if api == 2 && internal == 2 {
+            stereo_ms_to_lr(&mut self.stereo, mid, side, ms_pred_q13, self.channels[0].fs_khz, n_out_dec);
+        } else {
+            mid[..2].copy_from_slice(&self.stereo.s_mid);
+            self.stereo.s_mid.copy_from_slice(&mid[n_out_dec..n_out_dec + 2]);
+        }
+
+        let n_out = n_out_dec * 48 / self.channels[0].fs_khz;
+        let mut resampled = [0i16; 6 * MAX_FRAME_LENGTH];
+        for n in 0..api.min(internal) {
+            let src = if n == 0 { &*mid } else { &*side };
+            self.channels[n].resampler.process(&mut resampled[..n_out], &src[1..1 + n_out_dec]);
+            for (i, &s) in resampled[..n_out].iter().enumerate() {
+                out[n + api * i] = s;
+            }
+        }
+        if api == 2 && internal == 1 {
+            if stereo_to_mono {
+                self.channels[1].resampler.process(&mut resampled[..n_out], &mid[1..1 + n_out_dec]);
+                for (i, &s) in resampled[..n_out].iter().enumerate() {
+                    out[1 + 2 * i] = s;
+                }
+            } else {
+                for i in 0..n_out {
+                    out[1 + 2 * i] = out[2 * i];
+                }
+            }
+        }
+
+        if lost {
+            for ch in &mut self.channels[..self.n_channels_internal] {
+                ch.last_gain_index = 10;
+            }
+        } else {
+            self.prev_decode_only_middle = decode_only_middle;
+        }
+        n_out
+    }
+
+    /// Reads the per-packet VAD and LBRR flags, then steps over the LBRR
+    /// (forward error correction) frames, which only matter when recovering
+    /// a lost packet.
+    fn read_flags_and_skip_lbrr(&mut self, dec: &mut RangeDecoder, internal: usize) {
+        let mut lbrr = [false; 2];
+        for (ch, lbrr) in self.channels[..internal].iter_mut().zip(&mut lbrr) {
+            for flag in &mut ch.vad_flags[..ch.n_frames_per_packet] {
+                *flag = dec.bit_logp(1);
+            }
+            *lbrr = dec.bit_logp(1);
+        }
+        for (ch, &lbrr) in self.channels[..internal].iter_mut().zip(&lbrr) {
+            ch.lbrr_flags = [false; MAX_FRAMES_PER_PACKET];
+            if lbrr {
+                if ch.n_frames_per_packet == 1 {
+                    ch.lbrr_flags[0] = true;
+                } else {
+                    let icdf: &[u8] =
+                        if ch.n_frames_per_packet == 2 { &LBRR_FLAGS_2_ICDF } else { &LBRR_FLAGS_3_ICDF };
+                    let symbol = dec.icdf(icdf, 8) + 1;
+                    for i in 0..ch.n_frames_per_packet {
+                        ch.lbrr_flags[i] = (symbol >> i) & 1 != 0;
+                    }
+                }
+            }
+        }
+
+        for i in 0..self.channels[0].n_frames_per_packet {
+            for n in 0..internal {
+                if self.channels[n].lbrr_flags[i] {
+                    if internal == 2 && n == 0 {
+                        stereo_decode_pred(dec);
+                        if !self.channels[1].lbrr_flags[i] {
+                            dec.icdf(&STEREO_ONLY_CODE_MID_ICDF, 8);
+                        }
+                    }
+                    let ch = &mut self.channels[n];
+                    let cond = if i > 0 && ch.lbrr_flags[i - 1] {
+                        CondCoding::Conditionally
+                    } else {
+                        CondCoding::Independently
+                    };
+                    ch.decode_indices(dec, i, true, cond);
+                    let mut pulses = [0i16; MAX_FRAME_LENGTH];
+                    let ind = ch.indices;
+                    decode_pulses(dec, &mut pulses, ind.signal_type, ind.quant_offset_type, ch.frame_length);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,396 @@
+// SILK loss concealment and comfort noise (RFC 6716 §4.4)
+//
+// A lost frame is rebuilt from the last good one: its pitch pulse train,
+// fading out, plus noise drawn from the last excitation, through the last
+// LPC filter. Comfort noise fills the gap with noise shaped like the recent
+// background, and the first good frame after a loss is faded in from the
+// concealed level.
+
+use super::fixed::{
+    add_sat32, inverse32_varq, lshift_sat32, rand, rshift_round, sat16, smlawb, smulbb, smultt,
+    smulwb, smulww, sqrt_approx, sum_sqr_shift,
+};
+use super::lpc::{self, MAX_LPC_ORDER};
+use super::silk::{ChannelState, Control, LTP_ORDER, MAX_FRAME_LENGTH, TYPE_NO_VOICE_ACTIVITY, TYPE_VOICED};
+use super::silk_tables::{PLC_HARM_ATT_Q15, PLC_RAND_ATTENUATE_UV_Q15, PLC_RAND_ATTENUATE_V_Q15};
+
+const BWE_COEF_Q16: i32 = 64881;
+const V_PITCH_GAIN_START_MIN_Q14: i32 = 11469;
+const V_PITCH_GAIN_START_MAX_Q14: i32 = 15565;
+const MAX_PITCH_LAG_MS: i32 = 18;
+const RAND_BUF_SIZE: usize = 128;
+const PITCH_DRIFT_FAC_Q16: i32 = 655;
+const LOG2_INV_LPC_GAIN_HIGH_THRES: u32 = 3;
+const LOG2_INV_LPC_GAIN_LOW_THRES: u32 = 8;
+
+const CNG_BUF_MASK_MAX: usize = 255;
+const CNG_GAIN_SMTH_Q16: i32 = 4634;
+const CNG_GAIN_SMTH_THRESHOLD_Q16: i32 = 46396;
+const CNG_NLSF_SMTH_Q16: i32 = 16348;
+
+#[derive(Clone)]
+pub(super) struct Plc {
+    pitch_l_q8: i32,
+    ltp_coef_q14: [i16; LTP_ORDER],
+    prev_lpc_q12: [i16; MAX_LPC_ORDER],
+    last_frame_lost: bool,
+    rand_seed: i32,
+    rand_scale_q14: i16,
+    conc_energy: i32,
+    conc_energy_shift: i32,
+    prev_ltp_scale_q14: i32,
+    prev_gain_q16: [i32; 2],
+    fs_khz: usize,
+    nb_subfr: usize,
+    subfr_length: usize,
+}
+
+impl Plc {
+    pub(super) fn new() -> Self {
+        Plc {
+            pitch_l_q8: 0,
+            ltp_coef_q14: [0; LTP_ORDER],
+            prev_lpc_q12: [0; MAX_LPC_ORDER],
+            last_frame_lost: false,
+            rand_seed: 0,
+            rand_scale_q14: 0,
+            conc_energy: 0,
+            conc_energy_shift: 0,
+            prev_ltp_scale_q14: 0,
+            prev_gain_q16: [1 << 16; 2],
+            fs_khz: 0,
+            nb_subfr: 2,
+            subfr_length: 20,
+        }
+    }
+
+    fn reset(&mut self, frame_length: usize) {
+        self.pitch_l_q8 = (frame_length as i32) << 7;
+        self.prev_gain_q16 = [1 << 16; 2];
+        self.subfr_length = 20;
+        self.nb_subfr = 2;
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct Cng {
+    exc_buf_q14: [i32; MAX_FRAME_LENGTH],
+    smth_nlsf_q15: [i16; MAX_LPC_ORDER],
+    synth_state: [i32; MAX_LPC_ORDER],
+    smth_gain_q16: i32,
+    rand_seed: i32,
+    fs_khz: usize,
+}
+
+impl Cng {
+    pub(super) fn new() -> Self {
+        Cng {
+            exc_buf_q14: [0; MAX_FRAME_LENGTH],
+            smth_nlsf_q15: [0; MAX_LPC_ORDER],
+            synth_state: [0; MAX_LPC_ORDER],
+            smth_gain_q16: 0,
+            rand_seed: 3_176_576,
+            fs_khz: 0,
+        }
+    }
+
+    fn reset(&mut self, order: usize) {
+        let step = i32::from(i16::MAX) / (order as i32 + 1);
+        for (i, nlsf) in self.smth_nlsf_q15[..order].iter_mut().enumerate() {
+            *nlsf = (step * (i as i32 + 1)) as i16;
+        }
+        self.smth_gain_q16 = 0;
+        self.rand_seed = 3_176_576;
+    }
+}
+
+/// One step of the short-term synthesis filter over `sig[16..]`, whose first
+/// 16 entries hold the filter state.
+fn lpc_synthesis_step(sig: &[i32], i: usize, a_q12: &[i16]) -> i32 {
+    let mut pred_q10 = (a_q12.len() >> 1) as i32;
+    for (j, &a) in a_q12.iter().enumerate() {
+        pred_q10 = smlawb(pred_q10, sig[MAX_LPC_ORDER + i - j - 1], i32::from(a));
+    }
+    add_sat32(sig[MAX_LPC_ORDER + i], lshift_sat32(pred_q10, 4))
+}
+
+impl ChannelState {
+    /// Records a good frame for later concealment, or conceals a lost one
+    /// into `frame`.
+    pub(super) fn plc(&mut self, ctrl: &mut Control, frame: &mut [i16], lost: bool) {
+        if self.fs_khz != self.plc.fs_khz {
+            self.plc.reset(self.frame_length);
+            self.plc.fs_khz = self.fs_khz;
+        }
+        if lost {
+            self.plc_conceal(ctrl, frame);
+            self.loss_cnt += 1;
+        } else {
+            self.plc_update(ctrl);
+        }
+    }
+
+    fn plc_update(&mut self, ctrl: &Control) {
+        let nb = self.nb_subfr;
+        let plc = &mut self.plc;
+        self.prev_signal_type = i32::from(self.indices.signal_type);
+        if self.prev_signal_type == TYPE_VOICED {
+            // The strongest LTP filter within the last pitch period.
+            let mut ltp_gain_q14 = 0;
+            let mut j = 0;
+            while j * (self.subfr_length as i32) < ctrl.pitch_l[nb - 1] && (j as usize) < nb {
+                let at = (nb - 1 - j as usize) * LTP_ORDER;
+                let taps = &ctrl.ltp_coef_q14[at..at + LTP_ORDER];
+                let gain: i32 = taps.iter().map(|&b| i32::from(b)).sum();
+                if gain > ltp_gain_q14 {
+                    ltp_gain_q14 = gain;
+                    plc.ltp_coef_q14.copy_from_slice(taps);
+                    plc.pitch_l_q8 = ctrl.pitch_l[nb - 1 - j as usize] << 8;
+                }
+                j += 1;
+            }
+            plc.ltp_coef_q14 = [0; LTP_ORDER];
+            plc.ltp_coef_q14[LTP_ORDER / 2] = ltp_gain_q14 as i16;
+
+            if ltp_gain_q14 < V_PITCH_GAIN_START_MIN_Q14 {
+                let scale_q10 = (V_PITCH_GAIN_START_MIN_Q14 << 10) / ltp_gain_q14.max(1);
+                for b in &mut plc.ltp_coef_q14 {
+                    *b = (smulbb(i32::from(*b), scale_q10) >> 10) as i16;
+                }
+            } else if ltp_gain_q14 > V_PITCH_GAIN_START_MAX_Q14 {
+                let scale_q14 = (V_PITCH_GAIN_START_MAX_Q14 << 14) / ltp_gain_q14.max(1);
+                for b in &mut plc.ltp_coef_q14 {
+                    *b = (smulbb(i32::from(*b), scale_q14) >> 14) as i16;
+                }
+            }
+        } else {
+            plc.pitch_l_q8 = (self.fs_khz as i32 * 18) << 8;
+            plc.ltp_coef_q14 = [0; LTP_ORDER];
+        }
+
+        let order = self.lpc_order;
+        plc.prev_lpc_q12[..order].copy_from_slice(&ctrl.pred_coef_q12[1][..order]);
+        plc.prev_ltp_scale_q14 = ctrl.ltp_scale_q14;
+        plc.prev_gain_q16.copy_from_slice(&ctrl.gains_q16[nb - 2..nb]);
+        plc.subfr_length = self.subfr_length;
+        plc.nb_subfr = nb;
+    }
+
+    fn plc_conceal(&mut self, ctrl: &mut Control, frame: &mut [i16]) {
+        let order = self.lpc_order;
+        let ltp_mem = self.ltp_mem_length;
+        let subfr = self.subfr_length;
+        let mut s_ltp = [0i16; 2 * MAX_FRAME_LENGTH];
+        let mut s_ltp_q14 = [0i32; 2 * MAX_FRAME_LENGTH];
+        let plc = &mut self.plc;
+
+        let prev_gain_q10 = plc.prev_gain_q16.map(|g| g >> 6);
+        if self.first_frame_after_reset {
+            plc.prev_lpc_q12 = [0; MAX_LPC_ORDER];
+        }
+
+        // Draw the noise from whichever of the last two subframes is quieter.
+        let mut exc_buf = [0i16; 2 * MAX_FRAME_LENGTH / 4];
+        for k in 0..2 {
+            for i in 0..subfr {
+                let exc = self.exc_q14[i + (k + self.nb_subfr - 2) * subfr];
+                exc_buf[k * subfr + i] = sat16(smulww(exc, prev_gain_q10[k]) >> 8);
+            }
+        }
+        let (energy1, shift1) = sum_sqr_shift(&exc_buf[..subfr]);
+        let (energy2, shift2) = sum_sqr_shift(&exc_buf[subfr..2 * subfr]);
+        let rand_start = if energy1 >> shift2 < energy2 >> shift1 {
+            ((plc.nb_subfr - 1) * plc.subfr_length).saturating_sub(RAND_BUF_SIZE)
+        } else {
+            (plc.nb_subfr * plc.subfr_length).saturating_sub(RAND_BUF_SIZE)
+        };
+        let rand_buf = &self.exc_q14[rand_start..rand_start + RAND_BUF_SIZE];
+
+        let b_q14 = &mut plc.ltp_coef_q14;
+        let mut rand_scale_q14 = plc.rand_scale_q14;
+        let att = (self.loss_cnt as usize).min(1);
+        let harm_gain_q15 = i32::from(PLC_HARM_ATT_Q15[att]);
+        let mut rand_gain_q15 = i32::from(if self.prev_signal_type == TYPE_VOICED {
+            PLC_RAND_ATTENUATE_V_Q15[att]
+        } else {
+            PLC_RAND_ATTENUATE_UV_Q15[att]
+        });
+
+        lpc::bwexpander(&mut plc.prev_lpc_q12[..order], BWE_COEF_Q16);
+        let a_q12 = plc.prev_lpc_q12;
+
+        if self.loss_cnt == 0 {
+            rand_scale_q14 = 1 << 14;
+            if self.prev_signal_type == TYPE_VOICED {
+                for &b in b_q14.iter() {
+                    rand_scale_q14 = rand_scale_q14.wrapping_sub(b);
+                }
+                rand_scale_q14 = rand_scale_q14.max(3277);
+                rand_scale_q14 = (smulbb(i32::from(rand_scale_q14), plc.prev_ltp_scale_q14) >> 14) as i16;
+            } else {
+                let inv_gain_q30 = lpc::inverse_pred_gain(&a_q12[..order]);
+                let down_scale_q30 = inv_gain_q30
+                    .clamp((1 << 30) >> LOG2_INV_LPC_GAIN_LOW_THRES, (1 << 30) >> LOG2_INV_LPC_GAIN_HIGH_THRES)
+                    << LOG2_INV_LPC_GAIN_HIGH_THRES;
+                rand_gain_q15 = smulwb(down_scale_q30, rand_gain_q15) >> 14;
+            }
+        }
+
+        let mut rand_seed = plc.rand_seed;
+        let mut lag = rshift_round(plc.pitch_l_q8, 8) as usize;
+        let mut buf_idx = ltp_mem;
+
+        // Whiten the past output, then rescale it to the last gain.
+        let idx = ltp_mem - lag - order - LTP_ORDER / 2;
+        lpc::analysis_filter(&mut s_ltp[idx..ltp_mem], &self.out_buf[idx..ltp_mem], &a_q12[..order]);
+        let inv_gain_q30 = inverse32_varq(plc.prev_gain_q16[1], 46).min(i32::MAX >> 1);
+        for i in idx + order..ltp_mem {
+            s_ltp_q14[i] = smulwb(inv_gain_q30, i32::from(s_ltp[i]));
+        }
+
+        // Long-term synthesis with fading harmonics and noise.
+        for _ in 0..self.nb_subfr {
+            for _ in 0..subfr {
+                let at = buf_idx - lag + LTP_ORDER / 2;
+                let mut ltp_pred_q12 = 2;
+                for (j, &b) in b_q14.iter().enumerate() {
+                    ltp_pred_q12 = smlawb(ltp_pred_q12, s_ltp_q14[at - j], i32::from(b));
+                }
+                rand_seed = rand(rand_seed);
+                let ridx = ((rand_seed >> 25) & (RAND_BUF_SIZE as i32 - 1)) as usize;
+                s_ltp_q14[buf_idx] = smlawb(ltp_pred_q12, rand_buf[ridx], i32::from(rand_scale_q14)) << 2;
+                buf_idx += 1;
+            }
+            for b in b_q14.iter_mut() {
+                *b = (smulbb(harm_gain_q15, i32::from(*b)) >> 15) as i16;
+            }
+            rand_scale_q14 = (smulbb(i32::from(rand_scale_q14), rand_gain_q15) >> 15) as i16;
+            plc.pitch_l_q8 = smlawb(plc.pitch_l_q8, plc.pitch_l_q8, PITCH_DRIFT_FAC_Q16)
+                .min((MAX_PITCH_LAG_MS * self.fs_khz as i32) << 8);
+            lag = rshift_round(plc.pitch_l_q8, 8) as usize;
+        }
+
+        // Short-term synthesis in place, continuing the last frame's state.
+        let s_lpc_q14 = &mut s_ltp_q14[ltp_mem - MAX_LPC_ORDER..];
+        s_lpc_q14[..MAX_LPC_ORDER].copy_from_slice(&self.s_lpc_q14_buf);
+        for i in 0..self.frame_length {
+            let s = lpc_synthesis_step(s_lpc_q14, i, &a_q12[..order]);
+            s_lpc_q14[MAX_LPC_ORDER + i] = s;
+            frame[i] = sat16(rshift_round(smulww(s, prev_gain_q10[1]), 8));
+        }
+        self.s_lpc_q14_buf
+            .copy_from_slice(&s_lpc_q14[self.frame_length..self.frame_length + MAX_LPC_ORDER]);
+
+        plc.rand_seed = rand_seed;
+        plc.rand_scale_q14 = rand_scale_q14;
+        ctrl.pitch_l = [lag as i32; 4];
+    }
+
+    /// Fades a good frame in from the level of the concealment before it.
+    pub(super) fn plc_glue_frames(&mut self, frame: &mut [i16]) {
+        let plc = &mut self.plc;
+        if self.loss_cnt != 0 {
+            (plc.conc_energy, plc.conc_energy_shift) = sum_sqr_shift(frame);
+            plc.last_frame_lost = true;
+            return;
+        }
+        if plc.last_frame_lost {
+            let (mut energy, energy_shift) = sum_sqr_shift(frame);
+            if energy_shift > plc.conc_energy_shift {
+                plc.conc_energy >>= energy_shift - plc.conc_energy_shift;
+            } else if energy_shift < plc.conc_energy_shift {
+                energy >>= plc.conc_energy_shift - energy_shift;
+            }
+            if energy > plc.conc_energy {
+                let lz = plc.conc_energy.leading_zeros() as i32 - 1;
+                plc.conc_energy <<= lz;
+                energy >>= (24 - lz).max(0);
+                let frac_q24 = plc.conc_energy / energy.max(1);
+                let mut gain_q16 = sqrt_approx(frac_q24) << 4;
+                let slope_q16 = ((1 << 16) - gain_q16) / frame.len() as i32;
+                let slope_q16 = slope_q16 << 2;
+                for s in frame.iter_mut() {
+                    *s = smulwb(gain_q16, i32::from(*s)) as i16;
+                    gain_q16 += slope_q16;
+                    if gain_q16 > 1 << 16 {
+                        break;
+                    }
+                }
+            }
+        }
+        plc.last_frame_lost = false;
+    }
+
+    /// Tracks the background during unvoiced frames and adds comfort noise
+    /// to concealed ones.
+    pub(super) fn cng(&mut self, ctrl: &Control, frame: &mut [i16]) {
+        let order = self.lpc_order;
+        let subfr = self.subfr_length;
+        let cng = &mut self.cng;
+        if self.fs_khz != cng.fs_khz {
+            cng.reset(order);
+            cng.fs_khz = self.fs_khz;
+        }
+        if self.loss_cnt == 0 && self.prev_signal_type == TYPE_NO_VOICE_ACTIVITY {
+            for (smth, &prev) in cng.smth_nlsf_q15[..order].iter_mut().zip(&self.prev_nlsf_q15) {
+                *smth = smth.wrapping_add(smulwb(i32::from(prev) - i32::from(*smth), CNG_NLSF_SMTH_Q16) as i16);
+            }
+            let mut max_gain_q16 = 0;
+            let mut loudest = 0;
+            for (i, &g) in ctrl.gains_q16[..self.nb_subfr].iter().enumerate() {
+                if g > max_gain_q16 {
+                    max_gain_q16 = g;
+                    loudest = i;
+                }
+            }
+            cng.exc_buf_q14.copy_within(0..(self.nb_subfr - 1) * subfr, subfr);
+            cng.exc_buf_q14[..subfr].copy_from_slice(&self.exc_q14[loudest * subfr..(loudest + 1) * subfr]);
+            for &g in &ctrl.gains_q16[..self.nb_subfr] {
+                cng.smth_gain_q16 += smulwb(g - cng.smth_gain_q16, CNG_GAIN_SMTH_Q16);
+                if smulww(cng.smth_gain_q16, CNG_GAIN_SMTH_THRESHOLD_Q16) > g {
+                    cng.smth_gain_q16 = g;
+                }
+            }
+        }
+
+        if self.loss_cnt == 0 {
+            cng.synth_state[..order].fill(0);
+            return;
+        }
+        let length = frame.len();
+        let mut gain_q16 = smulww(i32::from(self.plc.rand_scale_q14), self.plc.prev_gain_q16[1]);
+        if gain_q16 >= 1 << 21 || cng.smth_gain_q16 > 1 << 23 {
+            gain_q16 = smultt(gain_q16, gain_q16);
+            gain_q16 = smultt(cng.smth_gain_q16, cng.smth_gain_q16).wrapping_sub(gain_q16.wrapping_shl(5));
+            gain_q16 = sqrt_approx(gain_q16) << 16;
+        } else {
+            gain_q16 = smulww(gain_q16, gain_q16);
+            gain_q16 = smulww(cng.smth_gain_q16, cng.smth_gain_q16).wrapping_sub(gain_q16.wrapping_shl(5));
+            gain_q16 = sqrt_approx(gain_q16) << 8;
+        }
+        let gain_q10 = gain_q16 >> 6;
+
+        let mut sig_q14 = [0i32; MAX_FRAME_LENGTH + MAX_LPC_ORDER];
+        let mut mask = CNG_BUF_MASK_MAX;
+        while mask > length {
+            mask >>= 1;
+        }
+        let mut seed = cng.rand_seed;
+        for s in &mut sig_q14[MAX_LPC_ORDER..MAX_LPC_ORDER + length] {
+            seed = rand(seed);
+            *s = cng.exc_buf_q14[(seed >> 24) as usize & mask];
+        }
+        cng.rand_seed = seed;
+
+        let mut a_q12 = [0i16; MAX_LPC_ORDER];
+        lpc::nlsf2a(&mut a_q12[..order], &cng.smth_nlsf_q15[..order]);
+        sig_q14[..MAX_LPC_ORDER].copy_from_slice(&cng.synth_state);
+        for i in 0..length {
+            let s = lpc_synthesis_step(&sig_q14, i, &a_q12[..order]);
+            sig_q14[MAX_LPC_ORDER + i] = s;
+            let noise = sat16(rshift_round(smulww(s, gain_q10), 8));
+            frame[i] = frame[i].saturating_add(noise);
+        }
+        cng.synth_state.copy_from_slice(&sig_q14[length..length + MAX_LPC_ORDER]);
+    }
+}
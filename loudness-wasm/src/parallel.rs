@@ -0,0 +1,52 @@
+// Data-parallel helpers
+//
+// With the `threads` feature work is spread over rayon's pool (web workers on
+// wasm, initialised from JS via `initThreadPool(navigator.hardwareConcurrency)`);
+// without it the same closures run sequentially, so call sites stay identical.
+
+#[cfg(any(feature = "loudness", feature = "music"))]
+use std::ops::Range;
+
+#[cfg(all(feature = "threads", any(feature = "loudness", feature = "music")))]
+use rayon::prelude::*;
+
+/// Map `f` over an index range, in parallel when threads are available
+#[cfg(all(feature = "threads", any(feature = "loudness", feature = "music")))]
+pub fn map_range<R, F>(range: Range<usize>, f: F) -> Vec<R>
+where
+    R: Send,
+    F: Fn(usize) -> R + Sync + Send,
+{
+    range.into_par_iter().map(f).collect()
+}
+
+/// Map `f` over an index range, in parallel when threads are available
+#[cfg(all(not(feature = "threads"), any(feature = "loudness", feature = "music")))]
+pub fn map_range<R, F>(range: Range<usize>, f: F) -> Vec<R>
+where
+    F: Fn(usize) -> R,
+{
+    range.map(f).collect()
+}
+
+/// Run `main` on the calling thread and `side` on the pool alongside it;
+/// only `side` has to be `Send`, so `main` may hold a progress callback
+#[cfg(all(feature = "threads", feature = "loudness", feature = "stereo", feature = "technical"))]
+pub fn join<A, B, RA, RB>(main: A, side: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA,
+    B: FnOnce() -> RB + Send,
+    RB: Send,
+{
+    let mut side_result = None;
+    let main_result = rayon::in_place_scope(|scope| {
+        scope.spawn(|_| side_result = Some(side()));
+        main()
+    });
+    (main_result, side_result.expect("scoped task finished"))
+}
+
+/// Whether this build runs analysis on a thread pool
+pub fn threads_enabled() -> bool {
+    cfg!(feature = "threads")
+}
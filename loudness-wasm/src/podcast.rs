@@ -0,0 +1,380 @@
+// Podcast delivery check: the loudness, peak, silence and noise rules podcast
+// hosts publish (Apple/Spotify -16 LUFS stereo / -19 LUFS mono, -1 dBTP, ACX
+// -60 dBFS noise floor), each with an explicit pass/fail and the measured
+// figure, so an episode can be checked before upload. `analyze_podcast` is
+// the one-call preset: the rules plus speech-gated loudness, dead air,
+// sibilance and mouth clicks, folded into a fix list, most urgent first.
+//
+//     analyzer.set_include_podcast(true);
+//     const { podcast } = analyzer.analyze(pcm);
+//     podcast.rules.filter(rule => !rule.passed).forEach(rule => warn(rule.message));
+//
+//     const { fixes } = analyze_podcast(pcm, 48000, 1);
+//     fixes.forEach(fix => show(fix.priority, fix.message, fix.times));
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::AnalyzerConfig;
+use crate::error::{validate_pcm, AnalysisError};
+use crate::gating::{blockize, Gate};
+use crate::loudness::{LoudnessAnalyzer, LoudnessResult};
+use crate::technical::{TechnicalAnalyzer, TechnicalResult};
+use crate::utils::{amplitude_to_db, calculate_rms, mix_to_mono, trimmed_mean, Biquad, KWeighting, PeakPicker};
+
+// Integrated loudness targets (LUFS) and the tolerance around them (LU)
+pub const PODCAST_STEREO_TARGET: f32 = -16.0;
+pub const PODCAST_MONO_TARGET: f32 = -19.0;
+const LOUDNESS_TOLERANCE: f32 = 1.0;
+const TRUE_PEAK_CEILING: f32 = -1.0;
+// Dead air (seconds) listeners sit through before the first word and after the last
+const LEADING_SILENCE_LIMIT: f32 = 1.0;
+const TRAILING_SILENCE_LIMIT: f32 = 3.0;
+// Room tone / hiss level (dBFS) between phrases
+const NOISE_FLOOR_LIMIT: f32 = -60.0;
+// Noise floor windows: 50ms RMS, averaged over the quietest 20% of those not
+// digitally silent with a quarter trimmed from either end (so centred on the
+// 10th percentile, without one window's flicker or dropout setting it)
+const NOISE_WINDOW_SECONDS: f32 = 0.05;
+const NOISE_SHARE: f32 = 0.2;
+const NOISE_TRIM: f32 = 0.25;
+const DIGITAL_SILENCE: f32 = -120.0;
+
+// Speech gate: 400ms blocks at a 100ms hop count as speech when at least
+// half their energy lies in the 300Hz-3.4kHz voice band
+const SPEECH_BLOCK_SECONDS: f32 = 0.4;
+const SPEECH_HOP_SECONDS: f32 = 0.1;
+const SPEECH_LOW: f32 = 300.0;
+const SPEECH_HIGH: f32 = 3400.0;
+const SPEECH_BAND_SHARE: f32 = 0.5;
+// Speech more than this far (LU) from the whole programme means beds or
+// stings are mixed too hot or too low
+const SPEECH_BALANCE_TOLERANCE: f32 = 2.0;
+// Dead air: at least 2 s of 50ms windows under -50 dBFS between sounds
+const DEAD_AIR_LEVEL: f32 = -50.0;
+const DEAD_AIR_SECONDS: f32 = 2.0;
+// Sibilance: 10ms windows louder than -30 dBFS above 5kHz, with no more than
+// 3 dB between that band and the whole signal
+const SIBILANCE_WINDOW_SECONDS: f32 = 0.01;
+const SIBILANCE_FREQUENCY: f32 = 5000.0;
+const SIBILANCE_LEVEL: f32 = -30.0;
+const SIBILANCE_DOMINANCE: f32 = 3.0;
+// Mouth clicks: 1ms peaks of the sample-to-sample difference rising 15 dB
+// over the surrounding 50ms, above -50 dBFS and at least 50ms apart
+const CLICK_WINDOW_SECONDS: f32 = 0.001;
+const CLICKS: PeakPicker = PeakPicker { radius: 5, mean_radius: 25, delta: 15.0, floor: -50.0, min_distance: 50 };
+
+/// One delivery rule with its outcome
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct PodcastRule {
+    // Stable identifier, e.g. "true_peak"
+    pub rule: String,
+    pub passed: bool,
+    pub measured: f32,
+    // Ceiling the measurement is held to (the target, for loudness)
+    pub limit: f32,
+    pub message: String,
+}
+
+/// Every podcast rule; the episode passes when all of them do
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct PodcastCheck {
+    pub passes: bool,
+    // -16 LUFS for stereo, -19 LUFS for mono
+    pub target_loudness: f32,
+    pub rules: Vec<PodcastRule>,
+}
+
+impl PodcastCheck {
+    /// Judge the loudness and technical results of a `num_channels` episode
+    /// with the given noise floor (dBFS, see `noise_floor`)
+    pub fn evaluate(loudness: &LoudnessResult, technical: &TechnicalResult, num_channels: usize, noise_floor: f32) -> Self {
+        let target = if num_channels == 1 { PODCAST_MONO_TARGET } else { PODCAST_STEREO_TARGET };
+        let integrated = loudness.integrated;
+        let peak = technical.true_peak.level;
+        let (leading, trailing) = (technical.silence.leading_silence, technical.silence.trailing_silence);
+
+        let rules = vec![
+            rule("integrated_loudness", (integrated - target).abs() <= LOUDNESS_TOLERANCE, integrated, target,
+                format!("Integrated loudness {:.1} LUFS, target {:.0} ±{:.0} LU", integrated, target, LOUDNESS_TOLERANCE)),
+            rule("true_peak", peak <= TRUE_PEAK_CEILING, peak, TRUE_PEAK_CEILING,
+                format!("True peak {:.1} dBTP, ceiling {:.1} dBTP", peak, TRUE_PEAK_CEILING)),
+            rule("leading_silence", leading <= LEADING_SILENCE_LIMIT, leading, LEADING_SILENCE_LIMIT,
+                format!("{:.1} s of silence before the first sound, at most {:.0} s allowed", leading, LEADING_SILENCE_LIMIT)),
+            rule("trailing_silence", trailing <= TRAILING_SILENCE_LIMIT, trailing, TRAILING_SILENCE_LIMIT,
+                format!("{:.1} s of silence after the last sound, at most {:.0} s allowed", trailing, TRAILING_SILENCE_LIMIT)),
+            rule("noise_floor", noise_floor <= NOISE_FLOOR_LIMIT, noise_floor, NOISE_FLOOR_LIMIT,
+                format!("Noise floor {:.1} dBFS, at most {:.0} dBFS allowed", noise_floor, NOISE_FLOOR_LIMIT)),
+        ];
+        PodcastCheck { passes: rules.iter().all(|rule| rule.passed), target_loudness: target, rules }
+    }
+}
+
+/// How urgently a fix is needed: High for delivery rules hosts enforce,
+/// Medium for what listeners notice, Low for polish
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub enum FixPriority {
+    High,
+    Medium,
+    Low,
+}
+
+/// One thing to fix before upload; `times` (seconds) locates it where it
+/// happens at particular moments
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct PodcastFix {
+    pub priority: FixPriority,
+    // Stable identifier, e.g. "sibilance"
+    pub issue: String,
+    pub message: String,
+    pub times: Vec<f32>,
+}
+
+/// Everything `analyze_podcast` measures, and the fixes it suggests
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct PodcastReport {
+    pub check: PodcastCheck,
+    // BS.1770 loudness (LUFS) of the speech blocks alone, and
+    // its offset from the whole programme measured the same way (LU)
+    pub speech_loudness: f32,
+    pub speech_offset: f32,
+    // Share of 400ms blocks classed as speech
+    pub speech_share: f32,
+    pub noise_floor: f32,
+    // Start and length (seconds) of each stretch of dead air between sounds
+    pub dead_air: Vec<(f32, f32)>,
+    pub sibilance_times: Vec<f32>,
+    pub click_times: Vec<f32>,
+    pub fixes: Vec<PodcastFix>,
+}
+
+/// Podcast preset: the delivery rules, speech-gated loudness, dead air,
+/// noise floor, sibilance and mouth clicks of interleaved PCM in one call
+pub fn analyze_podcast(pcm: &[f32], sample_rate: f32, num_channels: usize) -> Result<PodcastReport, AnalysisError> {
+    validate_pcm(pcm, num_channels, (SPEECH_BLOCK_SECONDS * sample_rate) as usize)?;
+    let config = AnalyzerConfig::new(sample_rate, num_channels);
+    let loudness = LoudnessAnalyzer::from_config(&config).analyze(pcm, None)?;
+    let technical = TechnicalAnalyzer::from_config(&config).analyze_technical(pcm, loudness.integrated, None)?;
+    let noise_floor = noise_floor(pcm, num_channels, sample_rate);
+    let check = PodcastCheck::evaluate(&loudness, &technical, num_channels, noise_floor);
+
+    let (speech_loudness, programme_loudness, speech_share) = speech_gated_loudness(pcm, num_channels, sample_rate);
+    let speech_offset = speech_loudness - programme_loudness;
+    let mono = mix_to_mono(pcm, num_channels);
+    let dead_air = dead_air(&mono, sample_rate);
+    let sibilance_times = sibilance(&mono, sample_rate);
+    let click_times = mouth_clicks(&mono, sample_rate);
+    let duration = mono.len() as f32 / sample_rate;
+
+    let mut fixes = Vec::new();
+    for failed in check.rules.iter().filter(|rule| !rule.passed) {
+        let (priority, message, times) = match failed.rule.as_str() {
+            "integrated_loudness" => (FixPriority::High, format!("Apply {:+.1} dB of gain to reach {:.0} LUFS", failed.limit - failed.measured, failed.limit), vec![]),
+            "true_peak" => (FixPriority::High, format!("Limit true peaks from {:.1} to {:.1} dBTP", failed.measured, failed.limit), vec![]),
+            "leading_silence" => (FixPriority::Medium, format!("Trim {:.1} s of silence from the start", failed.measured - failed.limit), vec![0.0]),
+            "trailing_silence" => (FixPriority::Medium, format!("Trim {:.1} s of silence from the end", failed.measured - failed.limit), vec![duration - failed.measured]),
+            _ => (FixPriority::Medium, format!("Reduce background noise from {:.1} to {:.0} dBFS or below", failed.measured, failed.limit), vec![]),
+        };
+        fixes.push(fix(priority, &failed.rule, message, times));
+    }
+    if speech_offset.abs() > SPEECH_BALANCE_TOLERANCE {
+        let direction = if speech_offset < 0.0 { "under" } else { "over" };
+        fixes.push(fix(FixPriority::Medium, "speech_balance", format!("Speech sits {:.1} LU {} the whole mix; rebalance music and effects", speech_offset.abs(), direction), vec![]));
+    }
+    if !dead_air.is_empty() {
+        fixes.push(fix(FixPriority::Medium, "dead_air", format!("{} stretch(es) of dead air of {:.0} s or more", dead_air.len(), DEAD_AIR_SECONDS), dead_air.iter().map(|&(start, _)| start).collect()));
+    }
+    if !sibilance_times.is_empty() {
+        fixes.push(fix(FixPriority::Low, "sibilance", format!("{} harsh sibilant(s); de-ess above {:.0} kHz", sibilance_times.len(), SIBILANCE_FREQUENCY / 1000.0), sibilance_times.clone()));
+    }
+    if !click_times.is_empty() {
+        fixes.push(fix(FixPriority::Low, "mouth_clicks", format!("{} mouth click(s); de-click or edit them out", click_times.len()), click_times.clone()));
+    }
+    fixes.sort_by_key(|fix| fix.priority);
+
+    Ok(PodcastReport { check, speech_loudness, speech_offset, speech_share, noise_floor, dead_air, sibilance_times, click_times, fixes })
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = analyze_podcast)]
+pub fn analyze_podcast_js(pcm: &[f32], sample_rate: f32, num_channels: usize) -> Result<PodcastReport, JsError> {
+    Ok(analyze_podcast(pcm, sample_rate, num_channels)?)
+}
+
+fn fix(priority: FixPriority, issue: &str, message: String, times: Vec<f32>) -> PodcastFix {
+    PodcastFix { priority, issue: issue.to_string(), message, times }
+}
+
+// Gated loudness (LUFS) of the speech blocks and of every block, and the
+// share of blocks that are speech
+fn speech_gated_loudness(pcm: &[f32], num_channels: usize, sample_rate: f32) -> (f32, f32, f32) {
+    let frames = pcm.len() / num_channels;
+    let mut weighted = vec![0.0f64; frames];
+    let (mut voice, mut full) = (vec![0.0f64; frames], vec![0.0f64; frames]);
+    for ch in 0..num_channels {
+        let mut k_weighting = KWeighting::<f64>::new(sample_rate);
+        let mut high_pass = Biquad::high_pass(sample_rate, SPEECH_LOW, std::f32::consts::FRAC_1_SQRT_2);
+        let mut low_pass = Biquad::low_pass(sample_rate, SPEECH_HIGH, std::f32::consts::FRAC_1_SQRT_2);
+        for (frame, &sample) in pcm.iter().skip(ch).step_by(num_channels).enumerate() {
+            let k = k_weighting.process(sample as f64);
+            let band = low_pass.process(high_pass.process(sample));
+            weighted[frame] += k * k;
+            voice[frame] += (band * band) as f64;
+            full[frame] += (sample * sample) as f64;
+        }
+    }
+    let (block, hop) = ((SPEECH_BLOCK_SECONDS * sample_rate) as usize, ((SPEECH_HOP_SECONDS * sample_rate) as usize).max(1));
+    let blocks = blockize(&weighted, block, hop);
+    let speech: Vec<f32> = blocks.iter().zip(blockize(&voice, block, hop).into_iter().zip(blockize(&full, block, hop)))
+        .filter(|&(_, (voice, full))| full > 0.0 && voice >= SPEECH_BAND_SHARE * full)
+        .map(|(&energy, _)| energy)
+        .collect();
+    let share = if blocks.is_empty() { 0.0 } else { speech.len() as f32 / blocks.len() as f32 };
+    (Gate::INTEGRATED.apply(&speech).loudness(), Gate::INTEGRATED.apply(&blocks).loudness(), share)
+}
+
+// Level (dBFS) of each `seconds` window of a mono signal, digital silence
+// floored so means stay finite
+fn window_levels(mono: &[f32], sample_rate: f32, seconds: f32) -> (usize, Vec<f32>) {
+    let window = ((seconds * sample_rate) as usize).max(1);
+    (window, mono.chunks_exact(window).map(|window| amplitude_to_db(calculate_rms(window)).max(DIGITAL_SILENCE)).collect())
+}
+
+// Stretches of dead air between the first and last sounds
+fn dead_air(mono: &[f32], sample_rate: f32) -> Vec<(f32, f32)> {
+    let (window, levels) = window_levels(mono, sample_rate, NOISE_WINDOW_SECONDS);
+    let seconds = window as f32 / sample_rate;
+    let (Some(first), Some(last)) = (levels.iter().position(|&level| level > DEAD_AIR_LEVEL), levels.iter().rposition(|&level| level > DEAD_AIR_LEVEL)) else {
+        return Vec::new();
+    };
+    let mut stretches = Vec::new();
+    let mut start = None;
+    for (index, &level) in levels.iter().enumerate().take(last + 1).skip(first) {
+        match (level <= DEAD_AIR_LEVEL, start) {
+            (true, None) => start = Some(index),
+            (false, Some(from)) => {
+                let length = (index - from) as f32 * seconds;
+                if length >= DEAD_AIR_SECONDS {
+                    stretches.push((from as f32 * seconds, length));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    stretches
+}
+
+// Start times of runs of sibilant windows
+pub(crate) fn sibilance(mono: &[f32], sample_rate: f32) -> Vec<f32> {
+    let mut stages = [Biquad::high_pass(sample_rate, SIBILANCE_FREQUENCY, std::f32::consts::FRAC_1_SQRT_2); 2];
+    let band: Vec<f32> = mono.iter().map(|&sample| stages.iter_mut().fold(sample, |x, stage| stage.process(x))).collect();
+    let (window, full) = window_levels(mono, sample_rate, SIBILANCE_WINDOW_SECONDS);
+    let (_, high) = window_levels(&band, sample_rate, SIBILANCE_WINDOW_SECONDS);
+    let mut times = Vec::new();
+    let mut previous = false;
+    for (index, (&full, &high)) in full.iter().zip(&high).enumerate() {
+        let sibilant = high > SIBILANCE_LEVEL && full - high <= SIBILANCE_DOMINANCE;
+        if sibilant && !previous {
+            times.push(index as f32 * window as f32 / sample_rate);
+        }
+        previous = sibilant;
+    }
+    times
+}
+
+// Times of short, sharp spikes in the sample-to-sample difference
+fn mouth_clicks(mono: &[f32], sample_rate: f32) -> Vec<f32> {
+    let window = ((CLICK_WINDOW_SECONDS * sample_rate) as usize).max(1);
+    let difference: Vec<f32> = mono.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let peaks: Vec<f32> = difference.chunks(window)
+        .map(|chunk| amplitude_to_db(chunk.iter().fold(0.0f32, |peak, &d| peak.max(d.abs()))).max(DIGITAL_SILENCE))
+        .collect();
+    CLICKS.pick(&peaks).into_iter().map(|index| index as f32 * window as f32 / sample_rate).collect()
+}
+
+fn rule(rule: &str, passed: bool, measured: f32, limit: f32, message: String) -> PodcastRule {
+    PodcastRule { rule: rule.to_string(), passed, measured, limit, message }
+}
+
+/// Level (dBFS) of the quiet passages: the trimmed mean of the quietest 50ms
+/// RMS windows over all channels, ignoring digital silence (-inf when every
+/// window is digitally silent)
+pub(crate) fn noise_floor(pcm: &[f32], num_channels: usize, sample_rate: f32) -> f32 {
+    let window = ((NOISE_WINDOW_SECONDS * sample_rate) as usize).max(1) * num_channels;
+    let mut levels: Vec<f32> = pcm.chunks_exact(window)
+        .map(|window| amplitude_to_db(calculate_rms(window)))
+        .filter(|&level| level > DIGITAL_SILENCE)
+        .collect();
+    levels.sort_unstable_by(f32::total_cmp);
+    let quiet = (levels.len() as f32 * NOISE_SHARE).ceil() as usize;
+    trimmed_mean(&mut levels[..quiet], NOISE_TRIM).unwrap_or(f32::NEG_INFINITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn noise_floor_reads_the_quiet_passages() {
+        // Speech-level tone with pauses of -70 dBFS hiss, after a second of digital silence
+        let hiss = |i: usize| 10f32.powf(-70.0 / 20.0) * if i.is_multiple_of(2) { 1.0 } else { -1.0 };
+        let pcm: Vec<f32> = std::iter::repeat_n(0.0, 48000)
+            .chain((0..10 * 48000).map(|i| if (i / 48000) % 2 == 0 { 0.3 * (i as f32 * 0.05).sin() } else { hiss(i) }))
+            .collect();
+
+        assert!((noise_floor(&pcm, 1, 48000.0) + 70.0).abs() < 0.1);
+
+        // A few windows dropping to -100 dBFS (edits) don't drag the floor down
+        let mut edited = pcm.clone();
+        for window in [25, 65, 105, 145, 185] {
+            edited[48000 + window * 2400..48000 + (window + 1) * 2400].iter_mut().for_each(|sample| *sample *= 10f32.powf(-30.0 / 20.0));
+        }
+        assert!((noise_floor(&edited, 1, 48000.0) + 70.0).abs() < 0.1);
+        assert_eq!(noise_floor(&[0.0; 4800], 1, 48000.0), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn preset_lists_fixes_by_priority() {
+        // 12 s of mono at 44.1kHz: a syllable-rate modulated 1kHz "voice" at
+        // 0.5-4 s and 7-10 s over -70 dBFS hiss, a 7kHz hiss burst at 8 s and
+        // a one-sample click at 9 s
+        let sample_rate = 44100.0;
+        let pcm: Vec<f32> = (0..12 * 44100usize)
+            .map(|i| {
+                let t = i as f32 / sample_rate;
+                let hiss = 10f32.powf(-70.0 / 20.0) * if i.is_multiple_of(2) { 1.0 } else { -1.0 };
+                let envelope = 0.6 + 0.4 * (2.0 * PI * 4.0 * t).sin();
+                let voice = if (0.5..4.0).contains(&t) || (7.0..10.0).contains(&t) { 0.1 * envelope * (2.0 * PI * 1000.0 * t).sin() } else { 0.0 };
+                let burst = if (8.0..8.06).contains(&t) { 0.3 * (2.0 * PI * 7000.0 * t).sin() } else { 0.0 };
+                let click = if i == 9 * 44100 { 0.3 } else { 0.0 };
+                hiss + voice + burst + click
+            })
+            .collect();
+
+        let report = analyze_podcast(&pcm, sample_rate, 1).unwrap();
+        assert_eq!(report.dead_air.len(), 1);
+        assert!((report.dead_air[0].0 - 4.0).abs() < 0.1 && (report.dead_air[0].1 - 3.0).abs() < 0.1);
+        assert_eq!(report.sibilance_times.len(), 1);
+        assert!((report.sibilance_times[0] - 8.0).abs() < 0.02);
+        assert_eq!(report.click_times.len(), 1);
+        assert!((report.click_times[0] - 9.0).abs() < 0.005);
+        assert!(report.speech_share > 0.3 && report.speech_share < 0.7);
+        assert!(report.speech_offset.abs() < SPEECH_BALANCE_TOLERANCE);
+
+        let issues: Vec<&str> = report.fixes.iter().map(|fix| fix.issue.as_str()).collect();
+        assert_eq!(issues, ["integrated_loudness", "dead_air", "sibilance", "mouth_clicks"]);
+        assert!(report.fixes.windows(2).all(|pair| pair[0].priority <= pair[1].priority));
+        assert_eq!(report.fixes[1].times, vec![report.dead_air[0].0]);
+    }
+}
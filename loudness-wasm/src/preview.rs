@@ -0,0 +1,185 @@
+// Quick-look previews: the input decimated to 8-12kHz (the smallest whole
+// factor that brings the rate to 12kHz or below) through an anti-aliasing
+// low-pass, so a first analysis of loudness, dynamics and rhythm comes back
+// almost at once while the full-resolution pass runs in a worker. Figures
+// that depend on the top octaves (true peak, brilliance, the spectral
+// rolloff) read low on a preview and should wait for the full pass.
+//
+//     const preview = decimate_preview(pcm, 48000, 2);
+//     const quick = new Analyzer(preview.sample_rate, preview.channels);
+//     show(quick.analyze(preview.samples()));
+//     worker.postMessage(pcm);  // full-resolution pass in the background
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+#[cfg(target_arch = "wasm32")]
+use crate::buffer::PcmBuffer;
+use crate::error::{validate_pcm, AnalysisError};
+use crate::utils::{Polyphase, SincQuality};
+
+// Highest sample rate a preview runs at
+pub const PREVIEW_MAX_RATE: f32 = 12000.0;
+
+/// Anti-aliased decimation by a whole factor: a polyphase low-pass at the
+/// output Nyquist, keeping every `factor`th filtered sample
+#[derive(Clone, Debug)]
+pub struct Decimator {
+    factor: usize,
+    bank: Polyphase,
+}
+
+impl Decimator {
+    pub fn new(factor: usize, quality: SincQuality) -> Self {
+        let factor = factor.max(1);
+        Decimator { factor, bank: Polyphase::new(1, factor, quality) }
+    }
+
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    pub fn output_rate(&self, sample_rate: f32) -> f32 {
+        sample_rate / self.factor as f32
+    }
+
+    /// Decimate interleaved PCM of `num_channels` channels
+    pub fn process(&self, pcm: &[f32], num_channels: usize) -> Vec<f32> {
+        let num_channels = num_channels.max(1);
+        let frames = pcm.len() / num_channels;
+        if self.factor == 1 {
+            return pcm[..frames * num_channels].to_vec();
+        }
+        let channels: Vec<Vec<f32>> = (0..num_channels)
+            .map(|channel| self.bank.process(&pcm[..frames * num_channels].iter().skip(channel).step_by(num_channels).copied().collect::<Vec<_>>()))
+            .collect();
+        (0..self.bank.output_len(frames)).flat_map(|n| channels.iter().map(move |samples| samples[n])).collect()
+    }
+}
+
+/// Decimated copy of the input for a quick first analysis
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct PreviewAudio {
+    samples: Vec<f32>,
+    sample_rate: f32,
+    channels: usize,
+    factor: usize,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl PreviewAudio {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    // Input samples per preview sample
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    // Copy of the interleaved samples
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = samples)]
+    pub fn samples_js(&self) -> Float32Array {
+        Float32Array::from(self.samples.as_slice())
+    }
+
+    // Hand the samples to the analyzers' buffer API without copying; the
+    // preview is consumed
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen]
+    pub fn into_buffer(self) -> PcmBuffer {
+        PcmBuffer::from_samples(self.samples)
+    }
+}
+
+impl PreviewAudio {
+    /// Interleaved decimated samples
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn into_samples(self) -> Vec<f32> {
+        self.samples
+    }
+}
+
+/// Decimation factor taking `sample_rate` to PREVIEW_MAX_RATE or below
+pub fn preview_factor(sample_rate: f32) -> usize {
+    (sample_rate / PREVIEW_MAX_RATE).ceil().max(1.0) as usize
+}
+
+/// Interleaved PCM decimated for a quick-look analysis; the low-pass is the
+/// fast sinc preset, since a preview trades accuracy for time
+pub fn decimate_preview(pcm: &[f32], sample_rate: f32, num_channels: usize) -> Result<PreviewAudio, AnalysisError> {
+    validate_pcm(pcm, num_channels, 1)?;
+    if !(sample_rate.is_finite() && sample_rate > 0.0) {
+        return Err(AnalysisError::InvalidSetting { reason: "sample rate must be positive" });
+    }
+    let decimator = Decimator::new(preview_factor(sample_rate), SincQuality::Fast);
+    Ok(PreviewAudio {
+        samples: decimator.process(pcm, num_channels),
+        sample_rate: decimator.output_rate(sample_rate),
+        channels: num_channels,
+        factor: decimator.factor(),
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = decimate_preview)]
+pub fn decimate_preview_js(pcm: &[f32], sample_rate: f32, num_channels: usize) -> Result<PreviewAudio, JsError> {
+    Ok(decimate_preview(pcm, sample_rate, num_channels)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previews_run_at_8_to_12_khz() {
+        for (rate, factor) in [(8000.0, 1), (16000.0, 2), (44100.0, 4), (48000.0, 4), (96000.0, 8), (192000.0, 16)] {
+            assert_eq!(preview_factor(rate), factor, "{} Hz", rate);
+            assert!((8000.0..=PREVIEW_MAX_RATE).contains(&(rate / factor as f32)));
+        }
+
+        let pcm: Vec<f32> = (0..44100 * 2).map(|i| 0.25 * (i as f32 * 0.01).sin()).collect();
+        let preview = decimate_preview(&pcm, 44100.0, 2).unwrap();
+        assert_eq!((preview.sample_rate(), preview.channels(), preview.factor()), (11025.0, 2, 4));
+        assert_eq!(preview.samples().len(), 11025 * 2);
+        assert!(decimate_preview(&pcm, 0.0, 2).is_err());
+        assert!(decimate_preview(&pcm[..3], 44100.0, 2).is_err());
+    }
+
+    #[test]
+    fn decimation_keeps_the_passband_and_rejects_aliases() {
+        let tone = |frequency: f32, frames: usize| -> Vec<f32> {
+            (0..frames).flat_map(|i| {
+                let x = 0.5 * (2.0 * std::f32::consts::PI * frequency * i as f32 / 48000.0).sin();
+                [x, -x]
+            }).collect()
+        };
+        let decimator = Decimator::new(4, SincQuality::Fast);
+        assert_eq!(decimator.output_rate(48000.0), 12000.0);
+
+        // 1kHz passes: the output matches the tone generated at 12kHz
+        let output = decimator.process(&tone(1000.0, 48000), 2);
+        assert_eq!(output.len(), 2 * 12000);
+        let expected: Vec<f32> = tone(1000.0, 48000).chunks(8).flat_map(|frame| [frame[0], frame[1]]).collect();
+        let error = output[200..23800].iter().zip(&expected[200..23800]).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+        assert!(error < 0.01, "max error {}", error);
+
+        // 9kHz would alias to 3kHz; the low-pass removes it
+        let aliased = decimator.process(&tone(9000.0, 48000), 2);
+        let peak = aliased[200..23800].iter().fold(0.0f32, |peak, &sample| peak.max(sample.abs()));
+        assert!(peak < 0.005, "alias peak {}", peak);
+        assert_eq!(Decimator::new(1, SincQuality::Fast).process(&[0.1, 0.2, 0.3], 1), vec![0.1, 0.2, 0.3]);
+    }
+}
@@ -0,0 +1,378 @@
+// Rule-based QC profiles: a delivery spec written as data (limits on
+// loudness, true peak, LRA, silence, clipping and phase) and evaluated
+// against an analysis result, one QC finding per rule. Broadcasters
+// encode EBU R128, ATSC A/85, ARIB TR-B32 or OP-59 (built in) or their own
+// house spec as JSON, without code changes:
+//
+//     {"name": "House spec", "rules": [
+//         {"metric": "integrated", "target": -24, "tolerance": 2},
+//         {"metric": "true_peak", "max": -2, "warn_margin": 0.5},
+//         {"id": "lra", "metric": "loudness_range", "max": 15, "violation": "warning"},
+//         {"metric": "phase_correlation", "min": 0}]}
+//
+// A rule holds the metric within `min`/`max` (or `target` ± `tolerance`);
+// outside them it is an error (or a warning, with "violation": "warning"), and
+// inside but closer than `warn_margin` to a limit a warning. Items are QC
+// report findings with the rule's metric and limits alongside.
+//
+//     analyzer.set_qc_profile(QcProfile.from_json(spec));  // or QcProfile.builtin("atsc-a85")
+//     const { profile } = analyzer.analyze(pcm);
+//     profile.items.filter(item => item.severity === "error").forEach(flag);
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::analyzer::AnalysisResult;
+use crate::qc::{finding, Finding, Severity};
+#[cfg(feature = "json")]
+use crate::error::AnalysisError;
+
+// Built-in delivery specs: integrated loudness and maximum true peak
+#[cfg(feature = "json")]
+const BUILTIN_PROFILES: [(&str, &str); 4] = [
+    ("ebu-r128", r#"{"name": "EBU R128", "rules": [
+        {"metric": "integrated", "target": -23, "tolerance": 0.5},
+        {"metric": "true_peak", "max": -1}]}"#),
+    ("atsc-a85", r#"{"name": "ATSC A/85", "rules": [
+        {"metric": "integrated", "target": -24, "tolerance": 2},
+        {"metric": "true_peak", "max": -2}]}"#),
+    ("arib-tr-b32", r#"{"name": "ARIB TR-B32", "rules": [
+        {"metric": "integrated", "target": -24, "tolerance": 1},
+        {"metric": "true_peak", "max": -1}]}"#),
+    ("op-59", r#"{"name": "Free TV OP-59", "rules": [
+        {"metric": "integrated", "target": -24, "tolerance": 1},
+        {"metric": "true_peak", "max": -2}]}"#),
+];
+
+/// Result figure a rule is checked against
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub enum ProfileMetric {
+    // LUFS
+    Integrated,
+    MaxMomentary,
+    MaxShortTerm,
+    // LU
+    LoudnessRange,
+    // dBTP
+    TruePeak,
+    // dB
+    Plr,
+    ClippedSamples,
+    // Percent of the samples
+    ClippingPercentage,
+    // Linear, signed
+    DcOffset,
+    // Seconds
+    LeadingSilence,
+    TrailingSilence,
+    // Stereo only
+    PhaseCorrelation,
+    MonoCompatibility,
+    // dB, positive when the right channel is louder; stereo only
+    Balance,
+}
+
+impl ProfileMetric {
+    /// Name as written in JSON
+    pub fn name(self) -> &'static str {
+        match self {
+            ProfileMetric::Integrated => "integrated",
+            ProfileMetric::MaxMomentary => "max_momentary",
+            ProfileMetric::MaxShortTerm => "max_short_term",
+            ProfileMetric::LoudnessRange => "loudness_range",
+            ProfileMetric::TruePeak => "true_peak",
+            ProfileMetric::Plr => "plr",
+            ProfileMetric::ClippedSamples => "clipped_samples",
+            ProfileMetric::ClippingPercentage => "clipping_percentage",
+            ProfileMetric::DcOffset => "dc_offset",
+            ProfileMetric::LeadingSilence => "leading_silence",
+            ProfileMetric::TrailingSilence => "trailing_silence",
+            ProfileMetric::PhaseCorrelation => "phase_correlation",
+            ProfileMetric::MonoCompatibility => "mono_compatibility",
+            ProfileMetric::Balance => "balance",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ProfileMetric::Integrated => "Integrated loudness",
+            ProfileMetric::MaxMomentary => "Maximum momentary loudness",
+            ProfileMetric::MaxShortTerm => "Maximum short-term loudness",
+            ProfileMetric::LoudnessRange => "Loudness range",
+            ProfileMetric::TruePeak => "True peak",
+            ProfileMetric::Plr => "Peak-to-loudness ratio",
+            ProfileMetric::ClippedSamples => "Clipped samples",
+            ProfileMetric::ClippingPercentage => "Clipped share",
+            ProfileMetric::DcOffset => "DC offset",
+            ProfileMetric::LeadingSilence => "Leading silence",
+            ProfileMetric::TrailingSilence => "Trailing silence",
+            ProfileMetric::PhaseCorrelation => "Phase correlation",
+            ProfileMetric::MonoCompatibility => "Mono compatibility",
+            ProfileMetric::Balance => "Channel balance",
+        }
+    }
+
+    // Value and unit (with its leading space) in `result`; None when the result does not carry it
+    // (stereo figures of mono material)
+    fn read(self, result: &AnalysisResult) -> (Option<f32>, &'static str) {
+        let (loudness, technical, stereo) = (&result.loudness, &result.technical, result.stereo.as_ref());
+        match self {
+            ProfileMetric::Integrated => (Some(loudness.integrated), " LUFS"),
+            ProfileMetric::MaxMomentary => (Some(loudness.momentary), " LUFS"),
+            ProfileMetric::MaxShortTerm => (Some(loudness.short_term), " LUFS"),
+            ProfileMetric::LoudnessRange => (Some(loudness.loudness_range), " LU"),
+            ProfileMetric::TruePeak => (Some(technical.true_peak.level), " dBTP"),
+            ProfileMetric::Plr => (Some(technical.mastering.plr), " dB"),
+            ProfileMetric::ClippedSamples => (Some(technical.quality.clipped_samples as f32), " samples"),
+            ProfileMetric::ClippingPercentage => (Some(technical.quality.clipping_percentage), "%"),
+            ProfileMetric::DcOffset => (Some(technical.quality.dc_offset), ""),
+            ProfileMetric::LeadingSilence => (Some(technical.silence.leading_silence), " s"),
+            ProfileMetric::TrailingSilence => (Some(technical.silence.trailing_silence), " s"),
+            ProfileMetric::PhaseCorrelation => (stereo.and_then(|stereo| stereo.phase_correlation), ""),
+            ProfileMetric::MonoCompatibility => (stereo.filter(|stereo| !stereo.is_mono).map(|stereo| stereo.mono_compatibility), ""),
+            ProfileMetric::Balance => (stereo.and_then(|stereo| stereo.lr_balance), " dB"),
+        }
+    }
+}
+
+/// One rule of a profile, as written in its JSON
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileRule {
+    // Stable identifier for the item; the metric's name when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub metric: ProfileMetric,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f32>,
+    // Shorthand for min/max of target ∓ tolerance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f32>,
+    // Distance inside a limit that still warns
+    #[serde(default)]
+    pub warn_margin: f32,
+    // Severity outside the limits: error (default) or warning
+    #[serde(default = "default_violation")]
+    pub violation: Severity,
+}
+
+fn default_violation() -> Severity {
+    Severity::Error
+}
+
+impl ProfileRule {
+    fn id(&self) -> String {
+        self.id.clone().unwrap_or_else(|| self.metric.name().to_string())
+    }
+
+    // Lower and upper limits, with the target form resolved
+    fn limits(&self) -> (Option<f32>, Option<f32>) {
+        match (self.target, self.tolerance) {
+            (Some(target), Some(tolerance)) => (Some(target - tolerance), Some(target + tolerance)),
+            _ => (self.min, self.max),
+        }
+    }
+
+    #[cfg(feature = "json")]
+    fn validate(&self) -> Result<(), AnalysisError> {
+        let invalid = |reason| Err(AnalysisError::InvalidSetting { reason });
+        match (self.target, self.tolerance, self.min, self.max) {
+            (Some(_), Some(tolerance), None, None) if tolerance >= 0.0 => {}
+            (Some(_), _, None, None) => return invalid("a rule target needs a non-negative tolerance"),
+            (None, None, None, None) => return invalid("a rule needs a min, a max or a target"),
+            (None, None, min, max) if min.zip(max).is_none_or(|(min, max)| min <= max) => {}
+            (None, None, _, _) => return invalid("a rule's min is above its max"),
+            _ => return invalid("a rule takes either a target and tolerance or min/max"),
+        }
+        if !matches!(self.violation, Severity::Warning | Severity::Error) {
+            return invalid("a rule's violation must be warning or error");
+        }
+        if self.warn_margin < 0.0 {
+            return invalid("a rule's warn margin must not be negative");
+        }
+        Ok(())
+    }
+
+    fn evaluate(&self, result: &AnalysisResult) -> ProfileItem {
+        let (measured, unit) = self.metric.read(result);
+        let (min, max) = self.limits();
+        let label = self.metric.label();
+        let (severity, message, fix) = match measured {
+            None => (Severity::NotMeasured, format!("{} is not measured for this material", label), None),
+            Some(value) => {
+                let below = min.filter(|&min| value < min);
+                let above = max.filter(|&max| value > max);
+                let near = min.is_some_and(|min| value - min < self.warn_margin) || max.is_some_and(|max| max - value < self.warn_margin);
+                match (below, above) {
+                    (Some(min), _) => (self.violation, format!("{} {:.2}{} is below the {:.2}{} minimum", label, value, unit, min, unit),
+                        Some(format!("Raise it to at least {:.2}{}", min, unit))),
+                    (_, Some(max)) => (self.violation, format!("{} {:.2}{} is above the {:.2}{} maximum", label, value, unit, max, unit),
+                        Some(format!("Lower it to at most {:.2}{}", max, unit))),
+                    _ if near => (Severity::Warning, format!("{} {:.2}{} is within {:.2} of a limit", label, value, unit, self.warn_margin), None),
+                    _ => (Severity::Pass, format!("{} {:.2}{} is within limits", label, value, unit), None),
+                }
+            }
+        };
+        ProfileItem { finding: finding(&self.id(), severity, message, measured, fix), metric: self.metric, min, max }
+    }
+}
+
+/// One rule's outcome: its finding (checked under the rule's id), with the
+/// metric and the limits it was held to
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(missing_as_null))]
+pub struct ProfileItem {
+    #[serde(flatten)]
+    pub finding: Finding,
+    pub metric: ProfileMetric,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+/// Every rule of a profile in order; `passes` unless a rule is an error
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct ProfileReport {
+    pub profile: String,
+    pub severity: Severity,
+    pub passes: bool,
+    pub items: Vec<ProfileItem>,
+}
+
+/// A named set of rules
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QcProfile {
+    name: String,
+    rules: Vec<ProfileRule>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl QcProfile {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    // Names `builtin` accepts
+    #[cfg(feature = "json")]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn builtin_names() -> Vec<String> {
+        BUILTIN_PROFILES.iter().map(|(name, _)| name.to_string()).collect()
+    }
+
+    #[cfg(feature = "json")]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn builtin(name: &str) -> Option<QcProfile> {
+        let (_, json) = BUILTIN_PROFILES.iter().find(|(builtin, _)| *builtin == name)?;
+        QcProfile::from_json(json).ok()
+    }
+
+    #[cfg(feature = "json")]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    #[cfg(all(target_arch = "wasm32", feature = "json"))]
+    #[wasm_bindgen(js_name = from_json)]
+    pub fn from_json_js(json: &str) -> Result<QcProfile, JsError> {
+        Ok(QcProfile::from_json(json)?)
+    }
+
+}
+
+impl QcProfile {
+    pub fn new(name: &str, rules: Vec<ProfileRule>) -> Self {
+        QcProfile { name: name.to_string(), rules }
+    }
+
+    /// Parse and check a profile written as JSON
+    #[cfg(feature = "json")]
+    pub fn from_json(json: &str) -> Result<QcProfile, AnalysisError> {
+        let profile: QcProfile = serde_json::from_str(json).map_err(|_| AnalysisError::InvalidSetting { reason: "malformed QC profile" })?;
+        profile.rules.iter().try_for_each(ProfileRule::validate)?;
+        Ok(profile)
+    }
+
+    pub fn rules(&self) -> &[ProfileRule] {
+        &self.rules
+    }
+
+    /// Check every rule against `result`
+    pub fn evaluate(&self, result: &AnalysisResult) -> ProfileReport {
+        let items: Vec<ProfileItem> = self.rules.iter().map(|rule| rule.evaluate(result)).collect();
+        let severity = items.iter().map(|item| item.finding.severity).fold(Severity::Pass, Severity::max);
+        ProfileReport { profile: self.name.clone(), severity, passes: severity < Severity::Error, items }
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::analyzer::Analyzer;
+
+    #[test]
+    fn profiles_judge_each_rule() {
+        let profile = QcProfile::from_json(r#"{"name": "House", "rules": [
+            {"metric": "integrated", "target": -24, "tolerance": 2},
+            {"metric": "true_peak", "max": -2, "warn_margin": 20},
+            {"id": "lra", "metric": "loudness_range", "max": 0.5, "violation": "warning"},
+            {"metric": "leading_silence", "max": 1},
+            {"metric": "phase_correlation", "min": 0}]}"#).unwrap();
+
+        // 5 s of a -6 dBFS mono tone: too loud, its peak inside the ceiling
+        // but within the wide warning margin
+        let pcm: Vec<f32> = (0..5 * 44100).map(|i| 0.5 * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 44100.0).sin()).collect();
+        let mut analyzer = Analyzer::new(44100.0, 1);
+        analyzer.set_qc_profile(&profile);
+        let report = analyzer.analyze(&pcm, None).unwrap().profile.unwrap();
+        let severities: Vec<(&str, Severity)> = report.items.iter().map(|item| (item.finding.check.as_str(), item.finding.severity)).collect();
+        assert_eq!(severities, [
+            ("integrated", Severity::Error),
+            ("true_peak", Severity::Warning),
+            ("lra", Severity::Pass),
+            ("leading_silence", Severity::Pass),
+            ("phase_correlation", Severity::NotMeasured),
+        ]);
+        assert_eq!((report.severity, report.passes), (Severity::Error, false));
+        let loudness = &report.items[0];
+        assert_eq!((loudness.min, loudness.max), (Some(-26.0), Some(-22.0)));
+        assert!(loudness.finding.message.starts_with("Integrated loudness") && loudness.finding.message.contains("above the -22.00 LUFS maximum"));
+        assert_eq!(loudness.finding.suggested_fix.as_deref(), Some("Lower it to at most -22.00 LUFS"));
+
+        // Items render as QC findings, flattened alongside the rule's limits
+        let item = serde_json::to_value(loudness).unwrap();
+        assert_eq!((item["check"].as_str(), item["severity"].as_str(), item["max"].as_f64()), (Some("integrated"), Some("error"), Some(-22.0)));
+        let older = QcProfile::from_json(r#"{"name": "x", "rules": [{"metric": "true_peak", "max": -1, "violation": "warn"}]}"#).unwrap();
+        assert_eq!(older.rules()[0].violation, Severity::Warning);
+
+        // Built-ins round-trip, and malformed specs are refused
+        for name in QcProfile::builtin_names() {
+            let builtin = QcProfile::builtin(&name).unwrap();
+            assert_eq!(QcProfile::from_json(&builtin.to_json()).unwrap(), builtin);
+        }
+        assert_eq!(QcProfile::builtin("atsc-a85").unwrap().name(), "ATSC A/85");
+        for malformed in [
+            r#"{"name": "x", "rules": [{"metric": "loudness"}]}"#,
+            r#"{"name": "x", "rules": [{"metric": "true_peak"}]}"#,
+            r#"{"name": "x", "rules": [{"metric": "true_peak", "min": 0, "max": -1}]}"#,
+            r#"{"name": "x", "rules": [{"metric": "integrated", "target": -23}]}"#,
+            r#"{"name": "x", "rules": [{"metric": "true_peak", "max": -1, "violation": "pass"}]}"#,
+            r#"{"name": "x", "rules": [{"metric": "true_peak", "maximum": -1}]}"#,
+        ] {
+            assert!(matches!(QcProfile::from_json(malformed), Err(AnalysisError::InvalidSetting { .. })), "{}", malformed);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+// Progress reporting and cooperative cancellation for long-running analyses
+//
+// Entry points take an optional callback (a JS function on the web) which is
+// invoked with the percent complete (0-100). Stages of a combined analysis report into sub-ranges so the
+// callback always sees a single monotonic sweep.
+//
+// Analyses stop at the next checkpoint once cancelled, which happens when
+// either the progress callback returns `false` (lets a worker poll a flag in a
+// SharedArrayBuffer) or a `CancellationToken` attached to the analyzer is
+// tripped (from JS, or from another thread in a `threads` build where wasm
+// memory is shared).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+use crate::clock::Trace;
+#[cfg(all(target_arch = "wasm32", any(feature = "loudness", feature = "music")))]
+use js_sys::Function;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// Marker error for an analysis aborted at a checkpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+// Handle JS can trip to abort analyses on every analyzer it is attached to
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    flag: Arc<AtomicBool>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl CancellationToken {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn reset(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    // Byte offset of the flag in wasm memory, so another thread sharing the
+    // memory can trip it with `Atomics.store(new Uint8Array(memory.buffer), ptr, 1)`
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn flag_ptr(&self) -> usize {
+        Arc::as_ptr(&self.flag) as usize
+    }
+}
+
+#[cfg(any(feature = "loudness", feature = "music"))]
+impl CancellationToken {
+    pub(crate) fn flag(&self) -> &AtomicBool {
+        &self.flag
+    }
+}
+
+/// Adapt a JS progress function to the native callback signature
+#[cfg(all(target_arch = "wasm32", any(feature = "loudness", feature = "music")))]
+pub fn js_progress(callback: &Function) -> Box<dyn Fn(f32) -> bool + '_> {
+    Box::new(move |percent| {
+        // Errors thrown by the callback must not abort the analysis
+        callback.call1(&JsValue::NULL, &JsValue::from_f64(percent as f64))
+            .map(|ret| ret.as_bool() != Some(false))
+            .unwrap_or(true)
+    })
+}
+
+#[cfg(any(feature = "loudness", feature = "music"))]
+pub struct Progress<'a> {
+    callback: Option<&'a dyn Fn(f32) -> bool>,
+    cancel: Option<&'a AtomicBool>,
+    #[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+    trace: Option<&'a Trace>,
+    start: f32,
+    end: f32,
+}
+
+#[cfg(any(feature = "loudness", feature = "music"))]
+impl<'a> Progress<'a> {
+    /// Reporter covering 0-100% of the given callback (a no-op without one)
+    pub fn new(callback: Option<&'a dyn Fn(f32) -> bool>, cancel: Option<&'a CancellationToken>) -> Self {
+        Progress {
+            callback,
+            cancel: cancel.map(CancellationToken::flag),
+            #[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+            trace: None,
+            start: 0.0,
+            end: 100.0,
+        }
+    }
+
+    /// Record stage timings into `trace` (shared with every sub-range)
+    #[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+    pub fn with_trace(mut self, trace: &'a Trace) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Reporter for the sub-range `from..to` (fractions of this range)
+    #[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+    pub fn stage(&self, from: f32, to: f32) -> Progress<'a> {
+        let span = self.end - self.start;
+        Progress {
+            callback: self.callback,
+            cancel: self.cancel,
+            trace: self.trace,
+            start: self.start + span * from,
+            end: self.start + span * to,
+        }
+    }
+
+    /// Report that `fraction` (0-1) of this range is complete
+    pub fn report(&self, fraction: f32) {
+        let _ = self.notify(fraction);
+    }
+
+    /// Report progress and stop if the analysis has been cancelled
+    pub fn checkpoint(&self, fraction: f32) -> Result<(), Cancelled> {
+        let keep_going = self.notify(fraction);
+        let cancelled = self.cancel.is_some_and(|flag| flag.load(Ordering::Relaxed));
+        if keep_going && !cancelled {
+            Ok(())
+        } else {
+            log::debug!("Analysis cancelled at {:.0}%", self.start + (self.end - self.start) * fraction.clamp(0.0, 1.0));
+            Err(Cancelled)
+        }
+    }
+
+    /// Book the time since the previous lap to `stage`, if tracing
+    #[cfg(all(feature = "loudness", feature = "stereo", feature = "technical"))]
+    pub fn lap(&self, stage: &str) {
+        if let Some(trace) = self.trace {
+            trace.lap(stage);
+        }
+    }
+
+    /// Book the time since the previous lap to `stage`, if tracing (only the
+    /// combined analyzer traces)
+    #[cfg(all(feature = "loudness", not(all(feature = "stereo", feature = "technical"))))]
+    pub fn lap(&self, _stage: &str) {}
+
+    // Invoke the callback; false when it asks for the analysis to stop
+    fn notify(&self, fraction: f32) -> bool {
+        match self.callback {
+            Some(callback) => callback(self.start + (self.end - self.start) * fraction.clamp(0.0, 1.0)),
+            None => true,
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "loudness", feature = "music")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_stops_once_token_is_tripped() {
+        let token = CancellationToken::new();
+        let progress = Progress::new(None, Some(&token));
+        assert_eq!(progress.checkpoint(0.5), Ok(()));
+
+        token.cancel();
+        assert_eq!(progress.checkpoint(0.75), Err(Cancelled));
+        token.reset();
+        assert_eq!(progress.checkpoint(1.0), Ok(()));
+    }
+}
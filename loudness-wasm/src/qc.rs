@@ -0,0 +1,425 @@
+// Quality-control report: the analysis results turned into the checklist a
+// mastering engineer would read, grouped into sections of findings with a
+// severity, a human-readable message and, where there is one, a suggested
+// fix. The frontend renders it to HTML/PDF as is; the thresholds come from the
+// analyzer config (loudness target, true-peak ceiling).
+//
+//     analyzer.set_include_report(true);
+//     const { report } = analyzer.analyze(pcm);
+//     report.sections.forEach(section => render(section.title, section.findings));
+
+use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::AnalyzerConfig;
+use crate::limits::MetricStatus;
+use crate::loudness::LoudnessResult;
+#[cfg(feature = "music")]
+use crate::rhythm::RhythmResult;
+use crate::stereo::StereoResult;
+use crate::technical::TechnicalResult;
+
+// Window (seconds) windowed issues are located to
+pub(crate) const ISSUE_WINDOW: f32 = 1.0;
+// Distance (LU) from the loudness target still reported as on target
+const DEFAULT_LOUDNESS_TOLERANCE: f32 = 1.0;
+// DC offset above -60dBFS is audible as lost headroom and clicks at edits
+const DC_OFFSET_LIMIT: f32 = 0.001;
+// Clipped share (percent) above which clipping is an error rather than a warning
+const CLIPPING_ERROR_PERCENTAGE: f32 = 0.01;
+// Silence (seconds) at the ends worth trimming before delivery
+const LEADING_SILENCE_LIMIT: f32 = 2.0;
+const TRAILING_SILENCE_LIMIT: f32 = 5.0;
+// Peak-to-loudness ratio (dB) below which a master reads as over-limited
+const LOW_PLR: f32 = 8.0;
+// Mono fold-down keeping less than this share of the stereo energy
+const LOW_MONO_COMPATIBILITY: f32 = 0.3;
+// Channel level difference (dB) reported as an imbalance
+const BALANCE_LIMIT: f32 = 3.0;
+
+/// How serious a finding is; a section and the report take their worst finding's
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub enum Severity {
+    // The result does not carry the figure the check needs
+    NotMeasured,
+    Pass,
+    Info,
+    // QC profiles also accept "warn" and "fail"
+    #[serde(alias = "warn")]
+    Warning,
+    #[serde(alias = "fail")]
+    Error,
+}
+
+/// One check and its outcome
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(missing_as_null))]
+pub struct Finding {
+    // Stable identifier for styling and filtering, e.g. "true_peak"
+    pub check: String,
+    pub severity: Severity,
+    pub message: String,
+    // The measured figure the check looked at, when there is one
+    pub value: Option<f32>,
+    pub suggested_fix: Option<String>,
+}
+
+/// A check located in the programme, as the club and vinyl checks report
+/// them: the one-second windows (`ISSUE_WINDOW`) it shows up in
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct WindowedIssue {
+    // Stable identifier, e.g. "mono_low_end"
+    pub check: String,
+    pub severity: Severity,
+    pub message: String,
+    // Start times (seconds) of the windows the issue shows up in
+    pub times: Vec<f32>,
+}
+
+/// Findings of one result section
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct ReportSection {
+    pub id: String,
+    pub title: String,
+    pub severity: Severity,
+    pub findings: Vec<Finding>,
+}
+
+/// Complete QC report in display order
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct QcReport {
+    pub severity: Severity,
+    pub summary: String,
+    pub target_loudness: f32,
+    pub true_peak_ceiling: f32,
+    pub sections: Vec<ReportSection>,
+}
+
+impl QcReport {
+    /// Findings at or above `severity`, across sections
+    pub fn findings_at_least(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.sections.iter().flat_map(|section| &section.findings).filter(move |finding| finding.severity >= severity)
+    }
+}
+
+/// Collects the result sections to report on; sections not supplied are left out
+#[derive(Clone)]
+pub struct QcReportBuilder<'r> {
+    target_loudness: f32,
+    loudness_tolerance: f32,
+    true_peak_ceiling: f32,
+    loudness: Option<&'r LoudnessResult>,
+    technical: Option<&'r TechnicalResult>,
+    stereo: Option<&'r StereoResult>,
+    #[cfg(feature = "music")]
+    rhythm: Option<&'r RhythmResult>,
+}
+
+impl<'r> QcReportBuilder<'r> {
+    /// Targets from the config the results were analysed with
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        QcReportBuilder {
+            target_loudness: config.target_loudness(),
+            loudness_tolerance: DEFAULT_LOUDNESS_TOLERANCE,
+            true_peak_ceiling: config.true_peak_ceiling(),
+            loudness: None,
+            technical: None,
+            stereo: None,
+            #[cfg(feature = "music")]
+            rhythm: None,
+        }
+    }
+
+    pub fn with_loudness_tolerance(mut self, lu: f32) -> Self {
+        self.loudness_tolerance = lu.abs();
+        self
+    }
+
+    pub fn loudness(mut self, result: &'r LoudnessResult) -> Self {
+        self.loudness = Some(result);
+        self
+    }
+
+    pub fn technical(mut self, result: &'r TechnicalResult) -> Self {
+        self.technical = Some(result);
+        self
+    }
+
+    pub fn stereo(mut self, result: &'r StereoResult) -> Self {
+        self.stereo = Some(result);
+        self
+    }
+
+    #[cfg(feature = "music")]
+    pub fn rhythm(mut self, result: &'r RhythmResult) -> Self {
+        self.rhythm = Some(result);
+        self
+    }
+
+    pub fn build(&self) -> QcReport {
+        let mut sections = Vec::new();
+        if let Some(loudness) = self.loudness {
+            sections.push(section("loudness", "Loudness", self.loudness_findings(loudness)));
+        }
+        if let Some(technical) = self.technical {
+            sections.push(section("technical", "Peaks and signal integrity", self.technical_findings(technical)));
+            sections.push(section("dynamics", "Dynamics", dynamics_findings(technical)));
+        }
+        if let Some(stereo) = self.stereo {
+            sections.push(section("stereo", "Stereo image", stereo_findings(stereo)));
+        }
+        #[cfg(feature = "music")]
+        if let Some(rhythm) = self.rhythm {
+            sections.push(section("rhythm", "Tempo and rhythm", rhythm_findings(rhythm)));
+        }
+
+        let severity = sections.iter().map(|section| section.severity).max().unwrap_or(Severity::Pass);
+        let count = |severity| sections.iter().flat_map(|section| &section.findings).filter(|finding| finding.severity == severity).count();
+        let summary = match severity {
+            Severity::NotMeasured | Severity::Pass | Severity::Info => "Ready for delivery: every check passed".to_string(),
+            _ => format!("{} error(s) and {} warning(s) to review before delivery", count(Severity::Error), count(Severity::Warning)),
+        };
+        QcReport {
+            severity,
+            summary,
+            target_loudness: self.target_loudness,
+            true_peak_ceiling: self.true_peak_ceiling,
+            sections,
+        }
+    }
+
+    fn loudness_findings(&self, loudness: &LoudnessResult) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        if let Some(finding) = status_finding("integrated", "Integrated loudness", loudness.status.integrated) {
+            findings.push(finding);
+        }
+        if !loudness.integrated.is_finite() {
+            findings.push(finding("integrated", Severity::Error, "No programme material above the absolute gate (-70 LUFS)".to_string(), None, None));
+            return findings;
+        }
+
+        let gain = self.target_loudness - loudness.integrated;
+        if gain.abs() <= self.loudness_tolerance {
+            findings.push(finding("integrated", Severity::Pass,
+                format!("Integrated loudness {:.1} LUFS is within {:.1} LU of the {:.1} LUFS target", loudness.integrated, self.loudness_tolerance, self.target_loudness),
+                Some(loudness.integrated), None));
+            return findings;
+        }
+
+        // Raising the level moves the true peak with it; past the ceiling the
+        // gain has to come from a limiter
+        let peak_after_gain = self.technical.map(|technical| technical.true_peak.level + gain);
+        let fix = match peak_after_gain {
+            Some(peak) if gain > 0.0 && peak > self.true_peak_ceiling => format!(
+                "Raise the level by {:.1} dB through a limiter set to {:.1} dBTP (plain gain would peak at {:.1} dBTP)",
+                gain, self.true_peak_ceiling, peak,
+            ),
+            _ => format!("Apply {:+.1} dB of gain", gain),
+        };
+        let direction = if gain > 0.0 { "below" } else { "above" };
+        findings.push(finding("integrated", Severity::Warning,
+            format!("Integrated loudness {:.1} LUFS is {:.1} LU {} the {:.1} LUFS target", loudness.integrated, gain.abs(), direction, self.target_loudness),
+            Some(loudness.integrated), Some(fix)));
+        findings
+    }
+
+    fn technical_findings(&self, technical: &TechnicalResult) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        if let Some(finding) = status_finding("true_peak", "True peak", technical.status.true_peak) {
+            findings.push(finding);
+        }
+
+        let peak = technical.true_peak.level;
+        findings.push(if peak > self.true_peak_ceiling {
+            finding("true_peak", Severity::Error,
+                format!("True peak {:.1} dBTP exceeds the {:.1} dBTP ceiling", peak, self.true_peak_ceiling),
+                Some(peak),
+                Some(format!("Lower the limiter ceiling by {:.1} dB, or reduce the gain into it", peak - self.true_peak_ceiling)))
+        } else {
+            finding("true_peak", Severity::Pass, format!("True peak {:.1} dBTP is within the {:.1} dBTP ceiling", peak, self.true_peak_ceiling), Some(peak), None)
+        });
+
+        let quality = &technical.quality;
+        if quality.has_clipping {
+            let severity = if quality.clipping_percentage > CLIPPING_ERROR_PERCENTAGE { Severity::Error } else { Severity::Warning };
+            findings.push(finding("clipping", severity,
+                format!("{} clipped samples ({:.3}% of the file)", quality.clipped_samples, quality.clipping_percentage),
+                Some(quality.clipping_percentage),
+                Some("Re-render from the session with lower gain into the output stage, or repair the clipped passages".to_string())));
+        } else {
+            findings.push(finding("clipping", Severity::Pass, "No clipped samples".to_string(), Some(0.0), None));
+        }
+
+        if quality.dc_offset.abs() > DC_OFFSET_LIMIT {
+            findings.push(finding("dc_offset", Severity::Warning,
+                format!("DC offset of {:.4} ({:.1} dBFS)", quality.dc_offset, 20.0 * quality.dc_offset.abs().log10()),
+                Some(quality.dc_offset),
+                Some("Apply a DC-blocking high-pass filter (5 to 20 Hz)".to_string())));
+        }
+
+        let silence = &technical.silence;
+        if silence.leading_silence > LEADING_SILENCE_LIMIT {
+            findings.push(finding("leading_silence", Severity::Info,
+                format!("{:.1} s of silence at the start", silence.leading_silence),
+                Some(silence.leading_silence),
+                Some("Trim the head unless the gap is intentional".to_string())));
+        }
+        if silence.trailing_silence > TRAILING_SILENCE_LIMIT {
+            findings.push(finding("trailing_silence", Severity::Info,
+                format!("{:.1} s of silence at the end", silence.trailing_silence),
+                Some(silence.trailing_silence),
+                Some("Trim the tail after the fade".to_string())));
+        }
+        findings
+    }
+}
+
+fn dynamics_findings(technical: &TechnicalResult) -> Vec<Finding> {
+    if let Some(finding) = status_finding("plr", "Peak-to-loudness ratio", technical.status.plr) {
+        return vec![finding];
+    }
+    let plr = technical.mastering.plr;
+    vec![if plr < LOW_PLR {
+        finding("plr", Severity::Warning,
+            format!("Peak-to-loudness ratio of {:.1} dB suggests heavy limiting", plr),
+            Some(plr),
+            Some("Ease the limiter or bus compression; streaming services turn loud masters down anyway".to_string()))
+    } else {
+        finding("plr", Severity::Pass, format!("Peak-to-loudness ratio of {:.1} dB", plr), Some(plr), None)
+    }]
+}
+
+fn stereo_findings(stereo: &StereoResult) -> Vec<Finding> {
+    if stereo.is_mono {
+        return vec![finding("image", Severity::Info, "Mono material".to_string(), None, None)];
+    }
+    let mut findings: Vec<Finding> = status_finding("image", "Stereo analysis", stereo.status).into_iter().collect();
+
+    if let Some(correlation) = stereo.phase_correlation {
+        findings.push(if correlation < 0.0 {
+            finding("phase_correlation", Severity::Error,
+                format!("Negative phase correlation ({:.2}): the channels partly cancel", correlation),
+                Some(correlation),
+                Some("Check the polarity of one channel and any wide stereo processing".to_string()))
+        } else {
+            finding("phase_correlation", Severity::Pass, format!("Phase correlation {:.2}", correlation), Some(correlation), None)
+        });
+    }
+    if stereo.mono_compatibility < LOW_MONO_COMPATIBILITY {
+        findings.push(finding("mono_compatibility", Severity::Warning,
+            format!("Only {:.0}% of the energy survives a mono fold-down", 100.0 * stereo.mono_compatibility),
+            Some(stereo.mono_compatibility),
+            Some("Narrow the low end and check wideners in mono".to_string())));
+    }
+    if let Some(balance) = stereo.lr_balance.filter(|balance| balance.abs() > BALANCE_LIMIT) {
+        let side = if balance > 0.0 { "right" } else { "left" };
+        findings.push(finding("balance", Severity::Warning,
+            format!("The {} channel is {:.1} dB louder", side, balance.abs()),
+            Some(balance),
+            Some("Re-centre the mix or check the panning of the lead elements".to_string())));
+    }
+    findings
+}
+
+// Rhythm figures describe the material rather than its quality, so they are
+// informational
+#[cfg(feature = "music")]
+fn rhythm_findings(rhythm: &RhythmResult) -> Vec<Finding> {
+    if let Some(finding) = status_finding("tempo", "Tempo analysis", rhythm.status) {
+        return vec![finding];
+    }
+    vec![
+        finding("tempo", Severity::Info, format!("{:.1} BPM in {}", rhythm.tempo, rhythm.time_signature), Some(rhythm.tempo), None),
+        finding("tempo_stability", Severity::Info, format!("Tempo stability {:.0}%", 100.0 * rhythm.tempo_stability), Some(rhythm.tempo_stability), None),
+    ]
+}
+
+fn section(id: &str, title: &str, findings: Vec<Finding>) -> ReportSection {
+    ReportSection {
+        id: id.to_string(),
+        title: title.to_string(),
+        severity: findings.iter().map(|finding| finding.severity).max().unwrap_or(Severity::Pass),
+        findings,
+    }
+}
+
+pub(crate) fn finding(check: &str, severity: Severity, message: String, value: Option<f32>, suggested_fix: Option<String>) -> Finding {
+    Finding { check: check.to_string(), severity, message, value, suggested_fix }
+}
+
+pub(crate) fn windowed_issue(check: &str, severity: Severity, message: String, times: Vec<f32>) -> WindowedIssue {
+    WindowedIssue { check: check.to_string(), severity, message, times }
+}
+
+// Start times of the flagged windows
+pub(crate) fn window_starts(flags: impl IntoIterator<Item = bool>) -> Vec<f32> {
+    flags.into_iter().enumerate().filter(|&(_, flagged)| flagged).map(|(index, _)| index as f32 * ISSUE_WINDOW).collect()
+}
+
+// Start of the window holding `seconds`
+pub(crate) fn window_start(seconds: f32) -> f32 {
+    (seconds / ISSUE_WINDOW).floor() * ISSUE_WINDOW
+}
+
+// Ready for the medium when no issue is an error
+pub(crate) fn ready(issues: &[WindowedIssue]) -> bool {
+    issues.iter().all(|issue| issue.severity < Severity::Error)
+}
+
+// Energy of a span of samples
+pub(crate) fn energy(signal: &[f32]) -> f64 {
+    signal.iter().map(|&x| x as f64 * x as f64).sum()
+}
+
+// Level (dB) of one energy relative to another; -Infinity against silence
+pub(crate) fn relative_db(part: f64, whole: f64) -> f32 {
+    if whole > 0.0 { (10.0 * (part / whole).log10()) as f32 } else { f32::NEG_INFINITY }
+}
+
+// A note on why a metric may not be trusted
+fn status_finding(check: &str, metric: &str, status: MetricStatus) -> Option<Finding> {
+    let (reason, fix) = match status {
+        MetricStatus::Ok => return None,
+        MetricStatus::InsufficientDuration => ("the file is too short to measure it reliably", "Analyse a longer excerpt"),
+        MetricStatus::SilenceDominated => ("most of the file is silence", "Trim the silence or analyse the programme material alone"),
+        MetricStatus::TruncatedToLimit => ("only part of the file was analysed", "Raise the duration limit or use the accurate preset"),
+    };
+    Some(finding(check, Severity::Info, format!("{} is approximate: {}", metric, reason), None, Some(fix.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::Analyzer;
+
+    #[test]
+    fn report_flags_hot_master_and_suggests_fixes() {
+        // A full-scale square wave: over the loudness target, clipped and
+        // peaking above the ceiling
+        let pcm: Vec<f32> = (0..2 * 5 * 44100).map(|i| if (i / 2) % 100 < 50 { 1.0 } else { -1.0 }).collect();
+        let config = AnalyzerConfig::new(44100.0, 2);
+        let result = Analyzer::from_config(&config).analyze(&pcm, None).unwrap();
+
+        let report = QcReportBuilder::from_config(&config)
+            .loudness(&result.loudness)
+            .technical(&result.technical)
+            .stereo(result.stereo.as_ref().unwrap())
+            .build();
+        assert_eq!(report.severity, Severity::Error);
+        assert_eq!(report.sections.iter().map(|section| section.id.as_str()).collect::<Vec<_>>(), ["loudness", "technical", "dynamics", "stereo"]);
+
+        let loudness = &report.sections[0].findings[0];
+        assert_eq!((loudness.check.as_str(), loudness.severity), ("integrated", Severity::Warning));
+        assert!(loudness.suggested_fix.as_deref().unwrap().starts_with("Apply -"));
+        let errors: Vec<&str> = report.findings_at_least(Severity::Error).map(|finding| finding.check.as_str()).collect();
+        assert!(errors.contains(&"true_peak") && errors.contains(&"clipping"));
+        assert!(report.findings_at_least(Severity::Error).all(|finding| finding.suggested_fix.is_some()));
+    }
+}
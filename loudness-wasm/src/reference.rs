@@ -0,0 +1,129 @@
+// Reference-track comparison: the track and a reference master are analysed
+// and the figures engineers match by ear (loudness, range, PLR, tonal balance,
+// width, tempo) are reported as deltas, track minus reference. Each side is
+// analysed with its own analyzer, so the reference may have another sample
+// rate or channel count. Key is not compared: this build has no key detection.
+//
+//     const comparison = analyzer.compare_to_reference(pcm, referenceAnalyzer, referencePcm);
+//     comparison.deltas.integrated.delta;   // +2.1 LU louder than the reference
+
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::analyzer::AnalysisResult;
+use crate::technical::FrequencyBalance;
+
+/// One figure of the track next to the reference's
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct MetricDelta {
+    pub track: f32,
+    pub reference: f32,
+    // Track minus reference
+    pub delta: f32,
+}
+
+impl MetricDelta {
+    fn new(track: f32, reference: f32) -> Self {
+        MetricDelta { track, reference, delta: track - reference }
+    }
+}
+
+/// Share of spectral energy (percent) in one frequency balance band
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct BandDelta {
+    pub band: String,
+    #[serde(flatten)]
+    pub delta: MetricDelta,
+}
+
+/// Deltas of the compared figures; stereo width is null unless both sides
+/// are stereo, tempo unless both were analysed with rhythm enabled
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(missing_as_null))]
+pub struct ReferenceDeltas {
+    pub integrated: MetricDelta,
+    pub loudness_range: MetricDelta,
+    pub true_peak: MetricDelta,
+    pub plr: MetricDelta,
+    pub band_balance: Vec<BandDelta>,
+    pub stereo_width: Option<MetricDelta>,
+    pub tempo: Option<MetricDelta>,
+}
+
+/// Both analyses with the deltas between them
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct ReferenceComparison {
+    pub track: AnalysisResult,
+    pub reference: AnalysisResult,
+    pub deltas: ReferenceDeltas,
+}
+
+impl ReferenceComparison {
+    pub fn new(track: AnalysisResult, reference: AnalysisResult) -> Self {
+        let deltas = ReferenceDeltas::between(&track, &reference);
+        ReferenceComparison { track, reference, deltas }
+    }
+}
+
+impl ReferenceDeltas {
+    pub fn between(track: &AnalysisResult, reference: &AnalysisResult) -> Self {
+        let width = |result: &AnalysisResult| result.stereo.as_ref().and_then(|stereo| stereo.stereo_width);
+        #[cfg(feature = "music")]
+        let tempo = track.rhythm.as_ref().zip(reference.rhythm.as_ref())
+            .map(|(track, reference)| MetricDelta::new(track.tempo, reference.tempo));
+        #[cfg(not(feature = "music"))]
+        let tempo = None;
+
+        ReferenceDeltas {
+            integrated: MetricDelta::new(track.loudness.integrated, reference.loudness.integrated),
+            loudness_range: MetricDelta::new(track.loudness.loudness_range, reference.loudness.loudness_range),
+            true_peak: MetricDelta::new(track.technical.true_peak.level, reference.technical.true_peak.level),
+            plr: MetricDelta::new(track.technical.mastering.plr, reference.technical.mastering.plr),
+            band_balance: band_deltas(&track.technical.spectral.frequency_balance, &reference.technical.spectral.frequency_balance),
+            stereo_width: width(track).zip(width(reference)).map(|(track, reference)| MetricDelta::new(track, reference)),
+            tempo,
+        }
+    }
+}
+
+fn band_deltas(track: &FrequencyBalance, reference: &FrequencyBalance) -> Vec<BandDelta> {
+    let bands = |balance: &FrequencyBalance| [
+        ("sub_bass", balance.sub_bass),
+        ("bass", balance.bass),
+        ("low_mids", balance.low_mids),
+        ("mids", balance.mids),
+        ("upper_mids", balance.upper_mids),
+        ("presence", balance.presence),
+        ("brilliance", balance.brilliance),
+    ];
+    bands(track).into_iter().zip(bands(reference))
+        .map(|((band, track), (_, reference))| BandDelta { band: band.to_string(), delta: MetricDelta::new(track, reference) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::Analyzer;
+
+    #[test]
+    fn deltas_compare_track_with_reference() {
+        let reference: Vec<f32> = (0..2 * 4 * 44100).map(|i| 0.25 * ((i / 2) as f32 * 0.05).sin() * if i % 2 == 0 { 1.0 } else { 0.8 }).collect();
+        let track: Vec<f32> = reference.iter().map(|sample| 2.0 * sample).collect();
+        let analyzer = Analyzer::new(44100.0, 2);
+
+        let comparison = analyzer.compare_to_reference(&track, &analyzer, &reference, None).unwrap();
+        let deltas = &comparison.deltas;
+        assert!((deltas.true_peak.delta - 6.02).abs() < 0.01);
+        assert!(deltas.integrated.delta > 5.0);
+        assert_eq!(deltas.integrated.track, comparison.track.loudness.integrated);
+        assert_eq!(deltas.band_balance.len(), 7);
+        assert!(deltas.band_balance.iter().all(|band| band.delta.delta.abs() < 0.01));
+        assert!(deltas.stereo_width.unwrap().delta.abs() < 1e-3);
+        assert!(deltas.tempo.is_none());
+    }
+}
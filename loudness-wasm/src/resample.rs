@@ -0,0 +1,238 @@
+// Band-limited resampling for analyses that assume a fixed rate (the loudness
+// block sizes and K-weighting coefficients are defined at 44.1kHz).
+//
+// Kaiser-windowed sinc interpolation (`utils::SincKernel`). Rate pairs with a
+// small common ratio (48kHz to 44.1kHz is 147/160) use the exact taps of a
+// `utils::Polyphase` filter bank; any other ratio tabulates the kernel at
+// PHASES points per input sample and interpolates linearly between them. When
+// downsampling the cutoff drops to the output Nyquist and the kernel widens to
+// match.
+
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::utils::{Polyphase, SincKernel, SincQuality};
+
+// Filter table resolution (points per input sample) for ratios without a filter bank
+const PHASES: usize = 256;
+
+/// How the input was resampled before analysis
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct ResamplingReport {
+    pub from_rate: f32,
+    pub to_rate: f32,
+    // Input samples weighted per output sample
+    pub filter_taps: usize,
+}
+
+pub struct Resampler {
+    from_rate: f32,
+    to_rate: f32,
+    // Output samples per input sample
+    ratio: f64,
+    // Kernel half-width in input samples
+    half_width: f64,
+    taps: Taps,
+}
+
+enum Taps {
+    Bank(Polyphase),
+    // Kernel at |x| = i / PHASES input samples
+    Table(Vec<f64>),
+}
+
+impl Resampler {
+    pub fn new(from_rate: f32, to_rate: f32) -> Self {
+        Resampler::with_quality(from_rate, to_rate, SincQuality::default())
+    }
+
+    pub fn with_quality(from_rate: f32, to_rate: f32, quality: SincQuality) -> Self {
+        let ratio = to_rate as f64 / from_rate as f64;
+        let kernel = SincKernel::new(ratio, quality);
+        let half_width = kernel.half_width();
+        let taps = match Polyphase::between(from_rate, to_rate, quality) {
+            Some(bank) => Taps::Bank(bank),
+            None => Taps::Table((0..=(half_width * PHASES as f64).ceil() as usize + 1).map(|i| kernel.at(i as f64 / PHASES as f64)).collect()),
+        };
+        Resampler { from_rate, to_rate, ratio, half_width, taps }
+    }
+
+    pub fn report(&self) -> ResamplingReport {
+        ResamplingReport {
+            from_rate: self.from_rate,
+            to_rate: self.to_rate,
+            filter_taps: 2 * self.half_width.ceil() as usize,
+        }
+    }
+
+    /// Resample interleaved PCM of `num_channels` channels
+    pub fn process(&self, pcm: &[f32], num_channels: usize) -> Vec<f32> {
+        self.run(pcm, num_channels, |sample| sample as f32)
+    }
+
+    /// Resample interleaved double-precision PCM of `num_channels` channels
+    pub fn process_f64(&self, pcm: &[f64], num_channels: usize) -> Vec<f64> {
+        self.run(pcm, num_channels, |sample| sample)
+    }
+
+    // Every output frame weighs the input frames within the kernel half-width;
+    // the weights are shared by all channels of the frame
+    fn run<S: Copy + Into<f64>, T>(&self, pcm: &[S], num_channels: usize, convert: impl Fn(f64) -> T) -> Vec<T> {
+        let num_channels = num_channels.max(1);
+        let frames = pcm.len() / num_channels;
+        let out_frames = (frames as f64 * self.ratio).round() as usize;
+        let mut output = Vec::with_capacity(out_frames * num_channels);
+        let mut weights = Vec::new();
+        let mut sums = vec![0.0f64; num_channels];
+
+        for n in 0..out_frames {
+            self.output_frame(pcm, 0, frames, n, &mut weights, &mut sums);
+            output.extend(sums.iter().map(|&sum| convert(sum)));
+        }
+        output
+    }
+
+    // Output frame `n` into `sums`, from `pcm` holding input frames from
+    // `first_frame` on, of `frames` input frames in total
+    fn output_frame<S: Copy + Into<f64>>(&self, pcm: &[S], first_frame: usize, frames: usize, n: usize, weights: &mut Vec<f64>, sums: &mut [f64]) {
+        let num_channels = sums.len();
+        let centre = n as f64 / self.ratio;
+        let first = (centre - self.half_width).ceil().max(0.0) as usize;
+        let last = ((centre + self.half_width).floor() as usize).min(frames.saturating_sub(1));
+
+        weights.clear();
+        match &self.taps {
+            Taps::Bank(bank) => {
+                let (tap_first, taps) = bank.taps(n);
+                weights.extend((first..=last).map(|k| usize::try_from(k as isize - tap_first).ok().and_then(|tap| taps.get(tap)).copied().unwrap_or(0.0)));
+            }
+            Taps::Table(table) => weights.extend((first..=last).map(|k| interpolate(table, (centre - k as f64).abs()))),
+        }
+        sums.iter_mut().for_each(|sum| *sum = 0.0);
+        for (k, &weight) in (first..=last).zip(weights.iter()) {
+            let frame = &pcm[(k - first_frame) * num_channels..(k - first_frame + 1) * num_channels];
+            for (sum, &sample) in sums.iter_mut().zip(frame) {
+                *sum += sample.into() * weight;
+            }
+        }
+    }
+
+    // First input frame output frame `n` weighs
+    fn first_input(&self, n: usize) -> usize {
+        (n as f64 / self.ratio - self.half_width).ceil().max(0.0) as usize
+    }
+
+    // Whether every input frame output frame `n` weighs is among the first `frames`
+    fn has_input(&self, n: usize, frames: usize) -> bool {
+        ((n as f64 / self.ratio + self.half_width).floor() as usize) < frames
+    }
+}
+
+// Tabulated kernel at distance `x` (input samples) from its centre
+fn interpolate(table: &[f64], x: f64) -> f64 {
+    let position = x * PHASES as f64;
+    let index = position as usize;
+    match (table.get(index), table.get(index + 1)) {
+        (Some(&a), Some(&b)) => a + (b - a) * (position - index as f64),
+        _ => 0.0,
+    }
+}
+
+/// Resampler fed in chunks (input streamed from the network): each output
+/// frame is produced once the input within the kernel half-width of it has
+/// arrived, and only that much input is kept. The concatenated output of
+/// `push` and `finish` equals `Resampler::process` over the whole input.
+pub struct StreamResampler {
+    resampler: Resampler,
+    num_channels: usize,
+    // Input from frame `first_frame` on
+    input: Vec<f32>,
+    first_frame: usize,
+    frames: usize,
+    next_output: usize,
+}
+
+impl StreamResampler {
+    pub fn new(from_rate: f32, to_rate: f32, num_channels: usize) -> Self {
+        StreamResampler {
+            resampler: Resampler::new(from_rate, to_rate),
+            num_channels: num_channels.max(1),
+            input: Vec::new(),
+            first_frame: 0,
+            frames: 0,
+            next_output: 0,
+        }
+    }
+
+    pub fn report(&self) -> ResamplingReport {
+        self.resampler.report()
+    }
+
+    /// Append whole interleaved frames; returns the output frames now complete
+    pub fn push(&mut self, pcm: &[f32]) -> Vec<f32> {
+        self.input.extend_from_slice(pcm);
+        self.frames += pcm.len() / self.num_channels;
+        let ready = (self.next_output..).take_while(|&n| self.resampler.has_input(n, self.frames)).count();
+        self.emit(self.next_output + ready)
+    }
+
+    /// The output frames left once the input has ended
+    pub fn finish(&mut self) -> Vec<f32> {
+        self.emit((self.frames as f64 * self.resampler.ratio).round() as usize)
+    }
+
+    // Output frames up to `end`, then drop the input no later frame weighs
+    fn emit(&mut self, end: usize) -> Vec<f32> {
+        let mut output = Vec::with_capacity(end.saturating_sub(self.next_output) * self.num_channels);
+        let mut weights = Vec::new();
+        let mut sums = vec![0.0f64; self.num_channels];
+        for n in self.next_output..end {
+            self.resampler.output_frame(&self.input, self.first_frame, self.frames, n, &mut weights, &mut sums);
+            output.extend(sums.iter().map(|&sum| sum as f32));
+        }
+        self.next_output = self.next_output.max(end);
+
+        let keep_from = self.resampler.first_input(self.next_output).min(self.frames);
+        if keep_from > self.first_frame {
+            self.input.drain(..(keep_from - self.first_frame) * self.num_channels);
+            self.first_frame = keep_from;
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resampled_sine_keeps_level_and_pitch() {
+        let tone = |rate: f32, frames: usize| -> Vec<f32> {
+            (0..frames).flat_map(|i| {
+                let x = 0.5 * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / rate).sin();
+                [x, -x]
+            }).collect()
+        };
+        let input = tone(48000.0, 48000);
+        let resampler = Resampler::new(48000.0, 44100.0);
+        let output = resampler.process(&input, 2);
+        assert_eq!(output.len(), 2 * 44100);
+
+        // Away from the edges the output matches a tone generated at 44.1kHz
+        let expected = tone(44100.0, 44100);
+        let error = output[2000..86000].iter().zip(&expected[2000..86000]).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+        assert!(error < 1e-3, "max error {}", error);
+        assert_eq!(resampler.report().to_rate, 44100.0);
+
+        // Fed in uneven chunks, the stream produces the same samples
+        let mut stream = StreamResampler::new(48000.0, 44100.0, 2);
+        let mut streamed = Vec::new();
+        for chunk in input.chunks(2 * 777) {
+            streamed.extend(stream.push(chunk));
+        }
+        streamed.extend(stream.finish());
+        assert_eq!(streamed, output);
+        assert!(stream.input.len() < 2 * 100);
+    }
+}
@@ -0,0 +1,1606 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::{Float32Array, Function};
+use crate::onset::{frame_to_time, onset_envelope, pick_onsets, MonoFold, ONSET_HOP};
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+#[cfg(target_arch = "wasm32")]
+use crate::buffer::PcmBuffer;
+use crate::config::{AnalyzerConfig, DEFAULT_SILENCE_THRESHOLD};
+use crate::limits::MetricStatus;
+use crate::parallel::map_range;
+use crate::error::{validate_pcm, AnalysisError};
+#[cfg(target_arch = "wasm32")]
+use crate::progress::js_progress;
+use crate::progress::{CancellationToken, Progress};
+use crate::simd::{dot, sum_squares};
+use crate::utils::{autocorrelation, median, median_absolute_deviation, median_smooth, mix_to_mono, silent_share, Biquad, KWeighting};
+
+// Default preferred tempo range (prior centred on its geometric mean)
+const DEFAULT_MIN_BPM: f32 = 60.0;
+const DEFAULT_MAX_BPM: f32 = 200.0;
+
+// Full range over which tempo candidates are considered
+const MIN_CANDIDATE_BPM: f32 = 30.0;
+const MAX_CANDIDATE_BPM: f32 = 300.0;
+
+// How strongly the beat tracker penalises deviations from the tempo period
+const BEAT_TIGHTNESS: f32 = 100.0;
+
+// Tempo curve window length in bars (advanced one bar at a time)
+const TEMPO_CURVE_BARS: usize = 8;
+// MAD to standard deviation for normally distributed values
+const MAD_TO_STD_DEV: f32 = 1.4826;
+
+// HPSS on a band-energy spectrogram: log-spaced band-pass filterbank, time
+// median for the harmonic part, cross-band median for the percussive part
+const HPSS_BANDS: usize = 12;
+const HPSS_LOW_HZ: f32 = 60.0;
+const HPSS_HIGH_HZ: f32 = 12800.0;
+const HPSS_HARMONIC_FRAMES: usize = 17;
+const HPSS_PERCUSSIVE_BANDS: usize = 7;
+
+// Click-track conformance: per-beat tolerance and required hit rate
+const CLICK_TOLERANCE_MS: f32 = 25.0;
+const CLICK_CONFORMANCE_THRESHOLD: f32 = 0.9;
+
+// Quantization histogram: symmetric range around the grid and bin count
+const QUANTIZATION_HISTOGRAM_RANGE_MS: f32 = 50.0;
+const QUANTIZATION_HISTOGRAM_BINS: usize = 20;
+
+// Per-bar band split (low / mid / high) in Hz
+const BAR_LOW_MID_HZ: f32 = 250.0;
+const BAR_MID_HIGH_HZ: f32 = 4000.0;
+
+// Upper edge of the low band used for danceability's kick/bass periodicity
+const DANCE_LOW_BAND_HZ: f32 = 150.0;
+
+// Drop detection: analysis window, minimum energy jump, build-up ramp and
+// spacing between reported drops
+const DROP_WINDOW_SECONDS: f32 = 0.5;
+const DROP_MIN_JUMP_DB: f32 = 6.0;
+const DROP_MIN_BUILDUP_SECONDS: f32 = 4.0;
+const DROP_MIN_RAMP_DB_PER_SECOND: f32 = 0.3;
+const DROP_MIN_SEPARATION_SECONDS: f32 = 8.0;
+
+// Percussiveness curve resolution and drum-presence threshold
+const PERCUSSIVENESS_WINDOW_SECONDS: f32 = 1.0;
+const DRUM_PRESENCE_THRESHOLD: f32 = 0.35;
+
+// Shortest input whose rhythm metrics are trusted (two 4/4 bars at 60 BPM)
+const MIN_RHYTHM_SECONDS: f32 = 8.0;
+
+/// Candidate tempo with its relative likelihood
+struct TempoCandidate {
+    bpm: f32,
+    period: f32,
+    likelihood: f32,
+}
+
+/// Swing and micro-timing of onsets relative to the beat grid
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct GrooveEstimate {
+    swing_percent: f32,
+    swing_ratio: f32,
+    microtiming_ms: f32,
+    offbeat_onsets: usize,
+}
+
+/// Onset deviations from a straight subdivision grid
+struct QuantizationEstimate {
+    subdivisions: usize,
+    tightness: f32,
+    mean_abs_deviation_ms: f32,
+    histogram: Vec<usize>,
+}
+
+/// Drop preceded by a build-up (times in seconds)
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct DropEvent {
+    time: f32,
+    buildup_start: f32,
+    jump_db: f32,
+}
+
+/// Bar-indexed loudness and band-energy series
+#[derive(Default)]
+struct BarSeries {
+    start_times: Vec<f32>,
+    loudness: Vec<f32>,
+    low_db: Vec<f32>,
+    mid_db: Vec<f32>,
+    high_db: Vec<f32>,
+}
+
+/// Meter estimate derived from the beat accent pattern
+struct MeterEstimate {
+    label: &'static str,
+    beats_per_bar: usize,
+    downbeat_phase: usize,
+    confidence: f32,
+}
+
+/// Tempo hypothesis as reported to JS
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct TempoCandidateResult {
+    pub bpm: f32,
+    pub likelihood: f32,
+}
+
+/// Local tempo (BPM) between consecutive beats, smoothed over a few bars
+#[derive(Clone, Serialize, Default)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct TempoCurve {
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub times: Vec<f32>,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub bpm: Vec<f32>,
+}
+
+/// Constant-BPM grid plus anchors for DJ software (Rekordbox/Serato grids are
+/// a BPM and the position of a bar's beat 1)
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct BeatGrid {
+    pub bpm: f32,
+    pub first_beat_ms: f32,
+    pub first_downbeat_ms: f32,
+    pub anchor_ms: f32,
+    pub beats_per_bar: usize,
+}
+
+/// Quantization against one grid (1/8 or 1/16 notes)
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct QuantizationResult {
+    pub grid: String,
+    pub tightness: f32,
+    pub mean_abs_deviation_ms: f32,
+    pub histogram_range_ms: f32,
+    pub histogram: Vec<usize>,
+}
+
+impl From<&QuantizationEstimate> for QuantizationResult {
+    fn from(estimate: &QuantizationEstimate) -> Self {
+        QuantizationResult {
+            grid: format!("1/{}", estimate.subdivisions * 4),
+            tightness: estimate.tightness,
+            mean_abs_deviation_ms: estimate.mean_abs_deviation_ms,
+            histogram_range_ms: QUANTIZATION_HISTOGRAM_RANGE_MS,
+            histogram: estimate.histogram.clone(),
+        }
+    }
+}
+
+/// Per-bar loudness (LUFS) and band energies (dB)
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct BarsResult {
+    pub count: usize,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub start_times: Vec<f32>,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub loudness: Vec<f32>,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub low_energy_db: Vec<f32>,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub mid_energy_db: Vec<f32>,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub high_energy_db: Vec<f32>,
+}
+
+impl From<BarSeries> for BarsResult {
+    fn from(bars: BarSeries) -> Self {
+        BarsResult {
+            count: bars.start_times.len(),
+            start_times: bars.start_times,
+            loudness: bars.loudness,
+            low_energy_db: bars.low_db,
+            mid_energy_db: bars.mid_db,
+            high_energy_db: bars.high_db,
+        }
+    }
+}
+
+/// Percussive share of the signal, globally and per window
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct PercussivenessResult {
+    pub global: f32,
+    pub drum_presence: bool,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub times: Vec<f32>,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub values: Vec<f32>,
+}
+
+/// Rhythm analysis result (times in seconds unless suffixed)
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct RhythmResult {
+    pub tempo: f32,
+    pub tempo_candidates: Vec<TempoCandidateResult>,
+    pub syncopation: f32,
+    pub rhythmic_complexity: f32,
+    pub danceability: f32,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub beats: Vec<f32>,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub downbeats: Vec<f32>,
+    pub time_signature: String,
+    pub beats_per_bar: usize,
+    pub meter_confidence: f32,
+    pub tempo_curve: TempoCurve,
+    pub tempo_stability: f32,
+    pub tempo_drift: f32,
+    pub beat_grid: Option<BeatGrid>,
+    pub groove: GrooveEstimate,
+    pub quantization: Vec<QuantizationResult>,
+    pub bars: BarsResult,
+    pub drops: Vec<DropEvent>,
+    pub percussiveness: PercussivenessResult,
+    // Shared by every rhythm metric
+    pub status: MetricStatus,
+}
+
+/// Timing of a performance against an expected click track (deviations in ms)
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct ClickConformanceResult {
+    pub expected_beats: usize,
+    pub matched_beats: usize,
+    pub missing_beats: usize,
+    pub mean_deviation_ms: f32,
+    pub std_deviation_ms: f32,
+    pub max_deviation_ms: f32,
+    pub tolerance_ms: f32,
+    pub conformance: f32,
+    pub conforms: bool,
+    // Missing beats are null so indices line up with the expected clicks
+    pub deviations_ms: Vec<Option<f32>>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct RhythmAnalyzer {
+    sample_rate: f32,
+    min_bpm: f32,
+    max_bpm: f32,
+    cancel: Option<CancellationToken>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl RhythmAnalyzer {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32) -> Self {
+        RhythmAnalyzer {
+            sample_rate,
+            min_bpm: DEFAULT_MIN_BPM,
+            max_bpm: DEFAULT_MAX_BPM,
+            cancel: None,
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        RhythmAnalyzer::new(config.sample_rate())
+    }
+
+    // Attach a token that aborts analyses at the next checkpoint when tripped
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_cancellation_token(&mut self, token: &CancellationToken) {
+        self.cancel = Some(token.clone());
+    }
+
+    // Restrict (and re-centre) the preferred tempo range, e.g. 160-180 for
+    // drum & bass so 87/174 ambiguities resolve to the faster reading
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_tempo_range(&mut self, min_bpm: f32, max_bpm: f32) {
+        let min_bpm = min_bpm.clamp(MIN_CANDIDATE_BPM, MAX_CANDIDATE_BPM);
+        let max_bpm = max_bpm.clamp(MIN_CANDIDATE_BPM, MAX_CANDIDATE_BPM);
+        if min_bpm < max_bpm {
+            self.min_bpm = min_bpm;
+            self.max_bpm = max_bpm;
+        }
+    }
+
+    // Set the preferred tempo range from a genre hint. Returns false (and
+    // leaves the range unchanged) for unrecognised genres
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_genre_hint(&mut self, genre: &str) -> bool {
+        let normalized: String = genre.to_lowercase().chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        let range = match normalized.as_str() {
+            "hiphop" | "rap" | "rnb" => (70.0, 110.0),
+            "house" | "techno" | "disco" => (115.0, 135.0),
+            "trance" => (125.0, 145.0),
+            "dubstep" => (135.0, 150.0),
+            "drumandbass" | "drumbass" | "dnb" | "jungle" => (155.0, 185.0),
+            "pop" | "rock" => (90.0, 150.0),
+            "ballad" | "ambient" | "downtempo" => (55.0, 100.0),
+            "metal" | "punk" => (100.0, 200.0),
+            _ => return false,
+        };
+        self.min_bpm = range.0;
+        self.max_bpm = range.1;
+        true
+    }
+
+    // Tempo prior: log-Gaussian around the centre of the preferred range,
+    // strongly attenuated outside it
+    fn tempo_prior(&self, bpm: f32) -> f32 {
+        let centre = (self.min_bpm * self.max_bpm).sqrt();
+        let octaves_from_centre = (bpm / centre).log2();
+        let in_range = if bpm >= self.min_bpm && bpm <= self.max_bpm { 1.0 } else { 0.1 };
+        (-0.5 * octaves_from_centre * octaves_from_centre).exp() * in_range
+    }
+
+    // Tempo candidates from the autocorrelation of the onset envelope: the
+    // best-scoring period plus its half/double (and 2:3) relatives, each with
+    // a likelihood combining periodicity strength and the tempo prior.
+    // Sorted by likelihood, likelihoods sum to one
+    fn tempo_candidates(&self, envelope: &[f32]) -> Vec<TempoCandidate> {
+        let frames_per_minute = 60.0 * self.sample_rate / ONSET_HOP as f32;
+        let min_lag = (frames_per_minute / MAX_CANDIDATE_BPM).floor().max(1.0) as usize;
+        let max_lag = (frames_per_minute / MIN_CANDIDATE_BPM).ceil() as usize;
+
+        if envelope.len() <= max_lag + 1 {
+            return Vec::new();
+        }
+
+        let mean = envelope.iter().sum::<f32>() / envelope.len() as f32;
+        let centered: Vec<f32> = envelope.iter().map(|&v| v - mean).collect();
+
+        // Unbiased: each lag's sum over the frames it overlaps
+        let autocorrelation: Vec<f32> = autocorrelation(&centered, max_lag + 1)
+            .into_iter()
+            .enumerate()
+            .map(|(lag, sum)| if lag < min_lag { 0.0 } else { (sum / (centered.len() - lag) as f32).max(0.0) })
+            .collect();
+
+        // Periodicity at a fractional period: local maximum within one frame,
+        // refined by parabolic interpolation
+        let periodicity_at = |period: f32| -> Option<(f32, f32)> {
+            let centre = period.round() as usize;
+            if centre <= min_lag || centre >= max_lag {
+                return None;
+            }
+            let lag = (centre - 1..=centre + 1)
+                .max_by(|&a, &b| autocorrelation[a].partial_cmp(&autocorrelation[b]).unwrap_or(std::cmp::Ordering::Equal))?;
+            let (a, b, c) = (autocorrelation[lag - 1], autocorrelation[lag], autocorrelation[lag + 1]);
+            let denominator = a - 2.0 * b + c;
+            let offset = if denominator.abs() > 1e-12 {
+                (0.5 * (a - c) / denominator).clamp(-0.5, 0.5)
+            } else {
+                0.0
+            };
+            Some((lag as f32 + offset, b))
+        };
+
+        let best_lag = (min_lag + 1..max_lag)
+            .max_by(|&a, &b| {
+                let score_a = autocorrelation[a] * self.tempo_prior(frames_per_minute / a as f32);
+                let score_b = autocorrelation[b] * self.tempo_prior(frames_per_minute / b as f32);
+                score_a.partial_cmp(&score_b).unwrap_or(std::cmp::Ordering::Equal)
+            });
+        let best_period = match best_lag.and_then(|lag| periodicity_at(lag as f32)) {
+            Some((period, strength)) if strength > 0.0 => period,
+            _ => return Vec::new(),
+        };
+
+        let mut candidates: Vec<TempoCandidate> = Vec::new();
+        for ratio in [1.0, 2.0, 0.5, 1.5, 2.0 / 3.0] {
+            if let Some((period, strength)) = periodicity_at(best_period * ratio) {
+                let bpm = frames_per_minute / period;
+                let likelihood = strength * self.tempo_prior(bpm);
+                if likelihood > 0.0 && candidates.iter().all(|c| (c.period - period).abs() >= 1.0) {
+                    candidates.push(TempoCandidate { bpm, period, likelihood });
+                }
+            }
+        }
+
+        let total: f32 = candidates.iter().map(|c| c.likelihood).sum();
+        for candidate in candidates.iter_mut() {
+            candidate.likelihood /= total;
+        }
+        candidates.sort_by(|a, b| b.likelihood.partial_cmp(&a.likelihood).unwrap_or(std::cmp::Ordering::Equal));
+        candidates
+    }
+
+    // Dynamic-programming beat tracker (Ellis 2007): each beat maximises its
+    // onset strength plus the best-scoring predecessor roughly one period back
+    fn track_beats(&self, envelope: &[f32], period: f32) -> Vec<usize> {
+        let n = envelope.len();
+        if n == 0 || period < 1.0 {
+            return Vec::new();
+        }
+
+        let mut score = vec![0.0f32; n];
+        let mut backlink = vec![usize::MAX; n];
+        let search_start = (2.0 * period).round() as usize;
+        let search_end = (period / 2.0).round().max(1.0) as usize;
+
+        for t in 0..n {
+            let mut best_score = 0.0;
+            let mut best_prev = usize::MAX;
+
+            let earliest = t.saturating_sub(search_start);
+            let latest = t.saturating_sub(search_end);
+            if t >= search_end {
+                for (prev, &previous_score) in score.iter().enumerate().take(latest + 1).skip(earliest) {
+                    let interval = (t - prev) as f32 / period;
+                    let penalty = BEAT_TIGHTNESS * interval.ln() * interval.ln();
+                    let candidate = previous_score - penalty;
+                    if best_prev == usize::MAX || candidate > best_score {
+                        best_score = candidate;
+                        best_prev = prev;
+                    }
+                }
+            }
+
+            score[t] = envelope[t] + best_score.max(0.0);
+            backlink[t] = if best_score > 0.0 { best_prev } else { usize::MAX };
+        }
+
+        // Start from the best-scoring frame within the final period
+        let tail_start = n.saturating_sub(period.round() as usize + 1);
+        let mut current = (tail_start..n)
+            .max_by(|&a, &b| score[a].partial_cmp(&score[b]).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap_or(n - 1);
+
+        let mut beats = vec![current];
+        while backlink[current] != usize::MAX {
+            current = backlink[current];
+            beats.push(current);
+        }
+        beats.reverse();
+        beats
+    }
+
+    // Onset strength around each beat (max within a small neighbourhood)
+    fn beat_strengths(&self, envelope: &[f32], beats: &[usize]) -> Vec<f32> {
+        beats.iter()
+            .map(|&beat| {
+                let start = beat.saturating_sub(2);
+                let end = (beat + 3).min(envelope.len());
+                envelope[start..end].iter().fold(0.0f32, |acc, &v| acc.max(v))
+            })
+            .collect()
+    }
+
+    // Mean onset strength at fractional positions between consecutive beats
+    fn subdivision_strength(&self, envelope: &[f32], beats: &[usize], fractions: &[f32]) -> f32 {
+        let mut sum = 0.0;
+        let mut count = 0;
+
+        for pair in beats.windows(2) {
+            let interval = (pair[1] - pair[0]) as f32;
+            for &fraction in fractions {
+                let position = (pair[0] as f32 + interval * fraction).round() as usize;
+                let start = position.saturating_sub(1);
+                let end = (position + 2).min(envelope.len());
+                if start < end {
+                    sum += envelope[start..end].iter().fold(0.0f32, |acc, &v| acc.max(v));
+                    count += 1;
+                }
+            }
+        }
+
+        if count > 0 { sum / count as f32 } else { 0.0 }
+    }
+
+    // Normalised autocorrelation of the beat-strength sequence at a given lag
+    fn accent_periodicity(&self, strengths: &[f32], lag: usize) -> f32 {
+        if strengths.len() <= lag * 2 {
+            return 0.0;
+        }
+
+        let mean = strengths.iter().sum::<f32>() / strengths.len() as f32;
+        let centered: Vec<f32> = strengths.iter().map(|&s| s - mean).collect();
+        let energy = sum_squares(&centered) / centered.len() as f32;
+        if energy < 1e-10 {
+            return 0.0;
+        }
+
+        let sum = dot(&centered[lag..], &centered[..centered.len() - lag]);
+        sum / (centered.len() - lag) as f32 / energy
+    }
+
+    // Estimate meter from accent periodicity (bar length in beats) and
+    // subdivision structure (duple vs triple), then pick the downbeat phase
+    // whose beats carry the strongest accents
+    fn estimate_meter(&self, envelope: &[f32], beats: &[usize]) -> MeterEstimate {
+        let strengths = self.beat_strengths(envelope, beats);
+
+        let triple_score = self.accent_periodicity(&strengths, 3);
+        // 4/4 material often accents beats 1 and 3 equally, so a strong
+        // two-beat periodicity also counts towards a four-beat bar
+        let quadruple_score = self.accent_periodicity(&strengths, 4)
+            .max(self.accent_periodicity(&strengths, 2));
+
+        let duple_subdivision = self.subdivision_strength(envelope, beats, &[0.5]);
+        let triple_subdivision = self.subdivision_strength(envelope, beats, &[1.0 / 3.0, 2.0 / 3.0]);
+        let compound = triple_subdivision > duple_subdivision * 1.25;
+
+        let (label, beats_per_bar, margin) = if compound && quadruple_score >= triple_score {
+            ("6/8", 2, (triple_subdivision - duple_subdivision) / (triple_subdivision + 1e-6))
+        } else if triple_score > quadruple_score {
+            ("3/4", 3, triple_score - quadruple_score)
+        } else {
+            ("4/4", 4, quadruple_score - triple_score)
+        };
+
+        let mut best_phase = 0;
+        let mut best_accent = f32::NEG_INFINITY;
+        for phase in 0..beats_per_bar {
+            let accents: Vec<f32> = strengths.iter().skip(phase).step_by(beats_per_bar).copied().collect();
+            if accents.is_empty() {
+                continue;
+            }
+            let mean_accent = accents.iter().sum::<f32>() / accents.len() as f32;
+            if mean_accent > best_accent {
+                best_accent = mean_accent;
+                best_phase = phase;
+            }
+        }
+
+        // Too few beats to see more than a couple of bars: low confidence
+        let coverage = (beats.len() as f32 / (beats_per_bar * 8) as f32).min(1.0);
+
+        MeterEstimate {
+            label,
+            beats_per_bar,
+            downbeat_phase: best_phase,
+            confidence: (margin.clamp(0.0, 1.0) * coverage).clamp(0.0, 1.0),
+        }
+    }
+
+    // Local tempo between each pair of beats: the inter-beat tempo, median
+    // smoothed over ~8 bars so one mistracked beat doesn't show as a jump.
+    // Returns (interval centre times, BPM values)
+    fn tempo_curve(&self, beat_times: &[f32], beats_per_bar: usize) -> (Vec<f32>, Vec<f32>) {
+        if beat_times.len() < 3 {
+            return (Vec::new(), Vec::new());
+        }
+
+        let (times, tempos): (Vec<f32>, Vec<f32>) = beat_times.windows(2)
+            .filter(|pair| pair[1] > pair[0])
+            .map(|pair| ((pair[0] + pair[1]) / 2.0, 60.0 / (pair[1] - pair[0])))
+            .unzip();
+        let radius = TEMPO_CURVE_BARS * beats_per_bar.max(1) / 2;
+        (times, median_smooth(&tempos, radius))
+    }
+
+    // Tempo stability (0-1) from the robust coefficient of variation of the
+    // tempo curve (scaled MAD over the median, so one mistracked window doesn't
+    // count as drift): grid-locked productions score near 1, live drift pulls
+    // it down
+    fn tempo_stability(&self, tempos: &[f32]) -> f32 {
+        if tempos.len() < 2 {
+            return 1.0;
+        }
+
+        let centre = median(&mut tempos.to_vec()).unwrap_or(0.0);
+        let spread = MAD_TO_STD_DEV * median_absolute_deviation(tempos).unwrap_or(0.0);
+        let variation = spread / centre.max(1e-6);
+
+        // 1% variation -> 0.9, 10% or more -> 0
+        (1.0 - variation * 10.0).clamp(0.0, 1.0)
+    }
+
+    // Position of each onset within its beat, as (beat index, fraction 0-1)
+    fn onset_beat_positions(&self, onsets: &[f32], beats: &[usize]) -> Vec<(usize, f32)> {
+        let mut positions = Vec::new();
+        let mut beat_idx = 0;
+
+        for &onset in onsets {
+            while beat_idx + 1 < beats.len() && beats[beat_idx + 1] as f32 <= onset {
+                beat_idx += 1;
+            }
+            if beat_idx + 1 >= beats.len() || onset < beats[beat_idx] as f32 {
+                continue;
+            }
+            let interval = (beats[beat_idx + 1] - beats[beat_idx]) as f32;
+            positions.push((beat_idx, (onset - beats[beat_idx] as f32) / interval));
+        }
+
+        positions
+    }
+
+    // Swing from the average position of off-beat eighth-note onsets (50% is
+    // straight, ~67% triplet swing), micro-timing from the spread of all onsets
+    // around the nearest swung-eighth grid point. The off-beat window stops
+    // short of the straight 16ths at 25% and 75%, which would otherwise pull
+    // straight sixteenth-note parts towards swing.
+    fn estimate_groove(&self, onsets: &[f32], beats: &[usize]) -> GrooveEstimate {
+        let positions = self.onset_beat_positions(onsets, beats);
+
+        let offbeats: Vec<f32> = positions.iter()
+            .map(|&(_, fraction)| fraction)
+            .filter(|&fraction| (0.4..=0.72).contains(&fraction))
+            .collect();
+
+        let swing_fraction = if offbeats.is_empty() {
+            0.5
+        } else {
+            offbeats.iter().sum::<f32>() / offbeats.len() as f32
+        };
+
+        let mut deviations_ms = Vec::with_capacity(positions.len());
+        for &(beat_idx, fraction) in &positions {
+            let grid = [0.0, swing_fraction, 1.0];
+            let nearest = grid.iter()
+                .map(|&g| fraction - g)
+                .fold(f32::INFINITY, |best, d| if d.abs() < best.abs() { d } else { best });
+            let interval_seconds = frame_to_time((beats[beat_idx + 1] - beats[beat_idx]) as f32, self.sample_rate);
+            deviations_ms.push(nearest * interval_seconds * 1000.0);
+        }
+
+        let microtiming_ms = if deviations_ms.is_empty() {
+            0.0
+        } else {
+            let mean = deviations_ms.iter().sum::<f32>() / deviations_ms.len() as f32;
+            (deviations_ms.iter().map(|&d| (d - mean) * (d - mean)).sum::<f32>() / deviations_ms.len() as f32).sqrt()
+        };
+
+        GrooveEstimate {
+            swing_percent: swing_fraction * 100.0,
+            swing_ratio: swing_fraction / (1.0 - swing_fraction).max(1e-6),
+            microtiming_ms,
+            offbeat_onsets: offbeats.len(),
+        }
+    }
+
+    // Deviation of each onset from the nearest 1/(4*subdivisions) note grid
+    // point: tightness is 1 on the grid and 0 for uniformly scattered onsets
+    // (mean deviation of a quarter grid cell), plus a ±50 ms histogram
+    fn quantization_tightness(&self, onsets: &[f32], beats: &[usize], subdivisions: usize) -> QuantizationEstimate {
+        let mut deviations_ms = Vec::new();
+        let mut cell_ms_sum = 0.0;
+
+        for (beat_idx, fraction) in self.onset_beat_positions(onsets, beats) {
+            let grid_position = (fraction * subdivisions as f32).round() / subdivisions as f32;
+            let beat_ms = frame_to_time((beats[beat_idx + 1] - beats[beat_idx]) as f32, self.sample_rate) * 1000.0;
+            deviations_ms.push((fraction - grid_position) * beat_ms);
+            cell_ms_sum += beat_ms / subdivisions as f32;
+        }
+
+        let mut histogram = vec![0usize; QUANTIZATION_HISTOGRAM_BINS];
+        let bin_width = 2.0 * QUANTIZATION_HISTOGRAM_RANGE_MS / QUANTIZATION_HISTOGRAM_BINS as f32;
+        for &deviation in &deviations_ms {
+            let bin = ((deviation + QUANTIZATION_HISTOGRAM_RANGE_MS) / bin_width).floor();
+            histogram[(bin.max(0.0) as usize).min(QUANTIZATION_HISTOGRAM_BINS - 1)] += 1;
+        }
+
+        if deviations_ms.is_empty() {
+            return QuantizationEstimate { subdivisions, tightness: 0.0, mean_abs_deviation_ms: 0.0, histogram };
+        }
+
+        let mean_abs = deviations_ms.iter().map(|d| d.abs()).sum::<f32>() / deviations_ms.len() as f32;
+        let mean_cell_ms = cell_ms_sum / deviations_ms.len() as f32;
+        QuantizationEstimate {
+            subdivisions,
+            tightness: (1.0 - mean_abs / (mean_cell_ms / 4.0)).clamp(0.0, 1.0),
+            mean_abs_deviation_ms: mean_abs,
+            histogram,
+        }
+    }
+
+    // Metrical weight of a grid slot within a bar (Longuet-Higgins & Lee):
+    // 0 on the downbeat, decreasing through half-bar, beat and subdivision levels
+    fn metrical_weight(&self, slot: usize, beats_per_bar: usize, subdivisions: usize) -> i32 {
+        let slots_per_bar = beats_per_bar * subdivisions;
+        if slot == 0 {
+            0
+        } else if beats_per_bar.is_multiple_of(2) && slot == slots_per_bar / 2 {
+            -1
+        } else if slot.is_multiple_of(subdivisions) {
+            -2
+        } else if subdivisions.is_multiple_of(2) && slot.is_multiple_of(subdivisions / 2) {
+            -3
+        } else {
+            -4
+        }
+    }
+
+    // Syncopation per bar: every onset followed by a rest on a metrically
+    // stronger slot contributes the weight difference. Returns the mean
+    // syncopation per bar and a 0-1 complexity score derived from it
+    fn rhythmic_complexity(&self, onsets: &[f32], beats: &[usize], meter: &MeterEstimate) -> (f32, f32) {
+        let beats_per_bar = meter.beats_per_bar;
+        if beats_per_bar == 0 || beats.len() < beats_per_bar + 1 {
+            return (0.0, 0.0);
+        }
+
+        // Compound meters subdivide the beat in three, simple meters in four
+        let subdivisions = if meter.label == "6/8" { 3 } else { 4 };
+        let slots_per_bar = beats_per_bar * subdivisions;
+        let num_bars = (beats.len() - 1).saturating_sub(meter.downbeat_phase) / beats_per_bar;
+        if num_bars == 0 {
+            return (0.0, 0.0);
+        }
+
+        let mut patterns = vec![vec![false; slots_per_bar]; num_bars];
+        for (beat_idx, fraction) in self.onset_beat_positions(onsets, beats) {
+            if beat_idx < meter.downbeat_phase {
+                continue;
+            }
+            let slot_in_beat = (fraction * subdivisions as f32).round() as usize;
+            let absolute_slot = (beat_idx - meter.downbeat_phase) * subdivisions + slot_in_beat;
+            let bar = absolute_slot / slots_per_bar;
+            if bar < num_bars {
+                patterns[bar][absolute_slot % slots_per_bar] = true;
+            }
+        }
+
+        let mut total = 0.0;
+        for pattern in &patterns {
+            let mut bar_syncopation = 0;
+            for slot in 0..slots_per_bar {
+                if !pattern[slot] {
+                    continue;
+                }
+                let onset_weight = self.metrical_weight(slot, beats_per_bar, subdivisions);
+                let strongest_rest = (slot + 1..slots_per_bar)
+                    .take_while(|&rest| !pattern[rest])
+                    .map(|rest| self.metrical_weight(rest, beats_per_bar, subdivisions))
+                    .max();
+                if let Some(rest_weight) = strongest_rest {
+                    if rest_weight > onset_weight {
+                        bar_syncopation += rest_weight - onset_weight;
+                    }
+                }
+            }
+            total += bar_syncopation as f32;
+        }
+
+        let syncopation = total / num_bars as f32;
+        (syncopation, 1.0 - (-syncopation / 4.0).exp())
+    }
+
+    // Least-squares straight-line fit of beat times against beat index, giving
+    // a constant-tempo grid as (time of beat 0, beat interval) in seconds
+    fn fit_beat_grid(&self, beat_times: &[f32]) -> Option<(f32, f32)> {
+        if beat_times.len() < 2 {
+            return None;
+        }
+
+        let n = beat_times.len() as f64;
+        let mean_index = (n - 1.0) / 2.0;
+        let mean_time = beat_times.iter().map(|&t| t as f64).sum::<f64>() / n;
+
+        let mut covariance = 0.0;
+        let mut variance = 0.0;
+        for (k, &time) in beat_times.iter().enumerate() {
+            let index_offset = k as f64 - mean_index;
+            covariance += index_offset * (time as f64 - mean_time);
+            variance += index_offset * index_offset;
+        }
+
+        let interval = covariance / variance;
+        if interval <= 0.0 {
+            return None;
+        }
+        Some(((mean_time - interval * mean_index) as f32, interval as f32))
+    }
+
+    // Log-spaced filterbank centre frequencies below ~0.45 fs
+    fn band_centres(&self) -> Vec<f32> {
+        let nyquist_limit = self.sample_rate * 0.45;
+        let ratio = (HPSS_HIGH_HZ / HPSS_LOW_HZ).powf(1.0 / (HPSS_BANDS - 1) as f32);
+        (0..HPSS_BANDS)
+            .map(|band| HPSS_LOW_HZ * ratio.powi(band as i32))
+            .filter(|&centre| centre < nyquist_limit)
+            .collect()
+    }
+
+    // Band energies per onset-envelope hop from a bank of RBJ band-pass
+    // filters (constant 0 dB peak gain, roughly octave-wide) -> [frame][band]
+    fn band_energy_spectrogram(&self, mono: &[f32]) -> Vec<Vec<f32>> {
+        let ratio = (HPSS_HIGH_HZ / HPSS_LOW_HZ).powf(1.0 / (HPSS_BANDS - 1) as f32);
+        let q = ratio.sqrt() / (ratio - 1.0);
+        let centres = self.band_centres();
+
+        let num_frames = mono.len() / ONSET_HOP;
+
+        // Each band filters the whole signal independently, so bands run in parallel
+        let band_energies = map_range(0..centres.len(), |band| {
+            let mut filter = Biquad::<f32>::band_pass(self.sample_rate, centres[band], q);
+            let mut energies = Vec::with_capacity(num_frames);
+            for frame in 0..num_frames {
+                let mut energy = 0.0;
+                for &sample in &mono[frame * ONSET_HOP..(frame + 1) * ONSET_HOP] {
+                    let filtered = filter.process(sample);
+                    energy += filtered * filtered;
+                }
+                energies.push(energy / ONSET_HOP as f32);
+            }
+            energies
+        });
+
+        (0..num_frames)
+            .map(|frame| band_energies.iter().map(|band| band[frame]).collect())
+            .collect()
+    }
+
+    // Harmonic/percussive separation with Wiener-style soft masks; returns
+    // the per-frame percussive energy and total energy
+    fn percussive_energy(&self, spectrogram: &[Vec<f32>]) -> (Vec<f32>, Vec<f32>) {
+        let num_frames = spectrogram.len();
+        let num_bands = spectrogram.first().map_or(0, |frame| frame.len());
+        let mut percussive = vec![0.0; num_frames];
+        let mut total = vec![0.0; num_frames];
+        let mut scratch = Vec::with_capacity(HPSS_HARMONIC_FRAMES);
+
+        for frame in 0..num_frames {
+            for band in 0..num_bands {
+                let energy = spectrogram[frame][band];
+
+                scratch.clear();
+                let start = frame.saturating_sub(HPSS_HARMONIC_FRAMES / 2);
+                let end = (frame + HPSS_HARMONIC_FRAMES / 2 + 1).min(num_frames);
+                scratch.extend(spectrogram[start..end].iter().map(|f| f[band]));
+                let harmonic = median(&mut scratch).unwrap_or(0.0);
+
+                scratch.clear();
+                let start = band.saturating_sub(HPSS_PERCUSSIVE_BANDS / 2);
+                let end = (band + HPSS_PERCUSSIVE_BANDS / 2 + 1).min(num_bands);
+                scratch.extend_from_slice(&spectrogram[frame][start..end]);
+                let percussive_estimate = median(&mut scratch).unwrap_or(0.0);
+
+                let h2 = harmonic * harmonic;
+                let p2 = percussive_estimate * percussive_estimate;
+                if h2 + p2 > 1e-20 {
+                    percussive[frame] += energy * p2 / (h2 + p2);
+                }
+                total[frame] += energy;
+            }
+        }
+
+        (percussive, total)
+    }
+
+    // Percussive-to-total energy ratio globally and over ~1 s windows.
+    // Returns (global ratio, window centre times, window ratios)
+    fn percussiveness(&self, spectrogram: &[Vec<f32>]) -> (f32, Vec<f32>, Vec<f32>) {
+        let (percussive, total) = self.percussive_energy(spectrogram);
+
+        let total_energy: f32 = total.iter().sum();
+        let global = if total_energy > 1e-10 {
+            percussive.iter().sum::<f32>() / total_energy
+        } else {
+            0.0
+        };
+
+        let window = ((PERCUSSIVENESS_WINDOW_SECONDS * self.sample_rate / ONSET_HOP as f32) as usize).max(1);
+        let mut times = Vec::new();
+        let mut values = Vec::new();
+        for start in (0..total.len()).step_by(window) {
+            let end = (start + window).min(total.len());
+            let window_total: f32 = total[start..end].iter().sum();
+            let window_percussive: f32 = percussive[start..end].iter().sum();
+            times.push(frame_to_time((start + end) as f32 / 2.0, self.sample_rate));
+            values.push(if window_total > 1e-10 { window_percussive / window_total } else { 0.0 });
+        }
+
+        (global, times, values)
+    }
+
+    // Sub-frame position of an envelope peak by parabolic interpolation
+    fn refine_peak(&self, envelope: &[f32], frame: usize) -> f32 {
+        if frame == 0 || frame + 1 >= envelope.len() {
+            return frame as f32;
+        }
+        let (a, b, c) = (envelope[frame - 1], envelope[frame], envelope[frame + 1]);
+        let denominator = a - 2.0 * b + c;
+        if denominator.abs() > 1e-12 {
+            frame as f32 + (0.5 * (a - c) / denominator).clamp(-0.5, 0.5)
+        } else {
+            frame as f32
+        }
+    }
+
+    // Signed deviation (ms, positive = late) of the nearest onset from each
+    // expected click in [offset, duration); None when no onset lies within a
+    // quarter of a beat
+    fn click_deviations(&self, onset_times: &[f32], expected_bpm: f32, offset: f32, duration: f32) -> Vec<Option<f32>> {
+        if expected_bpm <= 0.0 {
+            return Vec::new();
+        }
+
+        let interval = 60.0 / expected_bpm;
+        let search_radius = interval / 4.0;
+        let mut deviations = Vec::new();
+        let mut onset_idx = 0;
+
+        let mut click = offset;
+        while click < duration {
+            if click >= 0.0 {
+                while onset_idx < onset_times.len() && onset_times[onset_idx] < click - search_radius {
+                    onset_idx += 1;
+                }
+                let nearest = onset_times[onset_idx..].iter()
+                    .take_while(|&&t| t <= click + search_radius)
+                    .map(|&t| t - click)
+                    .fold(None, |best: Option<f32>, d| match best {
+                        Some(b) if b.abs() <= d.abs() => Some(b),
+                        _ => Some(d),
+                    });
+                deviations.push(nearest.map(|d| d * 1000.0));
+            }
+            click += interval;
+        }
+
+        deviations
+    }
+
+    // Pulse clarity: mean onset strength on the beats relative to the
+    // envelope as a whole, mapped to 0-1 (beats three times stronger -> 1)
+    fn beat_strength(&self, envelope: &[f32], beats: &[usize]) -> f32 {
+        let strengths = self.beat_strengths(envelope, beats);
+        let envelope_mean = envelope.iter().sum::<f32>() / envelope.len().max(1) as f32;
+        if strengths.is_empty() || envelope_mean <= 1e-8 {
+            return 0.0;
+        }
+
+        let beat_mean = strengths.iter().sum::<f32>() / strengths.len() as f32;
+        ((beat_mean / envelope_mean - 1.0) / 2.0).clamp(0.0, 1.0)
+    }
+
+    // Normalised autocorrelation of the low-band (kick/bass) energy at the
+    // beat period: how regularly the low end pulses with the beat
+    fn low_frequency_periodicity(&self, spectrogram: &[Vec<f32>], period: f32) -> f32 {
+        let low_bands = self.band_centres().iter().filter(|&&centre| centre < DANCE_LOW_BAND_HZ).count();
+        let lag = period.round() as usize;
+        if low_bands == 0 || lag == 0 || spectrogram.len() <= lag * 2 {
+            return 0.0;
+        }
+
+        let low_energy: Vec<f32> = spectrogram.iter().map(|frame| frame[..low_bands].iter().sum::<f32>()).collect();
+        let mean = low_energy.iter().sum::<f32>() / low_energy.len() as f32;
+        let centered: Vec<f32> = low_energy.iter().map(|&e| e - mean).collect();
+
+        let zero_lag = sum_squares(&centered) / centered.len() as f32;
+        if zero_lag <= 1e-20 {
+            return 0.0;
+        }
+        let sum = dot(&centered[lag..], &centered[..centered.len() - lag]);
+        (sum / (centered.len() - lag) as f32 / zero_lag).clamp(0.0, 1.0)
+    }
+
+    // Danceability (0-1): weighted blend of tempo stability, beat strength and
+    // low-frequency periodicity, in the spirit of streaming-API danceability
+    fn danceability(&self, tempo_stability: f32, beat_strength: f32, low_frequency_periodicity: f32) -> f32 {
+        (0.3 * tempo_stability + 0.35 * beat_strength + 0.35 * low_frequency_periodicity).clamp(0.0, 1.0)
+    }
+
+    // Build-up / drop detection on ~0.5 s windows: a drop is a sudden jump in
+    // both overall and low-band energy spread across most bands, preceded by
+    // a build-up of rising energy (or thinned-out low end). Drop times snap
+    // to the nearest downbeat when one is close
+    fn detect_drops(&self, power: &[f32], spectrogram: &[Vec<f32>], downbeat_times: &[f32]) -> Vec<DropEvent> {
+        let window = ((DROP_WINDOW_SECONDS * self.sample_rate / ONSET_HOP as f32) as usize).max(1);
+        let num_windows = power.len().min(spectrogram.len()) / window;
+        let num_bands = spectrogram.first().map_or(0, |frame| frame.len());
+        let low_bands = self.band_centres().iter().filter(|&&centre| centre < DANCE_LOW_BAND_HZ).count();
+        if num_windows < 4 || num_bands == 0 {
+            return Vec::new();
+        }
+
+        let to_db = |energy: f32| 10.0 * (energy + 1e-10).log10();
+        let mut loudness = Vec::with_capacity(num_windows);
+        let mut low_db = Vec::with_capacity(num_windows);
+        let mut band_db = Vec::with_capacity(num_windows);
+        for w in 0..num_windows {
+            let frames = w * window..(w + 1) * window;
+            loudness.push(to_db(power[frames.clone()].iter().sum::<f32>() / window as f32));
+            let mut bands = vec![0.0; num_bands];
+            for frame in &spectrogram[frames] {
+                for (band, &energy) in frame.iter().enumerate() {
+                    bands[band] += energy / window as f32;
+                }
+            }
+            low_db.push(to_db(bands[..low_bands].iter().sum()));
+            band_db.push(bands.iter().map(|&e| to_db(e)).collect::<Vec<f32>>());
+        }
+
+        let window_seconds = window as f32 * ONSET_HOP as f32 / self.sample_rate;
+        let buildup_windows = (DROP_MIN_BUILDUP_SECONDS / window_seconds).ceil() as usize;
+        let mut drops: Vec<DropEvent> = Vec::new();
+
+        for w in (buildup_windows + 1).max(2)..num_windows {
+            let before = (loudness[w - 1] + loudness[w - 2]) / 2.0;
+            let jump = loudness[w] - before;
+            let low_jump = low_db[w] - (low_db[w - 1] + low_db[w - 2]) / 2.0;
+            let rising_bands = (0..num_bands).filter(|&b| band_db[w][b] - band_db[w - 1][b] >= 3.0).count();
+
+            if jump < DROP_MIN_JUMP_DB || low_jump < DROP_MIN_JUMP_DB || rising_bands * 3 < num_bands * 2 {
+                continue;
+            }
+
+            // Build-up: extend backwards while the energy ramp (least-squares
+            // slope) stays positive, or the low end stayed well below the drop
+            let mut buildup_start = w - buildup_windows;
+            let slope = |start: usize| -> f32 {
+                let segment = &loudness[start..w];
+                let n = segment.len() as f32;
+                let mean_x = (n - 1.0) / 2.0;
+                let mean_y = segment.iter().sum::<f32>() / n;
+                let (mut cov, mut var) = (0.0, 0.0);
+                for (i, &y) in segment.iter().enumerate() {
+                    cov += (i as f32 - mean_x) * (y - mean_y);
+                    var += (i as f32 - mean_x) * (i as f32 - mean_x);
+                }
+                if var > 0.0 { cov / var / window_seconds } else { 0.0 }
+            };
+            let thinned_low_end = low_db[buildup_start..w].iter().all(|&l| l < low_db[w] - DROP_MIN_JUMP_DB);
+            if slope(buildup_start) < DROP_MIN_RAMP_DB_PER_SECOND && !thinned_low_end {
+                continue;
+            }
+            let earliest = w.saturating_sub(buildup_windows * 4);
+            while buildup_start > earliest && slope(buildup_start - 1) >= DROP_MIN_RAMP_DB_PER_SECOND {
+                buildup_start -= 1;
+            }
+
+            let mut time = w as f32 * window_seconds;
+            if let Some(&downbeat) = downbeat_times.iter()
+                .min_by(|a, b| (*a - time).abs().partial_cmp(&(*b - time).abs()).unwrap_or(std::cmp::Ordering::Equal))
+            {
+                if (downbeat - time).abs() <= window_seconds {
+                    time = downbeat;
+                }
+            }
+
+            if drops.last().is_some_and(|last| time - last.time < DROP_MIN_SEPARATION_SECONDS) {
+                continue;
+            }
+            drops.push(DropEvent { time, buildup_start: buildup_start as f32 * window_seconds, jump_db: jump });
+        }
+
+        drops
+    }
+
+    // K-weighted mean square per onset-envelope hop (same K-weighting filter
+    // as the loudness analyzer)
+    fn k_weighted_power(&self, mono: &[f32]) -> Vec<f32> {
+        let mut filter = KWeighting::<f32>::new(self.sample_rate);
+        mono.chunks_exact(ONSET_HOP)
+            .map(|hop| {
+                let mut energy = 0.0;
+                for &sample in hop {
+                    let filtered = filter.process(sample);
+                    energy += filtered * filtered;
+                }
+                energy / ONSET_HOP as f32
+            })
+            .collect()
+    }
+
+    // Aggregate loudness and low/mid/high band energy (dB) per bar, where bar
+    // k runs from downbeat k to downbeat k + 1
+    fn bar_energies(&self, power: &[f32], spectrogram: &[Vec<f32>], beats: &[usize], meter: &MeterEstimate) -> BarSeries {
+        let mut bars = BarSeries::default();
+        if meter.beats_per_bar == 0 {
+            return bars;
+        }
+
+        let centres = self.band_centres();
+        let band_groups: Vec<usize> = centres.iter()
+            .map(|&centre| if centre < BAR_LOW_MID_HZ { 0 } else if centre < BAR_MID_HIGH_HZ { 1 } else { 2 })
+            .collect();
+        let to_db = |energy: f32| 10.0 * (energy + 1e-10).log10();
+
+        let downbeats: Vec<usize> = beats.iter().skip(meter.downbeat_phase).step_by(meter.beats_per_bar).copied().collect();
+        for pair in downbeats.windows(2) {
+            let (start, end) = (pair[0], pair[1].min(power.len()).min(spectrogram.len()));
+            if end <= start {
+                break;
+            }
+
+            let frames = (end - start) as f32;
+            let mean_power = power[start..end].iter().sum::<f32>() / frames;
+            let mut group_energy = [0.0f32; 3];
+            for frame in &spectrogram[start..end] {
+                for (band, &energy) in frame.iter().enumerate() {
+                    group_energy[band_groups[band]] += energy;
+                }
+            }
+
+            bars.start_times.push(frame_to_time(start as f32, self.sample_rate));
+            bars.loudness.push(-0.691 + to_db(mean_power));
+            bars.low_db.push(to_db(group_energy[0] / frames));
+            bars.mid_db.push(to_db(group_energy[1] / frames));
+            bars.high_db.push(to_db(group_energy[2] / frames));
+        }
+
+        bars
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_rhythm)]
+    pub fn analyze_rhythm_js(&self, pcm: &Float32Array, num_channels: usize, on_progress: Option<Function>) -> Result<RhythmResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_rhythm(&pcm.to_vec(), num_channels, callback.as_deref())?)
+    }
+
+    // Analyse samples already written into WASM memory (no copy)
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen]
+    pub fn analyze_rhythm_buffer(&self, buffer: &PcmBuffer, num_channels: usize, on_progress: Option<Function>) -> Result<RhythmResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_rhythm(buffer.as_slice(), num_channels, callback.as_deref())?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = check_click_conformance)]
+    pub fn check_click_conformance_js(&self, pcm: &Float32Array, num_channels: usize, expected_bpm: f32, offset_ms: f32) -> Result<ClickConformanceResult, JsError> {
+        Ok(self.check_click_conformance(&pcm.to_vec(), num_channels, expected_bpm, offset_ms)?)
+    }
+}
+
+impl RhythmAnalyzer {
+    /// Tempo, beat, meter, groove and structure analysis of interleaved PCM;
+    /// `on_progress` receives the percent complete and returns false to cancel
+    pub fn analyze_rhythm(&self, pcm: &[f32], num_channels: usize, on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<RhythmResult, AnalysisError> {
+        validate_pcm(pcm, num_channels, 1)?;
+        let progress = Progress::new(on_progress, self.cancel.as_ref());
+        let result = self.analyze_mono(&MonoFold::new(pcm, num_channels), &progress)?;
+        progress.report(1.0);
+        Ok(result)
+    }
+
+    // Full rhythm analysis of an already downmixed signal and its onset envelope
+    pub(crate) fn analyze_mono(&self, fold: &MonoFold, progress: &Progress) -> Result<RhythmResult, AnalysisError> {
+        let (mono, envelope) = (&fold.samples[..], &fold.envelope[..]);
+        let too_short = (mono.len() as f32) < MIN_RHYTHM_SECONDS * self.sample_rate;
+        let status = MetricStatus::assess(too_short, silent_share(mono, DEFAULT_SILENCE_THRESHOLD), false);
+        progress.checkpoint(0.2)?;
+
+        // Percussiveness section (independent of beat tracking, so ambient
+        // material without a detectable tempo still reports it)
+        let spectrogram = self.band_energy_spectrogram(mono);
+        let (percussiveness, percussive_times, percussive_values) = self.percussiveness(&spectrogram);
+        progress.checkpoint(0.5)?;
+        let percussiveness = PercussivenessResult {
+            global: percussiveness,
+            drum_presence: percussiveness >= DRUM_PRESENCE_THRESHOLD,
+            times: percussive_times,
+            values: percussive_values,
+        };
+
+        let power = self.k_weighted_power(mono);
+
+        let candidates = self.tempo_candidates(envelope);
+        let period = match candidates.first() {
+            Some(candidate) => candidate.period,
+            None => {
+                // Too short or no rhythmic content
+                log::debug!("No tempo candidates in {} samples", mono.len());
+                return Ok(RhythmResult {
+                    tempo: 0.0,
+                    tempo_candidates: Vec::new(),
+                    syncopation: 0.0,
+                    rhythmic_complexity: 0.0,
+                    danceability: 0.0,
+                    beats: Vec::new(),
+                    downbeats: Vec::new(),
+                    time_signature: "unknown".to_string(),
+                    beats_per_bar: 0,
+                    meter_confidence: 0.0,
+                    tempo_curve: TempoCurve::default(),
+                    tempo_stability: 0.0,
+                    tempo_drift: 0.0,
+                    beat_grid: None,
+                    groove: GrooveEstimate { swing_percent: 50.0, swing_ratio: 1.0, microtiming_ms: 0.0, offbeat_onsets: 0 },
+                    quantization: Vec::new(),
+                    bars: BarSeries::default().into(),
+                    drops: self.detect_drops(&power, &spectrogram, &[]),
+                    percussiveness,
+                    status,
+                });
+            }
+        };
+
+        let tempo = 60.0 * self.sample_rate / (period * ONSET_HOP as f32);
+        log::debug!("Tempo {:.1} BPM from {} candidates", tempo, candidates.len());
+        let beats = self.track_beats(envelope, period);
+        let meter = self.estimate_meter(envelope, &beats);
+        progress.checkpoint(0.7)?;
+
+        let beat_times: Vec<f32> = beats.iter().map(|&b| frame_to_time(b as f32, self.sample_rate)).collect();
+        let downbeat_times: Vec<f32> = beat_times.iter()
+            .skip(meter.downbeat_phase)
+            .step_by(meter.beats_per_bar)
+            .copied()
+            .collect();
+
+        let (curve_times, curve_tempos) = self.tempo_curve(&beat_times, meter.beats_per_bar);
+        let tempo_stability = self.tempo_stability(&curve_tempos);
+        let tempo_drift = match (curve_tempos.first(), curve_tempos.last()) {
+            (Some(first), Some(last)) => last - first,
+            _ => 0.0,
+        };
+
+        // Onset positions in fractional envelope frames
+        let onsets: Vec<f32> = pick_onsets(envelope).iter().map(|&t| self.refine_peak(envelope, t)).collect();
+        let groove = self.estimate_groove(&onsets, &beats);
+        let quantization: Vec<QuantizationEstimate> = [2, 4].iter()
+            .map(|&subdivisions| self.quantization_tightness(&onsets, &beats, subdivisions))
+            .collect();
+        let (syncopation, rhythmic_complexity) = self.rhythmic_complexity(&onsets, &beats, &meter);
+        let beat_grid = self.fit_beat_grid(&beat_times);
+        let danceability = self.danceability(
+            tempo_stability,
+            self.beat_strength(envelope, &beats),
+            self.low_frequency_periodicity(&spectrogram, period),
+        );
+        let bars = self.bar_energies(&power, &spectrogram, &beats, &meter);
+        let drops = self.detect_drops(&power, &spectrogram, &downbeat_times);
+        progress.checkpoint(0.95)?;
+
+        // Beat grid section: anchor is the earliest grid downbeat at or after the file start
+        let beat_grid = beat_grid.map(|(grid_start, interval)| {
+            let bar_length = interval * meter.beats_per_bar as f32;
+            let first_downbeat = grid_start + interval * meter.downbeat_phase as f32;
+            let anchor = first_downbeat - (first_downbeat / bar_length).floor() * bar_length;
+            BeatGrid {
+                bpm: 60.0 / interval,
+                first_beat_ms: grid_start * 1000.0,
+                first_downbeat_ms: first_downbeat * 1000.0,
+                anchor_ms: anchor * 1000.0,
+                beats_per_bar: meter.beats_per_bar,
+            }
+        });
+
+        Ok(RhythmResult {
+            tempo,
+            tempo_candidates: candidates.iter()
+                .map(|candidate| TempoCandidateResult { bpm: candidate.bpm, likelihood: candidate.likelihood })
+                .collect(),
+            syncopation,
+            rhythmic_complexity,
+            danceability,
+            beats: beat_times,
+            downbeats: downbeat_times,
+            time_signature: meter.label.to_string(),
+            beats_per_bar: meter.beats_per_bar,
+            meter_confidence: meter.confidence,
+            tempo_curve: TempoCurve { times: curve_times, bpm: curve_tempos },
+            tempo_stability,
+            tempo_drift,
+            beat_grid,
+            groove,
+            quantization: quantization.iter().map(QuantizationResult::from).collect(),
+            bars: bars.into(),
+            drops,
+            percussiveness,
+            status,
+        })
+    }
+
+    /// Compare detected onsets against a click track at `expected_bpm` starting
+    /// at `offset_ms`
+    pub fn check_click_conformance(&self, pcm: &[f32], num_channels: usize, expected_bpm: f32, offset_ms: f32) -> Result<ClickConformanceResult, AnalysisError> {
+        validate_pcm(pcm, num_channels, 1)?;
+        let mono = mix_to_mono(pcm, num_channels);
+        let envelope = onset_envelope(&mono);
+        let onset_times: Vec<f32> = pick_onsets(&envelope).iter()
+            .map(|&t| frame_to_time(self.refine_peak(&envelope, t), self.sample_rate))
+            .collect();
+
+        let duration = mono.len() as f32 / self.sample_rate;
+        let deviations = self.click_deviations(&onset_times, expected_bpm, offset_ms / 1000.0, duration);
+        let matched: Vec<f32> = deviations.iter().flatten().copied().collect();
+
+        let mean_deviation = if matched.is_empty() { 0.0 } else { matched.iter().sum::<f32>() / matched.len() as f32 };
+        let std_deviation = if matched.is_empty() {
+            0.0
+        } else {
+            (matched.iter().map(|&d| (d - mean_deviation) * (d - mean_deviation)).sum::<f32>() / matched.len() as f32).sqrt()
+        };
+        let max_deviation = matched.iter().fold(0.0f32, |acc, &d| acc.max(d.abs()));
+        let within_tolerance = matched.iter().filter(|&&d| d.abs() <= CLICK_TOLERANCE_MS).count();
+        let conformance = if deviations.is_empty() { 0.0 } else { within_tolerance as f32 / deviations.len() as f32 };
+
+        Ok(ClickConformanceResult {
+            expected_beats: deviations.len(),
+            matched_beats: matched.len(),
+            missing_beats: deviations.len() - matched.len(),
+            mean_deviation_ms: mean_deviation,
+            std_deviation_ms: std_deviation,
+            max_deviation_ms: max_deviation,
+            tolerance_ms: CLICK_TOLERANCE_MS,
+            conformance,
+            conforms: conformance >= CLICK_CONFORMANCE_THRESHOLD,
+            deviations_ms: deviations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+
+    // Click track with an accented click every `accent_every` beats
+    fn click_track(bpm: f32, beats: usize, accent_every: usize) -> Vec<f32> {
+        let beat_samples = (60.0 / bpm * SAMPLE_RATE) as usize;
+        let mut samples = vec![0.0; beat_samples * beats];
+        for beat in 0..beats {
+            let amplitude = if beat % accent_every == 0 { 0.9 } else { 0.3 };
+            let start = beat * beat_samples;
+            for i in 0..400 {
+                let decay = (-(i as f32) / 80.0).exp();
+                samples[start + i] = amplitude * decay * if i % 2 == 0 { 1.0 } else { -1.0 };
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn detects_tempo_and_common_time() {
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        let envelope = onset_envelope(&click_track(120.0, 64, 4));
+        let period = analyzer.tempo_candidates(&envelope)[0].period;
+        let tempo = 60.0 * SAMPLE_RATE / (period * ONSET_HOP as f32);
+        assert!((tempo - 120.0).abs() < 2.0, "tempo {}", tempo);
+
+        let beats = analyzer.track_beats(&envelope, period);
+        let meter = analyzer.estimate_meter(&envelope, &beats);
+        assert_eq!(meter.label, "4/4");
+    }
+
+    #[test]
+    fn straight_sixteenths_do_not_swing() {
+        // Onsets on every 16th of 500-frame beats, then swung eighths (2:1)
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        let beats: Vec<usize> = (0..=16).map(|beat| beat * 500).collect();
+        let sixteenths: Vec<f32> = (0..64).map(|i| i as f32 * 125.0).collect();
+        let groove = analyzer.estimate_groove(&sixteenths, &beats);
+        assert!((groove.swing_percent - 50.0).abs() < 1.0, "{}", groove.swing_percent);
+        assert_eq!(groove.offbeat_onsets, 16);
+
+        let swung: Vec<f32> = (0..16).flat_map(|beat| [beat as f32 * 500.0, beat as f32 * 500.0 + 333.0]).collect();
+        let groove = analyzer.estimate_groove(&swung, &beats);
+        assert!((groove.swing_percent - 66.6).abs() < 1.0, "{}", groove.swing_percent);
+    }
+
+    #[test]
+    fn detects_triple_meter() {
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        let envelope = onset_envelope(&click_track(120.0, 60, 3));
+        let period = analyzer.tempo_candidates(&envelope)[0].period;
+        let beats = analyzer.track_beats(&envelope, period);
+        let meter = analyzer.estimate_meter(&envelope, &beats);
+        assert_eq!(meter.label, "3/4");
+        assert_eq!(meter.beats_per_bar, 3);
+    }
+
+    #[test]
+    fn genre_hint_resolves_tempo_octave() {
+        // Quarter-note kicks at 87 BPM with eighth-note hats in between
+        let mut samples = click_track(87.0, 48, 4);
+        let beat_samples = (60.0 / 87.0 * SAMPLE_RATE) as usize;
+        for beat in 0..47 {
+            let start = beat * beat_samples + beat_samples / 2;
+            for i in 0..200 {
+                samples[start + i] += 0.5 * (-(i as f32) / 40.0).exp() * if i % 2 == 0 { 1.0 } else { -1.0 };
+            }
+        }
+        let envelope = onset_envelope(&samples);
+
+        let mut analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        let candidates = analyzer.tempo_candidates(&envelope);
+        assert!(candidates.len() >= 2);
+        assert!(candidates.iter().any(|c| (c.bpm - 174.0).abs() < 3.0));
+
+        assert!(analyzer.set_genre_hint("Drum & Bass"));
+        let preferred = analyzer.tempo_candidates(&envelope);
+        assert!((preferred[0].bpm - 174.0).abs() < 3.0, "tempo {}", preferred[0].bpm);
+    }
+
+    #[test]
+    fn tempo_curve_follows_drifting_beats() {
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        let steady: Vec<f32> = (0..64).map(|beat| beat as f32 * 0.5).collect();
+        let (times, tempos) = analyzer.tempo_curve(&steady, 4);
+        // One point per inter-beat interval, at its centre
+        assert_eq!(tempos.len(), 63);
+        assert!((times[0] - 0.25).abs() < 1e-4, "first interval centre {}", times[0]);
+        assert!(tempos.iter().all(|&bpm| (bpm - 120.0).abs() < 0.01));
+        assert!((analyzer.tempo_stability(&tempos) - 1.0).abs() < 1e-4);
+
+        // Accelerating from 100 to about 140 BPM
+        let mut drifting = vec![0.0f32];
+        for beat in 1..64 {
+            let bpm = 100.0 + 40.0 * beat as f32 / 64.0;
+            drifting.push(drifting[beat - 1] + 60.0 / bpm);
+        }
+        // A mistracked beat (one interval halved, the next stretched) is smoothed out
+        drifting[40] -= 0.25;
+        let (_, tempos) = analyzer.tempo_curve(&drifting, 4);
+        assert!(tempos.windows(2).all(|pair| (pair[1] - pair[0]).abs() < 3.0), "{:?}", tempos);
+        assert!(tempos[0] < 115.0 && tempos[tempos.len() - 1] > 125.0, "{:?}", tempos);
+        assert!(analyzer.tempo_stability(&tempos) < 0.75, "{}", analyzer.tempo_stability(&tempos));
+    }
+
+    #[test]
+    fn offbeat_onsets_are_syncopated() {
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        let meter = MeterEstimate { label: "4/4", beats_per_bar: 4, downbeat_phase: 0, confidence: 1.0 };
+        let beats: Vec<usize> = (0..=16).map(|beat| beat * 500).collect();
+
+        let on_beat: Vec<f32> = (0..16).map(|beat| beat as f32 * 500.0).collect();
+        assert_eq!(analyzer.rhythmic_complexity(&on_beat, &beats, &meter), (0.0, 0.0));
+
+        // Off-beat eighths resting on beats 2, 3 and 4: 1 + 2 + 1 per bar
+        let off_beat: Vec<f32> = (0..16).map(|beat| beat as f32 * 500.0 + 250.0).collect();
+        let (syncopation, complexity) = analyzer.rhythmic_complexity(&off_beat, &beats, &meter);
+        assert!((syncopation - 4.0).abs() < 1e-6, "{}", syncopation);
+        assert!((complexity - (1.0 - (-1.0f32).exp())).abs() < 1e-6, "{}", complexity);
+    }
+
+    #[test]
+    fn beat_grid_fits_through_jittered_beats() {
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        assert!(analyzer.fit_beat_grid(&[1.0]).is_none());
+        assert!(analyzer.fit_beat_grid(&[1.0, 1.0, 1.0]).is_none());
+
+        // 120 BPM from 250 ms with ±10 ms tracking jitter
+        let beat_times: Vec<f32> = (0..32)
+            .map(|beat| 0.25 + beat as f32 * 0.5 + if beat % 2 == 0 { 0.01 } else { -0.01 })
+            .collect();
+        let (start, interval) = analyzer.fit_beat_grid(&beat_times).unwrap();
+        assert!((interval - 0.5).abs() < 1e-3, "interval {}", interval);
+        assert!((start - 0.25).abs() < 0.01, "start {}", start);
+    }
+
+    #[test]
+    fn late_onsets_loosen_quantization() {
+        // ~120 BPM beats of 43 envelope frames (one frame is ~11.6 ms)
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        let beats: Vec<usize> = (0..=16).map(|beat| beat * 43).collect();
+        let frame_ms = frame_to_time(1.0, SAMPLE_RATE) * 1000.0;
+
+        let on_grid: Vec<f32> = (0..16).map(|beat| beat as f32 * 43.0).collect();
+        let tight = analyzer.quantization_tightness(&on_grid, &beats, 4);
+        assert_eq!(tight.tightness, 1.0);
+        assert_eq!(tight.histogram[QUANTIZATION_HISTOGRAM_BINS / 2], 16);
+
+        let late: Vec<f32> = on_grid.iter().map(|&onset| onset + 1.0).collect();
+        let loose = analyzer.quantization_tightness(&late, &beats, 4);
+        assert!((loose.mean_abs_deviation_ms - frame_ms).abs() < 0.01, "{}", loose.mean_abs_deviation_ms);
+        assert!(loose.tightness < 0.7, "{}", loose.tightness);
+        assert_eq!(loose.histogram[(QUANTIZATION_HISTOGRAM_BINS / 2) + 2], 16);
+    }
+
+    #[test]
+    fn bars_run_between_downbeats() {
+        // Downbeats on every fourth beat from the second: bars over frames 10-50 and 50-90
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        let meter = MeterEstimate { label: "4/4", beats_per_bar: 4, downbeat_phase: 1, confidence: 1.0 };
+        let beats: Vec<usize> = (0..=12).map(|beat| beat * 10).collect();
+        let bands = analyzer.band_centres().len();
+
+        // Quiet bass bar, then a louder bar with all energy in the top band
+        let power: Vec<f32> = (0..100).map(|frame| if frame < 50 { 0.01 } else { 0.1 }).collect();
+        let spectrogram: Vec<Vec<f32>> = (0..100)
+            .map(|frame| {
+                let mut energies = vec![0.0; bands];
+                energies[if frame < 50 { 0 } else { bands - 1 }] = 1.0;
+                energies
+            })
+            .collect();
+
+        let bars = analyzer.bar_energies(&power, &spectrogram, &beats, &meter);
+        assert_eq!(bars.start_times, vec![frame_to_time(10.0, SAMPLE_RATE), frame_to_time(50.0, SAMPLE_RATE)]);
+        assert!((bars.loudness[0] + 20.691).abs() < 1e-3, "{}", bars.loudness[0]);
+        assert!((bars.loudness[1] + 10.691).abs() < 1e-3, "{}", bars.loudness[1]);
+        assert!(bars.low_db[0].abs() < 1e-3 && bars.high_db[0] < -90.0);
+        assert!(bars.high_db[1].abs() < 1e-3 && bars.low_db[1] < -90.0);
+        assert!(bars.mid_db.iter().all(|&db| db < -90.0));
+    }
+
+    #[test]
+    fn kicks_on_the_beat_are_danceable() {
+        // 60 Hz kicks at 120 BPM against noise of about the same level
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        let beat_samples = (0.5 * SAMPLE_RATE) as usize;
+        let kicks: Vec<f32> = (0..32 * beat_samples)
+            .map(|i| {
+                let phase = (i % beat_samples) as f32;
+                0.8 * (-phase / 2000.0).exp() * (2.0 * std::f32::consts::PI * 60.0 * phase / SAMPLE_RATE).sin()
+            })
+            .collect();
+        let mut state = 12345u32;
+        let noise: Vec<f32> = (0..kicks.len())
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 24) as f32 * 0.6 - 0.3
+            })
+            .collect();
+
+        let period = beat_samples as f32 / ONSET_HOP as f32;
+        let kick_periodicity = analyzer.low_frequency_periodicity(&analyzer.band_energy_spectrogram(&kicks), period);
+        let noise_periodicity = analyzer.low_frequency_periodicity(&analyzer.band_energy_spectrogram(&noise), period);
+        assert!(kick_periodicity > 0.5, "kicks {}", kick_periodicity);
+        assert!(noise_periodicity < 0.1, "noise {}", noise_periodicity);
+
+        assert_eq!(analyzer.danceability(1.0, 1.0, 1.0), 1.0);
+        assert_eq!(analyzer.danceability(0.0, 0.0, 0.0), 0.0);
+        let kick_score = analyzer.danceability(1.0, 0.5, kick_periodicity);
+        let noise_score = analyzer.danceability(1.0, 0.5, noise_periodicity);
+        assert!(kick_score - noise_score > 0.15, "kicks {} noise {}", kick_score, noise_score);
+    }
+
+    #[test]
+    fn clicks_are_more_percussive_than_tones() {
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        let clicks = click_track(120.0, 16, 4);
+        let tone: Vec<f32> = (0..clicks.len())
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / SAMPLE_RATE).sin())
+            .collect();
+
+        let (click_ratio, _, _) = analyzer.percussiveness(&analyzer.band_energy_spectrogram(&clicks));
+        let (tone_ratio, _, _) = analyzer.percussiveness(&analyzer.band_energy_spectrogram(&tone));
+        assert!(click_ratio > DRUM_PRESENCE_THRESHOLD, "clicks {}", click_ratio);
+        assert!(tone_ratio < 0.1, "tone {}", tone_ratio);
+    }
+
+    #[test]
+    fn click_deviations_measure_offset_and_gaps() {
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        // Onsets 10 ms late at 120 BPM, with the fourth click missing
+        let onsets = [0.51, 1.01, 1.51, 2.51];
+        let deviations = analyzer.click_deviations(&onsets, 120.0, 0.5, 3.0);
+
+        assert_eq!(deviations.len(), 5);
+        assert!((deviations[0].unwrap() - 10.0).abs() < 0.01);
+        assert!(deviations[3].is_none());
+        assert!((deviations[4].unwrap() - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn detects_drop_after_buildup() {
+        let analyzer = RhythmAnalyzer::new(SAMPLE_RATE);
+        let tone = |freq: f32, i: usize| (2.0 * std::f32::consts::PI * freq * i as f32 / SAMPLE_RATE).sin();
+
+        // 8 s high-passed riser getting louder, then a full-band drop at 8 s
+        let riser_len = (8.0 * SAMPLE_RATE) as usize;
+        let drop_len = (4.0 * SAMPLE_RATE) as usize;
+        let mut samples = Vec::with_capacity(riser_len + drop_len);
+        for i in 0..riser_len {
+            let gain = 0.01 + 0.04 * i as f32 / riser_len as f32;
+            samples.push(gain * tone(3000.0, i));
+        }
+        for i in 0..drop_len {
+            let n = riser_len + i;
+            let partials = [55.0, 110.0, 220.0, 440.0, 880.0, 1760.0, 3520.0, 7040.0];
+            samples.push(partials.iter().map(|&f| 0.08 * tone(f, n)).sum());
+        }
+
+        let power = analyzer.k_weighted_power(&samples);
+        let spectrogram = analyzer.band_energy_spectrogram(&samples);
+        let drops = analyzer.detect_drops(&power, &spectrogram, &[]);
+        assert_eq!(drops.len(), 1);
+        assert!((drops[0].time - 8.0).abs() <= 0.5, "drop at {}", drops[0].time);
+        assert!(drops[0].buildup_start < drops[0].time);
+    }
+}
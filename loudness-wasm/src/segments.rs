@@ -0,0 +1,105 @@
+// Per-segment analysis for chapter QC (audiobooks, podcasts): the programme is
+// split at chapter or CUE start times and every segment gets its own loudness,
+// true peak and edge silence, next to the programme totals. Each segment is
+// gated on its own, as a chapter delivered as a separate file would be.
+//
+//     const { segments, programme } = analyzer.analyze_segments(pcm, [0, 312.5, 1045.2]);
+
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::error::AnalysisError;
+use crate::limits::MetricStatus;
+use crate::loudness::LoudnessAnalyzer;
+use crate::progress::Progress;
+use crate::technical::TechnicalAnalyzer;
+use crate::utils::db_to_amplitude;
+
+/// Measurements of one segment (times in seconds from the programme start)
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct SegmentResult {
+    pub start: f32,
+    pub end: f32,
+    pub integrated: f32,
+    // Maximum momentary and short-term loudness
+    pub momentary: f32,
+    pub short_term: f32,
+    pub true_peak: f32,
+    pub leading_silence: f32,
+    pub trailing_silence: f32,
+    // Trust flag of the integrated loudness (short chapters are flagged)
+    pub status: MetricStatus,
+}
+
+/// Every segment in order, plus the whole programme measured as one
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct SegmentedResult {
+    pub segments: Vec<SegmentResult>,
+    pub programme: SegmentResult,
+}
+
+/// Frame ranges of the segments starting at `boundaries` (seconds). The
+/// programme start is always a boundary; times outside the programme or not
+/// finite are ignored and duplicates merged.
+pub(crate) fn segment_ranges(boundaries: &[f32], frames: usize, sample_rate: f32) -> Vec<(usize, usize)> {
+    let mut starts: Vec<usize> = boundaries.iter()
+        .filter(|time| time.is_finite() && **time >= 0.0)
+        .map(|&time| (time as f64 * sample_rate as f64).round() as usize)
+        .filter(|&start| start < frames)
+        .chain([0])
+        .collect();
+    starts.sort_unstable();
+    starts.dedup();
+    starts.iter().zip(starts.iter().skip(1).chain([&frames])).map(|(&start, &end)| (start, end)).collect()
+}
+
+// The analyzers and settings every segment is measured with
+pub(crate) struct SegmentMeter<'a> {
+    pub loudness: &'a LoudnessAnalyzer,
+    pub technical: &'a TechnicalAnalyzer,
+    pub num_channels: usize,
+    pub sample_rate: f32,
+    pub silence_threshold: f32,
+}
+
+impl SegmentMeter<'_> {
+    /// Measure interleaved `pcm` starting `start_frame` frames into the programme
+    pub(crate) fn measure(&self, pcm: &[f32], start_frame: usize, progress: &Progress) -> Result<SegmentResult, AnalysisError> {
+        let measured = self.loudness.analyze_samples(pcm, &progress.stage(0.0, 0.8))?;
+        let true_peak = self.technical.calculate_true_peak(pcm).0;
+        progress.stage(0.8, 1.0).checkpoint(1.0)?;
+
+        // Edge silence in whole frames: a frame is audible when any channel is
+        let frames = pcm.len() / self.num_channels;
+        let threshold = db_to_amplitude(self.silence_threshold);
+        let audible = |frame: &[f32]| frame.iter().any(|sample| sample.abs() > threshold);
+        let leading = pcm.chunks_exact(self.num_channels).position(audible).unwrap_or(frames);
+        let trailing = pcm.chunks_exact(self.num_channels).rev().position(audible).unwrap_or(frames);
+
+        Ok(SegmentResult {
+            start: start_frame as f32 / self.sample_rate,
+            end: (start_frame + frames) as f32 / self.sample_rate,
+            integrated: measured.integrated,
+            momentary: measured.momentary,
+            short_term: measured.short_term,
+            true_peak,
+            leading_silence: leading as f32 / self.sample_rate,
+            trailing_silence: trailing as f32 / self.sample_rate,
+            status: measured.status.integrated,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_cover_the_programme_in_order() {
+        assert_eq!(segment_ranges(&[2.0, 0.5, f32::NAN, 0.5, 9.0, -1.0], 300, 100.0), [(0, 50), (50, 200), (200, 300)]);
+        assert_eq!(segment_ranges(&[], 300, 100.0), [(0, 300)]);
+    }
+}
@@ -0,0 +1,203 @@
+// Time series kept in WASM memory and handed to JS a page at a time, so long
+// analyses never materialise one giant array on the JS heap. Band series
+// (spectrograms) can instead be rendered to canvas-ready RGBA pixels.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::colormap::Colormap;
+use crate::utils::{ema_smooth, median_smooth};
+
+/// Frames `offset..offset + frames` of a time series, `width` values per
+/// frame (row-major); frame `i` starts at `i * hop_seconds`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct SeriesPage {
+    pub offset: usize,
+    pub frames: usize,
+    pub total_frames: usize,
+    pub width: usize,
+    pub start_time: f32,
+    pub hop_seconds: f32,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub values: Vec<f32>,
+}
+
+// Dynamic range of rendered images when none is given
+const DEFAULT_RENDER_RANGE_DB: f32 = 80.0;
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeSeries {
+    hop_seconds: f32,
+    width: usize,
+    values: Vec<f32>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl TimeSeries {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn total_frames(&self) -> usize {
+        self.values.len() / self.width
+    }
+
+    // Values per frame (1 for scalar series, one per band for spectrograms)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn hop_seconds(&self) -> f32 {
+        self.hop_seconds
+    }
+
+    // At most `limit` frames starting at frame `offset`; past the end the page is empty
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn page(&self, offset: usize, limit: usize) -> SeriesPage {
+        let total_frames = self.total_frames();
+        let offset = offset.min(total_frames);
+        let frames = limit.min(total_frames - offset);
+        SeriesPage {
+            offset,
+            frames,
+            total_frames,
+            width: self.width,
+            start_time: offset as f32 * self.hop_seconds,
+            hop_seconds: self.hop_seconds,
+            values: self.values[offset * self.width..(offset + frames) * self.width].to_vec(),
+        }
+    }
+
+    // The series smoothed per band for display: a two-way exponential average
+    // with a time constant of `seconds` (no delay, -Infinity restarts it)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn smooth_ema(&self, seconds: f32) -> TimeSeries {
+        let time_constant = seconds / self.hop_seconds;
+        self.map_bands(|band| ema_smooth(band, time_constant))
+    }
+
+    // The series with each value replaced by the median of the frames within
+    // `seconds` either side, per band
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn smooth_median(&self, seconds: f32) -> TimeSeries {
+        let radius = (seconds / self.hop_seconds).round().max(0.0) as usize;
+        self.map_bands(|band| median_smooth(band, radius))
+    }
+
+    // The series as a `width` x `height` RGBA image (row-major, top row first)
+    // for `new ImageData(new Uint8ClampedArray(pixels.buffer), width, height)`:
+    // time runs left to right and bands bottom to top. Values are energies
+    // shown in dB, spanning the colormap from `range_db` below the loudest
+    // value (80 dB when not positive) up to it. Each pixel column takes the loudest of the
+    // frames it covers, so short transients survive downscaling.
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn render_rgba(&self, width: usize, height: usize, colormap: Colormap, range_db: f32) -> Vec<u8> {
+        let total_frames = self.total_frames();
+        let to_db = |energy: f32| 10.0 * (energy + 1e-12).log10();
+        let ceiling = self.values.iter().copied().map(to_db).fold(f32::NEG_INFINITY, f32::max);
+        let range_db = if range_db > 0.0 { range_db } else { DEFAULT_RENDER_RANGE_DB };
+
+        // Column `x` covers frames first..last (at least one when there are any)
+        let columns: Vec<(usize, usize)> = (0..width)
+            .map(|x| {
+                let first = x * total_frames / width;
+                (first, ((x + 1) * total_frames / width).max(first + 1).min(total_frames))
+            })
+            .collect();
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            let band = (height - 1 - y) * self.width / height;
+            for &(first, last) in &columns {
+                let energy = (first..last).map(|frame| self.values[frame * self.width + band]).fold(0.0, f32::max);
+                let position = if total_frames == 0 { 0.0 } else { 1.0 - (ceiling - to_db(energy)) / range_db };
+                pixels.extend_from_slice(&colormap.rgba(position));
+            }
+        }
+        pixels
+    }
+}
+
+impl TimeSeries {
+    pub(crate) fn new(hop_seconds: f32, width: usize, values: Vec<f32>) -> Self {
+        let width = width.max(1);
+        debug_assert!(values.len().is_multiple_of(width));
+        TimeSeries { hop_seconds, width, values }
+    }
+
+    /// Every value, row-major
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    // The same series with every band's values passed through `smooth`
+    fn map_bands(&self, smooth: impl Fn(&[f32]) -> Vec<f32>) -> TimeSeries {
+        let mut values = self.values.clone();
+        for band in 0..self.width {
+            let column: Vec<f32> = self.values.iter().skip(band).step_by(self.width).copied().collect();
+            values.iter_mut().skip(band).step_by(self.width).zip(smooth(&column)).for_each(|(value, smoothed)| *value = smoothed);
+        }
+        TimeSeries::new(self.hop_seconds, self.width, values)
+    }
+
+    /// At most `max_frames` frames, each the maximum of the consecutive
+    /// frames it replaces (so peaks survive); the hop grows to match
+    pub fn downsample(&self, max_frames: usize) -> TimeSeries {
+        let factor = self.total_frames().div_ceil(max_frames.max(1)).max(1);
+        if factor == 1 {
+            return self.clone();
+        }
+        let values = self.values
+            .chunks(factor * self.width)
+            .flat_map(|group| (0..self.width).map(move |band| {
+                group.iter().skip(band).step_by(self.width).copied().fold(f32::NEG_INFINITY, f32::max)
+            }))
+            .collect();
+        TimeSeries::new(self.hop_seconds * factor as f32, self.width, values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_cover_the_series() {
+        let series = TimeSeries::new(0.5, 2, (0..10).map(|i| i as f32).collect());
+        assert_eq!(series.total_frames(), 5);
+
+        let page = series.page(3, 4);
+        assert_eq!((page.offset, page.frames, page.start_time), (3, 2, 1.5));
+        assert_eq!(page.values, vec![6.0, 7.0, 8.0, 9.0]);
+        assert_eq!(series.page(7, 4).frames, 0);
+
+        let coarse = series.downsample(2);
+        assert_eq!((coarse.hop_seconds(), coarse.values()), (1.5, &[4.0, 5.0, 8.0, 9.0][..]));
+        assert_eq!(series.downsample(5), series);
+
+        // Smoothing runs down each band separately
+        let smoothed = series.smooth_median(0.5);
+        assert_eq!(smoothed.values(), series.values());
+        let steps = TimeSeries::new(0.5, 2, vec![0.0, 10.0, 9.0, 10.0, 0.0, 10.0]).smooth_median(0.5);
+        assert_eq!(steps.values(), &[0.0, 10.0, 0.0, 10.0, 0.0, 10.0][..]);
+        assert_eq!(series.smooth_ema(0.0), series);
+    }
+
+    #[test]
+    fn render_maps_time_across_and_bands_up() {
+        // Two frames of two bands: only the high band of the second frame is loud
+        let series = TimeSeries::new(0.5, 2, vec![0.0, 0.0, 0.0, 1.0]);
+        let pixels = series.render_rgba(4, 2, Colormap::Grayscale, 60.0);
+        assert_eq!(pixels.len(), 4 * 2 * 4);
+
+        let level = |x: usize, y: usize| pixels[(y * 4 + x) * 4];
+        assert_eq!([level(0, 0), level(1, 0), level(2, 0), level(3, 0)], [0, 0, 255, 255]);
+        assert!((0..4).all(|x| level(x, 1) == 0));
+        assert_eq!(pixels[3], 255);
+        assert_eq!(TimeSeries::new(0.5, 2, vec![]).render_rgba(2, 2, Colormap::Viridis, 60.0).len(), 16);
+    }
+}
@@ -0,0 +1,142 @@
+// Saved analysis sessions: the complete result plus downsampled time series
+// (loudness history, spectrogram) in one JSON report of kind "session", so the
+// UI can reopen a file it analysed before without touching the audio again.
+// The result is kept as the JSON document it was saved as, the same plain
+// object JS receives from `analyze`; series come back as `TimeSeries`.
+//
+//     const session = analyzer.analyze_session(pcm);
+//     localStorage.setItem(id, session.save());
+//     const reopened = Session.load(localStorage.getItem(id));
+//     render(reopened.result(), reopened.series("momentary"));
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::analyzer::AnalysisResult;
+use crate::error::AnalysisError;
+use crate::json::{read_report, report, to_value};
+use crate::series::TimeSeries;
+
+// Frames kept per series: enough for a full-width overview on any screen
+pub const SESSION_SERIES_FRAMES: usize = 2000;
+
+// A series as stored; non-finite values (silence as -inf) are written as null
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StoredSeries {
+    name: String,
+    hop_seconds: f32,
+    width: usize,
+    #[serde(deserialize_with = "nulls_as_silence")]
+    values: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StoredSession {
+    pub(crate) result: serde_json::Value,
+    pub(crate) series: Vec<StoredSeries>,
+}
+
+fn nulls_as_silence<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f32>, D::Error> {
+    let values = Vec::<Option<f32>>::deserialize(deserializer)?;
+    Ok(values.into_iter().map(|value| value.unwrap_or(f32::NEG_INFINITY)).collect())
+}
+
+#[derive(Clone)]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct Session {
+    result: serde_json::Value,
+    series: Vec<(String, TimeSeries)>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl Session {
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = save)]
+    pub fn save_js(&self) -> Result<String, JsError> {
+        Ok(self.save()?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = load)]
+    pub fn load_js(json: &str) -> Result<Session, JsError> {
+        Ok(Session::load(json)?)
+    }
+
+    // The saved analysis result, as `Analyzer.analyze` returned it
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = result)]
+    pub fn result_js(&self) -> Result<JsValue, JsError> {
+        Ok(self.result.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = series)]
+    pub fn series_js(&self, name: &str) -> Option<TimeSeries> {
+        self.series(name).cloned()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn series_names(&self) -> Vec<String> {
+        self.series.iter().map(|(name, _)| name.clone()).collect()
+    }
+}
+
+impl Session {
+    pub fn new(result: &AnalysisResult) -> Result<Self, AnalysisError> {
+        Ok(Session { result: to_value(result)?, series: Vec::new() })
+    }
+
+    /// The session as a versioned JSON report
+    pub fn save(&self) -> Result<String, AnalysisError> {
+        report("session", &self.stored())
+    }
+
+    /// Keep `series` under `name`, downsampled to `SESSION_SERIES_FRAMES`;
+    /// replaces a series of the same name
+    pub fn add_series(&mut self, name: &str, series: &TimeSeries) {
+        let series = series.downsample(SESSION_SERIES_FRAMES);
+        match self.series.iter_mut().find(|(existing, _)| existing == name) {
+            Some((_, existing)) => *existing = series,
+            None => self.series.push((name.to_string(), series)),
+        }
+    }
+
+    /// Reopen a session written by `save`
+    pub fn load(json: &str) -> Result<Self, AnalysisError> {
+        Session::from_stored(read_report("session", json)?)
+    }
+
+    // The session as written to a report
+    pub(crate) fn stored(&self) -> StoredSession {
+        let series = self.series.iter()
+            .map(|(name, series)| StoredSeries {
+                name: name.clone(),
+                hop_seconds: series.hop_seconds(),
+                width: series.width(),
+                values: series.values().to_vec(),
+            })
+            .collect();
+        StoredSession { result: self.result.clone(), series }
+    }
+
+    pub(crate) fn from_stored(stored: StoredSession) -> Result<Self, AnalysisError> {
+        let series = stored.series.into_iter()
+            .map(|stored| {
+                if stored.width == 0 || !stored.values.len().is_multiple_of(stored.width) {
+                    return Err(AnalysisError::InvalidReport { reason: "series values do not fill whole frames" });
+                }
+                Ok((stored.name, TimeSeries::new(stored.hop_seconds, stored.width, stored.values)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Session { result: stored.result, series })
+    }
+
+    /// The saved analysis result as JSON (non-finite figures are null)
+    pub fn result(&self) -> &serde_json::Value {
+        &self.result
+    }
+
+    pub fn series(&self, name: &str) -> Option<&TimeSeries> {
+        self.series.iter().find(|(existing, _)| existing == name).map(|(_, series)| series)
+    }
+}
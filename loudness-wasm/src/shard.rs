@@ -0,0 +1,220 @@
+// Sharded analysis for worker pools: a long file is split into frame ranges,
+// each Web Worker runs `analyze_shard` on its range, and `merge` combines the
+// partial statistics into one result. Each shard also reads a lead-in the
+// K-weighting filters settle over before its first block, so with hop-aligned
+// shards and enough lookahead every block is computed once and the merged
+// loudness matches a single-pass analysis to within the filters' rounding.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::AnalyzerConfig;
+use crate::constants::*;
+use crate::error::{validate_pcm, AnalysisError};
+use crate::loudness::{absolute_gated, block_energies, LoudnessAnalyzer, LoudnessResult, Pcm, SEGMENT};
+use crate::technical::TechnicalAnalyzer;
+
+// Shard starts fall on multiples of both block hops (short-term hop = 3 momentary hops)
+const SHARD_ALIGN: usize = SHORT_TERM_HOP;
+// Frames read past a shard's end so its last blocks (and peak interpolation) complete
+const SHARD_LOOKAHEAD: usize = SHORT_TERM_BLOCK_SIZE;
+// Frames read before a shard's start for the K-weighting to settle over (a
+// whole number of energy segments)
+const SHARD_LEAD_IN: usize = MOMENTARY_BLOCK_SIZE;
+
+/// Frames `start..end` are owned by the shard; `read_start..read_end` must be
+/// passed to `analyze_shard`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, from_wasm_abi))]
+pub struct ShardRange {
+    pub read_start: usize,
+    pub start: usize,
+    pub end: usize,
+    pub read_end: usize,
+}
+
+/// Frame ranges covering a file, in order
+#[derive(Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct ShardPlan {
+    pub shards: Vec<ShardRange>,
+}
+
+/// Partial statistics of one shard, posted back from its worker
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, from_wasm_abi))]
+pub struct ShardStats {
+    pub start: usize,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub head: Vec<f32>,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub momentary_energies: Vec<f32>,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub short_term_energies: Vec<f32>,
+    pub true_peak: f32,
+    pub clipped_samples: u32,
+    pub sample_sum: f64,
+    pub sample_count: usize,
+}
+
+/// Every shard's statistics, in any order
+#[derive(Deserialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(from_wasm_abi))]
+pub struct ShardSet {
+    pub shards: Vec<ShardStats>,
+}
+
+/// Combined result of a sharded analysis
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct ShardedResult {
+    pub loudness: LoudnessResult,
+    pub true_peak: f32,
+    pub clipped_samples: u32,
+    pub clipping_percentage: f32,
+    pub dc_offset: f32,
+    pub shard_count: usize,
+}
+
+// Split `total_frames` into at most `num_shards` hop-aligned ranges
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub fn plan_shards(total_frames: usize, num_shards: usize) -> ShardPlan {
+    let per_shard = total_frames.div_ceil(num_shards.max(1)).next_multiple_of(SHARD_ALIGN).max(SHARD_ALIGN);
+    let shards = (0..total_frames)
+        .step_by(per_shard)
+        .map(|start| {
+            let end = (start + per_shard).min(total_frames);
+            ShardRange { read_start: start.saturating_sub(SHARD_LEAD_IN), start, end, read_end: (end + SHARD_LOOKAHEAD).min(total_frames) }
+        })
+        .collect();
+    ShardPlan { shards }
+}
+
+// Shard boundaries sit on 44.1kHz block hops, so shards are not resampled:
+// resample other rates before planning (the single-pass analyzers do it
+// internally)
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct ShardAnalyzer {
+    num_channels: usize,
+    loudness: LoudnessAnalyzer,
+    technical: TechnicalAnalyzer,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl ShardAnalyzer {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        ShardAnalyzer::from_config(&AnalyzerConfig::new(sample_rate, num_channels))
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        ShardAnalyzer {
+            num_channels: config.num_channels(),
+            loudness: LoudnessAnalyzer::from_config(config),
+            technical: TechnicalAnalyzer::from_config(config),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_shard)]
+    pub fn analyze_shard_js(&self, pcm: &Float32Array, range: ShardRange) -> Result<ShardStats, JsError> {
+        Ok(self.analyze_shard(&pcm.to_vec(), range)?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = merge)]
+    pub fn merge_js(&self, set: ShardSet) -> Result<ShardedResult, JsError> {
+        Ok(self.merge(&set.shards)?)
+    }
+}
+
+impl ShardAnalyzer {
+    /// Partial statistics of one shard; `pcm` holds the interleaved frames
+    /// `range.read_start..range.read_end`
+    pub fn analyze_shard(&self, pcm: &[f32], range: ShardRange) -> Result<ShardStats, AnalysisError> {
+        let required = range.read_end.saturating_sub(range.read_start);
+        validate_pcm(pcm, self.num_channels, required)?;
+
+        let channels = self.num_channels;
+        let lead_in = range.start.saturating_sub(range.read_start);
+        let segments = self.loudness.segment_energies(Pcm::Single(pcm));
+        let segments = &segments[(lead_in / SEGMENT).min(segments.len())..];
+        let pcm = &pcm[(lead_in * channels).min(pcm.len())..];
+        let owned = range.end.saturating_sub(range.start);
+        let owned_pcm = &pcm[..owned * channels];
+        // One frame past the end lets the peak interpolate across the shard boundary
+        let peak_pcm = &pcm[..(owned + 1).min(pcm.len() / channels) * channels];
+
+        Ok(ShardStats {
+            start: range.start,
+            head: owned_pcm.iter().take(5).copied().collect(),
+            momentary_energies: absolute_gated(block_energies(segments, owned, MOMENTARY_BLOCK_SIZE, MOMENTARY_HOP)),
+            short_term_energies: absolute_gated(block_energies(segments, owned, SHORT_TERM_BLOCK_SIZE, SHORT_TERM_HOP)),
+            true_peak: self.technical.calculate_true_peak(peak_pcm).0,
+            clipped_samples: self.technical.detect_clipping(owned_pcm).1,
+            sample_sum: owned_pcm.iter().map(|&sample| sample as f64).sum(),
+            sample_count: owned_pcm.len(),
+        })
+    }
+
+    /// Combine every shard's statistics into one result
+    pub fn merge(&self, shards: &[ShardStats]) -> Result<ShardedResult, AnalysisError> {
+        let mut ordered: Vec<&ShardStats> = shards.iter().collect();
+        ordered.sort_by_key(|shard| shard.start);
+        let first = ordered.first().ok_or(AnalysisError::EmptyInput)?;
+
+        let momentary: Vec<f32> = ordered.iter().flat_map(|shard| shard.momentary_energies.iter().copied()).collect();
+        let short_term: Vec<f32> = ordered.iter().flat_map(|shard| shard.short_term_energies.iter().copied()).collect();
+        let clipped_samples: u32 = ordered.iter().map(|shard| shard.clipped_samples).sum();
+        let sample_count: usize = ordered.iter().map(|shard| shard.sample_count).sum();
+        let sample_sum: f64 = ordered.iter().map(|shard| shard.sample_sum).sum();
+
+        Ok(ShardedResult {
+            loudness: self.loudness.result_from_energies(first.head.clone(), sample_count / self.num_channels, &momentary, &short_term),
+            true_peak: ordered.iter().map(|shard| shard.true_peak).fold(f32::NEG_INFINITY, f32::max),
+            clipped_samples,
+            clipping_percentage: clipped_samples as f32 / sample_count.max(1) as f32 * 100.0,
+            dc_offset: (sample_sum / sample_count.max(1) as f64) as f32,
+            shard_count: ordered.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::Progress;
+
+    #[test]
+    fn merged_shards_match_single_pass() {
+        let frames = 20 * 44100;
+        let pcm: Vec<f32> = (0..2 * frames).map(|i| 0.3 * ((i / 2) as f32 * 0.05).sin() * (1.0 + (i as f32 * 1e-5).sin())).collect();
+        let analyzer = ShardAnalyzer::new(44100.0, 2);
+
+        let plan = plan_shards(frames, 3);
+        assert_eq!(plan.shards.len(), 3);
+        let stats: Vec<ShardStats> = plan.shards.iter().rev()
+            .map(|range| analyzer.analyze_shard(&pcm[range.read_start * 2..range.read_end * 2], *range).unwrap())
+            .collect();
+        let merged = analyzer.merge(&stats).unwrap();
+
+        let single = analyzer.loudness.analyze_samples(&pcm, &Progress::new(None, None)).unwrap();
+        assert!((merged.loudness.integrated - single.integrated).abs() < 1e-4);
+        assert!((merged.loudness.short_term - single.short_term).abs() < 1e-4);
+        assert_eq!(merged.loudness.total_blocks, single.total_blocks);
+        assert_eq!(merged.true_peak, analyzer.technical.calculate_true_peak(&pcm).0);
+    }
+}
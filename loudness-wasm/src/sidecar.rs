@@ -0,0 +1,216 @@
+// `.lufalyze` sidecar files: a saved session stored next to the audio it
+// describes, so archives of analysis results stay loadable as the metric set
+// changes. Files are read through a chain of migrations, one per version, so a
+// file of any earlier version is upgraded step by step to the current layout
+// before it is parsed; files from a newer version are refused.
+//
+// Version 2 (current), a JSON object:
+//
+//     {
+//         "format": "lufalyze",           // always "lufalyze"
+//         "version": 2,
+//         "generator": "lufalyze 0.1.0",  // library that wrote the file
+//         "source": { "name": "mix.wav", "bytes": 5292044 },  // either may be null
+//         "result": { ... },              // `Analyzer.analyze` result, non-finite figures as null
+//         "series": [ { "name": "momentary", "hop_seconds": 0.1, "width": 1, "values": [ ... ] } ]
+//     }
+//
+// Version 1 is the saved-session report (`Session.save`), which predates the
+// sidecar header: `{ "kind": "session", "version": 1, "generator", "result":
+// { "result", "series" } }`. It migrates with an unknown source.
+//
+// A change that renames, removes or redefines a field bumps SIDECAR_VERSION
+// and adds the migration from the previous version to MIGRATIONS.
+//
+//     const sidecar = new Sidecar(analyzer.analyze_session(pcm), file.name, BigInt(file.size));
+//     save(`${file.name}.lufalyze`, sidecar.write());
+//     const reopened = Sidecar.read(text);  // any earlier version
+//     render(reopened.session().result());
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::error::AnalysisError;
+use crate::json::{GENERATOR, REPORT_VERSION};
+use crate::session::{Session, StoredSeries, StoredSession};
+
+/// Layout version written by `Sidecar::write`
+pub const SIDECAR_VERSION: u32 = 2;
+/// File extension of sidecar files, without the dot
+pub const SIDECAR_EXTENSION: &str = "lufalyze";
+const FORMAT: &str = "lufalyze";
+
+type Migration = fn(Value) -> Result<Value, AnalysisError>;
+
+// Upgrades in version order: entry i turns a version i + 1 file into version i + 2
+const MIGRATIONS: [Migration; SIDECAR_VERSION as usize - 1] = [from_session_report];
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct SidecarSource {
+    name: Option<String>,
+    bytes: Option<u64>,
+}
+
+// A sidecar file in the current layout
+#[derive(Serialize, Deserialize)]
+struct SidecarFile {
+    format: String,
+    version: u32,
+    generator: String,
+    source: SidecarSource,
+    result: Value,
+    series: Vec<StoredSeries>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct Sidecar {
+    source: SidecarSource,
+    session: Session,
+    read_version: u32,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl Sidecar {
+    // Sidecar for `session`, with the audio file's name and size when known
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(session: &Session, source_name: Option<String>, source_bytes: Option<u64>) -> Self {
+        Sidecar {
+            source: SidecarSource { name: source_name, bytes: source_bytes },
+            session: session.clone(),
+            read_version: SIDECAR_VERSION,
+        }
+    }
+
+    // The sidecar in the current layout
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn write(&self) -> String {
+        let StoredSession { result, series } = self.session.stored();
+        let file = SidecarFile {
+            format: FORMAT.to_string(),
+            version: SIDECAR_VERSION,
+            generator: GENERATOR.to_string(),
+            source: self.source.clone(),
+            result,
+            series,
+        };
+        // Stored results are JSON values already, so writing cannot fail
+        serde_json::to_string(&file).expect("sidecars serialize to JSON")
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = read)]
+    pub fn read_js(json: &str) -> Result<Sidecar, JsError> {
+        Ok(Sidecar::read(json)?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = session)]
+    pub fn session_js(&self) -> Session {
+        self.session.clone()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn source_name(&self) -> Option<String> {
+        self.source.name.clone()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn source_bytes(&self) -> Option<u64> {
+        self.source.bytes
+    }
+
+    // Version of the file as read, below SIDECAR_VERSION when it was migrated
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn read_version(&self) -> u32 {
+        self.read_version
+    }
+}
+
+impl Sidecar {
+    /// Read a sidecar of this or any earlier version, migrating it to the
+    /// current layout
+    pub fn read(json: &str) -> Result<Self, AnalysisError> {
+        let mut document: Value = serde_json::from_str(json).map_err(|_| AnalysisError::InvalidReport { reason: "not a JSON document" })?;
+        let read_version = version_of(&document)?;
+        for migration in &MIGRATIONS[read_version as usize - 1..] {
+            document = migration(document)?;
+        }
+
+        let file: SidecarFile = serde_json::from_value(document).map_err(|_| AnalysisError::InvalidReport { reason: "malformed sidecar contents" })?;
+        let session = Session::from_stored(StoredSession { result: file.result, series: file.series })?;
+        Ok(Sidecar { source: file.source, session, read_version })
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+}
+
+// Layout version of a sidecar document
+fn version_of(document: &Value) -> Result<u32, AnalysisError> {
+    if document["format"] == FORMAT {
+        return match document["version"].as_u64() {
+            Some(version) if version > SIDECAR_VERSION as u64 => Err(AnalysisError::InvalidReport { reason: "sidecar from a newer version" }),
+            Some(version) if version >= 2 => Ok(version as u32),
+            _ => Err(AnalysisError::InvalidReport { reason: "invalid sidecar version" }),
+        };
+    }
+    // Version 1: a saved-session report
+    if document["kind"] == "session" {
+        return match document["version"].as_u64() {
+            Some(version) if version <= REPORT_VERSION as u64 => Ok(1),
+            _ => Err(AnalysisError::InvalidReport { reason: "report from a newer version" }),
+        };
+    }
+    Err(AnalysisError::InvalidReport { reason: "not a .lufalyze sidecar" })
+}
+
+// Version 1 to 2: lift the session out of the report envelope
+fn from_session_report(mut document: Value) -> Result<Value, AnalysisError> {
+    let mut session = document["result"].take();
+    if !session.is_object() {
+        return Err(AnalysisError::InvalidReport { reason: "malformed sidecar contents" });
+    }
+    Ok(json!({
+        "format": FORMAT,
+        "version": 2,
+        "generator": document["generator"].take(),
+        "source": { "name": null, "bytes": null },
+        "result": session["result"].take(),
+        "series": session["series"].take(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::report;
+
+    fn session() -> Session {
+        let stored = json!({
+            "result": { "loudness": { "integrated": -14.2, "momentary_max": null } },
+            "series": [{ "name": "momentary", "hop_seconds": 0.1, "width": 1, "values": [-20.0, null] }],
+        });
+        Session::from_stored(serde_json::from_value(stored).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn sidecars_round_trip_and_migrate_saved_sessions() {
+        let written = Sidecar::new(&session(), Some("mix.wav".to_string()), Some(5_292_044)).write();
+        let read = Sidecar::read(&written).unwrap();
+        assert_eq!((read.source_name().as_deref(), read.source_bytes(), read.read_version()), (Some("mix.wav"), Some(5_292_044), 2));
+        assert_eq!(read.session().result()["loudness"]["integrated"], -14.2);
+        assert_eq!(read.session().series("momentary").unwrap().values(), &[-20.0, f32::NEG_INFINITY]);
+
+        // A session saved before sidecars existed reads as version 1
+        let migrated = Sidecar::read(&report("session", &session().stored()).unwrap()).unwrap();
+        assert_eq!((migrated.source_name(), migrated.read_version()), (None, 1));
+        assert_eq!(migrated.session().result(), read.session().result());
+        assert_eq!(Sidecar::read(&migrated.write()).unwrap().read_version(), SIDECAR_VERSION);
+
+        let newer = written.replacen("\"version\":2", "\"version\":3", 1);
+        assert_eq!(Sidecar::read(&newer).err(), Some(AnalysisError::InvalidReport { reason: "sidecar from a newer version" }));
+        assert!(Sidecar::read(&report("batch", &json!({})).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+// SIMD-accelerated DSP kernels: the hot-loop primitives (dot products, sums
+// of squares, mid/side energies, scale-and-add, peak magnitude, FFT
+// butterflies) the analyzers share instead of hand-rolling their own loops.
+//
+// With the `simd` feature on a simd128 build (RUSTFLAGS="-C target-feature=+simd128")
+// these use WebAssembly SIMD128 intrinsics, otherwise the scalar loops are compiled.
+// WebAssembly cannot detect features at runtime from inside a module, so the
+// fallback happens at load time: ship both builds and have the loader pick the
+// SIMD one only when `WebAssembly.validate` accepts a SIMD module.
+// `simd_enabled()` reports which variant is running.
+//
+// The scalar loops run over contiguous slices with one partial sum per lane:
+// float addition isn't associative, so LLVM only vectorizes a reduction when
+// the source already splits it into independent accumulators.
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd"))]
+use core::arch::wasm32::*;
+
+/// Whether this build uses the SIMD128 kernels
+pub fn simd_enabled() -> bool {
+    cfg!(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd"))
+}
+
+// Independent accumulators in the scalar kernels (two SIMD128 vectors)
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd")))]
+const LANES: usize = 8;
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd"))]
+#[inline]
+fn load(slice: &[f32], offset: usize) -> v128 {
+    debug_assert!(offset + 4 <= slice.len());
+    // SAFETY: callers only pass offsets with four readable lanes; wasm loads
+    // have no alignment requirement
+    unsafe { v128_load(slice.as_ptr().add(offset) as *const v128) }
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd", any(feature = "technical", feature = "music")))]
+#[inline]
+fn store(slice: &mut [f32], offset: usize, value: v128) {
+    debug_assert!(offset + 4 <= slice.len());
+    // SAFETY: as for `load`, four writable lanes from `offset`
+    unsafe { v128_store(slice.as_mut_ptr().add(offset) as *mut v128, value) }
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd"))]
+#[inline]
+fn horizontal_sum(v: v128) -> f32 {
+    f32x4_extract_lane::<0>(v) + f32x4_extract_lane::<1>(v) + f32x4_extract_lane::<2>(v) + f32x4_extract_lane::<3>(v)
+}
+
+/// Dot product over the common length of two slices
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd"))]
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len().min(b.len());
+    let vector_end = n - n % 4;
+    let mut acc = f32x4_splat(0.0);
+    for i in (0..vector_end).step_by(4) {
+        acc = f32x4_add(acc, f32x4_mul(load(a, i), load(b, i)));
+    }
+    let mut sum = horizontal_sum(acc);
+    for i in vector_end..n {
+        sum += a[i] * b[i];
+    }
+    sum
+}
+
+/// Dot product over the common length of two slices
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd")))]
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len().min(b.len());
+    let (a, b) = (a[..n].chunks_exact(LANES), b[..n].chunks_exact(LANES));
+    let tail: f32 = a.remainder().iter().zip(b.remainder()).map(|(&x, &y)| x * y).sum();
+    let mut acc = [0.0f32; LANES];
+    for (a, b) in a.zip(b) {
+        for ((acc, &x), &y) in acc.iter_mut().zip(a).zip(b) {
+            *acc += x * y;
+        }
+    }
+    acc.iter().sum::<f32>() + tail
+}
+
+/// Sum of a slice
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd", feature = "technical"))]
+pub fn sum(x: &[f32]) -> f32 {
+    let vector_end = x.len() - x.len() % 4;
+    let mut acc = f32x4_splat(0.0);
+    for i in (0..vector_end).step_by(4) {
+        acc = f32x4_add(acc, load(x, i));
+    }
+    horizontal_sum(acc) + x[vector_end..].iter().sum::<f32>()
+}
+
+/// Sum of a slice
+#[cfg(all(not(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd")), feature = "technical"))]
+pub fn sum(x: &[f32]) -> f32 {
+    let chunks = x.chunks_exact(LANES);
+    let tail: f32 = chunks.remainder().iter().sum();
+    let mut acc = [0.0f32; LANES];
+    for chunk in chunks {
+        for (acc, &value) in acc.iter_mut().zip(chunk) {
+            *acc += value;
+        }
+    }
+    acc.iter().sum::<f32>() + tail
+}
+
+/// Multiply `frame` by `window` element-wise, returning the windowed energy
+#[cfg(feature = "technical")]
+pub fn apply_window(frame: &mut [f32], window: &[f32]) -> f32 {
+    let n = frame.len().min(window.len());
+    for (sample, &w) in frame[..n].iter_mut().zip(&window[..n]) {
+        *sample *= w;
+    }
+    sum_squares(&frame[..n])
+}
+
+/// Mid and side energies (Σm², Σs² with m = (l + r) / 2, s = (l - r) / 2)
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd", feature = "stereo"))]
+pub fn mid_side_energies(left: &[f32], right: &[f32]) -> (f32, f32) {
+    let n = left.len().min(right.len());
+    let vector_end = n - n % 4;
+    let mut mid = f32x4_splat(0.0);
+    let mut side = f32x4_splat(0.0);
+    for i in (0..vector_end).step_by(4) {
+        let (l, r) = (load(left, i), load(right, i));
+        let (sum, difference) = (f32x4_add(l, r), f32x4_sub(l, r));
+        mid = f32x4_add(mid, f32x4_mul(sum, sum));
+        side = f32x4_add(side, f32x4_mul(difference, difference));
+    }
+    let (mut sum_mid, mut sum_side) = (horizontal_sum(mid), horizontal_sum(side));
+    for (&l, &r) in left[vector_end..n].iter().zip(&right[vector_end..n]) {
+        sum_mid += (l + r) * (l + r);
+        sum_side += (l - r) * (l - r);
+    }
+    (sum_mid * 0.25, sum_side * 0.25)
+}
+
+/// Mid and side energies (Σm², Σs² with m = (l + r) / 2, s = (l - r) / 2)
+#[cfg(all(not(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd")), feature = "stereo"))]
+pub fn mid_side_energies(left: &[f32], right: &[f32]) -> (f32, f32) {
+    let n = left.len().min(right.len());
+    let (left, right) = (left[..n].chunks_exact(LANES), right[..n].chunks_exact(LANES));
+    let (mut mid_tail, mut side_tail) = (0.0f32, 0.0f32);
+    for (&l, &r) in left.remainder().iter().zip(right.remainder()) {
+        mid_tail += (l + r) * (l + r) * 0.25;
+        side_tail += (l - r) * (l - r) * 0.25;
+    }
+    let mut mid = [0.0f32; LANES];
+    let mut side = [0.0f32; LANES];
+    for (left, right) in left.zip(right) {
+        for (((mid, side), &l), &r) in mid.iter_mut().zip(side.iter_mut()).zip(left).zip(right) {
+            *mid += (l + r) * (l + r) * 0.25;
+            *side += (l - r) * (l - r) * 0.25;
+        }
+    }
+    (mid.iter().sum::<f32>() + mid_tail, side.iter().sum::<f32>() + side_tail)
+}
+
+/// Sum of squares
+#[cfg(any(feature = "stereo", feature = "technical", feature = "music"))]
+pub fn sum_squares(x: &[f32]) -> f32 {
+    dot(x, x)
+}
+
+// Samples per f32 partial sum in `sum_squares_f64` and the stereo sums;
+// short enough that f32 partials stay accurate, long enough to keep the SIMD
+// loop hot
+#[cfg(any(feature = "stereo", feature = "technical"))]
+pub(crate) const ACCUMULATION_BLOCK: usize = 4096;
+
+/// Sum of squares for long signals: SIMD partial sums over short blocks,
+/// accumulated in f64 so rounding error doesn't grow with the length
+#[cfg(feature = "technical")]
+pub fn sum_squares_f64(x: &[f32]) -> f64 {
+    x.chunks(ACCUMULATION_BLOCK).map(|block| sum_squares(block) as f64).sum()
+}
+
+/// y += a·x over the common length of the slices
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd", feature = "technical"))]
+pub fn axpy(a: f32, x: &[f32], y: &mut [f32]) {
+    let n = x.len().min(y.len());
+    let vector_end = n - n % 4;
+    let scale = f32x4_splat(a);
+    for i in (0..vector_end).step_by(4) {
+        store(y, i, f32x4_add(load(y, i), f32x4_mul(scale, load(x, i))));
+    }
+    for i in vector_end..n {
+        y[i] += a * x[i];
+    }
+}
+
+/// y += a·x over the common length of the slices
+#[cfg(all(not(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd")), feature = "technical"))]
+pub fn axpy(a: f32, x: &[f32], y: &mut [f32]) {
+    // No reduction, so LLVM vectorizes the plain loop
+    for (y, &x) in y.iter_mut().zip(x) {
+        *y += a * x;
+    }
+}
+
+/// Largest magnitude in a slice (0 when empty; NaNs are skipped)
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd", feature = "loudness"))]
+pub fn max_abs(x: &[f32]) -> f32 {
+    let vector_end = x.len() - x.len() % 4;
+    let mut acc = f32x4_splat(0.0);
+    for i in (0..vector_end).step_by(4) {
+        // pmax keeps the accumulator when the comparison fails on a NaN
+        acc = f32x4_pmax(acc, f32x4_abs(load(x, i)));
+    }
+    let lanes = [f32x4_extract_lane::<0>(acc), f32x4_extract_lane::<1>(acc), f32x4_extract_lane::<2>(acc), f32x4_extract_lane::<3>(acc)];
+    x[vector_end..].iter().chain(&lanes).fold(0.0, |peak, &value| peak.max(value.abs()))
+}
+
+/// Largest magnitude in a slice (0 when empty; NaNs are skipped)
+#[cfg(all(not(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd")), feature = "loudness"))]
+pub fn max_abs(x: &[f32]) -> f32 {
+    let chunks = x.chunks_exact(LANES);
+    let tail = chunks.remainder().iter().fold(0.0f32, |peak, &value| peak.max(value.abs()));
+    let mut acc = [0.0f32; LANES];
+    for chunk in chunks {
+        for (acc, &value) in acc.iter_mut().zip(chunk) {
+            *acc = acc.max(value.abs());
+        }
+    }
+    acc.iter().fold(tail, |peak, &value| peak.max(value))
+}
+
+/// Fused stereo sums (Σl·r, Σl², Σr²) in a single pass, for correlation
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd", feature = "stereo"))]
+pub fn stereo_sums(left: &[f32], right: &[f32]) -> (f32, f32, f32) {
+    let n = left.len().min(right.len());
+    let vector_end = n - n % 4;
+    let mut lr = f32x4_splat(0.0);
+    let mut ll = f32x4_splat(0.0);
+    let mut rr = f32x4_splat(0.0);
+    for i in (0..vector_end).step_by(4) {
+        let l = load(left, i);
+        let r = load(right, i);
+        lr = f32x4_add(lr, f32x4_mul(l, r));
+        ll = f32x4_add(ll, f32x4_mul(l, l));
+        rr = f32x4_add(rr, f32x4_mul(r, r));
+    }
+    let (mut sum_lr, mut sum_ll, mut sum_rr) = (horizontal_sum(lr), horizontal_sum(ll), horizontal_sum(rr));
+    for i in vector_end..n {
+        sum_lr += left[i] * right[i];
+        sum_ll += left[i] * left[i];
+        sum_rr += right[i] * right[i];
+    }
+    (sum_lr, sum_ll, sum_rr)
+}
+
+/// Fused stereo sums (Σl·r, Σl², Σr²) in a single pass, for correlation
+#[cfg(all(not(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd")), feature = "stereo"))]
+pub fn stereo_sums(left: &[f32], right: &[f32]) -> (f32, f32, f32) {
+    let n = left.len().min(right.len());
+    let (left, right) = (left[..n].chunks_exact(LANES), right[..n].chunks_exact(LANES));
+    let (mut tail_lr, mut tail_ll, mut tail_rr) = (0.0f32, 0.0f32, 0.0f32);
+    for (&l, &r) in left.remainder().iter().zip(right.remainder()) {
+        tail_lr += l * r;
+        tail_ll += l * l;
+        tail_rr += r * r;
+    }
+    let mut lr = [0.0f32; LANES];
+    let mut ll = [0.0f32; LANES];
+    let mut rr = [0.0f32; LANES];
+    for (left, right) in left.zip(right) {
+        for lane in 0..LANES {
+            let (l, r) = (left[lane], right[lane]);
+            lr[lane] += l * r;
+            ll[lane] += l * l;
+            rr[lane] += r * r;
+        }
+    }
+    (
+        lr.iter().sum::<f32>() + tail_lr,
+        ll.iter().sum::<f32>() + tail_ll,
+        rr.iter().sum::<f32>() + tail_rr,
+    )
+}
+
+/// Radix-2 butterflies over one block of an FFT stage: with t = w·b,
+/// a becomes a + t and b becomes a - t, lane by lane (all six slices the
+/// same length)
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd", any(feature = "technical", feature = "music")))]
+pub fn butterflies((a_re, a_im): (&mut [f32], &mut [f32]), (b_re, b_im): (&mut [f32], &mut [f32]), (w_re, w_im): (&[f32], &[f32])) {
+    let n = a_re.len();
+    let vector_end = n - n % 4;
+    for i in (0..vector_end).step_by(4) {
+        let (b_r, b_i, w_r, w_i) = (load(b_re, i), load(b_im, i), load(w_re, i), load(w_im, i));
+        let t_re = f32x4_sub(f32x4_mul(b_r, w_r), f32x4_mul(b_i, w_i));
+        let t_im = f32x4_add(f32x4_mul(b_r, w_i), f32x4_mul(b_i, w_r));
+        let (a_r, a_i) = (load(a_re, i), load(a_im, i));
+        store(b_re, i, f32x4_sub(a_r, t_re));
+        store(b_im, i, f32x4_sub(a_i, t_im));
+        store(a_re, i, f32x4_add(a_r, t_re));
+        store(a_im, i, f32x4_add(a_i, t_im));
+    }
+    let a = a_re[vector_end..].iter_mut().zip(&mut a_im[vector_end..]);
+    let b = b_re[vector_end..].iter_mut().zip(&mut b_im[vector_end..]);
+    for (((a_re, a_im), (b_re, b_im)), (&w_re, &w_im)) in a.zip(b).zip(w_re[vector_end..].iter().zip(&w_im[vector_end..])) {
+        let t_re = *b_re * w_re - *b_im * w_im;
+        let t_im = *b_re * w_im + *b_im * w_re;
+        *b_re = *a_re - t_re;
+        *b_im = *a_im - t_im;
+        *a_re += t_re;
+        *a_im += t_im;
+    }
+}
+
+/// Radix-2 butterflies over one block of an FFT stage: with t = w·b,
+/// a becomes a + t and b becomes a - t, lane by lane (all six slices the
+/// same length)
+#[cfg(all(not(all(target_arch = "wasm32", target_feature = "simd128", feature = "simd")), any(feature = "technical", feature = "music")))]
+pub fn butterflies((a_re, a_im): (&mut [f32], &mut [f32]), (b_re, b_im): (&mut [f32], &mut [f32]), (w_re, w_im): (&[f32], &[f32])) {
+    // No reduction, so LLVM vectorizes the plain loop
+    let a = a_re.iter_mut().zip(a_im.iter_mut());
+    let b = b_re.iter_mut().zip(b_im.iter_mut());
+    for (((a_re, a_im), (b_re, b_im)), (&w_re, &w_im)) in a.zip(b).zip(w_re.iter().zip(w_im)) {
+        let t_re = *b_re * w_re - *b_im * w_im;
+        let t_im = *b_re * w_im + *b_im * w_re;
+        *b_re = *a_re - t_re;
+        *b_im = *a_im - t_im;
+        *a_re += t_re;
+        *a_im += t_im;
+    }
+}
+
+#[cfg(all(test, feature = "technical"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(all(feature = "stereo", feature = "technical"))]
+    fn kernels_match_naive_sums() {
+        let left: Vec<f32> = (0..37).map(|i| (i as f32 * 0.37).sin()).collect();
+        let right: Vec<f32> = (0..37).map(|i| (i as f32 * 0.11).cos()).collect();
+
+        let (lr, ll, rr) = stereo_sums(&left, &right);
+        let naive_lr: f32 = left.iter().zip(&right).map(|(l, r)| l * r).sum();
+        assert!((lr - naive_lr).abs() < 1e-4);
+        assert!((ll - sum_squares(&left)).abs() < 1e-4);
+        assert!((rr - dot(&right, &right)).abs() < 1e-4);
+
+        let (mid, side) = mid_side_energies(&left, &right);
+        let naive_mid: f32 = left.iter().zip(&right).map(|(l, r)| (l + r) * (l + r) * 0.25).sum();
+        assert!((mid - naive_mid).abs() < 1e-4);
+        assert!((mid + side - (ll + rr) * 0.5).abs() < 1e-4);
+        assert!((sum(&left) - left.iter().sum::<f32>()).abs() < 1e-4);
+
+        let mut scaled = right.clone();
+        axpy(-0.5, &left, &mut scaled);
+        assert!(scaled.iter().zip(&left).zip(&right).all(|((s, l), r)| (s - (r - 0.5 * l)).abs() < 1e-6));
+        let mut peaky = left.clone();
+        peaky[33] = -3.0;
+        peaky[5] = f32::NAN;
+        assert_eq!(max_abs(&peaky), 3.0);
+        assert_eq!(max_abs(&[]), 0.0);
+    }
+
+    #[test]
+    #[cfg(feature = "technical")]
+    fn long_sums_stay_accurate() {
+        // Ten minutes of mono at 44.1kHz; a plain f32 running sum stalls short of the total
+        let samples = vec![0.1f32; 600 * 44100];
+        let expected = samples.len() as f64 * (0.1f32 * 0.1f32) as f64;
+        let naive: f32 = samples.iter().map(|x| x * x).sum();
+        assert!((naive as f64 / expected - 1.0).abs() > 5e-3);
+        assert!((sum_squares_f64(&samples) / expected - 1.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,323 @@
+// Mix-bus contribution of a stem set, for mix review: each stem's loudness
+// and share of the energy, how it interacts in phase with the rest of the mix
+// (a stem correlating negatively with everything else thins the sum out),
+// and masking hotspots where two stems sit at about the same level in the
+// same frequency band and together carry that band of the mix. Bands are the
+// frequency-balance bands, judged per one-second section on the mono fold of
+// each stem, so parts panned apart can show up as masking they don't cause
+// in stereo. The residual shows how far the stems fall short of summing to
+// the bus (bus processing, missing stems, misaligned exports). Everything is
+// compared over the shortest of the buffers.
+//
+//     const analyzer = new StemAnalyzer(48000, 2);
+//     const report = analyzer.analyze([drums, bass, keys, vocal], ["drums", "bass", "keys", "vocal"], mix);
+//     report.hotspots.forEach(spot => mark(spot.start, spot.end, `${spot.stem_a} / ${spot.stem_b}`));
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::AnalyzerConfig;
+use crate::constants::FREQUENCY_BANDS;
+use crate::error::{validate_pcm, AnalysisError};
+use crate::loudness::LoudnessAnalyzer;
+use crate::null_test::band_bins;
+use crate::parallel::map_range;
+use crate::stft::Stft;
+use crate::utils::mix_to_mono;
+use crate::window::Window;
+
+// STFT size (and hop) for the band levels, and the sections they are judged in
+const BAND_FFT_SIZE: usize = 4096;
+const SECTION_SECONDS: f32 = 1.0;
+// Two stems mask each other in a band when their levels are within 6 dB and
+// each is no more than 10 dB under the mix there, in bands above -60 dBFS
+const MASKING_RANGE_DB: f32 = 6.0;
+const CONTRIBUTION_DB: f32 = -10.0;
+const BAND_FLOOR_DB: f32 = -60.0;
+
+/// One stem's part in the mix
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct StemContribution {
+    pub name: String,
+    // Integrated loudness (LUFS), and relative to the mix's (LU)
+    pub integrated: f32,
+    pub relative_loudness: f32,
+    // Share of the stems' summed energy
+    pub energy_share: f32,
+    // Normalised correlation with the mix, and with the mix minus this stem
+    // (negative when the stem cancels against the rest)
+    pub mix_correlation: f32,
+    pub rest_correlation: f32,
+    // Mix energy against the stem's and the rest's energies added (dB):
+    // positive when they reinforce, negative when they cancel
+    pub interaction_db: f32,
+}
+
+/// A run of sections where two stems mask each other in one band
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct MaskingHotspot {
+    pub start: f32,
+    pub end: f32,
+    pub low_hz: f32,
+    pub high_hz: f32,
+    pub stem_a: String,
+    pub stem_b: String,
+    // Smallest level difference (dB) between the two stems over the run
+    pub difference_db: f32,
+    // Loudest mix level (dBFS) in the band over the run
+    pub level_db: f32,
+}
+
+/// Contribution of every stem (in input order), with masking hotspots in time order
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct StemReport {
+    pub mix_integrated: f32,
+    // Energy of the mix minus the summed stems, relative to the mix (dB);
+    // -Infinity when the stems sum to the bus exactly
+    pub residual_db: f32,
+    pub stems: Vec<StemContribution>,
+    pub section_seconds: f32,
+    pub hotspots: Vec<MaskingHotspot>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct StemAnalyzer {
+    loudness: LoudnessAnalyzer,
+    sample_rate: f32,
+    num_channels: usize,
+    window: Window,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl StemAnalyzer {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        StemAnalyzer::from_config(&AnalyzerConfig::new(sample_rate, num_channels))
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        StemAnalyzer {
+            loudness: LoudnessAnalyzer::from_config(config),
+            sample_rate: config.sample_rate(),
+            num_channels: config.num_channels(),
+            window: config.window(),
+        }
+    }
+
+    // `names` name the stems in order (stems without one are named by index)
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze)]
+    pub fn analyze_js(&self, stems: Vec<Float32Array>, names: Vec<String>, mix: &Float32Array) -> Result<StemReport, JsError> {
+        let stems: Vec<Vec<f32>> = stems.iter().map(Float32Array::to_vec).collect();
+        let stems: Vec<&[f32]> = stems.iter().map(Vec::as_slice).collect();
+        Ok(self.analyze(&stems, &names, &mix.to_vec())?)
+    }
+}
+
+impl StemAnalyzer {
+    /// Contribution of interleaved stems to the interleaved mix bus they were
+    /// summed into (all of this analyzer's rate and channel count)
+    pub fn analyze<S: AsRef<str>>(&self, stems: &[&[f32]], names: &[S], mix: &[f32]) -> Result<StemReport, AnalysisError> {
+        if stems.is_empty() {
+            return Err(AnalysisError::InvalidSetting { reason: "no stems to analyse" });
+        }
+        let min_frames = self.loudness.min_frames();
+        validate_pcm(mix, self.num_channels, min_frames)?;
+        for stem in stems {
+            validate_pcm(stem, self.num_channels, min_frames)?;
+        }
+        let names: Vec<String> = (0..stems.len()).map(|index| names.get(index).map_or_else(|| index.to_string(), |name| name.as_ref().to_string())).collect();
+        let length = stems.iter().map(|stem| stem.len()).fold(mix.len(), usize::min);
+        let mix = &mix[..length];
+        let stems: Vec<&[f32]> = stems.iter().map(|stem| &stem[..length]).collect();
+
+        let mix_integrated = self.loudness.analyze(mix, None)?.integrated;
+        let mix_energy = energy(mix);
+        let measured = map_range(0..stems.len(), |index| -> Result<_, AnalysisError> {
+            let stem = stems[index];
+            let integrated = self.loudness.analyze(stem, None)?.integrated;
+            Ok((integrated, self.interaction(stem, mix, mix_energy), self.band_sections(stem)))
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let total_energy: f64 = measured.iter().map(|(_, interaction, _)| interaction.energy).sum();
+        let residual: f64 = (0..length).map(|i| mix[i] as f64 - stems.iter().map(|stem| stem[i] as f64).sum::<f64>()).map(|x| x * x).sum();
+        let mix_bands = self.band_sections(mix);
+        let hotspots = self.hotspots(&names, &measured.iter().map(|(_, _, bands)| bands.as_slice()).collect::<Vec<_>>(), &mix_bands);
+        let stems = names.into_iter().zip(measured)
+            .map(|(name, (integrated, interaction, _))| StemContribution {
+                name,
+                integrated,
+                relative_loudness: integrated - mix_integrated,
+                energy_share: if total_energy > 0.0 { (interaction.energy / total_energy) as f32 } else { 0.0 },
+                mix_correlation: interaction.mix_correlation,
+                rest_correlation: interaction.rest_correlation,
+                interaction_db: interaction.interaction_db,
+            })
+            .collect();
+        Ok(StemReport {
+            mix_integrated,
+            residual_db: power_db(residual / mix_energy.max(f64::MIN_POSITIVE)),
+            stems,
+            section_seconds: self.section_seconds(),
+            hotspots,
+        })
+    }
+
+    // Energy of a stem and how it adds up with the rest of the mix
+    fn interaction(&self, stem: &[f32], mix: &[f32], mix_energy: f64) -> Interaction {
+        let (mut stem_energy, mut rest_energy, mut stem_mix, mut stem_rest) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+        for (&s, &m) in stem.iter().zip(mix) {
+            let (s, m) = (s as f64, m as f64);
+            let rest = m - s;
+            stem_energy += s * s;
+            rest_energy += rest * rest;
+            stem_mix += s * m;
+            stem_rest += s * rest;
+        }
+        let correlation = |cross: f64, energy: f64| if stem_energy > 0.0 && energy > 0.0 { (cross / (stem_energy * energy).sqrt()) as f32 } else { 0.0 };
+        let parts = stem_energy + rest_energy;
+        Interaction {
+            energy: stem_energy,
+            mix_correlation: correlation(stem_mix, mix_energy),
+            rest_correlation: correlation(stem_rest, rest_energy),
+            interaction_db: if parts > 0.0 { power_db(mix_energy / parts) } else { 0.0 },
+        }
+    }
+
+    fn section_frames(&self) -> usize {
+        ((SECTION_SECONDS * self.sample_rate / BAND_FFT_SIZE as f32).round() as usize).max(1)
+    }
+
+    fn section_seconds(&self) -> f32 {
+        (self.section_frames() * BAND_FFT_SIZE) as f32 / self.sample_rate
+    }
+
+    // Mean power per band of the mono fold in every whole section
+    fn band_sections(&self, pcm: &[f32]) -> Vec<[f32; FREQUENCY_BANDS.len()]> {
+        let mono = mix_to_mono(pcm, self.num_channels);
+        let stft = Stft::new(BAND_FFT_SIZE, BAND_FFT_SIZE, self.window);
+        let window_power: f32 = stft.window().iter().map(|w| w * w).sum();
+        let frames: Vec<[f32; FREQUENCY_BANDS.len()]> = stft.frame_starts(mono.len())
+            .map(|start| {
+                let magnitudes = stft.forward(mono[start..start + BAND_FFT_SIZE].iter().copied()).magnitudes();
+                FREQUENCY_BANDS.map(|(low, high)| {
+                    let bins = band_bins(low, high, self.sample_rate, magnitudes.len());
+                    // One-sided spectrum: each bin stands for its mirror too
+                    magnitudes[bins].iter().map(|&m| 2.0 * m * m).sum::<f32>() / (BAND_FFT_SIZE as f32 * window_power)
+                })
+            })
+            .collect();
+        frames.chunks_exact(self.section_frames())
+            .map(|section| {
+                let mut bands = [0.0; FREQUENCY_BANDS.len()];
+                for frame in section {
+                    for (band, &power) in bands.iter_mut().zip(frame) {
+                        *band += power / section.len() as f32;
+                    }
+                }
+                bands
+            })
+            .collect()
+    }
+
+    // Runs of sections where a pair of stems masks each other in a band
+    fn hotspots(&self, names: &[String], stems: &[&[[f32; FREQUENCY_BANDS.len()]]], mix: &[[f32; FREQUENCY_BANDS.len()]]) -> Vec<MaskingHotspot> {
+        let section_seconds = self.section_seconds();
+        let mut hotspots = Vec::new();
+        for a in 0..stems.len() {
+            for b in a + 1..stems.len() {
+                for (band, &(low_hz, high_hz)) in FREQUENCY_BANDS.iter().enumerate() {
+                    let mut run: Option<MaskingHotspot> = None;
+                    for (section, mix_bands) in mix.iter().enumerate() {
+                        let level_db = power_db(mix_bands[band] as f64);
+                        let (level_a, level_b) = (power_db(stems[a][section][band] as f64), power_db(stems[b][section][band] as f64));
+                        let difference_db = (level_a - level_b).abs();
+                        let masking = level_db > BAND_FLOOR_DB && difference_db <= MASKING_RANGE_DB && level_a.min(level_b) >= level_db + CONTRIBUTION_DB;
+                        let start = section as f32 * section_seconds;
+                        match (&mut run, masking) {
+                            (Some(spot), true) => {
+                                spot.end = start + section_seconds;
+                                spot.difference_db = spot.difference_db.min(difference_db);
+                                spot.level_db = spot.level_db.max(level_db);
+                            }
+                            (None, true) => {
+                                run = Some(MaskingHotspot {
+                                    start,
+                                    end: start + section_seconds,
+                                    low_hz,
+                                    high_hz,
+                                    stem_a: names[a].clone(),
+                                    stem_b: names[b].clone(),
+                                    difference_db,
+                                    level_db,
+                                });
+                            }
+                            (_, false) => hotspots.extend(run.take()),
+                        }
+                    }
+                    hotspots.extend(run);
+                }
+            }
+        }
+        hotspots.sort_by(|x, y| x.start.total_cmp(&y.start).then(x.low_hz.total_cmp(&y.low_hz)));
+        hotspots
+    }
+}
+
+// Energy sums of one stem against the mix
+struct Interaction {
+    energy: f64,
+    mix_correlation: f32,
+    rest_correlation: f32,
+    interaction_db: f32,
+}
+
+fn energy(pcm: &[f32]) -> f64 {
+    pcm.iter().map(|&x| x as f64 * x as f64).sum()
+}
+
+// Power ratio in dB; -Infinity for zero
+fn power_db(power: f64) -> f32 {
+    (10.0 * power.log10()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn reports_masking_and_phase_interaction() {
+        // 4 s at 44.1kHz: a bass, keys and guitar fighting over the mids, and
+        // a bass DI in opposite polarity to the bass, 10 dB down
+        let sine = |frequency: f32, amplitude: f32| -> Vec<f32> { (0..4 * 44100).map(|i| amplitude * (2.0 * PI * frequency * i as f32 / 44100.0).sin()).collect() };
+        let stems = [sine(80.0, 0.3), sine(1000.0, 0.2), sine(1200.0, 0.2), sine(80.0, -0.1)];
+        let mix: Vec<f32> = (0..4 * 44100).map(|i| stems.iter().map(|stem| stem[i]).sum()).collect();
+        let stems: Vec<&[f32]> = stems.iter().map(Vec::as_slice).collect();
+        let report = StemAnalyzer::new(44100.0, 1).analyze(&stems, &["bass", "keys", "guitar", "bass_di"], &mix).unwrap();
+
+        assert!(report.residual_db < -100.0, "{}", report.residual_db);
+        assert_eq!(report.stems.len(), 4);
+        let [bass, keys, _, bass_di] = &report.stems[..] else { unreachable!() };
+        assert!(bass.energy_share > keys.energy_share && bass.relative_loudness > keys.relative_loudness);
+        assert!(bass_di.rest_correlation < -0.5 && bass_di.mix_correlation < 0.0 && bass_di.interaction_db < 0.0, "{:?}", bass_di);
+        assert!(keys.rest_correlation.abs() < 0.1 && keys.interaction_db.abs() < 0.5, "{:?}", keys);
+
+        // Keys and guitar mask each other in the mids all the way through
+        assert_eq!(report.hotspots.len(), 1, "{:?}", report.hotspots);
+        let spot = &report.hotspots[0];
+        assert_eq!((spot.stem_a.as_str(), spot.stem_b.as_str(), spot.low_hz), ("keys", "guitar", 500.0));
+        assert!(spot.start == 0.0 && spot.end > 3.0 && spot.difference_db < 1.0, "{:?}", spot);
+    }
+}
@@ -1,34 +1,212 @@
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
 use js_sys::Float32Array;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::{AnalyzerConfig, DEFAULT_SILENCE_THRESHOLD};
+use crate::error::{validate_pcm, AnalysisError};
+#[cfg(target_arch = "wasm32")]
+use crate::limits::BYTES_PER_SAMPLE;
+use crate::limits::{AnalysisLimits, LimitsReport, MetricStatus, Quality, STEREO_BYTES_PER_SAMPLE};
+#[cfg(target_arch = "wasm32")]
+use crate::buffer::PcmBuffer;
+use crate::series::TimeSeries;
+use crate::simd::{mid_side_energies, stereo_sums, sum_squares, ACCUMULATION_BLOCK};
+use crate::utils::{silent_share, DbScale, Scratch, ScratchPool};
+
+// Window and hop (seconds) of the phase correlation history
+const CORRELATION_WINDOW: f32 = 0.4;
+const CORRELATION_HOP: f32 = 0.1;
+
+// Window of the imaging quality (phase coherence) measure, in analysed frames
+pub(crate) const COHERENCE_WINDOW: usize = 512;
+
+// Running sums behind the whole-span metrics (correlation, width, balance,
+// mono compatibility), so they can be built up chunk by chunk
+#[derive(Clone, Copy, Default)]
+pub(crate) struct StereoSums {
+    pub(crate) frames: usize,
+    lr: f64,
+    ll: f64,
+    rr: f64,
+    mid: f64,
+    side: f64,
+}
+
+impl StereoSums {
+    pub(crate) fn add(&mut self, left: &[f32], right: &[f32]) {
+        for (left, right) in left.chunks(ACCUMULATION_BLOCK).zip(right.chunks(ACCUMULATION_BLOCK)) {
+            let (lr, ll, rr) = stereo_sums(left, right);
+            let (mid, side) = mid_side_energies(left, right);
+            self.lr += lr as f64;
+            self.ll += ll as f64;
+            self.rr += rr as f64;
+            self.mid += mid as f64;
+            self.side += side as f64;
+        }
+        self.frames += left.len().min(right.len());
+    }
+
+    // Phase correlation between L/R channels
+    // Returns value between -1 (out of phase) and +1 (in phase)
+    pub(crate) fn correlation(&self) -> f32 {
+        // Pearson correlation coefficient
+        let denominator = (self.ll * self.rr).sqrt();
+        if denominator > 1e-10 {
+            (self.lr / denominator).clamp(-1.0, 1.0) as f32
+        } else {
+            0.0
+        }
+    }
+
+    // Stereo width from the Mid/Side energies
+    // Returns value between 0 (mono) and 1 (full stereo width)
+    pub(crate) fn width(&self) -> f32 {
+        let total_energy = self.mid + self.side;
+        if total_energy > 1e-10 {
+            // Normalize to 0-1 range where 0.5 is typical stereo content
+            (self.side / total_energy * 2.0).min(1.0) as f32
+        } else {
+            0.0
+        }
+    }
+
+    // Mono compatibility from the phase cancellation of the mono sum
+    // Returns value between 0 (bad mono compatibility) and 1 (perfect mono compatibility)
+    fn mono_compatibility(&self) -> f32 {
+        // Energy of the stereo signal (Σl² + Σr² = 2·(Σm² + Σs²)) vs the mono sum,
+        // doubled for a fair comparison
+        let stereo_energy = 2.0 * (self.mid + self.side);
+        let mono_energy = 2.0 * self.mid;
+
+        if stereo_energy > 1e-10 {
+            (mono_energy / stereo_energy).min(1.0) as f32
+        } else {
+            1.0
+        }
+    }
+}
 
-#[wasm_bindgen]
+// Energy-weighted phase coherence over imaging quality windows
+#[derive(Clone, Copy, Default)]
+pub(crate) struct WindowCoherence {
+    sum: f32,
+    count: usize,
+}
+
+impl WindowCoherence {
+    pub(crate) fn add(&mut self, analyzer: &StereoAnalyzer, left: &[f32], right: &[f32]) {
+        // Calculate phase correlation for this window
+        let correlation = analyzer.calculate_phase_correlation(left, right);
+
+        // Weight by window energy to focus on audible content
+        let window_energy = sum_squares(left) + sum_squares(right);
+
+        if window_energy > 1e-8 {
+            self.sum += correlation.abs() * window_energy.sqrt();
+            self.count += 1;
+        }
+    }
+
+    pub(crate) fn score(&self) -> f32 {
+        if self.count > 0 {
+            (self.sum / self.count as f32).min(1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Stereo image measurements; mono input only reports compatibility and quality
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct StereoResult {
+    pub is_mono: bool,
+    pub channels: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub phase_correlation: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub stereo_width: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub lr_balance: Option<f32>,
+    pub mono_compatibility: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub imaging_quality_score: Option<f32>,
+    pub imaging_quality: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(target_arch = "wasm32", tsify(optional))]
+    pub limits: Option<LimitsReport>,
+    // Shared by every stereo metric: they are all measured over the same
+    // (possibly truncated) span, and need at least one 400ms correlation window
+    pub status: MetricStatus,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 pub struct StereoAnalyzer {
     sample_rate: f32,
+    limits: AnalysisLimits,
+    quality: Quality,
+    // Levels behind the L/R balance
+    db: DbScale,
+    scratch: ScratchPool,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 impl StereoAnalyzer {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
     pub fn new(sample_rate: f32) -> Self {
+        StereoAnalyzer::from_config(&AnalyzerConfig::new(sample_rate, 2))
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
         StereoAnalyzer {
-            sample_rate,
+            sample_rate: config.sample_rate(),
+            limits: config.limits(),
+            quality: config.quality(),
+            db: config.db_scale(),
+            scratch: ScratchPool::default(),
         }
     }
 
+    // Replace the default duration / decimation / memory limits
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_limits(&mut self, limits: &AnalysisLimits) {
+        self.limits = *limits;
+    }
+
+    // Apply a speed/precision preset (its limits and the coherence window
+    // overlap)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_quality(&mut self, quality: Quality) {
+        self.limits = AnalysisLimits::for_quality(quality);
+        self.quality = quality;
+    }
+
     // Extract left and right channels from interleaved stereo PCM data - Optimized for performance
-    fn extract_stereo_channels(&self, pcm: &Float32Array) -> (Vec<f32>, Vec<f32>) {
-        let length = pcm.length() as usize;
-        let samples_per_channel = length / 2;
+    // Channel buffers come from the analyzer's pool and are reused across calls
+    fn extract_stereo_channels(&self, pcm: &[f32]) -> (Scratch<'_>, Scratch<'_>) {
+        let samples_per_channel = pcm.len() / 2;
         
-        // Limit analysis to first 60 seconds for very long files to improve performance
-        let max_samples_per_channel = (self.sample_rate as usize * 60).min(samples_per_channel);
+        // Limit analysis to the configured duration and memory ceiling
+        let max_samples_per_channel = samples_per_channel.min(self.max_analyzed_frames());
+        let decimation = self.limits.decimation();
         
-        let mut left = Vec::with_capacity(max_samples_per_channel);
-        let mut right = Vec::with_capacity(max_samples_per_channel);
+        let mut left = self.scratch.take(0);
+        let mut right = self.scratch.take(0);
+        left.reserve(max_samples_per_channel / decimation + 1);
+        right.reserve(max_samples_per_channel / decimation + 1);
         
-        for i in 0..max_samples_per_channel {
-            left.push(pcm.get_index((i * 2) as u32));      // Left channel
-            right.push(pcm.get_index((i * 2 + 1) as u32)); // Right channel
+        for frame in pcm.chunks_exact(2).take(max_samples_per_channel).step_by(decimation) {
+            left.push(frame[0]);  // Left channel
+            right.push(frame[1]); // Right channel
         }
         
         (left, right)
@@ -36,119 +214,65 @@ impl StereoAnalyzer {
 
     // Calculate phase correlation between L/R channels
     // Returns value between -1 (out of phase) and +1 (in phase)
-    fn calculate_phase_correlation(&self, left: &[f32], right: &[f32]) -> f32 {
+    pub(crate) fn calculate_phase_correlation(&self, left: &[f32], right: &[f32]) -> f32 {
         if left.len() != right.len() || left.is_empty() {
             return 0.0;
         }
 
-        let mut sum_lr = 0.0;
-        let mut sum_ll = 0.0;
-        let mut sum_rr = 0.0;
-
         // Calculate cross-correlation and auto-correlations
-        for i in 0..left.len() {
-            let l = left[i];
-            let r = right[i];
-            
-            sum_lr += l * r;
-            sum_ll += l * l;
-            sum_rr += r * r;
-        }
+        let (sum_lr, sum_ll, sum_rr) = stereo_sums(left, right);
 
         // Pearson correlation coefficient
         let denominator = (sum_ll * sum_rr).sqrt();
         if denominator > 1e-10 {
-            (sum_lr / denominator).max(-1.0).min(1.0)
+            (sum_lr / denominator).clamp(-1.0, 1.0)
         } else {
             0.0
         }
     }
 
-    // Calculate stereo width using Mid/Side analysis
-    // Returns value between 0 (mono) and 1 (full stereo width)
-    fn calculate_stereo_width(&self, left: &[f32], right: &[f32]) -> f32 {
-        if left.len() != right.len() || left.is_empty() {
-            return 0.0;
-        }
-
-        let mut mid_energy = 0.0;
-        let mut side_energy = 0.0;
+    // Frames the duration and memory limits let the analysis read
+    pub(crate) fn max_analyzed_frames(&self) -> usize {
+        self.limits.max_frames(usize::MAX, self.sample_rate)
+            .min(self.limits.max_samples(usize::MAX, STEREO_BYTES_PER_SAMPLE) / 2)
+    }
 
-        // Convert to Mid/Side and calculate energies
-        for i in 0..left.len() {
-            let mid = (left[i] + right[i]) * 0.5;   // Mid = (L + R) / 2
-            let side = (left[i] - right[i]) * 0.5;  // Side = (L - R) / 2
-            
-            mid_energy += mid * mid;
-            side_energy += side * side;
-        }
+    // Rate of the analysed (decimated) frames
+    pub(crate) fn analysis_rate(&self) -> f32 {
+        self.sample_rate / self.limits.decimation() as f32
+    }
 
-        let total_energy = mid_energy + side_energy;
-        if total_energy > 1e-10 {
-            // Normalize to 0-1 range where 0.5 is typical stereo content
-            (side_energy / total_energy * 2.0).min(1.0)
-        } else {
-            0.0
-        }
+    // Hop between imaging quality windows over `analyzed` frames
+    pub(crate) fn coherence_hop(&self, analyzed: usize) -> usize {
+        self.quality.spectral_hop(COHERENCE_WINDOW.min(analyzed), analyzed, self.analysis_rate()).max(1)
     }
 
     // Calculate L/R balance in dB
-    // Positive values = right louder, negative = left louder
-    fn calculate_lr_balance(&self, left: &[f32], right: &[f32]) -> f32 {
-        if left.len() != right.len() || left.is_empty() {
+    // Positive values = right louder, negative = left louder; a silent side
+    // reads at the floor, or gives ±20dB when the floor is -Infinity
+    pub(crate) fn calculate_lr_balance(&self, sums: &StereoSums) -> f32 {
+        if sums.frames == 0 {
             return 0.0;
         }
 
-        let mut left_energy = 0.0;
-        let mut right_energy = 0.0;
-
-        // Calculate RMS energy for each channel
-        for i in 0..left.len() {
-            left_energy += left[i] * left[i];
-            right_energy += right[i] * right[i];
-        }
-
-        let left_rms = (left_energy / left.len() as f32).sqrt();
-        let right_rms = (right_energy / right.len() as f32).sqrt();
+        // RMS of each channel
+        let left_rms = (sums.ll / sums.frames as f64).sqrt() as f32;
+        let right_rms = (sums.rr / sums.frames as f64).sqrt() as f32;
 
         // Convert to dB difference
-        if left_rms > 1e-10 && right_rms > 1e-10 {
-            20.0 * (right_rms / left_rms).log10()
-        } else if right_rms > 1e-10 {
+        let level = |rms: f32| self.db.to_db(if rms > 1e-10 { rms } else { 0.0 });
+        let (left_db, right_db) = (level(left_rms), level(right_rms));
+        if left_db.is_finite() && right_db.is_finite() {
+            right_db - left_db
+        } else if right_db.is_finite() {
             20.0 // Right only
-        } else if left_rms > 1e-10 {
+        } else if left_db.is_finite() {
             -20.0 // Left only
         } else {
             0.0 // No signal
         }
     }
 
-    // Test mono compatibility by checking for phase cancellation
-    // Returns value between 0 (bad mono compatibility) and 1 (perfect mono compatibility)
-    fn calculate_mono_compatibility(&self, left: &[f32], right: &[f32]) -> f32 {
-        if left.len() != right.len() || left.is_empty() {
-            return 1.0;
-        }
-
-        let mut stereo_energy = 0.0;
-        let mut mono_energy = 0.0;
-
-        // Calculate energy of stereo signal vs mono sum
-        for i in 0..left.len() {
-            let stereo_sample = (left[i] + right[i]) * 0.5;
-            let mono_sample = stereo_sample;
-            
-            stereo_energy += left[i] * left[i] + right[i] * right[i];
-            mono_energy += mono_sample * mono_sample * 2.0; // Multiply by 2 for fair comparison
-        }
-
-        if stereo_energy > 1e-10 {
-            (mono_energy / stereo_energy).min(1.0)
-        } else {
-            1.0
-        }
-    }
-
     // Analyze stereo imaging quality based on phase coherence across frequency bands - Optimized
     fn calculate_imaging_quality(&self, left: &[f32], right: &[f32]) -> f32 {
         if left.len() != right.len() || left.is_empty() {
@@ -156,37 +280,17 @@ impl StereoAnalyzer {
         }
 
         // Analyze phase coherence in different frequency bands
-        let window_size = 512.min(left.len()); // Smaller window for speed
-        let mut coherence_sum = 0.0;
-        let mut window_count = 0;
+        let window_size = COHERENCE_WINDOW.min(left.len()); // Smaller window for speed
+        let mut coherence = WindowCoherence::default();
 
-        // Process overlapping windows with larger steps for speed
-        let step_size = if left.len() > 44100 * 10 { window_size } else { window_size / 2 }; // Larger steps for long files
-        for start in (0..left.len().saturating_sub(window_size)).step_by(step_size) {
+        // Overlapping windows, without overlap on long files for speed (the
+        // channels are decimated by the limits, so at that lower rate)
+        for start in (0..left.len().saturating_sub(window_size)).step_by(self.coherence_hop(left.len())) {
             let end = (start + window_size).min(left.len());
-            let window_left = &left[start..end];
-            let window_right = &right[start..end];
-
-            // Calculate phase correlation for this window
-            let correlation = self.calculate_phase_correlation(window_left, window_right);
-            
-            // Weight by window energy to focus on audible content
-            let mut window_energy = 0.0;
-            for i in 0..window_left.len() {
-                window_energy += window_left[i] * window_left[i] + window_right[i] * window_right[i];
-            }
-
-            if window_energy > 1e-8 {
-                coherence_sum += correlation.abs() * window_energy.sqrt();
-                window_count += 1;
-            }
-        }
-
-        if window_count > 0 {
-            (coherence_sum / window_count as f32).min(1.0)
-        } else {
-            0.0
+            coherence.add(self, &left[start..end], &right[start..end]);
         }
+
+        coherence.score()
     }
 
     // Classify stereo imaging quality
@@ -206,45 +310,124 @@ impl StereoAnalyzer {
         }
     }
 
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_stereo)]
+    pub fn analyze_stereo_js(&self, pcm: &Float32Array) -> Result<StereoResult, JsError> {
+        // Copy PCM into WASM memory once (no more than the memory ceiling allows,
+        // keeping whole frames), then split channels
+        let total = pcm.length() as usize;
+        let keep = if total.is_multiple_of(2) { self.limits.max_samples(total, BYTES_PER_SAMPLE) & !1 } else { total };
+        Ok(self.analyze_prefix(&pcm.subarray(0, keep as u32).to_vec(), total)?)
+    }
+
+    // Analyse samples already written into WASM memory (no copy)
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen]
-    pub fn analyze_stereo(&self, pcm: &Float32Array) -> JsValue {
-        // Check if we have stereo data (even number of samples)
-        if pcm.length() % 2 != 0 {
-            // Return mono analysis result
-            let result = js_sys::Object::new();
-            js_sys::Reflect::set(&result, &"is_mono".into(), &true.into()).unwrap();
-            js_sys::Reflect::set(&result, &"channels".into(), &1.into()).unwrap();
-            js_sys::Reflect::set(&result, &"mono_compatibility".into(), &1.0.into()).unwrap();
-            js_sys::Reflect::set(&result, &"imaging_quality".into(), &"Perfect".into()).unwrap();
-            return result.into();
-        }
+    pub fn analyze_stereo_buffer(&self, buffer: &PcmBuffer) -> Result<StereoResult, JsError> {
+        Ok(self.analyze_stereo(buffer.as_slice())?)
+    }
+
+    // Phase correlation every 100ms, read back page by page
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = correlation_history)]
+    pub fn correlation_history_js(&self, pcm: &Float32Array) -> Result<TimeSeries, JsError> {
+        Ok(self.correlation_history(&pcm.to_vec())?)
+    }
+}
 
-        // Extract stereo channels
+impl StereoAnalyzer {
+    /// Stereo image analysis of interleaved L/R PCM (odd-length input is
+    /// reported as mono rather than rejected)
+    pub fn analyze_stereo(&self, pcm: &[f32]) -> Result<StereoResult, AnalysisError> {
+        self.analyze_prefix(pcm, pcm.len())
+    }
+
+    /// Phase correlation of 400ms windows of interleaved L/R PCM, one per
+    /// 100ms hop (within the duration and decimation limits)
+    pub fn correlation_history(&self, pcm: &[f32]) -> Result<TimeSeries, AnalysisError> {
+        validate_pcm(pcm, 2, 1)?;
         let (left, right) = self.extract_stereo_channels(pcm);
+        let rate = self.sample_rate / self.limits.decimation() as f32;
+        if left.is_empty() {
+            return Ok(TimeSeries::new(CORRELATION_HOP, 1, Vec::new()));
+        }
+
+        let window = ((CORRELATION_WINDOW * rate) as usize).clamp(1, left.len());
+        let hop = ((CORRELATION_HOP * rate) as usize).max(1);
+        let correlation = (0..=left.len() - window)
+            .step_by(hop)
+            .map(|start| self.calculate_phase_correlation(&left[start..start + window], &right[start..start + window]))
+            .collect();
+        Ok(TimeSeries::new(hop as f32 / rate, 1, correlation))
+    }
+
+    // Validate and analyse `samples`, which may be a prefix of `total_samples` input samples
+    fn analyze_prefix(&self, samples: &[f32], total_samples: usize) -> Result<StereoResult, AnalysisError> {
+        validate_pcm(samples, 1, 1)?;
+        Ok(self.analyze_samples(samples, total_samples))
+    }
+
+    // Stereo analysis of interleaved samples already in WASM memory, which may
+    // be a prefix of `total_samples` input samples
+    pub(crate) fn analyze_samples(&self, samples: &[f32], total_samples: usize) -> StereoResult {
+        // Check if we have stereo data (even number of samples)
+        if !samples.len().is_multiple_of(2) {
+            return Self::mono_result();
+        }
 
-        // Perform all stereo analysis calculations
-        let phase_correlation = self.calculate_phase_correlation(&left, &right);
-        let stereo_width = self.calculate_stereo_width(&left, &right);
-        let lr_balance = self.calculate_lr_balance(&left, &right);
-        let mono_compatibility = self.calculate_mono_compatibility(&left, &right);
+        let (left, right) = self.extract_stereo_channels(samples);
+        let mut sums = StereoSums::default();
+        sums.add(&left, &right);
         let imaging_quality_score = self.calculate_imaging_quality(&left, &right);
+
+        let analyzed_frames = (samples.len() / 2).min(self.max_analyzed_frames());
+        self.stereo_result(&sums, imaging_quality_score, analyzed_frames, total_samples, silent_share(samples, DEFAULT_SILENCE_THRESHOLD))
+    }
+
+    // Result of a stereo analysis, from the sums and coherence score over the
+    // `analyzed_frames` (before decimation) the limits let it read
+    pub(crate) fn stereo_result(&self, sums: &StereoSums, imaging_quality_score: f32, analyzed_frames: usize, total_samples: usize, silent_share: f32) -> StereoResult {
+        let phase_correlation = sums.correlation();
+        let stereo_width = sums.width();
+        let lr_balance = self.calculate_lr_balance(sums);
+        let mono_compatibility = sums.mono_compatibility();
         let imaging_quality = self.classify_imaging(phase_correlation, stereo_width, mono_compatibility);
 
-        // Create result object
-        let result = js_sys::Object::new();
-        
-        // Basic info
-        js_sys::Reflect::set(&result, &"is_mono".into(), &false.into()).unwrap();
-        js_sys::Reflect::set(&result, &"channels".into(), &2.into()).unwrap();
-        
-        // Stereo analysis results
-        js_sys::Reflect::set(&result, &"phase_correlation".into(), &phase_correlation.into()).unwrap();
-        js_sys::Reflect::set(&result, &"stereo_width".into(), &stereo_width.into()).unwrap();
-        js_sys::Reflect::set(&result, &"lr_balance".into(), &lr_balance.into()).unwrap();
-        js_sys::Reflect::set(&result, &"mono_compatibility".into(), &mono_compatibility.into()).unwrap();
-        js_sys::Reflect::set(&result, &"imaging_quality_score".into(), &imaging_quality_score.into()).unwrap();
-        js_sys::Reflect::set(&result, &"imaging_quality".into(), &imaging_quality.into()).unwrap();
+        // Applied limits
+        let limits = self.limits.report(analyzed_frames as f32 / self.sample_rate, (total_samples / 2) as f32 / self.sample_rate);
+        if limits.truncated {
+            log::info!("Stereo analysis limited to {:.1}s", limits.analyzed_duration);
+        }
+        let too_short = (sums.frames as f32) < CORRELATION_WINDOW * self.analysis_rate();
+        let status = MetricStatus::assess(too_short, silent_share, limits.truncated);
+
+        StereoResult {
+            is_mono: false,
+            channels: 2,
+            phase_correlation: Some(phase_correlation),
+            stereo_width: Some(stereo_width),
+            lr_balance: Some(lr_balance),
+            mono_compatibility,
+            imaging_quality_score: Some(imaging_quality_score),
+            imaging_quality: imaging_quality.to_string(),
+            limits: Some(limits),
+            status,
+        }
+    }
 
-        result.into()
+    // Result for odd-length (mono) input
+    pub(crate) fn mono_result() -> StereoResult {
+        StereoResult {
+            is_mono: true,
+            channels: 1,
+            phase_correlation: None,
+            stereo_width: None,
+            lr_balance: None,
+            mono_compatibility: 1.0,
+            imaging_quality_score: None,
+            imaging_quality: "Perfect".to_string(),
+            limits: None,
+            status: MetricStatus::Ok,
+        }
     }
-} 
\ No newline at end of file
+}
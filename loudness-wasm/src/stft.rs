@@ -0,0 +1,154 @@
+// Short-time Fourier transform shared by the spectral analyses, so they agree
+// on one framing convention: whole frames of `frame_size` samples every `hop`
+// samples, the first at sample 0 and none running past the end, each
+// multiplied by the analysis window and zero-padded to the FFT size (the
+// next power of two). The tests check the framing with `synthesize`, which
+// inverts a run of frame spectra by weighted overlap-add, normalised by the
+// summed squared window, so the input comes back wherever the frames cover it
+// with non-zero window weight.
+
+use std::sync::Arc;
+use crate::simd::apply_window;
+use crate::utils::{plan_fft, window, Fft};
+use crate::window::Window;
+
+// Summed squared window below which an output sample counts as uncovered
+#[cfg(test)]
+const MIN_SYNTHESIS_WEIGHT: f32 = 1e-6;
+
+/// Complex spectrum of one frame, all `fft_size` bins
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spectrum {
+    pub real: Vec<f32>,
+    pub imag: Vec<f32>,
+}
+
+impl Spectrum {
+    /// Unnormalised magnitudes of bins 0..size/2
+    pub fn magnitudes(&self) -> Vec<f32> {
+        self.real.iter().zip(&self.imag).take(self.real.len() / 2).map(|(re, im)| (re * re + im * im).sqrt()).collect()
+    }
+}
+
+#[derive(Clone)]
+pub struct Stft {
+    fft: Arc<Fft>,
+    window: Arc<Vec<f32>>,
+    hop: usize,
+}
+
+impl Stft {
+    /// Frames `frame_size` samples long every `hop` samples, both at least 1;
+    /// plans and windows come from the shared planner
+    pub fn new(frame_size: usize, hop: usize, window: Window) -> Self {
+        let frame_size = frame_size.max(1);
+        Stft { fft: plan_fft(frame_size), window: self::window(window, frame_size), hop: hop.max(1) }
+    }
+
+    pub fn frame_size(&self) -> usize {
+        self.window.len()
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    pub fn fft(&self) -> &Fft {
+        &self.fft
+    }
+
+    /// Analysis window coefficients
+    pub fn window(&self) -> &[f32] {
+        &self.window
+    }
+
+    /// Start of every whole frame of a signal `len` samples long
+    pub fn frame_starts(&self, len: usize) -> impl Iterator<Item = usize> {
+        let frame_size = self.frame_size();
+        (0..len).step_by(self.hop).take_while(move |&start| start + frame_size <= len)
+    }
+
+    /// Window `frame` into `real` (`fft().size()` long, the padding zeroed),
+    /// returning the windowed energy
+    pub fn load(&self, frame: impl IntoIterator<Item = f32>, real: &mut [f32]) -> f32 {
+        real.fill(0.0);
+        for (out, sample) in real[..self.frame_size()].iter_mut().zip(frame) {
+            *out = sample;
+        }
+        apply_window(real, &self.window)
+    }
+
+    /// Spectrum of one frame
+    pub fn forward(&self, frame: impl IntoIterator<Item = f32>) -> Spectrum {
+        let mut real = vec![0.0; self.fft.size()];
+        let mut imag = vec![0.0; self.fft.size()];
+        self.load(frame, &mut real);
+        self.fft.process(&mut real, &mut imag);
+        Spectrum { real, imag }
+    }
+
+    /// Spectra of every whole frame of a mono signal
+    #[cfg(test)]
+    pub fn analyze(&self, signal: &[f32]) -> Vec<Spectrum> {
+        let frame_size = self.frame_size();
+        self.frame_starts(signal.len()).map(|start| self.forward(signal[start..start + frame_size].iter().copied())).collect()
+    }
+
+    /// Signal `len` samples long from the spectra of consecutive frames (as
+    /// `analyze` returns them, possibly modified) by weighted overlap-add;
+    /// samples no frame covers, or only the tapered window edges do, are zero
+    #[cfg(test)]
+    pub fn synthesize(&self, spectra: &[Spectrum], len: usize) -> Vec<f32> {
+        let mut output = vec![0.0f32; len];
+        let mut weights = vec![0.0f32; len];
+        let mut real = vec![0.0; self.fft.size()];
+        let mut imag = vec![0.0; self.fft.size()];
+        for (frame, spectrum) in spectra.iter().enumerate() {
+            real.copy_from_slice(&spectrum.real);
+            imag.copy_from_slice(&spectrum.imag);
+            self.fft.inverse(&mut real, &mut imag);
+
+            let start = (frame * self.hop).min(len);
+            let span = (len - start).min(self.frame_size());
+            let frame_output = output[start..start + span].iter_mut().zip(&mut weights[start..start + span]);
+            for ((out, weight), (&w, &sample)) in frame_output.zip(self.window.iter().zip(&real)) {
+                *out += sample * w;
+                *weight += w * w;
+            }
+        }
+
+        for (sample, &weight) in output.iter_mut().zip(&weights) {
+            *sample = if weight > MIN_SYNTHESIS_WEIGHT { *sample / weight } else { 0.0 };
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_and_overlap_add_round_trip() {
+        let signal: Vec<f32> = (0..5000).map(|i| 0.5 * (i as f32 * 0.05).sin() + 0.2 * (i as f32 * 0.9).cos()).collect();
+        let stft = Stft::new(1000, 250, Window::Hann);
+        assert_eq!(stft.fft().size(), 1024);
+        assert_eq!(stft.frame_starts(5000).collect::<Vec<_>>(), (0..=4000).step_by(250).collect::<Vec<_>>());
+        assert_eq!(stft.frame_starts(999).count(), 0);
+
+        let spectra = stft.analyze(&signal);
+        assert_eq!(spectra.len(), 17);
+        let rebuilt = stft.synthesize(&spectra, signal.len());
+        let error = signal[50..4950].iter().zip(&rebuilt[50..4950]).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+        assert!(error < 1e-4, "max error {}", error);
+        // The window tapers to zero, leaving the outermost samples uncovered
+        assert_eq!((rebuilt[0], rebuilt[4999]), (0.0, 0.0));
+
+        // Magnitudes agree with the plain FFT of the windowed frame
+        let mut windowed = signal[250..1250].to_vec();
+        Window::Hann.apply(&mut windowed);
+        let expected = stft.fft().magnitudes(&windowed);
+        let error = spectra[1].magnitudes().iter().zip(&expected).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+        assert!(error < 1e-3, "max error {}", error);
+    }
+}
@@ -0,0 +1,308 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use crate::constants::*;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::AnalyzerConfig;
+use crate::error::AnalysisError;
+use crate::gating::{block_loudness, Gate};
+use crate::loudness::{sum_channels, ChannelEnergy, LoudnessAnalyzer, LoudnessResult, SEGMENT};
+use crate::resample::{ResamplingReport, StreamResampler};
+use super::{MeterSnapshot, Pushed, StreamingAnalyzer};
+
+/// Running loudness (LUFS) while streaming
+#[derive(Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+#[serde(rename_all = "camelCase")]
+pub struct LoudnessSnapshot {
+    pub frames: usize,
+    pub momentary: f32,
+    pub short_term: f32,
+    pub momentary_max: f32,
+    pub short_term_max: f32,
+    pub integrated: f32,
+}
+
+// Sliding block measurement for one block length (momentary or short-term)
+pub(crate) struct BlockMeter {
+    block_size: usize,
+    hop: usize,
+    next_block: usize,
+    pub(crate) gated_energies: Vec<f32>,
+    pub(crate) last_loudness: f32,
+}
+
+impl BlockMeter {
+    pub(crate) fn new(block_size: usize, hop: usize) -> Self {
+        BlockMeter { block_size, hop, next_block: 0, gated_energies: Vec::new(), last_loudness: f32::NEG_INFINITY }
+    }
+
+    // Measure every block that is now complete, keeping those above the
+    // absolute gate; `totals[i]` is the running energy sum before segment
+    // `first_segment + i`, as `blockize` differences them
+    pub(crate) fn advance(&mut self, totals: &[f64], first_segment: usize) {
+        let (block, hop) = (self.block_size / SEGMENT, self.hop / SEGMENT);
+        while self.next_block * hop + block < first_segment + totals.len() {
+            let start = self.next_block * hop - first_segment;
+            let energy = ((totals[start + block] - totals[start]) / block as f64) as f32;
+            self.last_loudness = block_loudness(energy);
+            if Gate::INTEGRATED.passes_absolute(energy) {
+                self.gated_energies.push(energy);
+            }
+            self.next_block += 1;
+        }
+    }
+
+    // First segment a block not yet measured starts at
+    pub(crate) fn next_segment(&self) -> usize {
+        self.next_block * self.hop / SEGMENT
+    }
+}
+
+// Momentary and short-term meters over input that may arrive in any chunking
+// (frames split across chunks too), resampled to 44.1kHz first when off rate.
+// Each channel's K-weighting runs on as frames arrive, and only the running
+// energy totals the open blocks still need are kept. Streams filter in f32,
+// as the batch analyzer does by default
+pub(crate) struct BlockWindow {
+    num_channels: usize,
+    resampler: Option<StreamResampler>,
+    // Samples of a frame split across chunks, held back until it is whole
+    partial: Vec<f32>,
+    channels: Vec<ChannelEnergy<f32>>,
+    // Running energy totals from segment `first_segment` on
+    pub(crate) totals: Vec<f64>,
+    first_segment: usize,
+    samples: usize,
+    pub(crate) momentary: BlockMeter,
+    pub(crate) short_term: BlockMeter,
+}
+
+impl BlockWindow {
+    pub(crate) fn new(num_channels: usize) -> Self {
+        BlockWindow::for_rate(BLOCK_SAMPLE_RATE, num_channels)
+    }
+
+    // Window over input at `sample_rate`
+    pub(crate) fn for_rate(sample_rate: f32, num_channels: usize) -> Self {
+        let off_rate = sample_rate > 0.0 && (sample_rate - BLOCK_SAMPLE_RATE).abs() >= 0.5;
+        BlockWindow {
+            num_channels,
+            resampler: off_rate.then(|| StreamResampler::new(sample_rate, BLOCK_SAMPLE_RATE, num_channels)),
+            partial: Vec::new(),
+            channels: (0..num_channels).map(|_| ChannelEnergy::new()).collect(),
+            totals: vec![0.0],
+            first_segment: 0,
+            samples: 0,
+            momentary: BlockMeter::new(MOMENTARY_BLOCK_SIZE, MOMENTARY_HOP),
+            short_term: BlockMeter::new(SHORT_TERM_BLOCK_SIZE, SHORT_TERM_HOP),
+        }
+    }
+
+    // Whole 44.1kHz frames so far
+    pub(crate) fn frames(&self) -> usize {
+        self.samples / self.num_channels
+    }
+
+    pub(crate) fn push(&mut self, pcm: &[f32]) {
+        self.partial.extend_from_slice(pcm);
+        let whole = self.partial.len() / self.num_channels * self.num_channels;
+        let frames: Vec<f32> = self.partial.drain(..whole).collect();
+        match &mut self.resampler {
+            Some(resampler) => {
+                let resampled = resampler.push(&frames);
+                self.measure(&resampled);
+            }
+            None => self.measure(&frames),
+        }
+    }
+
+    // Measure the resampler's tail once the input has ended
+    pub(crate) fn finish(&mut self) -> Option<ResamplingReport> {
+        let resampler = self.resampler.as_mut()?;
+        let (tail, report) = (resampler.finish(), resampler.report());
+        self.measure(&tail);
+        Some(report)
+    }
+
+    // K-weight whole 44.1kHz frames, measure the blocks they complete, then
+    // drop the totals before the earliest block still open
+    fn measure(&mut self, pcm: &[f32]) {
+        let n = self.num_channels;
+        let segments: Vec<Vec<f64>> = self.channels.iter_mut().enumerate()
+            .map(|(ch, channel)| {
+                let mut segments = Vec::new();
+                channel.push(pcm.iter().skip(ch).step_by(n).copied(), &mut segments);
+                segments
+            })
+            .collect();
+        for energy in sum_channels(&segments, &vec![1.0; n]) {
+            self.totals.push(self.totals[self.totals.len() - 1] + energy);
+        }
+        self.samples += pcm.len();
+        self.momentary.advance(&self.totals, self.first_segment);
+        self.short_term.advance(&self.totals, self.first_segment);
+
+        let keep_from = self.momentary.next_segment().min(self.short_term.next_segment());
+        if keep_from > self.first_segment {
+            self.totals.drain(..keep_from - self.first_segment);
+            self.first_segment = keep_from;
+        }
+    }
+
+    // Running values, reported against `frames` input frames
+    pub(crate) fn snapshot(&self, analyzer: &LoudnessAnalyzer, frames: usize) -> LoudnessSnapshot {
+        LoudnessSnapshot {
+            frames,
+            momentary: self.momentary.last_loudness,
+            short_term: self.short_term.last_loudness,
+            momentary_max: analyzer.calculate_max_loudness(&self.momentary.gated_energies),
+            short_term_max: analyzer.calculate_max_loudness(&self.short_term.gated_energies),
+            integrated: analyzer.calculate_integrated_loudness(&self.momentary.gated_energies),
+        }
+    }
+
+    // Latest momentary and short-term blocks, the only readings loudness streams take
+    pub(crate) fn meter_snapshot(&self) -> MeterSnapshot {
+        MeterSnapshot {
+            momentary: self.momentary.last_loudness,
+            short_term: self.short_term.last_loudness,
+            true_peak: f32::NEG_INFINITY,
+            correlation: 0.0,
+            width: 0.0,
+        }
+    }
+
+    // Gated result over every block measured, as the batch analyzer reports it
+    pub(crate) fn result(&self, analyzer: &LoudnessAnalyzer, pcm_debug: Vec<f32>) -> LoudnessResult {
+        analyzer.result_from_energies(pcm_debug, self.frames(), &self.momentary.gated_energies, &self.short_term.gated_energies)
+    }
+}
+
+// Streaming EBU R128 loudness: momentary and short-term blocks are measured as
+// soon as they are complete, integrated loudness is re-gated on each poll
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct LoudnessStream {
+    analyzer: LoudnessAnalyzer,
+    num_channels: usize,
+    pushed: Pushed,
+    // First samples, for the result's debug field
+    head: Vec<f32>,
+    blocks: BlockWindow,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl LoudnessStream {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(num_channels: usize) -> Self {
+        let num_channels = num_channels.max(1);
+        LoudnessStream {
+            analyzer: LoudnessAnalyzer::new(num_channels),
+            num_channels,
+            pushed: Pushed::default(),
+            head: Vec::with_capacity(5),
+            blocks: BlockWindow::new(num_channels),
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        LoudnessStream::new(config.num_channels())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = push)]
+    pub fn push_chunk(&mut self, chunk: &Float32Array) {
+        StreamingAnalyzer::push(self, &chunk.to_vec());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = poll)]
+    pub fn poll_results(&self) -> LoudnessSnapshot {
+        StreamingAnalyzer::poll(self)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = get_meter_snapshot)]
+    pub fn meter_snapshot_results(&self) -> MeterSnapshot {
+        StreamingAnalyzer::meter_snapshot(self)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = finalize)]
+    pub fn finalize_results(&mut self) -> Result<LoudnessResult, JsError> {
+        Ok(StreamingAnalyzer::finalize(self)?)
+    }
+}
+
+impl StreamingAnalyzer for LoudnessStream {
+    type Snapshot = LoudnessSnapshot;
+    type Output = LoudnessResult;
+
+    fn push(&mut self, chunk: &[f32]) {
+        self.pushed.record(chunk);
+        self.head.extend(chunk.iter().take(5 - self.head.len()));
+        self.blocks.push(chunk);
+    }
+
+    fn poll(&self) -> LoudnessSnapshot {
+        self.blocks.snapshot(&self.analyzer, self.blocks.frames())
+    }
+
+    fn meter_snapshot(&self) -> MeterSnapshot {
+        self.blocks.meter_snapshot()
+    }
+
+    fn finalize(&mut self) -> Result<LoudnessResult, AnalysisError> {
+        self.pushed.validate(self.num_channels, MOMENTARY_BLOCK_SIZE)?;
+        Ok(self.blocks.result(&self.analyzer, self.head.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunked_pushes_match_a_single_push() {
+        let pcm: Vec<f32> = (0..2 * 48000).map(|i| 0.3 * (i as f32 * 0.05).sin()).collect();
+
+        let mut whole = LoudnessStream::new(2);
+        StreamingAnalyzer::push(&mut whole, &pcm);
+
+        let mut chunked = LoudnessStream::new(2);
+        for chunk in pcm.chunks(1001) {
+            StreamingAnalyzer::push(&mut chunked, chunk);
+        }
+
+        assert!(!whole.blocks.momentary.gated_energies.is_empty());
+        assert_eq!(whole.blocks.momentary.gated_energies, chunked.blocks.momentary.gated_energies);
+        assert_eq!(whole.blocks.short_term.next_block, chunked.blocks.short_term.next_block);
+    }
+
+    #[test]
+    fn keeps_only_open_blocks_and_matches_the_batch_result() {
+        let pcm: Vec<f32> = (0..2 * 20 * 44100).map(|i| 0.3 * ((i / 2) as f32 * 0.05).sin() * (1.0 + (i as f32 * 1e-5).sin())).collect();
+
+        let mut stream = LoudnessStream::new(2);
+        for chunk in pcm.chunks(4097) {
+            StreamingAnalyzer::push(&mut stream, chunk);
+            assert!(stream.blocks.totals.len() <= SHORT_TERM_BLOCK_SIZE / SEGMENT);
+        }
+        let streamed = StreamingAnalyzer::finalize(&mut stream).unwrap();
+        let batch = LoudnessAnalyzer::new(2).analyze(&pcm, None).unwrap();
+        assert_eq!(streamed.integrated, batch.integrated);
+        assert_eq!(streamed.short_term, batch.short_term);
+        assert_eq!(streamed.loudness_range, batch.loudness_range);
+        assert_eq!(streamed.pcm_debug, batch.pcm_debug);
+        assert_eq!(streamed.total_blocks, batch.total_blocks);
+
+        let mut partial = LoudnessStream::new(2);
+        StreamingAnalyzer::push(&mut partial, &pcm[..2 * 44100 + 1]);
+        assert!(matches!(StreamingAnalyzer::finalize(&mut partial), Err(AnalysisError::InvalidChannelCount { .. })));
+    }
+}
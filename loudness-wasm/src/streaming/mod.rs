@@ -0,0 +1,92 @@
+// Push/finalize streaming analysis
+//
+// Streams accept PCM chunks as they arrive (e.g. while a file is still
+// downloading) and measure them as they go: only rolling state is kept (block
+// energies, running sums, per-window summaries and the few frames the open
+// windows still need), never the whole signal, so memory doesn't grow with
+// the samples themselves. Running values can be polled at any time; finalize
+// builds the result from the same state. Loudness and technical results equal
+// the one-shot analyzers'; the stereo sums are accumulated chunk by chunk, so
+// those match up to float rounding.
+
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::error::AnalysisError;
+#[cfg(any(feature = "loudness", feature = "stereo", feature = "technical"))]
+use crate::error::validate_counts;
+
+#[cfg(feature = "loudness")]
+mod loudness;
+#[cfg(feature = "stereo")]
+mod stereo;
+#[cfg(feature = "technical")]
+mod technical;
+
+#[cfg(feature = "loudness")]
+pub(crate) use loudness::BlockWindow;
+#[cfg(feature = "loudness")]
+pub use loudness::{LoudnessSnapshot, LoudnessStream};
+#[cfg(feature = "stereo")]
+pub use stereo::{StereoSnapshot, StereoStream};
+#[cfg(feature = "technical")]
+pub use technical::{TechnicalSnapshot, TechnicalStream};
+
+/// Every live reading in one object, cheap enough to poll at display rate
+/// (10-30 Hz); loudness in LUFS, true peak in dBTP. Readings a meter doesn't
+/// take are -Infinity (levels) or 0 (correlation, width)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct MeterSnapshot {
+    pub momentary: f32,
+    pub short_term: f32,
+    // Held true peak
+    pub true_peak: f32,
+    pub correlation: f32,
+    // Side share of the stereo energy, 0 (mono) to 1, as StereoResult's width
+    pub width: f32,
+}
+
+/// Common interface of incremental analyzers
+pub trait StreamingAnalyzer {
+    /// Provisional results available while streaming
+    type Snapshot;
+    /// Final result, as the batch analyzer would report it
+    type Output;
+
+    /// Append a chunk of interleaved samples
+    fn push(&mut self, chunk: &[f32]);
+
+    /// Provisional results over the samples pushed so far
+    fn poll(&self) -> Self::Snapshot;
+
+    /// Current meter readings, for live meter UIs
+    fn meter_snapshot(&self) -> MeterSnapshot;
+
+    /// Result over every pushed sample
+    fn finalize(&mut self) -> Result<Self::Output, AnalysisError>;
+}
+
+// Count and first non-finite index of every pushed sample, so finalize can
+// validate input the stream no longer holds
+#[cfg(any(feature = "loudness", feature = "stereo", feature = "technical"))]
+#[derive(Default)]
+pub(crate) struct Pushed {
+    pub(crate) samples: usize,
+    non_finite: Option<usize>,
+}
+
+#[cfg(any(feature = "loudness", feature = "stereo", feature = "technical"))]
+impl Pushed {
+    pub(crate) fn record(&mut self, chunk: &[f32]) {
+        if self.non_finite.is_none() {
+            self.non_finite = chunk.iter().position(|sample| !sample.is_finite()).map(|index| self.samples + index);
+        }
+        self.samples += chunk.len();
+    }
+
+    pub(crate) fn validate(&self, num_channels: usize, min_frames: usize) -> Result<(), AnalysisError> {
+        validate_counts(self.samples, self.non_finite, num_channels, min_frames)
+    }
+}
@@ -0,0 +1,245 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::{AnalyzerConfig, DEFAULT_SILENCE_THRESHOLD};
+use crate::error::AnalysisError;
+use crate::stereo::{StereoAnalyzer, StereoResult, StereoSums, WindowCoherence, COHERENCE_WINDOW};
+use crate::utils::silent_count;
+use super::{MeterSnapshot, Pushed, StreamingAnalyzer};
+
+/// Running stereo correlation and L/R balance (dB)
+#[derive(Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct StereoSnapshot {
+    pub frames: usize,
+    pub phase_correlation: f32,
+    pub lr_balance: f32,
+}
+
+// Imaging quality windows over the analysed frames as they arrive, on the
+// half-window grid and the full-window one (the batch hop depends on the final
+// length); only the frames of windows not yet measured are kept
+#[derive(Default)]
+struct CoherenceWindows {
+    left: Vec<f32>,
+    right: Vec<f32>,
+    // Analysed frame `left[0]` is
+    first: usize,
+    next_start: usize,
+    overlapped: WindowCoherence,
+    spaced: WindowCoherence,
+}
+
+impl CoherenceWindows {
+    fn push(&mut self, analyzer: &StereoAnalyzer, left: &[f32], right: &[f32]) {
+        self.left.extend_from_slice(left);
+        self.right.extend_from_slice(right);
+
+        // Batch windows end before the last analysed frame, so a window is
+        // measured once a frame past it has arrived
+        while self.next_start + COHERENCE_WINDOW < self.first + self.left.len() {
+            let start = self.next_start - self.first;
+            let (left, right) = (&self.left[start..start + COHERENCE_WINDOW], &self.right[start..start + COHERENCE_WINDOW]);
+            self.overlapped.add(analyzer, left, right);
+            if self.next_start.is_multiple_of(COHERENCE_WINDOW) {
+                self.spaced.add(analyzer, left, right);
+            }
+            self.next_start += COHERENCE_WINDOW / 2;
+        }
+
+        let drop = self.next_start - self.first;
+        if drop > 0 {
+            self.left.drain(..drop);
+            self.right.drain(..drop);
+            self.first = self.next_start;
+        }
+    }
+
+    // Score over `analyzed` frames, at the hop the batch analysis would use
+    fn score(&self, analyzer: &StereoAnalyzer, analyzed: usize) -> f32 {
+        if analyzer.coherence_hop(analyzed) < COHERENCE_WINDOW {
+            self.overlapped.score()
+        } else {
+            self.spaced.score()
+        }
+    }
+}
+
+// Streaming stereo analysis: running correlation, width and balance sums, and
+// the imaging quality windows measured as they complete
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct StereoStream {
+    analyzer: StereoAnalyzer,
+    pushed: Pushed,
+    // Left sample of a frame split across chunks
+    pending_left: Option<f32>,
+    frames: usize,
+    // Frames past this one are beyond the duration / memory limits
+    max_frames: usize,
+    decimation: usize,
+    silent_samples: usize,
+    sums: StereoSums,
+    coherence: CoherenceWindows,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl StereoStream {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32) -> Self {
+        StereoStream::from_config(&AnalyzerConfig::new(sample_rate, 2))
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        let analyzer = StereoAnalyzer::from_config(config);
+        StereoStream {
+            max_frames: analyzer.max_analyzed_frames(),
+            decimation: config.limits().decimation(),
+            analyzer,
+            pushed: Pushed::default(),
+            pending_left: None,
+            frames: 0,
+            silent_samples: 0,
+            sums: StereoSums::default(),
+            coherence: CoherenceWindows::default(),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = push)]
+    pub fn push_chunk(&mut self, chunk: &Float32Array) {
+        StreamingAnalyzer::push(self, &chunk.to_vec());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = poll)]
+    pub fn poll_results(&self) -> StereoSnapshot {
+        StreamingAnalyzer::poll(self)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = get_meter_snapshot)]
+    pub fn meter_snapshot_results(&self) -> MeterSnapshot {
+        StreamingAnalyzer::meter_snapshot(self)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = finalize)]
+    pub fn finalize_results(&mut self) -> Result<StereoResult, JsError> {
+        Ok(StreamingAnalyzer::finalize(self)?)
+    }
+}
+
+impl StreamingAnalyzer for StereoStream {
+    type Snapshot = StereoSnapshot;
+    type Output = StereoResult;
+
+    fn push(&mut self, chunk: &[f32]) {
+        self.pushed.record(chunk);
+        self.silent_samples += silent_count(chunk, DEFAULT_SILENCE_THRESHOLD);
+
+        // Chunks may split a frame, so complete the pending one first
+        let mut samples = chunk;
+        let mut frames = Vec::new();
+        if let Some(left) = self.pending_left.take() {
+            match samples.split_first() {
+                Some((&right, rest)) => {
+                    frames.push((left, right));
+                    samples = rest;
+                }
+                None => self.pending_left = Some(left),
+            }
+        }
+        frames.extend(samples.chunks_exact(2).map(|frame| (frame[0], frame[1])));
+        if samples.len() % 2 == 1 {
+            self.pending_left = samples.last().copied();
+        }
+
+        // Keep the frames within the limits, one per decimation step
+        let first = self.frames;
+        self.frames += frames.len();
+        let (left, right): (Vec<f32>, Vec<f32>) = frames
+            .into_iter()
+            .enumerate()
+            .filter(|&(index, _)| first + index < self.max_frames && (first + index).is_multiple_of(self.decimation))
+            .map(|(_, frame)| frame)
+            .unzip();
+        self.sums.add(&left, &right);
+        self.coherence.push(&self.analyzer, &left, &right);
+    }
+
+    fn poll(&self) -> StereoSnapshot {
+        StereoSnapshot {
+            frames: self.frames,
+            phase_correlation: self.sums.correlation(),
+            lr_balance: self.analyzer.calculate_lr_balance(&self.sums),
+        }
+    }
+
+    fn meter_snapshot(&self) -> MeterSnapshot {
+        MeterSnapshot {
+            momentary: f32::NEG_INFINITY,
+            short_term: f32::NEG_INFINITY,
+            true_peak: f32::NEG_INFINITY,
+            correlation: self.sums.correlation(),
+            width: self.sums.width(),
+        }
+    }
+
+    fn finalize(&mut self) -> Result<StereoResult, AnalysisError> {
+        self.pushed.validate(1, 1)?;
+        if self.pending_left.is_some() {
+            return Ok(StereoAnalyzer::mono_result());
+        }
+
+        let analyzed_frames = self.frames.min(self.max_frames);
+        let imaging_quality_score = self.coherence.score(&self.analyzer, self.sums.frames);
+        let silent_share = self.silent_samples as f32 / self.pushed.samples as f32;
+        Ok(self.analyzer.stereo_result(&self.sums, imaging_quality_score, analyzed_frames, self.pushed.samples, silent_share))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_batch_analysis_while_keeping_only_open_windows() {
+        let pcm: Vec<f32> = (0..2 * 3 * 44100)
+            .map(|i| {
+                let t = (i / 2) as f32 / 44100.0;
+                let phase = if i % 2 == 0 { 0.0 } else { 0.7 };
+                0.4 * (2.0 * std::f32::consts::PI * 330.0 * t + phase).sin() * (1.0 + (t * 3.0).sin())
+            })
+            .collect();
+        let batch = StereoAnalyzer::new(44100.0).analyze_stereo(&pcm).unwrap();
+
+        let mut stream = StereoStream::new(44100.0);
+        for chunk in pcm.chunks(1001) {
+            StreamingAnalyzer::push(&mut stream, chunk);
+            assert!(stream.coherence.left.len() <= COHERENCE_WINDOW + chunk.len());
+        }
+        let meter = StreamingAnalyzer::meter_snapshot(&stream);
+        let streamed = StreamingAnalyzer::finalize(&mut stream).unwrap();
+
+        let close = |a: Option<f32>, b: Option<f32>| (a.unwrap() - b.unwrap()).abs() < 1e-4;
+        assert!(close(Some(meter.correlation), batch.phase_correlation));
+        assert!(close(Some(meter.width), batch.stereo_width));
+        assert_eq!(meter.momentary, f32::NEG_INFINITY);
+        assert!(close(streamed.phase_correlation, batch.phase_correlation));
+        assert!(close(streamed.stereo_width, batch.stereo_width));
+        assert!(close(streamed.lr_balance, batch.lr_balance));
+        assert!((streamed.mono_compatibility - batch.mono_compatibility).abs() < 1e-4);
+        assert_eq!(streamed.imaging_quality_score, batch.imaging_quality_score);
+        assert_eq!(streamed.status, batch.status);
+
+        let mut odd = StereoStream::new(44100.0);
+        StreamingAnalyzer::push(&mut odd, &pcm[..2001]);
+        assert!(StreamingAnalyzer::finalize(&mut odd).unwrap().is_mono);
+    }
+}
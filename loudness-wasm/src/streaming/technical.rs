@@ -0,0 +1,518 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use std::sync::Arc;
+use crate::config::AnalyzerConfig;
+use crate::error::AnalysisError;
+use crate::limits::BYTES_PER_SAMPLE;
+use crate::loudness::LoudnessAnalyzer;
+use crate::onset::{envelope_from_bands, onset_bands, pick_onsets, ONSET_FRAME_SIZE, ONSET_HOP};
+use crate::simd::max_abs;
+use crate::stft::Stft;
+use crate::technical::{channel_samples, punch_window, spectral_summary, true_peak_block, SilenceTracker, SpectralWindow, TechnicalAnalyzer, TechnicalMeasures, TechnicalResult, TRUE_PEAK_BLOCK_FRAMES};
+use crate::utils::{hilbert_block, mix_to_mono, plan_fft, silent_count, DbScale, Fft, FixedFft, Polyphase, HILBERT_BLOCK, HILBERT_HOP, HILBERT_MARGIN};
+use super::{MeterSnapshot, BlockWindow, Pushed, StreamingAnalyzer};
+
+/// Running sample peak (dBFS), clipping and DC offset
+#[derive(Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct TechnicalSnapshot {
+    pub samples: usize,
+    pub peak: f32,
+    pub clipped_samples: u32,
+    pub dc_offset: f32,
+}
+
+// True peak of each channel, oversampled block by block as the frames (and
+// the kernel's reach past them) arrive
+struct TruePeakBlocks {
+    oversampler: Polyphase,
+    reach: usize,
+    next_block: usize,
+    channels: Vec<(f32, Vec<f32>)>,
+}
+
+impl TruePeakBlocks {
+    // Measure the blocks `frames` (held from `first_frame` on) complete; once
+    // `frames` is final, every block left
+    fn advance(&mut self, frames: &[f32], first_frame: usize, num_channels: usize, whole: usize, last: bool) {
+        while self.next_block < whole && (last || self.next_block + TRUE_PEAK_BLOCK_FRAMES + self.reach <= whole) {
+            let block = self.next_block;
+            let end = (block + TRUE_PEAK_BLOCK_FRAMES).min(whole);
+            let start = block.saturating_sub(self.reach);
+            for (channel, peak) in self.channels.iter_mut().enumerate() {
+                let input: Vec<f32> = (start..(end + self.reach).min(whole)).map(|frame| frames[(frame - first_frame) * num_channels + channel]).collect();
+                true_peak_block(&self.oversampler, &input, start, block, end, peak);
+            }
+            self.next_block += TRUE_PEAK_BLOCK_FRAMES;
+        }
+    }
+
+    // First frame a block still to measure reads
+    fn needed_from(&self) -> usize {
+        self.next_block.saturating_sub(self.reach)
+    }
+}
+
+// Spectral windows of every channel on the half-window grid (the batch hop
+// depends on the final length), as each one's frames arrive
+struct SpectralWindows {
+    stft: Stft,
+    fixed: Option<FixedFft>,
+    window_size: usize,
+    step: usize,
+    // Windows start before the duration cap
+    max_start: usize,
+    next_start: usize,
+    channels: Vec<Vec<Option<SpectralWindow>>>,
+}
+
+impl SpectralWindows {
+    fn advance(&mut self, analyzer: &TechnicalAnalyzer, frames: &[f32], first_frame: usize, num_channels: usize, whole: usize) {
+        while self.next_start < self.max_start && self.next_start + self.window_size <= whole {
+            let offset = (self.next_start - first_frame) * num_channels;
+            let frame = &frames[offset..offset + self.window_size * num_channels];
+            for (channel, windows) in self.channels.iter_mut().enumerate() {
+                windows.push(analyzer.analyze_spectral_window(&self.stft, self.fixed.as_ref(), channel_samples(frame, channel, num_channels)));
+            }
+            self.next_start += self.step;
+        }
+    }
+
+    // First frame a window still to measure reads (none past the duration cap)
+    fn needed_from(&self) -> usize {
+        if self.next_start < self.max_start { self.next_start } else { usize::MAX }
+    }
+}
+
+// Band energies of the onset frames of the mono fold
+#[derive(Default)]
+struct OnsetFrames {
+    mono: Vec<f32>,
+    // Mono frame `mono[0]` is
+    first: usize,
+    full_band: Vec<f32>,
+    high_band: Vec<f32>,
+}
+
+impl OnsetFrames {
+    fn push(&mut self, mono: &[f32]) {
+        self.mono.extend_from_slice(mono);
+        let mut start = self.full_band.len() * ONSET_HOP;
+        while start + ONSET_FRAME_SIZE <= self.first + self.mono.len() {
+            let (full, high) = onset_bands(&self.mono[start - self.first..start - self.first + ONSET_FRAME_SIZE]);
+            self.full_band.push(full);
+            self.high_band.push(high);
+            start += ONSET_HOP;
+        }
+
+        let drop = (start - self.first).min(self.mono.len());
+        self.mono.drain(..drop);
+        self.first += drop;
+    }
+}
+
+// Punchiness windows over the amplitude envelope of the mono fold, the
+// envelope taken a Hilbert block at a time as its context arrives
+struct PunchWindows {
+    fft: Arc<Fft>,
+    scratch: (Vec<f32>, Vec<f32>),
+    window_size: usize,
+    // Mono frames the estimate reads at most
+    max_length: usize,
+    // Mono fold from frame `mono_first`, and the frames seen
+    mono: Vec<f32>,
+    mono_first: usize,
+    length: usize,
+    next_block: usize,
+    // Envelope from frame `envelope_first`
+    envelope: Vec<f32>,
+    envelope_first: usize,
+    next_window: usize,
+    sum: f32,
+}
+
+impl PunchWindows {
+    fn push(&mut self, mono: &[f32]) {
+        let take = self.max_length.saturating_sub(self.length).min(mono.len());
+        self.mono.extend_from_slice(&mono[..take]);
+        self.length += take;
+        self.advance(self.max_length);
+    }
+
+    // Envelope blocks and windows that are complete, given the signal ends at
+    // `limit` at the latest (exactly there once the input has ended)
+    fn advance(&mut self, limit: usize) {
+        while self.next_block < limit && self.length >= (self.next_block + HILBERT_HOP + HILBERT_MARGIN).min(limit) {
+            let start = self.next_block;
+            let first = start.saturating_sub(HILBERT_MARGIN);
+            let last = (start + HILBERT_HOP + HILBERT_MARGIN).min(limit);
+            let end = (start + HILBERT_HOP).min(limit);
+            let mut quadrature = vec![0.0; end - start];
+            let (real, imag) = &mut self.scratch;
+            hilbert_block(&self.fft, (real, imag), &self.mono[first - self.mono_first..last - self.mono_first], start - first, &mut quadrature);
+            let signal = &self.mono[start - self.mono_first..end - self.mono_first];
+            self.envelope.extend(signal.iter().zip(quadrature).map(|(&x, h)| x.hypot(h)));
+            self.next_block += HILBERT_HOP;
+
+            let keep_from = self.next_block.saturating_sub(HILBERT_MARGIN).min(self.length);
+            self.mono.drain(..keep_from - self.mono_first);
+            self.mono_first = keep_from;
+        }
+
+        let measured = self.envelope_first + self.envelope.len();
+        while self.next_window < limit && measured >= (self.next_window + self.window_size).min(limit) {
+            let start = self.next_window - self.envelope_first;
+            let end = (self.next_window + self.window_size).min(limit) - self.envelope_first;
+            self.sum += punch_window(&self.envelope[start..end]);
+            self.next_window += 2 * self.window_size;
+        }
+        let keep_from = self.next_window.min(measured);
+        self.envelope.drain(..keep_from - self.envelope_first);
+        self.envelope_first = keep_from;
+    }
+}
+
+// Streaming technical analysis: running peak, clipping and DC statistics,
+// with true peak blocks, spectral, onset and dynamics windows measured as they
+// complete and integrated loudness for PLR from the same stream. Samples past
+// the memory ceiling are counted but not measured, as in the batch analysis
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct TechnicalStream {
+    analyzer: TechnicalAnalyzer,
+    loudness: LoudnessAnalyzer,
+    blocks: BlockWindow,
+    num_channels: usize,
+    pushed: Pushed,
+    max_samples: usize,
+    // Samples measured so far (those within the memory ceiling)
+    samples: usize,
+    // First samples, for inputs too short for a whole spectral window
+    head: Vec<f32>,
+    peak: f32,
+    silence_threshold: f32,
+    db: DbScale,
+    clipped_samples: u32,
+    sum: f64,
+    silent_samples: usize,
+    silence: SilenceTracker,
+    // Open dynamic range window and the levels of the closed ones
+    dynamics_window: Vec<f32>,
+    rms_levels: Vec<f32>,
+    // Interleaved samples from frame `first_frame` on
+    frames: Vec<f32>,
+    first_frame: usize,
+    true_peak: TruePeakBlocks,
+    spectral: SpectralWindows,
+    onsets: OnsetFrames,
+    punch: PunchWindows,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl TechnicalStream {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        TechnicalStream::from_config(&AnalyzerConfig::new(sample_rate, num_channels))
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        let analyzer = TechnicalAnalyzer::from_config(config);
+        let (num_channels, quality, limits) = (config.num_channels().max(1), config.quality(), config.limits());
+        let oversampler = analyzer.true_peak_oversampler();
+        let window_size = quality.fft_size();
+        let step = window_size / 2 * limits.decimation();
+        let (stft, fixed) = analyzer.spectral_transform(window_size, step);
+        let fft = plan_fft(HILBERT_BLOCK);
+        let scratch = (vec![0.0; fft.size()], vec![0.0; fft.size()]);
+        TechnicalStream {
+            loudness: LoudnessAnalyzer::from_config(config),
+            blocks: BlockWindow::for_rate(config.sample_rate(), num_channels),
+            num_channels,
+            pushed: Pushed::default(),
+            max_samples: limits.max_samples(usize::MAX, BYTES_PER_SAMPLE),
+            samples: 0,
+            head: Vec::new(),
+            peak: 0.0,
+            silence_threshold: config.silence_threshold(),
+            db: config.db_scale(),
+            clipped_samples: 0,
+            sum: 0.0,
+            silent_samples: 0,
+            silence: SilenceTracker::new(config.silence_threshold(), config.sample_rate()),
+            dynamics_window: Vec::new(),
+            rms_levels: Vec::new(),
+            frames: Vec::new(),
+            first_frame: 0,
+            true_peak: TruePeakBlocks {
+                reach: oversampler.taps_per_phase() / 2,
+                oversampler,
+                next_block: 0,
+                channels: vec![(-f32::INFINITY, Vec::new()); num_channels],
+            },
+            spectral: SpectralWindows {
+                stft,
+                fixed,
+                window_size,
+                step,
+                max_start: limits.max_frames(usize::MAX, config.sample_rate()),
+                next_start: 0,
+                channels: vec![Vec::new(); num_channels],
+            },
+            onsets: OnsetFrames::default(),
+            punch: PunchWindows {
+                fft,
+                scratch,
+                window_size: analyzer.punch_window_size(),
+                max_length: analyzer.mastering_frames(),
+                mono: Vec::new(),
+                mono_first: 0,
+                length: 0,
+                next_block: 0,
+                envelope: Vec::new(),
+                envelope_first: 0,
+                next_window: 0,
+                sum: 0.0,
+            },
+            analyzer,
+        }
+    }
+
+    // Whole frames measured so far
+    fn whole_frames(&self) -> usize {
+        self.first_frame + self.frames.len() / self.num_channels
+    }
+
+    // Drop the frames no true peak block or spectral window still reads
+    fn trim_frames(&mut self) {
+        let keep_from = self.true_peak.needed_from().min(self.spectral.needed_from()).min(self.whole_frames());
+        self.frames.drain(..(keep_from - self.first_frame) * self.num_channels);
+        self.first_frame = keep_from;
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = push)]
+    pub fn push_chunk(&mut self, chunk: &Float32Array) {
+        StreamingAnalyzer::push(self, &chunk.to_vec());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = poll)]
+    pub fn poll_results(&self) -> TechnicalSnapshot {
+        StreamingAnalyzer::poll(self)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = get_meter_snapshot)]
+    pub fn meter_snapshot_results(&self) -> MeterSnapshot {
+        StreamingAnalyzer::meter_snapshot(self)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = finalize)]
+    pub fn finalize_results(&mut self) -> Result<TechnicalResult, JsError> {
+        Ok(StreamingAnalyzer::finalize(self)?)
+    }
+}
+
+impl StreamingAnalyzer for TechnicalStream {
+    type Snapshot = TechnicalSnapshot;
+    type Output = TechnicalResult;
+
+    fn push(&mut self, chunk: &[f32]) {
+        self.pushed.record(chunk);
+        self.blocks.push(chunk);
+
+        let take = self.max_samples.saturating_sub(self.samples).min(chunk.len());
+        let chunk = &chunk[..take];
+        if chunk.is_empty() {
+            return;
+        }
+        self.samples += chunk.len();
+        let head_size = self.spectral.window_size * self.num_channels;
+        self.head.extend(chunk.iter().take(head_size.saturating_sub(self.head.len())));
+
+        // Sample statistics
+        self.peak = self.peak.max(max_abs(chunk));
+        self.clipped_samples += self.analyzer.count_clipped(chunk.iter().copied());
+        for &sample in chunk {
+            self.sum += sample as f64;
+        }
+        self.silent_samples += silent_count(chunk, self.silence_threshold);
+        self.silence.push(chunk);
+
+        // Dynamic range windows run over the interleaved samples
+        let window_size = self.analyzer.dynamics_window_size();
+        self.dynamics_window.extend_from_slice(chunk);
+        let closed = self.dynamics_window.len() / window_size * window_size;
+        for window in self.dynamics_window[..closed].chunks_exact(window_size) {
+            self.rms_levels.extend(self.analyzer.dynamics_level(window));
+        }
+        self.dynamics_window.drain(..closed);
+
+        // Frame-based measures, over the frames this chunk completes
+        let folded = self.whole_frames();
+        self.frames.extend_from_slice(chunk);
+        let whole = self.whole_frames();
+        let new_frames = &self.frames[(folded - self.first_frame) * self.num_channels..(whole - self.first_frame) * self.num_channels];
+        let mono = mix_to_mono(new_frames, self.num_channels);
+        self.onsets.push(&mono);
+        self.punch.push(&mono);
+        self.true_peak.advance(&self.frames, self.first_frame, self.num_channels, whole, false);
+        self.spectral.advance(&self.analyzer, &self.frames, self.first_frame, self.num_channels, whole);
+        self.trim_frames();
+    }
+
+    fn poll(&self) -> TechnicalSnapshot {
+        let dc_offset = if self.samples == 0 { 0.0 } else { self.sum / self.samples as f64 };
+
+        TechnicalSnapshot {
+            samples: self.pushed.samples,
+            peak: self.db.to_db(self.peak),
+            clipped_samples: self.clipped_samples,
+            dc_offset: dc_offset as f32,
+        }
+    }
+
+    // Loudness blocks and the true peak held since the start; the blocks
+    // still waiting for the oversampler's reach are not in it yet
+    fn meter_snapshot(&self) -> MeterSnapshot {
+        let true_peak = self.true_peak.channels.iter().fold(0.0f32, |peak, channel| peak.max(channel.0));
+        MeterSnapshot { true_peak: self.db.to_db(true_peak), ..self.blocks.meter_snapshot() }
+    }
+
+    fn finalize(&mut self) -> Result<TechnicalResult, AnalysisError> {
+        self.pushed.validate(1, 1)?;
+        if self.samples == 0 {
+            return Err(AnalysisError::EmptyInput);
+        }
+        self.blocks.finish();
+        let integrated = self.blocks.result(&self.loudness, Vec::new()).integrated;
+
+        // Windows and blocks cut short by the end of the input
+        let whole = self.whole_frames();
+        self.true_peak.advance(&self.frames, self.first_frame, self.num_channels, whole, true);
+        self.punch.advance(self.punch.length);
+        if !self.dynamics_window.is_empty() {
+            self.rms_levels.extend(self.analyzer.dynamics_level(&self.dynamics_window));
+            self.dynamics_window.clear();
+        }
+
+        // Short inputs read a single window as long as the input, as in the
+        // batch pass; otherwise every window or every other one, by the hop
+        // the batch pass would take
+        let spectral = if whole < self.spectral.window_size {
+            self.analyzer.calculate_spectral_metrics(&self.head)
+        } else {
+            let analysis_length = whole.min(self.spectral.max_start);
+            let every = self.analyzer.spectral_hop(self.spectral.window_size, analysis_length) / self.spectral.step;
+            spectral_summary(self.spectral.channels.iter().flat_map(|windows| windows.iter().step_by(every.max(1))).flatten().copied())
+        };
+
+        let measures = TechnicalMeasures {
+            samples: self.samples,
+            true_peaks: self.true_peak.channels.clone(),
+            sample_peak: self.peak,
+            clipped_samples: self.clipped_samples,
+            dc_offset: (self.sum / self.samples as f64) as f32,
+            spectral,
+            silence: self.silence.finish(),
+            silent_share: self.silent_samples as f32 / self.samples as f32,
+            onset_count: pick_onsets(&envelope_from_bands(&self.onsets.full_band, &self.onsets.high_band)).len(),
+            rms_levels: self.rms_levels.clone(),
+            punch_sum: self.punch.sum,
+            punch_length: self.punch.length,
+        };
+        Ok(self.analyzer.technical_result(measures, self.pushed.samples, integrated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::AnalysisLimits;
+
+    // Stereo noise bursts over a decaying chord, with some silence and a clipped stretch
+    fn programme(seconds: f32, sample_rate: f32) -> Vec<f32> {
+        let mut seed = 7u32;
+        (0..2 * (seconds * sample_rate) as usize)
+            .map(|i| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let t = (i / 2) as f32 / sample_rate;
+                let beat = t % 0.5;
+                let chord = 0.3 * (2.0 * std::f32::consts::PI * 220.0 * t + (i % 2) as f32).sin() + 0.1 * (2.0 * std::f32::consts::PI * 3300.0 * t).sin();
+                let burst = if beat < 0.05 { (seed as f32 / u32::MAX as f32 - 0.5) * (1.0 - beat * 20.0) } else { 0.0 };
+                let level = if (2.0..2.5).contains(&t) { 0.0 } else { 1.0 };
+                ((chord + burst) * level * 1.5).clamp(-1.0, 1.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_the_batch_analysis_while_keeping_only_open_windows() {
+        let mut limited = AnalyzerConfig::new(22050.0, 2);
+        let mut limits = AnalysisLimits::new();
+        limits.set_max_duration(4.0);
+        limited.set_limits(&limits);
+        // Long enough for windows without overlap, short enough for
+        // overlapping ones, and a duration cap on the spectral pass
+        for (seconds, config) in [(12.0, AnalyzerConfig::new(22050.0, 2)), (3.0, AnalyzerConfig::new(22050.0, 2)), (6.0, limited)] {
+            let pcm = programme(seconds, 22050.0);
+            let integrated = LoudnessAnalyzer::from_config(&config).analyze(&pcm, None).unwrap().integrated;
+            let batch = TechnicalAnalyzer::from_config(&config).analyze_technical(&pcm, integrated, None).unwrap();
+
+            let mut stream = TechnicalStream::from_config(&config);
+            for chunk in pcm.chunks(3001) {
+                StreamingAnalyzer::push(&mut stream, chunk);
+                assert!(stream.frames.len() <= 2 * (TRUE_PEAK_BLOCK_FRAMES + stream.true_peak.reach) + chunk.len());
+                assert!(stream.punch.mono.len() <= HILBERT_BLOCK + chunk.len());
+            }
+            let streamed = StreamingAnalyzer::finalize(&mut stream).unwrap();
+
+            assert_eq!(streamed.true_peak.level, batch.true_peak.level);
+            assert_eq!(streamed.true_peak.locations, batch.true_peak.locations);
+            assert!(streamed.quality.clipped_samples > 0);
+            assert_eq!(streamed.quality.clipped_samples, batch.quality.clipped_samples);
+            assert_eq!(streamed.quality.dc_offset, batch.quality.dc_offset);
+            assert_eq!(streamed.spectral.centroid, batch.spectral.centroid);
+            assert_eq!(streamed.spectral.flatness, batch.spectral.flatness);
+            assert_eq!(streamed.spectral.frequency_balance.bass, batch.spectral.frequency_balance.bass);
+            assert_eq!(streamed.silence.gap_count, batch.silence.gap_count);
+            assert_eq!(streamed.silence.trailing_silence, batch.silence.trailing_silence);
+            assert_eq!(streamed.mastering.plr, batch.mastering.plr);
+            assert_eq!(streamed.mastering.dynamic_range, batch.mastering.dynamic_range);
+            assert_eq!(streamed.mastering.punchiness, batch.mastering.punchiness);
+            assert_eq!(streamed.mastering.onset_count, batch.mastering.onset_count);
+            assert_eq!(streamed.status.spectral, batch.status.spectral);
+            assert_eq!(streamed.limits.truncated, batch.limits.truncated);
+        }
+    }
+
+    #[test]
+    fn meter_snapshot_reads_like_the_live_meter() {
+        // 4 s of a 997 Hz sine in both channels (a whole short-term block), pushed
+        // in audio-worklet sized chunks
+        let pcm: Vec<f32> = (0..2 * 4 * 44100)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 997.0 * (i / 2) as f32 / 44100.0).sin())
+            .collect();
+        let mut stream = TechnicalStream::new(44100.0, 2);
+        let mut live = crate::live::LiveMeter::new(44100.0, 2);
+        assert_eq!(StreamingAnalyzer::meter_snapshot(&stream).momentary, f32::NEG_INFINITY);
+        for chunk in pcm.chunks(256) {
+            StreamingAnalyzer::push(&mut stream, chunk);
+            live.process(chunk);
+        }
+
+        let snapshot = StreamingAnalyzer::meter_snapshot(&stream);
+        let reference = live.meter_snapshot();
+        assert!((snapshot.momentary - reference.momentary).abs() < 0.05, "{} vs {}", snapshot.momentary, reference.momentary);
+        assert!((snapshot.short_term - reference.short_term).abs() < 0.05, "{} vs {}", snapshot.short_term, reference.short_term);
+        assert!((snapshot.true_peak - reference.true_peak).abs() < 0.1, "{} vs {}", snapshot.true_peak, reference.true_peak);
+        assert_eq!((snapshot.correlation, snapshot.width), (0.0, 0.0));
+    }
+}
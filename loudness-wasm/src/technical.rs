@@ -1,309 +1,644 @@
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
-use js_sys::Float32Array;
-use std::f32::consts::PI;
-use crate::utils::{amplitude_to_db, calculate_rms};
+#[cfg(target_arch = "wasm32")]
+use js_sys::{Float32Array, Function};
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::AnalyzerConfig;
+use crate::constants::{FREQUENCY_BANDS, SHORT_TERM_BLOCK_SIZE};
+use crate::limits::{AnalysisLimits, LimitsReport, MetricStatus, Quality, BYTES_PER_SAMPLE};
+use crate::onset::{pick_onsets, MonoFold};
+use crate::parallel::map_range;
+use crate::error::{validate_pcm, AnalysisError};
+#[cfg(target_arch = "wasm32")]
+use crate::progress::js_progress;
+use crate::progress::{CancellationToken, Progress};
+#[cfg(target_arch = "wasm32")]
+use crate::buffer::PcmBuffer;
+use crate::stft::Stft;
+use crate::utils::{analytic_envelope, calculate_rms, db_to_amplitude, silent_share, trimmed_mean, DbScale, FixedFft, Polyphase, ScratchPool};
+use crate::window::Window;
+use crate::series::TimeSeries;
+use crate::simd::{max_abs, sum};
 
-#[wasm_bindgen]
+// Shortest input (seconds) whose 100ms-window dynamic range is meaningful
+const MIN_DYNAMICS_DURATION: f32 = 1.0;
+// Dynamic range runs between the loudest and quietest fifths of the windows,
+// each averaged with a tenth trimmed from either end
+const DYNAMICS_TAIL_SHARE: f32 = 0.2;
+const DYNAMICS_TRIM: f32 = 0.1;
+// Input frames oversampled at a time for true peak
+pub(crate) const TRUE_PEAK_BLOCK_FRAMES: usize = 8192;
+// Silence gaps shorter than this (seconds) aren't counted
+const MIN_SILENCE_GAP: f32 = 0.1;
+
+/// Sample and true peak levels (dBTP) with delivery compliance
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct TruePeakResult {
+    #[serde(serialize_with = "crate::utils::serialize_level")]
+    pub level: f32,
+    #[serde(serialize_with = "crate::typed_array::serialize")]
+    #[cfg_attr(target_arch = "wasm32", tsify(type = "Float32Array"))]
+    pub locations: Vec<f32>,
+    pub broadcast_compliant: bool,
+    pub spotify_compliant: bool,
+    pub youtube_compliant: bool,
+}
+
+/// Clipping and DC offset checks
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct QualityResult {
+    pub has_clipping: bool,
+    pub clipped_samples: u32,
+    pub clipping_percentage: f32,
+    pub dc_offset: f32,
+}
+
+/// Share of spectral energy per band, in percent
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct FrequencyBalance {
+    pub sub_bass: f32,
+    pub bass: f32,
+    pub low_mids: f32,
+    pub mids: f32,
+    pub upper_mids: f32,
+    pub presence: f32,
+    pub brilliance: f32,
+}
+
+/// Spectral shape (centroid and rolloff in Hz)
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct SpectralResult {
+    pub centroid: f32,
+    pub rolloff: f32,
+    pub flatness: f32,
+    pub frequency_balance: FrequencyBalance,
+}
+
+/// Leading/trailing silence in seconds
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct SilenceResult {
+    pub leading_silence: f32,
+    pub trailing_silence: f32,
+    pub gap_count: usize,
+}
+
+/// Dynamics and mastering quality scores
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct MasteringResult {
+    pub plr: f32,
+    pub dynamic_range: f32,
+    pub punchiness: f32,
+    pub warmth: f32,
+    pub clarity: f32,
+    pub spaciousness: f32,
+    pub quality_score: f32,
+    pub onset_count: usize,
+    pub transient_density: f32,
+}
+
+/// Technical analysis result
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi, missing_as_null))]
+pub struct TechnicalResult {
+    pub true_peak: TruePeakResult,
+    pub quality: QualityResult,
+    pub spectral: SpectralResult,
+    pub silence: SilenceResult,
+    pub mastering: MasteringResult,
+    pub limits: LimitsReport,
+    pub status: TechnicalStatus,
+}
+
+/// Trust flags of the technical measurements, one per result section (PLR and
+/// dynamic range separately from the other mastering scores)
+#[derive(Clone, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct TechnicalStatus {
+    pub true_peak: MetricStatus,
+    pub quality: MetricStatus,
+    pub spectral: MetricStatus,
+    pub silence: MetricStatus,
+    pub dynamic_range: MetricStatus,
+    pub plr: MetricStatus,
+    pub mastering: MetricStatus,
+}
+
+// Spectral metrics of one window: (centroid, rolloff, flatness, band energies)
+pub(crate) type SpectralWindow = (f32, f32, f32, [f32; 7]);
+
+// Samples of one channel of interleaved PCM
+pub(crate) fn channel_samples(pcm: &[f32], channel: usize, num_channels: usize) -> impl Iterator<Item = f32> + '_ {
+    pcm.iter().skip(channel).step_by(num_channels).copied()
+}
+
+// Fold the oversampled frames `block..end` of one channel into its running
+// peak and peak locations; `input` holds the channel from frame `start` to
+// the kernel's reach past `end` (or the end of the signal)
+pub(crate) fn true_peak_block(oversampler: &Polyphase, input: &[f32], start: usize, block: usize, end: usize, (max_true_peak, peak_locations): &mut (f32, Vec<f32>)) {
+    let factor = oversampler.up();
+    let outputs = oversampler.process(input).into_iter().enumerate().skip((block - start) * factor).take((end - block) * factor);
+    for (local, interpolated) in outputs {
+        let mut abs_value = interpolated.abs();
+        if local.is_multiple_of(factor) {
+            abs_value = abs_value.max(input[local / factor].abs());
+        }
+        if abs_value > *max_true_peak {
+            *max_true_peak = abs_value;
+            peak_locations.push((start * factor + local) as f32 / factor as f32);
+        }
+    }
+}
+
+// Averages of the spectral windows' metrics: (centroid, rolloff, flatness,
+// frequency balance in percent)
+pub(crate) fn spectral_summary(windows: impl Iterator<Item = SpectralWindow>) -> (f32, f32, f32, Vec<f32>) {
+    let mut spectral_centroid = 0.0;
+    let mut spectral_rolloff = 0.0;
+    let mut spectral_flatness = 0.0;
+    let mut frequency_balance = vec![0.0; 7]; // 7 frequency bands
+    let mut window_count = 0;
+
+    for (centroid, rolloff, flatness, band_energies) in windows {
+        spectral_centroid += centroid;
+        spectral_rolloff += rolloff;
+        spectral_flatness += flatness;
+        for (balance, energy) in frequency_balance.iter_mut().zip(band_energies) {
+            *balance += energy;
+        }
+        window_count += 1;
+    }
+
+    // Average results and normalize frequency balance
+    if window_count > 0 {
+        spectral_centroid /= window_count as f32;
+        spectral_rolloff /= window_count as f32;
+        spectral_flatness /= window_count as f32;
+
+        // Average and normalize frequency balance to percentages
+        let total_energy: f32 = frequency_balance.iter().sum::<f32>() / window_count as f32;
+        if total_energy > 0.0 {
+            for balance in &mut frequency_balance {
+                *balance = (*balance / window_count as f32) / total_energy * 100.0; // Convert to percentages
+            }
+        } else {
+            // Fallback to equal distribution
+            let num_bands = frequency_balance.len() as f32;
+            for balance in &mut frequency_balance {
+                *balance = 100.0 / num_bands; // ~14.3% each
+            }
+        }
+    }
+
+    (spectral_centroid, spectral_rolloff, spectral_flatness, frequency_balance)
+}
+
+// Peak-to-mean of one punchiness window of the amplitude envelope (0 when silent)
+pub(crate) fn punch_window(levels: &[f32]) -> f32 {
+    let mut max_val: f32 = 0.0;
+    let mut avg_val = 0.0;
+
+    for &level in levels {
+        max_val = max_val.max(level);
+        avg_val += level;
+    }
+
+    avg_val /= levels.len() as f32;
+    if avg_val > 1e-10 { max_val / avg_val } else { 0.0 }
+}
+
+// Leading/trailing silence and silence gaps of samples seen in order
+pub(crate) struct SilenceTracker {
+    threshold: f32,
+    sample_rate: f32,
+    samples: usize,
+    first_loud: Option<usize>,
+    last_loud: Option<usize>,
+    silence_start: Option<f32>,
+    gaps: Vec<(f32, f32)>,
+}
+
+impl SilenceTracker {
+    pub(crate) fn new(threshold_db: f32, sample_rate: f32) -> Self {
+        SilenceTracker {
+            threshold: db_to_amplitude(threshold_db),
+            sample_rate,
+            samples: 0,
+            first_loud: None,
+            last_loud: None,
+            silence_start: None,
+            gaps: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, samples: &[f32]) {
+        for (i, &sample) in (self.samples..).zip(samples) {
+            let current_time = i as f32 / self.sample_rate;
+            if sample.abs() <= self.threshold {
+                self.silence_start.get_or_insert(current_time);
+                continue;
+            }
+
+            self.first_loud.get_or_insert(i);
+            self.last_loud = Some(i);
+            if let Some(silence_start) = self.silence_start.take() {
+                if current_time - silence_start > MIN_SILENCE_GAP {
+                    self.gaps.push((silence_start, current_time));
+                }
+            }
+        }
+        self.samples += samples.len();
+    }
+
+    // Leading and trailing silence (seconds) and the gaps between
+    pub(crate) fn finish(&self) -> (f32, f32, Vec<(f32, f32)>) {
+        let leading_silence = self.first_loud.map_or(0.0, |i| i as f32 / self.sample_rate);
+        let trailing_silence = self.last_loud.map_or(0.0, |i| (self.samples - 1 - i) as f32 / self.sample_rate);
+        (leading_silence, trailing_silence, self.gaps.clone())
+    }
+}
+
+// Measurements a technical result is built from, by the batch analysis or a
+// stream; `samples` is how many (interleaved) input samples they cover
+pub(crate) struct TechnicalMeasures {
+    pub(crate) samples: usize,
+    // Linear true peak and peak locations of each channel
+    pub(crate) true_peaks: Vec<(f32, Vec<f32>)>,
+    pub(crate) sample_peak: f32,
+    pub(crate) clipped_samples: u32,
+    pub(crate) dc_offset: f32,
+    pub(crate) spectral: (f32, f32, f32, Vec<f32>),
+    pub(crate) silence: (f32, f32, Vec<(f32, f32)>),
+    pub(crate) silent_share: f32,
+    pub(crate) onset_count: usize,
+    // Levels (dB) of the non-silent dynamic range windows
+    pub(crate) rms_levels: Vec<f32>,
+    // Sum of the punchiness windows' peak-to-mean ratios over `punch_length` mono frames
+    pub(crate) punch_sum: f32,
+    pub(crate) punch_length: usize,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 pub struct TechnicalAnalyzer {
     sample_rate: f32,
+    num_channels: usize,
+    cancel: Option<CancellationToken>,
+    limits: AnalysisLimits,
+    quality: Quality,
+    target_loudness: f32,
+    clip_threshold: f32,
+    silence_threshold: f32,
+    true_peak_ceiling: f32,
+    window: Window,
+    db: DbScale,
+    reduced_precision: bool,
+    scratch: ScratchPool,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
 impl TechnicalAnalyzer {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
     pub fn new(sample_rate: f32) -> Self {
-        TechnicalAnalyzer { sample_rate }
+        TechnicalAnalyzer::from_config(&AnalyzerConfig::new(sample_rate, 1))
     }
 
-    // True Peak Detection (ITU-R BS.1770-4 compliant)
-    fn calculate_true_peak(&self, pcm: &Float32Array) -> (f32, Vec<f32>, bool) {
-        let mut max_true_peak = -f32::INFINITY;
-        let mut peak_locations = Vec::new();
-        
-        // 4x oversampling for true peak detection
-        let oversample_factor = 4;
-        let _upsampled_length = pcm.length() as usize * oversample_factor;
-        
-        // Simple linear interpolation upsampling for true peak
-        for i in 0..(pcm.length() as usize - 1) {
-            let current = pcm.get_index(i as u32);
-            let next = pcm.get_index((i + 1) as u32);
-            
-            for j in 0..oversample_factor {
-                let t = j as f32 / oversample_factor as f32;
-                let interpolated = current + t * (next - current);
-                let abs_value = interpolated.abs();
-                
-                if abs_value > max_true_peak {
-                    max_true_peak = abs_value;
-                    peak_locations.push(i as f32 + t);
-                }
-            }
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn from_config(config: &AnalyzerConfig) -> Self {
+        TechnicalAnalyzer {
+            sample_rate: config.sample_rate(),
+            num_channels: config.num_channels().max(1),
+            cancel: None,
+            limits: config.limits(),
+            quality: config.quality(),
+            target_loudness: config.target_loudness(),
+            clip_threshold: config.clip_threshold(),
+            silence_threshold: config.silence_threshold(),
+            true_peak_ceiling: config.true_peak_ceiling(),
+            window: config.window(),
+            db: config.db_scale(),
+            reduced_precision: false,
+            scratch: ScratchPool::default(),
         }
+    }
+
+    // Interleaved channel count; peak, clipping and spectral metrics are
+    // measured per channel (in parallel on threads builds)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_num_channels(&mut self, num_channels: usize) {
+        self.num_channels = num_channels.max(1);
+    }
+
+    // Apply a speed/precision preset; also replaces the limits with the preset's
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_quality(&mut self, quality: Quality) {
+        self.quality = quality;
+        self.limits = AnalysisLimits::for_quality(quality);
+    }
+
+    // Replace the default duration / decimation / memory limits
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_limits(&mut self, limits: &AnalysisLimits) {
+        self.limits = *limits;
+    }
+
+    // Take the spectral windows (spectrogram, centroid, rolloff, flatness,
+    // balance) through a Q15 fixed-point FFT, for low-end devices. Strong
+    // bands agree with the float transform to a tenth of a dB; bands 20dB or
+    // more under the loudest sit on the rounding floor, and flatness reads
+    // higher for clean tones
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_reduced_precision(&mut self, enabled: bool) {
+        self.reduced_precision = enabled;
+    }
+
+    // Attach a token that aborts analyses at the next checkpoint when tripped
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_cancellation_token(&mut self, token: &CancellationToken) {
+        self.cancel = Some(token.clone());
+    }
+
+    // True Peak Detection (ITU-R BS.1770-4 compliant); the loudest channel's
+    // peak and its locations (in frames)
+    pub(crate) fn calculate_true_peak(&self, pcm: &[f32]) -> (f32, Vec<f32>, bool) {
+        let oversampler = self.true_peak_oversampler();
+        self.loudest_true_peak(map_range(0..self.num_channels, |channel| self.channel_true_peak(pcm, channel, &oversampler)))
+    }
+
+    // Band-limited oversampler of the true peak measure (4x at balanced quality)
+    pub(crate) fn true_peak_oversampler(&self) -> Polyphase {
+        Polyphase::oversampler(self.quality.true_peak_oversampling(), self.quality.resampling())
+    }
+
+    // Level (dBTP), locations and compliance of the loudest channel's true peak
+    fn loudest_true_peak(&self, channels: Vec<(f32, Vec<f32>)>) -> (f32, Vec<f32>, bool) {
+        let (max_true_peak, peak_locations) = channels.into_iter()
+            .fold((-f32::INFINITY, Vec::new()), |loudest, channel| if channel.0 > loudest.0 { channel } else { loudest });
         
         // Convert to dBTP (decibels True Peak)
-        let true_peak_db = amplitude_to_db(max_true_peak);
+        let true_peak_db = self.db.to_db(max_true_peak);
         
-        // Check broadcast compliance (-1.0 dBTP threshold)
-        let is_compliant = true_peak_db <= -1.0;
+        // Check broadcast compliance (-1.0 dBTP ceiling by default)
+        let is_compliant = true_peak_db <= self.true_peak_ceiling;
         
         (true_peak_db, peak_locations, is_compliant)
     }
 
-    // Digital Clipping Detection
-    fn detect_clipping(&self, pcm: &Float32Array) -> (bool, u32, f32) {
-        let mut clipped_samples = 0;
-        let threshold = 0.99; // Digital clipping threshold
-        
-        for i in 0..pcm.length() {
-            let sample = pcm.get_index(i).abs();
-            if sample >= threshold {
-                clipped_samples += 1;
-            }
+    // Linear peak and running peak locations of one channel. Oversampled in
+    // blocks, each read with the kernel's reach of input either side, so only
+    // a block's worth of oversampled signal exists at once
+    fn channel_true_peak(&self, pcm: &[f32], channel: usize, oversampler: &Polyphase) -> (f32, Vec<f32>) {
+        let mut peak = (-f32::INFINITY, Vec::new());
+
+        // The samples themselves bound the true peak from below, so they count too
+        let frames = pcm.len() / self.num_channels;
+        let reach = oversampler.taps_per_phase() / 2;
+        for block in (0..frames).step_by(TRUE_PEAK_BLOCK_FRAMES) {
+            let end = (block + TRUE_PEAK_BLOCK_FRAMES).min(frames);
+            let start = block.saturating_sub(reach);
+            let input: Vec<f32> = (start..(end + reach).min(frames)).map(|frame| pcm[frame * self.num_channels + channel]).collect();
+            true_peak_block(oversampler, &input, start, block, end, &mut peak);
         }
+
+        peak
+    }
+
+    // Digital Clipping Detection
+    pub(crate) fn detect_clipping(&self, pcm: &[f32]) -> (bool, u32, f32) {
+        let per_channel = map_range(0..self.num_channels, |channel| self.count_clipped(channel_samples(pcm, channel, self.num_channels)));
+        let clipped_samples: u32 = per_channel.into_iter().sum();
         
-        let clipping_percentage = (clipped_samples as f32 / pcm.length() as f32) * 100.0;
+        let clipping_percentage = (clipped_samples as f32 / pcm.len() as f32) * 100.0;
         let has_clipping = clipped_samples > 0;
         
         (has_clipping, clipped_samples, clipping_percentage)
     }
 
+    // Samples at or over the digital clipping threshold
+    pub(crate) fn count_clipped(&self, samples: impl Iterator<Item = f32>) -> u32 {
+        samples.filter(|sample| sample.abs() >= self.clip_threshold).count() as u32
+    }
+
     // DC Offset Detection
-    fn calculate_dc_offset(&self, pcm: &Float32Array) -> f32 {
-        let mut sum = 0.0;
-        for i in 0..pcm.length() {
-            sum += pcm.get_index(i);
-        }
-        sum / pcm.length() as f32
+    fn calculate_dc_offset(&self, pcm: &[f32]) -> f32 {
+        let sum: f64 = pcm.iter().map(|&sample| sample as f64).sum();
+        (sum / pcm.len() as f64) as f32
     }
 
-    // Spectral Analysis - Optimized for performance
-    fn calculate_spectral_metrics(&self, pcm: &Float32Array) -> (f32, f32, f32, Vec<f32>) {
-        let window_size = 2048.min(pcm.length() as usize); // Smaller window for speed
-        let mut spectral_centroid = 0.0;
-        let mut spectral_rolloff = 0.0;
-        let mut spectral_flatness = 0.0;
-        let mut frequency_balance = vec![0.0; 7]; // 7 frequency bands
-        let mut window_count = 0;
-
-        // Limit analysis to first 30 seconds for very long files to improve performance
-        let max_samples = (self.sample_rate * 30.0) as usize;
-        let analysis_length = (pcm.length() as usize).min(max_samples);
-
-        // Process overlapping windows with larger steps for speed
-        let step_size = if analysis_length > 44100 * 10 { window_size } else { window_size / 2 }; // Larger steps for long files
-        for start in (0..analysis_length).step_by(step_size) {
-            if start + window_size > pcm.length() as usize { break; }
-            
-            // Apply Hann window and compute spectrum
-            let mut windowed = vec![0.0; window_size];
-            let mut total_energy = 0.0;
-            
-            for i in 0..window_size {
-                let window_val = 0.5 * (1.0 - (2.0 * PI * i as f32 / (window_size - 1) as f32).cos());
-                let sample = pcm.get_index((start + i) as u32);
-                windowed[i] = sample * window_val;
-                total_energy += windowed[i] * windowed[i];
-            }
-            
-            if total_energy < 1e-10 { continue; }
-            
-            // Simple DFT for spectrum analysis
-            let mut spectrum = vec![0.0; window_size / 2];
-            for k in 1..window_size / 2 {
-                let mut real = 0.0;
-                let mut imag = 0.0;
-                let freq_scale = 2.0 * PI * k as f32 / window_size as f32;
-                
-                for i in 0..window_size {
-                    let angle = freq_scale * i as f32;
-                    real += windowed[i] * angle.cos();
-                    imag += windowed[i] * angle.sin();
-                }
-                
-                spectrum[k] = (real * real + imag * imag).sqrt();
-            }
-            
-            // Calculate spectral centroid
-            let mut weighted_freq_sum = 0.0;
-            let mut magnitude_sum = 0.0;
-            
-            for (k, &magnitude) in spectrum.iter().enumerate() {
-                let freq = k as f32 * self.sample_rate / window_size as f32;
-                weighted_freq_sum += freq * magnitude;
-                magnitude_sum += magnitude;
-            }
-            
-            if magnitude_sum > 0.0 {
-                spectral_centroid += weighted_freq_sum / magnitude_sum;
-                
-                // Calculate spectral rolloff (85% energy point)
-                let energy_threshold = magnitude_sum * 0.85;
-                let mut cumulative_energy = 0.0;
-                for (k, &magnitude) in spectrum.iter().enumerate() {
-                    cumulative_energy += magnitude;
-                    if cumulative_energy >= energy_threshold {
-                        spectral_rolloff += k as f32 * self.sample_rate / window_size as f32;
-                        break;
-                    }
-                }
-                
-                // Calculate spectral flatness (geometric mean / arithmetic mean)
-                let mut geometric_mean = 1.0;
-                let mut arithmetic_mean = 0.0;
-                let valid_bins = spectrum.len() - 1;
-                
-                for &magnitude in &spectrum[1..] {
-                    if magnitude > 1e-10 {
-                        geometric_mean *= magnitude.powf(1.0 / valid_bins as f32);
-                        arithmetic_mean += magnitude;
-                    }
-                }
-                arithmetic_mean /= valid_bins as f32;
-                
-                if arithmetic_mean > 0.0 {
-                    spectral_flatness += geometric_mean / arithmetic_mean;
-                }
-                
-                // Frequency balance analysis
-                let bands = [
-                    (20.0, 60.0),     // Sub-bass
-                    (60.0, 250.0),    // Bass
-                    (250.0, 500.0),   // Low-mids
-                    (500.0, 2000.0),  // Mids
-                    (2000.0, 5000.0), // Upper-mids
-                    (5000.0, 8000.0), // Presence
-                    (8000.0, 20000.0) // Brilliance
-                ];
-                
-                for (band_idx, &(low_freq, high_freq)) in bands.iter().enumerate() {
-                    let low_bin = (low_freq * window_size as f32 / self.sample_rate) as usize;
-                    let high_bin = (high_freq * window_size as f32 / self.sample_rate) as usize;
-                    
-                    let mut band_energy = 0.0;
-                    for k in low_bin..high_bin.min(spectrum.len()) {
-                        band_energy += spectrum[k];
-                    }
-                    frequency_balance[band_idx] += band_energy;
-                }
-                
-                window_count += 1;
-            }
+    // Spectral metrics for one window: (centroid, rolloff, flatness, band energies),
+    // or None for silent windows
+    pub(crate) fn analyze_spectral_window(&self, stft: &Stft, fixed: Option<&FixedFft>, frame: impl Iterator<Item = f32>) -> Option<SpectralWindow> {
+        let fft_size = stft.fft().size();
+
+        // Apply the analysis window into pooled FFT buffers and compute spectrum in place
+        let mut real = self.scratch.take(fft_size);
+        let mut imag = self.scratch.take(fft_size);
+        let total_energy = stft.load(frame, &mut real);
+        
+        if total_energy < 1e-10 { return None; }
+        
+        match fixed {
+            Some(fixed) => fixed.magnitudes_in_place(&mut real),
+            None => stft.fft().magnitudes_in_place(&mut real, &mut imag),
         }
+        let spectrum = &mut real[..fft_size / 2];
+        spectrum[0] = 0.0;
         
-        // Average results and normalize frequency balance
-        if window_count > 0 {
-            spectral_centroid /= window_count as f32;
-            spectral_rolloff /= window_count as f32;
-            spectral_flatness /= window_count as f32;
-            
-            // Average and normalize frequency balance to percentages
-            let total_energy: f32 = frequency_balance.iter().sum::<f32>() / window_count as f32;
-            if total_energy > 0.0 {
-                for balance in &mut frequency_balance {
-                    *balance = (*balance / window_count as f32) / total_energy * 100.0; // Convert to percentages
-                }
-            } else {
-                // Fallback to equal distribution
-                let num_bands = frequency_balance.len() as f32;
-                for balance in &mut frequency_balance {
-                    *balance = 100.0 / num_bands; // ~14.3% each
-                }
-            }
+        // Calculate spectral centroid
+        let mut weighted_freq_sum = 0.0;
+        let mut magnitude_sum = 0.0;
+        
+        for (k, &magnitude) in spectrum.iter().enumerate() {
+            let freq = k as f32 * self.sample_rate / fft_size as f32;
+            weighted_freq_sum += freq * magnitude;
+            magnitude_sum += magnitude;
         }
         
-        (spectral_centroid, spectral_rolloff, spectral_flatness, frequency_balance)
-    }
+        if magnitude_sum <= 0.0 { return None; }
 
-    // Silence Detection
-    fn detect_silence(&self, pcm: &Float32Array, threshold_db: f32) -> (f32, f32, Vec<(f32, f32)>) {
-        let threshold_linear = 10.0_f32.powf(threshold_db / 20.0);
-        let sample_rate = self.sample_rate;
-        let mut silence_gaps = Vec::new();
-        let mut in_silence = false;
-        let mut silence_start = 0.0;
+        let centroid = weighted_freq_sum / magnitude_sum;
         
-        // Leading silence
-        let mut leading_silence = 0.0;
-        for i in 0..pcm.length() {
-            if pcm.get_index(i).abs() > threshold_linear {
-                leading_silence = i as f32 / sample_rate;
+        // Calculate spectral rolloff (85% energy point)
+        let mut rolloff = 0.0;
+        let energy_threshold = magnitude_sum * 0.85;
+        let mut cumulative_energy = 0.0;
+        for (k, &magnitude) in spectrum.iter().enumerate() {
+            cumulative_energy += magnitude;
+            if cumulative_energy >= energy_threshold {
+                rolloff = k as f32 * self.sample_rate / fft_size as f32;
                 break;
             }
         }
         
-        // Trailing silence
-        let mut trailing_silence = 0.0;
-        for i in (0..pcm.length()).rev() {
-            if pcm.get_index(i).abs() > threshold_linear {
-                trailing_silence = (pcm.length() - 1 - i) as f32 / sample_rate;
-                break;
+        // Calculate spectral flatness (geometric mean / arithmetic mean)
+        let mut geometric_mean = 1.0;
+        let mut arithmetic_mean = 0.0;
+        let valid_bins = spectrum.len() - 1;
+        
+        for &magnitude in &spectrum[1..] {
+            if magnitude > 1e-10 {
+                geometric_mean *= magnitude.powf(1.0 / valid_bins as f32);
+                arithmetic_mean += magnitude;
             }
         }
+        arithmetic_mean /= valid_bins as f32;
+        
+        let flatness = if arithmetic_mean > 0.0 { geometric_mean / arithmetic_mean } else { 0.0 };
         
-        // Find silence gaps
-        for i in 0..pcm.length() {
-            let current_time = i as f32 / sample_rate;
-            let is_silent = pcm.get_index(i).abs() <= threshold_linear;
+        // Frequency balance analysis
+        let mut band_energies = [0.0; 7];
+        for (band_idx, &(low_freq, high_freq)) in FREQUENCY_BANDS.iter().enumerate() {
+            let low_bin = (low_freq * fft_size as f32 / self.sample_rate) as usize;
+            let high_bin = (high_freq * fft_size as f32 / self.sample_rate) as usize;
             
-            if is_silent && !in_silence {
-                in_silence = true;
-                silence_start = current_time;
-            } else if !is_silent && in_silence {
-                in_silence = false;
-                let gap_duration = current_time - silence_start;
-                if gap_duration > 0.1 { // Only gaps longer than 100ms
-                    silence_gaps.push((silence_start, current_time));
+            let high_bin = high_bin.min(spectrum.len());
+            band_energies[band_idx] = if low_bin < high_bin { sum(&spectrum[low_bin..high_bin]) } else { 0.0 };
+        }
+
+        Some((centroid, rolloff, flatness, band_energies))
+    }
+
+    // Spectral Analysis - Optimized for performance
+    pub(crate) fn calculate_spectral_metrics(&self, pcm: &[f32]) -> (f32, f32, f32, Vec<f32>) {
+        let (_, windows) = self.spectral_windows(pcm);
+        spectral_summary(windows.into_iter().flatten())
+    }
+
+    // Metrics of every spectral window of every channel (channel-major; None
+    // for silent windows) and the hop between windows in frames
+    fn spectral_windows(&self, pcm: &[f32]) -> (usize, Vec<Option<SpectralWindow>>) {
+        let num_channels = self.num_channels;
+        let frames = pcm.len() / num_channels;
+        let window_size = self.quality.fft_size().min(frames);
+
+        // Limit analysis to the configured duration for very long files to improve performance
+        let analysis_length = self.limits.max_frames(frames, self.sample_rate);
+
+        // Process overlapping windows, with larger steps for speed (and decimation)
+        let step_size = self.spectral_hop(window_size, analysis_length);
+        let (stft, fixed) = self.spectral_transform(window_size, step_size);
+        let starts: Vec<usize> = stft.frame_starts(frames).take_while(|&start| start < analysis_length).collect();
+
+        // Windows of every channel are independent, so they can be transformed in parallel
+        let windows = map_range(0..starts.len() * num_channels, |index| {
+            let (channel, start) = (index / starts.len(), starts[index % starts.len()]);
+            let frame = &pcm[start * num_channels..(start + window_size) * num_channels];
+            self.analyze_spectral_window(&stft, fixed.as_ref(), channel_samples(frame, channel, num_channels))
+        });
+        (stft.hop(), windows)
+    }
+
+    // Frames between spectral windows of `window_size` over `analysis_length` frames
+    pub(crate) fn spectral_hop(&self, window_size: usize, analysis_length: usize) -> usize {
+        self.quality.spectral_hop(window_size, analysis_length, self.sample_rate) * self.limits.decimation()
+    }
+
+    // Framing of the spectral windows, with the fixed-point transform on
+    // reduced precision
+    pub(crate) fn spectral_transform(&self, window_size: usize, step_size: usize) -> (Stft, Option<FixedFft>) {
+        (Stft::new(window_size, step_size, self.window), self.reduced_precision.then(|| FixedFft::new(window_size)))
+    }
+
+    // Energy of the 7 frequency-balance bands per window, averaged over channels
+    fn band_energy_series(&self, pcm: &[f32]) -> TimeSeries {
+        let (step_size, windows) = self.spectral_windows(pcm);
+        let window_count = windows.len() / self.num_channels;
+        let mut bands = vec![0.0; window_count * 7];
+        for (index, window) in windows.into_iter().enumerate() {
+            if let Some((_, _, _, band_energies)) = window {
+                let frame = &mut bands[(index % window_count) * 7..][..7];
+                for (band, energy) in frame.iter_mut().zip(band_energies) {
+                    *band += energy / self.num_channels as f32;
                 }
             }
         }
-        
-        (leading_silence, trailing_silence, silence_gaps)
+        TimeSeries::new(step_size as f32 / self.sample_rate, 7, bands)
+    }
+
+    // Silence Detection
+    fn detect_silence(&self, pcm: &[f32], threshold_db: f32) -> (f32, f32, Vec<(f32, f32)>) {
+        let mut tracker = SilenceTracker::new(threshold_db, self.sample_rate);
+        tracker.push(pcm);
+        tracker.finish()
+    }
+
+    // Transient density from the shared onset detector (onsets per second),
+    // run on the mono fold of the channels
+    fn calculate_transient_density(&self, mono: &MonoFold) -> (usize, f32) {
+        let onset_count = pick_onsets(&mono.envelope).len();
+        (onset_count, self.transient_density(onset_count, mono.samples.len()))
+    }
+
+    // Onsets per second over `frames` frames
+    fn transient_density(&self, onset_count: usize, frames: usize) -> f32 {
+        let duration = frames as f32 / self.sample_rate;
+        if duration > 0.0 { onset_count as f32 / duration } else { 0.0 }
     }
 
-    // Calculate PLR (Peak-to-Loudness Ratio)
-    fn calculate_plr(&self, pcm: &Float32Array, integrated_loudness: f32) -> f32 {
-        // Find peak level
-        let mut peak: f32 = 0.0;
-        for i in 0..pcm.length() {
-            peak = peak.max(pcm.get_index(i).abs());
+    // Calculate PLR (Peak-to-Loudness Ratio) from the linear sample peak
+    fn calculate_plr(&self, peak: f32, integrated_loudness: f32) -> f32 {
+        let peak_db = self.db.to_db(peak);
+
+        // Undefined for gated-out (silent) programmes; report no range rather than NaN/inf
+        if !integrated_loudness.is_finite() {
+            return 0.0;
         }
         
-        let peak_db = amplitude_to_db(peak);
-        
         // PLR = Peak Level - Integrated Loudness
         peak_db - integrated_loudness
     }
 
-    // Mastering Quality Assessment - Optimized for performance
-    fn assess_mastering_quality(&self, pcm: &Float32Array, loudness: f32, dynamics: f32, spectral_balance: &[f32]) -> (f32, f32, f32, f32, f32) {
-        // Limit analysis to first 30 seconds for performance
-        let max_samples = (self.sample_rate * 30.0) as usize;
-        let length = (pcm.length() as usize).min(max_samples);
-        
-        // Punchiness (transient preservation)
-        let mut punchiness = 0.0;
-        let window_size = (self.sample_rate * 0.02) as usize; // 20ms windows for speed
-        
-        for i in (0..length).step_by(window_size * 2) { // Larger steps for speed
-            let end = (i + window_size).min(length);
-            let mut max_val: f32 = 0.0;
-            let mut avg_val = 0.0;
-            
-            for j in i..end {
-                let sample = pcm.get_index(j as u32).abs();
-                max_val = max_val.max(sample);
-                avg_val += sample;
-            }
-            
-            avg_val /= (end - i) as f32;
-            if avg_val > 1e-10 {
-                punchiness += max_val / avg_val;
-            }
-        }
-        punchiness /= (length / window_size) as f32;
-        punchiness = (punchiness / 10.0).min(1.0); // Normalize
-        
+    // Punchiness (transient preservation): peak-to-mean of the amplitude
+    // envelope of the mono fold, which unlike the rectified waveform is flat
+    // for a steady tone. The windows' ratios summed, and the frames they span
+    fn punch_sum(&self, mono: &[f32]) -> (f32, usize) {
+        // Limit analysis to the quality preset's window (the first 30 seconds
+        // at balanced) for performance
+        let length = mono.len().min(self.mastering_frames());
+        let window_size = self.punch_window_size();
+        let envelope = analytic_envelope(&mono[..length]);
+
+        let punch_sum = (0..length).step_by(window_size * 2) // Larger steps for speed
+            .map(|i| punch_window(&envelope[i..(i + window_size).min(length)]))
+            .sum();
+        (punch_sum, length)
+    }
+
+    // Mono frames the punchiness estimate reads at most
+    pub(crate) fn mastering_frames(&self) -> usize {
+        (self.sample_rate * self.quality.mastering_window()) as usize
+    }
+
+    // Punchiness window, 20ms for speed (read every other window)
+    pub(crate) fn punch_window_size(&self) -> usize {
+        ((self.sample_rate * 0.02) as usize).max(1)
+    }
+
+    // Frames of the 100ms dynamic range windows (in interleaved samples)
+    pub(crate) fn dynamics_window_size(&self) -> usize {
+        ((self.sample_rate * 0.1) as usize).max(1)
+    }
+
+    // Level (dB) of one dynamic range window, None when silent
+    pub(crate) fn dynamics_level(&self, window: &[f32]) -> Option<f32> {
+        let rms = calculate_rms(window);
+        (rms > 1e-10).then(|| self.db.to_db(rms))
+    }
+
+    // Mastering Quality Assessment: punchiness (from the windows' summed
+    // ratios over `length` mono frames), warmth, clarity, spaciousness and
+    // the overall mastering score
+    fn mastering_scores(&self, punch_sum: f32, length: usize, loudness: f32, dynamics: f32, spectral_balance: &[f32]) -> (f32, f32, f32, f32, f32) {
+        let punchiness = punch_sum / (length / self.punch_window_size()).max(1) as f32;
+        let punchiness = (punchiness / 10.0).min(1.0); // Normalize
+
         // Warmth (low frequency content)
         let warmth = (spectral_balance[0] + spectral_balance[1]) / 2.0; // Sub-bass + bass
         let warmth_normalized = warmth.min(1.0);
@@ -316,7 +651,9 @@ impl TechnicalAnalyzer {
         let spaciousness = (dynamics / 30.0).min(1.0); // Higher dynamics = more spacious
         
         // Overall mastering score
-        let loudness_score = if loudness >= -16.0 && loudness <= -8.0 { 1.0 } else { 0.5 };
+        // Full marks from 2 LU under to 6 LU over the target loudness
+        let target = self.target_loudness;
+        let loudness_score = if (target - 2.0..=target + 6.0).contains(&loudness) { 1.0 } else { 0.5 };
         let balance_score = if spectral_balance.iter().all(|&x| x > 0.1 && x < 2.0) { 1.0 } else { 0.7 };
         
         let mastering_score = (loudness_score + balance_score + punchiness + warmth_normalized + clarity_normalized) / 5.0 * 100.0;
@@ -324,107 +661,387 @@ impl TechnicalAnalyzer {
         (punchiness, warmth_normalized, clarity_normalized, spaciousness, mastering_score)
     }
 
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = analyze_technical)]
+    pub fn analyze_technical_js(&self, pcm: &Float32Array, integrated_loudness: f32, on_progress: Option<Function>) -> Result<TechnicalResult, JsError> {
+        // Copy PCM into WASM memory once, no more than the memory ceiling allows
+        let total = pcm.length() as usize;
+        let keep = self.limits.max_samples(total, BYTES_PER_SAMPLE);
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_prefix(&pcm.subarray(0, keep as u32).to_vec(), total, integrated_loudness, callback.as_deref())?)
+    }
+
+    // Spectral band energies per window, read back page by page
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = spectrogram)]
+    pub fn spectrogram_js(&self, pcm: &Float32Array) -> Result<TimeSeries, JsError> {
+        // Copy no more whole frames than the memory ceiling allows
+        let (total, channels) = (pcm.length() as usize, self.num_channels);
+        let keep = if total.is_multiple_of(channels) { self.limits.max_samples(total, BYTES_PER_SAMPLE) / channels * channels } else { total };
+        Ok(self.spectrogram(&pcm.subarray(0, keep as u32).to_vec())?)
+    }
+
+    // Analyse samples already written into WASM memory (no copy)
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen]
-    pub fn analyze_technical(&self, pcm: &Float32Array, integrated_loudness: f32) -> JsValue {
+    pub fn analyze_technical_buffer(&self, buffer: &PcmBuffer, integrated_loudness: f32, on_progress: Option<Function>) -> Result<TechnicalResult, JsError> {
+        let callback = on_progress.as_ref().map(js_progress);
+        Ok(self.analyze_technical(buffer.as_slice(), integrated_loudness, callback.as_deref())?)
+    }
+}
+
+impl TechnicalAnalyzer {
+    /// Peak, quality, spectral, silence and mastering analysis; `on_progress`
+    /// receives the percent complete and returns false to cancel
+    pub fn analyze_technical(&self, pcm: &[f32], integrated_loudness: f32, on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<TechnicalResult, AnalysisError> {
+        self.analyze_prefix(pcm, pcm.len(), integrated_loudness, on_progress)
+    }
+
+    /// Energy in each of the 7 frequency-balance bands per spectral window
+    /// (averaged over channels; silent windows are zero), within the limits
+    pub fn spectrogram(&self, pcm: &[f32]) -> Result<TimeSeries, AnalysisError> {
+        validate_pcm(pcm, self.num_channels, 1)?;
+        let keep = self.limits.max_samples(pcm.len(), BYTES_PER_SAMPLE) / self.num_channels * self.num_channels;
+        Ok(self.band_energy_series(&pcm[..keep]))
+    }
+
+    // Validate and analyse `pcm`, which may be a prefix of `total_samples` input samples
+    fn analyze_prefix(&self, pcm: &[f32], total_samples: usize, integrated_loudness: f32, on_progress: Option<&dyn Fn(f32) -> bool>) -> Result<TechnicalResult, AnalysisError> {
+        let keep = self.limits.max_samples(pcm.len(), BYTES_PER_SAMPLE);
+        validate_pcm(&pcm[..keep], 1, 1)?;
+        let progress = Progress::new(on_progress, self.cancel.as_ref());
+        let mono = MonoFold::new(&pcm[..keep], self.num_channels);
+        let result = self.analyze_samples(pcm, &mono, total_samples, integrated_loudness, &progress)?;
+        progress.report(1.0);
+        Ok(result)
+    }
+
+    // Technical analysis of samples already in WASM memory, which may be a
+    // prefix of `total_samples` input samples, with their mono fold
+    pub(crate) fn analyze_samples(&self, pcm: &[f32], mono: &MonoFold, total_samples: usize, integrated_loudness: f32, progress: &Progress) -> Result<TechnicalResult, AnalysisError> {
+        let pcm = &pcm[..self.limits.max_samples(pcm.len(), BYTES_PER_SAMPLE)];
+        // The fold covers what the caller passed; fold again if the limits cut it
+        let refolded;
+        let mono = if mono.samples.len() == pcm.len() / self.num_channels {
+            mono
+        } else {
+            refolded = MonoFold::new(pcm, self.num_channels);
+            &refolded
+        };
+
         // True Peak Analysis
-        let (true_peak_db, peak_locations, broadcast_compliant) = self.calculate_true_peak(pcm);
+        let oversampler = self.true_peak_oversampler();
+        let true_peaks = map_range(0..self.num_channels, |channel| self.channel_true_peak(pcm, channel, &oversampler));
+        progress.lap("true_peak");
         
+        progress.checkpoint(0.3)?;
+
         // Quality Metrics
-        let (has_clipping, clipped_samples, clipping_percentage) = self.detect_clipping(pcm);
+        let (_, clipped_samples, _) = self.detect_clipping(pcm);
         let dc_offset = self.calculate_dc_offset(pcm);
+        progress.lap("clipping_dc");
         
         // Spectral Analysis
-        let (spectral_centroid, spectral_rolloff, spectral_flatness, frequency_balance) = self.calculate_spectral_metrics(pcm);
+        let spectral = self.calculate_spectral_metrics(pcm);
+        progress.lap("spectral_fft");
         
+        progress.checkpoint(0.6)?;
+
         // Silence Detection
-        let (leading_silence, trailing_silence, silence_gaps) = self.detect_silence(pcm, -60.0);
+        let silence = self.detect_silence(pcm, self.silence_threshold);
         
-        // PLR Calculation
-        let plr = self.calculate_plr(pcm, integrated_loudness);
+        // Peak for PLR
+        let sample_peak = max_abs(pcm);
+        progress.lap("silence_plr");
+
+        progress.checkpoint(0.7)?;
+
+        // Transient Analysis
+        let (onset_count, _) = self.calculate_transient_density(mono);
+        progress.lap("transient_fft");
         
-        // Dynamic Range (simplified)
-        let mut rms_values = Vec::new();
-        let window_size = (self.sample_rate * 0.1) as usize; // 100ms windows
+        progress.checkpoint(0.85)?;
+
+        // Dynamic Range (simplified), over 100ms windows
+        let rms_levels = pcm.chunks(self.dynamics_window_size()).filter_map(|window| self.dynamics_level(window)).collect();
         
-        for i in (0..pcm.length() as usize).step_by(window_size) {
-            let end = (i + window_size).min(pcm.length() as usize);
-            let mut samples = Vec::new();
-            
-            for j in i..end {
-                samples.push(pcm.get_index(j as u32));
-            }
-            
-            let rms = calculate_rms(&samples);
-            if rms > 1e-10 {
-                rms_values.push(amplitude_to_db(rms));
+        // Mastering Quality Assessment
+        let (punch_sum, punch_length) = self.punch_sum(&mono.samples);
+        progress.lap("dynamics_mastering");
+        
+        progress.checkpoint(0.95)?;
+
+        let measures = TechnicalMeasures {
+            samples: pcm.len(),
+            true_peaks,
+            sample_peak,
+            clipped_samples,
+            dc_offset,
+            spectral,
+            silence,
+            silent_share: silent_share(pcm, self.silence_threshold),
+            onset_count,
+            rms_levels,
+            punch_sum,
+            punch_length,
+        };
+        Ok(self.technical_result(measures, total_samples, integrated_loudness))
+    }
+
+    // Result over the measured samples, which may be a prefix of
+    // `total_samples` input samples
+    pub(crate) fn technical_result(&self, measures: TechnicalMeasures, total_samples: usize, integrated_loudness: f32) -> TechnicalResult {
+        let TechnicalMeasures { samples, true_peaks, sample_peak, clipped_samples, dc_offset, spectral, silence, silent_share: silent, onset_count, mut rms_levels, punch_sum, punch_length } = measures;
+        let (true_peak_db, peak_locations, broadcast_compliant) = self.loudest_true_peak(true_peaks);
+        let clipping_percentage = (clipped_samples as f32 / samples as f32) * 100.0;
+        let (spectral_centroid, spectral_rolloff, spectral_flatness, frequency_balance) = spectral;
+        let (leading_silence, trailing_silence, silence_gaps) = silence;
+        let plr = self.calculate_plr(sample_peak, integrated_loudness);
+
+        let frames = samples / self.num_channels;
+        let transient_density = self.transient_density(onset_count, frames);
+
+        rms_levels.sort_unstable_by(f32::total_cmp);
+        let (windows, tail) = (rms_levels.len(), (rms_levels.len() as f32 * DYNAMICS_TAIL_SHARE).ceil() as usize);
+        let quiet = trimmed_mean(&mut rms_levels[..tail], DYNAMICS_TRIM);
+        let loud = trimmed_mean(&mut rms_levels[windows - tail..], DYNAMICS_TRIM);
+        let dynamic_range = match (loud, quiet) {
+            (Some(loud), Some(quiet)) => loud - quiet,
+            _ => 0.0,
+        };
+        let (punchiness, warmth, clarity, spaciousness, mastering_score) =
+            self.mastering_scores(punch_sum, punch_length, integrated_loudness, dynamic_range, &frequency_balance);
+
+        // Applied limits (the duration cap covers the windowed spectral pass)
+        let analyzed = self.limits.max_frames(samples, self.sample_rate) as f32 / self.sample_rate;
+        let limits = self.limits.report(analyzed, total_samples as f32 / self.sample_rate);
+
+        // Trust flags: the memory ceiling truncates every metric, the duration
+        // cap only the spectral pass, and punchiness sees the mastering window
+        let duration = frames as f32 / self.sample_rate;
+        let memory_truncated = samples < total_samples;
+        let spectral_too_short = frames < self.quality.fft_size();
+        let status = TechnicalStatus {
+            true_peak: MetricStatus::assess(false, silent, memory_truncated),
+            quality: MetricStatus::assess(false, silent, memory_truncated),
+            spectral: MetricStatus::assess(spectral_too_short, silent, limits.truncated),
+            silence: MetricStatus::assess(false, 0.0, memory_truncated),
+            dynamic_range: MetricStatus::assess(duration < MIN_DYNAMICS_DURATION, silent, memory_truncated),
+            plr: MetricStatus::assess(frames < SHORT_TERM_BLOCK_SIZE, silent, memory_truncated),
+            mastering: MetricStatus::assess(spectral_too_short, silent, limits.truncated || duration > self.quality.mastering_window()),
+        };
+
+        TechnicalResult {
+            true_peak: TruePeakResult {
+                level: true_peak_db,
+                locations: peak_locations,
+                broadcast_compliant,
+                spotify_compliant: true_peak_db <= -2.0,
+                youtube_compliant: true_peak_db <= -1.0,
+            },
+            quality: QualityResult {
+                has_clipping: clipped_samples > 0,
+                clipped_samples,
+                clipping_percentage,
+                dc_offset,
+            },
+            spectral: SpectralResult {
+                centroid: spectral_centroid,
+                rolloff: spectral_rolloff,
+                flatness: spectral_flatness,
+                frequency_balance: FrequencyBalance {
+                    sub_bass: frequency_balance[0],
+                    bass: frequency_balance[1],
+                    low_mids: frequency_balance[2],
+                    mids: frequency_balance[3],
+                    upper_mids: frequency_balance[4],
+                    presence: frequency_balance[5],
+                    brilliance: frequency_balance[6],
+                },
+            },
+            silence: SilenceResult {
+                leading_silence,
+                trailing_silence,
+                gap_count: silence_gaps.len(),
+            },
+            mastering: MasteringResult {
+                plr,
+                dynamic_range,
+                punchiness,
+                warmth,
+                clarity,
+                spaciousness,
+                quality_score: mastering_score,
+                onset_count,
+                transient_density,
+            },
+            limits,
+            status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{mix_to_mono, SincQuality};
+
+    #[test]
+    fn true_peak_and_clipping_are_per_channel() {
+        // Left carries a single full-scale sample, right is silent
+        let mut pcm = vec![0.0; 2 * 100];
+        pcm[2 * 10] = 1.0;
+        let mut analyzer = TechnicalAnalyzer::new(44100.0);
+        analyzer.set_num_channels(2);
+
+        let (true_peak_db, locations, _) = analyzer.calculate_true_peak(&pcm);
+        assert_eq!(true_peak_db, 0.0);
+        assert_eq!(locations.last(), Some(&10.0));
+        assert_eq!(analyzer.detect_clipping(&pcm).1, 1);
+
+        // A quarter-rate sine sampled 45 degrees off its crests peaks 3dB above its samples
+        // (a little more where it starts abruptly)
+        let pcm: Vec<f32> = (0..4410).map(|i| (std::f32::consts::FRAC_PI_2 * i as f32 + std::f32::consts::FRAC_PI_4).sin()).collect();
+        analyzer.set_num_channels(1);
+        let (true_peak_db, _, compliant) = analyzer.calculate_true_peak(&pcm);
+        assert!(true_peak_db > -0.1 && true_peak_db < 0.5, "{}", true_peak_db);
+        assert!(!compliant);
+    }
+
+    #[test]
+    fn blockwise_true_peak_matches_whole_signal_oversampling() {
+        // Noise over several blocks, rising so each block sets new peaks
+        let frames = 3 * TRUE_PEAK_BLOCK_FRAMES + 1000;
+        let mut seed = 1u32;
+        let pcm: Vec<f32> = (0..2 * frames)
+            .map(|i| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed as f32 / u32::MAX as f32 - 0.5) * (i as f32 / (2 * frames) as f32)
+            })
+            .collect();
+        let mut analyzer = TechnicalAnalyzer::new(48000.0);
+        analyzer.set_num_channels(2);
+        let oversampler = Polyphase::oversampler(4, SincQuality::Balanced);
+
+        for channel in 0..2 {
+            let samples: Vec<f32> = channel_samples(&pcm, channel, 2).collect();
+            let (mut peak, mut locations) = (-f32::INFINITY, Vec::new());
+            for (n, x) in oversampler.process(&samples).into_iter().enumerate() {
+                let x = if n.is_multiple_of(4) { x.abs().max(samples[n / 4].abs()) } else { x.abs() };
+                if x > peak {
+                    peak = x;
+                    locations.push(n as f32 / 4.0);
+                }
             }
+            assert_eq!(analyzer.channel_true_peak(&pcm, channel, &oversampler), (peak, locations));
         }
-        
-        rms_values.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        let dynamic_range = if rms_values.len() > 0 {
-            let p90 = rms_values[(rms_values.len() as f32 * 0.9) as usize];
-            let p10 = rms_values[(rms_values.len() as f32 * 0.1) as usize];
-            p90 - p10
-        } else {
-            0.0
+    }
+
+    #[test]
+    fn transient_density_counts_frames_not_samples() {
+        // Clicks every half second for 4 s: the stereo copy reads the same
+        // density as the mono one
+        let mono: Vec<f32> = (0..4 * 44100).map(|i| if i % 22050 < 64 { 0.8 * (i as f32 * 0.7).sin() } else { 0.0 }).collect();
+        let stereo: Vec<f32> = mono.iter().flat_map(|&x| [x, x]).collect();
+        let mut analyzer = TechnicalAnalyzer::new(44100.0);
+        let (mono_count, mono_density) = analyzer.calculate_transient_density(&MonoFold::new(&mono, 1));
+        analyzer.set_num_channels(2);
+        let (stereo_count, stereo_density) = analyzer.calculate_transient_density(&MonoFold::new(&stereo, 2));
+        assert!((6..=8).contains(&mono_count), "{}", mono_count);
+        assert_eq!((stereo_count, stereo_density), (mono_count, mono_density));
+        assert!((mono_density - 2.0).abs() < 0.3, "{}", mono_density);
+    }
+
+    #[test]
+    fn punchiness_reads_the_envelope_of_a_wide_image() {
+        // A steady tone in quadrature between the channels: the interleaved
+        // samples alternate wildly, the mono fold is as steady as the tone
+        let tone = |i: usize, phase: f32| 0.5 * (i as f32 * 0.05 + phase).sin();
+        let mono: Vec<f32> = (0..2 * 44100).map(|i| tone(i, 0.0)).collect();
+        let wide: Vec<f32> = (0..2 * 44100).flat_map(|i| [tone(i, 0.0), tone(i, std::f32::consts::FRAC_PI_2)]).collect();
+        let punchiness = |analyzer: &TechnicalAnalyzer, pcm: &[f32]| {
+            let (punch_sum, length) = analyzer.punch_sum(&mix_to_mono(pcm, analyzer.num_channels));
+            analyzer.mastering_scores(punch_sum, length, -14.0, 10.0, &[0.5; 7]).0
         };
-        
-        // Mastering Quality Assessment
-        let (punchiness, warmth, clarity, spaciousness, mastering_score) = 
-            self.assess_mastering_quality(pcm, integrated_loudness, dynamic_range, &frequency_balance);
-        
-        // Create result object
-        let result = js_sys::Object::new();
-        
-        // True Peak section
-        let true_peak_obj = js_sys::Object::new();
-        js_sys::Reflect::set(&true_peak_obj, &"level".into(), &true_peak_db.into()).unwrap();
-        js_sys::Reflect::set(&true_peak_obj, &"locations".into(), &js_sys::Array::from_iter(peak_locations.iter().map(|&v| JsValue::from_f64(v as f64)))).unwrap();
-        js_sys::Reflect::set(&true_peak_obj, &"broadcast_compliant".into(), &broadcast_compliant.into()).unwrap();
-        js_sys::Reflect::set(&true_peak_obj, &"spotify_compliant".into(), &(true_peak_db <= -2.0).into()).unwrap();
-        js_sys::Reflect::set(&true_peak_obj, &"youtube_compliant".into(), &(true_peak_db <= -1.0).into()).unwrap();
-        js_sys::Reflect::set(&result, &"true_peak".into(), &true_peak_obj).unwrap();
-        
-        // Quality section
-        let quality_obj = js_sys::Object::new();
-        js_sys::Reflect::set(&quality_obj, &"has_clipping".into(), &has_clipping.into()).unwrap();
-        js_sys::Reflect::set(&quality_obj, &"clipped_samples".into(), &clipped_samples.into()).unwrap();
-        js_sys::Reflect::set(&quality_obj, &"clipping_percentage".into(), &clipping_percentage.into()).unwrap();
-        js_sys::Reflect::set(&quality_obj, &"dc_offset".into(), &dc_offset.into()).unwrap();
-        js_sys::Reflect::set(&result, &"quality".into(), &quality_obj).unwrap();
-        
-        // Spectral section
-        let spectral_obj = js_sys::Object::new();
-        js_sys::Reflect::set(&spectral_obj, &"centroid".into(), &spectral_centroid.into()).unwrap();
-        js_sys::Reflect::set(&spectral_obj, &"rolloff".into(), &spectral_rolloff.into()).unwrap();
-        js_sys::Reflect::set(&spectral_obj, &"flatness".into(), &spectral_flatness.into()).unwrap();
-        
-        let balance_obj = js_sys::Object::new();
-        let band_names = ["sub_bass", "bass", "low_mids", "mids", "upper_mids", "presence", "brilliance"];
-        for (i, &name) in band_names.iter().enumerate() {
-            js_sys::Reflect::set(&balance_obj, &name.into(), &frequency_balance[i].into()).unwrap();
+        let mut analyzer = TechnicalAnalyzer::new(44100.0);
+        let steady = punchiness(&analyzer, &mono);
+        analyzer.set_num_channels(2);
+        let punchiness = punchiness(&analyzer, &wide);
+        assert!((punchiness - steady).abs() < 0.005, "{} vs {}", punchiness, steady);
+    }
+
+    #[test]
+    fn dynamic_range_ignores_stray_windows() {
+        // 5 s at -6 dBFS then 5 s at -26 dBFS (100ms windows), with one window
+        // of each half 6dB hotter or 40dB quieter than the rest
+        let window = 2205;
+        let pcm: Vec<f32> = (0..100 * window)
+            .map(|i| {
+                let level = match i / window {
+                    10 => 1.0,
+                    70 => 0.0005,
+                    w if w < 50 => 0.5,
+                    _ => 0.05,
+                };
+                level * (i as f32 * 0.3).sin()
+            })
+            .collect();
+        let analyzer = TechnicalAnalyzer::new(22050.0);
+        let result = analyzer.analyze_technical(&pcm, -14.0, None).unwrap();
+        assert!((result.mastering.dynamic_range - 20.0).abs() < 0.1, "{}", result.mastering.dynamic_range);
+    }
+
+    #[test]
+    fn status_flags_untrusted_metrics() {
+        let tone = |seconds: f32| -> Vec<f32> { (0..(seconds * 44100.0) as usize).map(|i| 0.5 * (i as f32 * 0.05).sin()).collect() };
+        let mut analyzer = TechnicalAnalyzer::new(44100.0);
+
+        let status = analyzer.analyze_technical(&tone(0.5), -14.0, None).unwrap().status;
+        assert_eq!(status.true_peak, MetricStatus::Ok);
+        assert_eq!(status.dynamic_range, MetricStatus::InsufficientDuration);
+        assert_eq!(status.plr, MetricStatus::InsufficientDuration);
+
+        let mut mostly_silent = tone(1.0);
+        mostly_silent.resize(10 * 44100, 0.0);
+        let status = analyzer.analyze_technical(&mostly_silent, -14.0, None).unwrap().status;
+        assert_eq!(status.spectral, MetricStatus::SilenceDominated);
+
+        let mut limits = AnalysisLimits::new();
+        limits.set_max_duration(2.0);
+        analyzer.set_limits(&limits);
+        let status = analyzer.analyze_technical(&tone(5.0), -14.0, None).unwrap().status;
+        assert_eq!(status.spectral, MetricStatus::TruncatedToLimit);
+        assert_eq!(status.true_peak, MetricStatus::Ok);
+    }
+
+    #[test]
+    fn reduced_precision_spectrogram_stays_close() {
+        // Two seconds of a chord with a decaying tail
+        let pcm: Vec<f32> = (0..88200).map(|i| {
+            let t = i as f32 / 44100.0;
+            (0.4 * (2.0 * std::f32::consts::PI * 110.0 * t).sin() + 0.1 * (2.0 * std::f32::consts::PI * 1760.0 * t).sin() + 0.02 * (2.0 * std::f32::consts::PI * 7040.0 * t).sin()) * (-t).exp()
+        }).collect();
+        let mut analyzer = TechnicalAnalyzer::new(44100.0);
+        let full = analyzer.spectrogram(&pcm).unwrap();
+        analyzer.set_reduced_precision(true);
+        let reduced = analyzer.spectrogram(&pcm).unwrap();
+        assert_eq!(full.total_frames(), reduced.total_frames());
+
+        // Accuracy delta, in the dB the series renders with: bands within
+        // 5dB of the loudest agree to 0.1dB (measured 0.09), bands 5-20dB down
+        // to 1dB (measured 0.7); weaker bands sit on the rounding floor, which
+        // stays 15dB or more under the loudest band
+        let db = |energy: f32| 10.0 * (energy + 1e-12).log10();
+        let ceiling = full.values().iter().copied().map(db).fold(f32::NEG_INFINITY, f32::max);
+        for (&a, &b) in full.values().iter().zip(reduced.values()) {
+            let (level, delta) = (db(a) - ceiling, (db(b) - db(a)).abs());
+            if level > -5.0 {
+                assert!(delta < 0.1, "{} dB off at {} dB", delta, level);
+            } else if level > -20.0 {
+                assert!(delta < 1.0, "{} dB off at {} dB", delta, level);
+            } else {
+                assert!(db(b) < ceiling - 15.0, "{} dB floor", db(b) - ceiling);
+            }
         }
-        js_sys::Reflect::set(&spectral_obj, &"frequency_balance".into(), &balance_obj).unwrap();
-        js_sys::Reflect::set(&result, &"spectral".into(), &spectral_obj).unwrap();
-        
-        // Silence section
-        let silence_obj = js_sys::Object::new();
-        js_sys::Reflect::set(&silence_obj, &"leading_silence".into(), &leading_silence.into()).unwrap();
-        js_sys::Reflect::set(&silence_obj, &"trailing_silence".into(), &trailing_silence.into()).unwrap();
-        js_sys::Reflect::set(&silence_obj, &"gap_count".into(), &silence_gaps.len().into()).unwrap();
-        js_sys::Reflect::set(&result, &"silence".into(), &silence_obj).unwrap();
-        
-        // Mastering section
-        let mastering_obj = js_sys::Object::new();
-        js_sys::Reflect::set(&mastering_obj, &"plr".into(), &plr.into()).unwrap();
-        js_sys::Reflect::set(&mastering_obj, &"dynamic_range".into(), &dynamic_range.into()).unwrap();
-        js_sys::Reflect::set(&mastering_obj, &"punchiness".into(), &punchiness.into()).unwrap();
-        js_sys::Reflect::set(&mastering_obj, &"warmth".into(), &warmth.into()).unwrap();
-        js_sys::Reflect::set(&mastering_obj, &"clarity".into(), &clarity.into()).unwrap();
-        js_sys::Reflect::set(&mastering_obj, &"spaciousness".into(), &spaciousness.into()).unwrap();
-        js_sys::Reflect::set(&mastering_obj, &"quality_score".into(), &mastering_score.into()).unwrap();
-        js_sys::Reflect::set(&result, &"mastering".into(), &mastering_obj).unwrap();
-        
-        result.into()
     }
-} 
\ No newline at end of file
+}
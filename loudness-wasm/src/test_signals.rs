@@ -0,0 +1,247 @@
+// Reference verification signals: the EBU Tech 3341 loudness cases, the EBU
+// Tech 3342 loudness range cases and a -1 dBTP inter-sample peak, synthesized
+// on demand (stereo, 44.1kHz) with the readings a compliant meter gives. They
+// back the unit tests and the in-app self-test, which runs every case through
+// the loudness and true-peak meters `Analyzer.analyze` reports from.
+//
+//     const report = run_self_test();
+//     if (!report.passed) console.table(report.cases.filter(c => !c.passed));
+//     const pcm = reference_signal("ebu-3341-3");  // interleaved stereo
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+#[cfg(feature = "technical")]
+use crate::config::AnalyzerConfig;
+#[cfg(feature = "technical")]
+use crate::error::AnalysisError;
+#[cfg(feature = "technical")]
+use crate::loudness::LoudnessAnalyzer;
+#[cfg(feature = "technical")]
+use crate::technical::TechnicalAnalyzer;
+
+// The meters' block rate, so nothing is resampled on the way in
+pub const TEST_SIGNAL_RATE: f32 = 44100.0;
+pub const TEST_SIGNAL_CHANNELS: usize = 2;
+
+// EBU tolerances: ±0.1 LU for integrated loudness and ±1 LU for loudness
+// range; true peak allows +0.2/-0.4 dB, held here to the tighter ±0.2
+const INTEGRATED_TOLERANCE: f32 = 0.1;
+const RANGE_TOLERANCE: f32 = 1.0;
+
+/// Measurement a reference signal pins down
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub enum Metric {
+    // LUFS
+    Integrated,
+    // LU
+    LoudnessRange,
+    // dBTP
+    TruePeak,
+}
+
+/// Reading a compliant meter gives, within `tolerance` either side
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Expectation {
+    pub metric: Metric,
+    pub value: f32,
+    pub tolerance: f32,
+}
+
+impl Expectation {
+    #[cfg(feature = "technical")]
+    pub fn accepts(&self, measured: f32) -> bool {
+        (measured - self.value).abs() <= self.tolerance
+    }
+}
+
+/// A stereo tone (same on both channels) stepping through peak levels:
+/// `segments` holds (dBFS, seconds) pairs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReferenceSignal {
+    pub name: &'static str,
+    pub frequency: f32,
+    // Phase (radians) of the first sample
+    pub phase: f32,
+    pub segments: &'static [(f32, f32)],
+    pub expectations: &'static [Expectation],
+}
+
+const fn integrated(value: f32) -> Expectation {
+    Expectation { metric: Metric::Integrated, value, tolerance: INTEGRATED_TOLERANCE }
+}
+
+const fn loudness_range(value: f32) -> Expectation {
+    Expectation { metric: Metric::LoudnessRange, value, tolerance: RANGE_TOLERANCE }
+}
+
+pub const REFERENCE_SIGNALS: [ReferenceSignal; 9] = [
+    ReferenceSignal { name: "ebu-3341-1", frequency: 1000.0, phase: 0.0, segments: &[(-23.0, 20.0)], expectations: &[integrated(-23.0)] },
+    ReferenceSignal { name: "ebu-3341-2", frequency: 1000.0, phase: 0.0, segments: &[(-33.0, 20.0)], expectations: &[integrated(-33.0)] },
+    ReferenceSignal {
+        name: "ebu-3341-3",
+        frequency: 1000.0,
+        phase: 0.0,
+        segments: &[(-36.0, 10.0), (-23.0, 60.0), (-36.0, 10.0)],
+        expectations: &[integrated(-23.0)],
+    },
+    ReferenceSignal {
+        name: "ebu-3341-4",
+        frequency: 1000.0,
+        phase: 0.0,
+        segments: &[(-72.0, 10.0), (-36.0, 10.0), (-23.0, 60.0), (-36.0, 10.0), (-72.0, 10.0)],
+        expectations: &[integrated(-23.0)],
+    },
+    ReferenceSignal { name: "ebu-3342-1", frequency: 1000.0, phase: 0.0, segments: &[(-20.0, 20.0), (-30.0, 20.0)], expectations: &[loudness_range(10.0)] },
+    ReferenceSignal { name: "ebu-3342-2", frequency: 1000.0, phase: 0.0, segments: &[(-20.0, 20.0), (-15.0, 20.0)], expectations: &[loudness_range(5.0)] },
+    ReferenceSignal { name: "ebu-3342-3", frequency: 1000.0, phase: 0.0, segments: &[(-40.0, 20.0), (-20.0, 20.0)], expectations: &[loudness_range(20.0)] },
+    ReferenceSignal {
+        name: "ebu-3342-4",
+        frequency: 1000.0,
+        phase: 0.0,
+        segments: &[(-50.0, 20.0), (-35.0, 20.0), (-20.0, 20.0), (-35.0, 20.0), (-50.0, 20.0)],
+        expectations: &[loudness_range(15.0)],
+    },
+    // A quarter-rate sine sampled 45 degrees off its crests: samples peak at
+    // -4 dBFS, the waveform between them at -1 dBTP
+    ReferenceSignal {
+        name: "true-peak-1",
+        frequency: TEST_SIGNAL_RATE / 4.0,
+        phase: std::f32::consts::FRAC_PI_4,
+        segments: &[(-1.0, 1.0)],
+        expectations: &[Expectation { metric: Metric::TruePeak, value: -1.0, tolerance: 0.2 }],
+    },
+];
+
+impl ReferenceSignal {
+    pub fn by_name(name: &str) -> Option<&'static ReferenceSignal> {
+        REFERENCE_SIGNALS.iter().find(|signal| signal.name == name)
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.segments.iter().map(|&(_, seconds)| seconds).sum()
+    }
+
+    /// Interleaved stereo PCM at `TEST_SIGNAL_RATE`; the phase runs on across
+    /// level steps
+    pub fn synthesize(&self) -> Vec<f32> {
+        let step = 2.0 * std::f64::consts::PI * self.frequency as f64 / TEST_SIGNAL_RATE as f64;
+        let mut pcm = Vec::with_capacity((self.duration() * TEST_SIGNAL_RATE) as usize * TEST_SIGNAL_CHANNELS);
+        let mut n = 0usize;
+        for &(level, seconds) in self.segments {
+            let amplitude = 10f64.powf(level as f64 / 20.0);
+            for _ in 0..(seconds * TEST_SIGNAL_RATE) as usize {
+                let sample = (amplitude * (self.phase as f64 + step * n as f64).sin()) as f32;
+                pcm.extend_from_slice(&[sample; TEST_SIGNAL_CHANNELS]);
+                n += 1;
+            }
+        }
+        pcm
+    }
+}
+
+/// One expectation checked against the meters
+#[cfg(feature = "technical")]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+pub struct SelfTestCase {
+    pub signal: String,
+    pub metric: Metric,
+    pub expected: f32,
+    pub tolerance: f32,
+    pub measured: f32,
+    pub passed: bool,
+}
+
+/// Every reference case; `passed` when all of them are
+#[cfg(feature = "technical")]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct SelfTestReport {
+    pub cases: Vec<SelfTestCase>,
+    pub passed: bool,
+}
+
+/// Run every reference signal through the loudness meter (integrated loudness
+/// and loudness range) and the true-peak meter the analyzers use
+#[cfg(feature = "technical")]
+pub fn run_self_test() -> Result<SelfTestReport, AnalysisError> {
+    let config = AnalyzerConfig::new(TEST_SIGNAL_RATE, TEST_SIGNAL_CHANNELS);
+    let loudness = LoudnessAnalyzer::from_config(&config);
+    let peaks = TechnicalAnalyzer::from_config(&config);
+    let mut cases = Vec::new();
+    for signal in &REFERENCE_SIGNALS {
+        let pcm = signal.synthesize();
+        let needs_loudness = signal.expectations.iter().any(|expectation| expectation.metric != Metric::TruePeak);
+        let measured = if needs_loudness { Some(loudness.analyze(&pcm, None)?) } else { None };
+        for expectation in signal.expectations {
+            let value = match (expectation.metric, &measured) {
+                (Metric::Integrated, Some(result)) => result.integrated,
+                (Metric::LoudnessRange, Some(result)) => result.loudness_range,
+                _ => peaks.calculate_true_peak(&pcm).0,
+            };
+            cases.push(SelfTestCase {
+                signal: signal.name.to_string(),
+                metric: expectation.metric,
+                expected: expectation.value,
+                tolerance: expectation.tolerance,
+                measured: value,
+                passed: expectation.accepts(value),
+            });
+        }
+    }
+    let passed = cases.iter().all(|case| case.passed);
+    Ok(SelfTestReport { cases, passed })
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "technical"))]
+#[wasm_bindgen(js_name = run_self_test)]
+pub fn run_self_test_js() -> Result<SelfTestReport, JsError> {
+    Ok(run_self_test()?)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = reference_signal)]
+pub fn reference_signal_js(name: &str) -> Result<Vec<f32>, JsError> {
+    ReferenceSignal::by_name(name)
+        .map(ReferenceSignal::synthesize)
+        .ok_or_else(|| JsError::new("unknown reference signal"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signals_step_through_their_levels() {
+        let signal = ReferenceSignal::by_name("ebu-3341-3").unwrap();
+        assert_eq!(signal.duration(), 80.0);
+        let pcm = signal.synthesize();
+        assert_eq!(pcm.len(), 80 * 44100 * 2);
+        let peak = |from: f32, to: f32| pcm[(from * 88200.0) as usize..(to * 88200.0) as usize].iter().fold(0.0f32, |peak, &s| peak.max(s.abs()));
+        assert!((20.0 * peak(0.0, 10.0).log10() - -36.0).abs() < 0.01);
+        assert!((20.0 * peak(10.0, 70.0).log10() - -23.0).abs() < 0.01);
+
+        // The inter-sample peak hides 3dB above the samples
+        let samples = ReferenceSignal::by_name("true-peak-1").unwrap().synthesize();
+        let sample_peak = samples.iter().fold(0.0f32, |peak, &s| peak.max(s.abs()));
+        assert!((20.0 * sample_peak.log10() - -4.01).abs() < 0.01);
+        assert!(ReferenceSignal::by_name("ebu-3341-99").is_none());
+    }
+
+    #[cfg(feature = "technical")]
+    #[test]
+    fn self_test_covers_every_case() {
+        let report = run_self_test().unwrap();
+        assert_eq!(report.cases.len(), REFERENCE_SIGNALS.len());
+        for case in &report.cases {
+            assert!(case.passed, "{} {:?}: measured {} expected {}", case.signal, case.metric, case.measured, case.expected);
+        }
+        assert!(report.passed);
+    }
+}
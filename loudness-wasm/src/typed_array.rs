@@ -0,0 +1,84 @@
+// Numeric vectors in results cross into JS as `Float32Array` copies (a single
+// memcpy) instead of arrays of boxed numbers; native serializers (the CLI's
+// JSON output) still see a plain sequence. serde-wasm-bindgen deserializes
+// sequences from any iterable, so typed arrays posted back (shard statistics)
+// read as `Vec<f32>` again. JSON reports (`to_json`) get plain sequences on
+// wasm too.
+
+use serde::Serializer;
+#[cfg(any(not(target_arch = "wasm32"), all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical")))]
+use serde::Serialize;
+#[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+use std::cell::Cell;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+
+#[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+thread_local! {
+    // Set while JSON is written, so typed-array fields serialize as plain
+    // number arrays instead of JS object handles
+    static WRITING_JSON: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks JSON as being written on this thread until dropped, unwinding
+/// included; nested guards restore the outer state
+#[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+pub(crate) struct WritingJson(bool);
+
+#[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+impl WritingJson {
+    pub(crate) fn start() -> Self {
+        WritingJson(WRITING_JSON.with(|writing| writing.replace(true)))
+    }
+}
+
+#[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+impl Drop for WritingJson {
+    fn drop(&mut self) {
+        WRITING_JSON.with(|writing| writing.set(self.0));
+    }
+}
+
+// Whether JSON is being written on this thread
+#[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+pub(crate) fn writing_json() -> bool {
+    WRITING_JSON.with(Cell::get)
+}
+
+/// `serialize_with` target for `Vec<f32>` result fields
+#[cfg(target_arch = "wasm32")]
+pub fn serialize<S: Serializer>(values: &[f32], serializer: S) -> Result<S::Ok, S::Error> {
+    #[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+    if writing_json() {
+        return values.serialize(serializer);
+    }
+    serde_wasm_bindgen::preserve::serialize(&Float32Array::from(values), serializer)
+}
+
+/// `serialize_with` target for `Vec<f32>` result fields
+#[cfg(not(target_arch = "wasm32"))]
+pub fn serialize<S: Serializer>(values: &[f32], serializer: S) -> Result<S::Ok, S::Error> {
+    values.serialize(serializer)
+}
+
+#[cfg(all(test, feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writing_json_is_scoped_to_its_guard() {
+        {
+            let _report = WritingJson::start();
+            drop(WritingJson::start());
+            assert!(writing_json());
+        }
+        assert!(!writing_json());
+
+        // A panic mid-report still clears the flag
+        assert!(std::panic::catch_unwind(|| {
+            let _report = WritingJson::start();
+            panic!("serializer failed");
+        }).is_err());
+        assert!(!writing_json());
+    }
+}
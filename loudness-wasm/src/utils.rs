@@ -1,146 +1,1495 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(any(feature = "technical", feature = "music"))]
+use std::collections::HashMap;
+#[cfg(any(feature = "technical", feature = "music"))]
 use std::f32::consts::PI;
+#[cfg(any(feature = "loudness", feature = "music"))]
+use std::ops::{Add, Mul, Sub};
+#[cfg(any(feature = "stereo", feature = "technical"))]
+use std::ops::{Deref, DerefMut};
+#[cfg(any(feature = "stereo", feature = "technical", feature = "music"))]
+use std::sync::Mutex;
+#[cfg(any(feature = "technical", feature = "music"))]
+use std::sync::{Arc, OnceLock};
+use crate::simd::dot;
+#[cfg(feature = "technical")]
+use crate::simd::{max_abs, sum_squares_f64};
+#[cfg(any(feature = "technical", feature = "music"))]
+use crate::simd::butterflies;
+use crate::fir::windowed_sinc;
+use crate::window::Window;
+#[cfg(feature = "technical")]
+use crate::window::WindowKey;
 
-/// High-precision frequency to pitch class conversion
-pub fn freq_to_pitch_class_precise(freq: f32) -> usize {
-    if freq <= 0.0 { return 0; }
-    
-    // Use precise A4 = 440 Hz reference
-    let a4_freq = 440.0;
-    let semitones_from_a4 = 12.0 * (freq / a4_freq).log2();
-    
-    // Convert A-relative to C-relative: In chromatic scale C=0, A=9
-    // So to convert from A=0 reference to C=0 reference, we add 9
-    let pitch_class = ((semitones_from_a4 + 9.0).round() as i32) % 12;
-    let pitch_class = if pitch_class < 0 { pitch_class + 12 } else { pitch_class };
-    
-    pitch_class as usize
+// Power floor for the cepstrum's log spectrum (-200dB)
+#[cfg(any(feature = "technical", feature = "music"))]
+const MIN_CEPSTRUM_POWER: f32 = 1e-20;
+
+// Block the Hilbert transform runs in, and the context kept either side of
+// each block's output (the transform's 1/n response has faded to a fraction
+// of a percent by then)
+#[cfg(feature = "technical")]
+pub(crate) const HILBERT_BLOCK: usize = 8192;
+#[cfg(feature = "technical")]
+pub(crate) const HILBERT_MARGIN: usize = 1024;
+// Output samples per block
+#[cfg(feature = "technical")]
+pub(crate) const HILBERT_HOP: usize = HILBERT_BLOCK - 2 * HILBERT_MARGIN;
+
+/// Iterative radix-2 FFT with precomputed twiddles and bit-reversal table.
+/// Inputs shorter than the transform size are zero-padded.
+#[cfg(any(feature = "technical", feature = "music"))]
+pub struct Fft {
+    size: usize,
+    // Twiddles laid out stage by stage, the `half` of a stage at
+    // `half - 1..2 * half - 1`, so each stage's butterflies read them
+    // contiguously
+    twiddles_re: Vec<f32>,
+    twiddles_im: Vec<f32>,
+    bit_reverse: Vec<usize>,
 }
 
-/// Calculate Pearson correlation with professional optimizations
-pub fn calculate_enhanced_correlation(chroma: &[f32], key_profile: &[f32], root: usize) -> f32 {
-    // Calculate means
-    let chroma_mean: f32 = chroma.iter().sum::<f32>() / chroma.len() as f32;
-    let profile_mean: f32 = key_profile.iter().sum::<f32>() / key_profile.len() as f32;
-    
-    let mut numerator = 0.0;
-    let mut chroma_variance = 0.0;
-    let mut profile_variance = 0.0;
-    
-    for i in 0..12 {
-        let chroma_idx = (i + root) % 12;
-        let chroma_centered = chroma[chroma_idx] - chroma_mean;
-        let profile_centered = key_profile[i] - profile_mean;
-        
-        numerator += chroma_centered * profile_centered;
-        chroma_variance += chroma_centered * chroma_centered;
-        profile_variance += profile_centered * profile_centered;
+#[cfg(any(feature = "technical", feature = "music"))]
+impl Fft {
+    /// Plan a transform of at least `len` points (rounded up to a power of two)
+    pub fn new(len: usize) -> Fft {
+        let size = len.max(2).next_power_of_two();
+        let bits = size.trailing_zeros();
+        let (twiddles_re, twiddles_im) = (0..bits)
+            .flat_map(|stage| {
+                let half = 1 << stage;
+                (0..half).map(move |k| {
+                    let (sin, cos) = (-PI * k as f32 / half as f32).sin_cos();
+                    (cos, sin)
+                })
+            })
+            .unzip();
+        let bit_reverse = (0..size)
+            .map(|i| i.reverse_bits() >> (usize::BITS - bits))
+            .collect();
+
+        Fft { size, twiddles_re, twiddles_im, bit_reverse }
     }
-    
-    let denominator = (chroma_variance * profile_variance).sqrt();
-    
-    if denominator > 1e-8 {
-        (numerator / denominator).max(-1.0).min(1.0)
-    } else {
-        0.0
+
+    /// Transform size in points
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// In-place complex forward transform; both buffers must be `size()` long
+    pub fn process(&self, real: &mut [f32], imag: &mut [f32]) {
+        for i in 0..self.size {
+            let j = self.bit_reverse[i];
+            if j > i {
+                real.swap(i, j);
+                imag.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+        while len <= self.size {
+            let half = len / 2;
+            let twiddles = (&self.twiddles_re[half - 1..len - 1], &self.twiddles_im[half - 1..len - 1]);
+            for (real, imag) in real[..self.size].chunks_exact_mut(len).zip(imag[..self.size].chunks_exact_mut(len)) {
+                let (a_re, b_re) = real.split_at_mut(half);
+                let (a_im, b_im) = imag.split_at_mut(half);
+                butterflies((a_re, a_im), (b_re, b_im), twiddles);
+            }
+            len *= 2;
+        }
+    }
+
+    /// In-place inverse of `process`, scaled by 1/size so it undoes it
+    #[cfg(any(feature = "technical", feature = "music"))]
+    pub fn inverse(&self, real: &mut [f32], imag: &mut [f32]) {
+        // Conjugate, transform forward, conjugate again
+        imag.iter_mut().for_each(|im| *im = -*im);
+        self.process(real, imag);
+        let scale = 1.0 / self.size as f32;
+        real.iter_mut().for_each(|re| *re *= scale);
+        imag.iter_mut().for_each(|im| *im *= -scale);
+    }
+
+    /// Unnormalised magnitudes of bins 0..size/2 for a real signal
+    #[cfg(any(test, feature = "bench"))]
+    pub fn magnitudes(&self, samples: &[f32]) -> Vec<f32> {
+        let mut real = vec![0.0; self.size];
+        let mut imag = vec![0.0; self.size];
+        let n = samples.len().min(self.size);
+        real[..n].copy_from_slice(&samples[..n]);
+
+        self.magnitudes_in_place(&mut real, &mut imag);
+        real.truncate(self.size / 2);
+        real
+    }
+
+    /// Magnitudes without allocating: `real` holds the zero-padded signal and
+    /// `imag` zeros (both `size()` long); the magnitudes are left in
+    /// `real[..size / 2]`
+    #[cfg(any(feature = "technical", test))]
+    pub fn magnitudes_in_place(&self, real: &mut [f32], imag: &mut [f32]) {
+        self.process(real, imag);
+        for (re, &im) in real.iter_mut().zip(imag.iter()).take(self.size / 2) {
+            *re = (*re * *re + im * im).sqrt();
+        }
     }
 }
 
-/// Professional FFT with optimal parameters for musical analysis
-pub fn compute_professional_fft(samples: &[f32]) -> Vec<f32> {
-    let n = samples.len();
-    let mut magnitudes = vec![0.0; n / 2];
-    
-    // High-precision DFT optimized for musical analysis
-    for k in 1..(n/2) {
-        let mut real = 0.0;
-        let mut imag = 0.0;
-        let freq_scale = 2.0 * PI * k as f32 / n as f32;
-        
-        for i in 0..n {
-            let angle = freq_scale * i as f32;
-            let (sin_val, cos_val) = angle.sin_cos();
-            real += samples[i] * cos_val;
-            imag += samples[i] * sin_val;
-        }
-        
-        magnitudes[k] = (real * real + imag * imag).sqrt() / (n as f32);
+// Full scale of a Q15 value
+#[cfg(feature = "technical")]
+const Q15_ONE: f32 = 32767.0;
+
+/// Radix-2 FFT in Q15 fixed point for low-end devices: i16 samples and
+/// twiddles, i32 products, every stage halved so nothing overflows (the
+/// result is the DFT / size). Frames are scaled to full range before they are
+/// quantised (block floating point), so quiet frames keep their resolution;
+/// the rounding noise floor sits roughly 75dB under the strongest bin.
+#[cfg(feature = "technical")]
+pub struct FixedFft {
+    size: usize,
+    twiddles: Vec<(i16, i16)>,
+    bit_reverse: Vec<usize>,
+}
+
+#[cfg(feature = "technical")]
+impl FixedFft {
+    /// Plan a transform of at least `len` points (rounded up to a power of two)
+    pub fn new(len: usize) -> FixedFft {
+        let size = len.max(2).next_power_of_two();
+        let bits = size.trailing_zeros();
+        let twiddles = (0..size / 2)
+            .map(|k| {
+                let (sin, cos) = (-2.0 * PI * k as f32 / size as f32).sin_cos();
+                ((cos * Q15_ONE).round() as i16, (sin * Q15_ONE).round() as i16)
+            })
+            .collect();
+        let bit_reverse = (0..size).map(|i| i.reverse_bits() >> (usize::BITS - bits)).collect();
+        FixedFft { size, twiddles, bit_reverse }
+    }
+
+    /// In-place Q15 forward transform scaled by 1/size; both buffers must be
+    /// the transform size long
+    pub fn process(&self, real: &mut [i16], imag: &mut [i16]) {
+        for i in 0..self.size {
+            let j = self.bit_reverse[i];
+            if j > i {
+                real.swap(i, j);
+                imag.swap(i, j);
+            }
+        }
+
+        // Q15 product and halved sum, rounded to nearest
+        let product = |x: i32, w: i16| x * w as i32;
+        let halve = |sum: i32| ((sum + 1) >> 1).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        let mut len = 2;
+        while len <= self.size {
+            let half = len / 2;
+            let stride = self.size / len;
+            for start in (0..self.size).step_by(len) {
+                for k in 0..half {
+                    let (w_re, w_im) = self.twiddles[k * stride];
+                    let (a, b) = (start + k, start + k + half);
+                    let (b_re, b_im) = (real[b] as i32, imag[b] as i32);
+                    let t_re = (product(b_re, w_re) - product(b_im, w_im) + (1 << 14)) >> 15;
+                    let t_im = (product(b_re, w_im) + product(b_im, w_re) + (1 << 14)) >> 15;
+                    let (a_re, a_im) = (real[a] as i32, imag[a] as i32);
+                    real[b] = halve(a_re - t_re);
+                    imag[b] = halve(a_im - t_im);
+                    real[a] = halve(a_re + t_re);
+                    imag[a] = halve(a_im + t_im);
+                }
+            }
+            len *= 2;
+        }
+    }
+
+    /// Fixed-point counterpart of `Fft::magnitudes_in_place`: `real` holds the
+    /// zero-padded signal (`size()` long) and is left with the same
+    /// unnormalised magnitudes in `real[..size / 2]`
+    pub fn magnitudes_in_place(&self, real: &mut [f32]) {
+        let peak = max_abs(&real[..self.size]);
+        if peak <= 0.0 {
+            real.fill(0.0);
+            return;
+        }
+        let scale = Q15_ONE / peak;
+        let mut fixed_real: Vec<i16> = real[..self.size].iter().map(|&sample| (sample * scale).round() as i16).collect();
+        let mut fixed_imag = vec![0i16; self.size];
+        self.process(&mut fixed_real, &mut fixed_imag);
+
+        // Undo the quantising scale and the 1/size of the halved stages
+        let unscale = self.size as f32 / scale;
+        for ((magnitude, &re), &im) in real.iter_mut().zip(&fixed_real).zip(&fixed_imag).take(self.size / 2) {
+            *magnitude = (re as f32).hypot(im as f32) * unscale;
+        }
     }
-    
-    magnitudes
 }
 
-/// Simple DFT for performance-critical sections
-pub fn compute_simple_fft(samples: &[f32]) -> Vec<f32> {
-    let n = samples.len();
-    let mut magnitudes = vec![0.0; n / 2];
-    
-    // Simple DFT for first half of frequency bins
-    for k in 1..(n/2) {
-        let mut real = 0.0;
-        let mut imag = 0.0;
-        let freq_scale = 2.0 * PI * k as f32 / n as f32;
-        
-        // Subsample for performance (every 4th sample)
-        for i in (0..n).step_by(4) {
-            let angle = freq_scale * i as f32;
-            real += samples[i] * angle.cos();
-            imag += samples[i] * angle.sin();
-        }
-        
-        magnitudes[k] = (real * real + imag * imag).sqrt() / (n as f32 / 4.0);
+// Buffers kept per pool; enough for every worker thread's windows in flight
+#[cfg(any(feature = "stereo", feature = "technical"))]
+const MAX_POOLED_BUFFERS: usize = 32;
+// Largest buffer (samples) kept: window-sized scratch is recycled, while
+// whole-channel buffers are freed rather than held for the analyzer's lifetime
+#[cfg(any(feature = "stereo", feature = "technical"))]
+const MAX_POOLED_LEN: usize = 1 << 16;
+
+/// Reusable f32 buffers owned by an analyzer, so per-window scratch space is
+/// recycled across windows and across calls instead of reallocated each time
+#[cfg(any(feature = "stereo", feature = "technical"))]
+#[derive(Default)]
+pub struct ScratchPool {
+    buffers: Mutex<Vec<Vec<f32>>>,
+}
+
+#[cfg(any(feature = "stereo", feature = "technical"))]
+impl ScratchPool {
+    /// Zeroed buffer of `len` samples, handed back to the pool when dropped
+    pub fn take(&self, len: usize) -> Scratch<'_> {
+        let mut buffer = self.buffers.lock().unwrap().pop().unwrap_or_default();
+        buffer.clear();
+        buffer.resize(len, 0.0);
+        Scratch { pool: self, buffer }
     }
-    
-    magnitudes
 }
 
-/// Apply Blackman-Harris window for optimal frequency resolution
-pub fn apply_blackman_harris_window(frame: &mut [f32]) {
-    let frame_len = frame.len();
-    for (i, sample) in frame.iter_mut().enumerate() {
-        let w = 0.35875 
-            - 0.48829 * (2.0 * PI * i as f32 / (frame_len - 1) as f32).cos()
-            + 0.14128 * (4.0 * PI * i as f32 / (frame_len - 1) as f32).cos()
-            - 0.01168 * (6.0 * PI * i as f32 / (frame_len - 1) as f32).cos();
-        
-        *sample *= w;
+/// Buffer borrowed from a `ScratchPool`
+#[cfg(any(feature = "stereo", feature = "technical"))]
+pub struct Scratch<'a> {
+    pool: &'a ScratchPool,
+    buffer: Vec<f32>,
+}
+
+#[cfg(any(feature = "stereo", feature = "technical"))]
+impl Deref for Scratch<'_> {
+    type Target = Vec<f32>;
+
+    fn deref(&self) -> &Vec<f32> {
+        &self.buffer
+    }
+}
+
+#[cfg(any(feature = "stereo", feature = "technical"))]
+impl DerefMut for Scratch<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<f32> {
+        &mut self.buffer
+    }
+}
+
+#[cfg(any(feature = "stereo", feature = "technical"))]
+impl Drop for Scratch<'_> {
+    fn drop(&mut self) {
+        let mut buffers = self.pool.buffers.lock().unwrap();
+        if buffers.len() < MAX_POOLED_BUFFERS && self.buffer.capacity() <= MAX_POOLED_LEN {
+            buffers.push(std::mem::take(&mut self.buffer));
+        }
+    }
+}
+
+/// Cache of FFT plans and analysis windows keyed by size, shared by every
+/// analyzer so repeated analyses don't re-derive twiddles or window tables
+#[cfg(any(feature = "technical", feature = "music"))]
+#[derive(Default)]
+pub struct FftPlanner {
+    plans: HashMap<usize, Arc<Fft>>,
+    #[cfg(feature = "technical")]
+    windows: HashMap<(WindowKey, usize), Arc<Vec<f32>>>,
+}
+
+#[cfg(any(feature = "technical", feature = "music"))]
+impl FftPlanner {
+    /// Process-wide planner
+    pub fn shared() -> &'static Mutex<FftPlanner> {
+        static PLANNER: OnceLock<Mutex<FftPlanner>> = OnceLock::new();
+        PLANNER.get_or_init(|| Mutex::new(FftPlanner::default()))
+    }
+
+    /// Plan for a transform of at least `len` points
+    pub fn plan(&mut self, len: usize) -> Arc<Fft> {
+        let size = len.max(2).next_power_of_two();
+        self.plans.entry(size).or_insert_with(|| Arc::new(Fft::new(size))).clone()
+    }
+
+    /// Coefficients of `window` at length `len`
+    #[cfg(feature = "technical")]
+    pub fn window(&mut self, window: Window, len: usize) -> Arc<Vec<f32>> {
+        self.windows
+            .entry((window.key(), len))
+            .or_insert_with(|| Arc::new(window.coefficients(len)))
+            .clone()
+    }
+}
+
+/// Cached FFT plan from the shared planner
+#[cfg(any(feature = "technical", feature = "music"))]
+pub fn plan_fft(len: usize) -> Arc<Fft> {
+    FftPlanner::shared().lock().unwrap().plan(len)
+}
+
+/// Cached window from the shared planner
+#[cfg(feature = "technical")]
+pub fn window(window: Window, len: usize) -> Arc<Vec<f32>> {
+    FftPlanner::shared().lock().unwrap().window(window, len)
+}
+
+/// Value `share` (0..1) of the way through the values in ascending order, by
+/// nearest rank (the upper of the two middle values for an even-length
+/// median); reorders `values`. None when empty.
+pub fn percentile(values: &mut [f32], share: f32) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    let rank = ((values.len() - 1) as f32 * share.clamp(0.0, 1.0)).round() as usize;
+    Some(*values.select_nth_unstable_by(rank, f32::total_cmp).1)
+}
+
+/// Median by nearest rank; reorders `values`
+pub fn median(values: &mut [f32]) -> Option<f32> {
+    percentile(values, 0.5)
+}
+
+/// Mean of the values left after dropping `share` (up to half) of them from
+/// each end; reorders `values`
+pub fn trimmed_mean(values: &mut [f32], share: f32) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable_by(f32::total_cmp);
+    let cut = ((values.len() as f32 * share.clamp(0.0, 0.5)) as usize).min((values.len() - 1) / 2);
+    let kept = &values[cut..values.len() - cut];
+    Some((kept.iter().map(|&value| value as f64).sum::<f64>() / kept.len() as f64) as f32)
+}
+
+/// Median absolute deviation from the median (unscaled; multiply by 1.4826
+/// to estimate the standard deviation of normal data)
+pub fn median_absolute_deviation(values: &[f32]) -> Option<f32> {
+    let mut scratch = values.to_vec();
+    let centre = median(&mut scratch)?;
+    scratch.iter_mut().for_each(|value| *value = (*value - centre).abs());
+    median(&mut scratch)
+}
+
+/// Zero-phase exponential smoothing: a one-pole average with a time constant
+/// of `time_constant` values, run forwards then backwards so the curve is not
+/// delayed. Non-finite values (silence at -Infinity) pass through and restart
+/// the average on either side.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = smooth_ema))]
+pub fn ema_smooth(values: &[f32], time_constant: f32) -> Vec<f32> {
+    let coefficient = if time_constant > 0.0 { (-1.0 / time_constant).exp() } else { 0.0 };
+    let pass = |value: &mut f32, state: &mut Option<f32>| {
+        if !value.is_finite() {
+            *state = None;
+            return;
+        }
+        let smoothed = state.map_or(*value, |previous| coefficient * previous + (1.0 - coefficient) * *value);
+        *state = Some(smoothed);
+        *value = smoothed;
+    };
+    let mut smoothed = values.to_vec();
+    let mut state = None;
+    smoothed.iter_mut().for_each(|value| pass(value, &mut state));
+    state = None;
+    smoothed.iter_mut().rev().for_each(|value| pass(value, &mut state));
+    smoothed
+}
+
+/// Moving median over the values within `radius` either side, the window
+/// narrowing towards the ends so it stays centred; removes outliers and keeps
+/// steps sharp
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = smooth_median))]
+pub fn median_smooth(values: &[f32], radius: usize) -> Vec<f32> {
+    let mut scratch = Vec::with_capacity(2 * radius + 1);
+    (0..values.len())
+        .map(|i| {
+            let radius = radius.min(i).min(values.len() - 1 - i);
+            scratch.clear();
+            scratch.extend_from_slice(&values[i - radius..=i + radius]);
+            median(&mut scratch).unwrap_or(values[i])
+        })
+        .collect()
+}
+
+/// Σ x[n]·x[n + k] for lags k in 0..=max_lag (zero past the signal), through
+/// the power spectrum in O(n log n)
+#[cfg(feature = "music")]
+pub fn autocorrelation(signal: &[f32], max_lag: usize) -> Vec<f32> {
+    let mut correlation = vec![0.0; max_lag + 1];
+    if signal.is_empty() {
+        return correlation;
+    }
+    // Zero-padded to twice the length so the circular correlation doesn't wrap
+    let fft = plan_fft(2 * signal.len());
+    let mut real = vec![0.0; fft.size()];
+    let mut imag = vec![0.0; fft.size()];
+    real[..signal.len()].copy_from_slice(signal);
+    fft.process(&mut real, &mut imag);
+    for (re, im) in real.iter_mut().zip(imag.iter_mut()) {
+        *re = *re * *re + *im * *im;
+        *im = 0.0;
     }
+    fft.inverse(&mut real, &mut imag);
+
+    let lags = correlation.len().min(signal.len());
+    correlation[..lags].copy_from_slice(&real[..lags]);
+    correlation
 }
 
-/// Apply Hann window
-pub fn apply_hann_window(frame: &mut [f32]) {
-    let frame_len = frame.len();
-    for (i, sample) in frame.iter_mut().enumerate() {
-        let w = 0.5 * (1.0 - (2.0 * PI * i as f32 / (frame_len - 1) as f32).cos());
-        *sample *= w;
+/// Σ a[n]·b[n + k] for lags k in -max_lag..=max_lag, at index k + max_lag
+/// (zero where the signals don't overlap): positive lags find `b` late
+/// against `a`. Through the spectra in O(n log n)
+#[cfg(feature = "technical")]
+pub fn cross_correlation(a: &[f32], b: &[f32], max_lag: usize) -> Vec<f32> {
+    let mut correlation = vec![0.0; 2 * max_lag + 1];
+    if a.is_empty() || b.is_empty() {
+        return correlation;
     }
+    // Zero-padded past both lengths so negative lags sit at the end instead
+    // of wrapping onto positive ones
+    let fft = plan_fft(a.len() + b.len());
+    let size = fft.size();
+    let spectrum = |signal: &[f32]| {
+        let mut real = vec![0.0; size];
+        let mut imag = vec![0.0; size];
+        real[..signal.len()].copy_from_slice(signal);
+        fft.process(&mut real, &mut imag);
+        (real, imag)
+    };
+    let (a_re, a_im) = spectrum(a);
+    let (b_re, b_im) = spectrum(b);
+    // conj(A)·B
+    let mut real: Vec<f32> = (0..size).map(|i| a_re[i] * b_re[i] + a_im[i] * b_im[i]).collect();
+    let mut imag: Vec<f32> = (0..size).map(|i| a_re[i] * b_im[i] - a_im[i] * b_re[i]).collect();
+    fft.inverse(&mut real, &mut imag);
+
+    for (lag, out) in (-(max_lag as i64)..).zip(correlation.iter_mut()) {
+        if lag < b.len() as i64 && -lag < a.len() as i64 {
+            *out = real[lag.rem_euclid(size as i64) as usize];
+        }
+    }
+    correlation
 }
 
-/// Normalize vector to sum to 1.0
-pub fn normalize_vector(vector: &mut [f32]) {
-    let sum: f32 = vector.iter().sum();
-    if sum > 0.0 {
-        for value in vector.iter_mut() {
-            *value /= sum;
+/// Real cepstrum (inverse transform of the log magnitude spectrum) of a
+/// frame, zero-padded to the next power of two; quefrencies 0..size/2 in
+/// samples. A period or echo delay of `d` samples shows as a peak at `d`.
+#[cfg(any(feature = "technical", feature = "music"))]
+pub fn real_cepstrum(frame: &[f32]) -> Vec<f32> {
+    let fft = plan_fft(frame.len());
+    let mut real = vec![0.0; fft.size()];
+    let mut imag = vec![0.0; fft.size()];
+    let n = frame.len().min(fft.size());
+    real[..n].copy_from_slice(&frame[..n]);
+    fft.process(&mut real, &mut imag);
+    for (re, im) in real.iter_mut().zip(imag.iter_mut()) {
+        // Floored so silent bins don't send the log to -Infinity
+        *re = (*re * *re + *im * *im).max(MIN_CEPSTRUM_POWER).ln() * 0.5;
+        *im = 0.0;
+    }
+    fft.inverse(&mut real, &mut imag);
+    real.truncate(fft.size() / 2);
+    real
+}
+
+/// Hilbert transform (the signal phase-shifted by 90°) through the spectrum,
+/// in overlapping blocks so long signals stay cheap
+#[cfg(feature = "technical")]
+pub fn hilbert(signal: &[f32]) -> Vec<f32> {
+    let fft = plan_fft(HILBERT_BLOCK);
+    let mut quadrature = vec![0.0; signal.len()];
+    let mut real = vec![0.0; fft.size()];
+    let mut imag = vec![0.0; fft.size()];
+    for start in (0..signal.len()).step_by(HILBERT_HOP) {
+        let first = start.saturating_sub(HILBERT_MARGIN);
+        let last = (start + HILBERT_HOP + HILBERT_MARGIN).min(signal.len());
+        let end = (start + HILBERT_HOP).min(signal.len());
+        hilbert_block(&fft, (&mut real, &mut imag), &signal[first..last], start - first, &mut quadrature[start..end]);
+    }
+    quadrature
+}
+
+// Quadrature of one hop of a signal into `out`: `block` holds the signal
+// from up to `HILBERT_MARGIN` samples before the hop (`lead` of them) to up
+// to `HILBERT_MARGIN` after it
+#[cfg(feature = "technical")]
+pub(crate) fn hilbert_block(fft: &Fft, (real, imag): (&mut [f32], &mut [f32]), block: &[f32], lead: usize, out: &mut [f32]) {
+    // Block from the margin before the hop, zero outside the signal
+    let size = fft.size();
+    real.fill(0.0);
+    imag.fill(0.0);
+    let offset = HILBERT_MARGIN - lead;
+    real[offset..offset + block.len()].copy_from_slice(block);
+
+    // Multiply by -j·sign(f): the analytic signal's imaginary part
+    fft.process(real, imag);
+    for bin in 0..size {
+        let (re, im) = (real[bin], imag[bin]);
+        let sign = match bin {
+            0 => 0.0,
+            _ if bin < size / 2 => 1.0,
+            _ if bin == size / 2 => 0.0,
+            _ => -1.0,
+        };
+        real[bin] = sign * im;
+        imag[bin] = -sign * re;
+    }
+    fft.inverse(real, imag);
+
+    out.copy_from_slice(&real[HILBERT_MARGIN..HILBERT_MARGIN + out.len()]);
+}
+
+/// Amplitude envelope: magnitude of the analytic signal, smooth through each
+/// cycle where rectification ripples at twice the frequency
+#[cfg(feature = "technical")]
+pub fn analytic_envelope(signal: &[f32]) -> Vec<f32> {
+    signal.iter().zip(hilbert(signal)).map(|(&x, h)| x.hypot(h)).collect()
+}
+
+/// Adaptive peak picking: local maxima within `radius` that rise `delta` above
+/// the mean of the values within `mean_radius` and above `floor`, at least
+/// `min_distance` apart (the larger of two closer peaks wins, ties the first)
+#[cfg(any(feature = "technical", feature = "music"))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PeakPicker {
+    pub radius: usize,
+    pub mean_radius: usize,
+    pub delta: f32,
+    pub floor: f32,
+    pub min_distance: usize,
+}
+
+#[cfg(any(feature = "technical", feature = "music"))]
+impl PeakPicker {
+    /// Indices of the peaks, ascending
+    pub fn pick(&self, values: &[f32]) -> Vec<usize> {
+        let window = |t: usize, radius: usize| &values[t.saturating_sub(radius)..(t + radius + 1).min(values.len())];
+        let mut peaks: Vec<usize> = Vec::new();
+        for (t, &value) in values.iter().enumerate() {
+            if value <= self.floor || window(t, self.radius).iter().any(|&v| v > value) {
+                continue;
+            }
+            let neighbourhood = window(t, self.mean_radius);
+            if value < neighbourhood.iter().sum::<f32>() / neighbourhood.len() as f32 + self.delta {
+                continue;
+            }
+            match peaks.last_mut() {
+                Some(last) if t - *last < self.min_distance => {
+                    if value > values[*last] {
+                        *last = t;
+                    }
+                }
+                _ => peaks.push(t),
+            }
         }
+        peaks
     }
 }
 
 /// Calculate RMS energy of a signal
+#[cfg(feature = "technical")]
 pub fn calculate_rms(samples: &[f32]) -> f32 {
     if samples.is_empty() { return 0.0; }
     
-    let sum_squares: f32 = samples.iter().map(|&x| x * x).sum();
-    (sum_squares / samples.len() as f32).sqrt()
+    (sum_squares_f64(samples) / samples.len() as f64).sqrt() as f32
 }
 
-/// Convert amplitude to dB
+/// Convert amplitude to dB (relative to full scale, silence at -Infinity)
+#[cfg(feature = "loudness")]
 pub fn amplitude_to_db(amplitude: f32) -> f32 {
-    if amplitude > 0.0 {
-        20.0 * amplitude.log10()
-    } else {
-        f32::NEG_INFINITY
+    DbScale::default().to_db(amplitude)
+}
+
+/// Convert dB (relative to full scale) to amplitude
+#[cfg(any(feature = "loudness", feature = "stereo", feature = "music"))]
+pub fn db_to_amplitude(db: f32) -> f32 {
+    DbScale::default().to_amplitude(db)
+}
+
+// Decibel scale for reported levels: dB relative to a reference amplitude,
+// never below a floor. The default reads dBFS with silence at -Infinity
+// (written as JSON_DB_FLOOR in JSON reports); a finite floor such as -200
+// keeps every level a number, and a calibrated reference reads dB SPL-style
+// levels.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DbScale {
+    reference: f32,
+    floor: f32,
+}
+
+impl Default for DbScale {
+    fn default() -> Self {
+        DbScale { reference: 1.0, floor: f32::NEG_INFINITY }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl DbScale {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(constructor))]
+    pub fn new() -> Self {
+        DbScale::default()
+    }
+
+    // Scale on which `amplitude` reads `level_db`, e.g. the recorded level of
+    // a 94dB SPL calibrator
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn calibrated(amplitude: f32, level_db: f32) -> Self {
+        let mut scale = DbScale::default();
+        scale.set_reference(amplitude / 10f32.powf(level_db / 20.0));
+        scale
+    }
+
+    // Amplitude that reads 0dB (1.0 is digital full scale)
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_reference(&mut self, amplitude: f32) {
+        if amplitude.is_finite() && amplitude > 0.0 {
+            self.reference = amplitude;
+        }
+    }
+
+    // Lowest level reported; quieter levels and silence read as the floor
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn set_floor(&mut self, db: f32) {
+        if !db.is_nan() {
+            self.floor = db;
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn reference(&self) -> f32 {
+        self.reference
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn floor(&self) -> f32 {
+        self.floor
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn to_db(&self, amplitude: f32) -> f32 {
+        if amplitude > 0.0 {
+            (20.0 * (amplitude / self.reference).log10()).max(self.floor)
+        } else {
+            self.floor
+        }
+    }
+
+    // Amplitude of a level; levels at or below a finite floor read as silence
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+    pub fn to_amplitude(&self, db: f32) -> f32 {
+        if db <= self.floor && self.floor.is_finite() {
+            0.0
+        } else {
+            self.reference * 10f32.powf(db / 20.0)
+        }
+    }
+}
+
+impl DbScale {
+    pub fn with_floor(mut self, db: f32) -> Self {
+        self.set_floor(db);
+        self
+    }
+
+    pub fn with_reference(mut self, amplitude: f32) -> Self {
+        self.set_reference(amplitude);
+        self
+    }
+
+    /// A level already in dB, raised to the floor
+    pub fn floored(&self, level_db: f32) -> f32 {
+        level_db.max(self.floor)
+    }
+}
+
+/// `serialize_with` target for level fields (dB): JSON reports write the
+/// -Infinity of silence on an unfloored scale as `JSON_DB_FLOOR`
+#[cfg(feature = "loudness")]
+pub fn serialize_level<S: serde::Serializer>(level: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    #[cfg(all(feature = "json", feature = "loudness", feature = "stereo", feature = "technical"))]
+    if *level == f32::NEG_INFINITY && crate::typed_array::writing_json() {
+        return serializer.serialize_f32(crate::json::JSON_DB_FLOOR);
+    }
+    serializer.serialize_f32(*level)
+}
+
+
+/// Share of samples at or below `threshold_db` (dBFS); empty input counts as silent
+#[cfg(any(feature = "stereo", feature = "technical", feature = "music"))]
+pub fn silent_share(samples: &[f32], threshold_db: f32) -> f32 {
+    if samples.is_empty() { return 1.0; }
+
+    silent_count(samples, threshold_db) as f32 / samples.len() as f32
+}
+
+// Number of samples at or below `threshold_db` (dBFS)
+#[cfg(any(feature = "stereo", feature = "technical", feature = "music"))]
+pub(crate) fn silent_count(samples: &[f32], threshold_db: f32) -> usize {
+    let threshold = db_to_amplitude(threshold_db);
+    samples.iter().filter(|s| s.abs() <= threshold).count()
+}
+
+/// Factor mapping signed integer PCM of the given bit depth to -1..1
+#[cfg(any(feature = "loudness", feature = "wav"))]
+pub fn int_scale(bits_per_sample: u32) -> f64 {
+    1.0 / (1u64 << (bits_per_sample.clamp(1, 32) - 1)) as f64
+}
+
+/// Integer PCM (right-aligned, sign-extended) scaled to -1..1
+#[cfg(feature = "loudness")]
+pub fn int_to_f32<S: Copy + Into<i32>>(pcm: &[S], bits_per_sample: u32) -> Vec<f32> {
+    let scale = int_scale(bits_per_sample) as f32;
+    pcm.iter().map(|&sample| sample.into() as f32 * scale).collect()
+}
+
+/// Integer PCM scaled to -1..1 in double precision, exact for 32-bit samples
+#[cfg(feature = "loudness")]
+pub fn int_to_f64(pcm: &[i32], bits_per_sample: u32) -> Vec<f64> {
+    let scale = int_scale(bits_per_sample);
+    pcm.iter().map(|&sample| sample as f64 * scale).collect()
+}
+
+/// Single-frequency DFT by the Goertzel recurrence: one multiply-add per
+/// sample, far cheaper than an FFT when only a few frequencies matter (mains
+/// hum, pilot and test tones). Any frequency works, not just bin centres.
+#[cfg(feature = "technical")]
+#[derive(Clone, Copy, Debug)]
+pub struct Goertzel {
+    // 2cos(w) for w = 2pi f / fs
+    coefficient: f64,
+}
+
+#[cfg(feature = "technical")]
+impl Goertzel {
+    pub fn new(frequency: f32, sample_rate: f32) -> Self {
+        let omega = 2.0 * std::f64::consts::PI * frequency as f64 / sample_rate as f64;
+        Goertzel { coefficient: 2.0 * omega.cos() }
+    }
+
+    /// Magnitude of the DFT of `samples` at the frequency
+    pub fn magnitude(&self, samples: &[f32]) -> f32 {
+        let (mut s1, mut s2) = (0.0f64, 0.0f64);
+        for &sample in samples {
+            let s0 = sample as f64 + self.coefficient * s1 - s2;
+            (s2, s1) = (s1, s0);
+        }
+        (s1 * s1 + s2 * s2 - self.coefficient * s1 * s2).max(0.0).sqrt() as f32
     }
+
+    /// Peak amplitude of a sine at the frequency (exact over whole cycles)
+    pub fn amplitude(&self, samples: &[f32]) -> f32 {
+        if samples.is_empty() {
+            return 0.0;
+        }
+        2.0 * self.magnitude(samples) / samples.len() as f32
+    }
+}
+
+/// Sample types a `Biquad` can run in
+#[cfg(any(feature = "loudness", feature = "music"))]
+pub trait BiquadSample: Copy + Default + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> {
+    fn from_f64(value: f64) -> Self;
+}
+
+#[cfg(any(feature = "loudness", feature = "music"))]
+impl BiquadSample for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+#[cfg(any(feature = "loudness", feature = "music"))]
+impl BiquadSample for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+/// Second-order IIR section (direct form I) with its own state, so one
+/// filter runs across consecutive blocks of a channel. Designs follow the RBJ
+/// audio EQ cookbook; frequencies in Hz, gains in dB.
+#[cfg(any(feature = "loudness", feature = "music"))]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Biquad<T: BiquadSample = f32> {
+    // Feed-forward b0..b2 and feedback a1, a2, normalised so a0 = 1
+    b: [T; 3],
+    a: [T; 2],
+    x: [T; 2],
+    y: [T; 2],
+}
+
+#[cfg(any(feature = "loudness", feature = "music"))]
+impl<T: BiquadSample> Biquad<T> {
+    /// Filter from raw coefficients (`a[0]` divides the others)
+    pub fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Biquad {
+            b: b.map(|b| T::from_f64(b / a[0])),
+            a: [T::from_f64(a[1] / a[0]), T::from_f64(a[2] / a[0])],
+            x: [T::default(); 2],
+            y: [T::default(); 2],
+        }
+    }
+
+    /// Filter from an analog prototype b(s) / a(s), given as the coefficients
+    /// of s², s and 1, by the bilinear transform; analog frequency w lands at
+    /// digital 2atan(w / 2fs), so corners should be prewarped to match
+    pub fn bilinear(b: [f64; 3], a: [f64; 3], sample_rate: f32) -> Self {
+        let k = 2.0 * sample_rate as f64;
+        let map = |[s2, s1, s0]: [f64; 3]| [s2 * k * k + s1 * k + s0, 2.0 * (s0 - s2 * k * k), s2 * k * k - s1 * k + s0];
+        Biquad::new(map(b), map(a))
+    }
+
+    /// First K-weighting stage: the BS.1770 head-effect high shelf (+4dB
+    /// above about 1.7kHz), designed for `sample_rate`
+    pub fn k_shelf(sample_rate: f32) -> Self {
+        let k = (std::f64::consts::PI * K_SHELF_FREQUENCY / sample_rate as f64).tan();
+        let high = 10f64.powf(K_SHELF_GAIN_DB / 20.0);
+        let band = high.powf(K_SHELF_BAND_EXPONENT);
+        let q = K_SHELF_Q;
+        Biquad::new([high + band * k / q + k * k, 2.0 * (k * k - high), high - band * k / q + k * k], [1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k])
+    }
+
+    /// Second K-weighting stage: the BS.1770 RLB high-pass (about 38Hz),
+    /// designed for `sample_rate`
+    pub fn rlb_high_pass(sample_rate: f32) -> Self {
+        let k = (std::f64::consts::PI * RLB_FREQUENCY / sample_rate as f64).tan();
+        let q = RLB_Q;
+        Biquad::new([1.0, -2.0, 1.0], [1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k])
+    }
+
+    /// High-pass at 10Hz that strips DC offset without touching the audio band
+    pub fn dc_blocker(sample_rate: f32) -> Self {
+        Biquad::high_pass(sample_rate, DC_BLOCKER_CUTOFF, std::f32::consts::FRAC_1_SQRT_2)
+    }
+
+    pub fn low_pass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos, alpha) = rbj_terms(sample_rate, frequency, q);
+        Biquad::new([(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0], [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    pub fn high_pass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos, alpha) = rbj_terms(sample_rate, frequency, q);
+        Biquad::new([(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0], [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    /// Band-pass with unity gain at `frequency`
+    pub fn band_pass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos, alpha) = rbj_terms(sample_rate, frequency, q);
+        Biquad::new([alpha, 0.0, -alpha], [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    pub fn notch(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos, alpha) = rbj_terms(sample_rate, frequency, q);
+        Biquad::new([1.0, -2.0 * cos, 1.0], [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    pub fn peaking(sample_rate: f32, frequency: f32, gain_db: f32, q: f32) -> Self {
+        let (cos, alpha) = rbj_terms(sample_rate, frequency, q);
+        let gain = 10f64.powf(gain_db as f64 / 40.0);
+        Biquad::new([1.0 + alpha * gain, -2.0 * cos, 1.0 - alpha * gain], [1.0 + alpha / gain, -2.0 * cos, 1.0 - alpha / gain])
+    }
+
+    pub fn low_shelf(sample_rate: f32, frequency: f32, gain_db: f32, q: f32) -> Self {
+        let (cos, alpha) = rbj_terms(sample_rate, frequency, q);
+        let gain = 10f64.powf(gain_db as f64 / 40.0);
+        let root = 2.0 * gain.sqrt() * alpha;
+        Biquad::new(
+            [gain * ((gain + 1.0) - (gain - 1.0) * cos + root), 2.0 * gain * ((gain - 1.0) - (gain + 1.0) * cos), gain * ((gain + 1.0) - (gain - 1.0) * cos - root)],
+            [(gain + 1.0) + (gain - 1.0) * cos + root, -2.0 * ((gain - 1.0) + (gain + 1.0) * cos), (gain + 1.0) + (gain - 1.0) * cos - root],
+        )
+    }
+
+    pub fn high_shelf(sample_rate: f32, frequency: f32, gain_db: f32, q: f32) -> Self {
+        let (cos, alpha) = rbj_terms(sample_rate, frequency, q);
+        let gain = 10f64.powf(gain_db as f64 / 40.0);
+        let root = 2.0 * gain.sqrt() * alpha;
+        Biquad::new(
+            [gain * ((gain + 1.0) + (gain - 1.0) * cos + root), -2.0 * gain * ((gain - 1.0) + (gain + 1.0) * cos), gain * ((gain + 1.0) + (gain - 1.0) * cos - root)],
+            [(gain + 1.0) - (gain - 1.0) * cos + root, 2.0 * ((gain - 1.0) - (gain + 1.0) * cos), (gain + 1.0) - (gain - 1.0) * cos - root],
+        )
+    }
+
+    /// Filter one sample
+    #[inline]
+    pub fn process(&mut self, sample: T) -> T {
+        let [b0, b1, b2] = self.b;
+        let [a1, a2] = self.a;
+        let filtered = b0 * sample + b1 * self.x[0] + b2 * self.x[1] - a1 * self.y[0] - a2 * self.y[1];
+        self.x = [sample, self.x[0]];
+        self.y = [filtered, self.y[0]];
+        filtered
+    }
+
+    /// Filter a block in place, continuing from the previous block
+    pub fn process_block(&mut self, samples: &mut [T]) {
+        for sample in samples {
+            *sample = self.process(*sample);
+        }
+    }
+
+    /// Clear the state, as before the first sample
+    pub fn reset(&mut self) {
+        self.x = [T::default(); 2];
+        self.y = [T::default(); 2];
+    }
+}
+
+#[cfg(any(feature = "loudness", feature = "music"))]
+const DC_BLOCKER_CUTOFF: f32 = 10.0;
+
+// Analog prototypes of the BS.1770 K-weighting stages, from which the
+// published 48kHz coefficients follow by the bilinear transform
+#[cfg(any(feature = "loudness", feature = "music"))]
+const K_SHELF_FREQUENCY: f64 = 1681.974450955533;
+#[cfg(any(feature = "loudness", feature = "music"))]
+const K_SHELF_GAIN_DB: f64 = 3.999843853973347;
+#[cfg(any(feature = "loudness", feature = "music"))]
+const K_SHELF_Q: f64 = 0.7071752369554196;
+#[cfg(any(feature = "loudness", feature = "music"))]
+const K_SHELF_BAND_EXPONENT: f64 = 0.4996667741545416;
+#[cfg(any(feature = "loudness", feature = "music"))]
+const RLB_FREQUENCY: f64 = 38.13547087602444;
+#[cfg(any(feature = "loudness", feature = "music"))]
+const RLB_Q: f64 = 0.5003270373238773;
+
+/// BS.1770 K-weighting: the high shelf followed by the RLB high-pass, both
+/// designed for the rate they run at
+#[cfg(any(feature = "loudness", feature = "music"))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KWeighting<T: BiquadSample = f32> {
+    shelf: Biquad<T>,
+    high_pass: Biquad<T>,
+}
+
+#[cfg(any(feature = "loudness", feature = "music"))]
+impl<T: BiquadSample> KWeighting<T> {
+    pub fn new(sample_rate: f32) -> Self {
+        KWeighting { shelf: Biquad::k_shelf(sample_rate), high_pass: Biquad::rlb_high_pass(sample_rate) }
+    }
+
+    /// Filter one sample
+    #[inline]
+    pub fn process(&mut self, sample: T) -> T {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+// cos(w0) and alpha of the RBJ designs
+#[cfg(any(feature = "loudness", feature = "music"))]
+fn rbj_terms(sample_rate: f32, frequency: f32, q: f32) -> (f64, f64) {
+    let omega = 2.0 * std::f64::consts::PI * frequency as f64 / sample_rate as f64;
+    (omega.cos(), omega.sin() / (2.0 * q.max(1e-3) as f64))
+}
+
+/// Windowed-sinc filter presets for resampling and oversampling: more zero
+/// crossings narrow the transition band and a larger Kaiser beta deepens the
+/// stopband, at the cost of more taps per output sample
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SincQuality {
+    // 8 zero crossings, about 60dB stopband attenuation
+    Fast,
+    // 16 zero crossings, about 80dB
+    #[default]
+    Balanced,
+    // 32 zero crossings, about 100dB
+    #[cfg(feature = "technical")]
+    Accurate,
+}
+
+impl SincQuality {
+    /// Zero crossings of the sinc on each side of the kernel centre
+    pub fn zero_crossings(self) -> usize {
+        match self {
+            SincQuality::Fast => 8,
+            SincQuality::Balanced => 16,
+            #[cfg(feature = "technical")]
+            SincQuality::Accurate => 32,
+        }
+    }
+
+    /// Passband edge as a share of the lower Nyquist frequency
+    pub fn rolloff(self) -> f64 {
+        match self {
+            SincQuality::Fast => 0.90,
+            SincQuality::Balanced => 0.95,
+            #[cfg(feature = "technical")]
+            SincQuality::Accurate => 0.97,
+        }
+    }
+
+    /// Kaiser window shape
+    pub fn kaiser_beta(self) -> f64 {
+        match self {
+            SincQuality::Fast => 6.0,
+            SincQuality::Balanced => 8.0,
+            #[cfg(feature = "technical")]
+            SincQuality::Accurate => 10.0,
+        }
+    }
+}
+
+/// Kaiser-windowed sinc low-pass for band-limited interpolation between two
+/// rates; the cutoff drops to the output Nyquist when decimating and the
+/// kernel widens to match
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SincKernel {
+    // Passband edge as a share of the input Nyquist
+    cutoff: f64,
+    // Half-width in input samples
+    half_width: f64,
+    window: Window,
+}
+
+impl SincKernel {
+    // Kernel for `ratio` output samples per input sample
+    pub(crate) fn new(ratio: f64, quality: SincQuality) -> Self {
+        let cutoff = ratio.min(1.0) * quality.rolloff();
+        SincKernel { cutoff, half_width: quality.zero_crossings() as f64 / cutoff, window: Window::Kaiser(quality.kaiser_beta() as f32) }
+    }
+
+    pub(crate) fn half_width(&self) -> f64 {
+        self.half_width
+    }
+
+    // Weight at `x` input samples from the centre
+    pub(crate) fn at(&self, x: f64) -> f64 {
+        windowed_sinc(x, self.cutoff, self.half_width, self.window)
+    }
+}
+
+/// Most phases a `Polyphase` filter bank is built with; rate pairs whose
+/// reduced ratio needs more are resampled from an interpolated kernel table
+#[cfg(feature = "loudness")]
+pub const MAX_POLYPHASE_PHASES: usize = 1024;
+
+/// Polyphase windowed-sinc resampler by the rational factor `up / down`: the
+/// low-pass is evaluated once per phase, so every output sample is a single
+/// dot product with the input around it. Serves both true-peak oversampling
+/// (`down` of 1) and conversion between sample rates with a small common
+/// ratio (48kHz to 44.1kHz is 147/160). Output sample `n` sits at input
+/// position `n * down / up`; input outside the slice counts as silence.
+#[derive(Clone, Debug)]
+pub struct Polyphase {
+    up: usize,
+    down: usize,
+    // Taps per side: output phase p after input sample i weighs inputs
+    // i + 1 - reach ..= i + reach
+    reach: usize,
+    // `up` phases of 2 * reach taps each, phase-major
+    taps: Vec<f64>,
+    // The same in single precision, for `process`
+    taps_f32: Vec<f32>,
 }
 
+impl Polyphase {
+    /// Resampler by `up / down`, reduced to lowest terms
+    pub fn new(up: usize, down: usize, quality: SincQuality) -> Self {
+        let (up, down) = (up.max(1), down.max(1));
+        let divisor = gcd(up, down);
+        let (up, down) = (up / divisor, down / divisor);
+        let kernel = SincKernel::new(up as f64 / down as f64, quality);
+        let reach = kernel.half_width().ceil() as usize;
+        let taps = (0..up)
+            .flat_map(|phase| {
+                let centre = reach as f64 - 1.0 + phase as f64 / up as f64;
+                (0..2 * reach).map(move |tap| kernel.at(centre - tap as f64))
+            })
+            .collect::<Vec<f64>>();
+        let taps_f32 = taps.iter().map(|&tap| tap as f32).collect();
+        Polyphase { up, down, reach, taps, taps_f32 }
+    }
+
+    /// Oversampler by an integer factor
+    #[cfg(any(feature = "technical", feature = "loudness"))]
+    pub fn oversampler(factor: usize, quality: SincQuality) -> Self {
+        Polyphase::new(factor, 1, quality)
+    }
+
+    /// Resampler between two whole-number rates, when their ratio needs at
+    /// most MAX_POLYPHASE_PHASES phases
+    #[cfg(feature = "loudness")]
+    pub fn between(from_rate: f32, to_rate: f32, quality: SincQuality) -> Option<Self> {
+        let whole = |rate: f32| (rate >= 1.0 && rate.fract() == 0.0).then_some(rate as usize);
+        let (from, to) = (whole(from_rate)?, whole(to_rate)?);
+        (to / gcd(from, to) <= MAX_POLYPHASE_PHASES).then(|| Polyphase::new(to, from, quality))
+    }
+
+    #[cfg(any(feature = "technical", feature = "loudness"))]
+    pub fn up(&self) -> usize {
+        self.up
+    }
+
+    #[cfg(feature = "wav")]
+    pub fn down(&self) -> usize {
+        self.down
+    }
+
+    /// Input samples weighed per output sample
+    #[cfg(any(feature = "technical", feature = "loudness", feature = "wav"))]
+    pub fn taps_per_phase(&self) -> usize {
+        2 * self.reach
+    }
+
+    /// Output length for `frames` input samples
+    pub fn output_len(&self, frames: usize) -> usize {
+        (frames * self.up + self.down / 2) / self.down
+    }
+
+    /// Output `phase` of `window`, the `taps_per_phase` input samples around
+    /// it: the point `phase / up` past the sample at the window's middle, for
+    /// running the filter over input that arrives a sample at a time
+    #[cfg(feature = "loudness")]
+    pub fn phase(&self, phase: usize, window: &[f32]) -> f32 {
+        let width = 2 * self.reach;
+        dot(&self.taps_f32[phase * width..(phase + 1) * width], window)
+    }
+
+    /// Resample one channel
+    pub fn process(&self, input: &[f32]) -> Vec<f32> {
+        let width = 2 * self.reach;
+        (0..self.output_len(input.len()))
+            .map(|n| {
+                let (first, _) = self.taps(n);
+                let phase = n * self.down % self.up;
+                let taps = &self.taps_f32[phase * width..(phase + 1) * width];
+                match usize::try_from(first).ok().and_then(|start| input.get(start..start + width)) {
+                    Some(window) => dot(taps, window),
+                    // Near the edges, where part of the kernel falls outside the input
+                    None => taps.iter().enumerate()
+                        .filter_map(|(tap, &weight)| usize::try_from(first + tap as isize).ok().and_then(|i| input.get(i)).map(|&sample| weight * sample))
+                        .sum(),
+                }
+            })
+            .collect()
+    }
+
+    // First input sample output `n` weighs (negative before the input start)
+    // and the taps of its phase
+    pub(crate) fn taps(&self, n: usize) -> (isize, &[f64]) {
+        let position = n * self.down;
+        let (sample, phase) = (position / self.up, position % self.up);
+        let width = 2 * self.reach;
+        (sample as isize + 1 - self.reach as isize, &self.taps[phase * width..(phase + 1) * width])
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Average interleaved channels down to a single mono signal
+#[cfg(any(feature = "technical", feature = "music"))]
+pub fn mix_to_mono(pcm: &[f32], num_channels: usize) -> Vec<f32> {
+    let num_channels = num_channels.max(1);
+    pcm.chunks_exact(num_channels)
+        .map(|frame| frame.iter().sum::<f32>() / num_channels as f32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(any(feature = "technical", feature = "music"))]
+    fn fft_matches_naive_dft() {
+        let samples: Vec<f32> = (0..64).map(|i| (i as f32 * 0.7).sin() + 0.3 * (i as f32 * 2.1).cos()).collect();
+        let magnitudes = Fft::new(samples.len()).magnitudes(&samples);
+
+        for (k, &magnitude) in magnitudes.iter().enumerate() {
+            let (mut real, mut imag) = (0.0f32, 0.0f32);
+            for (i, &x) in samples.iter().enumerate() {
+                let angle = 2.0 * PI * (k * i) as f32 / samples.len() as f32;
+                real += x * angle.cos();
+                imag -= x * angle.sin();
+            }
+            assert!((magnitude - (real * real + imag * imag).sqrt()).abs() < 1e-3, "bin {}", k);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "technical")]
+    fn fixed_point_fft_tracks_the_float_transform() {
+        // Two tones 40dB apart under a Hann window, at a quiet -30dBFS overall
+        let samples: Vec<f32> = (0..2048).map(|i| {
+            let hann = 0.5 - 0.5 * (2.0 * PI * i as f32 / 2048.0).cos();
+            hann * 0.03 * ((2.0 * PI * 100.0 * i as f32 / 2048.0).sin() + 0.01 * (2.0 * PI * 700.0 * i as f32 / 2048.0).sin())
+        }).collect();
+        let expected = Fft::new(2048).magnitudes(&samples);
+        let fixed = FixedFft::new(2048);
+        let mut magnitudes = samples.clone();
+        fixed.magnitudes_in_place(&mut magnitudes);
+
+        // Accuracy delta: within 0.01dB at the strong tone, 0.1dB at the weak
+        // one (measured 0.002dB and 0.02dB), and the rounding floor 70dB or more
+        // under the strong tone (measured 78dB)
+        let db = |magnitude: f32| 20.0 * magnitude.log10();
+        assert!((db(magnitudes[100]) - db(expected[100])).abs() < 0.01);
+        assert!((db(magnitudes[700]) - db(expected[700])).abs() < 0.1);
+        let floor = magnitudes[1000..1024].iter().fold(0.0f32, |peak, &m| peak.max(m));
+        assert!(db(magnitudes[100]) - db(floor) > 70.0, "floor {} dB down", db(magnitudes[100]) - db(floor));
+
+        let mut silence = vec![0.0; 2048];
+        fixed.magnitudes_in_place(&mut silence);
+        assert!(silence.iter().all(|&m| m == 0.0));
+    }
+
+    #[test]
+    #[cfg(feature = "technical")]
+    fn goertzel_reads_one_frequency() {
+        let sample_rate = 8000.0;
+        let samples: Vec<f32> = (0..8000).map(|i| 0.25 * (2.0 * PI * 50.0 * i as f32 / sample_rate).sin() + 0.5 * (2.0 * PI * 1234.5 * i as f32 / sample_rate).sin()).collect();
+        assert!((Goertzel::new(50.0, sample_rate).amplitude(&samples) - 0.25).abs() < 1e-3);
+        assert!(Goertzel::new(60.0, sample_rate).amplitude(&samples) < 1e-3);
+
+        // Matches the FFT at a bin centre
+        let magnitudes = Fft::new(64).magnitudes(&samples[..64]);
+        assert!((Goertzel::new(5.0 * sample_rate / 64.0, sample_rate).magnitude(&samples[..64]) - magnitudes[5]).abs() < 1e-3);
+    }
+
+    #[test]
+    #[cfg(feature = "technical")]
+    fn biquads_shape_their_bands() {
+        let sample_rate = 48000.0;
+        // Steady-state gain (dB) of `filter` for a sine at `frequency`
+        let gain = |mut filter: Biquad, frequency: f32| {
+            let mut samples: Vec<f32> = (0..sample_rate as usize).map(|i| (2.0 * PI * frequency * i as f32 / sample_rate).sin()).collect();
+            filter.process_block(&mut samples);
+            amplitude_to_db(calculate_rms(&samples[24000..]) * std::f32::consts::SQRT_2)
+        };
+
+        assert!(gain(Biquad::low_pass(sample_rate, 1000.0, 0.707), 100.0).abs() < 0.1);
+        assert!(gain(Biquad::low_pass(sample_rate, 1000.0, 0.707), 10000.0) < -35.0);
+        assert!(gain(Biquad::high_pass(sample_rate, 1000.0, 0.707), 100.0) < -35.0);
+        assert!(gain(Biquad::notch(sample_rate, 1000.0, 2.0), 1000.0) < -40.0);
+        assert!((gain(Biquad::peaking(sample_rate, 1000.0, 6.0, 1.0), 1000.0) - 6.0).abs() < 0.1);
+        assert!((gain(Biquad::low_shelf(sample_rate, 200.0, -6.0, 0.707), 30.0) + 6.0).abs() < 0.2);
+        assert!((gain(Biquad::high_shelf(sample_rate, 4000.0, 4.0, 0.707), 18000.0) - 4.0).abs() < 0.2);
+        assert!(gain(Biquad::band_pass(sample_rate, 1000.0, 2.0), 1000.0).abs() < 0.1);
+        assert!(gain(Biquad::band_pass(sample_rate, 1000.0, 2.0), 100.0) < -20.0);
+
+        // DC goes, the audio band stays
+        let mut blocker = Biquad::<f32>::dc_blocker(sample_rate);
+        let settled = (0..48000).map(|_| blocker.process(0.5)).last().unwrap();
+        assert!(settled.abs() < 1e-4);
+        assert!(gain(Biquad::dc_blocker(sample_rate), 100.0).abs() < 0.1);
+
+        // Blocks pick up where the previous one left off
+        let signal: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.3).sin()).collect();
+        let (mut whole, mut split) = (signal.clone(), signal);
+        Biquad::peaking(sample_rate, 2000.0, 6.0, 1.0).process_block(&mut whole);
+        let mut filter = Biquad::peaking(sample_rate, 2000.0, 6.0, 1.0);
+        let (first, second) = split.split_at_mut(337);
+        filter.process_block(first);
+        filter.process_block(second);
+        assert_eq!(whole, split);
+    }
 
- 
\ No newline at end of file
+    #[test]
+    #[cfg(feature = "technical")]
+    fn k_weighting_matches_bs1770_at_any_rate() {
+        // The published 48kHz coefficients of both stages
+        let shelf = Biquad::<f64>::k_shelf(48000.0);
+        assert!(shelf.b.iter().zip([1.53512485958697, -2.69169618940638, 1.19839281085285]).all(|(a, b)| (a - b).abs() < 1e-9), "{:?}", shelf.b);
+        assert!(shelf.a.iter().zip([-1.69065929318241, 0.73248077421585]).all(|(a, b)| (a - b).abs() < 1e-9), "{:?}", shelf.a);
+        let high_pass = Biquad::<f64>::rlb_high_pass(48000.0);
+        assert!(high_pass.a.iter().zip([-1.99004745483398, 0.99007225036621]).all(|(a, b)| (a - b).abs() < 1e-9), "{:?}", high_pass.a);
+
+        // A 1kHz sine gains about +0.69dB (which BS.1770's -0.691 cancels)
+        // whatever the rate; the bilinear design drifts a few hundredths of a
+        // dB from the 48kHz reference (0.65dB at 44.1kHz)
+        for sample_rate in [44100.0, 48000.0, 96000.0] {
+            let mut filter = KWeighting::<f64>::new(sample_rate);
+            let samples: Vec<f32> = (0..sample_rate as usize).map(|i| filter.process((2.0 * PI * 1000.0 * i as f32 / sample_rate).sin() as f64) as f32).collect();
+            let gain = amplitude_to_db(calculate_rms(&samples[samples.len() / 2..]) * std::f32::consts::SQRT_2);
+            assert!((gain - 0.691).abs() < 0.05, "{} dB at {} Hz", gain, sample_rate);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "technical", feature = "wav"))]
+    fn polyphase_resamples_band_limited() {
+        let tone = |rate: f32, len: usize| -> Vec<f32> { (0..len).map(|i| 0.5 * (2.0 * PI * 1000.0 * i as f32 / rate).sin()).collect() };
+
+        // Oversampling interpolates the tone between its samples
+        let oversampler = Polyphase::oversampler(4, SincQuality::Balanced);
+        let output = oversampler.process(&tone(44100.0, 4410));
+        assert_eq!(output.len(), 4 * 4410);
+        let expected = tone(4.0 * 44100.0, 4 * 4410);
+        let error = output[400..17000].iter().zip(&expected[400..17000]).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+        assert!(error < 1e-3, "max error {}", error);
+
+        // 48kHz to 44.1kHz reduces to 147/160
+        let converter = Polyphase::between(48000.0, 44100.0, SincQuality::Fast).unwrap();
+        assert_eq!((converter.up(), converter.down()), (147, 160));
+        let output = converter.process(&tone(48000.0, 4800));
+        assert_eq!(output.len(), 4410);
+        let expected = tone(44100.0, 4410);
+        let error = output[200..4200].iter().zip(&expected[200..4200]).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+        assert!(error < 1e-2, "max error {}", error);
+
+        // Finer presets weigh more input
+        assert!(Polyphase::oversampler(4, SincQuality::Fast).taps_per_phase() < Polyphase::oversampler(4, SincQuality::Accurate).taps_per_phase());
+        assert!(Polyphase::between(44100.5, 48000.0, SincQuality::Balanced).is_none());
+        assert!(Polyphase::between(44100.0, 48001.0, SincQuality::Balanced).is_none());
+    }
+
+    #[test]
+    fn robust_statistics_resist_outliers() {
+        let mut values = vec![3.0, 1.0, 100.0, 2.0, 4.0, 5.0, -50.0];
+        assert_eq!(median(&mut values), Some(3.0));
+        assert_eq!(percentile(&mut values, 0.0), Some(-50.0));
+        assert_eq!(percentile(&mut values, 1.0), Some(100.0));
+        assert_eq!(percentile(&mut values, 0.9), Some(5.0));
+        assert_eq!(trimmed_mean(&mut values, 0.2), Some(3.0));
+        assert_eq!(median_absolute_deviation(&values), Some(2.0));
+
+        // Even lengths take the upper middle value; empty input has no statistics
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), Some(3.0));
+        assert_eq!(trimmed_mean(&mut [1.0, 2.0], 0.5), Some(1.5));
+        assert_eq!(median(&mut []), None);
+        assert_eq!(median_absolute_deviation(&[]), None);
+    }
+
+    #[test]
+    #[cfg(any(feature = "technical", feature = "music"))]
+    fn peak_picker_adapts_and_spaces_peaks() {
+        let picker = PeakPicker { radius: 1, mean_radius: 3, delta: 0.5, floor: 0.0, min_distance: 3 };
+        // A bump on a raised plateau does not clear its local mean
+        assert_eq!(picker.pick(&[0.0, 4.0, 0.0, 3.0, 3.0, 3.0, 3.2, 3.0, 3.0, 3.0, 3.0]), vec![1]);
+        // Peaks closer than min_distance: the larger wins, ties keep the first
+        assert_eq!(picker.pick(&[0.0, 3.0, 0.0, 4.0, 0.0, 0.0, 0.0, 3.0, 3.0, 0.0, 0.0]), vec![3, 7]);
+        assert!(picker.pick(&[-1.0, -0.5, -1.0]).is_empty());
+    }
+
+    #[test]
+    fn smoothing_settles_noise_without_delay() {
+        // A step with an outlier: the median drops the outlier and keeps the edge
+        let step = [0.0, 0.0, 9.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0];
+        assert_eq!(median_smooth(&step, 1), vec![0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+
+        // The two-way EMA keeps a symmetric bump centred and restarts at -Infinity
+        let mut bump = vec![0.0; 41];
+        bump[20] = 1.0;
+        let smoothed = ema_smooth(&bump, 4.0);
+        let peak = smoothed.iter().enumerate().fold((0, 0.0), |best, (i, &v)| if v > best.1 { (i, v) } else { best });
+        assert_eq!(peak.0, 20);
+        assert!((smoothed[15] - smoothed[25]).abs() < 1e-3);
+        assert_eq!(ema_smooth(&[-20.0, f32::NEG_INFINITY, -10.0], 4.0), vec![-20.0, f32::NEG_INFINITY, -10.0]);
+        assert_eq!(ema_smooth(&step, 0.0), step.to_vec());
+    }
+
+    #[test]
+    #[cfg(all(feature = "technical", feature = "music"))]
+    fn correlations_match_direct_sums() {
+        let a: Vec<f32> = (0..300).map(|i| (i as f32 * 0.37).sin() + 0.3 * (i as f32 * 1.9).cos()).collect();
+        let b: Vec<f32> = (0..200).map(|i| (i as f32 * 0.21).cos()).collect();
+        let direct = |x: &[f32], y: &[f32], lag: i64| -> f32 {
+            (0..x.len() as i64).filter(|&n| (0..y.len() as i64).contains(&(n + lag))).map(|n| x[n as usize] * y[(n + lag) as usize]).sum()
+        };
+
+        let auto = autocorrelation(&a, 310);
+        assert_eq!(auto.len(), 311);
+        for lag in [0, 1, 17, 299, 300, 310] {
+            assert!((auto[lag] - direct(&a, &a, lag as i64)).abs() < 1e-3, "lag {}: {}", lag, auto[lag]);
+        }
+
+        let cross = cross_correlation(&a, &b, 320);
+        for lag in [-320, -299, -5, 0, 42, 199, 200] {
+            let value = cross[(lag + 320) as usize];
+            assert!((value - direct(&a, &b, lag)).abs() < 1e-3, "lag {}: {}", lag, value);
+        }
+        // A delayed copy peaks at its delay
+        let delayed: Vec<f32> = std::iter::repeat_n(0.0, 25).chain(a.iter().copied()).collect();
+        let cross = cross_correlation(&a, &delayed, 50);
+        let peak = (0..cross.len()).max_by(|&x, &y| cross[x].total_cmp(&cross[y])).unwrap();
+        assert_eq!(peak, 75);
+        assert_eq!(autocorrelation(&[], 3), vec![0.0; 4]);
+    }
+
+    #[test]
+    #[cfg(any(feature = "technical", feature = "music"))]
+    fn cepstrum_peaks_at_the_echo_delay() {
+        let mut state = 12345u32;
+        let noise: Vec<f32> = (0..4096).map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as f32 / (1 << 24) as f32 - 0.5
+        }).collect();
+        let echoed: Vec<f32> = (0..noise.len()).map(|i| noise[i] + if i >= 300 { 0.5 * noise[i - 300] } else { 0.0 }).collect();
+
+        let cepstrum = real_cepstrum(&echoed);
+        assert_eq!(cepstrum.len(), 2048);
+        let peak = (20..cepstrum.len()).max_by(|&x, &y| cepstrum[x].total_cmp(&cepstrum[y])).unwrap();
+        assert_eq!(peak, 300);
+        assert!(real_cepstrum(&[0.0; 64]).iter().all(|value| value.is_finite()));
+    }
+
+    #[test]
+    #[cfg(feature = "technical")]
+    fn analytic_envelope_is_smooth() {
+        // A 1kHz tone at 48kHz rising from 0.2 to 0.8 across block boundaries
+        let len = 30000;
+        let amplitude = |i: usize| 0.2 + 0.6 * i as f32 / len as f32;
+        let tone: Vec<f32> = (0..len).map(|i| amplitude(i) * (2.0 * PI * 1000.0 * i as f32 / 48000.0).sin()).collect();
+        let quadrature = hilbert(&tone);
+        let envelope = analytic_envelope(&tone);
+        assert_eq!(envelope.len(), len);
+        for i in (500..len - 500).step_by(37) {
+            let expected = -amplitude(i) * (2.0 * PI * 1000.0 * i as f32 / 48000.0).cos();
+            assert!((quadrature[i] - expected).abs() < 0.01, "at {}: {} vs {}", i, quadrature[i], expected);
+            assert!((envelope[i] - amplitude(i)).abs() < 0.01, "at {}: {}", i, envelope[i]);
+        }
+        assert!(analytic_envelope(&[]).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "loudness")]
+    fn db_scale_floors_and_references_levels() {
+        assert_eq!(amplitude_to_db(0.0), f32::NEG_INFINITY);
+        assert!((amplitude_to_db(0.5) + 6.0206).abs() < 1e-4);
+        assert!((db_to_amplitude(-6.0206) - 0.5).abs() < 1e-5);
+        assert_eq!(db_to_amplitude(f32::NEG_INFINITY), 0.0);
+
+        let floored = DbScale::default().with_floor(-120.0);
+        assert_eq!((floored.to_db(0.0), floored.to_db(1e-9)), (-120.0, -120.0));
+        assert_eq!(floored.to_amplitude(-120.0), 0.0);
+
+        // 0.1 recorded from a 94dB calibrator
+        let spl = DbScale::calibrated(0.1, 94.0);
+        assert!((spl.to_db(0.1) - 94.0).abs() < 1e-4);
+        assert!((spl.to_db(0.01) - 74.0).abs() < 1e-4);
+        assert!((spl.to_amplitude(74.0) - 0.01).abs() < 1e-6);
+    }
+
+    #[test]
+    #[cfg(any(feature = "technical", feature = "music"))]
+    fn planner_reuses_plans_and_windows() {
+        let mut planner = FftPlanner::default();
+        assert!(Arc::ptr_eq(&planner.plan(1000), &planner.plan(1024)));
+        assert_eq!(planner.plan(1000).size(), 1024);
+        #[cfg(feature = "technical")]
+        {
+            assert!(Arc::ptr_eq(&planner.window(Window::Hann, 512), &planner.window(Window::Hann, 512)));
+            assert!(!Arc::ptr_eq(&planner.window(Window::Kaiser(6.0), 512), &planner.window(Window::Kaiser(8.0), 512)));
+        }
+    }
+
+    #[test]
+    #[cfg(any(feature = "stereo", feature = "technical"))]
+    fn scratch_buffers_are_recycled_zeroed() {
+        let pool = ScratchPool::default();
+        let address = {
+            let mut buffer = pool.take(256);
+            buffer.fill(1.0);
+            buffer.as_ptr()
+        };
+
+        let buffer = pool.take(128);
+        assert_eq!(buffer.as_ptr(), address);
+        assert!(buffer.len() == 128 && buffer.iter().all(|&x| x == 0.0));
+        drop(buffer);
+
+        // Grown to channel length, the buffer is freed rather than pooled
+        drop(pool.take(MAX_POOLED_LEN + 1));
+        assert!(pool.buffers.lock().unwrap().is_empty());
+    }
+}
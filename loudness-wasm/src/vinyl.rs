@@ -0,0 +1,220 @@
+// Vinyl pre-master check: what a cutting engineer sends a master back for.
+// Subsonic energy wastes groove excursion, out-of-phase low end cuts deep
+// vertical modulation the stylus can jump out of, strong high frequencies and
+// sibilance distort on the inner grooves, and a master cut too hot forces a
+// lower level or shorter sides.
+//
+//     const report = check_vinyl(pcm, 44100, 2);
+//     if (!report.ready) report.issues.forEach(issue => mark(issue.times, issue.message));
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use serde::Serialize;
+#[cfg(target_arch = "wasm32")]
+use tsify_next::Tsify;
+use crate::config::AnalyzerConfig;
+use crate::error::{validate_pcm, AnalysisError};
+use crate::gating::{block_loudness, blockize};
+use crate::loudness::LoudnessAnalyzer;
+use crate::podcast::sibilance;
+use crate::qc::{energy, ready, relative_db, window_start, window_starts, windowed_issue as issue, Severity, WindowedIssue, ISSUE_WINDOW};
+use crate::utils::{amplitude_to_db, mix_to_mono, Biquad, KWeighting};
+
+// Subsonic band edge (Hz) and the level (dB, relative to the window) it may reach
+const SUBSONIC_FREQUENCY: f32 = 30.0;
+const SUBSONIC_LIMIT: f32 = -20.0;
+// Low end that must stay in phase: correlation under 0 below 150Hz, when the
+// band is louder than -40 dBFS
+const LOW_END_FREQUENCY: f32 = 150.0;
+const LOW_END_CORRELATION_LIMIT: f32 = 0.0;
+const LOW_END_LEVEL: f32 = -40.0;
+// High band edge (Hz), and the level relative to the window (dB) and the
+// absolute level (dBFS) above which it strains the cut
+const HIGH_FREQUENCY: f32 = 10000.0;
+const HIGH_LIMIT: f32 = -15.0;
+const HIGH_LEVEL: f32 = -35.0;
+// Integrated loudness (LUFS) and one-second loudness above which the cut
+// has to come down
+const LOUDNESS_LIMIT: f32 = -10.0;
+const WINDOW_LOUDNESS_LIMIT: f32 = -8.0;
+
+/// Vinyl-cut readiness: `ready` when no issue is an error
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(target_arch = "wasm32", derive(Tsify))]
+#[cfg_attr(target_arch = "wasm32", tsify(into_wasm_abi))]
+pub struct VinylReport {
+    pub ready: bool,
+    pub integrated: f32,
+    // Worst window's subsonic and high-band level relative to the window (dB)
+    pub subsonic: f32,
+    pub high_frequency: f32,
+    // Lowest low-end correlation of any window (1 for mono)
+    pub low_end_correlation: f32,
+    pub issues: Vec<WindowedIssue>,
+}
+
+// Per-window readings
+struct Window {
+    subsonic: f32,
+    high: f32,
+    high_level: f32,
+    low_correlation: Option<f32>,
+}
+
+/// Check interleaved PCM (mono or stereo) for a vinyl cut
+pub fn check_vinyl(pcm: &[f32], sample_rate: f32, num_channels: usize) -> Result<VinylReport, AnalysisError> {
+    let window = (ISSUE_WINDOW * sample_rate) as usize;
+    validate_pcm(pcm, num_channels, window.max(1))?;
+    let integrated = LoudnessAnalyzer::from_config(&AnalyzerConfig::new(sample_rate, num_channels)).analyze(pcm, None)?.integrated;
+    let mono = mix_to_mono(pcm, num_channels);
+    let windows = measure_windows(pcm, &mono, num_channels, sample_rate, window);
+    let loudness = window_loudness(pcm, num_channels, sample_rate, window);
+    let starts = |flagged: &dyn Fn(usize) -> bool| window_starts((0..windows.len()).map(flagged));
+
+    let mut issues = Vec::new();
+    let subsonic = starts(&|index| windows[index].subsonic > SUBSONIC_LIMIT);
+    if !subsonic.is_empty() {
+        issues.push(issue("subsonic", Severity::Warning, format!("Energy below {:.0} Hz within {:.0} dB of the programme; high-pass before the cut", SUBSONIC_FREQUENCY, -SUBSONIC_LIMIT), subsonic));
+    }
+    let out_of_phase = starts(&|index| windows[index].low_correlation.is_some_and(|correlation| correlation < LOW_END_CORRELATION_LIMIT));
+    if !out_of_phase.is_empty() {
+        issues.push(issue("low_end_phase", Severity::Error, format!("Out-of-phase low end below {:.0} Hz; sum the bass to mono", LOW_END_FREQUENCY), out_of_phase));
+    }
+    let bright = starts(&|index| windows[index].high > HIGH_LIMIT && windows[index].high_level > HIGH_LEVEL);
+    if !bright.is_empty() {
+        issues.push(issue("high_frequency", Severity::Warning, format!("Strong energy above {:.0} kHz will distort on inner grooves; tame the top end", HIGH_FREQUENCY / 1000.0), bright));
+    }
+    let sibilants = sibilance(&mono, sample_rate);
+    if !sibilants.is_empty() {
+        // Located to the windows the sibilants start in, like every other issue
+        let mut times: Vec<f32> = sibilants.iter().map(|&onset| window_start(onset)).collect();
+        times.dedup();
+        issues.push(issue("sibilance", Severity::Warning, format!("{} harsh sibilant(s); de-ess before the cut", sibilants.len()), times));
+    }
+    let loud = starts(&|index| loudness.get(index).is_some_and(|&lufs| lufs > WINDOW_LOUDNESS_LIMIT));
+    if integrated > LOUDNESS_LIMIT || !loud.is_empty() {
+        issues.push(issue("level", Severity::Warning, format!("Integrated loudness {:.1} LUFS is hot for vinyl (at most {:.0}); expect a lower cut level or shorter sides", integrated, LOUDNESS_LIMIT), loud));
+    }
+
+    let worst = |reading: fn(&Window) -> f32| windows.iter().map(reading).fold(f32::NEG_INFINITY, f32::max);
+    Ok(VinylReport {
+        ready: ready(&issues),
+        integrated,
+        subsonic: worst(|window| window.subsonic),
+        high_frequency: worst(|window| window.high),
+        low_end_correlation: windows.iter().filter_map(|window| window.low_correlation).fold(1.0, f32::min),
+        issues,
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = check_vinyl)]
+pub fn check_vinyl_js(pcm: &[f32], sample_rate: f32, num_channels: usize) -> Result<VinylReport, JsError> {
+    Ok(check_vinyl(pcm, sample_rate, num_channels)?)
+}
+
+// Band levels of every whole window (-Infinity when silent); the low-end
+// correlation needs two channels
+fn measure_windows(pcm: &[f32], mono: &[f32], num_channels: usize, sample_rate: f32, window: usize) -> Vec<Window> {
+    let q = std::f32::consts::FRAC_1_SQRT_2;
+    let filter = |signal: &[f32], mut stages: Vec<Biquad>| -> Vec<f32> {
+        signal.iter().map(|&sample| stages.iter_mut().fold(sample, |x, stage| stage.process(x))).collect()
+    };
+    let subsonic = filter(mono, vec![Biquad::low_pass(sample_rate, SUBSONIC_FREQUENCY, q); 2]);
+    let high = filter(mono, vec![Biquad::high_pass(sample_rate, HIGH_FREQUENCY.min(0.45 * sample_rate), q); 2]);
+    let low_bands: Option<(Vec<f32>, Vec<f32>)> = (num_channels >= 2).then(|| {
+        let channel = |ch: usize| pcm.iter().skip(ch).step_by(num_channels).copied().collect::<Vec<f32>>();
+        let low_pass = || vec![Biquad::low_pass(sample_rate, LOW_END_FREQUENCY, q); 2];
+        (filter(&channel(0), low_pass()), filter(&channel(1), low_pass()))
+    });
+
+    (0..mono.len() / window.max(1))
+        .map(|index| {
+            let span = index * window..(index + 1) * window;
+            let total = energy(&mono[span.clone()]);
+            let high_energy = energy(&high[span.clone()]);
+            let low_correlation = low_bands.as_ref().and_then(|(left, right)| {
+                let (left, right) = (&left[span.clone()], &right[span.clone()]);
+                let (ll, rr) = (energy(left), energy(right));
+                let lr: f64 = left.iter().zip(right).map(|(&l, &r)| l as f64 * r as f64).sum();
+                let level = amplitude_to_db((((ll + rr) / (2 * window) as f64).sqrt()) as f32);
+                (level > LOW_END_LEVEL && ll * rr > 0.0).then(|| (lr / (ll * rr).sqrt()) as f32)
+            });
+            Window {
+                subsonic: relative_db(energy(&subsonic[span.clone()]), total),
+                high: relative_db(high_energy, total),
+                high_level: amplitude_to_db((high_energy / window as f64).sqrt() as f32),
+                low_correlation,
+            }
+        })
+        .collect()
+}
+
+// BS.1770 loudness (LUFS) of every whole window
+fn window_loudness(pcm: &[f32], num_channels: usize, sample_rate: f32, window: usize) -> Vec<f32> {
+    let mut energy = vec![0.0f64; pcm.len() / num_channels];
+    for ch in 0..num_channels {
+        let mut k_weighting = KWeighting::<f64>::new(sample_rate);
+        for (frame, &sample) in pcm.iter().skip(ch).step_by(num_channels).enumerate() {
+            let weighted = k_weighting.process(sample as f64);
+            energy[frame] += weighted * weighted;
+        }
+    }
+    blockize(&energy, window, window).into_iter().map(block_loudness).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn flags_each_issue_where_it_happens() {
+        // 6 s of stereo at 44.1kHz: a -20 dBFS 1kHz tone throughout, with
+        // 15Hz rumble swelling through second 1, antiphase 60Hz bass through
+        // second 3 and 14kHz hiss through second 5
+        let sample_rate = 44100.0;
+        let pcm: Vec<f32> = (0..6 * 44100usize)
+            .flat_map(|i| {
+                let t = i as f32 / sample_rate;
+                let swell = |second: usize| if i / 44100 == second { 0.2 * (PI * t.fract()).sin().powi(2) } else { 0.0 };
+                let tone = 0.1 * (2.0 * PI * 1000.0 * t).sin();
+                let rumble = swell(1) * (2.0 * PI * 15.0 * t).sin();
+                let bass = swell(3) * (2.0 * PI * 60.0 * t).sin();
+                let hiss = swell(5) * (2.0 * PI * 14000.0 * t).sin();
+                [tone + rumble + bass + hiss, tone + rumble - bass + hiss]
+            })
+            .collect();
+
+        let report = check_vinyl(&pcm, sample_rate, 2).unwrap();
+        let times = |check: &str| report.issues.iter().find(|issue| issue.check == check).map(|issue| issue.times.clone());
+        assert_eq!(times("subsonic"), Some(vec![1.0]));
+        assert_eq!(times("low_end_phase"), Some(vec![3.0]));
+        assert_eq!(times("high_frequency"), Some(vec![5.0]));
+        assert_eq!(times("level"), None);
+        assert!(!report.ready && report.low_end_correlation < -0.9);
+
+        // The tone alone is ready to cut
+        let clean: Vec<f32> = pcm.chunks_exact(2).enumerate().flat_map(|(i, _)| [0.1 * (2.0 * PI * 1000.0 * i as f32 / sample_rate).sin(); 2]).collect();
+        let report = check_vinyl(&clean, sample_rate, 2).unwrap();
+        assert!(report.ready && report.issues.is_empty(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn sibilance_is_located_to_windows() {
+        // 6 s of mono: 7kHz bursts at 2.3 s, 2.6 s and 4.5 s
+        let sample_rate = 44100.0;
+        let pcm: Vec<f32> = (0..6 * 44100usize)
+            .map(|i| {
+                let t = i as f32 / sample_rate;
+                let burst = [2.3, 2.6, 4.5].iter().any(|&start| (start..start + 0.05).contains(&t));
+                if burst { 0.3 * (2.0 * PI * 7000.0 * t).sin() } else { 0.0 }
+            })
+            .collect();
+
+        let report = check_vinyl(&pcm, sample_rate, 1).unwrap();
+        let sibilance = report.issues.iter().find(|issue| issue.check == "sibilance").unwrap();
+        assert_eq!(sibilance.times, [2.0, 4.0]);
+        assert!(sibilance.message.starts_with("3 harsh"));
+    }
+}
@@ -0,0 +1,166 @@
+// WAV decoding inside the module: web apps hand over the file bytes instead of
+// going through `decodeAudioData`, which resamples to the audio context's rate
+// and hides the original bit depth. The CLI reads files through the same path.
+//
+//     const wav = decode_wav(new Uint8Array(await file.arrayBuffer()));
+//     const analyzer = new Analyzer(wav.sample_rate, wav.channels);
+//     const buffer = wav.into_buffer();
+//     const result = analyzer.analyze_buffer(buffer);
+//     buffer.free();
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use js_sys::Float32Array;
+use std::io::Cursor;
+#[cfg(target_arch = "wasm32")]
+use crate::buffer::PcmBuffer;
+use crate::error::AnalysisError;
+use crate::formats::not_pcm_wav;
+use crate::utils::int_scale;
+
+/// Decoded WAV file: interleaved samples in -1..1 with the file's own format
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+pub struct WavFile {
+    sample_rate: u32,
+    channels: usize,
+    bits_per_sample: u16,
+    is_float: bool,
+    samples: Vec<f32>,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl WavFile {
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn bits_per_sample(&self) -> u16 {
+        self.bits_per_sample
+    }
+
+    // Whether the file stores IEEE float rather than integer PCM
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn is_float(&self) -> bool {
+        self.is_float
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    // Seconds at the file's own sample rate
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    pub fn duration(&self) -> f32 {
+        self.frames() as f32 / self.sample_rate as f32
+    }
+
+    // Copy of the interleaved samples
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen(js_name = samples)]
+    pub fn samples_js(&self) -> Float32Array {
+        Float32Array::from(self.samples.as_slice())
+    }
+
+    // Hand the samples to the analyzers' buffer API without copying; the
+    // WavFile is consumed
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen]
+    pub fn into_buffer(self) -> PcmBuffer {
+        PcmBuffer::from_samples(self.samples)
+    }
+}
+
+impl WavFile {
+    pub(crate) fn from_samples(sample_rate: u32, channels: usize, bits_per_sample: u16, samples: Vec<f32>) -> Self {
+        WavFile { sample_rate, channels, bits_per_sample, is_float: false, samples }
+    }
+
+    /// Interleaved samples in -1..1
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn into_samples(self) -> Vec<f32> {
+        self.samples
+    }
+}
+
+/// Decode RIFF/WAVE bytes: integer PCM of 8 to 32 bits or 32-bit float, any
+/// channel count
+pub fn decode_wav(bytes: &[u8]) -> Result<WavFile, AnalysisError> {
+    let reader = hound::WavReader::new(Cursor::new(bytes))
+        .map_err(|error| not_pcm_wav(bytes).unwrap_or(invalid_wav(error)))?;
+    let spec = reader.spec();
+    if spec.channels == 0 || spec.sample_rate == 0 {
+        return Err(AnalysisError::InvalidWav { reason: "zero channels or sample rate" });
+    }
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<Vec<_>, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = int_scale(spec.bits_per_sample as u32) as f32;
+            reader.into_samples::<i32>()
+                .map(|sample| sample.map(|s| s as f32 * scale))
+                .collect::<Result<Vec<_>, _>>()
+        }
+    }
+    .map_err(invalid_wav)?;
+    log::debug!("Decoded {} samples of {}-bit WAV at {} Hz", samples.len(), spec.bits_per_sample, spec.sample_rate);
+
+    Ok(WavFile {
+        sample_rate: spec.sample_rate,
+        channels: spec.channels as usize,
+        bits_per_sample: spec.bits_per_sample,
+        is_float: spec.sample_format == hound::SampleFormat::Float,
+        samples,
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = decode_wav)]
+pub fn decode_wav_js(bytes: &[u8]) -> Result<WavFile, JsError> {
+    Ok(decode_wav(bytes)?)
+}
+
+fn invalid_wav(error: hound::Error) -> AnalysisError {
+    let reason = match error {
+        hound::Error::FormatError(reason) => reason,
+        hound::Error::IoError(_) | hound::Error::UnfinishedSample => "truncated data",
+        hound::Error::TooWide => "samples wider than 32 bits",
+        hound::Error::Unsupported | hound::Error::InvalidSampleFormat => "unsupported sample format",
+    };
+    AnalysisError::InvalidWav { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_format_and_scaled_samples() {
+        let spec = hound::WavSpec { channels: 2, sample_rate: 48000, bits_per_sample: 24, sample_format: hound::SampleFormat::Int };
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for sample in [0, 1 << 22, -(1 << 23), 1 << 21] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let wav = decode_wav(bytes.get_ref()).unwrap();
+        assert_eq!((wav.sample_rate(), wav.channels(), wav.bits_per_sample(), wav.is_float()), (48000, 2, 24, false));
+        assert_eq!(wav.frames(), 2);
+        assert_eq!(wav.samples(), &[0.0, 0.5, -1.0, 0.25]);
+
+        assert_eq!(decode_wav(&bytes.get_ref()[..50]).err(), Some(AnalysisError::InvalidWav { reason: "truncated data" }));
+        assert!(matches!(decode_wav(b"not a wav file"), Err(AnalysisError::InvalidWav { .. })));
+    }
+}
@@ -0,0 +1,65 @@
+/**
+ * Worker-pool analysis of long files: the PCM is split into hop-aligned
+ * shards, each shard is analyzed in its own Web Worker running the WASM
+ * module, and the partial statistics are merged in Rust into one result.
+ */
+
+import { logger } from './logger';
+
+export interface ShardedAnalysisOptions {
+  /** Number of workers; defaults to the available hardware concurrency */
+  workers?: number;
+}
+
+function runShard(pcm: Float32Array, sampleRate: number, numChannels: number, range: any): Promise<any> {
+  return new Promise((resolve, reject) => {
+    const worker = new Worker(new URL('../workers/shard.worker.ts', import.meta.url), { type: 'module' });
+    worker.onmessage = (e: MessageEvent) => {
+      worker.terminate();
+      if (e.data.type === 'result') {
+        resolve(e.data.data);
+      } else {
+        reject(new Error(e.data.data));
+      }
+    };
+    worker.onerror = (e: ErrorEvent) => {
+      worker.terminate();
+      reject(new Error(e.message));
+    };
+    worker.postMessage({ pcm, sampleRate, numChannels, range }, [pcm.buffer]);
+  });
+}
+
+/**
+ * Analyze interleaved PCM across a pool of workers and return the merged
+ * loudness, true peak, clipping and DC offset
+ */
+export async function analyzeSharded(
+  pcm: Float32Array,
+  sampleRate: number,
+  numChannels: number,
+  options: ShardedAnalysisOptions = {}
+) {
+  // @ts-ignore
+  const wasm: any = await import('../../loudness-wasm/pkg/loudness_wasm.js');
+  await wasm.default();
+
+  const workers = options.workers ?? navigator.hardwareConcurrency ?? 4;
+  const plan = wasm.plan_shards(pcm.length / numChannels, workers);
+  logger.debug(`Analyzing ${plan.shards.length} shards`);
+
+  // Each worker gets a copy of its shard (including lead-in and lookahead) so the
+  // original buffer stays usable by the caller
+  const shards = await Promise.all(
+    plan.shards.map((range: any) =>
+      runShard(pcm.slice(range.read_start * numChannels, range.read_end * numChannels), sampleRate, numChannels, range)
+    )
+  );
+
+  const analyzer = new wasm.ShardAnalyzer(sampleRate, numChannels);
+  try {
+    return analyzer.merge({ shards });
+  } finally {
+    analyzer.free();
+  }
+}
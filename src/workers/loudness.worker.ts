@@ -52,7 +52,8 @@ async function initWasm() {
         
         // Test the analyzer with a small sample with timeout protection
         workerLogger.debug('🧪 Testing analyzer with small sample...');
-        const testSample = new Float32Array([0.1, -0.1, 0.05, -0.05]);
+        // One 400ms stereo block: shorter input is rejected with a TooShort error
+        const testSample = new Float32Array(17640 * 2).fill(0.05);
         
         // Add timeout protection for the test
         let testResult;
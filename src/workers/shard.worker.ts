@@ -0,0 +1,37 @@
+/// <reference lib="webworker" />
+
+// Analyzes one shard of a long file; the statistics are merged in Rust by
+// `ShardAnalyzer.merge` on the main thread (see utils/shardedAnalysis.ts)
+
+let wasmModule: any = null;
+
+async function initWasm() {
+  if (!wasmModule) {
+    // @ts-ignore
+    wasmModule = await import('../../loudness-wasm/pkg/loudness_wasm.js');
+    await wasmModule.default();
+  }
+  return wasmModule;
+}
+
+self.onmessage = async (e: MessageEvent) => {
+  const { pcm, sampleRate, numChannels, range } = e.data as {
+    pcm: Float32Array;
+    sampleRate: number;
+    numChannels: number;
+    range: { read_start: number; start: number; end: number; read_end: number };
+  };
+
+  try {
+    const mod = await initWasm();
+    const analyzer = new mod.ShardAnalyzer(sampleRate, numChannels);
+    try {
+      self.postMessage({ type: 'result', data: analyzer.analyze_shard(pcm, range) });
+    } finally {
+      analyzer.free();
+    }
+  } catch (error: unknown) {
+    const errorMessage = error instanceof Error ? error.message : 'Unknown error occurred';
+    self.postMessage({ type: 'error', data: errorMessage });
+  }
+};